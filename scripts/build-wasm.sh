@@ -23,11 +23,14 @@ cp -a "${ASSETS_SOURCE}/." "${ASSETS_DEST}/"
 
 "${ROOT_DIR}/scripts/generate-wasm-indexes.sh"
 
-for dir in entity particle sound structure; do
+for dir in ambient backdrop crop cutscene decal entity interior item locale particle raid recipe shop sound status structure trigger upgrade; do
   src_dir="${ROOT_DIR}/src/${dir}"
   dest_dir="${ASSETS_DEST}/${dir}"
   mkdir -p "${dest_dir}"
   cp -a "${src_dir}/." "${dest_dir}/"
 done
+cp "${ROOT_DIR}/src/content.yaml" "${ASSETS_DEST}/content.yaml"
+cp "${ROOT_DIR}/src/preload.yaml" "${ASSETS_DEST}/preload.yaml"
+cp "${ROOT_DIR}/src/config.yaml" "${ASSETS_DEST}/config.yaml"
 
 printf 'Built wasm and copied assets to web output.\n'
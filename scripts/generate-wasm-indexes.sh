@@ -5,14 +5,18 @@ ROOT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
 
 generate_index() {
   local rel_dir="$1"
-  local pattern="$2"
+  shift
   local dir="${ROOT_DIR}/${rel_dir}"
   local out="${dir}/index.json"
 
   mkdir -p "${dir}"
 
   mapfile -t files < <(
-    find "${dir}" -maxdepth 1 -type f -name "${pattern}" ! -name "index.json" -printf "%f\n" | sort
+    local names=()
+    for pattern in "$@"; do
+      names+=(${names:+-o} -name "${pattern}")
+    done
+    find "${dir}" -maxdepth 1 -type f \( "${names[@]}" \) ! -name "index.json" -printf "%f\n" | sort
   )
 
   {
@@ -35,12 +39,78 @@ generate_index() {
   } > "${out}"
 }
 
+# Sound entries are flat `key: value` yaml, so they are inlined into one manifest that the web
+# build loads in a single request instead of fetching every yaml.
+generate_sound_manifest() {
+  local dir="${ROOT_DIR}/src/sound"
+  local out="${dir}/sounds.json"
+
+  mapfile -t files < <(find "${dir}" -maxdepth 1 -type f -name "*.yaml" -printf "%f\n" | sort)
+
+  {
+    printf '{\n'
+    printf '  "sounds": ['
+    if [[ ${#files[@]} -eq 0 ]]; then
+      printf ']\n'
+    else
+      printf '\n'
+      for i in "${!files[@]}"; do
+        local comma=","
+        if [[ "${i}" -eq $((${#files[@]} - 1)) ]]; then
+          comma=""
+        fi
+        awk -v comma="${comma}" '
+          /^[[:space:]]*(#|$)/ { next }
+          {
+            key = $0
+            sub(/:.*/, "", key)
+            value = $0
+            sub(/^[^:]*:[[:space:]]*/, "", value)
+            sub(/[[:space:]]+$/, "", value)
+            if (value !~ /^"/ && value !~ /^(true|false|-?[0-9.]+)$/) {
+              value = "\"" value "\""
+            }
+            fields[++count] = sprintf("\"%s\": %s", key, value)
+          }
+          END {
+            printf "    {"
+            for (j = 1; j <= count; j++) {
+              printf "%s%s", (j > 1 ? ", " : ""), fields[j]
+            }
+            printf "}%s\n", comma
+          }
+        ' "${dir}/${files[$i]}"
+      done
+      printf '  ]\n'
+    fi
+    printf '}\n'
+  } > "${out}"
+}
+
 generate_index "src/structure" "*.json"
+generate_index "src/ambient" "*.yaml"
+generate_index "src/backdrop" "*.yaml"
+generate_index "src/crop" "*.yaml"
+generate_index "src/cutscene" "*.yaml"
+generate_index "src/decal" "*.yaml"
 generate_index "src/entity/behaviour" "*.yaml"
 generate_index "src/entity/trait" "*.yaml"
 generate_index "src/entity/enemy" "*.yaml"
 generate_index "src/entity/friend" "*.yaml"
 generate_index "src/entity/misc" "*.yaml"
 generate_index "src/particle" "*.yaml"
+generate_index "src/raid" "*.yaml"
+generate_index "src/interior" "*.json" "*.tmj" "*.tmx" "*.ldtk"
+generate_index "src/item" "*.yaml"
+generate_index "src/locale" "*.yaml"
+generate_index "src/recipe" "*.yaml"
+generate_index "src/trigger" "*.yaml"
+generate_index "src/shop" "*.yaml"
+generate_index "src/sound" "*.yaml"
+generate_index "src/sound/terrain" "*.yaml"
+generate_index "src/sound/events" "*.yaml"
+generate_index "src/status" "*.yaml"
+generate_index "src/upgrade" "*.yaml"
+generate_sound_manifest
 
 printf 'WASM index manifests generated.\n'
@@ -0,0 +1,183 @@
+use macroquad::prelude::*;
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::helpers::{data_path, load_wasm_manifest_files};
+use crate::scene::SceneKind;
+use crate::sound::SoundSystem;
+
+/// Seconds a loop takes to fade fully in or out.
+const FADE_SECONDS: f32 = 1.5;
+
+#[derive(Debug)]
+pub enum AmbientLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for AmbientLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for AmbientLoadError {}
+
+impl From<std::io::Error> for AmbientLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for AmbientLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+/// An area that plays a looping sound while the player is in or near it.
+#[derive(Clone, Deserialize)]
+pub struct AmbientZoneDef {
+    /// `[x, y, w, h]` in tiles.
+    pub rect: [f32; 4],
+    /// Sound id, normally on the ambient channel and looped.
+    pub sound: String,
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    /// Tiles outside the rect over which the sound fades to silence.
+    #[serde(default = "default_falloff")]
+    pub falloff: f32,
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+fn default_falloff() -> f32 {
+    8.0
+}
+
+impl AmbientZoneDef {
+    /// Volume heard at `pos`: full inside the rect, fading linearly across the falloff.
+    fn volume_at(&self, pos: Vec2, tile_size: f32) -> f32 {
+        let [x, y, w, h] = self.rect.map(|v| v * tile_size);
+        let nearest = vec2(pos.x.clamp(x, x + w), pos.y.clamp(y, y + h));
+        let falloff = self.falloff * tile_size;
+        let fade = if falloff > 0.0 {
+            1.0 - pos.distance(nearest) / falloff
+        } else if pos == nearest {
+            1.0
+        } else {
+            0.0
+        };
+        self.volume * fade.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+struct AmbientFile {
+    /// Scene id, `expedition` or `farm`.
+    scene: String,
+    zones: Vec<AmbientZoneDef>,
+}
+
+/// Ambient loops per scene. Each sound plays at the loudest of its zones around the player and
+/// eases toward that level, so walking between overlapping zones crossfades them.
+pub struct AmbientZones {
+    zones: Vec<(SceneKind, AmbientZoneDef)>,
+    /// Current volume of every loop that is playing.
+    playing: HashMap<String, f32>,
+    targets: HashMap<String, f32>,
+}
+
+impl AmbientZones {
+    pub fn empty() -> Self {
+        Self {
+            zones: Vec::new(),
+            playing: HashMap::new(),
+            targets: HashMap::new(),
+        }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, AmbientLoadError> {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let names = load_wasm_manifest_files(&dir, &["expedition.yaml", "farm.yaml"]).await;
+            for name in names {
+                let path = format!("{}/{}", dir, name);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| AmbientLoadError::Io(std::io::Error::other(err.to_string())))?;
+                files.push(serde_yaml::from_str::<AmbientFile>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                files.push(serde_yaml::from_str::<AmbientFile>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+
+        let mut zones = Vec::new();
+        for file in files {
+            let Some(scene) = SceneKind::from_id(&file.scene) else {
+                eprintln!("ambient zones for unknown scene '{}'", file.scene);
+                continue;
+            };
+            zones.extend(file.zones.into_iter().map(|zone| (scene, zone)));
+        }
+        Ok(Self {
+            zones,
+            playing: HashMap::new(),
+            targets: HashMap::new(),
+        })
+    }
+
+    /// Fades loops toward what `listener` should hear in `scene`; None, inside an interior,
+    /// fades everything out.
+    pub fn update(&mut self, scene: Option<SceneKind>, listener: Vec2, tile_size: f32, dt: f32, sounds: &SoundSystem) {
+        self.targets.clear();
+        for (zone_scene, zone) in &self.zones {
+            if Some(*zone_scene) != scene {
+                continue;
+            }
+            let volume = zone.volume_at(listener, tile_size);
+            let target = self.targets.entry(zone.sound.clone()).or_insert(0.0);
+            *target = target.max(volume);
+        }
+
+        let step = dt / FADE_SECONDS;
+        for (sound, &target) in &self.targets {
+            if target > 0.0 && !self.playing.contains_key(sound) {
+                sounds.play_with_volume(sound, 0.0);
+                self.playing.insert(sound.clone(), 0.0);
+            }
+        }
+        self.playing.retain(|sound, volume| {
+            let target = self.targets.get(sound).copied().unwrap_or(0.0);
+            *volume += (target - *volume).clamp(-step, step);
+            if *volume <= 0.0 && target <= 0.0 {
+                sounds.stop(sound);
+                return false;
+            }
+            sounds.set_volume(sound, *volume);
+            true
+        });
+    }
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
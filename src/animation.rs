@@ -0,0 +1,255 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use crate::atlas::Sprite;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Reverse,
+    PingPong,
+}
+
+#[derive(Clone)]
+pub struct AnimationFrame {
+    /// Where the frame sits on the sheet, in pixels.
+    pub source: Rect,
+    /// Where the trimmed frame sits within the untrimmed one.
+    pub trim: Vec2,
+    /// The untrimmed frame's size.
+    pub size: Vec2,
+    /// Seconds it's shown.
+    pub duration: f32,
+}
+
+/// A named run of frames, like an Aseprite tag.
+#[derive(Clone)]
+pub struct Animation {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    pub direction: Direction,
+    /// Times it plays before holding its last frame; None loops forever.
+    pub repeat: Option<u32>,
+}
+
+/// Frames of a sprite sheet and the animations made of them. Entities play `walk` while
+/// moving and `idle` otherwise, falling back to the first animation, or every frame in turn
+/// without any.
+#[derive(Clone)]
+pub struct AnimationSet {
+    pub frames: Vec<AnimationFrame>,
+    pub animations: Vec<Animation>,
+    /// The point of the untrimmed frame that sits at the entity's position, if the sheet has
+    /// one.
+    pub origin: Option<Vec2>,
+}
+
+impl AnimationSet {
+    /// Reads the json Aseprite exports along with a sheet, frames as an array or a hash.
+    /// The origin is the pivot of the sheet's first slice.
+    pub fn from_aseprite(text: &str) -> Result<Self, serde_json::Error> {
+        let raw: AsepriteFile = serde_json::from_str(text)?;
+        let frames = match raw.frames {
+            AsepriteFrames::List(frames) => frames,
+            AsepriteFrames::Named(named) => {
+                let mut named: Vec<(String, AsepriteFrame)> = named.into_iter().collect();
+                // Hash keys are "name 12.aseprite"; order them by that number, not as text.
+                named.sort_by_key(|(name, _)| frame_number(name));
+                named.into_iter().map(|(_, frame)| frame).collect()
+            }
+        };
+        let frames: Vec<AnimationFrame> = frames
+            .into_iter()
+            .map(|frame| AnimationFrame {
+                source: frame.frame.rect(),
+                trim: vec2(frame.sprite_source_size.x, frame.sprite_source_size.y),
+                size: vec2(frame.source_size.w, frame.source_size.h),
+                duration: frame.duration.max(1.0) / 1000.0,
+            })
+            .collect();
+        let last = frames.len().saturating_sub(1);
+        let animations = raw
+            .meta
+            .frame_tags
+            .into_iter()
+            .filter(|tag| tag.from <= tag.to && tag.to <= last)
+            .map(|tag| Animation {
+                direction: match tag.direction.as_str() {
+                    "reverse" => Direction::Reverse,
+                    "pingpong" | "pingpong_reverse" => Direction::PingPong,
+                    _ => Direction::Forward,
+                },
+                repeat: tag.repeat.and_then(|repeat| repeat.parse().ok()).filter(|&repeat| repeat > 0),
+                name: tag.name,
+                from: tag.from,
+                to: tag.to,
+            })
+            .collect();
+        let origin = raw
+            .meta
+            .slices
+            .into_iter()
+            .flat_map(|slice| slice.keys)
+            .find_map(|key| key.pivot.map(|pivot| key.bounds.rect().point() + vec2(pivot.x, pivot.y)));
+        Ok(Self {
+            frames,
+            animations,
+            origin,
+        })
+    }
+
+    /// The animation to play, picked by name with the fallbacks described on the type.
+    pub fn pick(&self, moving: bool) -> Option<usize> {
+        let wanted = if moving { "walk" } else { "idle" };
+        self.animations
+            .iter()
+            .position(|animation| animation.name.eq_ignore_ascii_case(wanted))
+            .or((!self.animations.is_empty()).then_some(0))
+    }
+
+    /// The frame of `animation` shown `time` seconds in.
+    pub fn frame_at(&self, animation: Option<usize>, time: f32) -> usize {
+        let Some(animation) = animation.and_then(|index| self.animations.get(index)) else {
+            return self.frame_in(0, self.frames.len().saturating_sub(1), Direction::Forward, None, time);
+        };
+        self.frame_in(animation.from, animation.to, animation.direction, animation.repeat, time)
+    }
+
+    fn frame_in(&self, from: usize, to: usize, direction: Direction, repeat: Option<u32>, time: f32) -> usize {
+        if self.frames.is_empty() || to >= self.frames.len() {
+            return 0;
+        }
+        let len = to - from + 1;
+        // Ping-pong runs down and back up without showing either end twice in a row.
+        let steps = if direction == Direction::PingPong && len > 2 { len * 2 - 2 } else { len };
+        let at = |step: usize| match direction {
+            Direction::Forward => from + step,
+            Direction::Reverse => to - step,
+            Direction::PingPong if step < len => from + step,
+            Direction::PingPong => to - (step + 1 - len),
+        };
+        let cycle: f32 = (0..steps).map(|step| self.frames[at(step)].duration).sum();
+        let last = at(steps - 1);
+        if cycle <= 0.0 || repeat.is_some_and(|repeat| time >= cycle * repeat as f32) {
+            return last;
+        }
+        let mut left = time.max(0.0) % cycle;
+        for step in 0..steps {
+            left -= self.frames[at(step)].duration;
+            if left < 0.0 {
+                return at(step);
+            }
+        }
+        last
+    }
+
+    /// Frame `index` cut out of `sheet`, wherever the sheet was packed, with where to draw it
+    /// from the entity's position and how big. `dest_size` is the untrimmed frame's size on
+    /// screen, if it isn't drawn at its own.
+    pub fn cut(&self, sheet: &Sprite, index: usize, dest_size: Option<Vec2>) -> (Sprite, Vec2, Vec2) {
+        let Some(frame) = self.frames.get(index) else {
+            return (sheet.clone(), Vec2::ZERO, dest_size.unwrap_or(sheet.size()));
+        };
+        let scale = dest_size.map_or(Vec2::ONE, |size| size / frame.size.max(Vec2::ONE));
+        let sprite = Sprite {
+            texture: sheet.texture.clone(),
+            source: Rect::new(
+                sheet.source.x + frame.source.x,
+                sheet.source.y + frame.source.y,
+                frame.source.w,
+                frame.source.h,
+            ),
+        };
+        let offset = (frame.trim - self.origin.unwrap_or(Vec2::ZERO)) * scale;
+        (sprite, offset, frame.source.size() * scale)
+    }
+}
+
+/// The number just before a frame name's extension, e.g. 12 in "walk 12.aseprite".
+fn frame_number(name: &str) -> u32 {
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let digits = stem.len() - stem.trim_end_matches(|ch: char| ch.is_ascii_digit()).len();
+    stem[stem.len() - digits..].parse().unwrap_or(0)
+}
+
+#[derive(Deserialize)]
+struct AsepriteFile {
+    frames: AsepriteFrames,
+    meta: AsepriteMeta,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AsepriteFrames {
+    List(Vec<AsepriteFrame>),
+    Named(std::collections::HashMap<String, AsepriteFrame>),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AsepriteFrame {
+    frame: AsepriteRect,
+    sprite_source_size: AsepriteRect,
+    source_size: AsepriteSize,
+    duration: f32,
+}
+
+#[derive(Deserialize)]
+struct AsepriteRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+impl AsepriteRect {
+    fn rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.w, self.h)
+    }
+}
+
+#[derive(Deserialize)]
+struct AsepriteSize {
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AsepriteMeta {
+    #[serde(default)]
+    frame_tags: Vec<AsepriteTag>,
+    #[serde(default)]
+    slices: Vec<AsepriteSlice>,
+}
+
+#[derive(Deserialize)]
+struct AsepriteTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+    /// A number, written as a string.
+    #[serde(default)]
+    repeat: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AsepriteSlice {
+    #[serde(default)]
+    keys: Vec<AsepriteSliceKey>,
+}
+
+#[derive(Deserialize)]
+struct AsepriteSliceKey {
+    bounds: AsepriteRect,
+    #[serde(default)]
+    pivot: Option<AsepritePoint>,
+}
+
+#[derive(Deserialize)]
+struct AsepritePoint {
+    x: f32,
+    y: f32,
+}
@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+use crate::slot::SlotMeta;
+
+/// Layout of exported `.cbsave` archives: magic, format version, then a deflated postcard
+/// body holding the slot's metadata as JSON, so new metadata fields don't break old
+/// archives, and its files.
+pub const ARCHIVE_VERSION: u16 = 2;
+/// Archives from before the metadata was stored as JSON.
+const ARCHIVE_VERSION_POSTCARD_META: u16 = 1;
+pub const ARCHIVE_EXTENSION: &str = "cbsave";
+const ARCHIVE_MAGIC: &[u8; 4] = b"CBAR";
+/// Refuses archives that would inflate past this many bytes.
+const MAX_ARCHIVE_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(std::io::Error),
+    Binary(postcard::Error),
+    Meta(serde_json::Error),
+    Inflate(miniz_oxide::inflate::DecompressError),
+    Version(u16),
+    Corrupt(&'static str),
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Binary(err) => write!(f, "binary error: {err}"),
+            Self::Meta(err) => write!(f, "metadata error: {err}"),
+            Self::Inflate(err) => write!(f, "decompression error: {err}"),
+            Self::Version(version) => write!(f, "unsupported archive version {version}"),
+            Self::Corrupt(reason) => write!(f, "corrupt archive: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl From<std::io::Error> for ArchiveError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<postcard::Error> for ArchiveError {
+    fn from(err: postcard::Error) -> Self {
+        Self::Binary(err)
+    }
+}
+
+impl From<serde_json::Error> for ArchiveError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Meta(err)
+    }
+}
+
+impl From<miniz_oxide::inflate::DecompressError> for ArchiveError {
+    fn from(err: miniz_oxide::inflate::DecompressError) -> Self {
+        Self::Inflate(err)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchiveFile {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+/// A whole save slot packed into one file: its metadata, farm save, thumbnail, and the game
+/// settings it was played with.
+pub struct SaveArchive {
+    pub meta: SlotMeta,
+    pub files: Vec<ArchiveFile>,
+}
+
+#[derive(Deserialize)]
+struct ArchiveBody {
+    meta: String,
+    files: Vec<ArchiveFile>,
+}
+
+/// Encodes the same as [`ArchiveBody`] without copying the files.
+#[derive(Serialize)]
+struct ArchiveBodyRef<'a> {
+    meta: String,
+    files: &'a [ArchiveFile],
+}
+
+/// Slot metadata as version 1 archives stored it.
+#[derive(Deserialize)]
+struct LegacyArchive {
+    meta: LegacySlotMeta,
+    files: Vec<ArchiveFile>,
+}
+
+#[derive(Deserialize)]
+struct LegacySlotMeta {
+    id: String,
+    name: String,
+    playtime: f32,
+    day: u32,
+    player_hp: f32,
+    saved_at: f64,
+}
+
+impl SaveArchive {
+    pub fn file(&self, name: &str) -> Option<&[u8]> {
+        self.files.iter().find(|file| file.name == name).map(|file| file.bytes.as_slice())
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, ArchiveError> {
+        let body = postcard::to_allocvec(&ArchiveBodyRef {
+            meta: serde_json::to_string(&self.meta)?,
+            files: &self.files,
+        })?;
+        let mut bytes = Vec::with_capacity(ARCHIVE_MAGIC.len() + 2 + body.len() / 2);
+        bytes.extend_from_slice(ARCHIVE_MAGIC);
+        bytes.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&miniz_oxide::deflate::compress_to_vec(&body, 8));
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ArchiveError> {
+        let body = bytes
+            .strip_prefix(ARCHIVE_MAGIC)
+            .ok_or(ArchiveError::Corrupt("not a save archive"))?;
+        let (version, body) = body
+            .split_first_chunk::<2>()
+            .ok_or(ArchiveError::Corrupt("missing archive version"))?;
+        let version = u16::from_le_bytes(*version);
+        if version != ARCHIVE_VERSION && version != ARCHIVE_VERSION_POSTCARD_META {
+            return Err(ArchiveError::Version(version));
+        }
+        let body = miniz_oxide::inflate::decompress_to_vec_with_limit(body, MAX_ARCHIVE_SIZE)?;
+        if version == ARCHIVE_VERSION_POSTCARD_META {
+            let legacy: LegacyArchive = postcard::from_bytes(&body)?;
+            let meta = legacy.meta;
+            return Ok(Self {
+                meta: SlotMeta {
+                    id: meta.id,
+                    name: meta.name,
+                    playtime: meta.playtime,
+                    day: meta.day,
+                    player_hp: meta.player_hp,
+                    player_xp: 0.0,
+                    upgrades: Default::default(),
+                    equipment: Default::default(),
+                    saved_at: meta.saved_at,
+                    difficulty: Default::default(),
+                },
+                files: legacy.files,
+            });
+        }
+        let body: ArchiveBody = postcard::from_bytes(&body)?;
+        Ok(Self {
+            meta: serde_json::from_str(&body.meta)?,
+            files: body.files,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, bytes: &[u8]) -> ArchiveFile {
+        ArchiveFile {
+            name: name.to_string(),
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    fn framed(version: u16, body: &[u8]) -> Vec<u8> {
+        let mut bytes = ARCHIVE_MAGIC.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(&miniz_oxide::deflate::compress_to_vec(body, 8));
+        bytes
+    }
+
+    #[test]
+    fn round_trips() {
+        let meta = serde_json::from_str(r#"{"id":"slot-1","name":"Farm","playtime":12.5,"day":3,"player_xp":40}"#);
+        let archive = SaveArchive {
+            meta: meta.unwrap(),
+            files: vec![file("farm.bin", &[1, 2, 3]), file("settings.json", b"{}")],
+        };
+        let decoded = SaveArchive::decode(&archive.encode().unwrap()).unwrap();
+        assert_eq!((decoded.meta.id.as_str(), decoded.meta.name.as_str()), ("slot-1", "Farm"));
+        assert_eq!((decoded.meta.playtime, decoded.meta.day, decoded.meta.player_xp), (12.5, 3, 40.0));
+        assert_eq!(decoded.file("farm.bin"), Some(&[1, 2, 3][..]));
+        assert_eq!(decoded.file("settings.json"), Some(&b"{}"[..]));
+        assert!(decoded.file("missing").is_none());
+    }
+
+    #[test]
+    fn reads_version_1_archives() {
+        // Postcard lays structs out as tuples, so this is a version 1 body.
+        let meta = ("slot-1".to_string(), "Old farm".to_string(), 60.0f32, 7u32, 80.0f32, 1.0e9f64);
+        let body = postcard::to_allocvec(&(meta, vec![file("farm.bin", &[9])])).unwrap();
+        let decoded = SaveArchive::decode(&framed(ARCHIVE_VERSION_POSTCARD_META, &body)).unwrap();
+        assert_eq!(decoded.meta.name, "Old farm");
+        assert_eq!((decoded.meta.day, decoded.meta.player_hp, decoded.meta.player_xp), (7, 80.0, 0.0));
+        assert_eq!(decoded.file("farm.bin"), Some(&[9][..]));
+    }
+
+    #[test]
+    fn rejects_other_files_and_versions() {
+        assert!(matches!(SaveArchive::decode(b"PK\x03\x04"), Err(ArchiveError::Corrupt(_))));
+        assert!(matches!(SaveArchive::decode(b"CBAR"), Err(ArchiveError::Corrupt(_))));
+        let future = framed(ARCHIVE_VERSION + 1, &[]);
+        assert!(matches!(SaveArchive::decode(&future), Err(ArchiveError::Version(v)) if v == ARCHIVE_VERSION + 1));
+    }
+}
@@ -0,0 +1,189 @@
+use macroquad::prelude::*;
+use std::collections::HashMap;
+use crate::texture::{self, load_image_or_placeholder};
+
+/// Largest atlas page side; bigger images get a page of their own.
+const PAGE_SIZE: u32 = 2048;
+/// Pixels around each packed image, filled by repeating its edge so nearest sampling at
+/// fractional positions never picks up a neighbour.
+const PADDING: u32 = 1;
+
+/// A rectangle of a texture in pixels: all of a lone texture, or one image on an atlas page.
+#[derive(Clone)]
+pub struct Sprite {
+    pub texture: Texture2D,
+    pub source: Rect,
+}
+
+impl Sprite {
+    pub fn whole(texture: Texture2D) -> Self {
+        let source = Rect::new(0.0, 0.0, texture.width(), texture.height());
+        Self { texture, source }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.source.size()
+    }
+
+    /// `source` in texture coordinates, for shaders that work per sprite.
+    pub fn uv_rect(&self) -> Rect {
+        let size = self.texture.size().max(Vec2::ONE);
+        Rect::new(
+            self.source.x / size.x,
+            self.source.y / size.y,
+            self.source.w / size.x,
+            self.source.h / size.y,
+        )
+    }
+
+    /// `draw_texture_ex` of just this sprite, at its own size unless `params` sets one.
+    pub fn draw(&self, x: f32, y: f32, color: Color, params: DrawTextureParams) {
+        draw_texture_ex(
+            &self.texture,
+            x,
+            y,
+            color,
+            DrawTextureParams {
+                dest_size: params.dest_size.or(Some(self.size())),
+                source: Some(self.source),
+                ..params
+            },
+        );
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct AtlasHandle(usize);
+
+/// Images gathered while loading, packed onto as few textures as fit once everything is in,
+/// so sprites drawn one after another share a texture and batch.
+#[derive(Default)]
+pub struct AtlasBuilder {
+    images: Vec<Image>,
+    lookup: HashMap<String, usize>,
+}
+
+impl AtlasBuilder {
+    /// Queues the image at `path`; adding a path twice packs it once.
+    pub async fn add(&mut self, path: &str) -> AtlasHandle {
+        if let Some(&index) = self.lookup.get(path) {
+            return AtlasHandle(index);
+        }
+        let handle = self.add_image(load_image_or_placeholder(path).await);
+        self.lookup.insert(path.to_string(), handle.0);
+        handle
+    }
+
+    pub fn add_image(&mut self, image: Image) -> AtlasHandle {
+        self.images.push(image);
+        AtlasHandle(self.images.len() - 1)
+    }
+
+    /// Packs the queued images in shelves, tallest first, and uploads the pages.
+    pub fn build(self) -> Atlas {
+        let padded = |image: &Image| (image.width as u32 + PADDING * 2, image.height as u32 + PADDING * 2);
+        let area: u32 = self.images.iter().map(|image| padded(image).0 * padded(image).1).sum();
+        let widest = self.images.iter().map(|image| padded(image).0).max().unwrap_or(1);
+        // Roughly square pages, never narrower than the widest image that fits on one.
+        let width = ((area as f32).sqrt().ceil() as u32)
+            .next_power_of_two()
+            .max(widest.min(PAGE_SIZE))
+            .min(PAGE_SIZE);
+
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&index| std::cmp::Reverse(self.images[index].height));
+
+        // (page, x, y) of each image's padded corner, and each page's size.
+        let mut placements = vec![(0, 0, 0); self.images.len()];
+        let mut pages: Vec<(u32, u32)> = Vec::new();
+        // The page shelves are being filled on, if any.
+        let mut shelf_page: Option<usize> = None;
+        let (mut cursor_x, mut shelf_y, mut shelf_h) = (0, 0, 0);
+        for index in order {
+            let (w, h) = padded(&self.images[index]);
+            if w > PAGE_SIZE || h > PAGE_SIZE {
+                placements[index] = (pages.len(), 0, 0);
+                pages.push((w, h));
+                continue;
+            }
+            if cursor_x + w > width {
+                shelf_y += shelf_h;
+                cursor_x = 0;
+                shelf_h = 0;
+            }
+            let page = match shelf_page {
+                Some(page) if shelf_y + h <= PAGE_SIZE => page,
+                _ => {
+                    pages.push((width, 0));
+                    (cursor_x, shelf_y, shelf_h) = (0, 0, 0);
+                    *shelf_page.insert(pages.len() - 1)
+                }
+            };
+            placements[index] = (page, cursor_x, shelf_y);
+            pages[page].1 = pages[page].1.max(shelf_y + h);
+            cursor_x += w;
+            shelf_h = shelf_h.max(h);
+        }
+
+        let mut page_images: Vec<Image> = pages
+            .iter()
+            .map(|&(w, h)| Image::gen_image_color(w as u16, h as u16, BLANK))
+            .collect();
+        for (image, &(page, x, y)) in self.images.iter().zip(&placements) {
+            blit_padded(&mut page_images[page], image, x, y);
+        }
+        let textures: Vec<Texture2D> = page_images
+            .iter()
+            .map(|image| texture::upload(image.width, image.height, &image.bytes, FilterMode::Nearest))
+            .collect();
+
+        let sprites = self
+            .images
+            .iter()
+            .zip(placements)
+            .map(|(image, (page, x, y))| Sprite {
+                texture: textures[page].clone(),
+                source: Rect::new(
+                    (x + PADDING) as f32,
+                    (y + PADDING) as f32,
+                    image.width as f32,
+                    image.height as f32,
+                ),
+            })
+            .collect();
+        Atlas { sprites }
+    }
+}
+
+/// Copies `image` into `page` with its top-left padding corner at `x, y`, repeating the edge
+/// pixels out into the padding.
+fn blit_padded(page: &mut Image, image: &Image, x: u32, y: u32) {
+    let (w, h) = (image.width as i64, image.height as i64);
+    if w == 0 || h == 0 {
+        return;
+    }
+    let pad = PADDING as i64;
+    let page_w = page.width as usize;
+    for dy in -pad..h + pad {
+        let src_y = dy.clamp(0, h - 1) as usize;
+        for dx in -pad..w + pad {
+            let src_x = dx.clamp(0, w - 1) as usize;
+            let src = (src_y * w as usize + src_x) * 4;
+            let dst_x = (x as i64 + pad + dx) as usize;
+            let dst_y = (y as i64 + pad + dy) as usize;
+            let dst = (dst_y * page_w + dst_x) * 4;
+            page.bytes[dst..dst + 4].copy_from_slice(&image.bytes[src..src + 4]);
+        }
+    }
+}
+
+/// The pages a builder packed and where each of its images ended up.
+pub struct Atlas {
+    sprites: Vec<Sprite>,
+}
+
+impl Atlas {
+    pub fn sprite(&self, handle: AtlasHandle) -> Sprite {
+        self.sprites[handle.0].clone()
+    }
+}
@@ -0,0 +1,496 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use crate::atlas::Sprite;
+use crate::entity::{
+    DamageEvent, EntityCommand, EntityCommands, EntityContext, EntityDatabase, EntityDef, EntityInstance, EntityKind,
+    HitTarget, PendingHit, Target,
+};
+
+/// An attack an entity makes on its current target once it's in range.
+#[derive(Clone, Deserialize)]
+pub struct AttackDef {
+    #[serde(flatten)]
+    pub kind: AttackKind,
+    /// Distance between hitbox centers within which the attack starts.
+    pub range: f32,
+    /// Seconds from one attack going off until it can start again.
+    pub cooldown: f32,
+    pub damage: f32,
+    /// Warning given before the attack goes off; without one it goes off at once.
+    #[serde(default)]
+    pub telegraph: Option<TelegraphDef>,
+    /// Particle effect burst where the attack goes off.
+    #[serde(default)]
+    pub particles: Option<String>,
+    /// Sound id played where the attack goes off.
+    #[serde(default)]
+    pub sound: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AttackKind {
+    /// Throws the attacker at its target, hitting it on contact.
+    Lunge { speed: f32, duration: f32 },
+    /// Fires a projectile at where the target was.
+    Shot {
+        speed: f32,
+        /// Seconds before the projectile vanishes.
+        #[serde(default = "default_shot_lifetime")]
+        lifetime: f32,
+        #[serde(default = "default_shot_radius")]
+        radius: f32,
+        #[serde(default)]
+        sprite: Option<String>,
+        /// Filled in from the entity atlas.
+        #[serde(skip)]
+        texture: Option<Sprite>,
+    },
+    /// Hits everything of the target's kind within `radius` of the attacker.
+    Burst { radius: f32 },
+}
+
+/// A windup the attacker spends standing still, marking where the attack will land so it can
+/// be dodged.
+#[derive(Clone, Deserialize)]
+pub struct TelegraphDef {
+    /// Seconds of windup.
+    pub time: f32,
+    /// Ground marking shown during the windup; defaults to the area the attack covers.
+    #[serde(default)]
+    pub shape: Option<TelegraphShape>,
+    /// Sound id played as the windup starts.
+    #[serde(default)]
+    pub sound: Option<String>,
+    #[serde(default = "default_telegraph_color")]
+    pub color: [f32; 4],
+}
+
+fn default_telegraph_color() -> [f32; 4] {
+    [1.0, 0.25, 0.2, 0.7]
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TelegraphShape {
+    /// Centered on the attacker.
+    Circle { radius: f32 },
+    /// From the attacker toward its aim, `angle` degrees wide.
+    Cone { length: f32, angle: f32 },
+    /// From the attacker toward its aim.
+    Rect { length: f32, width: f32 },
+}
+
+fn default_shot_lifetime() -> f32 {
+    1.5
+}
+
+fn default_shot_radius() -> f32 {
+    2.0
+}
+
+impl AttackKind {
+    /// Image path of a shot's projectile, to pack into the entity atlas.
+    pub fn sprite_path(&self) -> Option<&str> {
+        match self {
+            Self::Shot { sprite, .. } => sprite.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn set_texture(&mut self, sprite: Sprite) {
+        if let Self::Shot { texture, .. } = self {
+            *texture = Some(sprite);
+        }
+    }
+}
+
+impl AttackDef {
+    fn windup(&self) -> f32 {
+        self.telegraph.as_ref().map_or(0.0, |telegraph| telegraph.time.max(0.0))
+    }
+
+    /// The telegraph's shape, or the area the attack covers for an attacker of `def`.
+    fn telegraph_shape(&self, def: &EntityDef) -> TelegraphShape {
+        if let Some(shape) = self.telegraph.as_ref().and_then(|telegraph| telegraph.shape) {
+            return shape;
+        }
+        let reach = def.hitbox.w.max(def.hitbox.h) * 0.5;
+        match self.kind {
+            AttackKind::Burst { radius } => TelegraphShape::Circle { radius },
+            AttackKind::Lunge { speed, duration } => TelegraphShape::Rect {
+                length: speed * duration + reach,
+                width: def.hitbox.h,
+            },
+            AttackKind::Shot {
+                speed,
+                lifetime,
+                radius,
+                ..
+            } => TelegraphShape::Rect {
+                length: (speed * lifetime).min(self.range + reach),
+                width: radius * 2.0,
+            },
+        }
+    }
+}
+
+/// Where one of an entity's attacks is at.
+#[derive(Clone, Default)]
+pub struct AttackState {
+    cooldown: f32,
+    /// Seconds of windup left, and the point the attack is aimed at.
+    windup: Option<(f32, Vec2)>,
+    lunge: Option<Lunge>,
+}
+
+#[derive(Clone, Copy)]
+struct Lunge {
+    left: f32,
+    dir: Vec2,
+    landed: bool,
+}
+
+/// What an attack may hit: whatever its attacker was after when it went off.
+#[derive(Clone, Copy, Debug)]
+pub enum HitFilter {
+    Player,
+    Kind(EntityKind),
+}
+
+/// A shot in flight. The entity sim moves it and lands its hit; `draw` is for the game.
+#[derive(Clone, Debug)]
+pub struct Projectile {
+    pub owner: u64,
+    /// Def and attack index of the shot, for its sprite.
+    pub def: usize,
+    pub attack: usize,
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub age: f32,
+    pub lifetime: f32,
+    pub radius: f32,
+    pub damage: f32,
+    pub hits: HitFilter,
+}
+
+impl Projectile {
+    pub fn draw(&self, db: &EntityDatabase) {
+        let texture = db.entities.get(self.def).and_then(|def| match &def.attacks.get(self.attack)?.kind {
+            AttackKind::Shot { texture, .. } => texture.as_ref(),
+            _ => None,
+        });
+        let Some(sprite) = texture else {
+            draw_circle(self.pos.x, self.pos.y, self.radius, Color::new(1.0, 0.85, 0.4, 1.0));
+            return;
+        };
+        let size = sprite.size() * 0.5;
+        sprite.draw(
+            self.pos.x - size.x * 0.5,
+            self.pos.y - size.y * 0.5,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(size),
+                rotation: self.vel.y.atan2(self.vel.x),
+                ..Default::default()
+            },
+        );
+    }
+
+    pub fn hits_rect(&self, rect: Rect) -> bool {
+        circle_hits_rect(self.pos, self.radius, rect)
+    }
+}
+
+/// A target an attack can land on, as it stands this step.
+#[derive(Clone, Copy)]
+struct LiveTarget {
+    target: Target,
+    hit: HitTarget,
+    hitbox: Rect,
+    iframes: f32,
+    filter: HitFilter,
+}
+
+fn live_target(target: Target, ctx: &EntityContext) -> Option<LiveTarget> {
+    match target {
+        Target::Position(_) => None,
+        Target::Player(player) => ctx.nearest_player(player.pos).map(|player| LiveTarget {
+            target: Target::Player(player),
+            hit: HitTarget::Player(player.index),
+            hitbox: player.hitbox,
+            iframes: player.iframes,
+            filter: HitFilter::Player,
+        }),
+        Target::Entity(entity) => ctx
+            .entities
+            .iter()
+            .find(|candidate| candidate.id == entity.id && candidate.alive)
+            .map(|candidate| LiveTarget {
+                target: Target::Entity(*candidate),
+                hit: HitTarget::Entity(candidate.id),
+                hitbox: candidate.hitbox,
+                iframes: candidate.iframes,
+                filter: HitFilter::Kind(candidate.kind),
+            }),
+    }
+}
+
+/// Counts down `entity`'s attacks and starts, winds up and lands them on its current target.
+/// Runs after steering, so a windup or lunge overrides the velocity it chose.
+pub fn run_attacks(entity: &mut EntityInstance, def: &EntityDef, dt: f32, ctx: &EntityContext, out: &mut EntityCommands) {
+    if def.attacks.is_empty() {
+        return;
+    }
+    let mut states = std::mem::take(&mut entity.attacks);
+    states.resize_with(def.attacks.len(), AttackState::default);
+    let target = entity.current_target.and_then(|target| live_target(target, ctx));
+    let hitbox = def.world_hitbox(entity.pos);
+    let center = hitbox.center();
+
+    for (index, attack) in def.attacks.iter().enumerate() {
+        let state = &mut states[index];
+        state.cooldown = (state.cooldown - dt).max(0.0);
+
+        if let Some(lunge) = state.lunge.as_mut() {
+            let speed = match attack.kind {
+                AttackKind::Lunge { speed, .. } => speed,
+                _ => 0.0,
+            };
+            entity.vel = lunge.dir * speed;
+            if !lunge.landed
+                && let Some(target) = target
+                && hitbox.overlaps(&target.hitbox)
+            {
+                lunge.landed = true;
+                push_hit(entity, attack, target, out);
+            }
+            lunge.left -= dt;
+            if lunge.left <= 0.0 {
+                state.lunge = None;
+            }
+        } else if let Some((left, aim)) = state.windup.as_mut() {
+            entity.vel = Vec2::ZERO;
+            *left -= dt;
+            if *left <= 0.0 {
+                let aim = *aim;
+                state.windup = None;
+                if let Some(target) = target {
+                    state.lunge = fire(entity, def, index, aim, target, ctx, out);
+                }
+            }
+        }
+    }
+
+    let busy = states.iter().any(|state| state.windup.is_some() || state.lunge.is_some());
+    if !busy
+        && crate::turret::loaded(entity, def)
+        && let Some(target) = target
+    {
+        for (index, attack) in def.attacks.iter().enumerate() {
+            let state = &mut states[index];
+            if state.cooldown > 0.0 || center.distance(target.hitbox.center()) > attack.range {
+                continue;
+            }
+            let windup = attack.windup();
+            state.cooldown = attack.cooldown.max(0.0) + windup;
+            crate::turret::spend_round(entity, def);
+            let aim = target.hitbox.center();
+            if windup > 0.0 {
+                state.windup = Some((windup, aim));
+                entity.vel = Vec2::ZERO;
+                if let Some(id) = attack.telegraph.as_ref().and_then(|telegraph| telegraph.sound.clone()) {
+                    out.queued.push(EntityCommand::PlaySound { id, pos: center });
+                }
+            } else {
+                state.lunge = fire(entity, def, index, aim, target, ctx, out);
+            }
+            break;
+        }
+    }
+    entity.attacks = states;
+}
+
+/// Sets attack `index` off toward `aim`, returning the lunge it starts, if it's one.
+fn fire(
+    entity: &EntityInstance,
+    def: &EntityDef,
+    index: usize,
+    aim: Vec2,
+    target: LiveTarget,
+    ctx: &EntityContext,
+    out: &mut EntityCommands,
+) -> Option<Lunge> {
+    let attack = &def.attacks[index];
+    let center = def.world_hitbox(entity.pos).center();
+    let dir = (aim - center).normalize_or_zero();
+    let mut lunge = None;
+    match &attack.kind {
+        AttackKind::Lunge { duration, .. } => {
+            lunge = Some(Lunge {
+                left: duration.max(0.0),
+                dir,
+                landed: false,
+            });
+        }
+        AttackKind::Shot {
+            speed,
+            lifetime,
+            radius,
+            ..
+        } => {
+            out.queued.push(EntityCommand::FireProjectile(Projectile {
+                owner: entity.uid,
+                def: entity.def,
+                attack: index,
+                pos: center,
+                vel: dir * *speed,
+                age: 0.0,
+                lifetime: *lifetime,
+                radius: *radius,
+                damage: attack.damage,
+                hits: target.filter,
+            }));
+        }
+        AttackKind::Burst { radius } => match target.filter {
+            HitFilter::Player => {
+                if circle_hits_rect(center, *radius, target.hitbox) {
+                    push_hit(entity, attack, target, out);
+                }
+            }
+            HitFilter::Kind(kind) => {
+                for candidate in &ctx.entities {
+                    if candidate.id == entity.uid
+                        || !candidate.alive
+                        || candidate.kind != kind
+                        || !circle_hits_rect(center, *radius, candidate.hitbox)
+                    {
+                        continue;
+                    }
+                    let victim = LiveTarget {
+                        target: Target::Entity(*candidate),
+                        hit: HitTarget::Entity(candidate.id),
+                        hitbox: candidate.hitbox,
+                        iframes: candidate.iframes,
+                        filter: target.filter,
+                    };
+                    push_hit(entity, attack, victim, out);
+                }
+            }
+        },
+    }
+    let effect_pos = match attack.kind {
+        AttackKind::Burst { .. } => center,
+        _ => center + dir * def.hitbox.w.max(def.hitbox.h) * 0.5,
+    };
+    if let Some(id) = &attack.particles {
+        out.queued.push(EntityCommand::EmitParticles {
+            id: id.clone(),
+            pos: effect_pos,
+        });
+    }
+    if let Some(id) = &attack.sound {
+        out.queued.push(EntityCommand::PlaySound {
+            id: id.clone(),
+            pos: effect_pos,
+        });
+    }
+    lunge
+}
+
+fn push_hit(entity: &EntityInstance, attack: &AttackDef, target: LiveTarget, out: &mut EntityCommands) {
+    let crit = crate::helpers::random_f32() < entity.stats.get("crit_chance", 0.0);
+    let amount = if crit {
+        attack.damage * entity.stats.get("crit_multiplier", 2.0)
+    } else {
+        attack.damage
+    };
+    out.hits.push(PendingHit {
+        event: DamageEvent {
+            amount,
+            target: target.target,
+            crit,
+            source: Some(entity.uid),
+        },
+        target: target.hit,
+        cooldown: 0.0,
+        iframes: target.iframes,
+    });
+}
+
+/// Shows where `entity`'s winding-up attacks will land: the full area outlined, filled from the
+/// attacker outward as the windup runs out. Drawn on the ground, with the world camera active.
+pub fn draw_telegraphs(entity: &EntityInstance, def: &EntityDef) {
+    let center = def.world_hitbox(entity.pos).center();
+    for (state, attack) in entity.attacks.iter().zip(&def.attacks) {
+        let (Some((left, aim)), Some(telegraph)) = (state.windup, attack.telegraph.as_ref()) else {
+            continue;
+        };
+        let progress = (1.0 - left / telegraph.time.max(0.001)).clamp(0.0, 1.0);
+        let [r, g, b, a] = telegraph.color;
+        let outline = Color::new(r, g, b, a);
+        let fill = Color::new(r, g, b, a * (0.2 + 0.4 * progress));
+        let dir = (aim - center).try_normalize().unwrap_or(Vec2::X);
+        match attack.telegraph_shape(def) {
+            TelegraphShape::Circle { radius } => {
+                draw_circle(center.x, center.y, radius * progress, fill);
+                draw_circle_lines(center.x, center.y, radius, 1.0, outline);
+            }
+            TelegraphShape::Cone { length, angle } => {
+                draw_cone(center, dir, length * progress, angle.to_radians(), Some(fill), None);
+                draw_cone(center, dir, length, angle.to_radians(), None, Some(outline));
+            }
+            TelegraphShape::Rect { length, width } => {
+                draw_strip(center, dir, length * progress, width, Some(fill), None);
+                draw_strip(center, dir, length, width, None, Some(outline));
+            }
+        }
+    }
+}
+
+/// Segments a telegraph cone's arc is drawn with.
+const CONE_SEGMENTS: usize = 12;
+
+fn draw_cone(origin: Vec2, dir: Vec2, length: f32, angle: f32, fill: Option<Color>, outline: Option<Color>) {
+    let start = dir.y.atan2(dir.x) - angle * 0.5;
+    let point = |step: usize| {
+        let theta = start + angle * step as f32 / CONE_SEGMENTS as f32;
+        origin + vec2(theta.cos(), theta.sin()) * length
+    };
+    for step in 0..CONE_SEGMENTS {
+        let (a, b) = (point(step), point(step + 1));
+        if let Some(color) = fill {
+            draw_triangle(origin, a, b, color);
+        }
+        if let Some(color) = outline {
+            draw_line(a.x, a.y, b.x, b.y, 1.0, color);
+        }
+    }
+    if let Some(color) = outline {
+        for edge in [point(0), point(CONE_SEGMENTS)] {
+            draw_line(origin.x, origin.y, edge.x, edge.y, 1.0, color);
+        }
+    }
+}
+
+/// A `width`-wide rectangle reaching `length` from `origin` along `dir`.
+fn draw_strip(origin: Vec2, dir: Vec2, length: f32, width: f32, fill: Option<Color>, outline: Option<Color>) {
+    let side = dir.perp() * width * 0.5;
+    let far = origin + dir * length;
+    let corners = [origin - side, far - side, far + side, origin + side];
+    if let Some(color) = fill {
+        draw_triangle(corners[0], corners[1], corners[2], color);
+        draw_triangle(corners[0], corners[2], corners[3], color);
+    }
+    if let Some(color) = outline {
+        for index in 0..4 {
+            let (a, b) = (corners[index], corners[(index + 1) % 4]);
+            draw_line(a.x, a.y, b.x, b.y, 1.0, color);
+        }
+    }
+}
+
+fn circle_hits_rect(center: Vec2, radius: f32, rect: Rect) -> bool {
+    let nearest = vec2(center.x.clamp(rect.x, rect.right()), center.y.clamp(rect.y, rect.bottom()));
+    center.distance_squared(nearest) <= radius * radius
+}
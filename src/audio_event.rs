@@ -0,0 +1,188 @@
+use macroquad::prelude::*;
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::helpers::{data_path, load_wasm_manifest_files};
+use crate::sound::{FootstepSets, SoundLoadError, SoundSystem};
+
+/// At most this many event sounds start in one frame; lower priorities lose out first.
+const MAX_SOUNDS_PER_FRAME: usize = 8;
+
+/// Something that happened this frame that may be worth a sound. Gameplay pushes these instead
+/// of picking and playing sounds itself; which sound plays, if any, is up to the event's data.
+#[derive(Clone, Copy, Debug)]
+pub enum AudioEvent {
+    /// `player` is true when the player was the one hit.
+    DamageTaken { pos: Vec2, player: bool },
+    /// `set` is the footstep set for the ground underfoot, from `FootstepSets::set_at`.
+    Footstep { pos: Vec2, set: Option<usize> },
+    Dash { pos: Vec2 },
+    UiClick,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioEventKind {
+    DamageTaken,
+    PlayerDamageTaken,
+    Footstep,
+    Dash,
+    UiClick,
+}
+
+impl AudioEvent {
+    fn kind(&self) -> AudioEventKind {
+        match self {
+            Self::DamageTaken { player: true, .. } => AudioEventKind::PlayerDamageTaken,
+            Self::DamageTaken { .. } => AudioEventKind::DamageTaken,
+            Self::Footstep { .. } => AudioEventKind::Footstep,
+            Self::Dash { .. } => AudioEventKind::Dash,
+            Self::UiClick => AudioEventKind::UiClick,
+        }
+    }
+
+    /// Where the sound comes from; None plays it unpanned, as for UI.
+    fn pos(&self) -> Option<Vec2> {
+        match *self {
+            Self::DamageTaken { pos, .. } | Self::Footstep { pos, .. } | Self::Dash { pos } => Some(pos),
+            Self::UiClick => None,
+        }
+    }
+}
+
+/// How one kind of event sounds.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AudioEventDef {
+    pub event: AudioEventKind,
+    /// Sound id to play. Footsteps play from their ground's set and only use this when the set
+    /// has none; without either the event is silent.
+    #[serde(default)]
+    pub sound: Option<String>,
+    /// Higher priorities are kept when a frame has more sounds than it can start.
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default = "default_max_per_frame")]
+    pub max_per_frame: usize,
+    /// Events of this kind closer than this to one already playing this frame are dropped, so
+    /// a crowd hit at once doesn't stack the same sound. Positionless events always merge.
+    #[serde(default)]
+    pub merge_distance: f32,
+}
+
+fn default_max_per_frame() -> usize {
+    4
+}
+
+/// Audio events queued during a frame, played together by `flush` at the end of it.
+pub struct AudioEvents {
+    defs: HashMap<AudioEventKind, AudioEventDef>,
+    queue: Vec<AudioEvent>,
+}
+
+impl AudioEvents {
+    pub fn empty() -> Self {
+        Self {
+            defs: HashMap::new(),
+            queue: Vec::new(),
+        }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, SoundLoadError> {
+        let dir = dir.as_ref();
+        let mut defs = HashMap::new();
+        let mut add = |def: AudioEventDef| {
+            let event = def.event;
+            if defs.insert(event, def).is_some() {
+                eprintln!("audio event {event:?} is defined more than once");
+            }
+        };
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let files = load_wasm_manifest_files(
+                &dir,
+                &["damage_taken.yaml", "dash.yaml", "footstep.yaml", "player_damage_taken.yaml", "ui_click.yaml"],
+            )
+            .await;
+            for file in files {
+                let path = format!("{}/{}", dir, file);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| SoundLoadError::Io(std::io::Error::other(err.to_string())))?;
+                add(serde_yaml::from_str(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                add(serde_yaml::from_str(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+        Ok(Self {
+            defs,
+            queue: Vec::new(),
+        })
+    }
+
+    pub fn push(&mut self, event: AudioEvent) {
+        self.queue.push(event);
+    }
+
+    /// Plays the frame's events, highest priority first, dropping repeats and anything past the
+    /// per-kind and per-frame caps, then clears the queue.
+    pub fn flush(&mut self, sounds: &SoundSystem, footsteps: &mut FootstepSets) {
+        let mut queue = std::mem::take(&mut self.queue);
+        // Stable, so events of equal priority keep the order they happened in.
+        queue.sort_by_key(|event| std::cmp::Reverse(self.defs.get(&event.kind()).map_or(0, |def| def.priority)));
+
+        let mut played: Vec<(AudioEventKind, Option<Vec2>)> = Vec::new();
+        for event in queue.drain(..) {
+            if played.len() >= MAX_SOUNDS_PER_FRAME {
+                break;
+            }
+            let kind = event.kind();
+            let Some(def) = self.defs.get(&kind) else {
+                continue;
+            };
+            let pos = event.pos();
+            let same_kind = played.iter().filter(|(played_kind, _)| *played_kind == kind);
+            let mut count = 0;
+            let mut merged = false;
+            for (_, played_pos) in same_kind {
+                count += 1;
+                merged |= match (pos, played_pos) {
+                    (Some(pos), Some(played_pos)) => pos.distance(*played_pos) < def.merge_distance,
+                    _ => true,
+                };
+            }
+            if merged || count >= def.max_per_frame {
+                continue;
+            }
+
+            let set_sound = match event {
+                AudioEvent::Footstep { set: Some(set), .. } => footsteps.next_sound(set),
+                _ => None,
+            };
+            let Some(sound) = set_sound.or(def.sound.as_deref()) else {
+                continue;
+            };
+            match pos {
+                Some(pos) => sounds.play_at(sound, pos),
+                None => sounds.play(sound),
+            }
+            played.push((kind, pos));
+        }
+        // Hand the allocation back for next frame.
+        self.queue = queue;
+    }
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
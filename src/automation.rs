@@ -0,0 +1,135 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::crop::CropDatabase;
+use crate::farm::FarmState;
+use crate::item::ItemStack;
+use crate::map::StructureDef;
+use crate::power::PowerGrid;
+
+const WATER_AREA_COLOR: Color = Color::new(0.3, 0.6, 1.0, 0.25);
+const HARVEST_AREA_COLOR: Color = Color::new(1.0, 0.8, 0.3, 0.25);
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutomationAction {
+    Water,
+    Harvest,
+}
+
+/// Work a structure does on the crops around it by itself, from `automation` in its json.
+/// Structures that also draw power only work while their network powers them.
+#[derive(Clone, Copy, Deserialize)]
+pub struct AutomationDef {
+    pub action: AutomationAction,
+    /// Tiles from the structure's center it reaches.
+    pub radius: f32,
+    /// Seconds between runs.
+    #[serde(default = "default_every")]
+    pub every: f32,
+}
+
+fn default_every() -> f32 {
+    5.0
+}
+
+impl AutomationDef {
+    /// Tiles in reach of a structure of `def`'s size placed at (`x`, `y`).
+    fn tiles(&self, def: &StructureDef, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
+        let center = vec2(x as f32, y as f32) + vec2(def.structure.width() as f32, def.structure.height() as f32) * 0.5;
+        let reach = self.radius.max(0.0);
+        let (min_x, min_y) = ((center.x - reach).max(0.0) as usize, (center.y - reach).max(0.0) as usize);
+        let (max_x, max_y) = ((center.x + reach) as usize, (center.y + reach) as usize);
+        (min_y..=max_y)
+            .flat_map(move |ty| (min_x..=max_x).map(move |tx| (tx, ty)))
+            .filter(move |&(tx, ty)| (vec2(tx as f32, ty as f32) + 0.5).distance(center) <= reach)
+    }
+}
+
+/// Countdowns of the farm's automated structures, by where they were built.
+#[derive(Default)]
+pub struct Automation {
+    timers: HashMap<(usize, usize), f32>,
+}
+
+impl Automation {
+    /// Runs every structure that's due. Returns what harvesters picked, and where, for the
+    /// caller to drop.
+    pub fn update(
+        &mut self,
+        dt: f32,
+        farm: &mut FarmState,
+        structures: &[StructureDef],
+        power: &PowerGrid,
+        crop_db: &CropDatabase,
+        tile_size: f32,
+    ) -> Vec<(ItemStack, Vec2)> {
+        let mut due = Vec::new();
+        for built in &farm.built {
+            let Some(def) = structures.iter().find(|def| def.id == built.id) else {
+                continue;
+            };
+            let Some(automation) = def.automation else {
+                continue;
+            };
+            let needs_power = def.power.is_some_and(|power| power.consumes > 0.0);
+            if needs_power && !power.is_powered(built.x, built.y) {
+                continue;
+            }
+            let timer = self.timers.entry((built.x, built.y)).or_insert(automation.every);
+            *timer -= dt;
+            if *timer <= 0.0 {
+                *timer = automation.every.max(0.1);
+                due.extend(automation.tiles(def, built.x, built.y).map(|tile| (automation.action, tile)));
+            }
+        }
+        self.timers.retain(|&(x, y), _| farm.built.iter().any(|built| built.x == x && built.y == y));
+
+        let mut picked = Vec::new();
+        for (action, (x, y)) in due {
+            match action {
+                AutomationAction::Water => {
+                    farm.soil.water(x, y);
+                }
+                AutomationAction::Harvest => {
+                    if let Some(produce) = farm.crops.harvest(x, y, crop_db) {
+                        picked.push((produce, (vec2(x as f32, y as f32) + 0.5) * tile_size));
+                    }
+                }
+            }
+        }
+        picked
+    }
+}
+
+/// Shades the tiles a structure reaches, in world space: the one being placed at `ghost`, or
+/// the built one under `mouse`.
+pub fn draw_area(
+    farm: &FarmState,
+    structures: &[StructureDef],
+    ghost: Option<(&StructureDef, usize, usize)>,
+    mouse: Vec2,
+    tile_size: f32,
+) {
+    let hovered = || {
+        farm.built.iter().find_map(|built| {
+            let def = structures.iter().find(|def| def.id == built.id)?;
+            let (w, h) = (def.structure.width() as f32, def.structure.height() as f32);
+            let area = Rect::new(built.x as f32 * tile_size, built.y as f32 * tile_size, w * tile_size, h * tile_size);
+            area.contains(mouse).then_some((def, built.x, built.y))
+        })
+    };
+    let Some((def, x, y)) = ghost.filter(|(def, ..)| def.automation.is_some()).or_else(hovered) else {
+        return;
+    };
+    let Some(automation) = def.automation else {
+        return;
+    };
+    let color = match automation.action {
+        AutomationAction::Water => WATER_AREA_COLOR,
+        AutomationAction::Harvest => HARVEST_AREA_COLOR,
+    };
+    for (tx, ty) in automation.tiles(def, x, y) {
+        draw_rectangle(tx as f32 * tile_size, ty as f32 * tile_size, tile_size, tile_size, color);
+    }
+}
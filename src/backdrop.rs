@@ -0,0 +1,209 @@
+use macroquad::prelude::*;
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::path::Path;
+use crate::helpers::{asset_path, data_path, load_wasm_manifest_files};
+use crate::scene::SceneKind;
+
+#[derive(Debug)]
+pub enum BackdropLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for BackdropLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for BackdropLoadError {}
+
+impl From<std::io::Error> for BackdropLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for BackdropLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+/// One backdrop layer. Without a texture the layer fills the whole view with `color`.
+#[derive(Clone, Deserialize)]
+pub struct BackdropLayerDef {
+    #[serde(default)]
+    pub texture: Option<String>,
+    #[serde(default = "default_color")]
+    pub color: [f32; 4],
+    /// How far the layer moves with the camera on each axis: 0 stays pinned to the view like the
+    /// sky, 1 moves with the world like the tilemap.
+    #[serde(default)]
+    pub scroll: [f32; 2],
+    /// World position of the first tile once scrolling is applied.
+    #[serde(default)]
+    pub offset: [f32; 2],
+    /// World units per second the layer drifts on its own, for clouds.
+    #[serde(default)]
+    pub drift: [f32; 2],
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(default = "default_true")]
+    pub repeat_x: bool,
+    #[serde(default)]
+    pub repeat_y: bool,
+}
+
+fn default_color() -> [f32; 4] {
+    [1.0, 1.0, 1.0, 1.0]
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Layers drawn behind a scene's tilemap, back to front.
+#[derive(Clone, Deserialize)]
+pub struct BackdropDef {
+    /// Scene id, `expedition` or `farm`.
+    pub scene: String,
+    pub layers: Vec<BackdropLayerDef>,
+}
+
+struct Backdrop {
+    scene: SceneKind,
+    layers: Vec<(BackdropLayerDef, Option<Texture2D>)>,
+}
+
+/// Parallax backdrops per scene, shown wherever the view reaches past the map's tiles.
+pub struct BackdropDatabase {
+    backdrops: Vec<Backdrop>,
+    clock: f32,
+}
+
+impl BackdropDatabase {
+    pub fn empty() -> Self {
+        Self {
+            backdrops: Vec::new(),
+            clock: 0.0,
+        }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, BackdropLoadError> {
+        let dir = dir.as_ref();
+        let mut defs = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let files = load_wasm_manifest_files(&dir, &["expedition.yaml", "farm.yaml"]).await;
+            for file in files {
+                let path = format!("{}/{}", dir, file);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| BackdropLoadError::Io(std::io::Error::other(err.to_string())))?;
+                defs.push(serde_yaml::from_str::<BackdropDef>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                defs.push(serde_yaml::from_str::<BackdropDef>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+
+        let mut backdrops = Vec::with_capacity(defs.len());
+        for def in defs {
+            let Some(scene) = SceneKind::from_id(&def.scene) else {
+                eprintln!("backdrop for unknown scene '{}'", def.scene);
+                continue;
+            };
+            let mut layers = Vec::with_capacity(def.layers.len());
+            for layer in def.layers {
+                let texture = match &layer.texture {
+                    Some(path) => Some(crate::texture::load_texture_or_placeholder(&asset_path(path)).await),
+                    None => None,
+                };
+                layers.push((layer, texture));
+            }
+            backdrops.push(Backdrop { scene, layers });
+        }
+        Ok(Self {
+            backdrops,
+            clock: 0.0,
+        })
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.clock += dt;
+    }
+
+    /// Draws `scene`'s layers over `view`, with the world camera active and before the tilemap.
+    pub fn draw(&self, scene: SceneKind, view: Rect, tint: Color) {
+        let Some(backdrop) = self.backdrops.iter().find(|backdrop| backdrop.scene == scene) else {
+            return;
+        };
+        for (layer, texture) in &backdrop.layers {
+            let color = Color::new(
+                layer.color[0] * tint.r,
+                layer.color[1] * tint.g,
+                layer.color[2] * tint.b,
+                layer.color[3] * tint.a,
+            );
+            let Some(texture) = texture else {
+                draw_rectangle(view.x, view.y, view.w, view.h, color);
+                continue;
+            };
+            let size = texture.size() * layer.scale.max(0.01);
+            // A layer that scrolls slower than the world lags behind the camera by the rest.
+            let origin = vec2(layer.offset[0], layer.offset[1])
+                + view.center() * (Vec2::ONE - vec2(layer.scroll[0], layer.scroll[1]))
+                + vec2(layer.drift[0], layer.drift[1]) * self.clock;
+            let xs = tile_starts(origin.x, size.x, view.x, view.x + view.w, layer.repeat_x);
+            let ys = tile_starts(origin.y, size.y, view.y, view.y + view.h, layer.repeat_y);
+            for y in ys.clone() {
+                for x in xs.clone() {
+                    draw_texture_ex(
+                        texture,
+                        x,
+                        y,
+                        color,
+                        DrawTextureParams {
+                            dest_size: Some(size),
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Where each copy of a tile of length `size` placed at `origin` starts while covering
+/// `min..max`, or just `origin` when the layer doesn't repeat on this axis.
+fn tile_starts(origin: f32, size: f32, min: f32, max: f32, repeat: bool) -> impl Iterator<Item = f32> + Clone {
+    let (first, count) = if repeat && size > 0.0 {
+        let first = origin + ((min - origin) / size).floor() * size;
+        (first, ((max - first) / size).ceil().max(0.0) as usize)
+    } else {
+        (origin, 1)
+    };
+    (0..count).map(move |i| first + i as f32 * size)
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
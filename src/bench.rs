@@ -0,0 +1,91 @@
+const WARMUP_SECONDS: f32 = 1.0;
+const MEASURE_SECONDS: f32 = 5.0;
+
+/// What a benchmark keeps alive while it measures.
+pub enum BenchLoad {
+    Particles { count: usize },
+    Entities { uids: Vec<u64> },
+}
+
+/// Holds a synthetic load steady, skips a warmup window, then samples frame times.
+pub struct Benchmark {
+    label: String,
+    load: BenchLoad,
+    warmup: f32,
+    remaining: f32,
+    frame_times: Vec<f32>,
+}
+
+pub struct BenchReport {
+    pub label: String,
+    pub frames: usize,
+    pub avg_ms: f32,
+    pub p95_ms: f32,
+    pub p99_ms: f32,
+    pub max_ms: f32,
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "bench {}: {} frames, avg {:.2}ms ({:.0} fps), p95 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+            self.label,
+            self.frames,
+            self.avg_ms,
+            1000.0 / self.avg_ms.max(0.001),
+            self.p95_ms,
+            self.p99_ms,
+            self.max_ms,
+        )
+    }
+}
+
+impl Benchmark {
+    pub fn new(label: impl Into<String>, load: BenchLoad) -> Self {
+        Self {
+            label: label.into(),
+            load,
+            warmup: WARMUP_SECONDS,
+            remaining: MEASURE_SECONDS,
+            frame_times: Vec::new(),
+        }
+    }
+
+    pub fn load(&self) -> &BenchLoad {
+        &self.load
+    }
+
+    /// Records one frame. Returns the report once the measure window has elapsed.
+    pub fn record(&mut self, dt: f32) -> Option<BenchReport> {
+        if self.warmup > 0.0 {
+            self.warmup -= dt;
+            return None;
+        }
+        self.frame_times.push(dt * 1000.0);
+        self.remaining -= dt;
+        if self.remaining > 0.0 {
+            return None;
+        }
+
+        let mut sorted = self.frame_times.clone();
+        sorted.sort_by(f32::total_cmp);
+        let frames = sorted.len().max(1);
+        Some(BenchReport {
+            label: self.label.clone(),
+            frames: sorted.len(),
+            avg_ms: sorted.iter().sum::<f32>() / frames as f32,
+            p95_ms: percentile(&sorted, 0.95),
+            p99_ms: percentile(&sorted, 0.99),
+            max_ms: sorted.last().copied().unwrap_or(0.0),
+        })
+    }
+}
+
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = ((sorted.len() - 1) as f32 * p).round() as usize;
+    sorted[index.min(sorted.len() - 1)]
+}
@@ -0,0 +1,134 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::entity::{Entity, EntityDatabase, EntityKind, MovementRegistry, StatBlock};
+use crate::item::{Inventory, ItemStack};
+use crate::particle::ParticleSystem;
+
+/// Structure id of the station that pairs bots up.
+pub const BREEDING_STATION: &str = "breeding_pod";
+/// How far from the station a bot can stand and still be picked as a parent.
+const PARENT_RANGE: f32 = 40.0;
+
+/// Lets two friendly entities be merged at the breeding station into one offspring.
+#[derive(Clone, Deserialize)]
+pub struct BreedDef {
+    /// Entity id of the offspring; defaults to the parents' own.
+    #[serde(default)]
+    pub offspring: Option<String>,
+    /// Per stat, the range of a random amount added to the parents' average.
+    #[serde(default)]
+    pub mutation: HashMap<String, [f32; 2]>,
+    /// Items used up by each pairing.
+    #[serde(default)]
+    pub cost: Vec<ItemStack>,
+    /// Particle effect burst where the offspring appears.
+    #[serde(default)]
+    pub particles: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum BreedError {
+    /// Fewer than two bots of a kind are standing by the station.
+    NoPair,
+    /// The player can't pay the pairing's cost.
+    Cost(Vec<ItemStack>),
+    UnknownOffspring(String),
+}
+
+impl std::fmt::Display for BreedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoPair => write!(f, "bring two bots of a kind to the pod"),
+            Self::Cost(cost) => {
+                let items: Vec<String> = cost.iter().map(|stack| format!("{} {}", stack.count, stack.item)).collect();
+                write!(f, "breeding needs {}", items.join(", "))
+            }
+            Self::UnknownOffspring(id) => write!(f, "unknown offspring entity '{id}'"),
+        }
+    }
+}
+
+impl std::error::Error for BreedError {}
+
+fn offspring_id<'a>(ent: &Entity, db: &'a EntityDatabase) -> Option<&'a str> {
+    let def = &db.entities[ent.instance.def];
+    let breed = def.breed.as_ref()?;
+    Some(breed.offspring.as_deref().unwrap_or(&def.id))
+}
+
+fn eligible(ent: &Entity, db: &EntityDatabase, station: Rect) -> bool {
+    let def = &db.entities[ent.instance.def];
+    def.kind == EntityKind::Friend
+        && def.breed.is_some()
+        && !ent.instance.is_dying()
+        && !ent.instance.ridden
+        && ent.hitbox(db).center().distance(station.center()) <= PARENT_RANGE
+}
+
+/// Each stat the parents have, averaged, plus a random amount from `mutation`.
+fn offspring_stats(a: &StatBlock, b: &StatBlock, mutation: &HashMap<String, [f32; 2]>) -> StatBlock {
+    let mut stats = StatBlock::default();
+    for key in a.keys().chain(b.keys()) {
+        if stats.has(key) {
+            continue;
+        }
+        let mut value = (a.get(key, b.get(key, 0.0)) + b.get(key, a.get(key, 0.0))) * 0.5;
+        if let Some(&[low, high]) = mutation.get(key) {
+            value += if high > low { rand::gen_range(low, high) } else { low };
+        }
+        stats.add(key, value.max(0.0));
+    }
+    stats
+}
+
+/// Merges the first two bots of a kind standing by `station` into an offspring there, paying
+/// the pairing's cost from `inventory`. Returns the offspring's name.
+pub fn breed(
+    entities: &mut Vec<Entity>,
+    db: &EntityDatabase,
+    registry: &MovementRegistry,
+    station: Rect,
+    inventory: &mut Inventory,
+    particles: &mut ParticleSystem,
+) -> Result<String, BreedError> {
+    let candidates: Vec<usize> = (0..entities.len())
+        .filter(|&idx| eligible(&entities[idx], db, station))
+        .collect();
+    let pair = candidates.iter().enumerate().find_map(|(n, &first)| {
+        let kind = offspring_id(&entities[first], db);
+        candidates[n + 1..]
+            .iter()
+            .find(|&&second| offspring_id(&entities[second], db) == kind)
+            .map(|&second| (first, second))
+    });
+    let Some((first, second)) = pair else {
+        return Err(BreedError::NoPair);
+    };
+    let breed = db.entities[entities[first].instance.def].breed.as_ref().ok_or(BreedError::NoPair)?;
+    let id = offspring_id(&entities[first], db).unwrap_or_default();
+    if !inventory.has_all(&breed.cost) {
+        return Err(BreedError::Cost(breed.cost.clone()));
+    }
+
+    let center = station.center();
+    let Some(mut child) = Entity::spawn(db, id, center, registry) else {
+        return Err(BreedError::UnknownOffspring(id.to_string()));
+    };
+    let (a, b) = (&entities[first].instance, &entities[second].instance);
+    child.instance.set_stats(offspring_stats(&a.stats, &b.stats, &breed.mutation));
+    child.instance.generation = a.generation.max(b.generation) + 1;
+    let offset = child.hitbox(db).center() - child.instance.pos;
+    child.instance.pos = center - offset;
+
+    inventory.remove_all(&breed.cost);
+    if let Some(particle) = breed.particles.as_deref() {
+        particles.burst(particle, center);
+    }
+    // Removes the later index first so the earlier one stays put.
+    entities.swap_remove(second);
+    entities.swap_remove(first);
+    let name = format!("{} (gen {})", db.entities[child.instance.def].name, child.instance.generation);
+    entities.push(child);
+    Ok(name)
+}
@@ -0,0 +1,174 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::farm::FarmState;
+use crate::font::draw_text;
+use crate::item::{Inventory, ItemStack};
+use crate::locale::Locale;
+use crate::map::{StructureDef, TileMap, TileSet};
+use crate::scene::farm_build_site_clear;
+
+const CLEAR_TINT: Color = Color::new(0.4, 1.0, 0.4, 0.6);
+const BLOCKED_TINT: Color = Color::new(1.0, 0.3, 0.3, 0.6);
+const MENU_LINE_HEIGHT: f32 = 20.0;
+
+/// Where the selected structure would go, in tiles.
+#[derive(Clone, Copy)]
+pub struct BuildSite {
+    pub x: usize,
+    pub y: usize,
+    pub clear: bool,
+}
+
+/// A structure the player built. Snapshots only keep tiles, so these are saved alongside
+/// to restore interactors and chest contents.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BuiltStructure {
+    pub id: String,
+    pub x: usize,
+    pub y: usize,
+}
+
+/// Farm build mode. B toggles it, Tab cycles through structures that have a `build_cost`,
+/// and left click places the selection.
+#[derive(Default)]
+pub struct BuildMode {
+    active: bool,
+    selected: usize,
+}
+
+impl BuildMode {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn close(&mut self) {
+        self.active = false;
+    }
+
+    pub fn update(&mut self, structures: &[StructureDef]) {
+        let count = buildable(structures).count();
+        if is_key_pressed(KeyCode::B) && count > 0 {
+            self.active = !self.active;
+        }
+        if self.active && is_key_pressed(KeyCode::Tab) {
+            self.selected = (self.selected + 1) % count;
+        }
+        self.selected = self.selected.min(count.saturating_sub(1));
+    }
+
+    pub fn selected<'a>(&self, structures: &'a [StructureDef]) -> Option<&'a StructureDef> {
+        if !self.active {
+            return None;
+        }
+        buildable(structures).nth(self.selected)
+    }
+
+    /// Snaps `def` to the tile grid centered on `world`. Sites overlapping the player
+    /// count as blocked so nobody gets walled in.
+    pub fn site(
+        &self,
+        def: &StructureDef,
+        map: &TileMap,
+        world: Vec2,
+        player_hitbox: Rect,
+    ) -> Option<BuildSite> {
+        if world.x < 0.0 || world.y < 0.0 {
+            return None;
+        }
+        let tile_size = map.tile_size();
+        let (width, height) = (def.structure.width(), def.structure.height());
+        let x = ((world.x / tile_size) as usize).saturating_sub(width.saturating_sub(1) / 2);
+        let y = ((world.y / tile_size) as usize).saturating_sub(height.saturating_sub(1) / 2);
+        let footprint = Rect::new(
+            x as f32 * tile_size,
+            y as f32 * tile_size,
+            width as f32 * tile_size,
+            height as f32 * tile_size,
+        );
+        let clear = farm_build_site_clear(map, def, x, y) && !footprint.overlaps(&player_hitbox);
+        Some(BuildSite { x, y, clear })
+    }
+
+    pub fn draw_ghost(&self, def: &StructureDef, site: BuildSite, tileset: &TileSet, tile_size: f32) {
+        let tint = if site.clear { CLEAR_TINT } else { BLOCKED_TINT };
+        let origin = vec2(site.x as f32 * tile_size, site.y as f32 * tile_size);
+        def.structure.draw_ghost(tileset, origin, tile_size, tint);
+    }
+
+    /// Lists buildable structures and their costs; unaffordable costs are drawn red.
+    pub fn draw_menu(&self, structures: &[StructureDef], inventory: &Inventory, locale: &Locale) {
+        if !self.active {
+            return;
+        }
+        let entries: Vec<&StructureDef> = buildable(structures).collect();
+        let x = 20.0;
+        let y = screen_height() * 0.5 - entries.len() as f32 * MENU_LINE_HEIGHT * 0.5;
+        let width = 280.0;
+        let height = (entries.len() as f32 + 1.5) * MENU_LINE_HEIGHT;
+        draw_rectangle(x - 8.0, y - MENU_LINE_HEIGHT, width, height, Color::new(0.0, 0.0, 0.0, 0.7));
+        draw_text("build  (Tab: next, B: close)", x, y, 18.0, WHITE);
+        for (i, def) in entries.iter().enumerate() {
+            let cost = def.build_cost.as_deref().unwrap_or_default();
+            let cost_label = cost
+                .iter()
+                .map(|stack| format!("{} {}", locale.format_int(stack.count as i64), stack.item))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let marker = if i == self.selected { ">" } else { " " };
+            let color = if inventory.has_all(cost) { LIGHTGRAY } else { RED };
+            draw_text(
+                &format!("{marker} {}  [{cost_label}]", def.id),
+                x,
+                y + (i as f32 + 1.0) * MENU_LINE_HEIGHT,
+                18.0,
+                color,
+            );
+        }
+    }
+}
+
+/// Places `def` at `site` if it's clear, paying its cost from `inventory`.
+pub fn place(
+    map: &mut TileMap,
+    farm: &mut FarmState,
+    def: &StructureDef,
+    site: BuildSite,
+    inventory: &mut Inventory,
+) -> bool {
+    if !site.clear {
+        return false;
+    }
+    if !inventory.remove_all(def.build_cost.as_deref().unwrap_or_default()) {
+        return false;
+    }
+    map.place_structure_def(def, site.x, site.y);
+    farm.built.push(BuiltStructure {
+        id: def.id.clone(),
+        x: site.x,
+        y: site.y,
+    });
+    if def.storage_slots > 0 {
+        farm.chests.insert(site.x, site.y, def.storage_slots);
+    }
+    true
+}
+
+/// Forgets the built structure that stood over `area` once it's broken. Returns what its
+/// chest held, if it was one.
+pub fn demolish(farm: &mut FarmState, area: Rect, tile_size: f32) -> Vec<ItemStack> {
+    let mut spilled = Vec::new();
+    farm.built.retain(|built| {
+        if !area.contains(vec2(built.x as f32 + 0.5, built.y as f32 + 0.5) * tile_size) {
+            return true;
+        }
+        if let Some(chest) = farm.chests.remove(built.x, built.y) {
+            spilled.extend(chest.inventory.stacks().iter().cloned());
+        }
+        false
+    });
+    spilled
+}
+
+fn buildable(structures: &[StructureDef]) -> impl Iterator<Item = &StructureDef> {
+    structures.iter().filter(|def| def.build_cost.is_some())
+}
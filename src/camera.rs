@@ -0,0 +1,230 @@
+use macroquad::prelude::*;
+use crate::helpers::random_range;
+
+const ZOOM_STEP: f32 = 1.15;
+const MIN_ZOOM: f32 = 0.6;
+const MAX_ZOOM: f32 = 2.5;
+const ZOOM_SMOOTHING: f32 = 12.0;
+/// Shake lost per second.
+const SHAKE_DECAY: f32 = 1.6;
+/// World units the view may jolt at full shake.
+const MAX_SHAKE_OFFSET: f32 = 6.0;
+/// World units kept clear around a followed group.
+const GROUP_MARGIN: f32 = 48.0;
+/// Widest a group can pull the view, as a multiple of the configured view height.
+const MAX_GROUP_ZOOM_OUT: f32 = 2.5;
+
+struct CameraFocus {
+    target: Vec2,
+    remaining: f32,
+}
+
+pub struct GameCamera {
+    camera: Camera2D,
+    fov: f32,
+    drag: f32,
+    zoom: f32,
+    target_zoom: f32,
+    dead_zone: Vec2,
+    bounds: Option<Rect>,
+    focus: Option<CameraFocus>,
+    /// Current shake, 0-1; the jolt grows with its square.
+    shake: f32,
+    shake_scale: f32,
+    shake_offset: Vec2,
+    /// Half the size of the box around the followed point that has to stay in view.
+    group_extent: Vec2,
+    /// View height the camera has widened to for `group_extent`, eased like the zoom.
+    group_height: f32,
+}
+
+impl GameCamera {
+    pub fn new(target: Vec2, fov: f32, drag: f32) -> Self {
+        Self {
+            camera: Camera2D {
+                target,
+                zoom: vec2(1.0, 1.0),
+                ..Default::default()
+            },
+            fov: fov.max(1.0),
+            drag,
+            zoom: 1.0,
+            target_zoom: 1.0,
+            dead_zone: Vec2::ZERO,
+            bounds: None,
+            focus: None,
+            shake: 0.0,
+            shake_scale: 1.0,
+            shake_offset: Vec2::ZERO,
+            group_extent: Vec2::ZERO,
+            group_height: 0.0,
+        }
+    }
+
+    /// Keeps a box of `half_extents` around the followed point in view, pulling out as far as
+    /// `MAX_GROUP_ZOOM_OUT` allows; for following two players from their midpoint.
+    pub fn set_group_extent(&mut self, half_extents: Vec2) {
+        self.group_extent = half_extents.abs();
+    }
+
+    /// Half extents (world units) the followed point may drift from the camera center
+    /// before the camera starts moving.
+    pub fn set_dead_zone(&mut self, half_extents: Vec2) {
+        self.dead_zone = half_extents.max(Vec2::ZERO);
+    }
+
+    pub fn set_tuning(&mut self, fov: f32, drag: f32) {
+        self.fov = fov.max(1.0);
+        self.drag = drag;
+    }
+
+    pub fn set_bounds(&mut self, bounds: Option<Rect>) {
+        self.bounds = bounds;
+    }
+
+    /// Scales every shake; zero turns screen shake off.
+    pub fn set_shake_scale(&mut self, scale: f32) {
+        self.shake_scale = scale.max(0.0);
+    }
+
+    /// Adds `amount` (0-1) of screen shake, which fades out over the next moments.
+    pub fn shake(&mut self, amount: f32) {
+        self.shake = (self.shake + amount).clamp(0.0, 1.0);
+    }
+
+    /// Temporarily pulls the camera to `target` for `duration` seconds, then resumes following.
+    pub fn focus_on(&mut self, target: Vec2, duration: f32) {
+        self.focus = Some(CameraFocus {
+            target,
+            remaining: duration.max(0.0),
+        });
+    }
+
+    pub fn snap_to(&mut self, target: Vec2) {
+        self.focus = None;
+        self.shake_offset = Vec2::ZERO;
+        self.camera.target = target;
+        self.clamp_to_bounds();
+    }
+
+    pub fn handle_zoom_input(&mut self) {
+        let (_, wheel_y) = mouse_wheel();
+        if wheel_y > 0.0 {
+            self.target_zoom = (self.target_zoom * ZOOM_STEP).min(MAX_ZOOM);
+        } else if wheel_y < 0.0 {
+            self.target_zoom = (self.target_zoom / ZOOM_STEP).max(MIN_ZOOM);
+        }
+    }
+
+    pub fn update(&mut self, follow: Vec2, dt: f32, render_target: Option<RenderTarget>) {
+        let zoom_t = 1.0 - (-ZOOM_SMOOTHING * dt).exp();
+        self.zoom += (self.target_zoom - self.zoom) * zoom_t;
+        let aspect = screen_width() / screen_height().max(1.0);
+        let needed = (self.group_extent + GROUP_MARGIN) * 2.0;
+        let fit = needed.y.max(needed.x / aspect.max(0.01));
+        let fit = if self.group_extent == Vec2::ZERO { 0.0 } else { fit.min(self.fov * MAX_GROUP_ZOOM_OUT) };
+        self.group_height += (fit - self.group_height) * zoom_t;
+        self.camera.zoom = zoom_for_view_height(self.view_height(), render_target.is_some());
+        self.camera.render_target = render_target;
+        self.camera.target -= self.shake_offset;
+
+        let desired = match self.focus.as_mut() {
+            Some(focus) => {
+                focus.remaining -= dt;
+                focus.target
+            }
+            None => self.dead_zone_target(follow),
+        };
+        if self.focus.as_ref().is_some_and(|focus| focus.remaining <= 0.0) {
+            self.focus = None;
+        }
+
+        let t = 1.0 - (-self.drag * dt).exp();
+        self.camera.target += (desired - self.camera.target) * t;
+        self.clamp_to_bounds();
+
+        self.shake = (self.shake - SHAKE_DECAY * dt).max(0.0);
+        let strength = self.shake * self.shake * self.shake_scale * MAX_SHAKE_OFFSET;
+        self.shake_offset = vec2(random_range(-1.0, 1.0), random_range(-1.0, 1.0)) * strength;
+        self.camera.target += self.shake_offset;
+    }
+
+    /// Puts the view straight on `target`, without follow lag or shake, for photo mode.
+    pub fn update_free(&mut self, target: Vec2, dt: f32, render_target: Option<RenderTarget>) {
+        let zoom_t = 1.0 - (-ZOOM_SMOOTHING * dt).exp();
+        self.zoom += (self.target_zoom - self.zoom) * zoom_t;
+        self.camera.zoom = zoom_for_view_height(self.view_height(), render_target.is_some());
+        self.camera.render_target = render_target;
+        self.focus = None;
+        self.shake = 0.0;
+        self.shake_offset = Vec2::ZERO;
+        self.camera.target = target;
+        self.clamp_to_bounds();
+    }
+
+    pub fn camera(&self) -> &Camera2D {
+        &self.camera
+    }
+
+    pub fn target(&self) -> Vec2 {
+        self.camera.target
+    }
+
+    pub fn zoom(&self) -> Vec2 {
+        self.camera.zoom
+    }
+
+    pub fn view_height(&self) -> f32 {
+        (self.fov / self.zoom.max(0.0001)).max(self.group_height)
+    }
+
+    /// Square logic rect around the camera used for culling and sim range checks.
+    pub fn view_rect(&self) -> Rect {
+        let view_h = self.view_height().max(1.0);
+        Rect::new(
+            self.camera.target.x - view_h * 0.5,
+            self.camera.target.y - view_h * 0.5,
+            view_h,
+            view_h,
+        )
+    }
+
+    pub fn screen_to_world(&self, point: Vec2) -> Vec2 {
+        self.camera.screen_to_world(point)
+    }
+
+    fn dead_zone_target(&self, follow: Vec2) -> Vec2 {
+        let center = self.camera.target;
+        let offset = follow - center;
+        let excess = vec2(
+            (offset.x.abs() - self.dead_zone.x).max(0.0) * offset.x.signum(),
+            (offset.y.abs() - self.dead_zone.y).max(0.0) * offset.y.signum(),
+        );
+        center + excess
+    }
+
+    fn clamp_to_bounds(&mut self) {
+        let Some(bounds) = self.bounds else {
+            return;
+        };
+        let half_w = 1.0 / self.camera.zoom.x.abs().max(0.0001);
+        let half_h = 1.0 / self.camera.zoom.y.abs().max(0.0001);
+        self.camera.target.x = clamp_axis(self.camera.target.x, bounds.x, bounds.w, half_w);
+        self.camera.target.y = clamp_axis(self.camera.target.y, bounds.y, bounds.h, half_h);
+    }
+}
+
+fn clamp_axis(value: f32, min: f32, size: f32, half_view: f32) -> f32 {
+    if size <= half_view * 2.0 {
+        return min + size * 0.5;
+    }
+    value.clamp(min + half_view, min + size - half_view)
+}
+
+fn zoom_for_view_height(view_height: f32, render_target: bool) -> Vec2 {
+    let view_h = view_height.max(1.0);
+    let aspect = screen_width().max(1.0) / screen_height().max(1.0);
+    let view_w = view_h * aspect;
+    let y_sign = if render_target { -1.0 } else { 1.0 };
+    vec2(2.0 / view_w, y_sign * 2.0 / view_h)
+}
@@ -0,0 +1,236 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::collections::VecDeque;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::{Receiver, channel};
+use crate::font::{TextStyle, draw_text_styled};
+use crate::photo::timestamp;
+
+/// Seconds a saved or failed clip message stays up.
+const MESSAGE_TIME: f32 = 3.0;
+/// 1 is the best colours and slowest, 30 the fastest.
+const GIF_SPEED: i32 = 20;
+
+/// How much gameplay the clip recorder keeps, from the `clips` section of the config.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ClipConfig {
+    /// Seconds kept; 0 turns the recorder off.
+    pub seconds: f32,
+    pub fps: f32,
+    /// Frames are scaled down to this many pixels across, keeping the screen's aspect.
+    pub width: u32,
+}
+
+impl Default for ClipConfig {
+    fn default() -> Self {
+        Self {
+            seconds: 0.0,
+            fps: 12.0,
+            width: 320,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ClipFrame {
+    image: image::RgbaImage,
+    /// Seconds since the frame before it was kept.
+    gap: f32,
+}
+
+/// Keeps the last few seconds of the screen, scaled down, so F8 can save them as a GIF and
+/// shift+F8 as numbered PNGs under `~/.cropbots/clips/`. The browser downloads the GIF instead.
+/// Frames are scaled on the GPU so only the small copy is read back, and exports are written
+/// on a thread of their own off the browser.
+#[derive(Default)]
+pub struct ClipRecorder {
+    frames: VecDeque<ClipFrame>,
+    since_capture: f32,
+    message: Option<(String, f32)>,
+    /// The whole screen, copied on the GPU.
+    screen: Option<Texture2D>,
+    /// `screen` scaled to the clip's size.
+    target: Option<RenderTarget>,
+    #[cfg(not(target_arch = "wasm32"))]
+    export: Option<Receiver<Result<String, String>>>,
+}
+
+impl ClipRecorder {
+    /// Handles the export keys, unless something else has the keyboard.
+    pub fn update(&mut self, dt: f32, keys: bool) {
+        if let Some((_, remaining)) = self.message.as_mut() {
+            *remaining -= dt;
+        }
+        if self.message.as_ref().is_some_and(|(_, remaining)| *remaining <= 0.0) {
+            self.message = None;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(result) = self.export.as_ref().and_then(|export| export.try_recv().ok()) {
+            self.export = None;
+            self.report(result);
+        }
+        if !keys || !is_key_pressed(KeyCode::F8) {
+            return;
+        }
+        let sequence = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        if self.frames.is_empty() {
+            self.report(Err("nothing recorded yet".to_string()));
+        } else {
+            self.export(sequence);
+        }
+    }
+
+    fn report(&mut self, result: Result<String, String>) {
+        let message = match result {
+            Ok(path) => format!("saved {path}"),
+            Err(err) => format!("clip failed: {err}"),
+        };
+        self.message = Some((message, MESSAGE_TIME));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export(&mut self, sequence: bool) {
+        if self.export.is_some() {
+            self.report(Err("still saving the last one".to_string()));
+            return;
+        }
+        let frames: Vec<ClipFrame> = self.frames.iter().cloned().collect();
+        let (send, receive) = channel();
+        std::thread::spawn(move || {
+            let result = if sequence { write_sequence(&frames) } else { write_gif(&frames) };
+            let _ = send.send(result);
+        });
+        self.export = Some(receive);
+        self.message = Some(("saving clip...".to_string(), f32::INFINITY));
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn export(&mut self, sequence: bool) {
+        let result = if sequence {
+            Err("PNG sequences aren't available in the browser, F8 saves a GIF".to_string())
+        } else {
+            let frames: Vec<ClipFrame> = self.frames.iter().cloned().collect();
+            let name = format!("cropbots-{}.gif", timestamp(miniquad::date::now()));
+            encode_gif(&frames).and_then(|bytes| {
+                crate::helpers::wasm_download(&name, &bytes).map_err(|err| err.to_string())?;
+                Ok(name)
+            })
+        };
+        self.report(result);
+    }
+
+    /// Keeps the screen as it is now if a frame is due. Call once the frame is fully drawn.
+    pub fn capture(&mut self, config: ClipConfig, dt: f32) {
+        if config.seconds <= 0.0 || config.fps <= 0.0 || config.width == 0 {
+            self.frames.clear();
+            return;
+        }
+        self.since_capture += dt;
+        if self.since_capture < 1.0 / config.fps {
+            return;
+        }
+        let gap = std::mem::take(&mut self.since_capture);
+        let image = self.grab(config.width);
+        self.frames.push_back(ClipFrame { image, gap });
+        let mut kept: f32 = self.frames.iter().skip(1).map(|frame| frame.gap).sum();
+        while kept > config.seconds
+            && let Some(dropped) = self.frames.pop_front()
+        {
+            kept -= self.frames.front().map_or(dropped.gap, |next| next.gap);
+        }
+    }
+
+    /// The last export's outcome.
+    pub fn draw(&self) {
+        if let Some((message, _)) = &self.message {
+            draw_text_styled(message, 16.0, screen_height() - 60.0, 16.0, LIGHTGRAY, TextStyle::SHADOWED);
+        }
+    }
+
+    /// The screen scaled to `width` pixels across, keeping its aspect. Copying and scaling
+    /// happen on the GPU; only the scaled frame is read back.
+    fn grab(&mut self, width: u32) -> image::RgbaImage {
+        let (screen_w, screen_h) = (screen_width().max(1.0) as u32, screen_height().max(1.0) as u32);
+        let width = width.min(screen_w).max(1);
+        let height = (screen_h * width / screen_w).max(1);
+        if self.screen.as_ref().is_none_or(|screen| screen.size() != vec2(screen_w as f32, screen_h as f32)) {
+            let screen = render_target(screen_w, screen_h).texture;
+            screen.set_filter(FilterMode::Nearest);
+            self.screen = Some(screen);
+        }
+        if self.target.as_ref().is_none_or(|target| target.texture.size() != vec2(width as f32, height as f32)) {
+            self.target = Some(render_target(width, height));
+        }
+        let (Some(screen), Some(target)) = (self.screen.as_ref(), self.target.as_ref()) else {
+            return image::RgbaImage::new(width, height);
+        };
+
+        push_camera_state();
+        let mut camera = Camera2D::from_display_rect(Rect::new(0.0, 0.0, width as f32, height as f32));
+        camera.render_target = Some(target.clone());
+        // Switching cameras flushes what's been drawn to the screen, so the copy has all of it.
+        set_camera(&camera);
+        screen.grab_screen();
+        let params = DrawTextureParams {
+            dest_size: Some(vec2(width as f32, height as f32)),
+            ..Default::default()
+        };
+        draw_texture_ex(screen, 0.0, 0.0, WHITE, params);
+        pop_camera_state();
+
+        // The copy is bottom row first, drawn as if top first, which leaves the target's rows
+        // the way images store them.
+        let data = target.texture.get_texture_data();
+        let mut image = image::RgbaImage::from_raw(width, height, data.bytes)
+            .unwrap_or_else(|| image::RgbaImage::new(width, height));
+        for pixel in image.pixels_mut() {
+            pixel.0[3] = 255;
+        }
+        image
+    }
+}
+
+fn encode_gif(frames: &[ClipFrame]) -> Result<Vec<u8>, String> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+    // Each frame shows until the next one was kept; the last for as long as the one before.
+    let last_gap = frames.last().map_or(0.1, |frame| frame.gap);
+    let delays = frames.iter().skip(1).map(|frame| frame.gap).chain(std::iter::once(last_gap));
+    let frames = frames.iter().zip(delays).map(|(frame, delay)| {
+        let delay = image::Delay::from_numer_denom_ms((delay * 1000.0).round().max(10.0) as u32, 1);
+        image::Frame::from_parts(frame.image.clone(), 0, 0, delay)
+    });
+    let mut bytes = Vec::new();
+    let mut encoder = GifEncoder::new_with_speed(&mut bytes, GIF_SPEED);
+    encoder.set_repeat(Repeat::Infinite).map_err(|err| err.to_string())?;
+    encoder.encode_frames(frames).map_err(|err| err.to_string())?;
+    drop(encoder);
+    Ok(bytes)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_gif(frames: &[ClipFrame]) -> Result<String, String> {
+    let path = clips_dir()?.join(format!("cropbots-{}.gif", timestamp(miniquad::date::now())));
+    std::fs::write(&path, encode_gif(frames)?).map_err(|err| err.to_string())?;
+    Ok(path.display().to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_sequence(frames: &[ClipFrame]) -> Result<String, String> {
+    let dir = clips_dir()?.join(format!("cropbots-{}", timestamp(miniquad::date::now())));
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    for (index, frame) in frames.iter().enumerate() {
+        frame
+            .image
+            .save(dir.join(format!("frame-{index:04}.png")))
+            .map_err(|err| err.to_string())?;
+    }
+    Ok(dir.display().to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn clips_dir() -> Result<std::path::PathBuf, String> {
+    let dir = crate::helpers::cropbots_dir().ok_or("no home directory")?.join("clips");
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    Ok(dir)
+}
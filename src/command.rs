@@ -0,0 +1,370 @@
+use std::collections::VecDeque;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::crop::CropDatabase;
+use crate::entity::{Entity, EntityDatabase, EntityKind};
+use crate::farm::FarmState;
+use crate::item::{GroundItems, ItemDatabase};
+use crate::map::TileMap;
+
+/// Orders a robot can receive. Positions are plain numbers so messages round-trip
+/// through JSON unchanged whether they come from the local UI or a console script.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RobotCommand {
+    MoveTo { x: f32, y: f32 },
+    Till { tile_x: usize, tile_y: usize },
+    Harvest { tile_x: usize, tile_y: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandSource {
+    Local,
+    /// Typed at the console with `order`.
+    Script,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommandMessage {
+    pub id: u64,
+    pub robot: u64,
+    pub source: CommandSource,
+    pub command: RobotCommand,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CommandStatus {
+    Accepted,
+    Completed,
+    Failed { reason: String },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommandResult {
+    pub id: u64,
+    pub robot: u64,
+    pub source: CommandSource,
+    pub status: CommandStatus,
+}
+
+/// What Till and Harvest work on. Only there in the farm scene.
+pub struct FarmWork<'a> {
+    pub farm: &'a mut FarmState,
+    pub ground_items: &'a mut GroundItems,
+    pub items: &'a ItemDatabase,
+    pub crops: &'a CropDatabase,
+    pub ground_tile: u8,
+}
+
+/// An accepted order waiting on its robot to get there.
+struct InFlight {
+    id: u64,
+    robot: u64,
+    source: CommandSource,
+    command: RobotCommand,
+}
+
+pub struct CommandBus {
+    pending: VecDeque<CommandMessage>,
+    results: Vec<CommandResult>,
+    in_flight: Vec<InFlight>,
+    next_id: u64,
+}
+
+impl CommandBus {
+    pub fn new() -> Self {
+        Self {
+            pending: VecDeque::new(),
+            results: Vec::new(),
+            in_flight: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    pub fn send(&mut self, robot: u64, source: CommandSource, command: RobotCommand) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.push_back(CommandMessage {
+            id,
+            robot,
+            source,
+            command,
+        });
+        id
+    }
+
+    pub fn take_results(&mut self) -> Vec<CommandResult> {
+        std::mem::take(&mut self.results)
+    }
+
+    fn report(&mut self, id: u64, robot: u64, source: CommandSource, status: CommandStatus) {
+        self.results.push(CommandResult {
+            id,
+            robot,
+            source,
+            status,
+        });
+    }
+
+    /// Executes queued commands against the current scene's robots and reports
+    /// completions of long-running ones. Till and Harvest send the robot to the tile and do
+    /// the work once it arrives, so they fail off the farm where `farm` is None.
+    pub fn dispatch(
+        &mut self,
+        entities: &mut [Entity],
+        db: &EntityDatabase,
+        map: &mut TileMap,
+        mut farm: Option<FarmWork>,
+    ) {
+        while let Some(message) = self.pending.pop_front() {
+            let Some(ent) = entities.iter_mut().find(|ent| ent.instance.uid == message.robot) else {
+                self.report(message.id, message.robot, message.source, failed("robot not found"));
+                continue;
+            };
+            if db.entities[ent.instance.def].kind != EntityKind::Friend {
+                self.report(message.id, message.robot, message.source, failed("not a robot"));
+                continue;
+            }
+
+            let target = match message.command {
+                RobotCommand::MoveTo { x, y } => {
+                    let target = vec2(x, y);
+                    if !map.world_bounds().contains(target) {
+                        Err("target outside map")
+                    } else {
+                        Ok(target)
+                    }
+                }
+                RobotCommand::Till { tile_x, tile_y } | RobotCommand::Harvest { tile_x, tile_y }
+                    if tile_x >= map.width() || tile_y >= map.height() =>
+                {
+                    Err("tile outside map")
+                }
+                RobotCommand::Till { tile_x, tile_y } => match farm.as_ref() {
+                    None => Err("not on the farm"),
+                    Some(work) if !work.farm.soil.can_till(map, tile_x, tile_y, work.ground_tile) => {
+                        Err("no tillable tile")
+                    }
+                    Some(_) => Ok(tile_center(map, tile_x, tile_y)),
+                },
+                RobotCommand::Harvest { tile_x, tile_y } => match farm.as_ref() {
+                    None => Err("not on the farm"),
+                    Some(work) if !work.farm.crops.ripe(work.crops).any(|tile| tile == (tile_x, tile_y)) => {
+                        Err("no ripe crop")
+                    }
+                    Some(_) => Ok(tile_center(map, tile_x, tile_y)),
+                },
+            };
+            let target = match target {
+                Ok(target) => target,
+                Err(reason) => {
+                    self.report(message.id, message.robot, message.source, failed(reason));
+                    continue;
+                }
+            };
+            if let Some((previous, _)) = ent.instance.move_order.replace((message.id, target)) {
+                self.finish(previous, failed("superseded"));
+            }
+            self.report(message.id, message.robot, message.source, CommandStatus::Accepted);
+            self.in_flight.push(InFlight {
+                id: message.id,
+                robot: message.robot,
+                source: message.source,
+                command: message.command,
+            });
+        }
+
+        for ent in entities.iter_mut() {
+            let Some(id) = ent.instance.completed_order.take() else {
+                continue;
+            };
+            let Some(flight) = self.in_flight.iter().find(|flight| flight.id == id) else {
+                continue;
+            };
+            // The tile may have changed while the robot walked over.
+            let status = match (&flight.command, farm.as_mut()) {
+                (RobotCommand::MoveTo { .. }, _) => CommandStatus::Completed,
+                (RobotCommand::Till { tile_x, tile_y }, Some(work)) => {
+                    if work.farm.soil.till(map, *tile_x, *tile_y, work.ground_tile) {
+                        CommandStatus::Completed
+                    } else {
+                        failed("no tillable tile")
+                    }
+                }
+                (RobotCommand::Harvest { tile_x, tile_y }, Some(work)) => {
+                    match work.farm.crops.harvest(*tile_x, *tile_y, work.crops) {
+                        Some(produce) => {
+                            let pos = tile_center(map, *tile_x, *tile_y);
+                            work.ground_items.spawn(work.items, &produce.item, produce.count, pos);
+                            CommandStatus::Completed
+                        }
+                        None => failed("no ripe crop"),
+                    }
+                }
+                (_, None) => failed("not on the farm"),
+            };
+            self.finish(id, status);
+        }
+        // Robots that despawned mid-order never complete.
+        let mut lost = Vec::new();
+        self.in_flight.retain(|flight| {
+            let alive = entities.iter().any(|ent| ent.instance.uid == flight.robot);
+            if !alive {
+                lost.push((flight.id, flight.robot, flight.source));
+            }
+            alive
+        });
+        for (id, robot, source) in lost {
+            self.report(id, robot, source, failed("robot lost"));
+        }
+    }
+
+    fn finish(&mut self, id: u64, status: CommandStatus) {
+        let Some(index) = self.in_flight.iter().position(|flight| flight.id == id) else {
+            return;
+        };
+        let flight = self.in_flight.swap_remove(index);
+        self.report(flight.id, flight.robot, flight.source, status);
+    }
+}
+
+fn tile_center(map: &TileMap, x: usize, y: usize) -> Vec2 {
+    (vec2(x as f32, y as f32) + 0.5) * map.tile_size()
+}
+
+fn failed(reason: &str) -> CommandStatus {
+    CommandStatus::Failed {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::MovementRegistry;
+    use crate::farm::SoilLayer;
+    use crate::map::LayerKind;
+
+    const GROUND: u8 = 24;
+
+    fn scene(kind: EntityKind) -> (EntityDatabase, Vec<Entity>, TileMap) {
+        let db = EntityDatabase::with_test_entity("robot", kind);
+        let instance = db.spawn("robot", vec2(8.0, 8.0), &MovementRegistry::new()).unwrap();
+        let mut map = TileMap::new_deferred(8, 8, 16.0, vec2(16.0, 16.0), 0.0);
+        map.fill_layer(LayerKind::Background, GROUND);
+        (db, vec![Entity { instance }], map)
+    }
+
+    /// Stands the robot on its order's target, as the entity update would.
+    fn arrive(entities: &mut [Entity]) {
+        for ent in entities {
+            if let Some((id, target)) = ent.instance.move_order.take() {
+                ent.instance.pos = target;
+                ent.instance.completed_order = Some(id);
+            }
+        }
+    }
+
+    fn statuses(bus: &mut CommandBus) -> Vec<String> {
+        bus.take_results()
+            .into_iter()
+            .map(|result| match result.status {
+                CommandStatus::Accepted => "accepted".to_string(),
+                CommandStatus::Completed => "completed".to_string(),
+                CommandStatus::Failed { reason } => reason,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn move_to_completes_on_arrival() {
+        let (db, mut entities, mut map) = scene(EntityKind::Friend);
+        let mut bus = CommandBus::new();
+        let robot = entities[0].instance.uid;
+        bus.send(robot, CommandSource::Local, RobotCommand::MoveTo { x: 40.0, y: 40.0 });
+        bus.dispatch(&mut entities, &db, &mut map, None);
+        assert_eq!(statuses(&mut bus), ["accepted"]);
+        assert_eq!(entities[0].instance.move_order.map(|(_, target)| target), Some(vec2(40.0, 40.0)));
+
+        arrive(&mut entities);
+        bus.dispatch(&mut entities, &db, &mut map, None);
+        assert_eq!(statuses(&mut bus), ["completed"]);
+    }
+
+    #[test]
+    fn rejects_bad_targets() {
+        let (db, mut entities, mut map) = scene(EntityKind::Friend);
+        let mut bus = CommandBus::new();
+        let robot = entities[0].instance.uid;
+        bus.send(robot + 1, CommandSource::Local, RobotCommand::MoveTo { x: 8.0, y: 8.0 });
+        bus.send(robot, CommandSource::Local, RobotCommand::MoveTo { x: -1.0, y: 8.0 });
+        bus.send(robot, CommandSource::Script, RobotCommand::Till { tile_x: 8, tile_y: 0 });
+        bus.send(robot, CommandSource::Script, RobotCommand::Till { tile_x: 2, tile_y: 2 });
+        bus.dispatch(&mut entities, &db, &mut map, None);
+        assert_eq!(
+            statuses(&mut bus),
+            ["robot not found", "target outside map", "tile outside map", "not on the farm"]
+        );
+    }
+
+    #[test]
+    fn only_robots_take_orders() {
+        let (db, mut entities, mut map) = scene(EntityKind::Enemy);
+        let mut bus = CommandBus::new();
+        bus.send(entities[0].instance.uid, CommandSource::Local, RobotCommand::MoveTo { x: 8.0, y: 8.0 });
+        bus.dispatch(&mut entities, &db, &mut map, None);
+        assert_eq!(statuses(&mut bus), ["not a robot"]);
+    }
+
+    #[test]
+    fn till_turns_the_tile_once_the_robot_arrives() {
+        let (db, mut entities, mut map) = scene(EntityKind::Friend);
+        let mut farm = FarmState {
+            soil: SoilLayer::new(8, 8),
+            ..FarmState::default()
+        };
+        let mut ground_items = GroundItems::default();
+        let (items, crops) = (ItemDatabase::empty(), CropDatabase::empty());
+        let mut bus = CommandBus::new();
+        let robot = entities[0].instance.uid;
+        bus.send(robot, CommandSource::Script, RobotCommand::Till { tile_x: 2, tile_y: 3 });
+        bus.send(robot, CommandSource::Script, RobotCommand::Harvest { tile_x: 2, tile_y: 3 });
+        let work = FarmWork {
+            farm: &mut farm,
+            ground_items: &mut ground_items,
+            items: &items,
+            crops: &crops,
+            ground_tile: GROUND,
+        };
+        bus.dispatch(&mut entities, &db, &mut map, Some(work));
+        assert_eq!(statuses(&mut bus), ["accepted", "no ripe crop"]);
+        assert_eq!(entities[0].instance.move_order.map(|(_, target)| target), Some(vec2(40.0, 56.0)));
+
+        arrive(&mut entities);
+        let work = FarmWork {
+            farm: &mut farm,
+            ground_items: &mut ground_items,
+            items: &items,
+            crops: &crops,
+            ground_tile: GROUND,
+        };
+        bus.dispatch(&mut entities, &db, &mut map, Some(work));
+        assert_eq!(statuses(&mut bus), ["completed"]);
+        assert!(farm.soil.get(2, 3).tilled());
+    }
+
+    #[test]
+    fn lost_robots_fail_their_orders() {
+        let (db, mut entities, mut map) = scene(EntityKind::Friend);
+        let mut bus = CommandBus::new();
+        bus.send(entities[0].instance.uid, CommandSource::Local, RobotCommand::MoveTo { x: 8.0, y: 8.0 });
+        bus.dispatch(&mut entities, &db, &mut map, None);
+        entities.clear();
+        bus.dispatch(&mut entities, &db, &mut map, None);
+        assert_eq!(statuses(&mut bus), ["accepted", "robot lost"]);
+    }
+}
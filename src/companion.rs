@@ -0,0 +1,135 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use crate::entity::{
+    Entity, EntityContext, EntityDatabase, EntityDef, EntityInstance, EntityKind, MovementRegistry, SavedEntity, Target,
+};
+
+/// Entity def id of the companion the farm starts with.
+pub const COMPANION_ENTITY: &str = "sparkbot";
+
+/// Keeps an entity at the player's side: it trails them, spreads out from other friends, jumps
+/// back to them when left behind and goes after whatever last hurt them.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct CompanionDef {
+    /// Distance from the player it's content to stay within.
+    pub follow_distance: f32,
+    /// Further than this from the player and it teleports to them.
+    pub teleport_distance: f32,
+    /// Seconds of trying and failing to get closer, e.g. stuck behind a wall, before it
+    /// teleports.
+    pub stuck_time: f32,
+    /// How far from the player it will go after their attacker.
+    pub assist_range: f32,
+    /// Keeps this far from other friends.
+    pub separation: f32,
+}
+
+impl Default for CompanionDef {
+    fn default() -> Self {
+        Self {
+            follow_distance: 24.0,
+            teleport_distance: 220.0,
+            stuck_time: 1.5,
+            assist_range: 120.0,
+            separation: 14.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct CompanionState {
+    /// Seconds since it last got any closer to the player while trying to.
+    stuck: f32,
+    /// Closest it has got to the player since it last stopped.
+    closest: f32,
+}
+
+/// Steers a companion toward the first player or their attacker, overriding the velocity its
+/// behaviors chose. Does nothing while that player can't be targeted.
+pub fn run_companion(entity: &mut EntityInstance, def: &EntityDef, dt: f32, ctx: &EntityContext) {
+    let (Some(companion), Some(&player)) = (def.companion, ctx.players.first()) else {
+        return;
+    };
+    let center = def.world_hitbox(entity.pos).center();
+    let home = player.hitbox.center();
+    let distance = center.distance(home);
+
+    if distance > companion.teleport_distance || entity.companion.stuck >= companion.stuck_time {
+        entity.pos += home - center;
+        entity.vel = Vec2::ZERO;
+        entity.waypoints.clear();
+        entity.companion = CompanionState::default();
+        return;
+    }
+
+    let attacker = ctx.player_attacker.and_then(|uid| {
+        ctx.entities.iter().find(|candidate| {
+            candidate.id == uid && candidate.alive && candidate.hitbox.center().distance(home) <= companion.assist_range
+        })
+    });
+    let state = &mut entity.companion;
+    if let Some(attacker) = attacker {
+        state.stuck = 0.0;
+        state.closest = distance;
+        entity.current_target = Some(Target::Entity(*attacker));
+        entity.vel = (attacker.hitbox.center() - center).normalize_or_zero() * entity.speed;
+    } else if distance > companion.follow_distance {
+        if distance < state.closest - 1.0 || state.closest <= 0.0 {
+            state.closest = distance;
+            state.stuck = 0.0;
+        } else {
+            state.stuck += dt;
+        }
+        // Eases in over the last stretch so it doesn't overshoot and turn back.
+        let urgency = ((distance - companion.follow_distance) / companion.follow_distance.max(1.0)).clamp(0.3, 1.0);
+        entity.current_target = Some(Target::Player(player));
+        entity.vel = (home - center).normalize_or_zero() * entity.speed * urgency;
+    } else {
+        *state = CompanionState::default();
+        entity.current_target = Some(Target::Player(player));
+        entity.vel = Vec2::ZERO;
+    }
+
+    for other in &ctx.entities {
+        if other.id == entity.uid || !other.alive || other.kind != EntityKind::Friend {
+            continue;
+        }
+        let away = center - other.hitbox.center();
+        let gap = away.length();
+        if gap < companion.separation && gap > 0.001 {
+            entity.vel += away / gap * entity.speed * 0.5 * (1.0 - gap / companion.separation);
+        }
+    }
+}
+
+/// Companions in `entities`, to bring along into the next scene.
+pub fn carry(entities: &[Entity], db: &EntityDatabase) -> Vec<SavedEntity> {
+    entities
+        .iter()
+        .filter(|ent| db.entities[ent.instance.def].companion.is_some() && !ent.instance.is_dying())
+        .map(|ent| ent.to_saved(db))
+        .collect()
+}
+
+/// Puts companions carried over from the last scene at `pos`, in place of any the new scene
+/// brought back from a save or an earlier visit. Does nothing if none were carried.
+pub fn land(
+    entities: &mut Vec<Entity>,
+    db: &EntityDatabase,
+    registry: &MovementRegistry,
+    carried: &[SavedEntity],
+    pos: Vec2,
+) {
+    if carried.is_empty() {
+        return;
+    }
+    entities.retain(|ent| db.entities[ent.instance.def].companion.is_none());
+    for saved in carried {
+        if let Some(mut ent) = Entity::from_saved(db, saved, registry) {
+            let offset = ent.hitbox(db).center() - ent.instance.pos;
+            ent.instance.pos = pos - offset;
+            entities.push(ent);
+        }
+    }
+}
@@ -0,0 +1,601 @@
+use macroquad::prelude::*;
+use crate::font::{draw_text, measure_text};
+use macroquad::file::load_string;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::helpers::data_path;
+use crate::level::GrowthDef;
+use crate::player::PlayerStats;
+use crate::locale::Locale;
+use crate::sound::SoundChannel;
+use crate::transition::TransitionStyle;
+use crate::clip::ClipConfig;
+
+pub const CONFIG_PATH: &str = "src/config.yaml";
+const RELOAD_POLL_INTERVAL: f32 = 1.0;
+#[cfg(not(target_arch = "wasm32"))]
+const SETTINGS_FILE: &str = "settings.toml";
+#[cfg(target_arch = "wasm32")]
+const SETTINGS_STORAGE_KEY: &str = "cropbots:settings.toml";
+const SETTINGS_PANEL_WIDTH: f32 = 420.0;
+const SETTINGS_ROW_HEIGHT: f32 = 28.0;
+
+#[derive(Debug)]
+pub enum ConfigLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for ConfigLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigLoadError {}
+
+impl From<std::io::Error> for ConfigLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for ConfigLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+/// Gameplay tuning values. Keys missing from `config.yaml` keep their defaults.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    pub camera_drag: f32,
+    pub camera_fov: f32,
+    pub camera_dead_zone: f32,
+    pub move_deadzone: f32,
+    pub footstep_interval: f32,
+    pub item_pickup_radius: f32,
+    pub chop_range: f32,
+    pub chop_damage: f32,
+    pub chop_cooldown: f32,
+    /// Seconds the player can't be hurt again after taking damage.
+    pub player_iframes: f32,
+    pub tool_range: f32,
+    pub shop_range: f32,
+    /// How far the E prompt reaches for crops, and for structures with no range of their own.
+    pub interact_range: f32,
+    /// How close a rideable friend has to be to get on it.
+    pub mount_range: f32,
+    /// Minutes an expedition lasts before the way home closes.
+    pub expedition_minutes: f32,
+    /// Tiles from the arrival point the extraction beacon stands.
+    pub extraction_distance: f32,
+    /// Tiles around the player uncovered on the expedition as they walk.
+    pub explore_radius: f32,
+    /// Coins the first plot of extra farm land costs; each one after costs twice the last.
+    pub land_cost: u32,
+    /// Wipe played going between the farm and the expedition.
+    pub scene_transition: TransitionStyle,
+    /// Wipe played stepping in and out of buildings.
+    pub interior_transition: TransitionStyle,
+    /// The player's stats before upgrades.
+    pub player: PlayerStats,
+    /// How the player levels up from XP. Only `hp` is grown.
+    pub player_growth: GrowthDef,
+    pub entity_cull_pad: f32,
+    pub chunk_alloc_per_frame: usize,
+    pub chunk_rebuild_per_frame: usize,
+    pub scene_warm_budget: f32,
+    /// Bake foreground tiles into the background's chunk targets to save GPU memory, at the
+    /// cost of decals and crops drawing over them. Applies to scenes built after it changes.
+    pub chunk_composite_layers: bool,
+    pub clips: ClipConfig,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            camera_drag: 5.0,
+            camera_fov: 300.0,
+            camera_dead_zone: 12.0,
+            move_deadzone: 16.0,
+            footstep_interval: 0.2,
+            item_pickup_radius: 14.0,
+            chop_range: 28.0,
+            chop_damage: 1.0,
+            chop_cooldown: 0.35,
+            player_iframes: 0.6,
+            tool_range: 28.0,
+            shop_range: 32.0,
+            interact_range: 32.0,
+            mount_range: 24.0,
+            expedition_minutes: 5.0,
+            extraction_distance: 60.0,
+            explore_radius: 12.0,
+            land_cost: 500,
+            scene_transition: TransitionStyle::Iris,
+            interior_transition: TransitionStyle::Fade,
+            player: PlayerStats::default(),
+            player_growth: GrowthDef {
+                base_xp: 20.0,
+                xp_factor: 1.4,
+                max_level: 30,
+                stats: HashMap::from([("hp".to_string(), 2.0)]),
+            },
+            entity_cull_pad: 96.0,
+            chunk_alloc_per_frame: 6,
+            chunk_rebuild_per_frame: 8,
+            scene_warm_budget: 0.006,
+            chunk_composite_layers: false,
+            clips: ClipConfig::default(),
+        }
+    }
+}
+
+impl GameConfig {
+    pub async fn load_from(path: impl AsRef<Path>) -> Result<Self, ConfigLoadError> {
+        let path = path.as_ref();
+        let raw_str = if cfg!(target_arch = "wasm32") {
+            load_string(&data_path(&path.to_string_lossy()))
+                .await
+                .map_err(|err| ConfigLoadError::Io(std::io::Error::other(err.to_string())))?
+        } else {
+            std::fs::read_to_string(path)?
+        };
+        Ok(serde_yaml::from_str(&raw_str)?)
+    }
+}
+
+/// Polls `config.yaml` for changes in native debug builds so values can be tuned live.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    timer: f32,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let modified = modified_time(&path);
+        Self {
+            path,
+            modified,
+            timer: RELOAD_POLL_INTERVAL,
+        }
+    }
+
+    pub fn enabled() -> bool {
+        cfg!(debug_assertions) && !cfg!(target_arch = "wasm32")
+    }
+
+    /// Returns the reloaded config once the file's modification time changes.
+    pub fn poll(&mut self, dt: f32) -> Option<Result<GameConfig, ConfigLoadError>> {
+        if !Self::enabled() {
+            return None;
+        }
+        self.timer -= dt;
+        if self.timer > 0.0 {
+            return None;
+        }
+        self.timer = RELOAD_POLL_INTERVAL;
+        let modified = modified_time(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        let result = std::fs::read_to_string(&self.path)
+            .map_err(ConfigLoadError::from)
+            .and_then(|raw| Ok(serde_yaml::from_str(&raw)?));
+        Some(result)
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Write(toml::ser::Error),
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Parse(err) => write!(f, "toml error: {err}"),
+            Self::Write(err) => write!(f, "toml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl From<std::io::Error> for SettingsError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for SettingsError {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl From<toml::ser::Error> for SettingsError {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::Write(err)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    /// Only read when the window opens, so changes apply after a restart.
+    pub vsync: bool,
+    /// World resolution relative to the window; below 1 the scene is drawn to a smaller render
+    /// target and scaled up.
+    pub render_scale: f32,
+    /// Multiplier on the particle budget.
+    pub particle_density: f32,
+    pub screen_shake: f32,
+    /// Floating numbers over anything that takes damage or heals.
+    pub damage_numbers: bool,
+    /// Draws the map's chunks as meshes built on the CPU instead of baking them into render
+    /// targets, which are costly on WebGL and weak GPUs.
+    pub chunk_meshes: bool,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            vsync: true,
+            render_scale: 1.0,
+            particle_density: 1.0,
+            screen_shake: 1.0,
+            damage_numbers: true,
+            chunk_meshes: cfg!(target_arch = "wasm32"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub ui: f32,
+    pub sfx: f32,
+    pub ambient: f32,
+    pub music: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            ui: 1.0,
+            sfx: 1.0,
+            ambient: 1.0,
+            music: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    /// Minutes between farm autosaves; zero turns the timer off.
+    pub autosave_minutes: f32,
+}
+
+impl Default for GameplaySettings {
+    fn default() -> Self {
+        Self { autosave_minutes: 5.0 }
+    }
+}
+
+/// Player preferences kept in `~/.cropbots/settings.toml`, or browser storage on wasm, unlike
+/// the shipped tuning in [`GameConfig`]. Missing keys keep their defaults.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub graphics: GraphicsSettings,
+    pub audio: AudioSettings,
+    pub gameplay: GameplaySettings,
+}
+
+impl Settings {
+    /// Reads the settings file, keeping the defaults if it's missing or broken.
+    pub fn load() -> Self {
+        let Some(raw) = read_settings() else {
+            return Self::default();
+        };
+        match toml::from_str::<Self>(&raw) {
+            Ok(settings) => settings.clamped(),
+            Err(err) => {
+                eprintln!("settings load failed: {err}");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        write_settings(&toml::to_string_pretty(self)?)
+    }
+
+    pub fn channel_volumes(&self) -> [(SoundChannel, f32); 4] {
+        [
+            (SoundChannel::Ui, self.audio.ui),
+            (SoundChannel::Sfx, self.audio.sfx),
+            (SoundChannel::Ambient, self.audio.ambient),
+            (SoundChannel::Music, self.audio.music),
+        ]
+    }
+
+    fn clamped(mut self) -> Self {
+        for row in SETTING_ROWS {
+            row.step(&mut self, 0.0);
+        }
+        self
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_settings() -> Option<String> {
+    std::fs::read_to_string(crate::helpers::cropbots_dir()?.join(SETTINGS_FILE)).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_settings(raw: &str) -> Result<(), SettingsError> {
+    let dir = crate::helpers::cropbots_dir().ok_or_else(|| std::io::Error::other("no home directory"))?;
+    std::fs::create_dir_all(&dir)?;
+    Ok(std::fs::write(dir.join(SETTINGS_FILE), raw)?)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_settings() -> Option<String> {
+    crate::helpers::wasm_storage_get_item(SETTINGS_STORAGE_KEY)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_settings(raw: &str) -> Result<(), SettingsError> {
+    Ok(crate::helpers::wasm_storage_set_item(SETTINGS_STORAGE_KEY, raw)?)
+}
+
+#[derive(Clone, Copy)]
+enum SettingRow {
+    Vsync,
+    RenderScale,
+    ParticleDensity,
+    ScreenShake,
+    DamageNumbers,
+    ChunkMeshes,
+    Volume(SoundChannel),
+    Autosave,
+}
+
+const SETTING_ROWS: [SettingRow; 11] = [
+    SettingRow::Vsync,
+    SettingRow::RenderScale,
+    SettingRow::ParticleDensity,
+    SettingRow::ScreenShake,
+    SettingRow::DamageNumbers,
+    SettingRow::ChunkMeshes,
+    SettingRow::Volume(SoundChannel::Ui),
+    SettingRow::Volume(SoundChannel::Sfx),
+    SettingRow::Volume(SoundChannel::Ambient),
+    SettingRow::Volume(SoundChannel::Music),
+    SettingRow::Autosave,
+];
+
+impl SettingRow {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Vsync => "VSync (restart)",
+            Self::RenderScale => "Render scale",
+            Self::ParticleDensity => "Particle density",
+            Self::ScreenShake => "Screen shake",
+            Self::DamageNumbers => "Damage numbers",
+            Self::ChunkMeshes => "Map renderer",
+            Self::Volume(SoundChannel::Ui) => "UI volume",
+            Self::Volume(SoundChannel::Sfx) => "Effects volume",
+            Self::Volume(SoundChannel::Ambient) => "Ambient volume",
+            Self::Volume(SoundChannel::Music) => "Music volume",
+            Self::Autosave => "Autosave (minutes)",
+        }
+    }
+
+    fn value(self, settings: &Settings, locale: &Locale) -> String {
+        let percent = |value: f32| format!("{}%", locale.format_int((value * 100.0).round() as i64));
+        match self {
+            Self::Vsync if settings.graphics.vsync => "on".to_string(),
+            Self::Vsync => "off".to_string(),
+            Self::RenderScale => percent(settings.graphics.render_scale),
+            Self::ParticleDensity => percent(settings.graphics.particle_density),
+            Self::ScreenShake => percent(settings.graphics.screen_shake),
+            Self::DamageNumbers if settings.graphics.damage_numbers => "on".to_string(),
+            Self::DamageNumbers => "off".to_string(),
+            Self::ChunkMeshes if settings.graphics.chunk_meshes => "meshes".to_string(),
+            Self::ChunkMeshes => "textures".to_string(),
+            Self::Volume(channel) => percent(self.volume(settings, channel)),
+            Self::Autosave if settings.gameplay.autosave_minutes <= 0.0 => "off".to_string(),
+            Self::Autosave => locale.format_int(settings.gameplay.autosave_minutes as i64),
+        }
+    }
+
+    fn volume(self, settings: &Settings, channel: SoundChannel) -> f32 {
+        settings
+            .channel_volumes()
+            .iter()
+            .find(|(other, _)| *other == channel)
+            .map_or(1.0, |(_, volume)| *volume)
+    }
+
+    /// Moves the setting `direction` notches and keeps it in range; a zero step only clamps.
+    fn step(self, settings: &mut Settings, direction: f32) {
+        let notch = |value: &mut f32, step: f32, min: f32, max: f32| {
+            *value = ((*value / step).round() + direction) * step;
+            *value = value.clamp(min, max);
+        };
+        match self {
+            Self::Vsync if direction != 0.0 => settings.graphics.vsync = !settings.graphics.vsync,
+            Self::Vsync => {}
+            Self::RenderScale => notch(&mut settings.graphics.render_scale, 0.25, 0.25, 1.0),
+            Self::ParticleDensity => notch(&mut settings.graphics.particle_density, 0.25, 0.0, 2.0),
+            Self::ScreenShake => notch(&mut settings.graphics.screen_shake, 0.25, 0.0, 2.0),
+            Self::DamageNumbers if direction != 0.0 => {
+                settings.graphics.damage_numbers = !settings.graphics.damage_numbers;
+            }
+            Self::DamageNumbers => {}
+            Self::ChunkMeshes if direction != 0.0 => settings.graphics.chunk_meshes = !settings.graphics.chunk_meshes,
+            Self::ChunkMeshes => {}
+            Self::Volume(channel) => {
+                let audio = &mut settings.audio;
+                let volume = match channel {
+                    SoundChannel::Ui => &mut audio.ui,
+                    SoundChannel::Sfx => &mut audio.sfx,
+                    SoundChannel::Ambient => &mut audio.ambient,
+                    SoundChannel::Music => &mut audio.music,
+                };
+                notch(volume, 0.1, 0.0, 1.0);
+            }
+            Self::Autosave => notch(&mut settings.gameplay.autosave_minutes, 1.0, 0.0, 60.0),
+        }
+    }
+}
+
+/// Settings panel toggled with F10. Up/down picks a row and left/right, or clicking the
+/// arrows, changes it; the file is written when the panel closes.
+#[derive(Default)]
+pub struct SettingsUi {
+    open: bool,
+    selected: usize,
+    dirty: bool,
+}
+
+impl SettingsUi {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    pub fn close(&mut self, settings: &Settings) {
+        self.open = false;
+        if std::mem::take(&mut self.dirty)
+            && let Err(err) = settings.save()
+        {
+            eprintln!("settings save failed: {err}");
+        }
+    }
+
+    /// Returns true when a setting changed this frame.
+    pub fn update(&mut self, settings: &mut Settings) -> bool {
+        if !self.open {
+            return false;
+        }
+        if is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::F10) {
+            self.close(settings);
+            return false;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = (self.selected + SETTING_ROWS.len() - 1) % SETTING_ROWS.len();
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.selected = (self.selected + 1) % SETTING_ROWS.len();
+        }
+        let mut step = (self.selected, 0.0);
+        if is_key_pressed(KeyCode::Left) {
+            step.1 = -1.0;
+        } else if is_key_pressed(KeyCode::Right) {
+            step.1 = 1.0;
+        } else if is_mouse_button_pressed(MouseButton::Left) {
+            let mouse = vec2(mouse_position().0, mouse_position().1);
+            let panel = settings_panel_rect();
+            for i in 0..SETTING_ROWS.len() {
+                let (less, more) = arrow_rects(settings_row_rect(panel, i));
+                if less.contains(mouse) {
+                    step = (i, -1.0);
+                } else if more.contains(mouse) {
+                    step = (i, 1.0);
+                }
+            }
+        }
+        let (row, direction) = step;
+        if direction == 0.0 {
+            return false;
+        }
+        self.selected = row;
+        SETTING_ROWS[row].step(settings, direction);
+        self.dirty = true;
+        true
+    }
+
+    pub fn draw(&self, settings: &Settings, locale: &Locale) {
+        if !self.open {
+            return;
+        }
+        let panel = settings_panel_rect();
+        let mouse = vec2(mouse_position().0, mouse_position().1);
+        draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.05, 0.05, 0.08, 0.9));
+        draw_rectangle_lines(panel.x, panel.y, panel.w, panel.h, 2.0, GRAY);
+        draw_text("Settings", panel.x + 12.0, panel.y + 28.0, 24.0, WHITE);
+        for (i, row) in SETTING_ROWS.iter().enumerate() {
+            let rect = settings_row_rect(panel, i);
+            if i == self.selected {
+                draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(1.0, 1.0, 1.0, 0.1));
+            }
+            draw_text(row.label(), rect.x + 6.0, rect.y + rect.h * 0.7, 18.0, WHITE);
+            let (less, more) = arrow_rects(rect);
+            for (arrow, label) in [(less, "<"), (more, ">")] {
+                let color = if arrow.contains(mouse) { WHITE } else { GRAY };
+                draw_text(label, arrow.x + 6.0, arrow.y + arrow.h * 0.75, 20.0, color);
+            }
+            let value = row.value(settings, locale);
+            let size = measure_text(&value, 18.0);
+            draw_text(
+                &value,
+                less.right() + (more.x - less.right() - size.width) * 0.5,
+                rect.y + rect.h * 0.7,
+                18.0,
+                LIGHTGRAY,
+            );
+        }
+        draw_text("arrows or click to change, F10 to close", panel.x + 12.0, panel.bottom() - 12.0, 16.0, GRAY);
+    }
+}
+
+fn settings_panel_rect() -> Rect {
+    let height = 84.0 + SETTING_ROWS.len() as f32 * SETTINGS_ROW_HEIGHT;
+    Rect::new(
+        (screen_width() - SETTINGS_PANEL_WIDTH) * 0.5,
+        (screen_height() - height) * 0.5,
+        SETTINGS_PANEL_WIDTH,
+        height,
+    )
+}
+
+fn settings_row_rect(panel: Rect, index: usize) -> Rect {
+    Rect::new(
+        panel.x + 12.0,
+        panel.y + 44.0 + index as f32 * SETTINGS_ROW_HEIGHT,
+        panel.w - 24.0,
+        SETTINGS_ROW_HEIGHT - 2.0,
+    )
+}
+
+/// The decrease and increase arrows at the right end of a row, around its value.
+fn arrow_rects(row: Rect) -> (Rect, Rect) {
+    let more = Rect::new(row.right() - 24.0, row.y, 24.0, row.h);
+    let less = Rect::new(more.x - 96.0, row.y, 24.0, row.h);
+    (less, more)
+}
@@ -0,0 +1,202 @@
+use macroquad::prelude::*;
+use crate::command::RobotCommand;
+use crate::difficulty::{Difficulty, DifficultyPreset};
+use crate::font::draw_text;
+use crate::net::DEFAULT_PORT;
+use std::collections::VecDeque;
+
+const MAX_LINES: usize = 12;
+const LINE_HEIGHT: f32 = 18.0;
+
+/// Drop-down debug console toggled with the backtick key.
+pub struct Console {
+    open: bool,
+    input: String,
+    lines: VecDeque<String>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            input: String::new(),
+            lines: VecDeque::new(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        if self.lines.len() == MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Handles the toggle key and typing. Returns a line when Enter is pressed.
+    pub fn update(&mut self) -> Option<String> {
+        if is_key_pressed(KeyCode::GraveAccent) {
+            self.open = !self.open;
+            self.input.clear();
+            while get_char_pressed().is_some() {}
+            return None;
+        }
+        if !self.open {
+            return None;
+        }
+
+        while let Some(ch) = get_char_pressed() {
+            if !ch.is_control() && ch != '`' {
+                self.input.push(ch);
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.input.pop();
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            self.open = false;
+            self.input.clear();
+            return None;
+        }
+        if is_key_pressed(KeyCode::Enter) && !self.input.trim().is_empty() {
+            let line = std::mem::take(&mut self.input);
+            self.print(format!("> {line}"));
+            return Some(line);
+        }
+        None
+    }
+
+    pub fn draw(&self) {
+        if !self.open {
+            return;
+        }
+        let height = (MAX_LINES as f32 + 1.5) * LINE_HEIGHT;
+        draw_rectangle(0.0, 0.0, screen_width(), height, Color::new(0.0, 0.0, 0.0, 0.75));
+        for (i, line) in self.lines.iter().enumerate() {
+            draw_text(line, 8.0, (i as f32 + 1.0) * LINE_HEIGHT, 18.0, LIGHTGRAY);
+        }
+        draw_text(
+            &format!("> {}_", self.input),
+            8.0,
+            height - LINE_HEIGHT * 0.4,
+            18.0,
+            WHITE,
+        );
+    }
+}
+
+pub enum ConsoleCommand {
+    BenchParticles { count: usize },
+    BenchEntities { def: String, count: usize },
+    Language { id: String },
+    Sleep,
+    Difficulty(Difficulty),
+    Host { port: u16 },
+    Join { addr: String },
+    Leave,
+    /// Brings player two of local co-op in, or sends them off.
+    LocalCoop { join: bool },
+    /// Sends a robot, by uid, a command as a script would.
+    Order { robot: u64, command: RobotCommand },
+    /// Plays back the last few seconds.
+    Replay,
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["bench", "particles", count] => Ok(Self::BenchParticles {
+                count: parse_count(count)?,
+            }),
+            ["bench", "entities", def, count] => Ok(Self::BenchEntities {
+                def: def.to_string(),
+                count: parse_count(count)?,
+            }),
+            ["lang", id] => Ok(Self::Language { id: id.to_string() }),
+            ["lang", ..] => Err("usage: lang <id>".to_string()),
+            ["sleep"] => Ok(Self::Sleep),
+            ["difficulty", "custom", health, damage, spawns] => Ok(Self::Difficulty(Difficulty::custom(
+                parse_multiplier(health)?,
+                parse_multiplier(damage)?,
+                parse_multiplier(spawns)?,
+            ))),
+            ["difficulty", preset] if *preset != "custom" => DifficultyPreset::parse(preset)
+                .map(|preset| Self::Difficulty(Difficulty::preset(preset)))
+                .ok_or_else(|| format!("unknown difficulty '{preset}'")),
+            ["difficulty", ..] => Err(
+                "usage: difficulty easy|normal|hard | difficulty custom <health> <damage> <spawns>".to_string(),
+            ),
+            ["host"] => Ok(Self::Host { port: DEFAULT_PORT }),
+            ["host", port] => port
+                .parse()
+                .map(|port| Self::Host { port })
+                .map_err(|_| format!("expected a port, got '{port}'")),
+            ["join", addr] => Ok(Self::Join { addr: addr.to_string() }),
+            ["leave"] => Ok(Self::Leave),
+            ["replay"] => Ok(Self::Replay),
+            ["coop", "join"] => Ok(Self::LocalCoop { join: true }),
+            ["coop", "leave"] => Ok(Self::LocalCoop { join: false }),
+            ["coop", ..] => Err("usage: coop join | coop leave".to_string()),
+            ["order", robot, "move", x, y] => Ok(Self::Order {
+                robot: parse_uid(robot)?,
+                command: RobotCommand::MoveTo {
+                    x: parse_coord(x)?,
+                    y: parse_coord(y)?,
+                },
+            }),
+            ["order", robot, "till", x, y] => Ok(Self::Order {
+                robot: parse_uid(robot)?,
+                command: RobotCommand::Till {
+                    tile_x: parse_tile(x)?,
+                    tile_y: parse_tile(y)?,
+                },
+            }),
+            ["order", robot, "harvest", x, y] => Ok(Self::Order {
+                robot: parse_uid(robot)?,
+                command: RobotCommand::Harvest {
+                    tile_x: parse_tile(x)?,
+                    tile_y: parse_tile(y)?,
+                },
+            }),
+            ["order", ..] => Err(
+                "usage: order <robot> move <x> <y> | order <robot> till|harvest <tile x> <tile y>".to_string(),
+            ),
+            ["host" | "join", ..] => Err("usage: host [port] | join <address[:port]> | leave".to_string()),
+            ["bench", ..] => Err("usage: bench particles <n> | bench entities <def> <n>".to_string()),
+            _ => Err(format!("unknown command '{}'", line.trim())),
+        }
+    }
+}
+
+fn parse_multiplier(raw: &str) -> Result<f32, String> {
+    raw.parse::<f32>()
+        .ok()
+        .filter(|multiplier| multiplier.is_finite() && *multiplier >= 0.0)
+        .ok_or_else(|| format!("expected a multiplier, got '{raw}'"))
+}
+
+fn parse_uid(raw: &str) -> Result<u64, String> {
+    raw.parse().map_err(|_| format!("expected a robot id, got '{raw}'"))
+}
+
+fn parse_coord(raw: &str) -> Result<f32, String> {
+    raw.parse::<f32>()
+        .ok()
+        .filter(|coord| coord.is_finite())
+        .ok_or_else(|| format!("expected a position, got '{raw}'"))
+}
+
+fn parse_tile(raw: &str) -> Result<usize, String> {
+    raw.parse().map_err(|_| format!("expected a tile, got '{raw}'"))
+}
+
+fn parse_count(raw: &str) -> Result<usize, String> {
+    raw.parse::<usize>()
+        .ok()
+        .filter(|&count| count > 0)
+        .ok_or_else(|| format!("expected a positive count, got '{raw}'"))
+}
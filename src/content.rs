@@ -0,0 +1,240 @@
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use crate::entity::SavedEntity;
+use crate::helpers::data_path;
+use crate::item::{GroundItem, Inventory, ItemDatabase};
+use crate::storage::Chest;
+
+/// Version each content pack had when a save was written, keyed by pack name.
+pub type ContentVersions = BTreeMap<String, u32>;
+
+/// Saves made before packs were versioned are treated as this version.
+const BASELINE_VERSION: u32 = 1;
+
+/// Rewrites save data made against one pack version so it matches the next.
+pub type MigrationHook = Box<dyn Fn(&mut SavedContent)>;
+
+#[derive(Debug)]
+pub enum ContentLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for ContentLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ContentLoadError {}
+
+impl From<std::io::Error> for ContentLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for ContentLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+/// The parts of a save that reference content ids.
+pub struct SavedContent {
+    pub ground_items: Vec<GroundItem>,
+    pub chests: Vec<Chest>,
+    pub entities: Vec<SavedEntity>,
+}
+
+impl SavedContent {
+    /// Chest contents and whatever saved entities carry.
+    fn inventories_mut(&mut self) -> impl Iterator<Item = &mut Inventory> {
+        self.chests
+            .iter_mut()
+            .map(|chest| &mut chest.inventory)
+            .chain(self.entities.iter_mut().map(|entity| &mut entity.inventory))
+    }
+
+    pub fn rename(&mut self, pack: &str, from: &str, to: &str) {
+        match pack {
+            "item" => {
+                for item in self.ground_items.iter_mut().filter(|item| item.item == from) {
+                    item.item = to.to_string();
+                }
+                for inventory in self.inventories_mut() {
+                    inventory.retain(|stack| {
+                        if stack.item == from {
+                            stack.item = to.to_string();
+                        }
+                        true
+                    });
+                }
+            }
+            "entity" => {
+                for entity in self.entities.iter_mut().filter(|entity| entity.def == from) {
+                    entity.def = to.to_string();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn remove(&mut self, pack: &str, id: &str) {
+        match pack {
+            "item" => {
+                self.ground_items.retain(|item| item.item != id);
+                for inventory in self.inventories_mut() {
+                    inventory.retain(|stack| stack.item != id);
+                }
+            }
+            "entity" => self.entities.retain(|entity| entity.def != id),
+            _ => {}
+        }
+    }
+
+    /// Drops references to content that no longer exists. Returns one warning per missing id.
+    pub fn drop_missing(&mut self, items: &ItemDatabase) -> Vec<String> {
+        let mut missing: BTreeMap<String, usize> = BTreeMap::new();
+        self.ground_items.retain(|item| {
+            let known = items.get(&item.item).is_some();
+            if !known {
+                *missing.entry(item.item.clone()).or_default() += 1;
+            }
+            known
+        });
+        let mut stored: BTreeMap<String, u32> = BTreeMap::new();
+        for inventory in self.inventories_mut() {
+            inventory.retain(|stack| {
+                let known = items.get(&stack.item).is_some();
+                if !known {
+                    *stored.entry(stack.item.clone()).or_default() += stack.count;
+                }
+                known
+            });
+        }
+        missing
+            .into_iter()
+            .map(|(id, count)| format!("dropped {count} ground item(s) of unknown item '{id}'"))
+            .chain(
+                stored
+                    .into_iter()
+                    .map(|(id, count)| format!("dropped {count} stored item(s) of unknown item '{id}'")),
+            )
+            .collect()
+    }
+}
+
+/// Current content pack versions plus the hooks that upgrade older saves.
+pub struct ContentManifest {
+    versions: ContentVersions,
+    migrations: Vec<(String, u32, MigrationHook)>,
+}
+
+impl ContentManifest {
+    pub fn empty() -> Self {
+        Self {
+            versions: ContentVersions::new(),
+            migrations: Vec::new(),
+        }
+    }
+
+    pub async fn load_from(path: impl AsRef<Path>) -> Result<Self, ContentLoadError> {
+        let path = path.as_ref();
+        let raw_str = if cfg!(target_arch = "wasm32") {
+            load_string(&data_path(&path.to_string_lossy()))
+                .await
+                .map_err(|err| ContentLoadError::Io(std::io::Error::other(err.to_string())))?
+        } else {
+            std::fs::read_to_string(path)?
+        };
+        let raw: BTreeMap<String, ContentPackFile> = serde_yaml::from_str(&raw_str)?;
+
+        let mut manifest = Self::empty();
+        for (pack, file) in raw {
+            manifest.versions.insert(pack.clone(), file.version.max(BASELINE_VERSION));
+            for step in file.migrations.unwrap_or_default() {
+                let hook_pack = pack.clone();
+                let rename = step.rename.unwrap_or_default();
+                let remove = step.remove.unwrap_or_default();
+                manifest.register_migration(
+                    &pack,
+                    step.from,
+                    Box::new(move |content: &mut SavedContent| {
+                        for (from, to) in &rename {
+                            content.rename(&hook_pack, from, to);
+                        }
+                        for id in &remove {
+                            content.remove(&hook_pack, id);
+                        }
+                    }),
+                );
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn versions(&self) -> &ContentVersions {
+        &self.versions
+    }
+
+    /// Registers a hook run on saves made against `pack` at `from_version`.
+    pub fn register_migration(&mut self, pack: &str, from_version: u32, hook: MigrationHook) {
+        self.migrations.push((pack.to_string(), from_version, hook));
+    }
+
+    /// Steps `content` from the `saved` pack versions up to the current ones, one
+    /// version at a time. Returns warnings for gaps it could not bridge.
+    pub fn migrate(&self, saved: &ContentVersions, content: &mut SavedContent) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (pack, &current) in &self.versions {
+            let mut version = saved.get(pack).copied().unwrap_or(BASELINE_VERSION);
+            if version > current {
+                warnings.push(format!(
+                    "save uses {pack} content v{version}, newer than installed v{current}"
+                ));
+                continue;
+            }
+            while version < current {
+                let mut ran = false;
+                for (_, _, hook) in self
+                    .migrations
+                    .iter()
+                    .filter(|(hook_pack, from, _)| hook_pack == pack && *from == version)
+                {
+                    hook(content);
+                    ran = true;
+                }
+                if !ran {
+                    warnings.push(format!("no {pack} migration from v{version}"));
+                }
+                version += 1;
+            }
+        }
+        for pack in saved.keys().filter(|pack| !self.versions.contains_key(*pack)) {
+            warnings.push(format!("save references unknown content pack '{pack}'"));
+        }
+        warnings
+    }
+}
+
+#[derive(Deserialize)]
+struct ContentPackFile {
+    version: u32,
+    #[serde(default)]
+    migrations: Option<Vec<ContentMigrationFile>>,
+}
+
+#[derive(Deserialize)]
+struct ContentMigrationFile {
+    from: u32,
+    #[serde(default)]
+    rename: Option<HashMap<String, String>>,
+    #[serde(default)]
+    remove: Option<Vec<String>>,
+}
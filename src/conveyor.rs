@@ -0,0 +1,140 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::farm::FarmState;
+use crate::item::{GroundItems, ItemDatabase};
+use crate::map::StructureDef;
+
+/// How fast items riding a belt slide toward its middle line, per second.
+const MERGE_RATE: f32 = 8.0;
+const CHEVRON_COLOR: Color = Color::new(1.0, 0.9, 0.5, 0.5);
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConveyorDir {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl ConveyorDir {
+    fn vec(self) -> Vec2 {
+        match self {
+            Self::Up => vec2(0.0, -1.0),
+            Self::Down => vec2(0.0, 1.0),
+            Self::Left => vec2(-1.0, 0.0),
+            Self::Right => vec2(1.0, 0.0),
+        }
+    }
+}
+
+/// A belt that carries dropped items along, from `conveyor` in a structure's json.
+#[derive(Clone, Copy, Deserialize)]
+pub struct ConveyorDef {
+    pub dir: ConveyorDir,
+    /// Tiles a second.
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+}
+
+fn default_speed() -> f32 {
+    1.5
+}
+
+/// What a built structure turns a tile into, as far as items on belts care.
+enum Cell {
+    Belt(ConveyorDef),
+    /// A storage structure, by the tile its chest is kept under.
+    Chest(usize, usize),
+    Blocked,
+}
+
+fn cells(farm: &FarmState, structures: &[StructureDef]) -> HashMap<(usize, usize), Cell> {
+    let mut cells = HashMap::new();
+    for built in &farm.built {
+        let Some(def) = structures.iter().find(|def| def.id == built.id) else {
+            continue;
+        };
+        for y in built.y..built.y + def.structure.height() {
+            for x in built.x..built.x + def.structure.width() {
+                let cell = match def.conveyor {
+                    Some(belt) => Cell::Belt(belt),
+                    None if def.storage_slots > 0 => Cell::Chest(built.x, built.y),
+                    None => Cell::Blocked,
+                };
+                cells.insert((x, y), cell);
+            }
+        }
+    }
+    cells
+}
+
+/// Moves items lying on belts along them. Items coming in from the side merge into the
+/// belt's line; one reaching a chest goes in if it fits, and one reaching anything else built
+/// waits at the edge. Items on a belt don't despawn.
+pub fn update(
+    dt: f32,
+    farm: &mut FarmState,
+    structures: &[StructureDef],
+    ground_items: &mut GroundItems,
+    item_db: &ItemDatabase,
+    tile_size: f32,
+) {
+    let cells = cells(farm, structures);
+    if !cells.values().any(|cell| matches!(cell, Cell::Belt(_))) {
+        return;
+    }
+    let tile_of = |pos: Vec2| ((pos.x / tile_size).max(0.0) as usize, (pos.y / tile_size).max(0.0) as usize);
+    ground_items.retain(|item| {
+        let pos = vec2(item.x, item.y);
+        let tile = tile_of(pos);
+        let Some(Cell::Belt(belt)) = cells.get(&tile) else {
+            return true;
+        };
+        item.despawn_in += dt;
+        let dir = belt.dir.vec();
+        let middle = (vec2(tile.0 as f32, tile.1 as f32) + 0.5) * tile_size;
+        let across = (middle - pos) * dir.perp().abs();
+        let mut next = pos + dir * belt.speed * tile_size * dt + across * (MERGE_RATE * dt).min(1.0);
+        let next_tile = tile_of(next + dir * tile_size * 0.25);
+        match cells.get(&next_tile) {
+            Some(Cell::Chest(x, y)) if next_tile != tile => {
+                if let Some(chest) = farm.chests.get_mut(*x, *y) {
+                    item.count = chest.inventory.add(item_db, &item.item, item.count);
+                }
+                if item.count == 0 {
+                    return false;
+                }
+                next = pos;
+            }
+            Some(Cell::Blocked) if next_tile != tile => next = pos,
+            _ => {}
+        }
+        item.x = next.x;
+        item.y = next.y;
+        true
+    });
+}
+
+/// Draws chevrons crawling along each belt inside `view`, in world space.
+pub fn draw(farm: &FarmState, structures: &[StructureDef], view: Rect, tile_size: f32) {
+    let phase = (get_time() as f32 * 1.5).fract();
+    for (&(x, y), cell) in &cells(farm, structures) {
+        let Cell::Belt(belt) = cell else {
+            continue;
+        };
+        let middle = (vec2(x as f32, y as f32) + 0.5) * tile_size;
+        if !view.contains(middle) {
+            continue;
+        }
+        let dir = belt.dir.vec();
+        let side = dir.perp() * tile_size * 0.25;
+        for step in [0.0, 0.5] {
+            let tip = middle + dir * tile_size * ((phase + step).fract() - 0.5);
+            let back = tip - dir * tile_size * 0.2;
+            draw_line(back.x + side.x, back.y + side.y, tip.x, tip.y, 1.0, CHEVRON_COLOR);
+            draw_line(back.x - side.x, back.y - side.y, tip.x, tip.y, 1.0, CHEVRON_COLOR);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use macroquad::prelude::*;
+use crate::entity::PlayerTarget;
+use crate::gamepad::Gamepad;
+use crate::map::TileMap;
+use crate::player::{Player, PlayerInput};
+
+/// Player two is pulled back beside player one once this far away, which also carries them
+/// along through scene changes.
+const LEASH_DISTANCE: f32 = 600.0;
+
+/// Local co-op: a second player on a gamepad, or on the same keyboard with the arrow keys and
+/// right shift to dash, joining and leaving from the console. Enemies go after whichever player
+/// is nearer and hurt each separately; the camera follows the pair.
+#[derive(Default)]
+pub struct LocalCoop {
+    player: Option<Player>,
+    /// Opened when player two first joins.
+    gamepad: Option<Gamepad>,
+}
+
+impl LocalCoop {
+    /// Brings in player two beside player one. Returns false if they're already in.
+    pub fn join(&mut self, one: &Player) -> bool {
+        if self.player.is_some() {
+            return false;
+        }
+        self.player = Some(one.remote(one.position()));
+        self.gamepad.get_or_insert_with(Gamepad::open);
+        true
+    }
+
+    /// Returns false if player two wasn't in.
+    pub fn leave(&mut self) -> bool {
+        self.player.take().is_some()
+    }
+
+    pub fn player(&self) -> Option<&Player> {
+        self.player.as_ref()
+    }
+
+    pub fn player_mut(&mut self) -> Option<&mut Player> {
+        self.player.as_mut()
+    }
+
+    /// Moves player two on their pad or keys while `can_move`, keeping them near player one.
+    /// Returns whether they started a dash.
+    pub fn update(&mut self, one: &Player, map: &TileMap, can_move: bool) -> bool {
+        let Some(two) = self.player.as_mut() else {
+            return false;
+        };
+        if two.position().distance(one.position()) > LEASH_DISTANCE {
+            two.set_position(one.position());
+        }
+        // Read either way, so presses while held still don't dash later.
+        let pad = self.gamepad.as_ref().and_then(Gamepad::input);
+        if !can_move {
+            return false;
+        }
+        let mut input = PlayerInput::from_arrow_keys();
+        if let Some(pad) = pad {
+            if input.movement == Vec2::ZERO {
+                input.movement = pad.movement;
+            }
+            input.dash |= pad.dash;
+        }
+        let was_dashing = two.is_dashing();
+        two.step(input, map, get_frame_time());
+        two.is_dashing() && !was_dashing
+    }
+
+    /// Drops player two once their health runs out. Returns whether that just happened.
+    pub fn knock_out(&mut self) -> bool {
+        if self.player.as_ref().is_some_and(|two| two.hp() <= 0.0) {
+            self.player = None;
+            return true;
+        }
+        false
+    }
+
+    pub fn target(&self, iframes: f32) -> Option<PlayerTarget> {
+        self.player.as_ref().map(|two| PlayerTarget {
+            index: 1,
+            pos: two.position(),
+            hitbox: two.world_hitbox(),
+            iframes,
+        })
+    }
+
+    /// Where the camera should follow and how much around it to keep in view.
+    pub fn camera_focus(&self, one: Vec2) -> (Vec2, Vec2) {
+        match &self.player {
+            Some(two) => ((one + two.position()) * 0.5, (one - two.position()) * 0.5),
+            None => (one, Vec2::ZERO),
+        }
+    }
+}
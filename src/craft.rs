@@ -0,0 +1,276 @@
+use macroquad::prelude::*;
+use crate::font::draw_text;
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::path::Path;
+use crate::helpers::{data_path, load_wasm_manifest_files};
+use crate::item::{Inventory, ItemDatabase, ItemStack};
+use crate::locale::Locale;
+
+const PANEL_WIDTH: f32 = 460.0;
+const ROW_HEIGHT: f32 = 30.0;
+const ICON_SIZE: f32 = 20.0;
+
+#[derive(Debug)]
+pub enum RecipeLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for RecipeLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RecipeLoadError {}
+
+impl From<std::io::Error> for RecipeLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for RecipeLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Recipe {
+    pub id: String,
+    pub inputs: Vec<ItemStack>,
+    pub output: ItemStack,
+    /// Structure id the player must stand near to craft this; `None` crafts anywhere.
+    #[serde(default)]
+    pub station: Option<String>,
+}
+
+impl Recipe {
+    pub fn station_available(&self, stations: &[String]) -> bool {
+        self.station
+            .as_ref()
+            .is_none_or(|station| stations.iter().any(|near| near == station))
+    }
+}
+
+pub struct RecipeDatabase {
+    recipes: Vec<Recipe>,
+}
+
+impl RecipeDatabase {
+    pub fn empty() -> Self {
+        Self { recipes: Vec::new() }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, RecipeLoadError> {
+        let dir = dir.as_ref();
+        let mut recipes = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let files = load_wasm_manifest_files(
+                &dir,
+                &["axe.yaml", "bolt.yaml", "fertilizer.yaml", "hammer.yaml", "hoe.yaml", "watering_can.yaml"],
+            )
+            .await;
+            for file in files {
+                let path = format!("{}/{}", dir, file);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| RecipeLoadError::Io(std::io::Error::other(err.to_string())))?;
+                recipes.push(serde_yaml::from_str::<Recipe>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                recipes.push(serde_yaml::from_str::<Recipe>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+        recipes.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(Self { recipes })
+    }
+
+    pub fn recipes(&self) -> &[Recipe] {
+        &self.recipes
+    }
+
+    /// Whether any recipe is crafted at structures with this id.
+    pub fn is_station(&self, structure_id: &str) -> bool {
+        self.recipes
+            .iter()
+            .any(|recipe| recipe.station.as_deref() == Some(structure_id))
+    }
+}
+
+/// Swaps a recipe's inputs for its output. Leaves `inventory` untouched and returns false
+/// when an input is missing or the output wouldn't fit.
+pub fn craft(recipe: &Recipe, inventory: &mut Inventory, items: &ItemDatabase) -> bool {
+    let mut trial = inventory.clone();
+    if !trial.remove_all(&recipe.inputs) {
+        return false;
+    }
+    if trial.add(items, &recipe.output.item, recipe.output.count) > 0 {
+        return false;
+    }
+    *inventory = trial;
+    true
+}
+
+/// Recipe list toggled with C or by using a crafting station. Clicking a row crafts it.
+#[derive(Default)]
+pub struct CraftUi {
+    open: bool,
+}
+
+impl CraftUi {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Handles toggling and clicks. Returns a message when crafting fails.
+    pub fn update(
+        &mut self,
+        recipes: &RecipeDatabase,
+        items: &ItemDatabase,
+        inventory: &mut Inventory,
+        stations: &[String],
+    ) -> Option<String> {
+        if is_key_pressed(KeyCode::C) {
+            self.open = !self.open;
+            return None;
+        }
+        if !self.open {
+            return None;
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            self.close();
+            return None;
+        }
+        if !is_mouse_button_pressed(MouseButton::Left) {
+            return None;
+        }
+        let mouse = vec2(mouse_position().0, mouse_position().1);
+        let panel = panel_rect(recipes.recipes().len());
+        if !panel.contains(mouse) {
+            self.close();
+            return None;
+        }
+        let recipe = recipes
+            .recipes()
+            .iter()
+            .enumerate()
+            .find(|(i, _)| row_rect(panel, *i).contains(mouse))
+            .map(|(_, recipe)| recipe)?;
+        if !recipe.station_available(stations) {
+            return Some(format!(
+                "{} needs a {}",
+                recipe.id,
+                recipe.station.as_deref().unwrap_or_default()
+            ));
+        }
+        if !inventory.has_all(&recipe.inputs) {
+            return Some(format!("missing ingredients for {}", recipe.id));
+        }
+        if !craft(recipe, inventory, items) {
+            return Some("inventory full".to_string());
+        }
+        None
+    }
+
+    pub fn draw(
+        &self,
+        recipes: &RecipeDatabase,
+        items: &ItemDatabase,
+        inventory: &Inventory,
+        stations: &[String],
+        locale: &Locale,
+    ) {
+        if !self.open {
+            return;
+        }
+        let panel = panel_rect(recipes.recipes().len());
+        let mouse = vec2(mouse_position().0, mouse_position().1);
+        draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.05, 0.05, 0.08, 0.9));
+        draw_rectangle_lines(panel.x, panel.y, panel.w, panel.h, 2.0, GRAY);
+        draw_text("Crafting", panel.x + 12.0, panel.y + 26.0, 24.0, WHITE);
+
+        for (i, recipe) in recipes.recipes().iter().enumerate() {
+            let rect = row_rect(panel, i);
+            let at_station = recipe.station_available(stations);
+            let craftable = at_station && inventory.has_all(&recipe.inputs);
+            if rect.contains(mouse) {
+                draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(1.0, 1.0, 1.0, 0.1));
+            }
+            if let Some(texture) = items.get(&recipe.output.item).and_then(|def| def.texture.as_ref()) {
+                texture.draw(
+                    rect.x + 4.0,
+                    rect.y + (rect.h - ICON_SIZE) * 0.5,
+                    WHITE,
+                    DrawTextureParams {
+                        dest_size: Some(vec2(ICON_SIZE, ICON_SIZE)),
+                        ..Default::default()
+                    },
+                );
+            }
+            let inputs = recipe
+                .inputs
+                .iter()
+                .map(|input| format!("{} {}", locale.format_int(input.count as i64), input.item))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let mut label = format!(
+                "{} x{}  <- {inputs}",
+                recipe.output.item,
+                locale.format_int(recipe.output.count as i64)
+            );
+            if !at_station && let Some(station) = recipe.station.as_deref() {
+                label.push_str(&format!("  [{station}]"));
+            }
+            let color = if craftable { WHITE } else { GRAY };
+            draw_text(&label, rect.x + ICON_SIZE + 10.0, rect.y + rect.h * 0.65, 18.0, color);
+        }
+        draw_text("click to craft, C to close", panel.x + 12.0, panel.bottom() - 12.0, 16.0, GRAY);
+    }
+}
+
+fn panel_rect(rows: usize) -> Rect {
+    let height = 80.0 + rows.max(1) as f32 * ROW_HEIGHT;
+    Rect::new(
+        (screen_width() - PANEL_WIDTH) * 0.5,
+        (screen_height() - height) * 0.5,
+        PANEL_WIDTH,
+        height,
+    )
+}
+
+fn row_rect(panel: Rect, index: usize) -> Rect {
+    Rect::new(
+        panel.x + 12.0,
+        panel.y + 40.0 + index as f32 * ROW_HEIGHT,
+        panel.w - 24.0,
+        ROW_HEIGHT - 2.0,
+    )
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
@@ -0,0 +1,249 @@
+use macroquad::prelude::*;
+use macroquad::file::load_string;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::farm::{Season, SoilLayer};
+use crate::helpers::{data_path, load_wasm_manifest_files};
+use crate::item::ItemStack;
+use crate::map::TileSet;
+
+#[derive(Debug)]
+pub enum CropLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for CropLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CropLoadError {}
+
+impl From<std::io::Error> for CropLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for CropLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct CropDef {
+    pub id: String,
+    /// Item that sows this crop when used on tilled soil.
+    pub seed: String,
+    pub produce: ItemStack,
+    /// Tileset tile for each growth stage; the last one is ripe.
+    pub stages: Vec<u8>,
+    /// Days of full growth needed per stage. Watering and fertility scale each day's growth.
+    #[serde(default = "default_days_per_stage")]
+    pub days_per_stage: f32,
+    /// Seasons the crop can be planted and grow in; empty means all year. Crops wither when
+    /// their season ends.
+    #[serde(default)]
+    pub seasons: Vec<Season>,
+    /// Damage it takes from enemies before it's destroyed.
+    #[serde(default = "default_hp")]
+    pub hp: f32,
+}
+
+fn default_days_per_stage() -> f32 {
+    1.0
+}
+
+fn default_hp() -> f32 {
+    2.0
+}
+
+impl CropDef {
+    pub fn in_season(&self, season: Season) -> bool {
+        self.seasons.is_empty() || self.seasons.contains(&season)
+    }
+
+    fn stage(&self, growth: f32) -> usize {
+        let stage = (growth / self.days_per_stage.max(0.01)) as usize;
+        stage.min(self.stages.len().saturating_sub(1))
+    }
+
+    pub fn is_ripe(&self, growth: f32) -> bool {
+        self.stage(growth) + 1 >= self.stages.len()
+    }
+}
+
+pub struct CropDatabase {
+    crops: Vec<CropDef>,
+}
+
+impl CropDatabase {
+    pub fn empty() -> Self {
+        Self { crops: Vec::new() }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, CropLoadError> {
+        let dir = dir.as_ref();
+        let mut crops = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let files = load_wasm_manifest_files(&dir, &["berry.yaml", "turnip.yaml"]).await;
+            for file in files {
+                let path = format!("{}/{}", dir, file);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| CropLoadError::Io(std::io::Error::other(err.to_string())))?;
+                crops.push(serde_yaml::from_str::<CropDef>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                crops.push(serde_yaml::from_str::<CropDef>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+        crops.retain(|crop| {
+            if crop.stages.is_empty() {
+                eprintln!("crop '{}' has no growth stages", crop.id);
+            }
+            !crop.stages.is_empty()
+        });
+        Ok(Self { crops })
+    }
+
+    pub fn get(&self, id: &str) -> Option<&CropDef> {
+        self.crops.iter().find(|crop| crop.id == id)
+    }
+
+    /// The crop sown by the seed item `item`.
+    pub fn by_seed(&self, item: &str) -> Option<&CropDef> {
+        self.crops.iter().find(|crop| crop.seed == item)
+    }
+}
+
+/// A planted crop. `growth` counts grown days; see [`CropDef::days_per_stage`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Crop {
+    pub x: usize,
+    pub y: usize,
+    pub crop: String,
+    pub growth: f32,
+    /// Damage taken so far. Not saved, so a reload mends it.
+    #[serde(skip)]
+    pub damage: f32,
+}
+
+#[derive(Default)]
+pub struct CropField {
+    crops: Vec<Crop>,
+}
+
+impl CropField {
+    pub fn from_crops(crops: Vec<Crop>) -> Self {
+        Self { crops }
+    }
+
+    pub fn crops(&self) -> &[Crop] {
+        &self.crops
+    }
+
+    pub fn is_planted(&self, x: usize, y: usize) -> bool {
+        self.crops.iter().any(|crop| crop.x == x && crop.y == y)
+    }
+
+    pub fn can_plant(&self, soil: &SoilLayer, x: usize, y: usize, def: &CropDef, season: Season) -> bool {
+        soil.get(x, y).tilled() && !self.is_planted(x, y) && def.in_season(season)
+    }
+
+    /// Sows `def` on empty tilled soil if it is in season. Returns whether it was planted.
+    pub fn plant(&mut self, soil: &SoilLayer, x: usize, y: usize, def: &CropDef, season: Season) -> bool {
+        if !self.can_plant(soil, x, y, def, season) {
+            return false;
+        }
+        self.crops.push(Crop {
+            x,
+            y,
+            crop: def.id.clone(),
+            growth: 0.0,
+            damage: 0.0,
+        });
+        true
+    }
+
+    /// Tiles holding a ripe crop.
+    pub fn ripe<'a>(&'a self, db: &'a CropDatabase) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.crops
+            .iter()
+            .filter(|crop| db.get(&crop.crop).is_some_and(|def| def.is_ripe(crop.growth)))
+            .map(|crop| (crop.x, crop.y))
+    }
+
+    /// Damages the crop at (`x`, `y`), destroying it once the damage reaches its hp. Returns
+    /// whether it was destroyed.
+    pub fn damage(&mut self, x: usize, y: usize, amount: f32, db: &CropDatabase) -> bool {
+        let Some(index) = self.crops.iter().position(|crop| crop.x == x && crop.y == y) else {
+            return false;
+        };
+        let crop = &mut self.crops[index];
+        crop.damage += amount;
+        if db.get(&crop.crop).is_some_and(|def| crop.damage < def.hp) {
+            return false;
+        }
+        self.crops.swap_remove(index);
+        true
+    }
+
+    /// Picks the ripe crop at (`x`, `y`), returning its produce.
+    pub fn harvest(&mut self, x: usize, y: usize, db: &CropDatabase) -> Option<ItemStack> {
+        let index = self.crops.iter().position(|crop| crop.x == x && crop.y == y)?;
+        let def = db.get(&self.crops[index].crop)?;
+        if !def.is_ripe(self.crops[index].growth) {
+            return None;
+        }
+        self.crops.swap_remove(index);
+        Some(def.produce.clone())
+    }
+
+    /// Grows every crop by a day at its soil's growth rate. Crops out of season, or whose
+    /// definition is gone, wither away.
+    pub fn grow(&mut self, soil: &SoilLayer, season: Season, db: &CropDatabase) {
+        self.crops.retain_mut(|crop| {
+            if !db.get(&crop.crop).is_some_and(|def| def.in_season(season)) {
+                return false;
+            }
+            crop.growth += soil.get(crop.x, crop.y).growth_rate();
+            true
+        });
+    }
+
+    pub fn draw(&self, db: &CropDatabase, tileset: &TileSet, view: Rect, tile_size: f32, tint: Color) {
+        for crop in &self.crops {
+            let pos = vec2(crop.x as f32 * tile_size, crop.y as f32 * tile_size);
+            if !view.overlaps(&Rect::new(pos.x, pos.y, tile_size, tile_size)) {
+                continue;
+            }
+            if let Some(def) = db.get(&crop.crop) {
+                // Damaged crops go redder as they near destruction.
+                let hurt = (crop.damage / def.hp.max(f32::EPSILON)).clamp(0.0, 1.0) * 0.6;
+                let tint = Color::new(tint.r, tint.g * (1.0 - hurt), tint.b * (1.0 - hurt), tint.a);
+                tileset.draw_tile(def.stages[def.stage(crop.growth)], pos, tile_size, tint);
+            }
+        }
+    }
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
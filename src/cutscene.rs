@@ -0,0 +1,324 @@
+use macroquad::prelude::*;
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::dialogue::{DialogueBox, LINE_SECONDS};
+use crate::entity::{Entity, EntityDatabase};
+use crate::font::{TextStyle, draw_text_styled, measure_text};
+use crate::helpers::{data_path, load_wasm_manifest_files};
+
+/// Screen pixels of the skip button.
+const SKIP_SIZE: Vec2 = vec2(96.0, 32.0);
+
+#[derive(Debug)]
+pub enum CutsceneLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for CutsceneLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CutsceneLoadError {}
+
+impl From<std::io::Error> for CutsceneLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for CutsceneLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct CutsceneDef {
+    pub id: String,
+    pub steps: Vec<CutsceneStep>,
+    /// Seconds the camera holds the last shot once it ends, before easing back to the player.
+    #[serde(default)]
+    pub linger: f32,
+}
+
+/// One beat of a cutscene. Everything in it plays out together over the step.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CutsceneStep {
+    /// Where the camera pans to, in tiles.
+    pub camera: Option<[f32; 2]>,
+    pub moves: Vec<EntityPath>,
+    pub say: Vec<String>,
+    pub speaker: Option<String>,
+    /// How dark the screen gets by the end of the step, from 0 to 1.
+    pub fade: Option<f32>,
+    /// A step with lines lasts at least as long as it takes to show them.
+    pub seconds: f32,
+}
+
+impl CutsceneStep {
+    fn duration(&self) -> f32 {
+        self.seconds.max(self.say.len() as f32 * LINE_SECONDS)
+    }
+}
+
+/// Walks the entity nearest the path's first point along it, at an even pace.
+#[derive(Clone, Deserialize)]
+pub struct EntityPath {
+    pub entity: String,
+    /// Points in tiles for the entity's hitbox center to pass through.
+    pub path: Vec<[f32; 2]>,
+}
+
+struct ActiveMove {
+    uid: u64,
+    /// World points, starting where the entity stood.
+    points: Vec<Vec2>,
+}
+
+impl ActiveMove {
+    fn point_at(&self, t: f32) -> Vec2 {
+        let total: f32 = self.points.windows(2).map(|pair| pair[0].distance(pair[1])).sum();
+        let mut left = total * t.clamp(0.0, 1.0);
+        for pair in self.points.windows(2) {
+            let length = pair[0].distance(pair[1]);
+            if left <= length && length > 0.0 {
+                return pair[0].lerp(pair[1], left / length);
+            }
+            left -= length;
+        }
+        self.points.last().copied().unwrap_or_default()
+    }
+}
+
+struct Playing {
+    steps: Vec<CutsceneStep>,
+    step: usize,
+    elapsed: f32,
+    started: bool,
+    camera_from: Vec2,
+    fade_from: f32,
+    moves: Vec<ActiveMove>,
+    linger: f32,
+}
+
+/// Loaded cutscenes and the one playing, if any. The player can't act while one plays.
+pub struct Cutscenes {
+    defs: HashMap<String, CutsceneDef>,
+    playing: Option<Playing>,
+    camera: Vec2,
+    fade: f32,
+    /// Where and how long the camera should hold after the last cutscene ended.
+    linger: Option<(Vec2, f32)>,
+}
+
+impl Cutscenes {
+    pub fn empty() -> Self {
+        Self {
+            defs: HashMap::new(),
+            playing: None,
+            camera: Vec2::ZERO,
+            fade: 0.0,
+            linger: None,
+        }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, CutsceneLoadError> {
+        let dir = dir.as_ref();
+        let mut defs = HashMap::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let names = load_wasm_manifest_files(&dir, &["farm_arrival.yaml"]).await;
+            for name in names {
+                let path = format!("{}/{}", dir, name);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| CutsceneLoadError::Io(std::io::Error::other(err.to_string())))?;
+                let def: CutsceneDef = serde_yaml::from_str(&raw_str)?;
+                defs.insert(def.id.clone(), def);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                let def: CutsceneDef = serde_yaml::from_str(&std::fs::read_to_string(&path)?)?;
+                defs.insert(def.id.clone(), def);
+            }
+        }
+
+        Ok(Self {
+            defs,
+            ..Self::empty()
+        })
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some()
+    }
+
+    /// Where the camera should look while a cutscene plays.
+    pub fn camera(&self) -> Option<Vec2> {
+        self.playing.as_ref().map(|_| self.camera)
+    }
+
+    /// The hold the camera should make now that a cutscene has ended, once.
+    pub fn take_linger(&mut self) -> Option<(Vec2, f32)> {
+        self.linger.take()
+    }
+
+    /// Starts cutscene `id` unless one is already playing. Returns whether `id` exists.
+    pub fn play(&mut self, id: &str) -> bool {
+        let Some(def) = self.defs.get(id) else {
+            return false;
+        };
+        if self.playing.is_none() {
+            self.playing = Some(Playing {
+                steps: def.steps.clone(),
+                step: 0,
+                elapsed: 0.0,
+                started: false,
+                camera_from: Vec2::ZERO,
+                fade_from: 0.0,
+                moves: Vec::new(),
+                linger: def.linger,
+            });
+        }
+        true
+    }
+
+    /// Plays the cutscene on from `camera`, where the view is now. Escape or the skip button
+    /// jumps to the end.
+    pub fn update(
+        &mut self,
+        dt: f32,
+        entities: &mut [Entity],
+        db: &EntityDatabase,
+        tile_size: f32,
+        camera: Vec2,
+        dialogue: &mut DialogueBox,
+    ) {
+        let Some(mut playing) = self.playing.take() else {
+            return;
+        };
+        let skip = is_key_pressed(KeyCode::Escape)
+            || (is_mouse_button_pressed(MouseButton::Left) && skip_rect().contains(mouse_position().into()));
+        if !playing.started {
+            self.camera = camera;
+        }
+        playing.elapsed += if skip { f32::INFINITY } else { dt };
+        while let Some(step) = playing.steps.get(playing.step) {
+            if !playing.started {
+                playing.started = true;
+                playing.camera_from = self.camera;
+                playing.fade_from = self.fade;
+                playing.moves =
+                    step.moves.iter().filter_map(|path| start_move(path, entities, db, tile_size)).collect();
+                if !skip {
+                    dialogue.start(step.speaker.as_deref(), &step.say);
+                }
+            }
+            let duration = step.duration();
+            let t = if duration > 0.0 { (playing.elapsed / duration).min(1.0) } else { 1.0 };
+            self.pose(step, &playing, t, entities, db, tile_size);
+            if t < 1.0 {
+                self.playing = Some(playing);
+                return;
+            }
+            playing.elapsed = if skip { f32::INFINITY } else { playing.elapsed - duration };
+            playing.step += 1;
+            playing.started = false;
+        }
+        if skip {
+            dialogue.clear();
+        }
+        self.fade = 0.0;
+        self.linger = (playing.linger > 0.0).then_some((self.camera, playing.linger));
+    }
+
+    /// Puts the camera, fade and moving entities where `step` has them `t` of the way through.
+    fn pose(
+        &mut self,
+        step: &CutsceneStep,
+        playing: &Playing,
+        t: f32,
+        entities: &mut [Entity],
+        db: &EntityDatabase,
+        tile_size: f32,
+    ) {
+        let eased = t * t * (3.0 - 2.0 * t);
+        if let Some([x, y]) = step.camera {
+            self.camera = playing.camera_from.lerp(vec2(x, y) * tile_size, eased);
+        }
+        if let Some(fade) = step.fade {
+            self.fade = playing.fade_from + (fade.clamp(0.0, 1.0) - playing.fade_from) * t;
+        }
+        for active in &playing.moves {
+            let Some(ent) = entities.iter_mut().find(|ent| ent.instance.uid == active.uid) else {
+                continue;
+            };
+            let offset = ent.hitbox(db).center() - ent.instance.pos;
+            ent.instance.pos = active.point_at(t) - offset;
+            ent.instance.vel = Vec2::ZERO;
+        }
+    }
+
+    /// Draws the fade and the skip button over everything else.
+    pub fn draw(&self) {
+        if self.fade > 0.0 {
+            draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, self.fade));
+        }
+        if !self.is_playing() {
+            return;
+        }
+        let rect = skip_rect();
+        let hovered = rect.contains(mouse_position().into());
+        let fill = if hovered { Color::new(0.25, 0.25, 0.3, 0.9) } else { Color::new(0.1, 0.1, 0.12, 0.8) };
+        draw_rectangle(rect.x, rect.y, rect.w, rect.h, fill);
+        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, Color::new(1.0, 1.0, 1.0, 0.3));
+        let label = "Skip (Esc)";
+        let width = measure_text(label, 16.0).width;
+        let x = rect.center().x - width * 0.5;
+        draw_text_styled(label, x, rect.y + 21.0, 16.0, WHITE, TextStyle::SHADOWED);
+    }
+}
+
+fn skip_rect() -> Rect {
+    Rect::new(screen_width() - SKIP_SIZE.x - 20.0, screen_height() - SKIP_SIZE.y - 20.0, SKIP_SIZE.x, SKIP_SIZE.y)
+}
+
+fn start_move(path: &EntityPath, entities: &mut [Entity], db: &EntityDatabase, tile_size: f32) -> Option<ActiveMove> {
+    let points: Vec<Vec2> = path.path.iter().map(|&[x, y]| vec2(x, y) * tile_size).collect();
+    let first = *points.first()?;
+    let nearest = entities
+        .iter_mut()
+        .filter(|ent| db.entities[ent.instance.def].id == path.entity)
+        .map(|ent| (ent.hitbox(db).center().distance_squared(first), ent))
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+    let Some((_, ent)) = nearest else {
+        eprintln!("cutscene moves missing entity '{}'", path.entity);
+        return None;
+    };
+    ent.instance.waypoints.clear();
+    Some(ActiveMove {
+        uid: ent.instance.uid,
+        points: std::iter::once(ent.hitbox(db).center()).chain(points).collect(),
+    })
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
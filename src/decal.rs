@@ -0,0 +1,349 @@
+use macroquad::prelude::*;
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use crate::atlas::{AtlasBuilder, Sprite};
+use crate::helpers::{asset_path, data_path, load_wasm_manifest_files};
+
+/// Decals kept on one map; stamping past this drops the oldest.
+const MAX_DECALS: usize = 384;
+/// A fading decal is re-baked each time its alpha crosses one of this many steps, so a fade
+/// costs a few chunk redraws instead of one every frame.
+const FADE_STEPS: f32 = 8.0;
+/// Decal chunks redrawn per frame at most; the rest keep showing their old contents meanwhile.
+const REBUILDS_PER_FRAME: usize = 2;
+
+#[derive(Debug)]
+pub enum DecalLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for DecalLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for DecalLoadError {}
+
+impl From<std::io::Error> for DecalLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for DecalLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+#[derive(Deserialize)]
+struct DecalFile {
+    id: String,
+    texture: String,
+    /// World units; defaults to the texture's size.
+    #[serde(default)]
+    size: Option<[f32; 2]>,
+    #[serde(default = "default_color")]
+    color: [f32; 4],
+    /// Seconds the decal stays at full strength; 0 keeps it until it's pushed out.
+    #[serde(default)]
+    lifetime: f32,
+    /// Seconds it then takes to fade away.
+    #[serde(default)]
+    fade: f32,
+    /// Turns each stamp a random way, unless the caller gives a rotation.
+    #[serde(default)]
+    random_rotation: bool,
+}
+
+fn default_color() -> [f32; 4] {
+    [1.0, 1.0, 1.0, 1.0]
+}
+
+struct DecalDef {
+    sprite: Sprite,
+    size: Vec2,
+    color: Color,
+    lifetime: f32,
+    fade: f32,
+    random_rotation: bool,
+}
+
+/// Marks gameplay can leave on the ground, by id.
+pub struct DecalDatabase {
+    defs: Vec<DecalDef>,
+    lookup: HashMap<String, usize>,
+}
+
+impl DecalDatabase {
+    pub fn empty() -> Self {
+        Self {
+            defs: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, DecalLoadError> {
+        let dir = dir.as_ref();
+        let mut raws = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let files = load_wasm_manifest_files(&dir, &["footprint.yaml", "oil.yaml"]).await;
+            for file in files {
+                let path = format!("{}/{}", dir, file);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| DecalLoadError::Io(std::io::Error::other(err.to_string())))?;
+                raws.push(serde_yaml::from_str::<DecalFile>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                raws.push(serde_yaml::from_str::<DecalFile>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+
+        let mut atlas = AtlasBuilder::default();
+        let mut sprites = Vec::with_capacity(raws.len());
+        for raw in &raws {
+            sprites.push(atlas.add(&asset_path(&raw.texture)).await);
+        }
+        let atlas = atlas.build();
+
+        let mut db = Self::empty();
+        for (raw, handle) in raws.into_iter().zip(sprites) {
+            let sprite = atlas.sprite(handle);
+            let size = raw.size.map(|[w, h]| vec2(w, h)).unwrap_or(sprite.size());
+            db.lookup.insert(raw.id, db.defs.len());
+            db.defs.push(DecalDef {
+                sprite,
+                size,
+                color: Color::new(raw.color[0], raw.color[1], raw.color[2], raw.color[3]),
+                lifetime: raw.lifetime.max(0.0),
+                fade: raw.fade.max(0.0),
+                random_rotation: raw.random_rotation,
+            });
+        }
+        Ok(db)
+    }
+}
+
+struct Decal {
+    sprite: Sprite,
+    center: Vec2,
+    size: Vec2,
+    rotation: f32,
+    color: Color,
+    age: f32,
+    lifetime: f32,
+    fade: f32,
+    /// Fade step the decal was last baked at.
+    step: u8,
+}
+
+impl Decal {
+    /// Covers the decal at any rotation.
+    fn bounds(&self) -> Rect {
+        let radius = self.size.length() * 0.5;
+        Rect::new(self.center.x - radius, self.center.y - radius, radius * 2.0, radius * 2.0)
+    }
+
+    fn fade_step(&self) -> u8 {
+        if self.lifetime <= 0.0 || self.age <= self.lifetime || self.fade <= 0.0 {
+            return 0;
+        }
+        (((self.age - self.lifetime) / self.fade).clamp(0.0, 1.0) * FADE_STEPS) as u8
+    }
+
+    fn expired(&self) -> bool {
+        self.lifetime > 0.0 && self.age >= self.lifetime + self.fade
+    }
+}
+
+#[derive(Default)]
+struct DecalChunk {
+    target: Option<RenderTarget>,
+    dirty: bool,
+}
+
+/// Ground marks stamped onto a map, baked into chunk-sized render targets of their own so they
+/// draw as one quad per chunk over the background tiles.
+pub struct DecalLayer {
+    decals: VecDeque<Decal>,
+    chunk_pixel_size: f32,
+    chunks: HashMap<(i32, i32), DecalChunk>,
+}
+
+impl DecalLayer {
+    pub fn new(chunk_pixel_size: f32) -> Self {
+        Self {
+            decals: VecDeque::new(),
+            chunk_pixel_size: chunk_pixel_size.max(1.0),
+            chunks: HashMap::new(),
+        }
+    }
+
+    /// Stamps decal `id` centered on `pos`, turned by `rotation` radians if given.
+    pub fn stamp(&mut self, db: &DecalDatabase, id: &str, pos: Vec2, rotation: Option<f32>) {
+        let Some(def) = db.lookup.get(id).map(|&idx| &db.defs[idx]) else {
+            return;
+        };
+        let rotation = rotation.unwrap_or_else(|| {
+            if def.random_rotation {
+                macroquad::rand::gen_range(0.0, std::f32::consts::TAU)
+            } else {
+                0.0
+            }
+        });
+        let decal = Decal {
+            sprite: def.sprite.clone(),
+            center: pos,
+            size: def.size,
+            rotation,
+            color: def.color,
+            age: 0.0,
+            lifetime: def.lifetime,
+            fade: def.fade,
+            step: 0,
+        };
+        self.mark_dirty(decal.bounds());
+        self.decals.push_back(decal);
+        if self.decals.len() > MAX_DECALS
+            && let Some(oldest) = self.decals.pop_front()
+        {
+            self.mark_dirty(oldest.bounds());
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let mut changed = Vec::new();
+        self.decals.retain_mut(|decal| {
+            decal.age += dt;
+            if decal.expired() {
+                changed.push(decal.bounds());
+                return false;
+            }
+            let step = decal.fade_step();
+            if step != decal.step {
+                decal.step = step;
+                changed.push(decal.bounds());
+            }
+            true
+        });
+        for rect in changed {
+            self.mark_dirty(rect);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.decals.clear();
+        self.chunks.clear();
+    }
+
+    /// Draws the chunks overlapping `view` with the world camera active, first redrawing up to
+    /// `REBUILDS_PER_FRAME` of them that changed.
+    pub fn draw(&mut self, view: Rect, tint: Color) {
+        let size = self.chunk_pixel_size;
+        let min = ((view.x / size).floor() as i32, (view.y / size).floor() as i32);
+        let max = (((view.x + view.w) / size).floor() as i32, ((view.y + view.h) / size).floor() as i32);
+
+        let mut rebuilds = 0;
+        for cy in min.1..=max.1 {
+            for cx in min.0..=max.0 {
+                let key = (cx, cy);
+                if rebuilds < REBUILDS_PER_FRAME && self.chunks.get(&key).is_some_and(|chunk| chunk.dirty) {
+                    self.rebuild_chunk(key);
+                    rebuilds += 1;
+                }
+                let Some(target) = self.chunks.get(&key).and_then(|chunk| chunk.target.as_ref()) else {
+                    continue;
+                };
+                draw_texture_ex(
+                    &target.texture,
+                    cx as f32 * size,
+                    cy as f32 * size,
+                    tint,
+                    DrawTextureParams {
+                        dest_size: Some(vec2(size, size)),
+                        flip_y: true,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+    }
+
+    fn mark_dirty(&mut self, rect: Rect) {
+        let size = self.chunk_pixel_size;
+        let (min_x, min_y) = ((rect.x / size).floor() as i32, (rect.y / size).floor() as i32);
+        let (max_x, max_y) = (((rect.x + rect.w) / size).floor() as i32, ((rect.y + rect.h) / size).floor() as i32);
+        for cy in min_y..=max_y {
+            for cx in min_x..=max_x {
+                self.chunks.entry((cx, cy)).or_default().dirty = true;
+            }
+        }
+    }
+
+    /// Redraws every decal touching chunk `key` into its target, or drops the chunk if none do.
+    fn rebuild_chunk(&mut self, key: (i32, i32)) {
+        let size = self.chunk_pixel_size;
+        let area = Rect::new(key.0 as f32 * size, key.1 as f32 * size, size, size);
+        if !self.decals.iter().any(|decal| decal.bounds().overlaps(&area)) {
+            self.chunks.remove(&key);
+            return;
+        }
+        let Some(chunk) = self.chunks.get_mut(&key) else {
+            return;
+        };
+        let target = chunk.target.get_or_insert_with(|| {
+            let pixels = size.round().max(1.0) as u32;
+            let target = render_target(pixels, pixels);
+            target.texture.set_filter(FilterMode::Nearest);
+            target
+        });
+        chunk.dirty = false;
+
+        let mut cam = Camera2D::from_display_rect(Rect::new(0.0, 0.0, size, size));
+        cam.render_target = Some(target.clone());
+        push_camera_state();
+        set_camera(&cam);
+        clear_background(Color::new(0.0, 0.0, 0.0, 0.0));
+        for decal in &self.decals {
+            if !decal.bounds().overlaps(&area) {
+                continue;
+            }
+            let mut color = decal.color;
+            color.a *= 1.0 - decal.step as f32 / FADE_STEPS;
+            let top_left = decal.center - decal.size * 0.5 - area.point();
+            decal.sprite.draw(
+                top_left.x,
+                top_left.y,
+                color,
+                DrawTextureParams {
+                    dest_size: Some(decal.size),
+                    rotation: decal.rotation,
+                    ..Default::default()
+                },
+            );
+        }
+        pop_camera_state();
+    }
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
@@ -0,0 +1,64 @@
+use macroquad::prelude::*;
+use crate::font::{TextStyle, draw_text_styled};
+
+/// Seconds each line stays up.
+pub const LINE_SECONDS: f32 = 3.5;
+
+struct Conversation {
+    speaker: Option<String>,
+    lines: Vec<String>,
+    line: usize,
+    timer: f32,
+}
+
+/// Lines of dialogue shown one at a time in a box near the bottom of the screen.
+#[derive(Default)]
+pub struct DialogueBox {
+    current: Option<Conversation>,
+}
+
+impl DialogueBox {
+    /// Replaces whatever is being said with `lines`.
+    pub fn start(&mut self, speaker: Option<&str>, lines: &[String]) {
+        self.current = (!lines.is_empty()).then(|| Conversation {
+            speaker: speaker.map(str::to_string),
+            lines: lines.to_vec(),
+            line: 0,
+            timer: LINE_SECONDS,
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let Some(conversation) = self.current.as_mut() else {
+            return;
+        };
+        conversation.timer -= dt;
+        if conversation.timer <= 0.0 {
+            conversation.line += 1;
+            conversation.timer = LINE_SECONDS;
+        }
+        if conversation.line >= conversation.lines.len() {
+            self.current = None;
+        }
+    }
+
+    pub fn draw(&self) {
+        let Some(conversation) = self.current.as_ref() else {
+            return;
+        };
+        let Some(line) = conversation.lines.get(conversation.line) else {
+            return;
+        };
+        let panel = Rect::new(screen_width() * 0.5 - 280.0, screen_height() - 150.0, 560.0, 64.0);
+        draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.05, 0.05, 0.08, 0.85));
+        draw_rectangle_lines(panel.x, panel.y, panel.w, panel.h, 2.0, Color::new(1.0, 1.0, 1.0, 0.3));
+        if let Some(speaker) = conversation.speaker.as_deref() {
+            draw_text_styled(speaker, panel.x + 14.0, panel.y + 22.0, 18.0, GOLD, TextStyle::SHADOWED);
+        }
+        draw_text_styled(line, panel.x + 14.0, panel.y + 48.0, 20.0, WHITE, TextStyle::SHADOWED);
+    }
+}
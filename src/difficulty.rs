@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DifficultyPreset {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    /// Multipliers set by hand with the `difficulty custom` console command.
+    Custom,
+}
+
+impl DifficultyPreset {
+    /// Presets offered when creating a farm; custom is only reachable from the console.
+    pub const CHOICES: [DifficultyPreset; 3] = [Self::Easy, Self::Normal, Self::Hard];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Easy => "Easy",
+            Self::Normal => "Normal",
+            Self::Hard => "Hard",
+            Self::Custom => "Custom",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "easy" => Some(Self::Easy),
+            "normal" => Some(Self::Normal),
+            "hard" => Some(Self::Hard),
+            "custom" => Some(Self::Custom),
+            _ => None,
+        }
+    }
+}
+
+/// How hard enemies hit and how many of them appear. Chosen per save slot and stored with its
+/// metadata; enemies spawned before a change keep their old stats.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Difficulty {
+    pub preset: DifficultyPreset,
+    /// Multiplier on enemy `hp`.
+    pub enemy_health: f32,
+    /// Multiplier on enemy `damage`.
+    pub enemy_damage: f32,
+    /// Multiplier on how many enemies scene spawners place.
+    pub spawn_count: f32,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self::preset(DifficultyPreset::Normal)
+    }
+}
+
+impl Difficulty {
+    /// The multipliers for `preset`. Custom starts from normal's.
+    pub fn preset(preset: DifficultyPreset) -> Self {
+        let (enemy_health, enemy_damage, spawn_count) = match preset {
+            DifficultyPreset::Easy => (0.6, 0.5, 0.7),
+            DifficultyPreset::Normal | DifficultyPreset::Custom => (1.0, 1.0, 1.0),
+            DifficultyPreset::Hard => (1.5, 1.5, 1.4),
+        };
+        Self {
+            preset,
+            enemy_health,
+            enemy_damage,
+            spawn_count,
+        }
+    }
+
+    pub fn custom(enemy_health: f32, enemy_damage: f32, spawn_count: f32) -> Self {
+        Self {
+            preset: DifficultyPreset::Custom,
+            enemy_health: enemy_health.max(0.1),
+            enemy_damage: enemy_damage.max(0.0),
+            spawn_count: spawn_count.max(0.0),
+        }
+    }
+
+    /// `base` spawns scaled by the spawn multiplier, rounded.
+    pub fn scale_count(self, base: f32) -> usize {
+        (base * self.spawn_count).round().max(0.0) as usize
+    }
+}
@@ -1,7 +1,6 @@
 use macroquad::prelude::*;
-use macroquad::file::load_string;
-use crate::helpers::{asset_path, data_path, load_wasm_manifest_files};
-use serde::Deserialize;
+use crate::helpers::{asset_path, data_path, load_string, load_wasm_manifest_files};
+use serde::{Deserialize, Serialize};
 use serde_yaml::Value as YamlValue;
 use std::collections::HashMap;
 use std::path::Path;
@@ -9,6 +8,20 @@ use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::r#trait::*;
 use crate::particle::ParticleEmitter;
+use crate::map::StructureHit;
+use crate::inspect::{AiEvent, AiTimeline, describe_target};
+use crate::item::{Inventory, ItemStack};
+use crate::atlas::{AtlasBuilder, AtlasHandle, Sprite};
+use crate::texture::blank_texture;
+use crate::difficulty::Difficulty;
+use crate::attack::{AttackDef, AttackState, Projectile};
+use crate::companion::{CompanionDef, CompanionState};
+use crate::mount::MountDef;
+use crate::breed::BreedDef;
+use crate::turret::TurretDef;
+use crate::sim::TargetGrid;
+use crate::level::GrowthDef;
+use crate::animation::AnimationSet;
 
 pub type MovementFn = fn(
     entity: &mut EntityInstance,
@@ -24,7 +37,7 @@ pub type MovementParams = HashMap<String, f32>;
 pub enum EntityLoadError {
     Io(std::io::Error),
     Yaml(serde_yaml::Error),
-    Texture(String),
+    Json(serde_json::Error),
     MissingDefinition(String),
 }
 
@@ -33,7 +46,7 @@ impl std::fmt::Display for EntityLoadError {
         match self {
             Self::Io(err) => write!(f, "io error: {err}"),
             Self::Yaml(err) => write!(f, "yaml error: {err}"),
-            Self::Texture(err) => write!(f, "texture error: {err}"),
+            Self::Json(err) => write!(f, "json error: {err}"),
             Self::MissingDefinition(err) => write!(f, "missing definition: {err}"),
         }
     }
@@ -53,6 +66,12 @@ impl From<serde_yaml::Error> for EntityLoadError {
     }
 }
 
+impl From<serde_json::Error> for EntityLoadError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum EntityKind {
@@ -61,6 +80,12 @@ pub enum EntityKind {
     Misc,
 }
 
+const MOVE_ORDER_ARRIVE_DISTANCE: f32 = 4.0;
+/// Height above the ground at which a shadow has shrunk to its smallest.
+const SHADOW_SHRINK_HEIGHT: f32 = 48.0;
+/// Smallest a shadow gets, as a fraction of its grounded width.
+const SHADOW_MIN_SCALE: f32 = 0.5;
+
 pub const DEF_FLAG_TARGET_PLAYER: u16 = 1 << 0;
 pub const DEF_FLAG_TARGET_NEAREST_ENTITY: u16 = 1 << 1;
 pub const DEF_FLAG_TARGET_NEAREST_ENEMY: u16 = 1 << 2;
@@ -85,12 +110,21 @@ impl EntityKind {
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct StatBlock {
     values: HashMap<String, f32>,
 }
 
 impl StatBlock {
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    pub fn has(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
     pub fn add(&mut self, key: &str, value: f32) {
         *self.values.entry(key.to_string()).or_insert(0.0) += value;
     }
@@ -104,6 +138,13 @@ impl StatBlock {
     pub fn get(&self, key: &str, default: f32) -> f32 {
         self.values.get(key).copied().unwrap_or(default)
     }
+
+    /// Multiplies `key` if the block has it.
+    pub fn scale(&mut self, key: &str, factor: f32) {
+        if let Some(value) = self.values.get_mut(key) {
+            *value *= factor;
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -150,7 +191,7 @@ pub enum BehaviorNode {
 
 #[derive(Clone)]
 pub struct TextureInfo {
-    pub texture: Texture2D,
+    pub sprite: Sprite,
     pub draw: DrawParams,
 }
 
@@ -169,6 +210,29 @@ pub struct Entity {
     pub instance: EntityInstance,
 }
 
+/// An entity as written to a farm save. The worker fields stay empty for anything that
+/// isn't a worker.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedEntity {
+    pub def: String,
+    pub x: f32,
+    pub y: f32,
+    pub hp: f32,
+    /// Index of the job region the worker was assigned to.
+    #[serde(default)]
+    pub job: Option<usize>,
+    /// Items the worker was carrying.
+    #[serde(default)]
+    pub inventory: Inventory,
+    /// Stats of a bred or leveled entity, in place of the ones its def gives.
+    #[serde(default)]
+    pub stats: Option<StatBlock>,
+    #[serde(default)]
+    pub generation: u32,
+    #[serde(default)]
+    pub xp: f32,
+}
+
 impl Entity {
     pub fn spawn(
         db: &EntityDatabase,
@@ -180,15 +244,72 @@ impl Entity {
             .map(|instance| Self { instance })
     }
 
+    /// Respawns a saved entity where it stood, with the health it had.
+    pub fn from_saved(db: &EntityDatabase, saved: &SavedEntity, registry: &MovementRegistry) -> Option<Self> {
+        let mut ent = Self::spawn(db, &saved.def, vec2(saved.x, saved.y), registry)?;
+        if let Some(stats) = saved.stats.clone() {
+            ent.instance.set_stats(stats);
+        }
+        ent.instance.generation = saved.generation;
+        ent.instance.xp = saved.xp.max(0.0);
+        if let Some(growth) = &db.entities[ent.instance.def].growth {
+            // Saved stats already include what the levels added.
+            ent.instance.level = growth.level_for(ent.instance.xp);
+        }
+        ent.instance.hp = saved.hp.clamp(1.0, ent.instance.max_hp);
+        if let Some(turret) = &db.entities[ent.instance.def].turret
+            && let Some(ammo) = turret.ammo.as_deref()
+        {
+            ent.instance.ammo = saved.inventory.count(ammo).min(turret.capacity);
+        }
+        Some(ent)
+    }
+
+    /// Whether using `item` on the entity now would capture it.
+    pub fn can_capture(&self, db: &EntityDatabase, item: &str) -> bool {
+        db.entities[self.instance.def].capture.as_ref().is_some_and(|capture| {
+            capture.item == item
+                && !self.instance.is_dying()
+                && self.instance.hp <= self.instance.max_hp * capture.hp_ratio
+        })
+    }
+
+    pub fn to_saved(&self, db: &EntityDatabase) -> SavedEntity {
+        SavedEntity {
+            def: db.entities[self.instance.def].id.clone(),
+            x: self.instance.pos.x,
+            y: self.instance.pos.y,
+            hp: self.instance.hp,
+            job: None,
+            inventory: self.saved_ammo(db),
+            stats: (self.instance.generation > 0 || self.instance.level > 1).then(|| self.instance.stats.clone()),
+            generation: self.instance.generation,
+            xp: self.instance.xp,
+        }
+    }
+
+    /// A turret's loaded rounds, kept in the saved inventory.
+    fn saved_ammo(&self, db: &EntityDatabase) -> Inventory {
+        let ammo = db.entities[self.instance.def].turret.as_ref().and_then(|turret| turret.ammo.as_deref());
+        match ammo {
+            Some(item) if self.instance.ammo > 0 => Inventory::from_stacks(vec![ItemStack {
+                item: item.to_string(),
+                count: self.instance.ammo,
+            }]),
+            _ => Inventory::default(),
+        }
+    }
+
     pub fn update(
         &mut self,
         dt: f32,
         db: &EntityDatabase,
-        ctx: &mut EntityContext,
+        ctx: &EntityContext,
+        out: &mut EntityCommands,
         map: &crate::map::TileMap,
         registry: &MovementRegistry,
     ) {
-        self.instance.update(dt, db, ctx, map, registry);
+        self.instance.update(dt, db, ctx, out, map, registry);
     }
 
     pub fn draw(&self, db: &EntityDatabase) {
@@ -221,6 +342,8 @@ pub struct EntityDef {
     pub name: String,
     pub kind: EntityKind,
     pub texture: TextureInfo,
+    /// Frames of `texture` to play instead of drawing it whole.
+    pub animation: Option<AnimationSet>,
     pub hitbox: Rect,
     pub traits: Vec<usize>,
     pub trait_tags: HashMap<String, YamlValue>,
@@ -229,6 +352,196 @@ pub struct EntityDef {
     pub speed: f32,
     pub collides: bool,
     pub flags: u16,
+    pub drops: Vec<EntityDrop>,
+    pub feedback: HitFeedback,
+    /// Seconds between footsteps while walking; None for entities that make no sound moving.
+    pub footstep_interval: Option<f32>,
+    pub shadow: ShadowDef,
+    pub hover: Option<HoverDef>,
+    pub spawner: Option<SpawnerDef>,
+    /// Seconds until the entity is removed, without dying or dropping anything.
+    pub lifetime: Option<f32>,
+    pub attacks: Vec<AttackDef>,
+    pub capture: Option<CaptureDef>,
+    /// Whether the farm's job board puts it to work.
+    pub worker: bool,
+    pub companion: Option<CompanionDef>,
+    pub mount: Option<MountDef>,
+    pub breed: Option<BreedDef>,
+    pub turret: Option<TurretDef>,
+    /// XP the player and the friend that killed it get.
+    pub xp: f32,
+    pub growth: Option<GrowthDef>,
+}
+
+/// How a wild entity can be won over into a friendly one.
+#[derive(Clone, Deserialize)]
+pub struct CaptureDef {
+    /// Item id used up by a capture.
+    pub item: String,
+    /// Share of its max hp the entity has to be worn down to first.
+    #[serde(default = "default_capture_hp_ratio")]
+    pub hp_ratio: f32,
+    /// Friendly entity id it turns into.
+    pub becomes: String,
+    /// Particle effect burst where it's captured.
+    #[serde(default)]
+    pub particles: Option<String>,
+}
+
+fn default_capture_hp_ratio() -> f32 {
+    0.35
+}
+
+/// Brings more entities into the world around this one every so often.
+#[derive(Clone, Deserialize)]
+pub struct SpawnerDef {
+    /// Entity id to spawn.
+    pub entity: String,
+    /// Seconds between spawns.
+    #[serde(alias = "cooldown")]
+    pub interval: f32,
+    #[serde(default = "default_spawn_count")]
+    pub count: usize,
+    /// Spawning pauses while this many of its own spawns are still alive.
+    #[serde(default = "default_spawn_max_alive")]
+    pub max_alive: usize,
+    /// World units from the spawner that spawns land within.
+    #[serde(default)]
+    pub radius: f32,
+    /// Particle effect burst at each spawn.
+    #[serde(default)]
+    pub particles: Option<String>,
+    /// Sound id played at each spawn.
+    #[serde(default)]
+    pub sound: Option<String>,
+    /// What happens to its spawns still alive when the spawner dies.
+    #[serde(default)]
+    pub on_death: MinionFate,
+    /// How much stronger spawns get when `on_death` is berserk.
+    #[serde(default)]
+    pub berserk: BerserkDef,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MinionFate {
+    #[default]
+    Keep,
+    /// Removed on the spot, without dying or dropping anything.
+    Despawn,
+    Berserk,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct BerserkDef {
+    pub speed: f32,
+    pub damage: f32,
+}
+
+impl Default for BerserkDef {
+    fn default() -> Self {
+        Self {
+            speed: 1.5,
+            damage: 2.0,
+        }
+    }
+}
+
+fn default_spawn_count() -> usize {
+    1
+}
+
+fn default_spawn_max_alive() -> usize {
+    4
+}
+
+/// The blob shadow under an entity's hitbox.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ShadowDef {
+    /// World units across; None uses the hitbox width and 0 draws no shadow.
+    pub width: Option<f32>,
+    pub opacity: f32,
+}
+
+impl Default for ShadowDef {
+    fn default() -> Self {
+        Self {
+            width: None,
+            opacity: 0.3,
+        }
+    }
+}
+
+/// Keeps an entity's sprite up off the ground, bobbing, while its hitbox and shadow stay put.
+#[derive(Clone, Copy, Deserialize)]
+pub struct HoverDef {
+    pub height: f32,
+    /// How far above and below `height` it bobs.
+    #[serde(default)]
+    pub bob: f32,
+    /// Bobs per second.
+    #[serde(default = "default_bob_speed")]
+    pub bob_speed: f32,
+}
+
+fn default_bob_speed() -> f32 {
+    1.0
+}
+
+/// A soft dark ellipse centered on `feet`, as cast by something standing there.
+pub fn draw_blob_shadow(feet: Vec2, width: f32, alpha: f32) {
+    if width <= 0.0 || alpha <= 0.0 {
+        return;
+    }
+    let (rx, ry) = (width * 0.5, width * 0.18);
+    draw_ellipse(feet.x, feet.y, rx, ry, 0.0, Color::new(0.0, 0.0, 0.0, alpha * 0.6));
+    draw_ellipse(feet.x, feet.y, rx * 0.7, ry * 0.7, 0.0, Color::new(0.0, 0.0, 0.0, alpha * 0.4));
+}
+
+/// How an entity reacts visibly to being hit and to dying.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct HitFeedback {
+    /// Seconds the sprite flashes white after taking damage; zero disables the flash.
+    pub flash_time: f32,
+    pub death: DeathEffect,
+    /// Seconds the death effect plays before the entity is removed.
+    pub death_time: f32,
+}
+
+impl Default for HitFeedback {
+    fn default() -> Self {
+        Self {
+            flash_time: 0.12,
+            death: DeathEffect::Dissolve,
+            death_time: 0.45,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeathEffect {
+    /// Removed the moment its hp runs out.
+    None,
+    Fade,
+    /// Breaks apart into noise, fading out instead where shaders are unavailable.
+    Dissolve,
+}
+
+/// Loot rolled when an entity dies or a structure breaks.
+#[derive(Clone, Deserialize)]
+pub struct EntityDrop {
+    pub item: String,
+    #[serde(default = "default_drop_chance")]
+    pub chance: f32,
+    #[serde(default = "default_drop_count")]
+    pub min: u32,
+    #[serde(default = "default_drop_count")]
+    pub max: u32,
 }
 
 impl EntityDef {
@@ -241,12 +554,40 @@ impl EntityDef {
     }
 
     pub fn draw_with_alpha(&self, pos: Vec2, alpha: f32) {
-        let tex = &self.texture.texture;
-        let draw = &self.texture.draw;
+        self.draw_with_effects(pos, alpha, 0.0, 0.0, None);
+    }
+
+    /// Draws `frame` of the animation, or the resting one, with the hit flash and death
+    /// dissolve, both from 0 to 1.
+    pub fn draw_with_effects(&self, pos: Vec2, alpha: f32, flash: f32, dissolve: f32, frame: Option<usize>) {
+        let (sprite, offset, dest_size) = self.sprite_frame(frame);
+        let pos = pos + offset;
+        if (flash > 0.0 || dissolve > 0.0)
+            && crate::shader::with_sprite_effect(flash, dissolve, sprite.uv_rect(), || {
+                self.draw_sprite(&sprite, pos, dest_size, alpha)
+            })
+        {
+            return;
+        }
+        self.draw_sprite(&sprite, pos, dest_size, alpha * (1.0 - dissolve));
+    }
 
-        let dest = draw.dest_size.or_else(|| Some(vec2(tex.width(), tex.height())));
+    /// The sprite for `frame` of the animation, or the resting one, with how far it's shifted
+    /// from the entity's position and its size on screen. Without an animation, the whole
+    /// texture.
+    pub fn sprite_frame(&self, frame: Option<usize>) -> (Sprite, Vec2, Option<Vec2>) {
+        let Some(animation) = self.animation.as_ref() else {
+            return (self.texture.sprite.clone(), Vec2::ZERO, self.texture.draw.dest_size);
+        };
+        let index = frame.unwrap_or_else(|| animation.frame_at(animation.pick(false), 0.0));
+        let (sprite, offset, size) = animation.cut(&self.texture.sprite, index, self.texture.draw.dest_size);
+        (sprite, offset, Some(size))
+    }
+
+    fn draw_sprite(&self, sprite: &Sprite, pos: Vec2, dest_size: Option<Vec2>, alpha: f32) {
+        let draw = &self.texture.draw;
         let params = DrawTextureParams {
-            dest_size: dest,
+            dest_size,
             rotation: draw.rotation,
             flip_x: draw.flip_x,
             flip_y: draw.flip_y,
@@ -256,13 +597,7 @@ impl EntityDef {
         let mut color = draw.color;
         color.a *= alpha.clamp(0.0, 1.0);
 
-        draw_texture_ex(
-            tex,
-            pos.x + draw.offset.x,
-            pos.y + draw.offset.y,
-            color,
-            params,
-        );
+        sprite.draw(pos.x + draw.offset.x, pos.y + draw.offset.y, color, params);
     }
 
     pub fn world_hitbox(&self, pos: Vec2) -> Rect {
@@ -286,8 +621,12 @@ pub struct BehaviorRuntime {
 
 #[derive(Clone, Copy)]
 pub struct PlayerTarget {
+    /// Which player: 0 for the first, 1 for the second in local co-op.
+    pub index: u8,
     pub pos: Vec2,
     pub hitbox: Rect,
+    /// Seconds the player ignores further hits after taking one.
+    pub iframes: f32,
 }
 
 #[derive(Clone, Copy)]
@@ -298,6 +637,8 @@ pub struct EntityTarget {
     pub pos: Vec2,
     pub hitbox: Rect,
     pub alive: bool,
+    /// Seconds the entity ignores further hits after taking one.
+    pub iframes: f32,
 }
 
 #[derive(Clone, Copy)]
@@ -328,6 +669,17 @@ impl Target {
 pub struct DamageEvent {
     pub amount: f32,
     pub target: Target,
+    /// Rolled from the attacker's `crit_chance`; the amount already includes the multiplier.
+    pub crit: bool,
+    /// Uid of the entity that dealt it.
+    pub source: Option<u64>,
+}
+
+/// Damage dealt to breakable map structures overlapping `area`.
+pub struct TileDamageEvent {
+    pub area: Rect,
+    pub amount: f32,
+    pub hit: StructureHit,
 }
 
 pub struct EntityInstance {
@@ -345,9 +697,50 @@ pub struct EntityInstance {
     pub dynamic_collision_scratch: Vec<Rect>,
     pub current_target: Option<Target>,
     pub dynamic_target_timer: f32,
-    pub contact_cooldown: f32,
     pub dash_trail: Option<ParticleEmitter>,
     pub dealt_damage_last_tick: bool,
+    /// Externally issued move order (command id, world target); overrides behavior steering.
+    pub move_order: Option<(u64, Vec2)>,
+    /// Id of a move order that arrived this tick, for result reporting.
+    pub completed_order: Option<u64>,
+    /// World positions queued by the worker layer, followed while no move order is active.
+    pub waypoints: Vec<Vec2>,
+    /// Recent AI decisions, shown in the inspector.
+    pub timeline: AiTimeline,
+    /// Seconds since the last damage taken, for the hit flash.
+    pub hit_age: f32,
+    /// Seconds into the death effect, once hp has run out.
+    pub dying: Option<f32>,
+    /// Seconds until the next footstep while walking.
+    pub footstep_timer: f32,
+    /// How far above `pos` the sprite is drawn, for hovering and jumps. Only the sprite moves;
+    /// the hitbox and shadow stay on the ground.
+    pub z: f32,
+    /// Seconds into the hover bob.
+    hover_time: f32,
+    /// The def's animation playing, and seconds into it.
+    animation: Option<usize>,
+    animation_time: f32,
+    /// Uids of the entities this one has spawned.
+    pub spawned: Vec<u64>,
+    spawn_timer: f32,
+    /// Seconds left of the def's `lifetime`.
+    lifetime_left: Option<f32>,
+    /// One per attack in the def.
+    pub attacks: Vec<AttackState>,
+    pub companion: CompanionState,
+    /// Whether the player is riding it; it's carried along under them and doesn't act.
+    pub ridden: bool,
+    /// Pairings at the breeding station it descends from; 0 if it was never bred.
+    pub generation: u32,
+    pub xp: f32,
+    pub level: u32,
+    /// Uid of the entity that last hurt it, credited with the kill if it dies.
+    pub last_attacker: Option<u64>,
+    /// Rounds loaded into a turret.
+    pub ammo: u32,
+    /// Set while the structure it stands on is out of power.
+    pub unpowered: bool,
     dealt_damage_pending: bool,
     dash_cooldown_memory: HashMap<String, f32>,
 }
@@ -357,11 +750,14 @@ impl EntityInstance {
         name == "dash_at_target" || name == "curve_dash_at_target"
     }
 
+    /// Thinks and moves. Only this entity changes; anything meant for the rest of the world
+    /// goes into `out`, so entities can update side by side.
     pub fn update(
         &mut self,
         dt: f32,
         db: &EntityDatabase,
-        ctx: &mut EntityContext,
+        ctx: &EntityContext,
+        out: &mut EntityCommands,
         map: &crate::map::TileMap,
         registry: &MovementRegistry,
     ) {
@@ -385,9 +781,18 @@ impl EntityInstance {
         } else {
             self.dynamic_target_timer = 0.0;
         }
-        self.current_target = ctx.resolve_target(db, self, dynamic_targeting, force_retarget);
-        if self.contact_cooldown > 0.0 {
-            self.contact_cooldown = (self.contact_cooldown - dt).max(0.0);
+        self.timeline.advance(dt);
+        let previous_target = describe_target(self.current_target);
+        self.current_target = match &db.entities[self.def].turret {
+            Some(turret) => crate::turret::acquire(self, &db.entities[self.def], turret, ctx),
+            None => ctx.resolve_target(db, self, dynamic_targeting, force_retarget, out),
+        };
+        let target = describe_target(self.current_target);
+        if target != previous_target {
+            self.timeline.record(AiEvent::TargetChanged {
+                from: previous_target,
+                to: target,
+            });
         }
 
         let def = &db.entities[self.def];
@@ -415,6 +820,7 @@ impl EntityInstance {
             {
                 synced.push(existing.remove(index));
             } else {
+                self.timeline.record(AiEvent::BehaviorStarted(desired.name.clone()));
                 let restored_cooldown = if Self::is_dash_cooldown_behavior(&desired.name) {
                     self.dash_cooldown_memory
                         .get(&desired.name)
@@ -434,6 +840,7 @@ impl EntityInstance {
             }
         }
         for stale in existing {
+            self.timeline.record(AiEvent::BehaviorStopped(stale.name.clone()));
             if Self::is_dash_cooldown_behavior(&stale.name) && stale.cooldown > 0.0 {
                 self.dash_cooldown_memory
                     .insert(stale.name.clone(), stale.cooldown);
@@ -445,8 +852,12 @@ impl EntityInstance {
         for behavior in behaviors.iter_mut() {
             let func = behavior.func;
             let params = std::mem::take(&mut behavior.params);
+            let was_dashing = behavior.timer > 0.0;
             (func)(self, behavior, dt, &params, ctx);
             behavior.params = params;
+            if !was_dashing && behavior.timer > 0.0 && Self::is_dash_behavior(&behavior.name) {
+                self.timeline.record(AiEvent::DashTriggered(behavior.name.clone()));
+            }
             if Self::is_dash_cooldown_behavior(&behavior.name) && behavior.cooldown > 0.0 {
                 self.dash_cooldown_memory
                     .insert(behavior.name.clone(), behavior.cooldown);
@@ -454,6 +865,23 @@ impl EntityInstance {
         }
         self.behaviors = behaviors;
 
+        if let Some((order_id, target)) = self.move_order {
+            let to_target = target - self.pos;
+            if to_target.length() <= MOVE_ORDER_ARRIVE_DISTANCE {
+                self.move_order = None;
+                self.completed_order = Some(order_id);
+            } else {
+                self.vel = to_target.normalize() * self.speed;
+            }
+        } else if let Some(&next) = self.waypoints.first() {
+            let to_next = next - self.pos;
+            if to_next.length() <= MOVE_ORDER_ARRIVE_DISTANCE {
+                self.waypoints.remove(0);
+            } else {
+                self.vel = to_next.normalize() * self.speed;
+            }
+        }
+
         let mut max_speed = self.speed.max(1.0);
         for behavior in self.behaviors.iter() {
             if (behavior.name == "dash_at_target" || behavior.name == "curve_dash_at_target")
@@ -493,6 +921,8 @@ impl EntityInstance {
         if speed > max_speed {
             self.vel = self.vel / speed * max_speed;
         }
+        crate::companion::run_companion(self, &db.entities[self.def], dt, ctx);
+        crate::attack::run_attacks(self, &db.entities[self.def], dt, ctx, out);
 
         let def = &db.entities[self.def];
         self.dynamic_collision_scratch.clear();
@@ -510,6 +940,16 @@ impl EntityInstance {
         });
         if phasing_dash_active {
             self.pos += self.vel * dt;
+            // Dashes plough through fragile structures; `structure_damage` is per second of contact.
+            let structure_damage = self.stats.get("structure_damage", 0.0);
+            let area = def.world_hitbox(self.pos);
+            if structure_damage > 0.0 && map.overlaps_breakable_structure(area, StructureHit::Dash) {
+                out.tile_damage.push(TileDamageEvent {
+                    area,
+                    amount: structure_damage * dt,
+                    hit: StructureHit::Dash,
+                });
+            }
         } else if def.collides || !self.dynamic_collision_scratch.is_empty() {
             let mut pos = self.pos;
             let mut vel = self.vel;
@@ -566,15 +1006,198 @@ impl EntityInstance {
             self.pos += self.vel * dt;
         }
 
-        self.apply_contact_damage(ctx, db);
+        // Choppers wear down whatever breakable structure they touch; `chop_damage` is per second.
+        // Enemies also wear down what the player built.
+        let chop_damage = self.stats.get("chop_damage", 0.0);
+        let area = def.world_hitbox(self.pos);
+        let hit = if def.kind == EntityKind::Enemy { StructureHit::Enemy } else { StructureHit::Tool };
+        if chop_damage > 0.0 && map.overlaps_breakable_structure(area, hit) {
+            out.tile_damage.push(TileDamageEvent {
+                area,
+                amount: chop_damage * dt,
+                hit,
+            });
+        }
+
+        self.tick_spawner(def, dt, ctx, out);
+        if let Some(left) = self.lifetime_left.as_mut() {
+            *left -= dt;
+            if *left <= 0.0 {
+                out.queued.push(EntityCommand::Despawn { uid: self.uid });
+            }
+        }
+
+        self.apply_contact_damage(ctx, out, db);
+    }
+
+    fn tick_spawner(&mut self, def: &EntityDef, dt: f32, ctx: &EntityContext, out: &mut EntityCommands) {
+        let Some(spawner) = def.spawner.as_ref() else {
+            return;
+        };
+        self.spawned
+            .retain(|uid| ctx.entities.iter().any(|candidate| candidate.id == *uid && candidate.alive));
+        self.spawn_timer -= dt;
+        if self.spawn_timer > 0.0 {
+            return;
+        }
+        self.spawn_timer = spawner.interval.max(0.1);
+        let room = spawner.max_alive.saturating_sub(self.spawned.len());
+        let origin = def.world_hitbox(self.pos).center();
+        for _ in 0..spawner.count.min(room) {
+            let angle = macroquad::rand::gen_range(0.0, std::f32::consts::TAU);
+            let distance = macroquad::rand::gen_range(0.0, spawner.radius.max(0.0));
+            let pos = self.pos + vec2(angle.cos(), angle.sin()) * distance;
+            out.queued.push(EntityCommand::Spawn {
+                def: spawner.entity.clone(),
+                pos,
+            });
+            if let Some(id) = &spawner.particles {
+                out.queued.push(EntityCommand::EmitParticles { id: id.clone(), pos: origin });
+            }
+            if let Some(id) = &spawner.sound {
+                out.queued.push(EntityCommand::PlaySound { id: id.clone(), pos: origin });
+            }
+        }
     }
 
     pub fn draw(&self, db: &EntityDatabase) {
-        db.entities[self.def].draw(self.pos);
+        self.draw_with_alpha(db, 1.0);
     }
 
     pub fn draw_with_alpha(&self, db: &EntityDatabase, alpha: f32) {
-        db.entities[self.def].draw_with_alpha(self.pos, alpha);
+        let def = &db.entities[self.def];
+        let feedback = def.feedback;
+        let flash = if feedback.flash_time > 0.0 {
+            1.0 - self.hit_age / feedback.flash_time
+        } else {
+            0.0
+        };
+        let progress = match self.dying {
+            Some(elapsed) if feedback.death_time > 0.0 => (elapsed / feedback.death_time).min(1.0),
+            Some(_) => 1.0,
+            None => 0.0,
+        };
+        let pos = self.pos - vec2(0.0, self.z);
+        let frame = self.animation_frame(db);
+        match feedback.death {
+            DeathEffect::Dissolve => def.draw_with_effects(pos, alpha, flash.max(0.0), progress, frame),
+            _ => def.draw_with_effects(pos, alpha * (1.0 - progress), flash.max(0.0), 0.0, frame),
+        }
+    }
+
+    /// Draws the shadow under the hitbox, smaller the higher the sprite is and fading out with
+    /// the death effect.
+    pub fn draw_shadow(&self, db: &EntityDatabase, alpha: f32) {
+        let def = &db.entities[self.def];
+        let hitbox = self.hitbox(db);
+        let width = def.shadow.width.unwrap_or(hitbox.w);
+        let scale = (1.0 - self.z.max(0.0) / SHADOW_SHRINK_HEIGHT).max(SHADOW_MIN_SCALE);
+        let fade = match self.dying {
+            Some(elapsed) if def.feedback.death_time > 0.0 => 1.0 - (elapsed / def.feedback.death_time).min(1.0),
+            Some(_) => 0.0,
+            None => 1.0,
+        };
+        draw_blob_shadow(
+            vec2(hitbox.center().x, hitbox.bottom()),
+            width * scale,
+            def.shadow.opacity * alpha * fade,
+        );
+    }
+
+    /// Whether the entity ran out of hp and is playing its death effect. Dying entities no
+    /// longer act, collide or take damage.
+    pub fn is_dying(&self) -> bool {
+        self.dying.is_some()
+    }
+
+    pub fn start_dying(&mut self) {
+        self.dying.get_or_insert(0.0);
+    }
+
+    /// Whether the death effect has finished and the entity can be removed.
+    pub fn death_finished(&self, db: &EntityDatabase) -> bool {
+        let feedback = db.entities[self.def].feedback;
+        self.dying.is_some_and(|elapsed| feedback.death == DeathEffect::None || elapsed >= feedback.death_time)
+    }
+
+    /// Advances the hit flash and death effect timers.
+    pub fn tick_feedback(&mut self, dt: f32) {
+        self.hit_age += dt;
+        if let Some(elapsed) = self.dying.as_mut() {
+            *elapsed += dt;
+        }
+    }
+
+    /// Replaces its stats, e.g. with a bred offspring's, at full health.
+    pub fn set_stats(&mut self, stats: StatBlock) {
+        self.speed = stats.get("speed", self.speed).max(1.0);
+        self.max_hp = stats.get("hp", self.max_hp).max(1.0);
+        self.hp = self.max_hp;
+        self.stats = stats;
+    }
+
+    /// Adds `amount` XP, raising its stats by `growth` for each level it reaches. Returns the
+    /// levels gained.
+    pub fn gain_xp(&mut self, growth: &GrowthDef, amount: f32) -> u32 {
+        self.xp += amount.max(0.0);
+        let level = growth.level_for(self.xp);
+        let gained = level.saturating_sub(self.level);
+        if gained == 0 {
+            return 0;
+        }
+        self.level = level;
+        // Hp and speed may come from the def rather than the stats; start those from the
+        // current values so they're grown instead of replaced.
+        for (key, current) in [("hp", self.max_hp), ("speed", self.speed)] {
+            if !self.stats.has(key) {
+                self.stats.add(key, current);
+            }
+        }
+        for (key, per_level) in &growth.stats {
+            self.stats.add(key, per_level * gained as f32);
+        }
+        let max_hp = self.stats.get("hp", self.max_hp).max(1.0);
+        self.hp = (self.hp + max_hp - self.max_hp).clamp(0.0, max_hp);
+        self.max_hp = max_hp;
+        self.speed = self.stats.get("speed", self.speed).max(1.0);
+        gained
+    }
+
+    /// Scales speed and contact damage up for the rest of the entity's life.
+    pub fn go_berserk(&mut self, berserk: BerserkDef) {
+        self.speed *= berserk.speed.max(0.0);
+        self.stats.scale("speed", berserk.speed.max(0.0));
+        self.stats.scale("damage", berserk.damage.max(0.0));
+    }
+
+    /// Plays the def's `walk` animation while moving and `idle` otherwise, starting over on
+    /// each switch.
+    pub fn tick_animation(&mut self, db: &EntityDatabase, dt: f32) {
+        let Some(animation) = db.entities[self.def].animation.as_ref() else {
+            return;
+        };
+        let picked = animation.pick(self.vel.length_squared() > 1.0);
+        if picked != self.animation {
+            self.animation = picked;
+            self.animation_time = 0.0;
+        }
+        self.animation_time += dt;
+    }
+
+    /// The frame of the def's animation showing now, if it has one.
+    pub fn animation_frame(&self, db: &EntityDatabase) -> Option<usize> {
+        let animation = db.entities[self.def].animation.as_ref()?;
+        Some(animation.frame_at(self.animation, self.animation_time))
+    }
+
+    /// Bobs a hovering entity's `z` around its hover height.
+    pub fn tick_hover(&mut self, db: &EntityDatabase, dt: f32) {
+        let Some(hover) = db.entities[self.def].hover else {
+            return;
+        };
+        self.hover_time += dt;
+        let phase = self.hover_time * hover.bob_speed * std::f32::consts::TAU;
+        self.z = hover.height + phase.sin() * hover.bob;
     }
 
     pub fn hitbox(&self, db: &EntityDatabase) -> Rect {
@@ -582,18 +1205,19 @@ impl EntityInstance {
     }
 
     pub fn is_dashing(&self) -> bool {
-        self.behaviors.iter().any(|behavior| {
-            (behavior.name == "dash_at_target"
-                || behavior.name == "curve_dash_at_target"
-                || behavior.name == "bird_ai"
-                || behavior.name == "virabird_ai")
-                && behavior.timer > 0.0
-        })
+        self.behaviors
+            .iter()
+            .any(|behavior| Self::is_dash_behavior(&behavior.name) && behavior.timer > 0.0)
     }
 
-    fn apply_contact_damage(&mut self, ctx: &mut EntityContext, db: &EntityDatabase) {
+    /// Behaviors whose positive `timer` means a dash is in progress.
+    fn is_dash_behavior(name: &str) -> bool {
+        matches!(name, "dash_at_target" | "curve_dash_at_target" | "bird_ai" | "virabird_ai")
+    }
+
+    fn apply_contact_damage(&self, ctx: &EntityContext, out: &mut EntityCommands, db: &EntityDatabase) {
         let damage = self.stats.get("damage", 0.0);
-        if damage <= 0.0 || self.contact_cooldown > 0.0 {
+        if damage <= 0.0 {
             return;
         }
         let Some(target) = self.current_target else {
@@ -607,16 +1231,16 @@ impl EntityInstance {
         let has_specific_target_flags = target_enemy || target_friend || target_misc;
         let target_player = (def_flags & DEF_FLAG_TARGET_PLAYER) != 0;
 
-        let target_hitbox = match target {
+        let (hit_target, target_hitbox, iframes) = match target {
             Target::Position(_) => return,
-            Target::Player(_) => {
+            Target::Player(target) => {
                 if !target_player {
                     return;
                 }
-                let Some(player) = ctx.player else {
+                let Some(player) = ctx.nearest_player(target.pos) else {
                     return;
                 };
-                player.hitbox
+                (HitTarget::Player(player.index), player.hitbox, player.iframes)
             }
             Target::Entity(target_entity) => {
                 let Some(target_live) = ctx
@@ -652,13 +1276,21 @@ impl EntityInstance {
                 if !kind_ok {
                     return;
                 }
-                target_live.hitbox
+                (HitTarget::Entity(target_live.id), target_live.hitbox, target_live.iframes)
             }
         };
+        if !ctx.damage_cooldowns.can_hit(self.uid, hit_target) {
+            return;
+        }
 
         let hb = db.entities[self.def].world_hitbox(self.pos);
         if hb.overlaps(&target_hitbox) {
-            ctx.damage_events.push(DamageEvent { amount: damage, target });
+            let crit = crate::helpers::random_f32() < self.stats.get("crit_chance", 0.0);
+            let amount = if crit {
+                damage * self.stats.get("crit_multiplier", 2.0)
+            } else {
+                damage
+            };
             let mut hit_cooldown = 0.3f32;
             for behavior in &self.behaviors {
                 let is_dash = behavior.name == "dash_at_target" || behavior.name == "curve_dash_at_target";
@@ -674,10 +1306,24 @@ impl EntityInstance {
                     .max(0.0);
                 hit_cooldown = hit_cooldown.max(dash_hit_cd);
             }
-            self.contact_cooldown = hit_cooldown;
-            self.dealt_damage_pending = true;
+            out.hits.push(PendingHit {
+                event: DamageEvent {
+                    amount,
+                    target,
+                    crit,
+                    source: Some(self.uid),
+                },
+                target: hit_target,
+                cooldown: hit_cooldown,
+                iframes,
+            });
         }
     }
+
+    /// Marks a hit from this tick's update as landed, for next tick's `dealt_damage` condition.
+    pub fn confirm_hit(&mut self) {
+        self.dealt_damage_pending = true;
+    }
 }
 
 #[derive(Default)]
@@ -735,22 +1381,134 @@ impl MovementRegistry {
     }
 }
 
+/// The world as every entity sees it during one step, unchanged until they've all updated.
 pub struct EntityContext {
-    pub player: Option<PlayerTarget>,
+    /// The players that can be targeted, the first one first.
+    pub players: Vec<PlayerTarget>,
     pub target: Option<Target>,
+    /// `entities` by where they stand.
+    pub grid: TargetGrid,
     pub entities: Vec<EntityTarget>,
     pub target_cache: HashMap<(u64, u8), Option<EntityTarget>>,
     pub view_height: f32,
-    pub damage_events: Vec<DamageEvent>,
+    /// Kept across frames like `target_cache`.
+    pub damage_cooldowns: DamageCooldowns,
+    /// Uid of the entity that last hurt the player, while it's remembered.
+    pub player_attacker: Option<u64>,
+}
+
+/// What one entity's update asks of the world, applied in entity order once every update is
+/// done.
+#[derive(Default)]
+pub struct EntityCommands {
+    /// Contact hits that were off cooldown as of the start of the step. Earlier entities' hits
+    /// can still put the target on cooldown before these land.
+    pub hits: Vec<PendingHit>,
+    pub tile_damage: Vec<TileDamageEvent>,
+    /// A nearest-target search to remember in `target_cache`.
+    pub cached_target: Option<((u64, u8), Option<EntityTarget>)>,
+    /// Where the entity was if it started a dash this step, for the dash effect.
+    pub dash_started: Option<Vec2>,
+    pub queued: Vec<EntityCommand>,
+}
+
+impl EntityCommands {
+    pub fn clear(&mut self) {
+        self.hits.clear();
+        self.tile_damage.clear();
+        self.cached_target = None;
+        self.dash_started = None;
+        self.queued.clear();
+    }
+}
+
+/// A change to the world outside the entity that queued it, carried out after the update.
+#[derive(Clone, Debug)]
+pub enum EntityCommand {
+    /// Spawns entity id `def`; the new entity's uid goes into the spawner's `spawned`.
+    Spawn { def: String, pos: Vec2 },
+    /// Removes an entity on the spot, with no death effect or drops.
+    Despawn { uid: u64 },
+    EmitParticles { id: String, pos: Vec2 },
+    PlaySound { id: String, pos: Vec2 },
+    FireProjectile(Projectile),
+}
+
+pub struct PendingHit {
+    pub event: DamageEvent,
+    pub target: HitTarget,
+    /// Seconds before the same entity may hit this target again.
+    pub cooldown: f32,
+    pub iframes: f32,
+}
+
+/// Invulnerability after a hit for entities without an `iframes` stat.
+pub const DEFAULT_IFRAMES: f32 = 0.2;
+
+/// What a hit landed on, keying damage cooldowns.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HitTarget {
+    Player(u8),
+    Entity(u64),
+}
+
+/// Limits how often damage lands: each source waits out its own cooldown per target, and a
+/// target that was just hit ignores every source for its invulnerability window.
+#[derive(Default)]
+pub struct DamageCooldowns {
+    /// Seconds until the source entity may hit the target again.
+    per_source: HashMap<(u64, HitTarget), f32>,
+    invulnerable: HashMap<HitTarget, f32>,
+}
+
+impl DamageCooldowns {
+    pub fn tick(&mut self, dt: f32) {
+        for remaining in self.per_source.values_mut().chain(self.invulnerable.values_mut()) {
+            *remaining -= dt;
+        }
+        self.per_source.retain(|_, remaining| *remaining > 0.0);
+        self.invulnerable.retain(|_, remaining| *remaining > 0.0);
+    }
+
+    pub fn is_invulnerable(&self, target: HitTarget) -> bool {
+        self.invulnerable.contains_key(&target)
+    }
+
+    pub fn can_hit(&self, source: u64, target: HitTarget) -> bool {
+        !self.is_invulnerable(target) && !self.per_source.contains_key(&(source, target))
+    }
+
+    pub fn record_hit(&mut self, source: u64, target: HitTarget, cooldown: f32, iframes: f32) {
+        if cooldown > 0.0 {
+            self.per_source.insert((source, target), cooldown);
+        }
+        if iframes > 0.0 {
+            self.invulnerable.insert(target, iframes);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.per_source.clear();
+        self.invulnerable.clear();
+    }
 }
 
 impl EntityContext {
+    /// The targetable player closest to `pos`.
+    pub fn nearest_player(&self, pos: Vec2) -> Option<PlayerTarget> {
+        self.players
+            .iter()
+            .copied()
+            .min_by(|a, b| a.pos.distance_squared(pos).total_cmp(&b.pos.distance_squared(pos)))
+    }
+
     fn resolve_target(
-        &mut self,
+        &self,
         db: &EntityDatabase,
         entity: &EntityInstance,
         dynamic_targeting: bool,
         force_retarget: bool,
+        out: &mut EntityCommands,
     ) -> Option<Target> {
         if let Some(target) = self.target {
             return Some(target);
@@ -758,7 +1516,7 @@ impl EntityContext {
         let def_flags = db.entities[entity.def].flags;
         let target_player = (def_flags & DEF_FLAG_TARGET_PLAYER) != 0;
         if target_player {
-            return self.player.map(Target::Player);
+            return self.nearest_player(entity.pos).map(Target::Player);
         }
 
         let target_any = (def_flags & DEF_FLAG_TARGET_NEAREST_ENTITY) != 0;
@@ -800,7 +1558,7 @@ impl EntityContext {
 
         let is_current_target_valid = |target: Target| match target {
             Target::Position(_) => false,
-            Target::Player(_) => target_player && self.player.is_some(),
+            Target::Player(_) => target_player && !self.players.is_empty(),
             Target::Entity(target_entity) => self.entities.iter().any(|candidate| {
                 candidate.id == target_entity.id
                     && candidate.alive
@@ -817,7 +1575,7 @@ impl EntityContext {
                 }
             }
             if let Some(current) = entity.current_target {
-                if matches!(current, Target::Player(_)) && target_player && self.player.is_some() {
+                if matches!(current, Target::Player(_)) && target_player && !self.players.is_empty() {
                     return Some(current);
                 }
             }
@@ -915,7 +1673,7 @@ impl EntityContext {
             }
         }
         let resolved = best.map(|(_, target)| target);
-        self.target_cache.insert((entity.uid, mask), resolved);
+        out.cached_target = Some(((entity.uid, mask), resolved));
         resolved.map(Target::Entity)
     }
 }
@@ -927,6 +1685,8 @@ pub struct EntityDatabase {
     trait_lookup: HashMap<String, usize>,
     behavior_lookup: HashMap<String, usize>,
     entity_lookup: HashMap<String, usize>,
+    /// Scales enemies as they spawn.
+    difficulty: Difficulty,
 }
 
 impl EntityDatabase {
@@ -944,80 +1704,50 @@ impl EntityDatabase {
         };
         let (trait_lookup, behavior_lookup) = build_lookups(&traits, &behaviors);
 
-        let mut entities = Vec::new();
-        let mut entity_lookup = HashMap::new();
+        let mut loader = EntityLoader {
+            trait_lookup: &trait_lookup,
+            behavior_lookup: &behavior_lookup,
+            traits: &traits,
+            behaviors: &behaviors,
+            entities: Vec::new(),
+            entity_lookup: HashMap::new(),
+            atlas: AtlasBuilder::default(),
+            sprites: Vec::new(),
+        };
         if cfg!(target_arch = "wasm32") {
             let root = data_path(&root_path.to_string_lossy());
-            load_entities_from_dir_wasm(
-                &format!("{}/enemy", root),
-                EntityKind::Enemy,
-                &trait_lookup,
-                &behavior_lookup,
-                &traits,
-                &behaviors,
-                &mut entities,
-                &mut entity_lookup,
-            )
-            .await?;
-            load_entities_from_dir_wasm(
-                &format!("{}/friend", root),
-                EntityKind::Friend,
-                &trait_lookup,
-                &behavior_lookup,
-                &traits,
-                &behaviors,
-                &mut entities,
-                &mut entity_lookup,
-            )
-            .await?;
-            load_entities_from_dir_wasm(
-                &format!("{}/misc", root),
-                EntityKind::Misc,
-                &trait_lookup,
-                &behavior_lookup,
-                &traits,
-                &behaviors,
-                &mut entities,
-                &mut entity_lookup,
-            )
-            .await?;
+            load_entities_from_dir_wasm(&format!("{}/enemy", root), EntityKind::Enemy, &mut loader).await?;
+            load_entities_from_dir_wasm(&format!("{}/friend", root), EntityKind::Friend, &mut loader).await?;
+            load_entities_from_dir_wasm(&format!("{}/misc", root), EntityKind::Misc, &mut loader).await?;
         } else {
             let enemy_dir = root_path.join("enemy");
             let friend_dir = root_path.join("friend");
             let misc_dir = root_path.join("misc");
-            load_entities_from_dir(
-                &enemy_dir,
-                EntityKind::Enemy,
-                &trait_lookup,
-                &behavior_lookup,
-                &traits,
-                &behaviors,
-                &mut entities,
-                &mut entity_lookup,
-            )
-            .await?;
-            load_entities_from_dir(
-                &friend_dir,
-                EntityKind::Friend,
-                &trait_lookup,
-                &behavior_lookup,
-                &traits,
-                &behaviors,
-                &mut entities,
-                &mut entity_lookup,
-            )
-            .await?;
-            load_entities_from_dir(
-                &misc_dir,
-                EntityKind::Misc,
-                &trait_lookup,
-                &behavior_lookup,
-                &traits,
-                &behaviors,
-                &mut entities,
-                &mut entity_lookup,
-            )
-            .await?;
+            load_entities_from_dir(&enemy_dir, EntityKind::Enemy, &mut loader).await?;
+            load_entities_from_dir(&friend_dir, EntityKind::Friend, &mut loader).await?;
+            load_entities_from_dir(&misc_dir, EntityKind::Misc, &mut loader).await?;
+        }
+        let EntityLoader {
+            mut entities,
+            entity_lookup,
+            mut atlas,
+            sprites,
+            ..
+        } = loader;
+        let mut attack_sprites = Vec::new();
+        for (def_index, def) in entities.iter().enumerate() {
+            for (attack_index, attack) in def.attacks.iter().enumerate() {
+                if let Some(path) = attack.kind.sprite_path() {
+                    attack_sprites.push((def_index, attack_index, atlas.add(&asset_path(path)).await));
+                }
+            }
+        }
+        let atlas = atlas.build();
+        for (def, handle) in entities.iter_mut().zip(sprites) {
+            def.texture.sprite = atlas.sprite(handle);
+        }
+        for (def_index, attack_index, handle) in attack_sprites {
+            entities[def_index].attacks[attack_index].kind.set_texture(atlas.sprite(handle));
         }
 
         Ok(Self {
@@ -1027,9 +1757,18 @@ impl EntityDatabase {
             trait_lookup,
             behavior_lookup,
             entity_lookup,
+            difficulty: Difficulty::default(),
         })
     }
 
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    pub fn set_difficulty(&mut self, difficulty: Difficulty) {
+        self.difficulty = difficulty;
+    }
+
     pub fn entity_id(&self, id: &str) -> Option<usize> {
         self.entity_lookup.get(id).copied()
     }
@@ -1042,6 +1781,7 @@ impl EntityDatabase {
             trait_lookup: HashMap::new(),
             behavior_lookup: HashMap::new(),
             entity_lookup: HashMap::new(),
+            difficulty: Difficulty::default(),
         }
     }
 
@@ -1058,6 +1798,10 @@ impl EntityDatabase {
         for &trait_idx in &def.traits {
             stats.merge(&self.traits[trait_idx].stats);
         }
+        if def.kind == EntityKind::Enemy {
+            stats.scale("hp", self.difficulty.enemy_health);
+            stats.scale("damage", self.difficulty.enemy_damage);
+        }
         let max_hp = stats.get("hp", 1.0).max(1.0);
 
         let mut behaviors = Vec::new();
@@ -1080,8 +1824,9 @@ impl EntityDatabase {
             cooldown: 0.0,
         });
 
+        let uid = next_entity_id();
         Some(EntityInstance {
-            uid: next_entity_id(),
+            uid,
             def: index,
             pos,
             vel: Vec2::ZERO,
@@ -1095,9 +1840,32 @@ impl EntityDatabase {
             dynamic_collision_scratch: Vec::with_capacity(25),
             current_target: None,
             dynamic_target_timer: 0.0,
-            contact_cooldown: 0.0,
             dash_trail: None,
             dealt_damage_last_tick: false,
+            move_order: None,
+            completed_order: None,
+            waypoints: Vec::new(),
+            timeline: AiTimeline::default(),
+            hit_age: f32::INFINITY,
+            dying: None,
+            footstep_timer: 0.0,
+            z: def.hover.map_or(0.0, |hover| hover.height),
+            // Spread out so a flock doesn't bob in step.
+            hover_time: (uid % 1000) as f32 * 0.137,
+            animation: None,
+            animation_time: 0.0,
+            spawned: Vec::new(),
+            spawn_timer: def.spawner.as_ref().map_or(0.0, |spawner| spawner.interval),
+            lifetime_left: def.lifetime,
+            attacks: vec![AttackState::default(); def.attacks.len()],
+            companion: CompanionState::default(),
+            ridden: false,
+            generation: 0,
+            xp: 0.0,
+            level: 1,
+            last_attacker: None,
+            ammo: 0,
+            unpowered: false,
             dealt_damage_pending: false,
             dash_cooldown_memory: HashMap::new(),
         })
@@ -1106,9 +1874,10 @@ impl EntityDatabase {
 
 impl EntityInstance {
     pub fn apply_damage(&mut self, amount: f32) {
-        if amount <= 0.0 {
+        if amount <= 0.0 || self.is_dying() {
             return;
         }
+        self.hit_age = 0.0;
         self.hp = (self.hp - amount).max(0.0);
     }
 }
@@ -1157,9 +1926,7 @@ fn collect_dynamic_collision_hitboxes(
     out.reserve(ctx.entities.len() + 1);
 
     if !no_player_collision && !target_is_player {
-        if let Some(player) = ctx.player {
-            out.push(player.hitbox);
-        }
+        out.extend(ctx.players.iter().map(|player| player.hitbox));
     }
 
     for other in &ctx.entities {
@@ -1348,9 +2115,9 @@ fn eval_condition(name: &str, value: Option<f32>, entity: &EntityInstance, ctx:
             entity.pos.distance(target) <= range
         }
         "player_in_range" => ctx
-            .player
-            .map(|player| entity.pos.distance_squared(player.pos) <= in_range_sq)
-            .unwrap_or(false),
+            .players
+            .iter()
+            .any(|player| entity.pos.distance_squared(player.pos) <= in_range_sq),
         "entity_in_range" => any_kind_in_range(None),
         "enemy_in_range" => any_kind_in_range(Some(EntityKind::Enemy)),
         "friend_in_range" => any_kind_in_range(Some(EntityKind::Friend)),
@@ -1549,18 +2316,32 @@ async fn load_traits_wasm(dir: &str) -> Result<Vec<TraitDef>, EntityLoadError> {
     Ok(traits)
 }
 
+/// The traits and behaviors entity files refer to, and what loading them builds up.
+struct EntityLoader<'a> {
+    trait_lookup: &'a HashMap<String, usize>,
+    behavior_lookup: &'a HashMap<String, usize>,
+    traits: &'a [TraitDef],
+    behaviors: &'a [BehaviorDef],
+    entities: Vec<EntityDef>,
+    entity_lookup: HashMap<String, usize>,
+    atlas: AtlasBuilder,
+    sprites: Vec<AtlasHandle>,
+}
+
 async fn load_entities_from_dir_wasm(
     dir: &str,
     fallback_kind: EntityKind,
-    trait_lookup: &HashMap<String, usize>,
-    behavior_lookup: &HashMap<String, usize>,
-    traits: &[TraitDef],
-    behaviors: &[BehaviorDef],
-    entities: &mut Vec<EntityDef>,
-    entity_lookup: &mut HashMap<String, usize>,
+    loader: &mut EntityLoader<'_>,
 ) -> Result<(), EntityLoadError> {
+    let EntityLoader {
+        trait_lookup,
+        behavior_lookup,
+        traits,
+        behaviors,
+        ..
+    } = *loader;
     let files = if dir.ends_with("/enemy") {
-        load_wasm_manifest_files(dir, &["virat.yaml", "virabird.yaml"]).await
+        load_wasm_manifest_files(dir, &["virat.yaml", "virabird.yaml", "virat_nest.yaml"]).await
     } else {
         load_wasm_manifest_files(dir, &[]).await
     };
@@ -1616,10 +2397,11 @@ async fn load_entities_from_dir_wasm(
             None
         };
 
-        let tex = load_texture(&asset_path(&raw.visuals.sprite))
-            .await
-            .map_err(|err| EntityLoadError::Texture(err.to_string()))?;
-        tex.set_filter(FilterMode::Nearest);
+        loader.sprites.push(loader.atlas.add(&asset_path(&raw.visuals.sprite)).await);
+        let animation = match raw.visuals.animation.as_ref() {
+            Some(path) => Some(AnimationSet::from_aseprite(&load_animation(path).await?)?),
+            None => None,
+        };
 
         let draw_params = raw.visuals.draw_params.unwrap_or_default();
         let color = Color::from_rgba(
@@ -1655,7 +2437,11 @@ async fn load_entities_from_dir_wasm(
             name: raw.name.unwrap_or_else(|| raw.id.clone()),
             kind,
             texture: TextureInfo {
-                texture: tex,
+                // Filled in from the atlas once every entity is loaded.
+                sprite: Sprite {
+                    texture: blank_texture(),
+                    source: Rect::default(),
+                },
                 draw: DrawParams {
                     dest_size,
                     rotation: draw_params.rotation,
@@ -1666,6 +2452,7 @@ async fn load_entities_from_dir_wasm(
                     offset: vec2(draw_params.offset[0], draw_params.offset[1]),
                 },
             },
+            animation,
             hitbox,
             traits: trait_indices,
             trait_tags: tags,
@@ -1674,26 +2461,50 @@ async fn load_entities_from_dir_wasm(
             speed: raw.speed,
             collides,
             flags,
+            drops: raw.drops,
+            feedback: raw.feedback,
+            footstep_interval: raw.footstep_interval,
+            shadow: raw.visuals.shadow,
+            hover: raw.visuals.hover,
+            spawner: raw.spawner,
+            lifetime: raw.lifetime,
+            attacks: raw.attacks,
+            capture: raw.capture,
+            worker: raw.worker,
+            companion: raw.companion,
+            mount: raw.mount,
+            breed: raw.breed,
+            turret: raw.turret,
+            xp: raw.xp.max(0.0),
+            growth: raw.growth,
         };
 
-        let index = entities.len();
-        entities.push(def);
-        entity_lookup.insert(raw.id, index);
+        let index = loader.entities.len();
+        loader.entities.push(def);
+        loader.entity_lookup.insert(raw.id, index);
     }
 
     Ok(())
 }
 
+async fn load_animation(path: &str) -> Result<String, EntityLoadError> {
+    load_string(&asset_path(path))
+        .await
+        .map_err(|e| EntityLoadError::Io(std::io::Error::other(format!("{path}: {e}"))))
+}
+
 async fn load_entities_from_dir(
     dir: &Path,
     fallback_kind: EntityKind,
-    trait_lookup: &HashMap<String, usize>,
-    behavior_lookup: &HashMap<String, usize>,
-    traits: &[TraitDef],
-    behaviors: &[BehaviorDef],
-    entities: &mut Vec<EntityDef>,
-    entity_lookup: &mut HashMap<String, usize>,
+    loader: &mut EntityLoader<'_>,
 ) -> Result<(), EntityLoadError> {
+    let EntityLoader {
+        trait_lookup,
+        behavior_lookup,
+        traits,
+        behaviors,
+        ..
+    } = *loader;
     if !dir.exists() {
         return Ok(());
     }
@@ -1750,10 +2561,11 @@ async fn load_entities_from_dir(
             None
         };
 
-        let tex = load_texture(&asset_path(&raw.visuals.sprite))
-            .await
-            .map_err(|err| EntityLoadError::Texture(err.to_string()))?;
-        tex.set_filter(FilterMode::Nearest);
+        loader.sprites.push(loader.atlas.add(&asset_path(&raw.visuals.sprite)).await);
+        let animation = match raw.visuals.animation.as_ref() {
+            Some(path) => Some(AnimationSet::from_aseprite(&load_animation(path).await?)?),
+            None => None,
+        };
 
         let draw_params = raw.visuals.draw_params.unwrap_or_default();
         let color = Color::from_rgba(
@@ -1790,7 +2602,11 @@ async fn load_entities_from_dir(
             name: raw.name.unwrap_or_else(|| raw.id.clone()),
             kind,
             texture: TextureInfo {
-                texture: tex,
+                // Filled in from the atlas once every entity is loaded.
+                sprite: Sprite {
+                    texture: blank_texture(),
+                    source: Rect::default(),
+                },
                 draw: DrawParams {
                     dest_size,
                     rotation: draw_params.rotation,
@@ -1801,6 +2617,7 @@ async fn load_entities_from_dir(
                     offset: vec2(draw_params.offset[0], draw_params.offset[1]),
                 },
             },
+            animation,
             hitbox,
             traits: trait_indices,
             trait_tags: tags,
@@ -1809,15 +2626,29 @@ async fn load_entities_from_dir(
             speed: raw.speed,
             collides,
             flags,
+            drops: raw.drops,
+            feedback: raw.feedback,
+            footstep_interval: raw.footstep_interval,
+            shadow: raw.visuals.shadow,
+            hover: raw.visuals.hover,
+            spawner: raw.spawner,
+            lifetime: raw.lifetime,
+            attacks: raw.attacks,
+            capture: raw.capture,
+            worker: raw.worker,
+            companion: raw.companion,
+            mount: raw.mount,
+            breed: raw.breed,
+            turret: raw.turret,
+            xp: raw.xp.max(0.0),
+            growth: raw.growth,
         };
 
-        let index = entities.len();
-        entities.push(def);
-        entity_lookup.insert(raw.id, index);
+        let index = loader.entities.len();
+        loader.entities.push(def);
+        loader.entity_lookup.insert(raw.id, index);
     }
 
-    let _ = traits;
-
     Ok(())
 }
 
@@ -1868,13 +2699,48 @@ struct EntityFile {
     behavior: Option<BehaviorNode>,
     #[serde(default)]
     behavior_id: Option<String>,
+    #[serde(default)]
+    drops: Vec<EntityDrop>,
+    #[serde(default)]
+    feedback: HitFeedback,
+    #[serde(default)]
+    footstep_interval: Option<f32>,
+    #[serde(default)]
+    spawner: Option<SpawnerDef>,
+    #[serde(default)]
+    lifetime: Option<f32>,
+    #[serde(default)]
+    attacks: Vec<AttackDef>,
+    #[serde(default)]
+    capture: Option<CaptureDef>,
+    #[serde(default)]
+    worker: bool,
+    #[serde(default)]
+    companion: Option<CompanionDef>,
+    #[serde(default)]
+    mount: Option<MountDef>,
+    #[serde(default)]
+    breed: Option<BreedDef>,
+    #[serde(default)]
+    turret: Option<TurretDef>,
+    #[serde(default)]
+    xp: f32,
+    #[serde(default)]
+    growth: Option<GrowthDef>,
 }
 
 #[derive(Deserialize)]
 struct VisualsFile {
     sprite: String,
+    /// Aseprite's json export for `sprite`, to animate it.
+    #[serde(default)]
+    animation: Option<String>,
     #[serde(default)]
     draw_params: Option<DrawParamsFile>,
+    #[serde(default)]
+    shadow: ShadowDef,
+    #[serde(default)]
+    hover: Option<HoverDef>,
 }
 
 #[derive(Default, Deserialize)]
@@ -1914,3 +2780,69 @@ fn default_color() -> [u8; 4] {
 fn default_speed() -> f32 {
     80.0
 }
+
+fn default_drop_chance() -> f32 {
+    1.0
+}
+
+fn default_drop_count() -> u32 {
+    1
+}
+
+#[cfg(test)]
+impl EntityDatabase {
+    /// A database holding only `id`, a bare entity of `kind` with nothing to draw.
+    pub(crate) fn with_test_entity(id: &str, kind: EntityKind) -> Self {
+        let mut db = Self::empty();
+        db.entities.push(EntityDef {
+            id: id.to_string(),
+            name: id.to_string(),
+            kind,
+            texture: TextureInfo {
+                // Tests run without a GL context, so no texture of macroquad's own.
+                sprite: Sprite {
+                    texture: Texture2D::from_miniquad_texture(miniquad::TextureId::from_raw_id(
+                        miniquad::RawId::OpenGl(0),
+                    )),
+                    source: Rect::default(),
+                },
+                draw: DrawParams {
+                    dest_size: None,
+                    rotation: 0.0,
+                    flip_x: false,
+                    flip_y: false,
+                    pivot: None,
+                    color: WHITE,
+                    offset: Vec2::ZERO,
+                },
+            },
+            animation: None,
+            hitbox: Rect::new(-4.0, -4.0, 8.0, 8.0),
+            traits: Vec::new(),
+            trait_tags: HashMap::new(),
+            behavior_tree: None,
+            base_stats: StatBlock::default(),
+            speed: 1.0,
+            collides: false,
+            flags: 0,
+            drops: Vec::new(),
+            feedback: HitFeedback::default(),
+            footstep_interval: None,
+            shadow: ShadowDef::default(),
+            hover: None,
+            spawner: None,
+            lifetime: None,
+            attacks: Vec::new(),
+            capture: None,
+            worker: false,
+            companion: None,
+            mount: None,
+            breed: None,
+            turret: None,
+            xp: 0.0,
+            growth: None,
+        });
+        db.entity_lookup.insert(id.to_string(), 0);
+        db
+    }
+}
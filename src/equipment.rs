@@ -0,0 +1,159 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::atlas::Sprite;
+use crate::item::{Inventory, ItemDatabase};
+use crate::player::{PlayerStats, StatModifiers};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EquipSlot {
+    Tool,
+    Armor,
+    Trinket,
+}
+
+impl EquipSlot {
+    pub const ALL: [Self; 3] = [Self::Tool, Self::Armor, Self::Trinket];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Tool => "tool",
+            Self::Armor => "armor",
+            Self::Trinket => "trinket",
+        }
+    }
+}
+
+/// An item's `equip` block as written in its YAML.
+#[derive(Deserialize)]
+pub struct EquipFile {
+    pub slot: EquipSlot,
+    #[serde(default)]
+    pub stats: StatModifiers,
+    #[serde(default)]
+    pub overlay: Option<OverlayFile>,
+}
+
+#[derive(Deserialize)]
+pub struct OverlayFile {
+    pub sprite: String,
+    /// World units from the top left of the player's sprite.
+    #[serde(default)]
+    pub offset: [f32; 2],
+    /// World units; defaults to the sprite's size.
+    #[serde(default)]
+    pub size: Option<[f32; 2]>,
+    #[serde(default = "default_overlay_color")]
+    pub color: [f32; 4],
+}
+
+fn default_overlay_color() -> [f32; 4] {
+    [1.0, 1.0, 1.0, 1.0]
+}
+
+/// Lets an item be worn in one of the player's equipment slots.
+#[derive(Clone)]
+pub struct EquipDef {
+    pub slot: EquipSlot,
+    pub stats: StatModifiers,
+    pub overlay: Option<EquipOverlay>,
+}
+
+/// Sprite drawn over the player while an item is worn.
+#[derive(Clone)]
+pub struct EquipOverlay {
+    pub sprite: Sprite,
+    pub offset: Vec2,
+    pub size: Vec2,
+    pub color: Color,
+}
+
+impl EquipOverlay {
+    pub fn new(file: &OverlayFile, sprite: Sprite) -> Self {
+        let [r, g, b, a] = file.color;
+        Self {
+            size: file.size.map(|[w, h]| vec2(w, h)).unwrap_or(sprite.size()),
+            offset: vec2(file.offset[0], file.offset[1]),
+            color: Color::new(r, g, b, a),
+            sprite,
+        }
+    }
+}
+
+/// Item ids worn in each slot.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Equipment {
+    #[serde(default)]
+    tool: Option<String>,
+    #[serde(default)]
+    armor: Option<String>,
+    #[serde(default)]
+    trinket: Option<String>,
+}
+
+impl Equipment {
+    pub fn get(&self, slot: EquipSlot) -> Option<&str> {
+        match slot {
+            EquipSlot::Tool => self.tool.as_deref(),
+            EquipSlot::Armor => self.armor.as_deref(),
+            EquipSlot::Trinket => self.trinket.as_deref(),
+        }
+    }
+
+    fn slot_mut(&mut self, slot: EquipSlot) -> &mut Option<String> {
+        match slot {
+            EquipSlot::Tool => &mut self.tool,
+            EquipSlot::Armor => &mut self.armor,
+            EquipSlot::Trinket => &mut self.trinket,
+        }
+    }
+
+    fn worn<'a>(&'a self, db: &'a ItemDatabase) -> impl Iterator<Item = &'a EquipDef> {
+        EquipSlot::ALL
+            .into_iter()
+            .filter_map(|slot| self.get(slot))
+            .filter_map(|item| db.get(item)?.equip.as_ref())
+    }
+
+    /// Wears one of the item in inventory `index`, putting back whatever its slot held.
+    pub fn equip(&mut self, db: &ItemDatabase, inventory: &mut Inventory, index: usize) -> Result<(), String> {
+        let Some(stack) = inventory.get(index) else {
+            return Err("nothing to equip".to_string());
+        };
+        let item = stack.item.clone();
+        let Some(equip) = db.get(&item).and_then(|def| def.equip.as_ref()) else {
+            return Err(format!("{item} can't be worn"));
+        };
+        let mut trial = inventory.clone();
+        trial.consume(index, 1);
+        if let Some(old) = self.get(equip.slot)
+            && trial.add(db, old, 1) > 0
+        {
+            return Err("inventory full".to_string());
+        }
+        *inventory = trial;
+        *self.slot_mut(equip.slot) = Some(item);
+        Ok(())
+    }
+
+    /// Puts the item in `slot` back in `inventory`, if there's room.
+    pub fn unequip(&mut self, db: &ItemDatabase, inventory: &mut Inventory, slot: EquipSlot) -> Result<(), String> {
+        let Some(item) = self.get(slot) else {
+            return Ok(());
+        };
+        if inventory.add(db, item, 1) > 0 {
+            return Err("inventory full".to_string());
+        }
+        *self.slot_mut(slot) = None;
+        Ok(())
+    }
+
+    /// `base` with every worn item's modifiers added on.
+    pub fn apply(&self, db: &ItemDatabase, base: PlayerStats) -> PlayerStats {
+        self.worn(db).fold(base, |stats, equip| stats.with(&equip.stats))
+    }
+
+    pub fn overlays(&self, db: &ItemDatabase) -> Vec<EquipOverlay> {
+        self.worn(db).filter_map(|equip| equip.overlay.clone()).collect()
+    }
+}
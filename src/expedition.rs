@@ -0,0 +1,174 @@
+use macroquad::prelude::*;
+use crate::font::{TextStyle, draw_text_styled, measure_text};
+use crate::item::{Inventory, ItemStack};
+
+pub const EXTRACTION_BEACON: &str = "extraction_beacon";
+/// Seconds a knocked-out player lies there before being carried home.
+const KNOCKOUT_SECONDS: f32 = 2.0;
+const TIMER_COLOR: Color = Color::new(1.0, 0.95, 0.6, 1.0);
+const URGENT_COLOR: Color = Color::new(1.0, 0.35, 0.3, 1.0);
+/// Seconds left when the timer turns red.
+const URGENT_SECONDS: f32 = 30.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Extracted,
+    KnockedOut,
+    TimedOut,
+}
+
+/// How a finished expedition went, shown once the player is back on the farm.
+pub struct Results {
+    pub outcome: Outcome,
+    pub seconds: f32,
+    pub kills: u32,
+    /// Items gained out there; kept if extracted, lost otherwise.
+    pub loot: Vec<ItemStack>,
+}
+
+struct Run {
+    elapsed: f32,
+    limit: f32,
+    /// What the player carried in, to tell the loot apart.
+    carried: Vec<ItemStack>,
+    kills: u32,
+    beacon: Vec2,
+    knocked_out: f32,
+}
+
+/// One trip out: a timer to reach the extraction beacon before, and a summary at the end.
+/// Getting knocked out or running out of time loses everything picked up along the way.
+#[derive(Default)]
+pub struct Expedition {
+    run: Option<Run>,
+    results: Option<Results>,
+}
+
+impl Expedition {
+    pub fn start(&mut self, inventory: &Inventory, minutes: f32, beacon: Vec2) {
+        self.run = Some(Run {
+            elapsed: 0.0,
+            limit: minutes.max(0.5) * 60.0,
+            carried: inventory.stacks().to_vec(),
+            kills: 0,
+            beacon,
+            knocked_out: 0.0,
+        });
+        self.results = None;
+    }
+
+    pub fn count_kills(&mut self, kills: usize) {
+        if let Some(run) = self.run.as_mut() {
+            run.kills += kills as u32;
+        }
+    }
+
+    /// Counts the timer down. Returns how the run ended once the player has to be sent home:
+    /// out of time, or knocked out for a moment.
+    pub fn update(&mut self, dt: f32, player_dead: bool) -> Option<Outcome> {
+        let run = self.run.as_mut()?;
+        run.elapsed += dt;
+        if player_dead {
+            run.knocked_out += dt;
+            return (run.knocked_out >= KNOCKOUT_SECONDS).then_some(Outcome::KnockedOut);
+        }
+        (run.elapsed >= run.limit).then_some(Outcome::TimedOut)
+    }
+
+    /// Ends the run, taking the loot back out of `inventory` unless the player extracted.
+    pub fn finish(&mut self, outcome: Outcome, inventory: &mut Inventory) {
+        let Some(run) = self.run.take() else {
+            return;
+        };
+        let mut loot: Vec<ItemStack> = Vec::new();
+        for stack in inventory.stacks() {
+            if loot.iter().any(|gained| gained.item == stack.item) {
+                continue;
+            }
+            let before: u32 =
+                run.carried.iter().filter(|carried| carried.item == stack.item).map(|carried| carried.count).sum();
+            let gained = inventory.count(&stack.item).saturating_sub(before);
+            if gained > 0 {
+                loot.push(ItemStack {
+                    item: stack.item.clone(),
+                    count: gained,
+                });
+            }
+        }
+        if outcome != Outcome::Extracted {
+            inventory.remove_all(&loot);
+        }
+        self.results = Some(Results {
+            outcome,
+            seconds: run.elapsed,
+            kills: run.kills,
+            loot,
+        });
+    }
+
+    /// Enter or a click puts the results away.
+    pub fn update_results(&mut self) {
+        if self.results.is_some() && (is_key_pressed(KeyCode::Enter) || is_mouse_button_pressed(MouseButton::Left)) {
+            self.results = None;
+        }
+    }
+
+    pub fn showing_results(&self) -> bool {
+        self.results.is_some()
+    }
+
+    /// Draws the time left and which way the beacon is while out, and the results once home.
+    pub fn draw(&self, player: Vec2, tile_size: f32) {
+        if let Some(run) = self.run.as_ref() {
+            let left = (run.limit - run.elapsed).max(0.0);
+            let distance = (run.beacon.distance(player) / tile_size).round() as i32;
+            let arrow = direction_arrow(run.beacon - player);
+            let clock = format!("{}:{:02}", left as u32 / 60, left as u32 % 60);
+            let text = format!("Extract in {clock}   beacon {arrow} {distance} tiles");
+            let color = if left <= URGENT_SECONDS { URGENT_COLOR } else { TIMER_COLOR };
+            let width = measure_text(&text, 20.0).width;
+            draw_text_styled(&text, (screen_width() - width) * 0.5, 56.0, 20.0, color, TextStyle::SHADOWED);
+        }
+        if let Some(results) = self.results.as_ref() {
+            draw_results(results);
+        }
+    }
+}
+
+fn direction_arrow(toward: Vec2) -> &'static str {
+    if toward.length_squared() < 1.0 {
+        return "*";
+    }
+    let octant = ((toward.y.atan2(toward.x) / std::f32::consts::FRAC_PI_4).round() as i32).rem_euclid(8);
+    ["E", "SE", "S", "SW", "W", "NW", "N", "NE"][octant as usize]
+}
+
+fn draw_results(results: &Results) {
+    let mut lines = vec![
+        format!("Time out: {}:{:02}", results.seconds as u32 / 60, results.seconds as u32 % 60),
+        format!("Enemies defeated: {}", results.kills),
+    ];
+    if results.loot.is_empty() {
+        lines.push("Loot: none".to_string());
+    } else {
+        let loot: Vec<String> = results.loot.iter().map(|stack| format!("{} {}", stack.count, stack.item)).collect();
+        lines.push(format!("Loot: {}", loot.join(", ")));
+    }
+    let (title, color) = match results.outcome {
+        Outcome::Extracted => ("Extracted!", GREEN),
+        Outcome::KnockedOut => ("Knocked out - the loot was lost", URGENT_COLOR),
+        Outcome::TimedOut => ("Out of time - the loot was lost", URGENT_COLOR),
+    };
+    let panel = Rect::new(screen_width() * 0.5 - 240.0, screen_height() * 0.5 - 90.0, 480.0, 180.0);
+    draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.05, 0.05, 0.08, 0.9));
+    draw_rectangle_lines(panel.x, panel.y, panel.w, panel.h, 2.0, Color::new(1.0, 1.0, 1.0, 0.3));
+    draw_text_styled("Expedition results", panel.x + 16.0, panel.y + 28.0, 22.0, GOLD, TextStyle::SHADOWED);
+    draw_text_styled(title, panel.x + 16.0, panel.y + 56.0, 20.0, color, TextStyle::SHADOWED);
+    for (i, line) in lines.iter().enumerate() {
+        let y = panel.y + 86.0 + i as f32 * 22.0;
+        draw_text_styled(line, panel.x + 16.0, y, 18.0, WHITE, TextStyle::SHADOWED);
+    }
+    let hint = "Enter to continue";
+    let width = measure_text(hint, 14.0).width;
+    draw_text_styled(hint, panel.right() - width - 12.0, panel.bottom() - 10.0, 14.0, LIGHTGRAY, TextStyle::SHADOWED);
+}
@@ -0,0 +1,101 @@
+use macroquad::prelude::*;
+
+const FOG_COLOR: Color = Color::new(0.02, 0.02, 0.05, 0.85);
+
+/// Which tiles of the expedition the player has been near, a bit each. Kept in the farm save
+/// so the map stays uncovered from one trip to the next.
+#[derive(Clone, Default)]
+pub struct Exploration {
+    width: usize,
+    height: usize,
+    bits: Vec<u8>,
+}
+
+impl Exploration {
+    /// Sizes it for a map of `width` by `height` tiles, forgetting everything if that's a change.
+    pub fn fit(&mut self, width: usize, height: usize) {
+        if self.width != width || self.height != height {
+            self.width = width;
+            self.height = height;
+            self.bits = vec![0; (width * height).div_ceil(8)];
+        }
+    }
+
+    /// Marks the tiles set in `bytes`, as written by `to_bytes` for a map of the same size.
+    pub fn merge(&mut self, bytes: &[u8]) {
+        if bytes.len() != self.bits.len() {
+            return;
+        }
+        for (bits, saved) in self.bits.iter_mut().zip(bytes) {
+            *bits |= saved;
+        }
+    }
+
+    pub fn to_bytes(&self) -> &[u8] {
+        &self.bits
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn is_explored(&self, x: usize, y: usize) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        let index = y * self.width + x;
+        self.bits[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// Uncovers the tiles within `radius` tiles of world `pos`. Returns whether any were new.
+    pub fn reveal(&mut self, pos: Vec2, radius: f32, tile_size: f32) -> bool {
+        if self.width == 0 || self.height == 0 {
+            return false;
+        }
+        let center = pos / tile_size;
+        let (min_x, min_y) = ((center.x - radius).max(0.0) as usize, (center.y - radius).max(0.0) as usize);
+        let max_x = ((center.x + radius).max(0.0) as usize).min(self.width - 1);
+        let max_y = ((center.y + radius).max(0.0) as usize).min(self.height - 1);
+        let mut changed = false;
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if (vec2(x as f32, y as f32) + 0.5).distance(center) > radius {
+                    continue;
+                }
+                let index = y * self.width + x;
+                let bit = 1 << (index % 8);
+                if self.bits[index / 8] & bit == 0 {
+                    self.bits[index / 8] |= bit;
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Darkens the unexplored tiles inside `view`, in world space, a run of tiles per rect.
+    pub fn draw_fog(&self, view: Rect, tile_size: f32) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+        let min_x = (view.x / tile_size).max(0.0) as usize;
+        let min_y = (view.y / tile_size).max(0.0) as usize;
+        let max_x = ((view.right() / tile_size).max(0.0) as usize + 1).min(self.width);
+        let max_y = ((view.bottom() / tile_size).max(0.0) as usize + 1).min(self.height);
+        for y in min_y..max_y {
+            let mut x = min_x;
+            while x < max_x {
+                if self.is_explored(x, y) {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while x < max_x && !self.is_explored(x, y) {
+                    x += 1;
+                }
+                let (left, top) = (start as f32 * tile_size, y as f32 * tile_size);
+                draw_rectangle(left, top, (x - start) as f32 * tile_size, tile_size, FOG_COLOR);
+            }
+        }
+    }
+}
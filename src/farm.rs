@@ -0,0 +1,294 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::build::BuiltStructure;
+use crate::crop::{CropDatabase, CropField};
+use crate::explore::Exploration;
+use crate::map::{LayerKind, TileMap};
+use crate::paged::PagedGrid;
+use crate::storage::ChestStorage;
+
+/// Background tile a tilled cell is painted with.
+pub const SOIL_TILE: u8 = 46;
+/// Seconds of play per in-game day.
+pub const DAY_LENGTH: f32 = 600.0;
+pub const DAYS_PER_SEASON: u32 = 28;
+pub const MAX_FERTILITY: u8 = 3;
+
+const TILLED: u8 = 0b0001;
+const WATERED: u8 = 0b0010;
+const FERTILITY_SHIFT: u8 = 2;
+const FERTILITY_MASK: u8 = 0b1100;
+
+/// Per-tile farming state packed into one byte: tilled, watered, and fertility (0-3).
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct Soil(u8);
+
+impl Soil {
+    pub fn tilled(self) -> bool {
+        self.0 & TILLED != 0
+    }
+
+    pub fn watered(self) -> bool {
+        self.0 & WATERED != 0
+    }
+
+    pub fn fertility(self) -> u8 {
+        (self.0 & FERTILITY_MASK) >> FERTILITY_SHIFT
+    }
+
+    fn with_fertility(self, fertility: u8) -> Self {
+        Self((self.0 & !FERTILITY_MASK) | (fertility.min(MAX_FERTILITY) << FERTILITY_SHIFT))
+    }
+
+    /// Crop growth multiplier: dry soil doesn't grow, each fertility level adds a quarter.
+    pub fn growth_rate(self) -> f32 {
+        if !self.tilled() || !self.watered() {
+            return 0.0;
+        }
+        1.0 + self.fertility() as f32 * 0.25
+    }
+}
+
+/// Farming tile states layered over the farm map.
+pub struct SoilLayer {
+    width: usize,
+    height: usize,
+    cells: PagedGrid<Soil>,
+}
+
+impl Default for SoilLayer {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+impl SoilLayer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: PagedGrid::new(width, height, Soil::default()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn from_bytes(width: usize, height: usize, bytes: &[u8]) -> Self {
+        let cells: Vec<Soil> = bytes.iter().map(|&bits| Soil(bits)).collect();
+        Self {
+            width,
+            height,
+            cells: PagedGrid::from_row_major(width, height, &cells, Soil::default()),
+        }
+    }
+
+    /// The same soil on a bigger map, with the new cells untilled.
+    pub fn grown(&self, width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: self.cells.grown(width, height, Soil::default()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.cells.to_row_major().into_iter().map(|soil| soil.0).collect()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Soil {
+        if x >= self.width || y >= self.height {
+            return Soil::default();
+        }
+        self.cells.get(x, y)
+    }
+
+    /// Whether the cell is bare, unblocked `ground_tile` that could be tilled.
+    pub fn can_till(&self, map: &TileMap, x: usize, y: usize, ground_tile: u8) -> bool {
+        x < self.width
+            && y < self.height
+            && !self.get(x, y).tilled()
+            && map.tile_at(LayerKind::Background, x, y) == ground_tile
+            && !map.is_solid(x, y)
+    }
+
+    /// Turns a bare `ground_tile` cell into soil. Returns whether anything changed.
+    pub fn till(&mut self, map: &mut TileMap, x: usize, y: usize, ground_tile: u8) -> bool {
+        if !self.can_till(map, x, y, ground_tile) {
+            return false;
+        }
+        map.set_tile(LayerKind::Background, x, y, SOIL_TILE);
+        self.cells.set(x, y, Soil(TILLED).with_fertility(1))
+    }
+
+    pub fn can_water(&self, x: usize, y: usize) -> bool {
+        let soil = self.get(x, y);
+        soil.tilled() && !soil.watered()
+    }
+
+    pub fn water(&mut self, x: usize, y: usize) -> bool {
+        if !self.can_water(x, y) {
+            return false;
+        }
+        let soil = self.get(x, y);
+        self.cells.set(x, y, Soil(soil.0 | WATERED))
+    }
+
+    pub fn can_fertilize(&self, x: usize, y: usize) -> bool {
+        let soil = self.get(x, y);
+        soil.tilled() && soil.fertility() < MAX_FERTILITY
+    }
+
+    pub fn fertilize(&mut self, x: usize, y: usize) -> bool {
+        if !self.can_fertilize(x, y) {
+            return false;
+        }
+        let soil = self.get(x, y);
+        self.cells.set(x, y, soil.with_fertility(soil.fertility() + 1))
+    }
+
+    /// Dries every watered tile at the start of a new day.
+    pub fn start_day(&mut self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let soil = self.cells.get(x, y);
+                if soil.watered() {
+                    self.cells.set(x, y, Soil(soil.0 & !WATERED));
+                }
+            }
+        }
+        self.cells.compact();
+    }
+
+    /// Darkens watered tiles inside `view`.
+    pub fn draw_watered(&self, view: Rect, tile_size: f32) {
+        if self.is_empty() {
+            return;
+        }
+        let min_x = (view.x / tile_size).floor().max(0.0) as usize;
+        let min_y = (view.y / tile_size).floor().max(0.0) as usize;
+        let max_x = ((view.right() / tile_size).ceil().max(0.0) as usize).min(self.width);
+        let max_y = ((view.bottom() / tile_size).ceil().max(0.0) as usize).min(self.height);
+        let tint = Color::new(0.1, 0.1, 0.25, 0.35);
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                if self.cells.get(x, y).watered() {
+                    draw_rectangle(x as f32 * tile_size, y as f32 * tile_size, tile_size, tile_size, tint);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Season {
+    Spring,
+    Summer,
+    Fall,
+    Winter,
+}
+
+impl Season {
+    pub const ALL: [Season; 4] = [Season::Spring, Season::Summer, Season::Fall, Season::Winter];
+
+    /// The name it goes by in data files.
+    pub fn key(self) -> &'static str {
+        match self {
+            Season::Spring => "spring",
+            Season::Summer => "summer",
+            Season::Fall => "fall",
+            Season::Winter => "winter",
+        }
+    }
+
+    /// Color multiplied into outdoor tiles while the season lasts.
+    pub fn tint(self) -> Color {
+        match self {
+            Season::Spring => WHITE,
+            Season::Summer => Color::new(1.0, 0.97, 0.86, 1.0),
+            Season::Fall => Color::new(1.0, 0.86, 0.72, 1.0),
+            Season::Winter => Color::new(0.84, 0.9, 1.0, 1.0),
+        }
+    }
+}
+
+/// In-game calendar. Days advance with play time regardless of the current scene, or all at
+/// once by sleeping.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct DayClock {
+    pub day: u32,
+    pub time: f32,
+}
+
+impl DayClock {
+    /// Advances the clock. Returns how many new days began.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        self.time += dt;
+        let mut days = 0;
+        while self.time >= DAY_LENGTH {
+            self.time -= DAY_LENGTH;
+            self.day += 1;
+            days += 1;
+        }
+        days
+    }
+
+    /// How far through the day it is, from 0 to 1.
+    pub fn day_fraction(self) -> f32 {
+        self.time / DAY_LENGTH
+    }
+
+    pub fn season(self) -> Season {
+        Season::ALL[(self.day / DAYS_PER_SEASON) as usize % Season::ALL.len()]
+    }
+
+    /// Zero-based day within the current season.
+    pub fn day_of_season(self) -> u32 {
+        self.day % DAYS_PER_SEASON
+    }
+
+    /// Zero-based year.
+    pub fn year(self) -> u32 {
+        self.day / (DAYS_PER_SEASON * Season::ALL.len() as u32)
+    }
+}
+
+/// Soil, crops, calendar, built structures and chest contents that persist with the farm
+/// save. Kept across scene switches so the in-memory state stays authoritative once the farm
+/// has been loaded.
+#[derive(Default)]
+pub struct FarmState {
+    pub soil: SoilLayer,
+    pub crops: CropField,
+    pub clock: DayClock,
+    pub built: Vec<BuiltStructure>,
+    pub chests: ChestStorage,
+    /// How much of the expedition the player has explored.
+    pub explored: Exploration,
+}
+
+impl FarmState {
+    /// Advances the calendar, starting a new day whenever one passes. Returns how many did.
+    pub fn update(&mut self, dt: f32, crop_db: &CropDatabase) -> u32 {
+        let days = self.clock.advance(dt);
+        for _ in 0..days {
+            self.start_day(crop_db);
+        }
+        days
+    }
+
+    /// Skips the rest of the current day.
+    pub fn sleep(&mut self, crop_db: &CropDatabase) {
+        self.clock.time = 0.0;
+        self.clock.day += 1;
+        self.start_day(crop_db);
+    }
+
+    /// Grows crops on yesterday's watering, then dries the soil.
+    fn start_day(&mut self, crop_db: &CropDatabase) {
+        self.crops.grow(&self.soil, self.clock.season(), crop_db);
+        self.soil.start_day();
+    }
+}
@@ -0,0 +1,364 @@
+use macroquad::prelude::*;
+use std::cell::RefCell;
+
+/// Optional TTF that replaces the built-in pixel font, e.g. a licensed pixel typeface.
+pub const FONT_PATH: &str = "src/assets/fonts/font.ttf";
+/// Marks a color code: `^` followed by one of the codes in [`code_color`], or `^^` for a caret.
+const COLOR_CODE: char = '^';
+const GLYPH_WIDTH: u32 = 5;
+/// Rows per glyph; the last one is below the baseline.
+const GLYPH_HEIGHT: u32 = 8;
+const GLYPH_ASCENT: f32 = 7.0;
+/// Font pixels between glyphs.
+const GLYPH_SPACING: f32 = 1.0;
+const ATLAS_COLUMNS: u32 = 16;
+
+/// 5x8 bitmap glyphs, one byte per row with the high bit on the left.
+const GLYPHS: [(char, [u8; 8]); 103] = [
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('!', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100, 0b00000]),
+    ('"', [0b01010, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('#', [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010, 0b00000]),
+    ('$', [0b00100, 0b01111, 0b10100, 0b01110, 0b00101, 0b11110, 0b00100, 0b00000]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011, 0b00000]),
+    ('&', [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101, 0b00000]),
+    ('\'', [0b00100, 0b00100, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010, 0b00000]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000, 0b00000]),
+    ('*', [0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000, 0b00000]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00100, 0b00100, 0b01000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00100, 0b00000]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000, 0b00000]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110, 0b00000]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110, 0b00000]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111, 0b00000]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110, 0b00000]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010, 0b00000]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110, 0b00000]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110, 0b00000]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110, 0b00000]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100, 0b00000]),
+    (':', [0b00000, 0b00000, 0b00100, 0b00000, 0b00000, 0b00100, 0b00000, 0b00000]),
+    (';', [0b00000, 0b00000, 0b00100, 0b00000, 0b00000, 0b00100, 0b00100, 0b01000]),
+    ('<', [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010, 0b00000]),
+    ('=', [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('>', [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000, 0b00000]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100, 0b00000]),
+    ('@', [0b01110, 0b10001, 0b00001, 0b01101, 0b10101, 0b10101, 0b01110, 0b00000]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001, 0b00000]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110, 0b00000]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110, 0b00000]),
+    ('D', [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100, 0b00000]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111, 0b00000]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000, 0b00000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111, 0b00000]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001, 0b00000]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110, 0b00000]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100, 0b00000]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001, 0b00000]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111, 0b00000]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001, 0b00000]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001, 0b00000]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110, 0b00000]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000, 0b00000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101, 0b00000]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001, 0b00000]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110, 0b00000]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110, 0b00000]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00000]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010, 0b00000]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001, 0b00000]),
+    ('Y', [0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111, 0b00000]),
+    ('[', [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110, 0b00000]),
+    ('\\', [0b00000, 0b10000, 0b01000, 0b00100, 0b00010, 0b00001, 0b00000, 0b00000]),
+    (']', [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110, 0b00000]),
+    ('^', [0b00100, 0b01010, 0b10001, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('_', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111, 0b00000]),
+    ('`', [0b01000, 0b00100, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('a', [0b00000, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111, 0b00000]),
+    ('b', [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b11110, 0b00000]),
+    ('c', [0b00000, 0b00000, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110, 0b00000]),
+    ('d', [0b00001, 0b00001, 0b01101, 0b10011, 0b10001, 0b10001, 0b01111, 0b00000]),
+    ('e', [0b00000, 0b00000, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110, 0b00000]),
+    ('f', [0b00110, 0b01001, 0b01000, 0b11100, 0b01000, 0b01000, 0b01000, 0b00000]),
+    ('g', [0b00000, 0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110]),
+    ('h', [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001, 0b00000]),
+    ('i', [0b00100, 0b00000, 0b01100, 0b00100, 0b00100, 0b00100, 0b01110, 0b00000]),
+    ('j', [0b00010, 0b00000, 0b00110, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('k', [0b10000, 0b10000, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b00000]),
+    ('l', [0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110, 0b00000]),
+    ('m', [0b00000, 0b00000, 0b11010, 0b10101, 0b10101, 0b10001, 0b10001, 0b00000]),
+    ('n', [0b00000, 0b00000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001, 0b00000]),
+    ('o', [0b00000, 0b00000, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110, 0b00000]),
+    ('p', [0b00000, 0b00000, 0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000]),
+    ('q', [0b00000, 0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b00001]),
+    ('r', [0b00000, 0b00000, 0b10110, 0b11001, 0b10000, 0b10000, 0b10000, 0b00000]),
+    ('s', [0b00000, 0b00000, 0b01110, 0b10000, 0b01110, 0b00001, 0b11110, 0b00000]),
+    ('t', [0b01000, 0b01000, 0b11100, 0b01000, 0b01000, 0b01001, 0b00110, 0b00000]),
+    ('u', [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b10011, 0b01101, 0b00000]),
+    ('v', [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00000]),
+    ('w', [0b00000, 0b00000, 0b10001, 0b10001, 0b10101, 0b10101, 0b01010, 0b00000]),
+    ('x', [0b00000, 0b00000, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b00000]),
+    ('y', [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110]),
+    ('z', [0b00000, 0b00000, 0b11111, 0b00010, 0b00100, 0b01000, 0b11111, 0b00000]),
+    ('{', [0b00010, 0b00100, 0b00100, 0b01000, 0b00100, 0b00100, 0b00010, 0b00000]),
+    ('|', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000]),
+    ('}', [0b01000, 0b00100, 0b00100, 0b00010, 0b00100, 0b00100, 0b01000, 0b00000]),
+    ('~', [0b00000, 0b00000, 0b01000, 0b10101, 0b00010, 0b00000, 0b00000, 0b00000]),
+    ('ä', [0b01010, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111, 0b00000]),
+    ('ö', [0b01010, 0b00000, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110, 0b00000]),
+    ('ü', [0b01010, 0b00000, 0b10001, 0b10001, 0b10001, 0b10011, 0b01101, 0b00000]),
+    ('Ä', [0b10001, 0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b00000]),
+    ('Ö', [0b10001, 0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110, 0b00000]),
+    ('Ü', [0b10001, 0b00000, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110, 0b00000]),
+    ('ß', [0b01100, 0b10010, 0b10010, 0b10100, 0b10010, 0b10001, 0b10110, 0b00000]),
+    ('€', [0b00111, 0b01000, 0b11110, 0b01000, 0b11110, 0b01000, 0b00111, 0b00000]),
+];
+
+thread_local! {
+    static FONT: RefCell<Option<GameFont>> = const { RefCell::new(None) };
+}
+
+enum GameFont {
+    Bitmap(Texture2D),
+    Ttf(Font),
+}
+
+/// Outline and drop shadow drawn behind text, in the text's font pixels.
+#[derive(Clone, Copy, Default)]
+pub struct TextStyle {
+    pub outline: Option<Color>,
+    pub shadow: Option<Color>,
+}
+
+impl TextStyle {
+    /// Dark outline that keeps text readable over the world.
+    pub const OUTLINED: Self = Self {
+        outline: Some(Color::new(0.0, 0.0, 0.0, 0.85)),
+        shadow: None,
+    };
+    pub const SHADOWED: Self = Self {
+        outline: None,
+        shadow: Some(Color::new(0.0, 0.0, 0.0, 0.6)),
+    };
+}
+
+/// Loads [`FONT_PATH`] if it exists, otherwise builds the pixel font atlas. Call once before
+/// drawing any text.
+pub async fn load() {
+    let font = match load_ttf_font(&crate::helpers::asset_path(FONT_PATH)).await {
+        Ok(mut font) => {
+            font.set_filter(FilterMode::Nearest);
+            GameFont::Ttf(font)
+        }
+        Err(_) => GameFont::Bitmap(bitmap_atlas()),
+    };
+    FONT.with(|cell| *cell.borrow_mut() = Some(font));
+}
+
+/// Drop-in for macroquad's `draw_text` using the game font. Text may carry color codes.
+pub fn draw_text(text: &str, x: f32, y: f32, font_size: f32, color: Color) -> TextDimensions {
+    draw_text_styled(text, x, y, font_size, color, TextStyle::default())
+}
+
+pub fn draw_text_styled(text: &str, x: f32, y: f32, font_size: f32, color: Color, style: TextStyle) -> TextDimensions {
+    let pixel = pixel_size(font_size);
+    if let Some(shadow) = style.shadow {
+        draw_spans(text, x + pixel, y + pixel, font_size, Some(shadow), color);
+    }
+    if let Some(outline) = style.outline {
+        for (dx, dy) in [(-1.0, 0.0), (1.0, 0.0), (0.0, -1.0), (0.0, 1.0), (-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+            draw_spans(text, x + dx * pixel, y + dy * pixel, font_size, Some(outline), color);
+        }
+    }
+    draw_spans(text, x, y, font_size, None, color)
+}
+
+/// Size of `text` as drawn at `font_size`, ignoring color codes.
+pub fn measure_text(text: &str, font_size: f32) -> TextDimensions {
+    let mut width = 0.0;
+    let mut height: f32 = 0.0;
+    let mut offset_y: f32 = 0.0;
+    for (_, span) in spans(text, WHITE) {
+        let size = measure_span(span, font_size);
+        width += size.width;
+        height = height.max(size.height);
+        offset_y = offset_y.max(size.offset_y);
+    }
+    TextDimensions { width, height, offset_y }
+}
+
+/// Draws each color span in turn. `force` overrides every span's color, for outlines and
+/// shadows.
+fn draw_spans(text: &str, x: f32, y: f32, font_size: f32, force: Option<Color>, color: Color) -> TextDimensions {
+    let mut cursor = x;
+    for (span_color, span) in spans(text, color) {
+        let mut tint = force.unwrap_or(span_color);
+        if force.is_some() {
+            tint.a *= color.a;
+        }
+        cursor += draw_span(span, cursor, y, font_size, tint);
+    }
+    let mut size = measure_text(text, font_size);
+    size.width = cursor - x;
+    size
+}
+
+fn draw_span(text: &str, x: f32, y: f32, font_size: f32, color: Color) -> f32 {
+    FONT.with(|cell| match cell.borrow().as_ref() {
+        Some(GameFont::Ttf(font)) => {
+            macroquad::text::draw_text_ex(
+                text,
+                x,
+                y,
+                TextParams {
+                    font: Some(font),
+                    font_size: font_size.round().max(1.0) as u16,
+                    color,
+                    ..Default::default()
+                },
+            )
+            .width
+        }
+        Some(GameFont::Bitmap(atlas)) => {
+            let pixel = pixel_size(font_size);
+            let top = y - GLYPH_ASCENT * pixel;
+            let mut cursor = x;
+            for ch in text.chars() {
+                let index = glyph_index(ch);
+                let source = Rect::new(
+                    (index as u32 % ATLAS_COLUMNS * GLYPH_WIDTH) as f32,
+                    (index as u32 / ATLAS_COLUMNS * GLYPH_HEIGHT) as f32,
+                    GLYPH_WIDTH as f32,
+                    GLYPH_HEIGHT as f32,
+                );
+                draw_texture_ex(
+                    atlas,
+                    cursor,
+                    top,
+                    color,
+                    DrawTextureParams {
+                        dest_size: Some(vec2(GLYPH_WIDTH as f32, GLYPH_HEIGHT as f32) * pixel),
+                        source: Some(source),
+                        ..Default::default()
+                    },
+                );
+                cursor += (GLYPH_WIDTH as f32 + GLYPH_SPACING) * pixel;
+            }
+            cursor - x
+        }
+        None => macroquad::text::draw_text(text, x, y, font_size, color).width,
+    })
+}
+
+fn measure_span(text: &str, font_size: f32) -> TextDimensions {
+    FONT.with(|cell| match cell.borrow().as_ref() {
+        Some(GameFont::Ttf(font)) => {
+            macroquad::text::measure_text(text, Some(font), font_size.round().max(1.0) as u16, 1.0)
+        }
+        Some(GameFont::Bitmap(_)) => {
+            let pixel = pixel_size(font_size);
+            let chars = text.chars().count() as f32;
+            TextDimensions {
+                width: chars * (GLYPH_WIDTH as f32 + GLYPH_SPACING) * pixel,
+                height: GLYPH_HEIGHT as f32 * pixel,
+                offset_y: GLYPH_ASCENT * pixel,
+            }
+        }
+        None => macroquad::text::measure_text(text, None, font_size.round().max(1.0) as u16, 1.0),
+    })
+}
+
+/// Screen pixels per font pixel. Bitmap glyphs only scale by whole steps so they stay crisp.
+fn pixel_size(font_size: f32) -> f32 {
+    (font_size / GLYPH_HEIGHT as f32).round().max(1.0)
+}
+
+/// Splits `text` at its color codes into runs drawn in one color, starting in `base`.
+fn spans(text: &str, base: Color) -> impl Iterator<Item = (Color, &str)> {
+    let mut rest = text;
+    let mut color = base;
+    std::iter::from_fn(move || {
+        loop {
+            if rest.is_empty() {
+                return None;
+            }
+            let Some(marker) = rest.find(COLOR_CODE) else {
+                return Some((color, std::mem::take(&mut rest)));
+            };
+            if marker > 0 {
+                let (span, tail) = rest.split_at(marker);
+                rest = tail;
+                return Some((color, span));
+            }
+            let mut chars = rest[COLOR_CODE.len_utf8()..].chars();
+            match chars.next() {
+                Some(COLOR_CODE) => {
+                    let span = &rest[COLOR_CODE.len_utf8()..COLOR_CODE.len_utf8() * 2];
+                    rest = &rest[COLOR_CODE.len_utf8() * 2..];
+                    return Some((color, span));
+                }
+                Some(code) => {
+                    if let Some(next) = code_color(code, base) {
+                        color = next;
+                    }
+                    rest = chars.as_str();
+                }
+                None => {
+                    return Some((color, std::mem::take(&mut rest)));
+                }
+            }
+        }
+    })
+}
+
+/// `^0` resets to the text's own color.
+fn code_color(code: char, base: Color) -> Option<Color> {
+    let color = match code {
+        '0' => base,
+        'w' => WHITE,
+        'k' => GRAY,
+        'r' => Color::new(1.0, 0.35, 0.3, 1.0),
+        'g' => Color::new(0.45, 0.9, 0.4, 1.0),
+        'b' => SKYBLUE,
+        'y' => GOLD,
+        'p' => PINK,
+        _ => return None,
+    };
+    Some(Color { a: base.a, ..color })
+}
+
+/// Glyphs missing from the font draw as the last one, a hollow box.
+fn glyph_index(ch: char) -> usize {
+    GLYPHS.iter().position(|(glyph, _)| *glyph == ch).unwrap_or(GLYPHS.len())
+}
+
+fn bitmap_atlas() -> Texture2D {
+    let rows = (GLYPHS.len() as u32 + 1).div_ceil(ATLAS_COLUMNS);
+    let mut image = Image::gen_image_color(
+        (ATLAS_COLUMNS * GLYPH_WIDTH) as u16,
+        (rows * GLYPH_HEIGHT) as u16,
+        BLANK,
+    );
+    const MISSING: [u8; 8] = [0b11111, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11111, 0];
+    let glyphs = GLYPHS.iter().map(|(_, rows)| rows).chain(std::iter::once(&MISSING));
+    for (index, glyph) in glyphs.enumerate() {
+        let origin_x = index as u32 % ATLAS_COLUMNS * GLYPH_WIDTH;
+        let origin_y = index as u32 / ATLAS_COLUMNS * GLYPH_HEIGHT;
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                    image.set_pixel(origin_x + column, origin_y + row as u32, WHITE);
+                }
+            }
+        }
+    }
+    let texture = Texture2D::from_image(&image);
+    texture.set_filter(FilterMode::Nearest);
+    texture
+}
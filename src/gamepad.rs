@@ -0,0 +1,150 @@
+use macroquad::prelude::*;
+use std::sync::{Arc, Mutex};
+use crate::player::PlayerInput;
+
+/// Stick travel, out of 1, ignored as drift.
+const STICK_DEADZONE: f32 = 0.25;
+/// The left stick's axes, then the d-pad's, as Linux numbers them for most pads.
+const STICK_AXES: (usize, usize) = (0, 1);
+const DPAD_AXES: (usize, usize) = (6, 7);
+/// The bottom face button: A on Xbox pads, cross on PlayStation ones.
+const DASH_BUTTON: u8 = 0;
+
+#[derive(Default)]
+struct PadState {
+    connected: bool,
+    axes: [i16; 8],
+    /// Dash presses since the game last asked.
+    dashes: u32,
+}
+
+/// The first gamepad plugged in, read on a thread of its own since macroquad has no input for
+/// them. Only Linux's joystick devices are read; elsewhere the pad never connects and player
+/// two stays on the keyboard.
+pub struct Gamepad {
+    state: Arc<Mutex<PadState>>,
+}
+
+impl Gamepad {
+    /// Starts watching for a pad. One plugged in later, or again after being pulled out, is
+    /// picked up within a second.
+    pub fn open() -> Self {
+        let state = Arc::new(Mutex::new(PadState::default()));
+        #[cfg(target_os = "linux")]
+        {
+            let state = Arc::clone(&state);
+            std::thread::spawn(move || linux::watch(&state));
+        }
+        Self { state }
+    }
+
+    /// What the pad's held since last asked, or None while there's no pad.
+    pub fn input(&self) -> Option<PlayerInput> {
+        let mut pad = self.state.lock().ok()?;
+        if !pad.connected {
+            return None;
+        }
+        let axis = |(x, y): (usize, usize)| vec2(pad.axes[x] as f32, pad.axes[y] as f32) / i16::MAX as f32;
+        let mut movement = axis(STICK_AXES);
+        if movement.length() < STICK_DEADZONE {
+            movement = axis(DPAD_AXES);
+        }
+        if movement.length() < STICK_DEADZONE {
+            movement = Vec2::ZERO;
+        }
+        Some(PlayerInput {
+            movement: movement.clamp_length_max(1.0),
+            dash: std::mem::take(&mut pad.dashes) > 0,
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{DASH_BUTTON, PadState};
+    use std::io::Read;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    const RETRY: Duration = Duration::from_secs(1);
+    const EVENT_BUTTON: u8 = 0x01;
+    const EVENT_AXIS: u8 = 0x02;
+    /// Marks the events a device sends on opening to give its current state.
+    const EVENT_INIT: u8 = 0x80;
+
+    /// Reads `/dev/input/js*` events into `state` for as long as the game runs.
+    pub(super) fn watch(state: &Mutex<PadState>) {
+        loop {
+            let device = (0..4).find_map(|index| std::fs::File::open(format!("/dev/input/js{index}")).ok());
+            if let Some(mut device) = device {
+                // Each event is a timestamp, a value, its kind and which axis or button.
+                let mut event = [0u8; 8];
+                while device.read_exact(&mut event).is_ok() {
+                    let Ok(mut pad) = state.lock() else {
+                        return;
+                    };
+                    pad.connected = true;
+                    let value = i16::from_le_bytes([event[4], event[5]]);
+                    let (kind, number) = (event[6], event[7]);
+                    // A button already held on opening isn't a press.
+                    match (kind & !EVENT_INIT, kind & EVENT_INIT != 0) {
+                        (EVENT_AXIS, _) => {
+                            if let Some(axis) = pad.axes.get_mut(number as usize) {
+                                *axis = value;
+                            }
+                        }
+                        (EVENT_BUTTON, false) if number == DASH_BUTTON && value != 0 => pad.dashes += 1,
+                        _ => {}
+                    }
+                }
+                // Pulled out.
+                let Ok(mut pad) = state.lock() else {
+                    return;
+                };
+                *pad = PadState::default();
+            }
+            std::thread::sleep(RETRY);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pad(axes: [i16; 8], dashes: u32) -> Gamepad {
+        let state = PadState {
+            connected: true,
+            axes,
+            dashes,
+        };
+        Gamepad {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    #[test]
+    fn stick_then_dpad_drive_movement() {
+        let drift = pad([1000, -2000, 0, 0, 0, 0, 0, 0], 0).input().unwrap();
+        assert_eq!(drift.movement, Vec2::ZERO);
+        let dpad = pad([1000, 0, 0, 0, 0, 0, i16::MAX, 0], 0).input().unwrap();
+        assert_eq!(dpad.movement, vec2(1.0, 0.0));
+        let stick = pad([i16::MAX, i16::MAX, 0, 0, 0, 0, -i16::MAX, 0], 0).input().unwrap();
+        assert!((stick.movement - Vec2::ONE.normalize()).length() < 1e-4);
+    }
+
+    #[test]
+    fn a_dash_is_only_taken_once() {
+        let pad = pad([0; 8], 2);
+        assert!(pad.input().unwrap().dash);
+        assert!(!pad.input().unwrap().dash);
+    }
+
+    #[test]
+    fn no_pad_no_input() {
+        let gamepad = Gamepad {
+            state: Arc::new(Mutex::new(PadState::default())),
+        };
+        assert!(gamepad.input().is_none());
+    }
+}
@@ -0,0 +1,320 @@
+use macroquad::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use crate::config::{self, GameConfig};
+use crate::content::ContentManifest;
+use crate::crop::CropDatabase;
+use crate::entity::{Entity, EntityDatabase, MovementRegistry, PlayerTarget, Target};
+use crate::farm::Season;
+use crate::item::ItemDatabase;
+use crate::map::{StructureDef, TileMap, TileSet, load_structures_from_dir};
+use crate::player::{PLAYER_HITBOX, Player};
+use crate::preload::{self, PreloadManifest};
+use crate::raid::Raids;
+use crate::scene::{self, SceneAssets};
+use crate::sim::{self, EntitySim, SimWorld};
+use crate::texture;
+
+const DEFAULT_TICKS: u32 = 600;
+/// Ticks run at a fixed 60 per simulated second so a seed replays the same way on any machine.
+const TICK_DT: f32 = 1.0 / 60.0;
+
+/// `--headless ticks=N seed=S`: step the expedition without drawing and print a JSON report.
+pub struct HeadlessOptions {
+    pub ticks: u32,
+    pub seed: u64,
+}
+
+impl HeadlessOptions {
+    /// None unless `--headless` is among `args`.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut headless = false;
+        let mut options = Self {
+            ticks: DEFAULT_TICKS,
+            seed: 0,
+        };
+        for arg in args {
+            if arg == "--headless" {
+                headless = true;
+                continue;
+            }
+            let Some((key, value)) = arg.split_once('=') else {
+                return Err(format!("unexpected argument: {arg}"));
+            };
+            match key {
+                "ticks" => options.ticks = value.parse().map_err(|_| format!("bad tick count: {value}"))?,
+                "seed" => options.seed = value.parse().map_err(|_| format!("bad seed: {value}"))?,
+                _ => return Err(format!("unknown option: {key}")),
+            }
+        }
+        Ok(headless.then_some(options))
+    }
+}
+
+#[derive(Serialize)]
+struct Report {
+    seed: u64,
+    ticks: u32,
+    dt: f32,
+    player: PlayerReport,
+    entities: EntityReport,
+    structures_broken: usize,
+    perf: PerfReport,
+}
+
+#[derive(Serialize)]
+struct PlayerReport {
+    hp: f32,
+    max_hp: f32,
+    x: f32,
+    y: f32,
+    damage_taken: f32,
+}
+
+#[derive(Serialize)]
+struct EntityReport {
+    alive: usize,
+    deaths: usize,
+    by_id: BTreeMap<String, usize>,
+    positions: Vec<EntityPosition>,
+}
+
+#[derive(Serialize)]
+struct EntityPosition {
+    uid: u64,
+    id: String,
+    x: f32,
+    y: f32,
+    hp: f32,
+}
+
+/// Wall clock milliseconds spent inside the simulation step.
+#[derive(Serialize)]
+struct PerfReport {
+    total_ms: f64,
+    mean_tick_ms: f64,
+    p95_tick_ms: f64,
+    max_tick_ms: f64,
+}
+
+/// The config and definitions the runs without a window share, from the same files the game
+/// loads at startup.
+pub struct WorldData {
+    pub config: GameConfig,
+    /// Tile fresh ground is filled with.
+    pub grass: u8,
+    pub structures: Vec<StructureDef>,
+    pub db: EntityDatabase,
+    pub registry: MovementRegistry,
+    pub items: ItemDatabase,
+    pub content: ContentManifest,
+    pub crops: CropDatabase,
+    pub raids: Raids,
+}
+
+impl WorldData {
+    /// Logs and skips whatever fails to load. Textures aren't uploaded, so this needs no window.
+    pub async fn load() -> Self {
+        texture::go_windowless();
+        let config = GameConfig::load_from(config::CONFIG_PATH)
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("config load failed, using defaults: {err}");
+                GameConfig::default()
+            });
+        let manifest = PreloadManifest::load_from(preload::PRELOAD_PATH)
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("preload manifest load failed, using defaults: {err}");
+                PreloadManifest::default()
+            });
+        let grass = match TileSet::load(&manifest.tileset_json, &manifest.tileset_png).await {
+            Ok(tileset) => tileset.ground_tile(),
+            Err(err) => {
+                eprintln!("tileset load failed: {err}");
+                0
+            }
+        };
+        let structures = load_structures_from_dir(&manifest.structures)
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("structure load failed: {err}");
+                Vec::new()
+            });
+        let db = EntityDatabase::load_from(&manifest.entities)
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("entity load failed: {err}");
+                EntityDatabase::empty()
+            });
+        let items = ItemDatabase::load_from(&manifest.items)
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("item load failed: {err}");
+                ItemDatabase::empty()
+            });
+        let content = ContentManifest::load_from(&manifest.content)
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("content manifest load failed: {err}");
+                ContentManifest::empty()
+            });
+        let crops = CropDatabase::load_from(&manifest.crops)
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("crop load failed: {err}");
+                CropDatabase::empty()
+            });
+        let raids = Raids::load_from(&manifest.raids)
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("raid load failed: {err}");
+                Raids::empty()
+            });
+        Self {
+            config,
+            grass,
+            structures,
+            db,
+            registry: MovementRegistry::new(),
+            items,
+            content,
+            crops,
+            raids,
+        }
+    }
+
+    pub fn scene_assets(&self) -> SceneAssets<'_> {
+        SceneAssets {
+            db: &self.db,
+            registry: &self.registry,
+            structures: &self.structures,
+            content: &self.content,
+            item_db: &self.items,
+            ground_tile: self.grass,
+            tile_size: crate::TILE_SIZE,
+            chunk_alloc_per_frame: self.config.chunk_alloc_per_frame,
+            chunk_rebuild_per_frame: self.config.chunk_rebuild_per_frame,
+        }
+    }
+
+    /// A player at `pos` who's never drawn.
+    pub fn player(&self, pos: Vec2) -> Player {
+        Player::new(pos, texture::blank_texture(), PLAYER_HITBOX, self.config.player)
+    }
+}
+
+/// Builds the expedition and runs it for `options.ticks` ticks with the player standing at the
+/// spawn point, without a window or sound.
+pub async fn run(options: HeadlessOptions) {
+    macroquad::rand::srand(options.seed);
+    let data = WorldData::load().await;
+    let WorldData {
+        config,
+        structures,
+        db,
+        registry,
+        ..
+    } = &data;
+
+    let mut maps = TileMap::new_deferred(1, 1, crate::TILE_SIZE, Vec2::new(crate::TILE_SIZE, crate::TILE_SIZE), 0.0);
+    let mut entities = Vec::<Entity>::new();
+    let assets = data.scene_assets();
+    scene::scene_expedition(&mut maps, &mut entities, assets, Season::Spring, &[], config.extraction_distance);
+    let mut player = data.player(scene::expedition_spawn_point());
+
+    let mut entity_sim = EntitySim::default();
+    // Keep a seed's run repeatable.
+    entity_sim.set_parallel(false);
+    let mut tick_ms = Vec::with_capacity(options.ticks as usize);
+    let mut deaths = 0usize;
+    let mut damage_taken = 0.0f32;
+    let mut structures_broken = 0usize;
+    for _ in 0..options.ticks {
+        let started = miniquad::date::now();
+        let player_target = (player.hp() > 0.0).then(|| PlayerTarget {
+            index: 0,
+            pos: player.position(),
+            hitbox: player.world_hitbox(),
+            iframes: config.player_iframes,
+        });
+        let world = SimWorld {
+            db,
+            registry,
+            map: &maps,
+            view_height: config.camera_fov,
+        };
+        let events = entity_sim.step(&mut entities, world, player_target.as_slice(), TICK_DT);
+        for hit in events.tiles {
+            structures_broken += maps.damage_structures(hit.area, hit.amount, hit.hit, structures).len();
+        }
+        for event in &events.damage {
+            match event.target {
+                Target::Player(_) => {
+                    damage_taken += event.amount.max(0.0);
+                    player.apply_damage(event.amount);
+                }
+                Target::Entity(target) => {
+                    if let Some(ent) = entities
+                        .iter_mut()
+                        .find(|ent| ent.instance.uid == target.id && !ent.instance.is_dying())
+                    {
+                        ent.instance.apply_damage(event.amount);
+                    }
+                }
+                Target::Position(_) => {}
+            }
+        }
+        sim::tick_deaths(&mut entities, db, TICK_DT, |_| deaths += 1);
+        tick_ms.push((miniquad::date::now() - started) * 1000.0);
+    }
+
+    let mut by_id = BTreeMap::new();
+    let mut positions = Vec::new();
+    for ent in entities.iter().filter(|ent| !ent.instance.is_dying()) {
+        let id = &db.entities[ent.instance.def].id;
+        *by_id.entry(id.clone()).or_insert(0) += 1;
+        positions.push(EntityPosition {
+            uid: ent.instance.uid,
+            id: id.clone(),
+            x: ent.instance.pos.x,
+            y: ent.instance.pos.y,
+            hp: ent.instance.hp,
+        });
+    }
+    let total_ms: f64 = tick_ms.iter().sum();
+    tick_ms.sort_by(f64::total_cmp);
+    let report = Report {
+        seed: options.seed,
+        ticks: options.ticks,
+        dt: TICK_DT,
+        player: PlayerReport {
+            hp: player.hp(),
+            max_hp: player.max_hp(),
+            x: player.position().x,
+            y: player.position().y,
+            damage_taken,
+        },
+        entities: EntityReport {
+            alive: positions.len(),
+            deaths,
+            by_id,
+            positions,
+        },
+        structures_broken,
+        perf: PerfReport {
+            total_ms,
+            mean_tick_ms: total_ms / tick_ms.len().max(1) as f64,
+            p95_tick_ms: tick_ms
+                .get((tick_ms.len() as f64 * 0.95) as usize)
+                .or(tick_ms.last())
+                .copied()
+                .unwrap_or(0.0),
+            max_tick_ms: tick_ms.last().copied().unwrap_or(0.0),
+        },
+    };
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => println!("{json}"),
+        Err(err) => eprintln!("headless report failed: {err}"),
+    }
+}
@@ -1,6 +1,6 @@
 use macroquad::prelude::*;
-use macroquad::file::load_string;
 use serde::Deserialize;
+use std::task::{Context, Poll, Waker};
 
 pub fn random_u32() -> u32 {
     macroquad::rand::rand()
@@ -18,12 +18,6 @@ pub fn random_range(min: f32, max: f32) -> f32 {
     min + (max - min) * random_f32()
 }
 
-pub async fn load_single_texture(dir: &str, name: &str) -> Option<Texture2D> {
-    let dir = asset_path(dir);
-    let tile_path = format!("{}/{}.png", dir, name);
-    load_texture(&tile_path).await.ok()
-}
-
 pub fn asset_root() -> &'static str {
     if cfg!(target_arch = "wasm32") {
         "assets"
@@ -62,6 +56,35 @@ pub fn asset_dir(subdir: &str) -> String {
     format!("{}/{}", asset_root(), subdir.trim_start_matches('/'))
 }
 
+/// Reads a file from disk, or fetches it on the web. Unlike macroquad's own this works without a
+/// window, which the server runs without.
+pub async fn load_file(path: &str) -> Result<Vec<u8>, macroquad::Error> {
+    if cfg!(target_arch = "wasm32") {
+        return macroquad::file::load_file(path).await;
+    }
+    std::fs::read(path).map_err(|err| macroquad::Error::FileError {
+        kind: miniquad::fs::Error::IOError(err),
+        path: path.to_string(),
+    })
+}
+
+pub async fn load_string(path: &str) -> Result<String, macroquad::Error> {
+    Ok(String::from_utf8_lossy(&load_file(path).await?).into_owned())
+}
+
+/// Runs `future` on this thread for the runs that skip macroquad's window, and so its executor.
+/// Off the web nothing loading waits on anything, so this never spins for long.
+pub fn block_on<T>(future: impl Future<Output = T>) -> T {
+    let mut future = std::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+            return value;
+        }
+        std::thread::yield_now();
+    }
+}
+
 #[derive(Deserialize)]
 struct WasmIndexFile {
     files: Vec<String>,
@@ -84,16 +107,6 @@ pub async fn load_wasm_manifest_files(dir: &str, fallback: &[&str]) -> Vec<Strin
     fallback.iter().map(|name| (*name).to_string()).collect()
 }
 
-pub async fn draw_hitbox(hitbox: Rect, pos: Vec2) {
-    draw_rectangle(
-        hitbox.x + pos.x,
-        hitbox.y + pos.y,
-        hitbox.w,
-        hitbox.h,
-        Color::from_hex(0xFF0000),
-    );
-}
-
 #[derive(Clone, Copy)]
 pub enum Axis {
     X,
@@ -248,3 +261,236 @@ pub fn clamp_hitbox_to_rect(hitbox: Rect, pos: Vec2, bounds: Rect) -> Vec2 {
 
     vec2(pos.x.clamp(min_x, max_x), pos.y.clamp(min_y, max_y))
 }
+
+/// `~/.cropbots`, where saves and settings live on native builds.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn cropbots_dir() -> Option<std::path::PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(std::path::PathBuf::from(home).join(".cropbots"))
+}
+
+/// Values past this many bytes are split over several storage keys by `wasm_storage_set_large`.
+#[cfg(target_arch = "wasm32")]
+const STORAGE_CHUNK_LEN: usize = 512 * 1024;
+/// Marks a storage value that points at chunks rather than holding the value itself.
+#[cfg(target_arch = "wasm32")]
+const CHUNKED_PREFIX: &str = "cropbots:chunked:";
+
+/// Fails with `StorageFull` once the page has used up its browser storage quota.
+#[cfg(target_arch = "wasm32")]
+pub fn wasm_storage_set_item(key: &str, value: &str) -> std::io::Result<()> {
+    let key_bytes = key.as_bytes();
+    let value_bytes = value.as_bytes();
+    let result = unsafe {
+        mq_storage_set_item(
+            key_bytes.as_ptr(),
+            key_bytes.len(),
+            value_bytes.as_ptr(),
+            value_bytes.len(),
+        )
+    };
+    match result {
+        1 => Ok(()),
+        -1 => Err(std::io::Error::new(std::io::ErrorKind::StorageFull, "browser storage is full")),
+        _ => Err(std::io::Error::other("browser storage unavailable")),
+    }
+}
+
+/// Stores a value of any size as numbered chunks, then points `key` at them. The chunks go
+/// under a new generation and the old ones are removed only once `key` has moved over, so a
+/// write that runs out of quota partway leaves the previous value whole.
+#[cfg(target_arch = "wasm32")]
+pub fn wasm_storage_set_large(key: &str, value: &str) -> std::io::Result<()> {
+    let previous = wasm_storage_get_item(key).and_then(|head| parse_chunk_header(&head));
+    let generation = previous.map_or(0, |(generation, _)| generation + 1);
+    let chunks = split_at_char_boundaries(value, STORAGE_CHUNK_LEN);
+    let remove_new = |count: usize| {
+        for index in 0..count {
+            wasm_storage_remove_item(&chunk_key(key, generation, index));
+        }
+    };
+    for (index, chunk) in chunks.iter().enumerate() {
+        if let Err(err) = wasm_storage_set_item(&chunk_key(key, generation, index), chunk) {
+            remove_new(index);
+            return Err(err);
+        }
+    }
+    if let Err(err) = wasm_storage_set_item(key, &format!("{CHUNKED_PREFIX}{generation}:{}", chunks.len())) {
+        remove_new(chunks.len());
+        return Err(err);
+    }
+    if let Some((generation, count)) = previous {
+        for index in 0..count {
+            wasm_storage_remove_item(&chunk_key(key, generation, index));
+        }
+    }
+    Ok(())
+}
+
+/// Reads a value written by `wasm_storage_set_large`, or a plain value from before chunking.
+#[cfg(target_arch = "wasm32")]
+pub fn wasm_storage_get_large(key: &str) -> Option<String> {
+    let head = wasm_storage_get_item(key)?;
+    let Some((generation, count)) = parse_chunk_header(&head) else {
+        return Some(head);
+    };
+    let mut value = String::new();
+    for index in 0..count {
+        value.push_str(&wasm_storage_get_item(&chunk_key(key, generation, index))?);
+    }
+    Some(value)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn wasm_storage_remove_large(key: &str) {
+    if let Some((generation, count)) = wasm_storage_get_item(key).and_then(|head| parse_chunk_header(&head)) {
+        for index in 0..count {
+            wasm_storage_remove_item(&chunk_key(key, generation, index));
+        }
+    }
+    wasm_storage_remove_item(key);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn chunk_key(key: &str, generation: u32, index: usize) -> String {
+    format!("{key}:{generation}:{index}")
+}
+
+/// `(generation, chunk count)` from a chunked value's head.
+#[cfg(target_arch = "wasm32")]
+fn parse_chunk_header(head: &str) -> Option<(u32, usize)> {
+    let (generation, count) = head.strip_prefix(CHUNKED_PREFIX)?.split_once(':')?;
+    Some((generation.parse().ok()?, count.parse().ok()?))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn split_at_char_boundaries(value: &str, max_len: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = value;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(max_len);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+/// Hands `bytes` to the browser as a file download called `name`.
+#[cfg(target_arch = "wasm32")]
+pub fn wasm_download(name: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let name_bytes = name.as_bytes();
+    let started = unsafe { mq_download(name_bytes.as_ptr(), name_bytes.len(), bytes.as_ptr(), bytes.len()) };
+    if started != 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::other("the browser refused the download"))
+    }
+}
+
+/// Standard base64 with padding, for keeping binary data in string-only browser storage.
+#[cfg(target_arch = "wasm32")]
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let n = (group[0] as u32) << 16
+            | (group.get(1).copied().unwrap_or(0) as u32) << 8
+            | group.get(2).copied().unwrap_or(0) as u32;
+        for i in 0..4 {
+            if i <= group.len() {
+                out.push(ALPHABET[(n >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+    let text = text.trim_end_matches('=').as_bytes();
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    for group in text.chunks(4) {
+        if group.len() == 1 {
+            return None;
+        }
+        let mut n = 0u32;
+        for (i, &c) in group.iter().enumerate() {
+            n |= (value(c)? as u32) << (18 - i * 6);
+        }
+        for i in 0..group.len() - 1 {
+            out.push((n >> (16 - i * 8)) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn wasm_storage_remove_item(key: &str) -> bool {
+    let key_bytes = key.as_bytes();
+    unsafe { mq_storage_remove_item(key_bytes.as_ptr(), key_bytes.len()) != 0 }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn wasm_storage_get_item(key: &str) -> Option<String> {
+    let key_bytes = key.as_bytes();
+    let len = unsafe { mq_storage_get_item_len(key_bytes.as_ptr(), key_bytes.len()) };
+    if len < 0 {
+        return None;
+    }
+
+    let mut buf = vec![0u8; len as usize];
+    let written = unsafe {
+        mq_storage_get_item(
+            key_bytes.as_ptr(),
+            key_bytes.len(),
+            buf.as_mut_ptr(),
+            buf.len(),
+        )
+    };
+    if written < 0 {
+        return None;
+    }
+    let written = written as usize;
+    if written > buf.len() {
+        return None;
+    }
+    buf.truncate(written);
+    String::from_utf8(buf).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe extern "C" {
+    fn mq_storage_set_item(
+        key_ptr: *const u8,
+        key_len: usize,
+        value_ptr: *const u8,
+        value_len: usize,
+    ) -> i32;
+
+    fn mq_storage_remove_item(key_ptr: *const u8, key_len: usize) -> i32;
+
+    fn mq_storage_get_item_len(key_ptr: *const u8, key_len: usize) -> i32;
+
+    fn mq_storage_get_item(
+        key_ptr: *const u8,
+        key_len: usize,
+        out_ptr: *mut u8,
+        out_len: usize,
+    ) -> i32;
+
+    fn mq_download(name_ptr: *const u8, name_len: usize, data_ptr: *const u8, data_len: usize) -> i32;
+}
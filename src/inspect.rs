@@ -0,0 +1,204 @@
+use macroquad::prelude::*;
+use std::collections::VecDeque;
+use crate::entity::{Entity, EntityDatabase, Target};
+use crate::font::draw_text;
+
+const TIMELINE_CAPACITY: usize = 24;
+const PANEL_WIDTH: f32 = 300.0;
+const LINE_HEIGHT: f32 = 16.0;
+
+const HITBOX_COLOR: Color = Color::new(1.0, 0.3, 0.3, 0.8);
+const SELECTED_COLOR: Color = Color::new(1.0, 0.95, 0.3, 1.0);
+const PLAYER_HITBOX_COLOR: Color = Color::new(0.3, 1.0, 0.4, 0.8);
+const TARGET_COLOR: Color = Color::new(1.0, 0.6, 0.2, 0.6);
+const VELOCITY_COLOR: Color = Color::new(0.3, 0.8, 1.0, 0.9);
+const LABEL_COLOR: Color = Color::new(1.0, 1.0, 1.0, 0.85);
+const VIEW_COLOR: Color = Color::new(1.0, 1.0, 1.0, 0.5);
+const CULL_COLOR: Color = Color::new(0.7, 0.4, 1.0, 0.5);
+
+/// An AI decision worth showing in the inspector.
+#[derive(Clone)]
+pub enum AiEvent {
+    TargetChanged { from: String, to: String },
+    BehaviorStarted(String),
+    BehaviorStopped(String),
+    DashTriggered(String),
+}
+
+impl std::fmt::Display for AiEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TargetChanged { from, to } => write!(f, "target {from} -> {to}"),
+            Self::BehaviorStarted(name) => write!(f, "+ {name}"),
+            Self::BehaviorStopped(name) => write!(f, "- {name}"),
+            Self::DashTriggered(name) => write!(f, "dash ({name})"),
+        }
+    }
+}
+
+/// Ring buffer of an entity's most recent AI decisions, stamped with its own age.
+#[derive(Default)]
+pub struct AiTimeline {
+    clock: f32,
+    entries: VecDeque<(f32, AiEvent)>,
+}
+
+impl AiTimeline {
+    pub fn advance(&mut self, dt: f32) {
+        self.clock += dt;
+    }
+
+    pub fn record(&mut self, event: AiEvent) {
+        if self.entries.len() == TIMELINE_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((self.clock, event));
+    }
+
+    /// Entries newest first, with how many seconds ago each happened.
+    pub fn recent(&self) -> impl Iterator<Item = (f32, &AiEvent)> {
+        self.entries
+            .iter()
+            .rev()
+            .map(|(time, event)| (self.clock - time, event))
+    }
+}
+
+/// Short label used to spot target switches, e.g. `player` or `entity#12`.
+pub fn describe_target(target: Option<Target>) -> String {
+    match target {
+        None => "none".to_string(),
+        Some(Target::Position(_)) => "point".to_string(),
+        Some(Target::Player(_)) => "player".to_string(),
+        Some(Target::Entity(entity)) => format!("entity#{}", entity.id),
+    }
+}
+
+/// Debug panel showing an entity's state and its AI timeline. Toggled with F3; inspects
+/// whichever entity was last under the cursor. While open, the world also shows every nearby
+/// entity's hitbox, target, velocity and behavior timers.
+#[derive(Default)]
+pub struct Inspector {
+    open: bool,
+    selected: Option<u64>,
+}
+
+impl Inspector {
+    pub fn update(&mut self, entities: &[Entity], db: &EntityDatabase, mouse_world: Vec2) {
+        if is_key_pressed(KeyCode::F3) {
+            self.open = !self.open;
+        }
+        if !self.open {
+            return;
+        }
+        if let Some(hovered) = entities
+            .iter()
+            .find(|entity| entity.hitbox(db).contains(mouse_world))
+        {
+            self.selected = Some(hovered.instance.uid);
+        }
+    }
+
+    /// World space half of the overlay; draws with the world camera active. `view_rect` is what
+    /// the camera sees and `cull_rect` where entities still draw.
+    pub fn draw_world(
+        &self,
+        entities: &[Entity],
+        db: &EntityDatabase,
+        player_hitbox: Option<Rect>,
+        view_rect: Rect,
+        cull_rect: Rect,
+    ) {
+        if !self.open {
+            return;
+        }
+        draw_rect_outline(cull_rect, CULL_COLOR);
+        draw_rect_outline(view_rect, VIEW_COLOR);
+        if let Some(hitbox) = player_hitbox {
+            draw_rect_outline(hitbox, PLAYER_HITBOX_COLOR);
+        }
+        for entity in entities {
+            let hitbox = entity.hitbox(db);
+            if !cull_rect.overlaps(&hitbox) {
+                continue;
+            }
+            let instance = &entity.instance;
+            let selected = self.selected == Some(instance.uid);
+            draw_rect_outline(hitbox, if selected { SELECTED_COLOR } else { HITBOX_COLOR });
+            let center = hitbox.center();
+            if let Some(target) = instance.current_target {
+                let to = target.hitbox().map_or(target.position(), |rect| rect.center());
+                draw_line(center.x, center.y, to.x, to.y, 0.5, TARGET_COLOR);
+            }
+            // A quarter second of travel, long enough to read the direction at any speed.
+            let ahead = center + instance.vel * 0.25;
+            draw_line(center.x, center.y, ahead.x, ahead.y, 0.5, VELOCITY_COLOR);
+            let mut label_y = hitbox.y - 2.0;
+            for behavior in instance.behaviors.iter().rev() {
+                draw_text(
+                    &format!("{} {:.1} cd {:.1}", behavior.name, behavior.timer, behavior.cooldown),
+                    hitbox.x,
+                    label_y,
+                    8.0,
+                    LABEL_COLOR,
+                );
+                label_y -= 8.0;
+            }
+        }
+    }
+
+    pub fn draw(&self, entities: &[Entity], db: &EntityDatabase) {
+        if !self.open {
+            return;
+        }
+        let x = screen_width() - PANEL_WIDTH - 12.0;
+        let y = 56.0;
+        let selected = self
+            .selected
+            .and_then(|uid| entities.iter().find(|entity| entity.instance.uid == uid));
+        let Some(entity) = selected else {
+            draw_rectangle(x, y, PANEL_WIDTH, LINE_HEIGHT * 2.0, Color::new(0.0, 0.0, 0.0, 0.75));
+            draw_text("hover an entity to inspect", x + 8.0, y + LINE_HEIGHT * 1.25, 16.0, LIGHTGRAY);
+            return;
+        };
+
+        let instance = &entity.instance;
+        let def = &db.entities[instance.def];
+        let mut lines = vec![
+            format!("{} #{}", def.name, instance.uid),
+            format!(
+                "hp {:.1}/{:.1}  target {}",
+                instance.hp,
+                instance.max_hp,
+                describe_target(instance.current_target)
+            ),
+            format!(
+                "behaviors: {}",
+                instance
+                    .behaviors
+                    .iter()
+                    .map(|behavior| behavior.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            String::new(),
+        ];
+        lines.extend(
+            instance
+                .timeline
+                .recent()
+                .map(|(ago, event)| format!("-{ago:.1}s  {event}")),
+        );
+
+        let height = (lines.len() as f32 + 0.5) * LINE_HEIGHT;
+        draw_rectangle(x, y, PANEL_WIDTH, height, Color::new(0.0, 0.0, 0.0, 0.75));
+        for (i, line) in lines.iter().enumerate() {
+            let color = if i == 0 { WHITE } else { LIGHTGRAY };
+            draw_text(line, x + 8.0, y + (i as f32 + 1.0) * LINE_HEIGHT, 16.0, color);
+        }
+    }
+}
+
+fn draw_rect_outline(rect: Rect, color: Color) {
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, color);
+}
@@ -0,0 +1,221 @@
+use macroquad::prelude::*;
+use crate::craft::RecipeDatabase;
+use crate::crop::CropDatabase;
+use crate::entity::{Entity, EntityDatabase};
+use crate::farm::FarmState;
+use crate::font::{TextStyle, draw_text_styled, measure_text};
+use crate::map::{StructureDef, StructureInteractor, TileMap};
+use crate::mount;
+use crate::player::Player;
+use crate::shop::ShopDatabase;
+use crate::turret;
+
+/// One font pixel per world pixel, matching the sprites.
+const PROMPT_FONT_SIZE: f32 = 8.0;
+const PROMPT_COLOR: Color = Color::new(1.0, 0.95, 0.6, 1.0);
+/// How much facing a candidate head on shortens its distance, and facing away lengthens it.
+const FACING_WEIGHT: f32 = 0.4;
+
+/// Something the player can use with E.
+#[derive(Clone)]
+pub enum Interactable {
+    /// A structure with something to do: a door, chest, bed, station or interact hook.
+    Structure(StructureInteractor),
+    Shopkeeper { shop: usize },
+    Mount { uid: u64 },
+    /// Filling a turret with the ammo the player carries.
+    Load { uid: u64 },
+    /// Getting off the mount being ridden, which beats everything else.
+    Dismount,
+    /// A ripe crop waiting to be picked.
+    Crop { tile: (usize, usize) },
+}
+
+/// Everything the scan looks through for things in reach.
+pub struct InteractScan<'a> {
+    pub player: &'a Player,
+    pub entities: &'a [Entity],
+    pub db: &'a EntityDatabase,
+    pub shops: &'a ShopDatabase,
+    pub map: &'a TileMap,
+    pub structures: &'a [StructureDef],
+    pub recipes: &'a RecipeDatabase,
+    /// Only on the farm, the one place crops grow.
+    pub farm: Option<(&'a FarmState, &'a CropDatabase)>,
+    /// Reach for crops, and for structures with no range of their own.
+    pub range: f32,
+    pub shop_range: f32,
+    pub mount_range: f32,
+}
+
+struct Candidate {
+    target: Interactable,
+    area: Rect,
+    label: String,
+}
+
+/// Picks the one thing E would use each frame, and shows a prompt over it.
+#[derive(Default)]
+pub struct InteractionManager {
+    best: Option<Candidate>,
+}
+
+impl InteractionManager {
+    pub fn target(&self) -> Option<&Interactable> {
+        self.best.as_ref().map(|best| &best.target)
+    }
+
+    pub fn clear(&mut self) {
+        self.best = None;
+    }
+
+    /// Finds the closest candidate in reach, favouring the ones the player faces.
+    pub fn scan(&mut self, scan: &InteractScan) {
+        let feet = scan.player.world_hitbox().center();
+        if scan.player.riding().is_some() {
+            self.best = Some(Candidate {
+                target: Interactable::Dismount,
+                area: scan.player.world_hitbox(),
+                label: "Get off".to_string(),
+            });
+            return;
+        }
+        let facing = scan.player.facing();
+        let score = |area: Rect| {
+            let nearest = vec2(feet.x.clamp(area.x, area.right()), feet.y.clamp(area.y, area.bottom()));
+            let toward = (area.center() - feet).normalize_or_zero();
+            feet.distance(nearest) * (1.0 - FACING_WEIGHT * facing.dot(toward))
+        };
+        self.best = candidates(scan, feet)
+            .map(|candidate| (score(candidate.area), candidate))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, candidate)| candidate);
+    }
+
+    /// Draws "E" and what it does above the chosen candidate, in world space.
+    pub fn draw(&self) {
+        let Some(best) = self.best.as_ref() else {
+            return;
+        };
+        let text = format!("E  {}", best.label);
+        let width = measure_text(&text, PROMPT_FONT_SIZE).width;
+        let pos = vec2(best.area.center().x - width * 0.5, best.area.y - 4.0).round();
+        draw_text_styled(&text, pos.x, pos.y, PROMPT_FONT_SIZE, PROMPT_COLOR, TextStyle::OUTLINED);
+    }
+}
+
+fn candidates<'a>(scan: &'a InteractScan, feet: Vec2) -> impl Iterator<Item = Candidate> + 'a {
+    structure_candidates(scan, feet)
+        .chain(entity_candidates(scan, feet))
+        .chain(crop_candidates(scan, feet))
+}
+
+fn structure_candidates<'a>(scan: &'a InteractScan, feet: Vec2) -> impl Iterator<Item = Candidate> + 'a {
+    let interactors = scan.map.structure_interactors();
+    // A structure registers one interactor per hotspot, all sharing its group's rect.
+    interactors
+        .iter()
+        .enumerate()
+        .filter(move |&(index, interactor)| {
+            interactors[..index].iter().all(|earlier| {
+                earlier.group_rect != interactor.group_rect || earlier.structure_id != interactor.structure_id
+            })
+        })
+        .filter(move |(_, interactor)| {
+            let range = if interactor.interact_range_world > 0.0 {
+                interactor.interact_range_world
+            } else {
+                scan.range
+            };
+            let area = interactor.group_rect;
+            let nearest = vec2(feet.x.clamp(area.x, area.right()), feet.y.clamp(area.y, area.bottom()));
+            feet.distance(nearest) <= range
+        })
+        .filter_map(move |(_, interactor)| {
+            Some(Candidate {
+                label: format!("{} {}", structure_verb(scan, interactor)?, title(&interactor.structure_id)),
+                area: interactor.group_rect,
+                target: Interactable::Structure(interactor.clone()),
+            })
+        })
+}
+
+/// What using the structure does, or None if it does nothing.
+fn structure_verb(scan: &InteractScan, interactor: &StructureInteractor) -> Option<&'static str> {
+    let def = scan.structures.iter().find(|def| def.id == interactor.structure_id);
+    let verb = if interactor.structure_id == crate::expedition::EXTRACTION_BEACON {
+        "Extract at"
+    } else if interactor.structure_id == crate::scene::LAND_OFFICE_ID {
+        "Buy land at"
+    } else if def.is_some_and(|def| def.waypoint) {
+        "Travel from"
+    } else if interactor.interior.is_some() || interactor.scene_transition.is_some() {
+        "Enter"
+    } else if def.is_some_and(|def| def.bed) {
+        "Sleep in"
+    } else if def.is_some_and(|def| def.storage_slots > 0) && scan.farm.is_some() {
+        "Open"
+    } else if scan.recipes.is_station(&interactor.structure_id) {
+        "Craft at"
+    } else if interactor.structure_id == crate::breed::BREEDING_STATION {
+        "Breed at"
+    } else if !interactor.on_interact.is_empty() {
+        "Use"
+    } else {
+        return None;
+    };
+    Some(verb)
+}
+
+fn entity_candidates<'a>(scan: &'a InteractScan, feet: Vec2) -> impl Iterator<Item = Candidate> + 'a {
+    scan.entities.iter().filter_map(move |ent| {
+        let def = &scan.db.entities[ent.instance.def];
+        let area = ent.hitbox(scan.db);
+        let distance = area.center().distance(feet);
+        if let Some(shop) = scan.shops.find_by_entity(&def.id)
+            && distance <= scan.shop_range
+        {
+            let name = scan.shops.get(shop).map_or(def.name.as_str(), |shop| shop.name.as_str());
+            return Some(Candidate {
+                target: Interactable::Shopkeeper { shop },
+                area,
+                label: format!("Shop at {name}"),
+            });
+        }
+        if let Some(turret) = def.turret.as_ref()
+            && distance <= scan.range
+            && turret::loadable(ent, scan.db, scan.player.inventory()) > 0
+        {
+            return Some(Candidate {
+                target: Interactable::Load { uid: ent.instance.uid },
+                area,
+                label: format!("Load {} ({}/{})", def.name, ent.instance.ammo, turret.capacity),
+            });
+        }
+        (mount::rideable(ent, scan.db) && distance <= scan.mount_range).then(|| Candidate {
+            target: Interactable::Mount { uid: ent.instance.uid },
+            area,
+            label: format!("Ride {}", def.name),
+        })
+    })
+}
+
+fn crop_candidates<'a>(scan: &'a InteractScan, feet: Vec2) -> impl Iterator<Item = Candidate> + 'a {
+    let size = scan.map.tile_size();
+    scan.farm.into_iter().flat_map(move |(farm, crop_db)| {
+        farm.crops.crops().iter().filter_map(move |crop| {
+            let def = crop_db.get(&crop.crop).filter(|def| def.is_ripe(crop.growth))?;
+            let area = Rect::new(crop.x as f32 * size, crop.y as f32 * size, size, size);
+            (area.center().distance(feet) <= scan.range).then(|| Candidate {
+                target: Interactable::Crop { tile: (crop.x, crop.y) },
+                area,
+                label: format!("Harvest {}", title(&def.id)),
+            })
+        })
+    })
+}
+
+/// `storage_crate` as "storage crate".
+fn title(id: &str) -> String {
+    id.replace('_', " ")
+}
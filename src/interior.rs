@@ -0,0 +1,210 @@
+use macroquad::prelude::*;
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::path::Path;
+use crate::helpers::{data_path, load_wasm_manifest_files};
+use crate::entity::{Entity, EntityDatabase, MovementRegistry};
+use crate::map::{LayerKind, StructureDef, TileMap};
+use crate::ldtk::{LdtkIds, parse_project};
+use crate::tiled::{TiledMap, is_tiled_map};
+
+/// A small hand-laid map entered through a structure door (e.g. a house).
+#[derive(Clone)]
+pub struct InteriorDef {
+    pub id: String,
+    pub width: usize,
+    pub height: usize,
+    pub floor: u8,
+    pub wall: u8,
+    pub spawn: (usize, usize),
+    pub structures: Vec<InteriorPlacement>,
+    /// Laid out in Tiled instead, in which case it's built from that alone.
+    pub tiled: Option<TiledMap>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct InteriorPlacement {
+    pub id: String,
+    pub x: usize,
+    pub y: usize,
+}
+
+impl InteriorDef {
+    /// Builds the interior map: a floor ringed by solid walls, with its structures placed on top.
+    pub fn build(
+        &self,
+        structures: &[StructureDef],
+        tile_size: f32,
+        chunk_alloc_per_frame: usize,
+        chunk_rebuild_per_frame: usize,
+    ) -> TileMap {
+        if let Some(tiled) = self.tiled.as_ref() {
+            return tiled.build(structures, tile_size, chunk_alloc_per_frame, chunk_rebuild_per_frame);
+        }
+        let mut map = TileMap::new_deferred(
+            self.width,
+            self.height,
+            tile_size,
+            Vec2::new(tile_size, tile_size),
+            0.0,
+        );
+        map.set_chunk_work_budget(chunk_alloc_per_frame, chunk_rebuild_per_frame);
+        map.fill_layer(LayerKind::Background, self.floor);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if x == 0 || y == 0 || x + 1 == self.width || y + 1 == self.height {
+                    map.set_tile(LayerKind::Background, x, y, self.wall);
+                    map.set_collision(x, y, true);
+                }
+            }
+        }
+        for placement in &self.structures {
+            let Some(def) = structures.iter().find(|def| def.id == placement.id) else {
+                eprintln!("interior '{}' references unknown structure '{}'", self.id, placement.id);
+                continue;
+            };
+            map.place_structure_def(def, placement.x, placement.y);
+        }
+        map
+    }
+
+    pub fn spawn_point(&self, tile_size: f32) -> Vec2 {
+        if let Some(spawn) = self.tiled.as_ref().and_then(|tiled| tiled.spawn_point(tile_size)) {
+            return spawn;
+        }
+        vec2(
+            (self.spawn.0 as f32 + 0.5) * tile_size,
+            (self.spawn.1 as f32 + 0.5) * tile_size,
+        )
+    }
+
+    /// The entities placed in it; only Tiled interiors have any.
+    pub fn spawn_entities(&self, db: &EntityDatabase, registry: &MovementRegistry, tile_size: f32) -> Vec<Entity> {
+        self.tiled.as_ref().map(|tiled| tiled.spawn_entities(db, registry, tile_size)).unwrap_or_default()
+    }
+}
+
+pub fn find_interior<'a>(interiors: &'a [InteriorDef], id: &str) -> Option<&'a InteriorDef> {
+    interiors.iter().find(|def| def.id == id)
+}
+
+pub async fn load_interiors_from_dir(dir: impl AsRef<Path>) -> Result<Vec<InteriorDef>, std::io::Error> {
+    let mut raws = Vec::new();
+    let mut tiled = Vec::new();
+
+    if cfg!(target_arch = "wasm32") {
+        let dir = data_path(&dir.as_ref().to_string_lossy());
+        let files = load_wasm_manifest_files(&dir, &["farm_house.json"]).await;
+        for file in files {
+            let path = format!("{}/{}", dir, file);
+            let raw_str = load_string(&path)
+                .await
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
+            if is_tiled_map(&file) {
+                tiled.push(parse_tiled(&file, &raw_str)?);
+                continue;
+            }
+            if file.ends_with(".ldtk") {
+                let ids = load_string(&path.replace(".ldtk", ".yaml")).await.ok();
+                tiled.extend(parse_ldtk(&file, &raw_str, ids)?);
+                continue;
+            }
+            raws.push(
+                serde_json::from_str::<InteriorFile>(&raw_str)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            );
+        }
+    } else {
+        let dir = dir.as_ref();
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let file = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            if is_tiled_map(file) {
+                tiled.push(parse_tiled(file, &std::fs::read_to_string(&path)?)?);
+                continue;
+            }
+            if file.ends_with(".ldtk") {
+                let ids = std::fs::read_to_string(path.with_extension("yaml")).ok();
+                tiled.extend(parse_ldtk(file, &std::fs::read_to_string(&path)?, ids)?);
+                continue;
+            }
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            if file == "index.json" {
+                continue;
+            }
+            raws.push(
+                serde_json::from_str::<InteriorFile>(&std::fs::read_to_string(&path)?)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            );
+        }
+    }
+
+    let tiled = tiled.into_iter().map(|map| InteriorDef {
+        id: map.name.clone(),
+        width: map.width,
+        height: map.height,
+        floor: 0,
+        wall: 0,
+        spawn: (map.width / 2, map.height / 2),
+        structures: Vec::new(),
+        tiled: Some(map),
+    });
+    Ok(raws
+        .into_iter()
+        .filter(|raw| raw.width >= 3 && raw.height >= 3)
+        .map(|raw| {
+            let spawn = raw.spawn.unwrap_or([raw.width / 2, raw.height / 2]);
+            InteriorDef {
+                spawn: (
+                    spawn[0].clamp(1, raw.width - 2),
+                    spawn[1].clamp(1, raw.height - 2),
+                ),
+                id: raw.id,
+                width: raw.width,
+                height: raw.height,
+                floor: raw.floor,
+                wall: raw.wall,
+                structures: raw.structures.unwrap_or_default(),
+                tiled: None,
+            }
+        })
+        .chain(tiled)
+        .collect())
+}
+
+/// An interior per level of an LDtk project, with `ids` the yaml mapping its identifiers.
+fn parse_ldtk(file: &str, text: &str, ids: Option<String>) -> Result<Vec<TiledMap>, std::io::Error> {
+    let ids = match ids.map(|ids| serde_yaml::from_str::<LdtkIds>(&ids)) {
+        Some(Ok(ids)) => ids,
+        Some(Err(err)) => {
+            eprintln!("ldtk ids for '{file}': {err}");
+            LdtkIds::default()
+        }
+        None => LdtkIds::default(),
+    };
+    parse_project(text, &ids).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// A Tiled interior, named after its file.
+fn parse_tiled(file: &str, text: &str) -> Result<TiledMap, std::io::Error> {
+    let id = Path::new(file).file_stem().and_then(|stem| stem.to_str()).unwrap_or(file);
+    TiledMap::parse(id, file, text).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+#[derive(Deserialize)]
+struct InteriorFile {
+    id: String,
+    width: usize,
+    height: usize,
+    floor: u8,
+    wall: u8,
+    #[serde(default)]
+    spawn: Option<[usize; 2]>,
+    #[serde(default)]
+    structures: Option<Vec<InteriorPlacement>>,
+}
@@ -0,0 +1,164 @@
+use macroquad::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// Samples kept per buffer; older ones are dropped as new ones arrive.
+const BUFFER_LEN: usize = 32;
+/// How far the playback clock may drift from the newest sample before it's reset to it.
+const MAX_CLOCK_DRIFT: f64 = 0.5;
+
+/// A state that can be blended between two samples of it, or carried on past the second when
+/// `t` is over 1.
+pub trait Interpolate: Clone {
+    fn lerp(&self, to: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for Vec2 {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        *self + (*to - *self) * t
+    }
+}
+
+/// Timestamped samples of one thing's state, oldest first.
+pub struct SnapshotBuffer<T> {
+    samples: VecDeque<(f64, T)>,
+}
+
+impl<T> Default for SnapshotBuffer<T> {
+    fn default() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+}
+
+impl<T: Interpolate> SnapshotBuffer<T> {
+    /// Adds the state at `time`. Samples arriving out of order are dropped.
+    pub fn push(&mut self, time: f64, state: T) {
+        if self.samples.back().is_some_and(|(last, _)| *last >= time) {
+            return;
+        }
+        self.samples.push_back((time, state));
+        while self.samples.len() > BUFFER_LEN {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The state at `time`: blended between the samples either side, or run on from the last
+    /// two for up to `max_extrapolation` seconds when nothing newer has come in, then held.
+    pub fn sample(&self, time: f64, max_extrapolation: f64) -> Option<T> {
+        let (first_time, first) = self.samples.front()?;
+        if time <= *first_time || self.samples.len() == 1 {
+            return Some(first.clone());
+        }
+        let after = self.samples.iter().position(|(at, _)| *at >= time);
+        let (from, to) = match after {
+            Some(index) => (&self.samples[index - 1], &self.samples[index]),
+            None => (&self.samples[self.samples.len() - 2], &self.samples[self.samples.len() - 1]),
+        };
+        let span = to.0 - from.0;
+        let t = ((time.min(to.0 + max_extrapolation) - from.0) / span) as f32;
+        Some(from.1.lerp(&to.1, t))
+    }
+}
+
+/// Plays back keyed states a fixed delay behind the newest sample, so there's usually one on
+/// each side to blend between and a late or lost one only shows as a short extrapolation. Knows
+/// nothing of where the states come from, so whatever feeds it keeps its own structs.
+pub struct Interpolator<K, T> {
+    buffers: HashMap<K, SnapshotBuffer<T>>,
+    /// Seconds behind `clock` that states are shown.
+    delay: f64,
+    max_extrapolation: f64,
+    clock: f64,
+}
+
+impl<K: Eq + Hash, T: Interpolate> Interpolator<K, T> {
+    pub fn new(delay: f64, max_extrapolation: f64) -> Self {
+        Self {
+            buffers: HashMap::new(),
+            delay,
+            max_extrapolation,
+            clock: 0.0,
+        }
+    }
+
+    /// Adds `key`'s state at `time`, in the same seconds `advance` counts.
+    pub fn push(&mut self, key: K, time: f64, state: T) {
+        self.buffers.entry(key).or_default().push(time, state);
+        // Starting out, or after a stall, catch up instead of crawling through old samples.
+        if (self.clock - time).abs() > MAX_CLOCK_DRIFT {
+            self.clock = time;
+        }
+    }
+
+    /// Moves playback on by `dt` seconds.
+    pub fn advance(&mut self, dt: f32) {
+        self.clock += dt as f64;
+    }
+
+    /// `key`'s state as it should be shown now.
+    pub fn sample(&self, key: &K) -> Option<T> {
+        self.buffers.get(key)?.sample(self.clock - self.delay, self.max_extrapolation)
+    }
+
+    /// Forgets everything not kept by `keep`.
+    pub fn retain(&mut self, mut keep: impl FnMut(&K) -> bool) {
+        self.buffers.retain(|key, _| keep(key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(samples: &[(f64, f32)]) -> SnapshotBuffer<Vec2> {
+        let mut buffer = SnapshotBuffer::default();
+        for &(time, x) in samples {
+            buffer.push(time, vec2(x, 0.0));
+        }
+        buffer
+    }
+
+    #[test]
+    fn blends_between_samples() {
+        let buffer = buffer(&[(0.0, 0.0), (1.0, 10.0), (2.0, 30.0)]);
+        assert_eq!(buffer.sample(0.5, 0.0), Some(vec2(5.0, 0.0)));
+        assert_eq!(buffer.sample(1.5, 0.0), Some(vec2(20.0, 0.0)));
+        assert_eq!(buffer.sample(-1.0, 0.0), Some(Vec2::ZERO));
+        assert!(SnapshotBuffer::<Vec2>::default().sample(0.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn extrapolates_for_a_while_then_holds() {
+        let buffer = buffer(&[(0.0, 0.0), (1.0, 10.0)]);
+        assert_eq!(buffer.sample(1.2, 0.5), Some(vec2(12.0, 0.0)));
+        assert_eq!(buffer.sample(1.5, 0.5), Some(vec2(15.0, 0.0)));
+        assert_eq!(buffer.sample(9.0, 0.5), Some(vec2(15.0, 0.0)));
+    }
+
+    #[test]
+    fn drops_late_samples_and_old_ones() {
+        let mut buffer = buffer(&[(1.0, 10.0), (0.5, 99.0), (1.0, 99.0)]);
+        assert_eq!(buffer.samples.len(), 1);
+        for tick in 0..BUFFER_LEN * 2 {
+            buffer.push(2.0 + tick as f64, vec2(tick as f32, 0.0));
+        }
+        assert_eq!(buffer.samples.len(), BUFFER_LEN);
+    }
+
+    #[test]
+    fn plays_back_a_delay_behind() {
+        let mut motion = Interpolator::new(0.1, 0.0);
+        motion.push("bot", 5.0, vec2(0.0, 0.0));
+        motion.push("bot", 5.1, vec2(10.0, 0.0));
+        // The first sample set the clock, so playback shows a tick before it.
+        assert_eq!(motion.sample(&"bot"), Some(Vec2::ZERO));
+        motion.advance(0.15);
+        let pos = motion.sample(&"bot").unwrap();
+        assert!((pos.x - 5.0).abs() < 1e-3);
+        assert!(motion.sample(&"other").is_none());
+        motion.retain(|key| *key != "bot");
+        assert!(motion.sample(&"bot").is_none());
+    }
+}
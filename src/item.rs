@@ -0,0 +1,452 @@
+use macroquad::prelude::*;
+use crate::font::draw_text;
+use macroquad::file::load_string;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use crate::helpers::{asset_path, data_path, load_wasm_manifest_files};
+use crate::locale::Locale;
+use crate::atlas::{AtlasBuilder, Sprite};
+use crate::equipment::{EquipDef, EquipFile, EquipOverlay};
+
+pub const INVENTORY_SLOTS: usize = 9;
+const ITEM_DRAW_SIZE: f32 = 10.0;
+const DEFAULT_DESPAWN_TIME: f32 = 300.0;
+
+#[derive(Debug)]
+pub enum ItemLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for ItemLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ItemLoadError {}
+
+impl From<std::io::Error> for ItemLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for ItemLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+#[derive(Clone)]
+pub struct ItemDef {
+    pub id: String,
+    pub texture: Option<Sprite>,
+    pub max_stack: u32,
+    pub despawn_time: f32,
+    /// Action the item performs when used on a tile, e.g. `till` or `water`.
+    pub tool: Option<String>,
+    /// Whether using the tool uses up one of the item.
+    pub consumed: bool,
+    pub equip: Option<EquipDef>,
+}
+
+pub struct ItemDatabase {
+    defs: Vec<ItemDef>,
+    lookup: HashMap<String, usize>,
+}
+
+impl ItemDatabase {
+    pub fn empty() -> Self {
+        Self {
+            defs: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, ItemLoadError> {
+        let dir = dir.as_ref();
+        let mut raws = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let files = load_wasm_manifest_files(
+                &dir,
+                &[
+                    "axe.yaml",
+                    "berry.yaml",
+                    "berry_seeds.yaml",
+                    "bolt.yaml",
+                    "capture_chip.yaml",
+                    "fertilizer.yaml",
+                    "gear.yaml",
+                    "hammer.yaml",
+                    "hoe.yaml",
+                    "lucky_gear.yaml",
+                    "ore.yaml",
+                    "scrap_plating.yaml",
+                    "steel_blade.yaml",
+                    "turnip.yaml",
+                    "turnip_seeds.yaml",
+                    "watering_can.yaml",
+                    "wood.yaml",
+                ],
+            )
+            .await;
+            for file in files {
+                let path = format!("{}/{}", dir, file);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| ItemLoadError::Io(std::io::Error::other(err.to_string())))?;
+                raws.push(serde_yaml::from_str::<ItemFile>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                raws.push(serde_yaml::from_str::<ItemFile>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+
+        let mut atlas = AtlasBuilder::default();
+        let mut textures = Vec::with_capacity(raws.len());
+        let mut overlays = Vec::with_capacity(raws.len());
+        for raw in &raws {
+            textures.push(match raw.texture.as_deref() {
+                Some(path) => Some(atlas.add(&asset_path(path)).await),
+                None => None,
+            });
+            overlays.push(match raw.equip.as_ref().and_then(|equip| equip.overlay.as_ref()) {
+                Some(overlay) => Some(atlas.add(&asset_path(&overlay.sprite)).await),
+                None => None,
+            });
+        }
+        let atlas = atlas.build();
+
+        let mut db = Self::empty();
+        for ((raw, texture), overlay) in raws.into_iter().zip(textures).zip(overlays) {
+            let equip = raw.equip.map(|equip| EquipDef {
+                slot: equip.slot,
+                stats: equip.stats,
+                overlay: equip
+                    .overlay
+                    .as_ref()
+                    .zip(overlay)
+                    .map(|(file, handle)| EquipOverlay::new(file, atlas.sprite(handle))),
+            });
+            db.lookup.insert(raw.id.clone(), db.defs.len());
+            db.defs.push(ItemDef {
+                id: raw.id,
+                texture: texture.map(|handle| atlas.sprite(handle)),
+                max_stack: raw.max_stack.unwrap_or(99).max(1),
+                despawn_time: raw.despawn_time.unwrap_or(DEFAULT_DESPAWN_TIME),
+                tool: raw.tool,
+                consumed: raw.consumed.unwrap_or(false),
+                equip,
+            });
+        }
+        Ok(db)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ItemDef> {
+        self.lookup.get(id).map(|&idx| &self.defs[idx])
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Inventory {
+    stacks: Vec<ItemStack>,
+    slots: usize,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::with_slots(INVENTORY_SLOTS)
+    }
+}
+
+impl Inventory {
+    pub fn with_slots(slots: usize) -> Self {
+        Self {
+            stacks: Vec::new(),
+            slots,
+        }
+    }
+
+    /// An inventory holding exactly `stacks`, one slot each.
+    pub fn from_stacks(stacks: Vec<ItemStack>) -> Self {
+        Self {
+            slots: stacks.len(),
+            stacks,
+        }
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Adds items, filling existing stacks first. Returns how many did not fit.
+    pub fn add(&mut self, db: &ItemDatabase, item: &str, count: u32) -> u32 {
+        let max_stack = db.get(item).map(|def| def.max_stack).unwrap_or(1);
+        let mut remaining = count;
+        for stack in self.stacks.iter_mut().filter(|stack| stack.item == item) {
+            let space = max_stack.saturating_sub(stack.count);
+            let moved = space.min(remaining);
+            stack.count += moved;
+            remaining -= moved;
+            if remaining == 0 {
+                return 0;
+            }
+        }
+        while remaining > 0 && self.stacks.len() < self.slots {
+            let moved = remaining.min(max_stack);
+            self.stacks.push(ItemStack {
+                item: item.to_string(),
+                count: moved,
+            });
+            remaining -= moved;
+        }
+        remaining
+    }
+
+    pub fn stacks(&self) -> &[ItemStack] {
+        &self.stacks
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&ItemStack> {
+        self.stacks.get(slot)
+    }
+
+    pub fn count(&self, item: &str) -> u32 {
+        self.stacks
+            .iter()
+            .filter(|stack| stack.item == item)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    pub fn has_all(&self, items: &[ItemStack]) -> bool {
+        items.iter().all(|cost| self.count(&cost.item) >= cost.count)
+    }
+
+    /// Removes every stack in `items`, or nothing if any is short.
+    pub fn remove_all(&mut self, items: &[ItemStack]) -> bool {
+        if !self.has_all(items) {
+            return false;
+        }
+        for cost in items {
+            let mut remaining = cost.count;
+            for stack in self.stacks.iter_mut().rev().filter(|stack| stack.item == cost.item) {
+                let taken = stack.count.min(remaining);
+                stack.count -= taken;
+                remaining -= taken;
+            }
+        }
+        self.stacks.retain(|stack| stack.count > 0);
+        true
+    }
+
+    /// Keeps only the stacks `keep` returns true for; it may also edit them in place.
+    pub fn retain(&mut self, keep: impl FnMut(&mut ItemStack) -> bool) {
+        self.stacks.retain_mut(keep);
+    }
+
+    /// Moves as much of `slot` as fits into `other`. Returns whether anything moved.
+    pub fn transfer(&mut self, slot: usize, other: &mut Inventory, db: &ItemDatabase) -> bool {
+        let Some(stack) = self.stacks.get(slot).cloned() else {
+            return false;
+        };
+        let moved = stack.count - other.add(db, &stack.item, stack.count);
+        self.consume(slot, moved);
+        moved > 0
+    }
+
+    /// Removes up to `count` items from `slot`, dropping the stack once it is empty.
+    pub fn consume(&mut self, slot: usize, count: u32) {
+        let Some(stack) = self.stacks.get_mut(slot) else {
+            return;
+        };
+        stack.count = stack.count.saturating_sub(count);
+        if stack.count == 0 {
+            self.stacks.remove(slot);
+        }
+    }
+}
+
+/// An item lying in the world. `despawn_in` only counts down while its scene is loaded.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GroundItem {
+    pub item: String,
+    pub count: u32,
+    pub x: f32,
+    pub y: f32,
+    pub despawn_in: f32,
+}
+
+#[derive(Default)]
+pub struct GroundItems {
+    items: Vec<GroundItem>,
+}
+
+impl GroundItems {
+    pub fn spawn(&mut self, db: &ItemDatabase, item: &str, count: u32, pos: Vec2) {
+        let Some(def) = db.get(item) else {
+            return;
+        };
+        if count == 0 {
+            return;
+        }
+        self.items.push(GroundItem {
+            item: def.id.clone(),
+            count,
+            x: pos.x,
+            y: pos.y,
+            despawn_in: def.despawn_time,
+        });
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for item in self.items.iter_mut() {
+            item.despawn_in -= dt;
+        }
+        self.items.retain(|item| item.despawn_in > 0.0);
+    }
+
+    /// Keeps only the items `keep` returns true for; it may also move or edit them in place.
+    pub fn retain(&mut self, keep: impl FnMut(&mut GroundItem) -> bool) {
+        self.items.retain_mut(keep);
+    }
+
+    /// Moves every item within `radius` of `pos` into `inventory`; leftovers stay on the ground.
+    pub fn collect_near(&mut self, db: &ItemDatabase, inventory: &mut Inventory, pos: Vec2, radius: f32) {
+        let radius_sq = radius * radius;
+        for item in self.items.iter_mut() {
+            if vec2(item.x, item.y).distance_squared(pos) > radius_sq {
+                continue;
+            }
+            item.count = inventory.add(db, &item.item, item.count);
+        }
+        self.items.retain(|item| item.count > 0);
+    }
+
+    pub fn draw(&self, db: &ItemDatabase, view: Rect) {
+        let half = ITEM_DRAW_SIZE * 0.5;
+        for item in &self.items {
+            if !view.contains(vec2(item.x, item.y)) {
+                continue;
+            }
+            let Some(texture) = db.get(&item.item).and_then(|def| def.texture.as_ref()) else {
+                continue;
+            };
+            texture.draw(
+                item.x - half,
+                item.y - half,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(ITEM_DRAW_SIZE, ITEM_DRAW_SIZE)),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<GroundItem> {
+        std::mem::take(&mut self.items)
+    }
+
+    pub fn replace(&mut self, items: Vec<GroundItem>) {
+        self.items = items;
+    }
+
+    pub fn items(&self) -> &[GroundItem] {
+        &self.items
+    }
+}
+
+/// Draws the inventory as a bottom-centered hotbar with the `selected` slot outlined. Slots,
+/// outlines, icons and counts each go in their own pass so same-texture draws stay together.
+pub fn draw_hotbar(
+    db: &ItemDatabase,
+    inventory: &Inventory,
+    selected: usize,
+    slot: &Sprite,
+    locale: &Locale,
+) {
+    let size = 40.0;
+    let gap = 4.0;
+    let total_w = INVENTORY_SLOTS as f32 * size + (INVENTORY_SLOTS as f32 - 1.0) * gap;
+    let start_x = (screen_width() - total_w) * 0.5;
+    let y = screen_height() - size - 8.0;
+    let slot_x = |i: usize| start_x + i as f32 * (size + gap);
+    for i in 0..INVENTORY_SLOTS {
+        slot.draw(
+            slot_x(i),
+            y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(size, size)),
+                ..Default::default()
+            },
+        );
+    }
+    if selected < INVENTORY_SLOTS {
+        let x = slot_x(selected);
+        draw_rectangle_lines(x - 2.0, y - 2.0, size + 4.0, size + 4.0, 3.0, YELLOW);
+    }
+    let stacks = inventory.stacks().iter().take(INVENTORY_SLOTS).enumerate();
+    for (i, stack) in stacks.clone() {
+        if let Some(texture) = db.get(&stack.item).and_then(|def| def.texture.as_ref()) {
+            let pad = size * 0.2;
+            texture.draw(
+                slot_x(i) + pad,
+                y + pad,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(size - pad * 2.0, size - pad * 2.0)),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+    for (i, stack) in stacks {
+        if stack.count > 1 {
+            draw_text(&locale.format_int(stack.count as i64), slot_x(i) + size - 16.0, y + size - 4.0, 18.0, WHITE);
+        }
+    }
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
+
+#[derive(Deserialize)]
+struct ItemFile {
+    id: String,
+    #[serde(default)]
+    texture: Option<String>,
+    #[serde(default)]
+    max_stack: Option<u32>,
+    #[serde(default)]
+    despawn_time: Option<f32>,
+    #[serde(default)]
+    tool: Option<String>,
+    #[serde(default)]
+    consumed: Option<bool>,
+    #[serde(default)]
+    equip: Option<EquipFile>,
+}
@@ -0,0 +1,222 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::tiled::TiledMap;
+
+#[derive(Debug)]
+pub enum LdtkError {
+    Json(serde_json::Error),
+    /// Valid LDtk data this importer doesn't handle, like levels saved in separate files.
+    Unsupported(String),
+}
+
+impl std::fmt::Display for LdtkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Json(err) => write!(f, "json error: {err}"),
+            Self::Unsupported(what) => write!(f, "unsupported: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for LdtkError {}
+
+impl From<serde_json::Error> for LdtkError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+/// How a project's identifiers map onto the game's, from the yaml of the same name beside it.
+/// Anything left out is read from its identifier in snake_case.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct LdtkIds {
+    /// Level identifier to the id it's entered by.
+    pub levels: HashMap<String, String>,
+    /// Layer identifier to `background`, `foreground` or `overlay`, for the tiles it draws.
+    pub layers: HashMap<String, String>,
+    pub entities: HashMap<String, LdtkEntityId>,
+    /// Int grid values that are solid; every nonzero one when empty.
+    pub solid: Vec<i64>,
+}
+
+/// What an LDtk entity becomes: an object of `class` as Tiled maps have them, named `id`.
+#[derive(Deserialize)]
+pub struct LdtkEntityId {
+    pub class: String,
+    #[serde(default)]
+    pub id: Option<String>,
+}
+
+/// Turns each level of an LDtk project into a map: int grid layers into collision, auto-layer
+/// and tile layers into tiles, and entities into spawns, structures and triggers the way
+/// `TiledMap` reads its objects, with their fields as properties.
+pub fn parse_project(text: &str, ids: &LdtkIds) -> Result<Vec<TiledMap>, LdtkError> {
+    let project: ProjectJson = serde_json::from_str(text)?;
+    if project.external_levels {
+        return Err(LdtkError::Unsupported("levels in separate files".to_string()));
+    }
+    let mut maps = Vec::new();
+    for level in project.levels {
+        let Some(layers) = level.layer_instances else {
+            continue;
+        };
+        let grid = layers.first().map_or(project.default_grid_size, |layer| layer.grid_size).max(1);
+        let (width, height) = ((level.px_wid / grid) as usize, (level.px_hei / grid) as usize);
+        let name = ids.levels.get(&level.identifier).cloned().unwrap_or_else(|| snake_case(&level.identifier));
+        let mut map = TiledMap::new(&name, width, height);
+        // LDtk lists the top layer first.
+        for layer in layers.iter().rev() {
+            if layer.c_wid != width || layer.c_hei != height {
+                eprintln!("ldtk level '{}': layer '{}' isn't the level's size", level.identifier, layer.identifier);
+                continue;
+            }
+            let cell = layer.grid_size.max(1);
+            if !layer.int_grid_csv.is_empty() {
+                let solid = |value: i64| if ids.solid.is_empty() { value != 0 } else { ids.solid.contains(&value) };
+                map.push_layer("collision", layer.int_grid_csv.iter().map(|&value| solid(value) as u32).collect());
+            }
+            let tiles = layer.auto_layer_tiles.iter().chain(&layer.grid_tiles);
+            if tiles.clone().next().is_some() {
+                let mut gids = vec![0; width * height];
+                for tile in tiles {
+                    let (x, y) = (tile.px[0] / cell as i64, tile.px[1] / cell as i64);
+                    if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+                        gids[y as usize * width + x as usize] = tile.t + 1;
+                    }
+                }
+                let target = ids.layers.get(&layer.identifier).cloned();
+                map.push_layer(&target.unwrap_or_else(|| snake_case(&layer.identifier)), gids);
+            }
+            for entity in &layer.entity_instances {
+                let mapped = ids.entities.get(&entity.identifier);
+                let class = mapped.map_or_else(|| snake_case(&entity.identifier), |mapped| mapped.class.clone());
+                let id = mapped.and_then(|mapped| mapped.id.clone()).unwrap_or_default();
+                let size = vec2(entity.width, entity.height);
+                let top_left = (Vec2::from(entity.px) - Vec2::from(entity.pivot) * size) / cell as f32;
+                let size = size / cell as f32;
+                let rect = Rect::new(top_left.x, top_left.y, size.x, size.y);
+                let properties = entity
+                    .field_instances
+                    .iter()
+                    .filter_map(|field| Some((field.identifier.clone(), field_value(&field.value)?)))
+                    .collect();
+                map.push_object(&id, &class, rect, properties);
+            }
+        }
+        maps.push(map);
+    }
+    Ok(maps)
+}
+
+/// A field's value as a property; lists become one entry per line.
+fn field_value(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(value) => Some(value.clone()),
+        serde_json::Value::Array(values) => Some(values.iter().filter_map(field_value).collect::<Vec<_>>().join("\n")),
+        value => Some(value.to_string()),
+    }
+}
+
+/// `PlayerStart` to `player_start`.
+fn snake_case(identifier: &str) -> String {
+    let mut out = String::with_capacity(identifier.len() + 4);
+    for (i, ch) in identifier.chars().enumerate() {
+        if ch.is_uppercase() && i > 0 && !out.ends_with('_') {
+            out.push('_');
+        }
+        out.extend(ch.to_lowercase());
+    }
+    out
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectJson {
+    #[serde(default)]
+    external_levels: bool,
+    #[serde(default = "default_grid_size")]
+    default_grid_size: u32,
+    levels: Vec<LevelJson>,
+}
+
+fn default_grid_size() -> u32 {
+    16
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LevelJson {
+    identifier: String,
+    px_wid: u32,
+    px_hei: u32,
+    #[serde(default)]
+    layer_instances: Option<Vec<LayerJson>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LayerJson {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__cWid")]
+    c_wid: usize,
+    #[serde(rename = "__cHei")]
+    c_hei: usize,
+    #[serde(rename = "__gridSize")]
+    grid_size: u32,
+    #[serde(default)]
+    int_grid_csv: Vec<i64>,
+    #[serde(default)]
+    auto_layer_tiles: Vec<TileJson>,
+    #[serde(default)]
+    grid_tiles: Vec<TileJson>,
+    #[serde(default)]
+    entity_instances: Vec<EntityJson>,
+}
+
+#[derive(Deserialize)]
+struct TileJson {
+    px: [i64; 2],
+    t: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EntityJson {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__pivot", default)]
+    pivot: [f32; 2],
+    px: [f32; 2],
+    width: f32,
+    height: f32,
+    #[serde(default)]
+    field_instances: Vec<FieldJson>,
+}
+
+#[derive(Deserialize)]
+struct FieldJson {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__value")]
+    value: serde_json::Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_zero_grid_size_is_read_as_one_pixel() {
+        let text = r#"{"levels": [{"identifier": "Shed", "pxWid": 4, "pxHei": 2, "layerInstances": [{
+            "__identifier": "Tiles", "__cWid": 4, "__cHei": 2, "__gridSize": 0,
+            "gridTiles": [{"px": [3, 1], "t": 5}],
+            "entityInstances": [{"__identifier": "Spawn", "px": [1, 1], "width": 1, "height": 1}]
+        }]}]}"#;
+        let maps = parse_project(text, &LdtkIds::default()).unwrap();
+        assert_eq!((maps[0].name.as_str(), maps[0].width, maps[0].height), ("shed", 4, 2));
+    }
+}
@@ -0,0 +1,104 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::entity::{Entity, EntityDatabase};
+use crate::font::{TextStyle, draw_text_styled, measure_text};
+
+/// One font pixel per world pixel, matching the sprites.
+const BADGE_FONT_SIZE: f32 = 8.0;
+/// World units of an entity's health bar.
+const BAR_SIZE: Vec2 = vec2(16.0, 2.0);
+/// Screen pixels of the HUD's XP bar.
+const XP_BAR_SIZE: Vec2 = vec2(160.0, 6.0);
+const XP_COLOR: Color = Color::new(0.45, 0.75, 1.0, 1.0);
+
+/// How XP adds up to levels, and what each level gained adds to the stats.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct GrowthDef {
+    /// XP from level 1 to 2.
+    pub base_xp: f32,
+    /// Each level after takes this many times the XP of the one before.
+    pub xp_factor: f32,
+    pub max_level: u32,
+    /// Added to each stat per level gained.
+    pub stats: HashMap<String, f32>,
+}
+
+impl Default for GrowthDef {
+    fn default() -> Self {
+        Self {
+            base_xp: 10.0,
+            xp_factor: 1.5,
+            max_level: 20,
+            stats: HashMap::new(),
+        }
+    }
+}
+
+impl GrowthDef {
+    /// Total XP it takes to reach `level`.
+    pub fn xp_for(&self, level: u32) -> f32 {
+        (1..level.min(self.max_level))
+            .map(|from| self.base_xp.max(0.0) * self.xp_factor.max(1.0).powi(from as i32 - 1))
+            .sum()
+    }
+
+    pub fn level_for(&self, xp: f32) -> u32 {
+        let mut level = 1;
+        while level < self.max_level && xp >= self.xp_for(level + 1) {
+            level += 1;
+        }
+        level
+    }
+
+    /// How far `xp` is from its level to the next, from 0 to 1; 1 at the top level.
+    pub fn progress(&self, xp: f32) -> f32 {
+        let level = self.level_for(xp);
+        if level >= self.max_level {
+            return 1.0;
+        }
+        let (from, to) = (self.xp_for(level), self.xp_for(level + 1));
+        ((xp - from) / (to - from).max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+
+    pub fn stat(&self, key: &str) -> f32 {
+        self.stats.get(key).copied().unwrap_or(0.0)
+    }
+}
+
+/// Draws a health bar over `ent`, with its level beside it if it can level up. Only entities
+/// that are hurt or can level get one.
+pub fn draw_entity_bar(ent: &Entity, db: &EntityDatabase) {
+    let def = &db.entities[ent.instance.def];
+    let hurt = ent.instance.hp < ent.instance.max_hp;
+    if ent.instance.is_dying() || (!hurt && def.growth.is_none()) {
+        return;
+    }
+    let hitbox = ent.hitbox(db);
+    let pos = vec2(hitbox.center().x - BAR_SIZE.x * 0.5, hitbox.y - ent.instance.z - 6.0).round();
+    let filled = (ent.instance.hp / ent.instance.max_hp.max(1.0)).clamp(0.0, 1.0);
+    draw_rectangle(pos.x - 0.5, pos.y - 0.5, BAR_SIZE.x + 1.0, BAR_SIZE.y + 1.0, Color::new(0.0, 0.0, 0.0, 0.6));
+    draw_rectangle(pos.x, pos.y, BAR_SIZE.x * filled, BAR_SIZE.y, Color::new(0.4, 0.9, 0.35, 1.0));
+    if def.growth.is_some() {
+        let badge = ent.instance.level.to_string();
+        let width = measure_text(&badge, BADGE_FONT_SIZE).width;
+        draw_text_styled(
+            &badge,
+            (pos.x - width - 2.0).round(),
+            (pos.y + BAR_SIZE.y + 1.0).round(),
+            BADGE_FONT_SIZE,
+            GOLD,
+            TextStyle::OUTLINED,
+        );
+    }
+}
+
+/// Draws the player's level and XP bar on the HUD, with its top left at `pos`.
+pub fn draw_xp_bar(growth: &GrowthDef, xp: f32, pos: Vec2) {
+    let level = growth.level_for(xp);
+    draw_text_styled(&format!("Lv {level}"), pos.x, pos.y + 14.0, 20.0, XP_COLOR, TextStyle::SHADOWED);
+    let bar = vec2(pos.x + 52.0, pos.y + 6.0);
+    draw_rectangle(bar.x, bar.y, XP_BAR_SIZE.x, XP_BAR_SIZE.y, Color::new(0.0, 0.0, 0.0, 0.5));
+    draw_rectangle(bar.x, bar.y, XP_BAR_SIZE.x * growth.progress(xp), XP_BAR_SIZE.y, XP_COLOR);
+}
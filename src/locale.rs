@@ -0,0 +1,224 @@
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::path::Path;
+use crate::farm::DayClock;
+use crate::helpers::{data_path, load_wasm_manifest_files};
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+#[derive(Debug)]
+pub enum LocaleLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for LocaleLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LocaleLoadError {}
+
+impl From<std::io::Error> for LocaleLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for LocaleLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+/// Number, currency and calendar conventions for one UI language.
+#[derive(Clone)]
+pub struct Locale {
+    pub id: String,
+    pub decimal_separator: char,
+    pub group_separator: Option<char>,
+    pub currency_symbol: String,
+    pub currency_suffix: bool,
+    /// Display names in calendar order: spring, summer, fall, winter.
+    pub seasons: Vec<String>,
+    /// `{day}`, `{season}` and `{year}` are substituted.
+    pub date_pattern: String,
+}
+
+impl Locale {
+    fn fallback() -> Self {
+        Self {
+            id: DEFAULT_LOCALE.to_string(),
+            decimal_separator: '.',
+            group_separator: Some(','),
+            currency_symbol: "$".to_string(),
+            currency_suffix: false,
+            seasons: vec![
+                "Spring".to_string(),
+                "Summer".to_string(),
+                "Fall".to_string(),
+                "Winter".to_string(),
+            ],
+            date_pattern: "{season} {day}, Year {year}".to_string(),
+        }
+    }
+
+    /// Formats an integer with digit grouping, e.g. `12,345` / `12.345`.
+    pub fn format_int(&self, value: i64) -> String {
+        let digits = value.unsigned_abs().to_string();
+        let mut out = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+        if value < 0 {
+            out.push('-');
+        }
+        for (i, ch) in digits.chars().enumerate() {
+            if i > 0
+                && (digits.len() - i).is_multiple_of(3)
+                && let Some(sep) = self.group_separator
+            {
+                out.push(sep);
+            }
+            out.push(ch);
+        }
+        out
+    }
+
+    pub fn format_decimal(&self, value: f64, places: usize) -> String {
+        let fixed = format!("{:.*}", places, value.abs());
+        let (whole, frac) = fixed.split_once('.').unwrap_or((&fixed, ""));
+        let whole = whole.parse::<i64>().unwrap_or(0);
+        let mut out = String::new();
+        if value < 0.0 && fixed.chars().any(|ch| ch.is_ascii_digit() && ch != '0') {
+            out.push('-');
+        }
+        out.push_str(&self.format_int(whole));
+        if !frac.is_empty() {
+            out.push(self.decimal_separator);
+            out.push_str(frac);
+        }
+        out
+    }
+
+    /// Formats a whole-coin amount with the locale's currency symbol.
+    pub fn format_currency(&self, amount: i64) -> String {
+        let number = self.format_int(amount);
+        if self.currency_suffix {
+            format!("{number} {}", self.currency_symbol)
+        } else {
+            format!("{}{number}", self.currency_symbol)
+        }
+    }
+
+    /// Formats the clock's current day as a calendar date.
+    pub fn format_date(&self, clock: DayClock) -> String {
+        let season = self
+            .seasons
+            .get(clock.season() as usize)
+            .map(String::as_str)
+            .unwrap_or("");
+        self.date_pattern
+            .replace("{day}", &(clock.day_of_season() + 1).to_string())
+            .replace("{season}", season)
+            .replace("{year}", &(clock.year() + 1).to_string())
+    }
+}
+
+pub struct LocaleDatabase {
+    locales: Vec<Locale>,
+    fallback: Locale,
+}
+
+impl LocaleDatabase {
+    pub fn empty() -> Self {
+        Self {
+            locales: Vec::new(),
+            fallback: Locale::fallback(),
+        }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, LocaleLoadError> {
+        let dir = dir.as_ref();
+        let mut raws = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let files = load_wasm_manifest_files(&dir, &["en.yaml", "de.yaml"]).await;
+            for file in files {
+                let path = format!("{}/{}", dir, file);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| LocaleLoadError::Io(std::io::Error::other(err.to_string())))?;
+                raws.push(serde_yaml::from_str::<LocaleFile>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                raws.push(serde_yaml::from_str::<LocaleFile>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+
+        let mut db = Self::empty();
+        for raw in raws {
+            let base = Locale::fallback();
+            db.locales.push(Locale {
+                id: raw.id,
+                decimal_separator: raw.decimal_separator.unwrap_or(base.decimal_separator),
+                group_separator: match raw.group_separator {
+                    Some(separator) => separator.chars().next(),
+                    None => base.group_separator,
+                },
+                currency_symbol: raw.currency_symbol.unwrap_or(base.currency_symbol),
+                currency_suffix: raw.currency_suffix.unwrap_or(base.currency_suffix),
+                seasons: raw.seasons.filter(|names| !names.is_empty()).unwrap_or(base.seasons),
+                date_pattern: raw.date_pattern.unwrap_or(base.date_pattern),
+            });
+        }
+        if let Some(en) = db.locales.iter().find(|locale| locale.id == DEFAULT_LOCALE) {
+            db.fallback = en.clone();
+        }
+        Ok(db)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.locales.iter().any(|locale| locale.id == id)
+    }
+
+    /// Returns the locale for `id`, or the default language when it is unknown.
+    pub fn get(&self, id: &str) -> &Locale {
+        self.locales
+            .iter()
+            .find(|locale| locale.id == id)
+            .unwrap_or(&self.fallback)
+    }
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
+
+#[derive(Deserialize)]
+struct LocaleFile {
+    id: String,
+    #[serde(default)]
+    decimal_separator: Option<char>,
+    /// An empty string turns digit grouping off; left out, the default language's is used.
+    #[serde(default)]
+    group_separator: Option<String>,
+    #[serde(default)]
+    currency_symbol: Option<String>,
+    #[serde(default)]
+    currency_suffix: Option<bool>,
+    #[serde(default)]
+    seasons: Option<Vec<String>>,
+    #[serde(default)]
+    date_pattern: Option<String>,
+}
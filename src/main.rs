@@ -2,8 +2,6 @@ use macroquad::prelude::*;
 use miniquad::conf::{Icon, Platform};
 use image::imageops::FilterType;
 use std::collections::HashMap;
-use std::future::poll_fn;
-use std::task::Poll;
 
 mod map;
 mod player;
@@ -11,30 +9,159 @@ mod helpers;
 mod entity;
 mod r#trait;
 mod particle;
-mod tilemap;
 mod sound;
 mod interact;
 mod scene;
-
-use map::{TileMap, TileSet, load_structures_from_dir};
-use player::Player;
-use entity::{DamageEvent, Entity, EntityContext, EntityDatabase, MovementRegistry, PlayerTarget, Target};
-
-use sound::SoundSystem;
+mod camera;
+mod status;
+mod item;
+mod command;
+mod paged;
+mod interior;
+mod console;
+mod bench;
+mod locale;
+mod save;
+mod content;
+mod texture;
+mod farm;
+mod config;
+mod inspect;
+mod build;
+mod shop;
+mod craft;
+mod path;
+mod worker;
+mod world_map;
+mod storage;
+mod crop;
+mod sleep;
+mod slot;
+mod archive;
+mod title;
+mod difficulty;
+mod font;
+mod popup;
+mod shader;
+mod photo;
+mod backdrop;
+mod ambient;
+mod sim;
+mod profile;
+mod headless;
+mod audio_event;
+mod preload;
+mod atlas;
+mod decal;
+mod attack;
+mod companion;
+mod mount;
+mod breed;
+mod level;
+mod upgrade;
+mod equipment;
+mod tool;
+mod interaction;
+mod trigger;
+mod dialogue;
+mod cutscene;
+mod raid;
+mod transition;
+mod turret;
+mod power;
+mod conveyor;
+mod expedition;
+mod explore;
+mod automation;
+mod tiled;
+mod ldtk;
+mod animation;
+mod clip;
+mod net;
+mod coop;
+mod gamepad;
+mod server;
+mod interp;
+mod replay;
+
+use map::{StructureDef, StructureHit, StructureInteractor, TileMap, TileSet, load_structures_from_dir};
+use player::{PLAYER_HITBOX, Player, PlayerInput};
+use entity::{DamageEvent, Entity, EntityDatabase, EntityKind, MovementRegistry, PlayerTarget, Target};
+
+use sound::{FootstepSets, SoundSystem};
 use particle::ParticleSystem;
 use interact::{InteractContext, InteractRegistry};
-use scene::SceneKind;
+use expedition::{Expedition, Outcome};
+use world_map::WorldMap;
+use transition::SceneTransition;
+use scene::{HarvestedNodes, SceneFrame, SceneKind, SceneRequest, SceneStack};
+use camera::GameCamera;
+use status::StatusEffectDatabase;
+use item::{GroundItem, GroundItems, ItemDatabase};
+use command::{CommandBus, CommandSource, CommandStatus, FarmWork, RobotCommand};
+use interior::load_interiors_from_dir;
+use console::{Console, ConsoleCommand};
+use bench::{BenchLoad, Benchmark};
+use locale::{DEFAULT_LOCALE, LocaleDatabase};
+use content::ContentManifest;
+use farm::{FarmState, Season};
+use config::{ConfigWatcher, GameConfig, Settings, SettingsUi};
+use inspect::Inspector;
+use build::BuildMode;
+use shop::{ShopDatabase, ShopUi};
+use craft::{CraftUi, RecipeDatabase};
+use upgrade::{Loadout, UpgradeDatabase, UpgradeUi};
+use tool::{ToolAction, ToolUse};
+use interaction::{InteractScan, Interactable, InteractionManager};
+use worker::{JobMode, WORKER_ENTITY, WorkerBoard};
+use storage::ChestUi;
+use crop::CropDatabase;
+use sleep::SleepFade;
+use slot::{SaveManager, SlotProgress};
+use font::{TextStyle, draw_text, draw_text_styled};
+use popup::{DamageNumbers, PopupKind};
+use photo::{PhotoFilter, PhotoMode};
+use clip::ClipRecorder;
+use replay::Replay;
+use net::{NetSession, NetWorld};
+use coop::LocalCoop;
+use backdrop::BackdropDatabase;
+use ambient::AmbientZones;
+use trigger::{TriggerContext, TriggerSystem};
+use dialogue::DialogueBox;
+use cutscene::Cutscenes;
+use raid::{RaidContext, Raids};
+use power::PowerGrid;
+use automation::Automation;
+use sim::{EntitySim, FarmWorld, SimWorld, break_structures, spawn_drops};
+use profile::{Profiler, Section};
+use audio_event::{AudioEvent, AudioEvents};
+use preload::{Preload, PreloadManifest};
+use decal::DecalDatabase;
+use atlas::{AtlasBuilder, Sprite};
 
-const CAMERA_DRAG: f32 = 5.0;
 const TILE_SIZE: f32 = 16.0;
-const MOVE_DEADZONE: f32 = 16.0;
-const FOOTSTEP_INTERVAL: f32 = 0.2;
-const CAMERA_FOV: f32 = 300.0;
-const ENTITY_CULL_FADE_PAD: f32 = 96.0;
+const STATUS_ICON_SIZE: f32 = 24.0;
+const HOTBAR_KEYS: [KeyCode; item::INVENTORY_SLOTS] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
 const LOADING_SPIN_SPEED: f32 = 3.0;
-const CHUNK_ALLOC_PER_FRAME: usize = 6;
-const CHUNK_REBUILD_PER_FRAME: usize = 8;
-const SCENE_WARM_BUDGET_S: f32 = 0.006;
+/// Cropbots the farm keeps staffed; any the save didn't restore are spawned fresh.
+const FARM_WORKERS: usize = 2;
+/// Seconds between captures of the farm view used as the save slot thumbnail.
+const THUMBNAIL_INTERVAL: f32 = 5.0;
+/// Entities slower than this, in world units per second, are standing still and make no steps.
+const ENTITY_STEP_MIN_SPEED: f32 = 4.0;
+/// Entity footsteps further than this from the player aren't worth starting.
+const ENTITY_STEP_RANGE: f32 = 200.0;
 
 fn window_conf() -> Conf {
     let icon = load_window_icon(&helpers::asset_path("src/assets/favicon.png"));
@@ -44,6 +171,7 @@ fn window_conf() -> Conf {
         sample_count: 1,
         platform: Platform {
             linux_wm_class: "cropbots",
+            swap_interval: Some(if Settings::load().graphics.vsync { 1 } else { 0 }),
             webgl_version: miniquad::conf::WebGLVersion::WebGL2,
             ..Default::default()
         },
@@ -109,389 +237,1486 @@ async fn show_loading(loading: &Texture2D, label: &str, progress: f32, spin: f32
     next_frame().await;
 }
 
-async fn await_with_loading<F, T>(
-    future: F,
+async fn warm_scene_chunks_loading(
+    map: &mut TileMap,
+    tileset: &TileSet,
     loading: &Texture2D,
     label: &str,
-    progress: f32,
-    spin: &mut f32,
-) -> T
-where
-    F: std::future::Future<Output = T>,
-{
-    let mut future = std::pin::pin!(future);
+    budget: f32,
+    loading_spin: &mut f32,
+) {
     loop {
-        let polled = poll_fn(|cx| Poll::Ready(future.as_mut().poll(cx))).await;
-        match polled {
-            Poll::Ready(value) => return value,
-            Poll::Pending => {
-                *spin += LOADING_SPIN_SPEED * get_frame_time();
-                show_loading(loading, label, progress, *spin).await;
-            }
+        let done = map.warm_all_chunks_step(tileset, budget);
+        let progress = map.warm_all_chunks_progress();
+        *loading_spin += LOADING_SPIN_SPEED * get_frame_time();
+        show_loading(loading, label, progress, *loading_spin).await;
+        if done {
+            break;
         }
     }
 }
 
-async fn warm_scene_chunks_loading(
-    map: &mut TileMap,
-    tileset: &TileSet,
+/// What the scenes are built from, with the map settings from `config`.
+fn scene_assets<'a>(
+    db: &'a EntityDatabase,
+    registry: &'a MovementRegistry,
+    structures: &'a [StructureDef],
+    content: &'a ContentManifest,
+    item_db: &'a ItemDatabase,
+    grass: u8,
+    config: &GameConfig,
+) -> scene::SceneAssets<'a> {
+    scene::SceneAssets {
+        db,
+        registry,
+        structures,
+        content,
+        item_db,
+        ground_tile: grass,
+        tile_size: TILE_SIZE,
+        chunk_alloc_per_frame: config.chunk_alloc_per_frame,
+        chunk_rebuild_per_frame: config.chunk_rebuild_per_frame,
+    }
+}
+
+/// Builds the expedition a step at a time behind the loading screen, ready to `finish`.
+async fn build_expedition_loading(
+    build: &mut scene::ExpeditionBuild,
+    assets: scene::SceneAssets<'_>,
     loading: &Texture2D,
-    label: &str,
+    budget: f32,
     loading_spin: &mut f32,
 ) {
     loop {
-        let done = map.warm_all_chunks_step(tileset, SCENE_WARM_BUDGET_S);
-        let progress = map.warm_all_chunks_progress();
+        let done = build.step(assets, budget);
         *loading_spin += LOADING_SPIN_SPEED * get_frame_time();
-        show_loading(loading, label, progress, *loading_spin).await;
+        show_loading(loading, "Loading Expedition", build.progress(), *loading_spin).await;
         if done {
             break;
         }
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let loading = load_texture(&helpers::asset_path("src/assets/loading.png"))
-        .await
-        .unwrap_or_else(|_| Texture2D::empty());
-    loading.set_filter(FilterMode::Nearest);
+// Servers and headless runs draw nothing, so they're sent off before a window is opened.
+fn main() {
+    match server::ServerOptions::from_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => {
+            helpers::block_on(server::run(options));
+            return;
+        }
+        Ok(None) => {}
+        Err(err) => {
+            eprintln!("{err}");
+            return;
+        }
+    }
+    match headless::HeadlessOptions::from_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => {
+            helpers::block_on(headless::run(options));
+            return;
+        }
+        Ok(None) => {}
+        Err(err) => {
+            eprintln!("{err}");
+            return;
+        }
+    }
+    macroquad::Window::from_config(window_conf(), game());
+}
+
+async fn game() {
+    font::load().await;
+    shader::load();
+    let loading = texture::load_texture_or_placeholder(&helpers::asset_path("src/assets/loading.png")).await;
     let mut loading_spin = 0.0f32;
     loading_spin += LOADING_SPIN_SPEED * get_frame_time();
     show_loading(&loading, "Loading", 0.0, loading_spin).await;
 
-    // Load the tileset atlas (tileset.json + tileset.png)
-    let tileset = await_with_loading(
-        TileSet::load("src/assets/tileset.json", "src/assets/tileset.png"),
-        &loading,
-        "Loading",
-        0.15,
-        &mut loading_spin,
-    )
+    let mut config = GameConfig::load_from(config::CONFIG_PATH)
         .await
         .unwrap_or_else(|err| {
-            eprintln!("tileset load failed: {err}");
-            eprintln!("Please ensure src/assets/tileset.json and src/assets/tileset.png exist");
-            panic!("Tileset loading failed");
+            eprintln!("config load failed, using defaults: {err}");
+            GameConfig::default()
         });
-    let grass: u8 = if tileset.count() > 24 { 24 } else { 0 };
-    loading_spin += LOADING_SPIN_SPEED * get_frame_time();
-    show_loading(&loading, "Loading", 0.22, loading_spin).await;
-    loading_spin += LOADING_SPIN_SPEED * get_frame_time();
-    show_loading(&loading, "Loading", 0.35, loading_spin).await;
-
-    // Load structures from JSON and apply them with a fixed seed.
-    let structures = await_with_loading(
-        load_structures_from_dir("src/structure"),
-        &loading,
-        "Loading",
-        0.45,
-        &mut loading_spin,
-    )
-    .await
-    .unwrap_or_else(|err| {
+    let mut config_watcher = ConfigWatcher::new(config::CONFIG_PATH);
+    map::set_composite_chunk_layers(config.chunk_composite_layers);
+
+    let manifest = PreloadManifest::load_from(preload::PRELOAD_PATH)
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("preload manifest load failed, using defaults: {err}");
+            PreloadManifest::default()
+        });
+    let mut tileset = None;
+    let mut structures = None;
+    let mut interiors = None;
+    let mut player_texture = None;
+    let mut heart_full = None;
+    let mut heart_empty = None;
+    let mut hotbar_slot = None;
+    let mut db = None;
+    let mut particles = None;
+    let mut sounds = None;
+    let mut footsteps = None;
+    let mut audio_events = None;
+    let mut ambient = None;
+    let mut triggers = None;
+    let mut cutscenes = None;
+    let mut raids = None;
+    let mut status_db = None;
+    let mut item_db = None;
+    let mut shops = None;
+    let mut recipes = None;
+    let mut upgrade_db = None;
+    let mut crop_db = None;
+    let mut backdrops = None;
+    let mut decals = None;
+    let mut locales = None;
+    let mut content = None;
+    {
+        let texture = |path: &str| {
+            let path = helpers::asset_path(path);
+            async move { texture::load_texture_or_placeholder(&path).await }
+        };
+        let image = |path: &str| {
+            let path = helpers::asset_path(path);
+            async move { texture::load_image_or_placeholder(&path).await }
+        };
+        let mut preload = Preload::default();
+        preload.add(&mut tileset, TileSet::load(&manifest.tileset_json, &manifest.tileset_png));
+        preload.add(&mut structures, load_structures_from_dir(&manifest.structures));
+        preload.add(&mut interiors, load_interiors_from_dir(&manifest.interiors));
+        preload.add(&mut player_texture, texture(&manifest.player_texture));
+        preload.add(&mut heart_full, image(&manifest.heart_full));
+        preload.add(&mut heart_empty, image(&manifest.heart_empty));
+        preload.add(&mut hotbar_slot, image(&manifest.hotbar_slot));
+        preload.add(&mut db, EntityDatabase::load_from(&manifest.entities));
+        preload.add(&mut particles, ParticleSystem::load_from(&manifest.particles));
+        preload.add(&mut sounds, SoundSystem::load_from(&manifest.sounds));
+        preload.add(&mut footsteps, FootstepSets::load_from(&manifest.footsteps));
+        preload.add(&mut audio_events, AudioEvents::load_from(&manifest.audio_events));
+        preload.add(&mut ambient, AmbientZones::load_from(&manifest.ambient));
+        preload.add(&mut triggers, TriggerSystem::load_from(&manifest.triggers));
+        preload.add(&mut cutscenes, Cutscenes::load_from(&manifest.cutscenes));
+        preload.add(&mut raids, Raids::load_from(&manifest.raids));
+        preload.add(&mut status_db, StatusEffectDatabase::load_from(&manifest.status_effects));
+        preload.add(&mut item_db, ItemDatabase::load_from(&manifest.items));
+        preload.add(&mut shops, ShopDatabase::load_from(&manifest.shops));
+        preload.add(&mut recipes, RecipeDatabase::load_from(&manifest.recipes));
+        preload.add(&mut upgrade_db, UpgradeDatabase::load_from(&manifest.upgrades));
+        preload.add(&mut crop_db, CropDatabase::load_from(&manifest.crops));
+        preload.add(&mut backdrops, BackdropDatabase::load_from(&manifest.backdrops));
+        preload.add(&mut decals, DecalDatabase::load_from(&manifest.decals));
+        preload.add(&mut locales, LocaleDatabase::load_from(&manifest.locales));
+        preload.add(&mut content, ContentManifest::load_from(&manifest.content));
+        while !preload.is_done() {
+            preload.step().await;
+            loading_spin += LOADING_SPIN_SPEED * get_frame_time();
+            show_loading(&loading, "Loading", preload.progress(), loading_spin).await;
+        }
+    }
+
+    let mut tileset = preload::loaded(tileset).unwrap_or_else(|err| {
+        eprintln!("tileset load failed: {err}");
+        eprintln!("Please ensure {} and {} exist", manifest.tileset_json, manifest.tileset_png);
+        panic!("Tileset loading failed");
+    });
+    let grass = tileset.ground_tile();
+    let structures = preload::loaded(structures).unwrap_or_else(|err| {
         eprintln!("structure load failed: {err}");
         Vec::new()
     });
-    loading_spin += LOADING_SPIN_SPEED * get_frame_time();
-    show_loading(&loading, "Loading", 0.55, loading_spin).await;
-
-    // Player
-    let player_texture = await_with_loading(
-        helpers::load_single_texture("src/assets/objects", "player08"),
-        &loading,
-        "Loading",
-        0.6,
-        &mut loading_spin,
-    )
-    .await
-    .unwrap_or_else(Texture2D::empty);
-    loading_spin += LOADING_SPIN_SPEED * get_frame_time();
-    show_loading(&loading, "Loading", 0.65, loading_spin).await;
+    let interiors = preload::loaded(interiors).unwrap_or_else(|err| {
+        eprintln!("interior load failed: {err}");
+        Vec::new()
+    });
+    // The HUD's own sprites share a page; item and status icons come packed by their databases.
+    let mut ui_atlas = AtlasBuilder::default();
+    let heart_full = ui_atlas.add_image(preload::loaded(heart_full));
+    let heart_empty = ui_atlas.add_image(preload::loaded(heart_empty));
+    let hotbar_slot = ui_atlas.add_image(preload::loaded(hotbar_slot));
+    let ui_atlas = ui_atlas.build();
+    let heart_full = ui_atlas.sprite(heart_full);
+    let heart_empty = ui_atlas.sprite(heart_empty);
+    let hotbar_slot = ui_atlas.sprite(hotbar_slot);
+    let mut db = preload::loaded(db).unwrap_or_else(|err| {
+        eprintln!("entity load failed: {err}");
+        EntityDatabase::empty()
+    });
+    let mut particles = preload::loaded(particles).unwrap_or_else(|err| {
+        eprintln!("particle load failed: {err}");
+        ParticleSystem::empty()
+    });
+    let mut sounds = preload::loaded(sounds).unwrap_or_else(|err| {
+        eprintln!("sound load failed: {err}");
+        SoundSystem::empty()
+    });
+    let mut footsteps = preload::loaded(footsteps).unwrap_or_else(|err| {
+        eprintln!("footstep set load failed: {err}");
+        FootstepSets::empty()
+    });
+    let mut audio_events = preload::loaded(audio_events).unwrap_or_else(|err| {
+        eprintln!("audio event load failed: {err}");
+        AudioEvents::empty()
+    });
+    let mut ambient = preload::loaded(ambient).unwrap_or_else(|err| {
+        eprintln!("ambient zone load failed: {err}");
+        AmbientZones::empty()
+    });
+    let mut triggers = preload::loaded(triggers).unwrap_or_else(|err| {
+        eprintln!("trigger load failed: {err}");
+        TriggerSystem::empty()
+    });
+    let mut cutscenes = preload::loaded(cutscenes).unwrap_or_else(|err| {
+        eprintln!("cutscene load failed: {err}");
+        Cutscenes::empty()
+    });
+    let mut raids = preload::loaded(raids).unwrap_or_else(|err| {
+        eprintln!("raid load failed: {err}");
+        Raids::empty()
+    });
+    let mut dialogue = DialogueBox::default();
+    let status_db = preload::loaded(status_db).unwrap_or_else(|err| {
+        eprintln!("status effect load failed: {err}");
+        StatusEffectDatabase::empty()
+    });
+    let item_db = preload::loaded(item_db).unwrap_or_else(|err| {
+        eprintln!("item load failed: {err}");
+        ItemDatabase::empty()
+    });
+    let shops = preload::loaded(shops).unwrap_or_else(|err| {
+        eprintln!("shop load failed: {err}");
+        ShopDatabase::empty()
+    });
+    let recipes = preload::loaded(recipes).unwrap_or_else(|err| {
+        eprintln!("recipe load failed: {err}");
+        RecipeDatabase::empty()
+    });
+    let upgrade_db = preload::loaded(upgrade_db).unwrap_or_else(|err| {
+        eprintln!("upgrade load failed: {err}");
+        UpgradeDatabase::empty()
+    });
+    let crop_db = preload::loaded(crop_db).unwrap_or_else(|err| {
+        eprintln!("crop load failed: {err}");
+        CropDatabase::empty()
+    });
+    let mut backdrops = preload::loaded(backdrops).unwrap_or_else(|err| {
+        eprintln!("backdrop load failed: {err}");
+        BackdropDatabase::empty()
+    });
+    let decals = preload::loaded(decals).unwrap_or_else(|err| {
+        eprintln!("decal load failed: {err}");
+        DecalDatabase::empty()
+    });
+    let locales = preload::loaded(locales).unwrap_or_else(|err| {
+        eprintln!("locale load failed: {err}");
+        LocaleDatabase::empty()
+    });
+    let mut language = DEFAULT_LOCALE.to_string();
+    let content = preload::loaded(content).unwrap_or_else(|err| {
+        eprintln!("content manifest load failed: {err}");
+        ContentManifest::empty()
+    });
+
     let mut player = Player::new(
         vec2(200.0, 300.0 + 16.0 / 2.0),
-        player_texture,
-        Rect::new(-6.5 / 2.0, -8.0, 6.5, 8.0),
+        preload::loaded(player_texture),
+        PLAYER_HITBOX,
+        config.player,
     );
-    loading_spin += LOADING_SPIN_SPEED * get_frame_time();
-    show_loading(&loading, "Loading", 0.68, loading_spin).await;
-
-    let heart_full = load_texture(&helpers::asset_path("src/assets/ui/heart.png"))
-        .await
-        .unwrap_or_else(|_| Texture2D::empty());
-    let heart_empty = load_texture(&helpers::asset_path("src/assets/ui/heart-empty.png"))
-        .await
-        .unwrap_or_else(|_| Texture2D::empty());
-    heart_full.set_filter(FilterMode::Nearest);
-    heart_empty.set_filter(FilterMode::Nearest);
 
     // Camera
-    let mut camera = Camera2D {
-        target: player.position(),
-        zoom: vec2(1.0, 1.0),
-        ..Default::default()
-    };
+    let mut camera = GameCamera::new(player.position(), config.camera_fov, config.camera_drag);
+    camera.set_dead_zone(vec2(config.camera_dead_zone, config.camera_dead_zone));
 
     let mut i: f32 = 0.0;
     let mut fps: i32 = 0;
 
-    let use_render_target = false;
-    let render_scale = 0.5;
+    let mut settings = Settings::load();
+    let mut settings_ui = SettingsUi::default();
+    camera.set_shake_scale(settings.graphics.screen_shake);
+    let mut render_scale = settings.graphics.render_scale;
     let mut scene_target = create_scene_target(render_scale, screen_width(), screen_height());
     let mut last_screen_width = screen_width();
     let mut last_screen_height = screen_height();
-    camera.zoom = camera_zoom_for_fov(CAMERA_FOV, use_render_target);
-    camera.render_target = if use_render_target {
-        Some(scene_target.clone())
-    } else {
-        None
-    };
+    for (channel, volume) in settings.channel_volumes() {
+        sounds.set_channel_volume(channel, volume);
+    }
 
     // Entity registry
     let registry = MovementRegistry::new();
-    let db = await_with_loading(
-        EntityDatabase::load_from("src/entity"),
-        &loading,
-        "Loading",
-        0.7,
-        &mut loading_spin,
-    )
-        .await
-        .unwrap_or_else(|err| {
-            eprintln!("entity load failed: {err}");
-            EntityDatabase::empty()
-        });
-    loading_spin += LOADING_SPIN_SPEED * get_frame_time();
-    show_loading(&loading, "Loading", 0.75, loading_spin).await;
     let mut maps = TileMap::new_deferred(1, 1, TILE_SIZE, Vec2::new(TILE_SIZE, TILE_SIZE), 0.0);
+    maps.set_mesh_chunks(settings.graphics.chunk_meshes);
     let mut entities = Vec::<Entity>::new();
-    scene::scene_expedition(
-        &mut maps,
-        &mut entities,
-        &db,
-        &registry,
-        &structures,
-        grass,
-        TILE_SIZE,
-        CHUNK_ALLOC_PER_FRAME,
-        CHUNK_REBUILD_PER_FRAME,
-    );
+    // The farm save, and with it the calendar, loads on the first visit to the farm.
+    let assets = scene_assets(&db, &registry, &structures, &content, &item_db, grass, &config);
+    let mut build = scene::ExpeditionBuild::new(assets, Season::Spring, &[], config.extraction_distance);
+    build_expedition_loading(&mut build, assets, &loading, config.scene_warm_budget, &mut loading_spin).await;
+    let beacon = build.finish(&mut maps, &mut entities);
     player.set_position(scene::expedition_spawn_point());
+    camera.set_bounds(Some(maps.world_bounds()));
+    camera.snap_to(player.position());
     let mut current_scene = SceneKind::Expedition;
+    let mut pending_scene: Option<SceneRequest> = None;
+    let mut scene_stack = SceneStack::default();
+    let mut console = Console::new();
+    let mut inspector = Inspector::default();
+    let mut build_mode = BuildMode::default();
+    let mut power = PowerGrid::default();
+    let mut automation = Automation::default();
+    let mut shop_ui = ShopUi::default();
+    let mut craft_ui = CraftUi::default();
+    let mut upgrade_ui = UpgradeUi::default();
+    let mut chest_ui = ChestUi::default();
+    let mut job_mode = JobMode::default();
+    let mut worker_board = WorkerBoard::default();
+    let mut benchmark: Option<Benchmark> = None;
+    let mut profiler = Profiler::default();
+    let mut map_debug = false;
+
+    let mut draw_order: Vec<(f32, DepthItem)> = Vec::new();
+    let mut visible_structures: Vec<usize> = Vec::new();
 
-    let mut draw_order: Vec<usize> = Vec::new();
-
-    // Particle system
-    let mut particles = await_with_loading(
-        ParticleSystem::load_from("src/particle"),
-        &loading,
-        "Loading",
-        0.8,
-        &mut loading_spin,
-    )
-        .await
-        .unwrap_or_else(|err| {
-            eprintln!("particle load failed: {err}");
-            ParticleSystem::empty()
-        });
-    loading_spin += LOADING_SPIN_SPEED * get_frame_time();
-    show_loading(&loading, "Loading", 0.85, loading_spin).await;
     let mut walk_trail = particles.emitter("dust_trail", player.position());
     let mut dash_trail = particles.emitter("dash_afterimage", player.position());
 
-    // Load sounds
-    let sounds = await_with_loading(
-        SoundSystem::load_from("src/sound"),
-        &loading,
-        "Loading sounds",
-        0.9,
-        &mut loading_spin,
-    )
-        .await
-        .unwrap_or_else(|err| {
-            eprintln!("sound load failed: {err}");
-            SoundSystem::empty()
-        });
-    loading_spin += LOADING_SPIN_SPEED * get_frame_time();
-    show_loading(&loading, "Loading", 0.98, loading_spin).await;
+    let mut saves = SaveManager::load();
+    if !title::run(&mut saves, locales.get(&language)).await {
+        return;
+    }
+    let mut playtime = saves.active().map_or(0.0, |meta| meta.playtime);
+    db.set_difficulty(saves.active().map(|meta| meta.difficulty).unwrap_or_default());
+    let mut loadout = saves.active().map_or_else(Loadout::default, |meta| Loadout {
+        upgrades: meta.upgrades.clone(),
+        equipment: meta.equipment.clone(),
+    });
+    player.set_stats(loadout.stats(config.player, &upgrade_db, &item_db));
+    player.set_overlays(loadout.equipment.overlays(&item_db));
+    if let Some(meta) = saves.active() {
+        player.gain_xp(&config.player_growth, meta.player_xp);
+    }
+    if let Some(meta) = saves.active()
+        && meta.player_hp > 0.0
+    {
+        player.apply_damage(player.hp() - meta.player_hp);
+    }
+    // Last view of the farm, stored with the next save as the slot's thumbnail.
+    let mut farm_thumbnail: Option<image::RgbaImage> = None;
+    let mut thumbnail_timer = 0.0f32;
+    let mut autosave_timer = 0.0f32;
+    let mut autosave_due = false;
 
     let mut footstep_timer = 0.0f32;
+    let mut chop_cooldown = 0.0f32;
+    let mut interactions = InteractionManager::default();
+    let mut selected_slot = 0usize;
+    let mut farm = FarmState::default();
     let mut damage_events: Vec<DamageEvent> = Vec::new();
-    let mut entity_target_cache: HashMap<(u64, u8), Option<entity::EntityTarget>> = HashMap::new();
+    let mut entity_sim = EntitySim::default();
     let mut player_dead = false;
+    let mut ground_items = GroundItems::default();
+    let mut expedition_items: Vec<GroundItem> = Vec::new();
+    let mut expedition_harvested = HarvestedNodes::default();
+    let mut expedition = Expedition::default();
+    expedition.start(player.inventory(), config.expedition_minutes, beacon);
+    let mut expedition_outcome: Option<Outcome> = None;
+    let mut world_map = WorldMap::default();
+    let mut scene_transition = SceneTransition::default();
+    // Set by the land office, and carried out at the top of the next frame.
+    let mut land_purchase = false;
+    world_map.reset(&maps);
+    farm.explored.fit(maps.width(), maps.height());
+    let mut sleep_fade = SleepFade::default();
+    let mut damage_numbers = DamageNumbers::default();
+    let mut photo = PhotoMode::default();
+    let mut clips = ClipRecorder::default();
+    let mut replay = Replay::default();
+    let mut net: Option<NetSession> = None;
+    let mut local_coop = LocalCoop::default();
+    damage_numbers.set_enabled(settings.graphics.damage_numbers);
+    let mut command_bus = CommandBus::new();
     let interact_registry = InteractRegistry::new();
+    player.inventory_mut().add(&item_db, "hoe", 1);
+    player.inventory_mut().add(&item_db, "watering_can", 1);
+    player.inventory_mut().add(&item_db, "axe", 1);
     
     loop {
-        let dt = get_frame_time();
+        // Photo mode and replays freeze the world; the free camera keeps real time.
+        let paused = photo.is_active() || replay.is_playing();
+        let dt = if paused { 0.0 } else { get_frame_time() };
+        playtime += dt;
+        if farm.update(dt, &crop_db) > 0 {
+            expedition_harvested.regrow(farm.clock.day);
+        }
+        if sleep_fade.update(dt) {
+            farm.sleep(&crop_db);
+            player.heal(player.max_hp());
+            expedition_harvested.regrow(farm.clock.day);
+            autosave_due = true;
+            console.print(locales.get(&language).format_date(farm.clock));
+        }
+        autosave_timer += dt;
+        let autosave_minutes = settings.gameplay.autosave_minutes;
+        if autosave_minutes > 0.0 && autosave_timer >= autosave_minutes * 60.0 {
+            autosave_due = true;
+        }
+        // The farm is only in memory while it's the current scene; leaving it saves on the way out.
+        if autosave_due && current_scene == SceneKind::Farm {
+            autosave_due = false;
+            autosave_timer = 0.0;
+            let (farm_map, farm_items, farm_entities) = match scene_stack.root() {
+                Some(root) => (&root.map, root.ground_items.as_slice(), root.entities.as_slice()),
+                None => (&maps, ground_items.items(), entities.as_slice()),
+            };
+            let progress = SlotProgress {
+                playtime,
+                day: farm.clock.day,
+                player_hp: player.hp(),
+                player_xp: player.xp(),
+                upgrades: loadout.upgrades.clone(),
+                equipment: loadout.equipment.clone(),
+            };
+            let saved = scene::save_farm_scene(
+                farm_map,
+                farm_items,
+                farm_entities,
+                &db,
+                &worker_board,
+                &farm,
+                &content,
+            )
+            .ok_or_else(|| std::io::Error::other("the farm couldn't be encoded"))
+            .and_then(|bytes| saves.write_farm(&bytes, progress, farm_thumbnail.as_ref()));
+            if let Err(err) = saved {
+                console.print(format!("autosave failed: {err}"));
+            }
+        }
+
+        if let Some(reloaded) = config_watcher.poll(dt) {
+            match reloaded {
+                Ok(next) => {
+                    camera.set_tuning(next.camera_fov, next.camera_drag);
+                    camera.set_dead_zone(vec2(next.camera_dead_zone, next.camera_dead_zone));
+                    map::set_composite_chunk_layers(next.chunk_composite_layers);
+                    config = next;
+                    console.print("config reloaded");
+                }
+                Err(err) => console.print(format!("config reload failed: {err}")),
+            }
+        }
         
-        // Check for resolution changes and recreate render target if needed
+        // Check for resolution or scale changes and recreate render target if needed
+        // Photo filters are applied while drawing the scene target to the screen.
+        let use_render_target = settings.graphics.render_scale < 1.0 || photo.is_active();
         if use_render_target {
             let current_width = screen_width();
             let current_height = screen_height();
-            if current_width != last_screen_width || current_height != last_screen_height {
+            if current_width != last_screen_width
+                || current_height != last_screen_height
+                || render_scale != settings.graphics.render_scale
+            {
+                render_scale = settings.graphics.render_scale;
                 scene_target = create_scene_target(render_scale, current_width, current_height);
                 last_screen_width = current_width;
                 last_screen_height = current_height;
             }
         }
 
-        if is_key_pressed(KeyCode::F1) && current_scene != SceneKind::Expedition {
-            if current_scene == SceneKind::Farm {
-                let _ = scene::save_farm_scene(&maps);
+        if let Some(line) = console.update() {
+            match ConsoleCommand::parse(&line) {
+                Ok(ConsoleCommand::Language { id }) => {
+                    if locales.contains(&id) {
+                        console.print(format!("language set to {id}"));
+                        language = id;
+                    } else {
+                        console.print(format!("unknown language '{id}'"));
+                    }
+                }
+                Ok(ConsoleCommand::Sleep) => sleep_fade.start(),
+                Ok(ConsoleCommand::Difficulty(difficulty)) => {
+                    db.set_difficulty(difficulty);
+                    if !saves.set_difficulty(difficulty) {
+                        eprintln!("failed to save difficulty");
+                    }
+                    console.print(format!(
+                        "difficulty {}: enemy health x{}, damage x{}, spawns x{}",
+                        difficulty.preset.label(),
+                        difficulty.enemy_health,
+                        difficulty.enemy_damage,
+                        difficulty.spawn_count
+                    ));
+                }
+                Ok(ConsoleCommand::Host { port }) => {
+                    if let Some(session) = net.take() {
+                        session.leave();
+                    }
+                    match NetSession::host(port) {
+                        Ok(session) => {
+                            console.print(format!("hosting co-op on port {port}"));
+                            net = Some(session);
+                        }
+                        Err(err) => console.print(format!("couldn't host: {err}")),
+                    }
+                }
+                Ok(ConsoleCommand::Join { addr }) => {
+                    if let Some(session) = net.take() {
+                        session.leave();
+                    }
+                    match NetSession::join(&addr) {
+                        Ok(session) => {
+                            console.print(format!("joining {addr}"));
+                            net = Some(session);
+                        }
+                        Err(err) => console.print(format!("couldn't join: {err}")),
+                    }
+                }
+                Ok(ConsoleCommand::Leave) => match net.take() {
+                    Some(session) => {
+                        session.leave();
+                        console.print("left co-op");
+                    }
+                    None => console.print("not in co-op"),
+                },
+                Ok(ConsoleCommand::LocalCoop { join: true }) => console.print(if local_coop.join(&player) {
+                    "player two joined: a gamepad, or arrow keys to move and right shift to dash"
+                } else {
+                    "player two is already in"
+                }),
+                Ok(ConsoleCommand::LocalCoop { join: false }) => {
+                    console.print(if local_coop.leave() { "player two left" } else { "player two isn't in" })
+                }
+                Ok(ConsoleCommand::Order { robot, command }) => {
+                    let id = command_bus.send(robot, CommandSource::Script, command);
+                    console.print(format!("sent order {id} to robot {robot}"));
+                }
+                // Peers would be sent the replayed positions.
+                Ok(ConsoleCommand::Replay) if net.is_some() => console.print("can't replay in co-op"),
+                Ok(ConsoleCommand::Replay) => console.print(if replay.play(&entities, &player) {
+                    "replaying the last few seconds"
+                } else {
+                    "nothing to replay"
+                }),
+                Ok(_) if benchmark.is_some() => console.print("a benchmark is already running"),
+                Ok(ConsoleCommand::BenchParticles { count }) => {
+                    let capacity = particles.capacity("bench").unwrap_or(0);
+                    if capacity == 0 {
+                        console.print("missing 'bench' particle template");
+                    } else {
+                        if count > capacity {
+                            console.print(format!("particle count capped at {capacity}"));
+                        }
+                        let count = count.min(capacity);
+                        console.print(format!("running bench particles {count}"));
+                        benchmark = Some(Benchmark::new(format!("particles {count}"), BenchLoad::Particles { count }));
+                    }
+                }
+                Ok(ConsoleCommand::BenchEntities { def, count }) => {
+                    let area = camera.view_rect();
+                    let mut uids = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        let pos = vec2(
+                            area.x + helpers::random_f32() * area.w,
+                            area.y + helpers::random_f32() * area.h,
+                        );
+                        let Some(ent) = Entity::spawn(&db, &def, pos, &registry) else {
+                            break;
+                        };
+                        uids.push(ent.instance.uid);
+                        entities.push(ent);
+                    }
+                    if uids.is_empty() {
+                        console.print(format!("unknown entity '{def}'"));
+                    } else {
+                        console.print(format!("running bench entities {def} {count}"));
+                        benchmark = Some(Benchmark::new(
+                            format!("entities {def} {count}"),
+                            BenchLoad::Entities { uids },
+                        ));
+                    }
+                }
+                Err(err) => console.print(err),
+            }
+        }
+
+        let mut requested_scene = pending_scene.take();
+        if is_key_pressed(KeyCode::F1) {
+            requested_scene = Some(SceneRequest::Switch(SceneKind::Expedition));
+        }
+        if is_key_pressed(KeyCode::F2) {
+            requested_scene = Some(SceneRequest::Switch(SceneKind::Farm));
+        }
+        if let Some(request) = requested_scene.as_ref() {
+            let style = match request {
+                SceneRequest::Switch(_) => config.scene_transition,
+                SceneRequest::EnterInterior(_) | SceneRequest::ExitInterior => config.interior_transition,
+            };
+            scene_transition.cover(style).await;
+        }
+
+        // Companions follow the player from scene to scene.
+        let scene_before = (current_scene, scene_stack.depth());
+        let companions = match requested_scene {
+            Some(_) => companion::carry(&entities, &db),
+            None => Vec::new(),
+        };
+
+        // Switching scenes from inside an interior first returns to the outdoor map.
+        if let Some(SceneRequest::Switch(kind)) = requested_scene
+            && kind != current_scene
+            && let Some(root) = scene_stack.unwind()
+        {
+            restore_scene_frame(root, &mut maps, &mut entities, &mut ground_items, &mut player, &mut camera);
+        }
+
+        if requested_scene == Some(SceneRequest::Switch(SceneKind::Expedition))
+            && current_scene != SceneKind::Expedition
+        {
+            if current_scene == SceneKind::Farm
+                && let Some(bytes) = scene::save_farm_scene(
+                    &maps,
+                    ground_items.items(),
+                    &entities,
+                    &db,
+                    &worker_board,
+                    &farm,
+                    &content,
+                )
+            {
+                let progress = SlotProgress {
+                    playtime,
+                    day: farm.clock.day,
+                    player_hp: player.hp(),
+                    player_xp: player.xp(),
+                    upgrades: loadout.upgrades.clone(),
+                    equipment: loadout.equipment.clone(),
+                };
+                if let Err(err) = saves.write_farm(&bytes, progress, farm_thumbnail.as_ref()) {
+                    eprintln!("failed to save the farm: {err}");
+                }
+                autosave_timer = 0.0;
+                autosave_due = false;
             }
-            loading_spin += LOADING_SPIN_SPEED * get_frame_time();
-            show_loading(&loading, "Loading Expedition", 0.1, loading_spin).await;
-            scene::scene_expedition(
-                &mut maps,
-                &mut entities,
-                &db,
-                &registry,
-                &structures,
-                grass,
-                TILE_SIZE,
-                CHUNK_ALLOC_PER_FRAME,
-                CHUNK_REBUILD_PER_FRAME,
-            );
+            let assets = scene_assets(&db, &registry, &structures, &content, &item_db, grass, &config);
+            let harvested = expedition_harvested.areas();
+            let mut build =
+                scene::ExpeditionBuild::new(assets, farm.clock.season(), &harvested, config.extraction_distance);
+            build_expedition_loading(&mut build, assets, &loading, config.scene_warm_budget, &mut loading_spin).await;
+            let beacon = build.finish(&mut maps, &mut entities);
+            // The expedition is laid out from fixed seeds, so what was left behind lies where it
+            // fell, but the beacon moves and harvested nodes grow back over whatever they land on.
+            let tile_size = maps.tile_size();
+            let left_behind = std::mem::take(&mut expedition_items)
+                .into_iter()
+                .filter(|item| !maps.is_solid((item.x / tile_size) as usize, (item.y / tile_size) as usize))
+                .collect();
+            ground_items.replace(left_behind);
+            expedition.start(player.inventory(), config.expedition_minutes, beacon);
+            farm.explored.fit(maps.width(), maps.height());
             player.set_position(scene::expedition_spawn_point());
-            camera.target = player.position();
-            entity_target_cache.clear();
+            camera.set_bounds(Some(maps.world_bounds()));
+            camera.snap_to(player.position());
+            entity_sim.clear();
+            replay.clear();
             damage_events.clear();
+            damage_numbers.clear();
             current_scene = SceneKind::Expedition;
-            loading_spin += LOADING_SPIN_SPEED * get_frame_time();
-            show_loading(&loading, "Loading Expedition", 1.0, loading_spin).await;
         }
 
-        if is_key_pressed(KeyCode::F2) && current_scene != SceneKind::Farm {
+        if requested_scene == Some(SceneRequest::Switch(SceneKind::Farm)) && current_scene != SceneKind::Farm {
+            // Workers captured out on the expedition come home with the player.
+            let mut recruits = Vec::new();
+            if current_scene == SceneKind::Expedition {
+                // Whatever ended the run, the loot is only kept by reaching the beacon.
+                let outcome = expedition_outcome.take().unwrap_or(Outcome::Extracted);
+                expedition.finish(outcome, player.inventory_mut());
+                if player_dead {
+                    player_dead = false;
+                    player.heal(player.max_hp());
+                }
+                expedition_items = ground_items.take();
+                recruits.extend(
+                    entities
+                        .iter()
+                        .filter(|ent| db.entities[ent.instance.def].worker && !ent.instance.is_dying())
+                        .map(|ent| ent.to_saved(&db)),
+                );
+            }
             loading_spin += LOADING_SPIN_SPEED * get_frame_time();
             show_loading(&loading, "Loading Farm", 0.08, loading_spin).await;
             scene::scene_farm(
                 &mut maps,
                 &mut entities,
-                &structures,
-                grass,
-                TILE_SIZE,
-                CHUNK_ALLOC_PER_FRAME,
-                CHUNK_REBUILD_PER_FRAME,
+                scene_assets(&db, &registry, &structures, &content, &item_db, grass, &config),
+                &mut farm,
+                &mut worker_board,
+                &mut ground_items,
+                &saves,
             );
+            // Shopkeepers and cropbots the save didn't bring back start at their posts.
+            for (i, keeper) in shops.keepers().enumerate() {
+                if entities.iter().any(|ent| db.entities[ent.instance.def].id == keeper) {
+                    continue;
+                }
+                let pos = scene::farm_shop_point(&maps, i);
+                if let Some(entity) = Entity::spawn(&db, keeper, pos, &registry) {
+                    entities.push(entity);
+                }
+            }
+            let workers = entities
+                .iter()
+                .filter(|ent| db.entities[ent.instance.def].id == WORKER_ENTITY)
+                .count();
+            for i in workers..FARM_WORKERS {
+                let pos = scene::farm_worker_point(&maps, i);
+                if let Some(entity) = Entity::spawn(&db, WORKER_ENTITY, pos, &registry) {
+                    entities.push(entity);
+                }
+            }
+            let pos = scene::farm_spawn_point(&maps);
+            if !entities.iter().any(|ent| db.entities[ent.instance.def].companion.is_some())
+                && let Some(entity) = Entity::spawn(&db, companion::COMPANION_ENTITY, pos, &registry)
+            {
+                entities.push(entity);
+            }
+            for (i, saved) in recruits.iter().enumerate() {
+                if let Some(mut entity) = Entity::from_saved(&db, saved, &registry) {
+                    entity.instance.pos = scene::farm_worker_point(&maps, FARM_WORKERS.max(workers) + i);
+                    entities.push(entity);
+                }
+            }
             player.set_position(scene::farm_spawn_point(&maps));
-            camera.target = player.position();
-            entity_target_cache.clear();
+            camera.set_bounds(Some(maps.world_bounds()));
+            camera.snap_to(player.position());
+            entity_sim.clear();
+            replay.clear();
             damage_events.clear();
+            damage_numbers.clear();
             current_scene = SceneKind::Farm;
+            thumbnail_timer = 0.0;
             warm_scene_chunks_loading(
                 &mut maps,
                 &tileset,
                 &loading,
                 "Loading Farm",
+                config.scene_warm_budget,
                 &mut loading_spin,
             )
             .await;
         }
 
+        if let Some(SceneRequest::EnterInterior(id)) = requested_scene.as_ref() {
+            match interior::find_interior(&interiors, id) {
+                Some(def) => {
+                    loading_spin += LOADING_SPIN_SPEED * get_frame_time();
+                    show_loading(&loading, "Loading", 0.1, loading_spin).await;
+                    let outdoor = std::mem::replace(
+                        &mut maps,
+                        def.build(
+                            &structures,
+                            TILE_SIZE,
+                            config.chunk_alloc_per_frame,
+                            config.chunk_rebuild_per_frame,
+                        ),
+                    );
+                    scene_stack.push(SceneFrame {
+                        map: outdoor,
+                        entities: std::mem::take(&mut entities),
+                        ground_items: ground_items.take(),
+                        player_pos: player.position(),
+                        camera_target: camera.target(),
+                    });
+                    entities = def.spawn_entities(&db, &registry, TILE_SIZE);
+                    player.set_position(def.spawn_point(TILE_SIZE));
+                    camera.set_bounds(Some(maps.world_bounds()));
+                    camera.snap_to(player.position());
+                    entity_sim.clear();
+                    replay.clear();
+                    damage_events.clear();
+                    damage_numbers.clear();
+                    warm_scene_chunks_loading(
+                        &mut maps,
+                        &tileset,
+                        &loading,
+                        "Loading",
+                        config.scene_warm_budget,
+                        &mut loading_spin,
+                    )
+                    .await;
+                }
+                None => eprintln!("unknown interior '{id}'"),
+            }
+        }
+
+        // Stepping in or out of a farm building is a scene transition worth saving on.
+        if matches!(requested_scene, Some(SceneRequest::EnterInterior(_) | SceneRequest::ExitInterior))
+            && current_scene == SceneKind::Farm
+        {
+            autosave_due = true;
+        }
+
+        if requested_scene == Some(SceneRequest::ExitInterior)
+            && let Some(frame) = scene_stack.pop()
+        {
+            restore_scene_frame(frame, &mut maps, &mut entities, &mut ground_items, &mut player, &mut camera);
+            entity_sim.clear();
+            replay.clear();
+            damage_events.clear();
+            damage_numbers.clear();
+        }
+        if (current_scene, scene_stack.depth()) != scene_before {
+            companion::land(&mut entities, &db, &registry, &companions, player.position());
+            // Mounts stay behind in the scene they were ridden in.
+            mount::dismount(&mut player, &mut entities);
+            world_map.reset(&maps);
+        }
+
+        if requested_scene.is_some() {
+            scene_transition.reveal();
+        }
+
+        if std::mem::take(&mut land_purchase) && current_scene == SceneKind::Farm && scene_stack.is_empty() {
+            match scene::farm_expansion(&maps).filter(|&level| level + 1 < scene::FARM_SIZES.len()) {
+                None => console.print("the farm can't grow any bigger".to_string()),
+                Some(level) => {
+                    let cost = config.land_cost << level;
+                    if player.spend_coins(cost) {
+                        loading_spin += LOADING_SPIN_SPEED * get_frame_time();
+                        show_loading(&loading, "Clearing Land", 0.1, loading_spin).await;
+                        let grown = scene::expand_farm(
+                            &mut maps,
+                            &structures,
+                            &mut farm,
+                            grass,
+                            config.chunk_alloc_per_frame,
+                            config.chunk_rebuild_per_frame,
+                        );
+                        camera.set_bounds(Some(maps.world_bounds()));
+                        warm_scene_chunks_loading(
+                            &mut maps,
+                            &tileset,
+                            &loading,
+                            "Clearing Land",
+                            config.scene_warm_budget,
+                            &mut loading_spin,
+                        )
+                        .await;
+                        world_map.reset(&maps);
+                        match grown {
+                            Some((width, height)) => {
+                                console.print(format!("bought land: the farm is now {width} by {height}"));
+                                autosave_due = true;
+                            }
+                            None => player.add_coins(cost),
+                        }
+                    } else {
+                        console.print(format!("more land costs {cost} coins"));
+                    }
+                }
+            }
+        }
+
         if is_quit_requested() {
-            if current_scene == SceneKind::Farm {
-                let _ = scene::save_farm_scene(&maps);
+            if let Some(root) = scene_stack.unwind() {
+                restore_scene_frame(root, &mut maps, &mut entities, &mut ground_items, &mut player, &mut camera);
+            }
+            if current_scene == SceneKind::Farm
+                && let Some(bytes) = scene::save_farm_scene(
+                    &maps,
+                    ground_items.items(),
+                    &entities,
+                    &db,
+                    &worker_board,
+                    &farm,
+                    &content,
+                )
+            {
+                let progress = SlotProgress {
+                    playtime,
+                    day: farm.clock.day,
+                    player_hp: player.hp(),
+                    player_xp: player.xp(),
+                    upgrades: loadout.upgrades.clone(),
+                    equipment: loadout.equipment.clone(),
+                };
+                if let Err(err) = saves.write_farm(&bytes, progress, farm_thumbnail.as_ref()) {
+                    eprintln!("failed to save the farm: {err}");
+                }
             }
             break;
         }
         
-        if !player_dead {
-            player.update(&maps);
+        let cutscene = cutscenes.is_playing();
+        let mut local_input = PlayerInput::default();
+        // Player two keeps going while player one is down.
+        let players_can_move =
+            !console.is_open() && !sleep_fade.is_active() && !settings_ui.is_open() && !paused && !cutscene;
+        let can_move = players_can_move && !player_dead;
+        if can_move {
+            let was_dashing = player.is_dashing();
+            local_input = PlayerInput::from_keys();
+            player.step(local_input, &maps, get_frame_time());
+            if player.is_dashing() && !was_dashing {
+                status_db.apply(player.status_effects_mut(), "winded");
+                audio_events.push(AudioEvent::Dash { pos: player.position() });
+            }
+        }
+        if local_coop.update(&player, &maps, players_can_move)
+            && let Some(two) = local_coop.player()
+        {
+            audio_events.push(AudioEvent::Dash { pos: two.position() });
+        }
+        mount::sync(&mut player, &mut entities, &db);
+        let farm_scene = current_scene == SceneKind::Farm && scene_stack.is_empty();
+        if let Some(session) = net.as_mut() {
+            let world = NetWorld {
+                map: &mut maps,
+                entities: &mut entities,
+                db: &db,
+                registry: &registry,
+                player: &mut player,
+                input: local_input,
+                on_farm: farm_scene,
+            };
+            for message in session.update(get_frame_time(), world) {
+                console.print(message);
+            }
+            if session.is_closed() {
+                net = None;
+            }
         }
+        // A co-op client's farm entities are the host's, so nothing here moves or spawns them.
+        let co_op_client = farm_scene && net.as_ref().is_some_and(NetSession::is_client);
+        if farm_scene {
+            let world = FarmWorld {
+                farm: &mut farm,
+                map: &mut maps,
+                entities: &mut entities,
+                ground_items: &mut ground_items,
+                workers: &mut worker_board,
+                power: &mut power,
+                automation: &mut automation,
+                db: &db,
+                registry: &registry,
+                structures: &structures,
+                items: &item_db,
+                crops: &crop_db,
+                ground_tile: grass,
+                host: !co_op_client,
+            };
+            let broken = sim::step_farm(dt, world);
+            let (farm, tile_size, particles) = (Some(&mut farm), maps.tile_size(), Some(&mut particles));
+            break_structures(broken, &structures, farm, tile_size, &mut ground_items, &item_db, particles);
+        }
+        player.status_effects_mut().update(dt);
+        maps.update_sorted_occlusion(
+            player.sprite_rect(),
+            if player_dead {
+                f32::INFINITY
+            } else {
+                player.world_hitbox().bottom()
+            },
+            dt,
+        );
         
         let particle_budget = particle_budget_scale(
             screen_width(),
             screen_height(),
             if use_render_target { render_scale } else { 1.0 },
         );
-        particles.set_budget_scale(particle_budget);
+        particles.set_budget_scale(particle_budget * settings.graphics.particle_density);
 
-        camera.zoom = camera_zoom_for_fov(CAMERA_FOV, use_render_target);
-        let follow = 1.0 - (-CAMERA_DRAG * get_frame_time()).exp();
-        camera.target += (player.position() - camera.target) * follow;
-        camera.render_target = if use_render_target {
-            Some(scene_target.clone())
+        replay.update(if photo.is_active() { 0.0 } else { get_frame_time() }, &mut entities, &mut player);
+
+        camera.handle_zoom_input();
+        let camera_target = use_render_target.then(|| scene_target.clone());
+        if photo.is_active() {
+            photo.update(&mut camera, camera_target);
+        } else if let Some(target) = cutscenes.camera() {
+            camera.update_free(target, get_frame_time(), camera_target);
         } else {
-            None
-        };
+            let (follow, group) = local_coop.camera_focus(player.position());
+            camera.set_group_extent(group);
+            camera.update(follow, get_frame_time(), camera_target);
+        }
+        sounds.set_listener(camera.target(), camera.view_rect().w * 0.5);
+        let chunks_started = profiler.start();
         maps.begin_frame_chunk_work();
-        maps.prewarm_visible_chunks(camera.target, camera.zoom);
+        maps.prewarm_visible_chunks(camera.target(), camera.zoom());
+        maps.process_chunk_rebuild_queue(&tileset, camera.target(), camera.zoom());
+        profiler.record(Section::ChunkRebuild, chunks_started);
+        profiler.set_chunk_queue(maps.chunk_rebuild_queue_depth());
 
-        let view_rect = camera_view_rect_logic(camera.target, CAMERA_FOV);
+        let view_rect = camera.view_rect();
         let mouse_screen = mouse_position();
         let mouse_world = camera.screen_to_world(vec2(mouse_screen.0, mouse_screen.1));
         let player_pos = player.position();
+        if !console.is_open() {
+            inspector.update(&entities, &db, mouse_world);
+            profiler.update();
+            if is_key_pressed(KeyCode::F5) {
+                map_debug = !map_debug;
+            }
+        }
+        let settings_was_open = settings_ui.is_open();
+        if console.is_open() {
+            settings_ui.close(&settings);
+        } else if settings_was_open {
+            if settings_ui.update(&mut settings) {
+                camera.set_shake_scale(settings.graphics.screen_shake);
+                damage_numbers.set_enabled(settings.graphics.damage_numbers);
+                for (channel, volume) in settings.channel_volumes() {
+                    sounds.set_channel_volume(channel, volume);
+                }
+            }
+        } else if is_key_pressed(KeyCode::F10) && !photo.is_active() {
+            settings_ui.open();
+        }
+        if console.is_open() || settings_ui.is_open() {
+            photo.close();
+        } else if is_key_pressed(KeyCode::F9) && !sleep_fade.is_active() {
+            photo.toggle(&camera);
+        }
+        clips.update(get_frame_time(), !console.is_open() && !settings_ui.is_open());
+        let shop_was_open = shop_ui.is_open();
+        if shop_was_open && (player_dead || console.is_open()) {
+            shop_ui.close();
+        } else if shop_was_open && let Some(message) = shop_ui.update(&shops, &item_db, &mut player) {
+            console.print(message);
+        }
+        let chest_was_open = chest_ui.is_open();
+        if player_dead || console.is_open() || current_scene != SceneKind::Farm {
+            chest_ui.close();
+        } else if chest_was_open {
+            chest_ui.update(&mut farm.chests, &item_db, player.inventory_mut());
+        }
+        let stations = nearby_stations(maps.structure_interactors(), player_pos);
+        let craft_was_open = craft_ui.is_open();
+        if player_dead || console.is_open() || shop_ui.is_open() || chest_ui.is_open() || paused {
+            craft_ui.close();
+        } else if let Some(message) = craft_ui.update(&recipes, &item_db, player.inventory_mut(), &stations) {
+            console.print(message);
+        }
+        let upgrade_was_open = upgrade_ui.is_open();
+        if player_dead
+            || console.is_open()
+            || shop_ui.is_open()
+            || chest_ui.is_open()
+            || craft_ui.is_open()
+            || paused
+            || cutscene
+        {
+            upgrade_ui.close();
+        } else if let Some(message) = upgrade_ui.update(&upgrade_db, &item_db, &mut loadout, &mut player, config.player) {
+            console.print(message);
+        }
+        let map_was_open = world_map.is_open();
+        if player_dead || console.is_open() || paused || cutscene {
+            world_map.close();
+        } else {
+            world_map.update_input();
+        }
+        let explored = (current_scene == SceneKind::Expedition && scene_stack.is_empty()).then_some(&farm.explored);
+        if let Some(dest) = world_map.update(&maps, &tileset, &structures, explored) {
+            // Mounts stay at the waypoint; companions come along.
+            mount::dismount(&mut player, &mut entities);
+            let companions = companion::carry(&entities, &db);
+            player.set_position(dest);
+            companion::land(&mut entities, &db, &registry, &companions, dest);
+            camera.snap_to(dest);
+        }
+        let results_were_open = expedition.showing_results();
+        expedition.update_results();
+        let menu_was_open = shop_was_open
+            || results_were_open
+            || map_was_open
+            || craft_was_open
+            || chest_was_open
+            || upgrade_was_open
+            || settings_was_open
+            || paused
+            || cutscene;
+        if menu_was_open && is_mouse_button_pressed(MouseButton::Left) {
+            audio_events.push(AudioEvent::UiClick);
+        }
+        let farm_controls = current_scene == SceneKind::Farm
+            && scene_stack.is_empty()
+            && !player_dead
+            && !console.is_open()
+            && !menu_was_open;
+        if farm_controls {
+            power.update_input();
+        }
+        if farm_controls && !job_mode.is_active() {
+            build_mode.update(&structures);
+        } else {
+            build_mode.close();
+        }
+        if farm_controls && !build_mode.is_active() {
+            if let Some(message) = job_mode.update(&mut worker_board, &entities, &db, &maps, mouse_world) {
+                console.print(message);
+            }
+        } else {
+            job_mode.close();
+        }
+        let build_target = build_mode.selected(&structures).and_then(|def| {
+            build_mode
+                .site(def, &maps, mouse_world, player.world_hitbox())
+                .map(|site| (def, site))
+        });
         let hovered_interactor = maps
             .structure_interactors()
             .iter()
             .find(|interactor| {
-                point_in_rect(mouse_world, interactor.rect)
+                build_target.is_none()
+                    && point_in_rect(mouse_world, interactor.rect)
                     && interactor_in_range(player_pos, interactor.group_rect, interactor.interact_range_world)
             })
             .cloned();
 
-        if is_mouse_button_pressed(MouseButton::Left) {
-            if let Some(interactor) = hovered_interactor.as_ref() {
-                let mut ctx = InteractContext {
-                    structure_id: &interactor.structure_id,
-                    area: interactor.group_rect,
-                    player: &mut player,
-                    map: &mut maps,
-                };
-                interact_registry.execute(&interactor.on_interact, &mut ctx);
+        if !console.is_open() {
+            for (slot, key) in HOTBAR_KEYS.iter().enumerate() {
+                if is_key_pressed(*key) {
+                    selected_slot = slot;
+                }
             }
         }
 
-        let mut entity_targets = Vec::with_capacity(entities.len());
-        for ent in &entities {
-            let def = &db.entities[ent.instance.def];
-            entity_targets.push(entity::EntityTarget {
-                id: ent.instance.uid,
-                def: ent.instance.def,
-                kind: def.kind,
-                pos: ent.instance.pos,
-                hitbox: ent.hitbox(&db),
-                alive: ent.instance.hp > 0.0,
+        // Clicking a breakable structure within reach chops at it; otherwise the selected
+        // tool is used on the farm tile under the cursor.
+        chop_cooldown = (chop_cooldown - dt).max(0.0);
+        let mut used_click = menu_was_open || job_mode.is_active();
+        if let Some((def, site)) = build_target
+            && is_mouse_button_pressed(MouseButton::Left)
+        {
+            used_click = true;
+            if !build::place(&mut maps, &mut farm, def, site, player.inventory_mut()) {
+                console.print(format!("can't build {} here", def.id));
+            }
+        }
+        // Using a capture item on a worn-down wild bot wins it over.
+        if is_mouse_button_pressed(MouseButton::Left)
+            && !used_click
+            && !player_dead
+            && player_pos.distance(mouse_world) <= config.tool_range
+            && let Some(stack) = player.inventory().get(selected_slot)
+            && item_db.get(&stack.item).is_some_and(|def| def.tool.as_deref() == Some("capture"))
+        {
+            let item = stack.item.clone();
+            if let Some(name) = capture_entity(&mut entities, &db, &registry, &item, mouse_world, &mut particles) {
+                used_click = true;
+                player.inventory_mut().consume(selected_slot, 1);
+                console.print(format!("captured {name}"));
+            }
+        }
+        // The held tool works on the tile under a click within reach, or with F on the tile the
+        // player faces. A ripe crop there is picked first, whatever is held.
+        let held = player.inventory().get(selected_slot).and_then(|stack| item_db.get(&stack.item));
+        let held_action = held.and_then(ToolAction::of);
+        let on_farm = current_scene == SceneKind::Farm && scene_stack.is_empty() && !build_mode.is_active();
+        let facing = tool::facing_tile(&maps, player.world_hitbox().center(), player.facing());
+        let reach = if matches!(held_action, Some(ToolAction::Chop | ToolAction::Repair)) {
+            config.chop_range
+        } else {
+            config.tool_range
+        };
+        let clicked = is_mouse_button_pressed(MouseButton::Left) && !used_click && hovered_interactor.is_none();
+        let aimed = if clicked && player_pos.distance(mouse_world) <= reach {
+            tool::tile_at(&maps, mouse_world)
+        } else if is_key_pressed(KeyCode::F) && !console.is_open() && !menu_was_open && !job_mode.is_active() {
+            facing
+        } else {
+            None
+        };
+        if let Some(tile) = aimed
+            && !player_dead
+        {
+            let picked =
+                on_farm && harvest_crop(&mut farm, &crop_db, &mut ground_items, &item_db, tile, maps.tile_size());
+            let used = match (held, held_action) {
+                _ if picked => ToolUse::Used,
+                (Some(def), Some(action)) => {
+                    action.apply(def, on_farm.then_some(&mut farm), &mut maps, &crop_db, grass, tile)
+                }
+                (Some(def), None) if def.tool.is_some() => {
+                    eprintln!("unknown tool '{}' on item '{}'", def.tool.as_deref().unwrap_or_default(), def.id);
+                    ToolUse::Nothing
+                }
+                _ => ToolUse::Nothing,
+            };
+            let done = match used {
+                ToolUse::Nothing => false,
+                ToolUse::Used => {
+                    if !picked && held.is_some_and(|def| def.consumed) {
+                        player.inventory_mut().consume(selected_slot, 1);
+                    }
+                    true
+                }
+                ToolUse::Chop(_) if chop_cooldown > 0.0 => false,
+                ToolUse::Chop(area) => {
+                    chop_cooldown = config.chop_cooldown;
+                    let broken = maps.damage_structures(
+                        area,
+                        config.chop_damage * player.stats().damage,
+                        StructureHit::Tool,
+                        &structures,
+                    );
+                    if current_scene == SceneKind::Expedition && scene_stack.is_empty() {
+                        expedition_harvested.record(&broken, &structures, farm.clock.day);
+                    }
+                    let farm = on_farm.then_some(&mut farm);
+                    let (tile_size, particles) = (maps.tile_size(), Some(&mut particles));
+                    break_structures(broken, &structures, farm, tile_size, &mut ground_items, &item_db, particles);
+                    true
+                }
+                ToolUse::Repair(area) => {
+                    let cost = maps.repairable_structure(area, &structures).map(|(_, def)| &def.repair_cost);
+                    match cost {
+                        Some(cost) if player.inventory_mut().remove_all(cost) => {
+                            maps.repair_structure(area, &structures);
+                            true
+                        }
+                        Some(cost) => {
+                            let needs: Vec<String> =
+                                cost.iter().map(|stack| format!("{} {}", stack.count, stack.item)).collect();
+                            console.print(format!("repairing needs {}", needs.join(", ")));
+                            false
+                        }
+                        None => false,
+                    }
+                }
+            };
+            used_click |= clicked && done;
+        }
+        // Shows where the held tool would land, and whether it would do anything there.
+        let tool_target = facing
+            .filter(|_| !player_dead && !build_mode.is_active() && !job_mode.is_active())
+            .and_then(|tile| {
+                let (def, action) = (held?, held_action.filter(|&action| action != ToolAction::Capture)?);
+                let ripe = on_farm && farm.crops.ripe(&crop_db).any(|ripe| ripe == tile);
+                Some((tile, ripe || action.can_use(def, on_farm.then_some(&farm), &maps, &crop_db, grass, tile)))
+            });
+
+        // E uses whatever the prompt points at; clicking a shopkeeper or a structure under the
+        // cursor works too.
+        let prompting = !player_dead && !console.is_open() && !menu_was_open && !paused;
+        if prompting {
+            interactions.scan(&InteractScan {
+                player: &player,
+                entities: &entities,
+                db: &db,
+                shops: &shops,
+                map: &maps,
+                structures: &structures,
+                recipes: &recipes,
+                farm: (current_scene == SceneKind::Farm && scene_stack.is_empty()).then_some((&farm, &crop_db)),
+                range: config.interact_range,
+                shop_range: config.shop_range,
+                mount_range: config.mount_range,
             });
+        } else {
+            interactions.clear();
+        }
+        let pressed = is_key_pressed(KeyCode::E).then(|| interactions.target().cloned()).flatten();
+        let mut interact_target = None;
+        match pressed.filter(|_| prompting) {
+            Some(Interactable::Dismount) => mount::dismount(&mut player, &mut entities),
+            Some(Interactable::Mount { uid }) => {
+                mount::ride(&mut player, &mut entities, &db, uid);
+            }
+            Some(Interactable::Shopkeeper { shop }) => shop_ui.open(shop),
+            Some(Interactable::Load { uid }) => {
+                turret::load(&mut entities, &db, uid, player.inventory_mut());
+            }
+            Some(Interactable::Crop { tile }) => {
+                harvest_crop(&mut farm, &crop_db, &mut ground_items, &item_db, tile, maps.tile_size());
+            }
+            Some(Interactable::Structure(interactor)) => interact_target = Some(interactor),
+            None => {}
+        }
+        if is_mouse_button_pressed(MouseButton::Left)
+            && !used_click
+            && !player_dead
+            && let Some(shop) = shopkeeper_at(&entities, &db, &shops, player_pos, config.shop_range, mouse_world)
+        {
+            shop_ui.open(shop);
+            used_click = true;
+        }
+        if is_mouse_button_pressed(MouseButton::Left) && !used_click {
+            interact_target = hovered_interactor.clone();
+        }
+        if let Some(interactor) = interact_target {
+            let mut ctx = InteractContext {
+                structure_id: &interactor.structure_id,
+                area: interactor.group_rect,
+                player: &mut player,
+                map: &mut maps,
+            };
+            let hp_before = ctx.player.hp();
+            interact_registry.execute(&interactor.on_interact, &mut ctx);
+            let hp_change = player.hp() - hp_before;
+            let hitbox = player.world_hitbox();
+            let above = vec2(hitbox.center().x, hitbox.y);
+            if hp_change > 0.0 {
+                damage_numbers.spawn(above, hp_change, PopupKind::Heal);
+            } else {
+                damage_numbers.spawn(above, -hp_change, PopupKind::PlayerDamage);
+            }
+            if current_scene == SceneKind::Farm
+                && structures
+                    .iter()
+                    .any(|def| def.id == interactor.structure_id && def.storage_slots > 0)
+            {
+                let ts = maps.tile_size();
+                chest_ui.open(
+                    (interactor.group_rect.x / ts) as usize,
+                    (interactor.group_rect.y / ts) as usize,
+                );
+            }
+            if structures.iter().any(|def| def.id == interactor.structure_id && def.bed) {
+                sleep_fade.start();
+            }
+            if interactor.structure_id == scene::LAND_OFFICE_ID {
+                land_purchase = true;
+            }
+            if structures.iter().any(|def| def.id == interactor.structure_id && def.waypoint) {
+                world_map.open_travel(interactor.group_rect.center());
+            }
+            if recipes.is_station(&interactor.structure_id) {
+                craft_ui.open();
+            }
+            if interactor.structure_id == breed::BREEDING_STATION {
+                let station = interactor.group_rect;
+                let inventory = player.inventory_mut();
+                match breed::breed(&mut entities, &db, &registry, station, inventory, &mut particles) {
+                    Ok(name) => console.print(format!("bred {name}")),
+                    Err(err) => console.print(err.to_string()),
+                }
+            }
+            if let Some(interior) = interactor.interior.as_ref() {
+                pending_scene = Some(SceneRequest::EnterInterior(interior.clone()));
+            } else if let Some(target) = interactor.scene_transition.as_deref() {
+                match SceneRequest::from_id(target) {
+                    Some(request) => pending_scene = Some(request),
+                    None => eprintln!(
+                        "unknown scene '{}' on structure '{}'",
+                        target, interactor.structure_id
+                    ),
+                }
+            }
+        }
+
+        // Right click orders every robot on screen to the cursor.
+        if is_mouse_button_pressed(MouseButton::Right) && !job_mode.is_active() {
+            for ent in &entities {
+                if db.entities[ent.instance.def].kind == EntityKind::Friend
+                    && ent.hitbox(&db).overlaps(&view_rect)
+                {
+                    command_bus.send(
+                        ent.instance.uid,
+                        CommandSource::Local,
+                        RobotCommand::MoveTo {
+                            x: mouse_world.x,
+                            y: mouse_world.y,
+                        },
+                    );
+                }
+            }
+        }
+        let farm_work = (farm_scene && !co_op_client).then_some(FarmWork {
+            farm: &mut farm,
+            ground_items: &mut ground_items,
+            items: &item_db,
+            crops: &crop_db,
+            ground_tile: grass,
+        });
+        command_bus.dispatch(&mut entities, &db, &mut maps, farm_work);
+        for result in command_bus.take_results() {
+            if result.source == CommandSource::Script {
+                console.print(match &result.status {
+                    CommandStatus::Accepted => format!("order {} accepted", result.id),
+                    CommandStatus::Completed => format!("order {} done", result.id),
+                    CommandStatus::Failed { reason } => format!("order {} failed: {reason}", result.id),
+                });
+            } else if let CommandStatus::Failed { reason } = &result.status {
+                eprintln!(
+                    "robot command {} for {} ({:?}) failed: {}",
+                    result.id, result.robot, result.source, reason
+                );
+            }
         }
 
         damage_events.clear();
-        let mut ctx = EntityContext {
-            player: if player_dead || player.hp() <= 0.0 {
-                None
-            } else {
-                Some(PlayerTarget {
+        let ridden = mount::ridden_target(&entities, &db);
+        let entities_started = profiler.start();
+        if !paused && !co_op_client {
+            let mut player_targets = Vec::with_capacity(2);
+            if !player_dead && player.hp() > 0.0 {
+                player_targets.push(PlayerTarget {
+                    index: 0,
                     pos: player.position(),
-                    hitbox: player.world_hitbox(),
-                })
-            },
-            target: None,
-            entities: entity_targets,
-            target_cache: std::mem::take(&mut entity_target_cache),
-            view_height: CAMERA_FOV,
-            damage_events: Vec::new(),
-        };
-
-        let mut ent_idx = 0usize;
-        while ent_idx < entities.len() {
-            entities[ent_idx].update(dt, &db, &mut ctx, &maps, &registry);
-            entities[ent_idx].clamp_to_map(&maps, &db);
-            ent_idx += 1;
+                    hitbox: ridden.map_or(player.world_hitbox(), |mount| mount.hitbox),
+                    iframes: ridden.map_or(config.player_iframes, |mount| mount.iframes),
+                });
+            }
+            player_targets.extend(local_coop.target(config.player_iframes));
+            if farm_scene && let Some(session) = net.as_ref() {
+                player_targets.extend(session.player_targets(config.player_iframes));
+            }
+            let world = SimWorld {
+                db: &db,
+                registry: &registry,
+                map: &maps,
+                view_height: config.camera_fov,
+            };
+            let events = entity_sim.step(&mut entities, world, &player_targets, dt);
+            damage_events.extend(events.damage);
+            for pos in events.dashes {
+                audio_events.push(AudioEvent::Dash { pos });
+            }
+            for (id, pos) in events.particles {
+                particles.burst(&id, pos);
+            }
+            for (id, pos) in events.sounds {
+                sounds.play_at(&id, pos);
+            }
+            for hit in events.tiles {
+                let broken = maps.damage_structures(hit.area, hit.amount, hit.hit, &structures);
+                if current_scene == SceneKind::Expedition && scene_stack.is_empty() {
+                    expedition_harvested.record(&broken, &structures, farm.clock.day);
+                }
+                let farm = (current_scene == SceneKind::Farm && scene_stack.is_empty()).then_some(&mut farm);
+                let (tile_size, particles) = (maps.tile_size(), Some(&mut particles));
+                break_structures(broken, &structures, farm, tile_size, &mut ground_items, &item_db, particles);
+            }
         }
-        resolve_entity_overlaps(&mut entities, &db, &maps);
-        damage_events.extend(ctx.damage_events.drain(..));
-        entity_target_cache = std::mem::take(&mut ctx.target_cache);
+        profiler.record(Section::EntityUpdate, entities_started);
+        raids.update(
+            dt,
+            &mut RaidContext {
+                clock: farm.clock,
+                on_farm: farm_scene && !co_op_client,
+                player_dead,
+                crops: &farm.crops,
+                map: &maps,
+                entities: &mut entities,
+                db: &db,
+                registry: &registry,
+                player: &mut player,
+                growth: &config.player_growth,
+                item_db: &item_db,
+                ground_items: &mut ground_items,
+                dialogue: &mut dialogue,
+                damage: &mut damage_events,
+            },
+        );
 
         for ent in entities.iter_mut() {
             let def = &db.entities[ent.instance.def];
-            let render_origin = ent.instance.pos + def.texture.draw.offset;
-            let size = def
-                .texture
-                .draw
-                .dest_size
-                .unwrap_or_else(|| def.texture.texture.size());
+            let (sprite, offset, size) = def.sprite_frame(ent.instance.animation_frame(&db));
+            let render_origin = ent.instance.pos + offset + def.texture.draw.offset;
+            let size = size.unwrap_or_else(|| sprite.size());
             let pos = render_origin + size * 0.5;
-            if ent.instance.is_dashing() {
+            if ent.instance.is_dashing() && !ent.instance.is_dying() {
                 if ent.instance.dash_trail.is_none() {
                     ent.instance.dash_trail = particles.emitter("dash_afterimage", pos);
                 }
@@ -500,7 +1725,7 @@ async fn main() {
                         emitter,
                         pos,
                         dt,
-                        Some(&def.texture.texture),
+                        Some(&sprite),
                         Some(size),
                     );
                 }
@@ -515,32 +1740,135 @@ async fn main() {
         }
 
         for event in &damage_events {
-            match event.target {
+            let target = match (event.target, ridden) {
+                (Target::Player(hit), Some(mount)) if hit.index == 0 => Target::Entity(mount),
+                (target, _) => target,
+            };
+            match target {
+                Target::Player(hit) if hit.index == 1 => {
+                    if let Some(two) = local_coop.player_mut() {
+                        if event.amount > 0.0 {
+                            audio_events.push(AudioEvent::DamageTaken {
+                                pos: two.position(),
+                                player: true,
+                            });
+                        }
+                        let kind = if event.crit { PopupKind::Crit } else { PopupKind::PlayerDamage };
+                        let hitbox = two.world_hitbox();
+                        damage_numbers.spawn(vec2(hitbox.center().x, hitbox.y), event.amount, kind);
+                        two.apply_damage(event.amount);
+                    }
+                }
+                Target::Player(hit) if hit.index > 1 => {
+                    let respawn = scene::farm_spawn_point(&maps);
+                    if let Some(message) =
+                        net.as_mut().and_then(|session| session.damage_player(hit.index, event.amount, respawn))
+                    {
+                        console.print(message);
+                    }
+                }
                 Target::Player(_) => {
                     if event.amount > 0.0 {
-                        sounds.play("hurt2");
+                        audio_events.push(AudioEvent::DamageTaken {
+                            pos: player.position(),
+                            player: true,
+                        });
+                        camera.shake(0.5);
+                        status_db.apply(player.status_effects_mut(), "hurt");
                     }
+                    let kind = if event.crit { PopupKind::Crit } else { PopupKind::PlayerDamage };
+                    let hitbox = player.world_hitbox();
+                    damage_numbers.spawn(vec2(hitbox.center().x, hitbox.y), event.amount, kind);
                     player.apply_damage(event.amount);
                 }
                 Target::Entity(target) => {
-                    if let Some(&ent_idx) = entity_index_by_uid.get(&target.id) {
+                    if let Some(&ent_idx) = entity_index_by_uid.get(&target.id)
+                        && !entities[ent_idx].instance.is_dying()
+                    {
                         let ent = &mut entities[ent_idx];
+                        let hitbox = ent.hitbox(&db);
                         if event.amount > 0.0 {
-                            sounds.play("hurt");
+                            audio_events.push(AudioEvent::DamageTaken {
+                                pos: hitbox.center(),
+                                player: false,
+                            });
                         }
+                        let kind = if event.crit { PopupKind::Crit } else { PopupKind::Damage };
+                        damage_numbers.spawn(vec2(hitbox.center().x, hitbox.y), event.amount, kind);
                         ent.instance.apply_damage(event.amount);
+                        if event.source.is_some() {
+                            ent.instance.last_attacker = event.source;
+                        }
+                    }
+                }
+                // A spot on the map: the crop planted there, or else the structure standing on it.
+                Target::Position(pos) => {
+                    let in_farm = current_scene == SceneKind::Farm && scene_stack.is_empty();
+                    let (x, y) = ((pos.x / maps.tile_size()) as usize, (pos.y / maps.tile_size()) as usize);
+                    if in_farm && farm.crops.is_planted(x, y) {
+                        if farm.crops.damage(x, y, event.amount, &crop_db) {
+                            particles.burst("structure_debris", pos);
+                        }
+                        continue;
                     }
+                    let area = Rect::new(pos.x - 0.5, pos.y - 0.5, 1.0, 1.0);
+                    let broken = maps.damage_structures(area, event.amount, StructureHit::Enemy, &structures);
+                    let farm = in_farm.then_some(&mut farm);
+                    let (tile_size, particles) = (maps.tile_size(), Some(&mut particles));
+                    break_structures(broken, &structures, farm, tile_size, &mut ground_items, &item_db, particles);
                 }
-                Target::Position(_) => {}
             }
         }
-        entities.retain(|ent| ent.instance.hp > 0.0);
+        let mut kills = Vec::new();
+        sim::tick_deaths(&mut entities, &db, dt, |ent| {
+            let def = &db.entities[ent.instance.def];
+            spawn_drops(&def.drops, ent.hitbox(&db).center(), &mut ground_items, &item_db);
+            maps.stamp_decal(&decals, "oil", ent.instance.pos, None);
+            if def.xp > 0.0
+                && let Some(killer) = ent.instance.last_attacker
+            {
+                kills.push((killer, def.xp));
+            }
+        });
+        if current_scene == SceneKind::Expedition {
+            expedition.count_kills(kills.len());
+        }
+        for (killer, xp) in kills {
+            award_kill_xp(&mut player, &mut entities, &db, &config, &mut console, killer, xp);
+        }
+        damage_numbers.update(dt);
+        ground_items.update(dt);
+        if !player_dead {
+            let feet = player.position();
+            ground_items.collect_near(&item_db, player.inventory_mut(), feet, config.item_pickup_radius);
+            // Player two shares player one's pockets.
+            if let Some(two) = local_coop.player() {
+                let feet = two.position();
+                ground_items.collect_near(&item_db, player.inventory_mut(), feet, config.item_pickup_radius);
+            }
+        }
+        if local_coop.knock_out() {
+            console.print("player two is out; coop join brings them back");
+        }
         if !player_dead && player.hp() <= 0.0 {
             player_dead = true;
         }
+        if current_scene == SceneKind::Expedition
+            && scene_stack.is_empty()
+            && farm.explored.reveal(player.position(), config.explore_radius, maps.tile_size())
+        {
+            world_map.refresh();
+        }
+        if current_scene == SceneKind::Expedition
+            && expedition_outcome.is_none()
+            && let Some(outcome) = expedition.update(dt, player_dead)
+        {
+            expedition_outcome = Some(outcome);
+            pending_scene = Some(SceneRequest::Switch(SceneKind::Farm));
+        }
 
         let dashing = !player_dead && player.is_dashing();
-        let moving = !player_dead && player.is_moving(MOVE_DEADZONE) && !dashing;
+        let moving = !player_dead && player.is_moving(config.move_deadzone) && !dashing;
         if let Some(emitter) = walk_trail.as_mut() {
             if moving {
                 particles.update_emitter(emitter, player.position(), dt);
@@ -555,7 +1883,7 @@ async fn main() {
                     emitter,
                     player.position() - Vec2::new(0.0, player.texture.size().y / 8.0),
                     dt,
-                    Some(&player.texture),
+                    Some(&Sprite::whole(player.texture.clone())),
                     Some(player.texture.size() * 0.25),
                 );
             } else {
@@ -566,73 +1894,261 @@ async fn main() {
             }
         }
 
+        let particles_started = profiler.start();
+        if let Some(BenchLoad::Particles { count }) = benchmark.as_ref().map(Benchmark::load) {
+            particles.top_up_in_rect("bench", view_rect, *count);
+        }
         particles.update(dt);
-
+        profiler.record(Section::ParticleUpdate, particles_started);
+        backdrops.update(dt);
+        tileset.update(dt);
+        maps.update_decals(dt);
+
+        let on_farm = current_scene == SceneKind::Farm && scene_stack.is_empty();
+        let tilled_at = |pos: Vec2| {
+            on_farm
+                && pos.x >= 0.0
+                && pos.y >= 0.0
+                && farm
+                    .soil
+                    .get((pos.x / maps.tile_size()) as usize, (pos.y / maps.tile_size()) as usize)
+                    .tilled()
+        };
+        let mut footprint = None;
         if moving {
             footstep_timer -= dt;
             if footstep_timer <= 0.0 {
-                sounds.play("footstep");
-                footstep_timer = FOOTSTEP_INTERVAL;
+                audio_events.push(AudioEvent::Footstep {
+                    pos: player.position(),
+                    set: footsteps.set_at(&maps, player.position(), tilled_at(player.position())),
+                });
+                let velocity = player.velocity();
+                footprint = Some((player.position(), velocity.y.atan2(velocity.x)));
+                footstep_timer = config.footstep_interval;
             }
         } else {
             footstep_timer = 0.0;
         }
+        for ent in entities.iter_mut() {
+            let Some(interval) = db.entities[ent.instance.def].footstep_interval else {
+                continue;
+            };
+            let instance = &mut ent.instance;
+            if instance.is_dying() || instance.vel.length() < ENTITY_STEP_MIN_SPEED {
+                instance.footstep_timer = 0.0;
+                continue;
+            }
+            instance.footstep_timer -= dt;
+            if instance.footstep_timer > 0.0 {
+                continue;
+            }
+            instance.footstep_timer = interval;
+            if instance.pos.distance(camera.target()) <= ENTITY_STEP_RANGE {
+                audio_events.push(AudioEvent::Footstep {
+                    pos: instance.pos,
+                    set: footsteps.set_at(&maps, instance.pos, tilled_at(instance.pos)),
+                });
+            }
+        }
+        if let Some((pos, rotation)) = footprint {
+            maps.stamp_decal(&decals, "footprint", pos, Some(rotation));
+        }
+        if !player_dead {
+            let mut ctx = TriggerContext {
+                player: &mut player,
+                map: &mut maps,
+                entities: &mut entities,
+                db: &db,
+                registry: &registry,
+                sounds: &sounds,
+                interact: &interact_registry,
+                dialogue: &mut dialogue,
+                cutscenes: &mut cutscenes,
+            };
+            let feet = ctx.player.world_hitbox().center();
+            triggers.update(scene_stack.is_empty().then_some(current_scene), feet, dt, &mut ctx);
+        }
+        cutscenes.update(dt, &mut entities, &db, maps.tile_size(), camera.target(), &mut dialogue);
+        if let Some((target, seconds)) = cutscenes.take_linger() {
+            camera.focus_on(target, seconds);
+        }
+        dialogue.update(dt);
+        ambient.update(
+            scene_stack.is_empty().then_some(current_scene),
+            player.position(),
+            maps.tile_size(),
+            get_frame_time(),
+            &sounds,
+        );
+        // Everything that makes a sound has had its turn by now.
+        audio_events.flush(&sounds, &mut footsteps);
 
-        set_camera(&camera);
+        let draw_started = profiler.start();
+        set_camera(camera.camera());
         clear_background(BLACK);
 
+        let outdoor_tint = farm.clock.season().tint();
+        maps.set_mesh_chunks(settings.graphics.chunk_meshes);
+        maps.set_tint(if scene_stack.is_empty() { outdoor_tint } else { WHITE });
+        if scene_stack.is_empty() {
+            backdrops.draw(current_scene, view_rect, outdoor_tint);
+        }
+
         maps.draw_background(
             &tileset,
-            camera.target,
-            camera.zoom,
+            camera.target(),
+            camera.zoom(),
             screen_width(),
             screen_height(),
         );
+        if current_scene == SceneKind::Farm && scene_stack.is_empty() {
+            farm.soil.draw_watered(view_rect, maps.tile_size());
+            farm.crops.draw(&crop_db, &tileset, view_rect, maps.tile_size(), outdoor_tint);
+        }
+        maps.draw_decals(view_rect);
         maps.draw_foreground(
             &tileset,
-            camera.target,
-            camera.zoom,
+            camera.target(),
+            camera.zoom(),
             screen_width(),
             screen_height(),
         );
 
-        let cull_rect = expand_rect(view_rect, ENTITY_CULL_FADE_PAD);
+        if current_scene == SceneKind::Farm && scene_stack.is_empty() {
+            conveyor::draw(&farm, &structures, view_rect, maps.tile_size());
+        }
+        let cull_rect = expand_rect(view_rect, config.entity_cull_pad);
+
+        // Shadows all go down before anything stands on them.
+        if !player_dead {
+            player.draw_shadow();
+        }
+        let remote_players: Vec<&Player> = net
+            .as_ref()
+            .filter(|_| current_scene == SceneKind::Farm && scene_stack.is_empty())
+            .map(|session| session.remote_players().collect())
+            .unwrap_or_default();
+        for remote in &remote_players {
+            remote.draw_shadow();
+        }
+        if let Some(two) = local_coop.player() {
+            two.draw_shadow();
+        }
+        for ent in &entities {
+            let alpha = offscreen_fade_alpha(ent.hitbox(&db), view_rect, config.entity_cull_pad);
+            if alpha > 0.0 {
+                ent.instance.draw_shadow(&db, alpha);
+                attack::draw_telegraphs(&ent.instance, &db.entities[ent.instance.def]);
+            }
+        }
 
+        ground_items.draw(&item_db, cull_rect);
         particles.draw_in_rect(cull_rect);
 
+        // Depth pass: player, entities and tall structures sorted by feet Y.
+        draw_order.clear();
         if !player_dead {
-            player.draw();
-        }
-        if !entities.is_empty() {
-            draw_order.clear();
-            for (idx, ent) in entities.iter().enumerate() {
-                let hb = ent.hitbox(&db);
-                if offscreen_fade_alpha(hb, view_rect, ENTITY_CULL_FADE_PAD) > 0.0 {
-                    draw_order.push(idx);
+            draw_order.push((player.world_hitbox().bottom(), DepthItem::Player));
+        }
+        if let Some(two) = local_coop.player() {
+            draw_order.push((two.world_hitbox().bottom(), DepthItem::PlayerTwo));
+        }
+        for (idx, remote) in remote_players.iter().enumerate() {
+            draw_order.push((remote.world_hitbox().bottom(), DepthItem::RemotePlayer(idx)));
+        }
+        for (idx, ent) in entities.iter().enumerate() {
+            let hb = ent.hitbox(&db);
+            if offscreen_fade_alpha(hb, view_rect, config.entity_cull_pad) > 0.0 {
+                draw_order.push((hb.bottom(), DepthItem::Entity(idx)));
+            }
+        }
+        maps.visible_sorted_structures(cull_rect, &mut visible_structures);
+        for &idx in &visible_structures {
+            if let Some(sorted) = maps.sorted_structure(idx) {
+                draw_order.push((sorted.base_y, DepthItem::Structure(idx)));
+            }
+        }
+        draw_order.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for &(_, item) in &draw_order {
+            match item {
+                DepthItem::Player => player.draw(),
+                DepthItem::PlayerTwo => {
+                    if let Some(two) = local_coop.player() {
+                        two.draw();
+                    }
+                }
+                DepthItem::RemotePlayer(idx) => remote_players[idx].draw(),
+                DepthItem::Entity(idx) => {
+                    let alpha = offscreen_fade_alpha(
+                        entities[idx].hitbox(&db),
+                        view_rect,
+                        config.entity_cull_pad,
+                    );
+                    entities[idx].draw_with_alpha(&db, alpha);
+                }
+                DepthItem::Structure(idx) => {
+                    maps.draw_sorted_structure(idx, &tileset, maps.sorted_structure_alpha(idx));
                 }
             }
-            if draw_order.len() > 1 {
-                draw_order.sort_unstable_by_key(|&idx| entities[idx].instance.def);
+        }
+        for projectile in entity_sim.projectiles() {
+            if cull_rect.contains(projectile.pos) {
+                projectile.draw(&db);
             }
-            for &idx in &draw_order {
-                let alpha = offscreen_fade_alpha(
-                    entities[idx].hitbox(&db),
-                    view_rect,
-                    ENTITY_CULL_FADE_PAD,
-                );
-                entities[idx].draw_with_alpha(&db, alpha);
+        }
+        for ent in &entities {
+            if cull_rect.contains(ent.instance.pos) {
+                level::draw_entity_bar(ent, &db);
             }
         }
 
         maps.draw_overlay(
             &tileset,
-            camera.target,
-            camera.zoom,
+            camera.target(),
+            camera.zoom(),
             screen_width(),
             screen_height(),
         );
+        if current_scene == SceneKind::Expedition && scene_stack.is_empty() {
+            farm.explored.draw_fog(view_rect, maps.tile_size());
+        }
+
+        if map_debug {
+            maps.draw_debug(camera.target(), camera.zoom(), view_rect);
+        }
+        damage_numbers.draw();
+        inspector.draw_world(
+            &entities,
+            &db,
+            (!player_dead).then(|| player.world_hitbox()),
+            view_rect,
+            cull_rect,
+        );
 
-        if let Some(interactor) = hovered_interactor.as_ref() {
+        maps.draw_structure_health(view_rect);
+        turret::draw_ammo(&entities, &db, view_rect);
+        if current_scene == SceneKind::Farm && scene_stack.is_empty() {
+            power.draw_overlay(view_rect, build_mode.is_active(), maps.tile_size());
+        }
+        if let Some((tile, usable)) = tool_target {
+            tool::draw_target(&maps, tile, usable);
+        }
+        interactions.draw();
+        if build_mode.is_active() {
+            let ghost = build_target.map(|(def, site)| (def, site.x, site.y));
+            automation::draw_area(&farm, &structures, ghost, mouse_world, maps.tile_size());
+        }
+        if let Some((def, site)) = build_target {
+            build_mode.draw_ghost(def, site, &tileset, maps.tile_size());
+        }
+        if job_mode.is_active() {
+            worker_board.draw_regions(maps.tile_size());
+            job_mode.draw_drag(&maps, mouse_world);
+        }
+
+        if let Some(interactor) = hovered_interactor.as_ref()
+            && !paused
+        {
             draw_rectangle(
                 interactor.group_rect.x,
                 interactor.group_rect.y,
@@ -650,28 +2166,86 @@ async fn main() {
             );
         }
 
+        thumbnail_timer -= dt;
+        if current_scene == SceneKind::Farm && scene_stack.is_empty() && thumbnail_timer <= 0.0 {
+            farm_thumbnail = Some(slot::capture_thumbnail(use_render_target.then_some(&scene_target)));
+            thumbnail_timer = THUMBNAIL_INTERVAL;
+        }
+
         set_default_camera();
         if use_render_target {
-            draw_texture_ex(
-                &scene_target.texture,
-                0.0,
-                0.0,
-                WHITE,
-                DrawTextureParams {
-                    dest_size: Some(vec2(screen_width(), screen_height())),
-                    flip_y: true,
-                    ..Default::default()
-                },
-            );
+            let draw_scene = || {
+                draw_texture_ex(
+                    &scene_target.texture,
+                    0.0,
+                    0.0,
+                    WHITE,
+                    DrawTextureParams {
+                        dest_size: Some(vec2(screen_width(), screen_height())),
+                        flip_y: true,
+                        ..Default::default()
+                    },
+                )
+            };
+            let filter = photo.filter();
+            if !photo.is_active()
+                || filter == PhotoFilter::None
+                || !shader::with_photo_filter(filter.shader_mode(), draw_scene)
+            {
+                draw_scene();
+            }
+        }
+        profiler.record(Section::WorldDraw, draw_started);
+        if photo.take_shutter() {
+            photo.save_screenshot(get_screen_data());
+        }
+        if photo.is_active() {
+            clips.capture(config.clips, get_frame_time());
+            photo.draw();
+            clips.draw();
+            profiler.end_frame(get_frame_time());
+            next_frame().await;
+            continue;
         }
 
-        draw_player_health(
+        let hud_started = profiler.start();
+        let hearts_rect = draw_player_health(
             player.hp(),
             player.max_hp(),
-            CAMERA_FOV,
+            config.camera_fov,
             &heart_full,
             &heart_empty,
+            0.0,
+        );
+        if let Some(two) = local_coop.player() {
+            let top = hearts_rect.bottom();
+            draw_player_health(two.hp(), two.max_hp(), config.camera_fov, &heart_full, &heart_empty, top);
+        }
+        status::draw_status_icons(
+            &status_db,
+            player.status_effects(),
+            hearts_rect.x - 8.0,
+            hearts_rect.y,
+            STATUS_ICON_SIZE,
         );
+        let locale = locales.get(&language);
+        item::draw_hotbar(&item_db, player.inventory(), selected_slot, &hotbar_slot, locale);
+        build_mode.draw_menu(&structures, player.inventory(), locale);
+        job_mode.draw_menu(&worker_board);
+        shop_ui.draw(&shops, &item_db, &player, locale);
+        craft_ui.draw(&recipes, &item_db, player.inventory(), &stations, locale);
+        upgrade_ui.draw(&upgrade_db, &item_db, &loadout, &player, &config.player_growth, locale);
+        chest_ui.draw(&farm.chests, &item_db, player.inventory(), locale);
+        settings_ui.draw(&settings, locale);
+        draw_text_styled(&locale.format_currency(player.coins() as i64), 20.0, 136.0, 20.0, GOLD, TextStyle::SHADOWED);
+        level::draw_xp_bar(&config.player_growth, player.xp(), vec2(20.0, 144.0));
+        triggers.draw(&entities, &db);
+        raids.draw();
+        expedition.draw(player.position(), maps.tile_size());
+        let explored = (current_scene == SceneKind::Expedition && scene_stack.is_empty()).then_some(&farm.explored);
+        world_map.draw(&maps, &structures, explored, player.position());
+        dialogue.draw();
+        cutscenes.draw();
 
         i += get_frame_time();
         if i >= 1.0 {
@@ -679,33 +2253,102 @@ async fn main() {
             i = 0.0;
         } 
         draw_text(
-            &format!("FPS: {:.0}", fps),
+            &format!("FPS: {}", locale.format_int(fps as i64)),
             20.0,
             40.0,
             30.0, // font size
             WHITE
         );
+        if map_debug {
+            let chunk_targets = maps.chunk_target_stats();
+            draw_text(
+                &format!(
+                    "chunk queue: {}  resident: {}  targets bg/fg/ov: {}/{}/{}  pooled: {}  meshes: {}",
+                    locale.format_int(maps.chunk_rebuild_queue_depth() as i64),
+                    locale.format_int(maps.resident_chunk_count() as i64),
+                    locale.format_int(chunk_targets.background as i64),
+                    locale.format_int(chunk_targets.foreground as i64),
+                    locale.format_int(chunk_targets.overlay as i64),
+                    locale.format_int(map::pooled_chunk_targets() as i64),
+                    locale.format_int(chunk_targets.meshes as i64)
+                ),
+                20.0,
+                64.0,
+                20.0,
+                WHITE,
+            );
+        }
+        draw_text_styled(&locale.format_date(farm.clock), 20.0, 88.0, 20.0, WHITE, TextStyle::SHADOWED);
+        if current_scene == SceneKind::Farm
+            && scene_stack.is_empty()
+            && mouse_world.x >= 0.0
+            && mouse_world.y >= 0.0
+        {
+            let soil = farm.soil.get(
+                (mouse_world.x / maps.tile_size()) as usize,
+                (mouse_world.y / maps.tile_size()) as usize,
+            );
+            if soil.tilled() {
+                draw_text_styled(
+                    &format!(
+                        "soil: fertility ^y{}^0  growth ^gx{}",
+                        soil.fertility(),
+                        locale.format_decimal(soil.growth_rate() as f64, 2)
+                    ),
+                    20.0,
+                    112.0,
+                    20.0,
+                    WHITE,
+                    TextStyle::SHADOWED,
+                );
+            }
+        }
+        if map_debug && mouse_world.x >= 0.0 && mouse_world.y >= 0.0 {
+            let tile = (mouse_world / maps.tile_size()).as_uvec2();
+            if let Some(placed) = maps.placed_structure_at(tile.x as usize, tile.y as usize) {
+                let id = if placed.structure_id.is_empty() { "?" } else { &placed.structure_id };
+                let text = format!("structure: {id} at {},{}", placed.x, placed.y);
+                draw_text_styled(&text, 20.0, 136.0, 20.0, WHITE, TextStyle::SHADOWED);
+            }
+        }
+        texture::draw_missing_texture_warning();
+        inspector.draw(&entities, &db);
+        sleep_fade.draw();
+        scene_transition.update(get_frame_time());
+        scene_transition.draw();
+        photo.draw();
+        profiler.draw();
+        console.draw();
+        clips.capture(config.clips, get_frame_time());
+        clips.draw();
+        profiler.record(Section::Hud, hud_started);
+
+        if let Some(report) = benchmark.as_mut().and_then(|bench| bench.record(get_frame_time()))
+            && let Some(finished) = benchmark.take()
+        {
+            if let BenchLoad::Entities { uids } = finished.load() {
+                entities.retain(|ent| !uids.contains(&ent.instance.uid));
+            }
+            console.print(report.to_string());
+        }
 
+        // A scene switch asked for this frame wipes over what was just drawn.
+        if pending_scene.is_some() {
+            scene_transition.hold_frame();
+        }
+        profiler.end_frame(get_frame_time());
         next_frame().await;
     }
 }
 
-fn camera_zoom_for_fov(view_height: f32, render_target: bool) -> Vec2 {
-    let view_h = view_height.max(1.0);
-    let aspect = screen_width().max(1.0) / screen_height().max(1.0);
-    let view_w = view_h * aspect;
-    let y_sign = if render_target { -1.0 } else { 1.0 };
-    vec2(2.0 / view_w, y_sign * 2.0 / view_h)
-}
-
-fn camera_view_rect_logic(target: Vec2, view_height: f32) -> Rect {
-    let view_h = view_height.max(1.0);
-    Rect::new(
-        target.x - view_h * 0.5,
-        target.y - view_h * 0.5,
-        view_h,
-        view_h,
-    )
+#[derive(Clone, Copy)]
+enum DepthItem {
+    Player,
+    PlayerTwo,
+    /// Another co-op player, by index into the frame's list of them.
+    RemotePlayer(usize),
+    Entity(usize),
+    Structure(usize),
 }
 
 fn expand_rect(rect: Rect, pad: f32) -> Rect {
@@ -764,228 +2407,150 @@ fn point_in_rect(point: Vec2, rect: Rect) -> bool {
         && point.y <= rect.y + rect.h
 }
 
-fn interactor_in_range(player_pos: Vec2, area: Rect, range_world: f32) -> bool {
-    if range_world <= 0.0 {
-        return true;
-    }
-    let nearest = vec2(
-        player_pos.x.clamp(area.x, area.x + area.w),
-        player_pos.y.clamp(area.y, area.y + area.h),
-    );
-    player_pos.distance(nearest) <= range_world
+fn restore_scene_frame(
+    frame: SceneFrame,
+    maps: &mut TileMap,
+    entities: &mut Vec<Entity>,
+    ground_items: &mut GroundItems,
+    player: &mut Player,
+    camera: &mut GameCamera,
+) {
+    *maps = frame.map;
+    *entities = frame.entities;
+    ground_items.replace(frame.ground_items);
+    player.set_position(frame.player_pos);
+    camera.set_bounds(Some(maps.world_bounds()));
+    camera.snap_to(frame.camera_target);
 }
 
-fn resolve_entity_overlaps(entities: &mut [Entity], db: &EntityDatabase, map: &TileMap) {
-    if entities.len() < 2 {
-        return;
-    }
-
-    const EPSILON: f32 = 0.0005;
-    const CELL_SIZE: f32 = 32.0;
-    const SOLVER_ITERS: usize = 4;
-
-    #[inline]
-    fn pair_sign(i: usize, j: usize, salt: u64) -> f32 {
-        let mut h = (i as u64).wrapping_mul(0x9E37_79B1_85EB_CA87);
-        h ^= (j as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
-        h ^= salt;
-        if (h & 1) == 0 {
-            -1.0
-        } else {
-            1.0
-        }
-    }
-
-    let mut overlap_marks = vec![0u32; entities.len()];
-    let mut overlap_stamp = 1u32;
-    let mut corrections = vec![Vec2::ZERO; entities.len()];
-    let mut collide_cache: HashMap<(usize, usize), bool> = HashMap::new();
-
-    for _ in 0..SOLVER_ITERS {
-        let mut any = false;
-        let mut hitboxes = Vec::with_capacity(entities.len());
-        let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::with_capacity(entities.len() * 2);
-
-        for (idx, ent) in entities.iter().enumerate() {
-            let hb = db.entities[ent.instance.def].world_hitbox(ent.instance.pos);
-            hitboxes.push(hb);
-            let (min_cx, max_cx, min_cy, max_cy) = rect_cell_range(hb, CELL_SIZE);
-            for cy in min_cy..=max_cy {
-                for cx in min_cx..=max_cx {
-                    grid.entry((cx, cy)).or_default().push(idx);
-                }
-            }
-        }
-
-        corrections.fill(Vec2::ZERO);
-
-        for i in 0..entities.len() {
-            overlap_stamp = overlap_stamp.wrapping_add(1);
-            if overlap_stamp == 0 {
-                overlap_marks.fill(0);
-                overlap_stamp = 1;
-            }
-
-            let a_hb = hitboxes[i];
-            let a_center = vec2(a_hb.x + a_hb.w * 0.5, a_hb.y + a_hb.h * 0.5);
-            let (min_cx, max_cx, min_cy, max_cy) = rect_cell_range(a_hb, CELL_SIZE);
-            for cy in min_cy..=max_cy {
-                for cx in min_cx..=max_cx {
-                    let Some(bucket) = grid.get(&(cx, cy)) else {
-                        continue;
-                    };
-                    for &j in bucket {
-                        if j <= i {
-                            continue;
-                        }
-                        if overlap_marks[j] == overlap_stamp {
-                            continue;
-                        }
-                        overlap_marks[j] = overlap_stamp;
-
-                        let a_def_idx = entities[i].instance.def;
-                        let b_def_idx = entities[j].instance.def;
-                        let pair = if a_def_idx <= b_def_idx {
-                            (a_def_idx, b_def_idx)
-                        } else {
-                            (b_def_idx, a_def_idx)
-                        };
-                        let can_collide = *collide_cache
-                            .entry(pair)
-                            .or_insert_with(|| entities_should_collide(db, a_def_idx, b_def_idx));
-                        if !can_collide {
-                            continue;
-                        }
-
-                        let b_hb = hitboxes[j];
-                        let overlap_x = (a_hb.x + a_hb.w).min(b_hb.x + b_hb.w) - a_hb.x.max(b_hb.x);
-                        let overlap_y = (a_hb.y + a_hb.h).min(b_hb.y + b_hb.h) - a_hb.y.max(b_hb.y);
-                        if overlap_x <= 0.0 || overlap_y <= 0.0 {
-                            continue;
-                        }
-
-                        any = true;
-                        let b_center = vec2(b_hb.x + b_hb.w * 0.5, b_hb.y + b_hb.h * 0.5);
-                        let delta = b_center - a_center;
-
-                        let choose_x = if (overlap_x - overlap_y).abs() <= 0.0001 {
-                            if delta.x.abs() > delta.y.abs() {
-                                true
-                            } else if delta.y.abs() > delta.x.abs() {
-                                false
-                            } else {
-                                pair_sign(i, j, 0xA53C_7E19) > 0.0
-                            }
-                        } else {
-                            overlap_x < overlap_y
-                        };
-
-                        let pair_extent = a_hb
-                            .w
-                            .min(a_hb.h)
-                            .min(b_hb.w.min(b_hb.h))
-                            .max(1.0);
-                        let max_pair_push = pair_extent * 0.35;
-
-                        if choose_x {
-                            let dir = if delta.x.abs() > 0.0001 {
-                                delta.x.signum()
-                            } else {
-                                pair_sign(i, j, 0x5F4D_CC3B)
-                            };
-                            let push = ((overlap_x + EPSILON) * 0.5).min(max_pair_push);
-                            corrections[i].x -= dir * push;
-                            corrections[j].x += dir * push;
-                        } else {
-                            let dir = if delta.y.abs() > 0.0001 {
-                                delta.y.signum()
-                            } else {
-                                pair_sign(i, j, 0x73D2_A11F)
-                            };
-                            let push = ((overlap_y + EPSILON) * 0.5).min(max_pair_push);
-                            corrections[i].y -= dir * push;
-                            corrections[j].y += dir * push;
-                        }
-                    }
-                }
-            }
-        }
-
-        if !any {
-            break;
-        }
-
-        for i in 0..entities.len() {
-            let mut correction = corrections[i];
-            if correction.length_squared() <= 0.0 {
-                continue;
-            }
-
-            let hb = hitboxes[i];
-            let max_total_push = hb.w.max(hb.h).max(1.0) * 0.45;
-            let len_sq = correction.length_squared();
-            if len_sq > max_total_push * max_total_push {
-                correction *= max_total_push / len_sq.sqrt();
-            }
+/// The shop run by the shopkeeper under `point`, if they're within `range` of the player.
+fn shopkeeper_at(
+    entities: &[Entity],
+    db: &EntityDatabase,
+    shops: &ShopDatabase,
+    player_pos: Vec2,
+    range: f32,
+    point: Vec2,
+) -> Option<usize> {
+    entities
+        .iter()
+        .filter(|ent| ent.position().distance(player_pos) <= range && ent.hitbox(db).contains(point))
+        .find_map(|ent| shops.find_by_entity(&db.entities[ent.instance.def].id))
+}
 
-            entities[i].instance.pos += correction;
-            entities[i].clamp_to_map(map, db);
-        }
-    }
+/// Picks the ripe crop on `tile`, dropping its produce there. Returns whether there was one.
+fn harvest_crop(
+    farm: &mut FarmState,
+    crop_db: &CropDatabase,
+    ground_items: &mut GroundItems,
+    item_db: &ItemDatabase,
+    (x, y): (usize, usize),
+    tile_size: f32,
+) -> bool {
+    let Some(produce) = farm.crops.harvest(x, y, crop_db) else {
+        return false;
+    };
+    let center = vec2((x as f32 + 0.5) * tile_size, (y as f32 + 0.5) * tile_size);
+    ground_items.spawn(item_db, &produce.item, produce.count, center);
+    true
 }
 
-fn rect_cell_range(rect: Rect, cell_size: f32) -> (i32, i32, i32, i32) {
-    let cell = cell_size.max(1.0);
-    let min_cx = (rect.x / cell).floor() as i32;
-    let max_cx = ((rect.x + rect.w) / cell).floor() as i32;
-    let min_cy = (rect.y / cell).floor() as i32;
-    let max_cy = ((rect.y + rect.h) / cell).floor() as i32;
-    (min_cx, max_cx, min_cy, max_cy)
+/// Turns the capturable entity under `world` that `item` works on into its friendly form,
+/// returning the friend's name.
+fn capture_entity(
+    entities: &mut [Entity],
+    db: &EntityDatabase,
+    registry: &MovementRegistry,
+    item: &str,
+    world: Vec2,
+    particles: &mut ParticleSystem,
+) -> Option<String> {
+    let ent = entities
+        .iter_mut()
+        .find(|ent| ent.hitbox(db).contains(world) && ent.can_capture(db, item))?;
+    let capture = db.entities[ent.instance.def].capture.as_ref()?;
+    let Some(friend) = Entity::spawn(db, &capture.becomes, ent.instance.pos, registry) else {
+        eprintln!("can't capture into unknown entity '{}'", capture.becomes);
+        return None;
+    };
+    if let Some(particle) = capture.particles.as_deref() {
+        particles.burst(particle, ent.hitbox(db).center());
+    }
+    *ent = friend;
+    Some(db.entities[ent.instance.def].name.clone())
 }
 
-fn entities_should_collide(db: &EntityDatabase, a_def_idx: usize, b_def_idx: usize) -> bool {
-    let a_flags = db.entities[a_def_idx].flags;
-    let b_flags = db.entities[b_def_idx].flags;
-    if (a_flags & entity::DEF_FLAG_NO_ENTITY_COLLISION) != 0
-        || (b_flags & entity::DEF_FLAG_NO_ENTITY_COLLISION) != 0
+/// Gives a kill's XP to the player and the friend that made it, if a friend did.
+fn award_kill_xp(
+    player: &mut Player,
+    entities: &mut [Entity],
+    db: &EntityDatabase,
+    config: &GameConfig,
+    console: &mut Console,
+    killer: u64,
+    xp: f32,
+) {
+    let Some(ent) = entities.iter_mut().find(|ent| ent.instance.uid == killer) else {
+        return;
+    };
+    let def = &db.entities[ent.instance.def];
+    if def.kind != EntityKind::Friend {
+        return;
+    }
+    if let Some(growth) = &def.growth
+        && ent.instance.gain_xp(growth, xp) > 0
     {
-        return false;
+        console.print(format!("{} reached level {}", def.name, ent.instance.level));
     }
+    if player.gain_xp(&config.player_growth, xp) > 0 {
+        console.print(format!("you reached level {}", config.player_growth.level_for(player.xp())));
+    }
+}
 
-    let a_kind = db.entities[a_def_idx].kind;
-    let b_kind = db.entities[b_def_idx].kind;
-    !blocks_kind(db, a_def_idx, b_kind) && !blocks_kind(db, b_def_idx, a_kind)
+/// Structure ids of every interactor the player is in range of, for station recipes.
+fn nearby_stations(interactors: &[StructureInteractor], player_pos: Vec2) -> Vec<String> {
+    interactors
+        .iter()
+        .filter(|interactor| {
+            interactor_in_range(player_pos, interactor.group_rect, interactor.interact_range_world)
+        })
+        .map(|interactor| interactor.structure_id.clone())
+        .collect()
 }
 
-fn blocks_kind(db: &EntityDatabase, def_idx: usize, kind: entity::EntityKind) -> bool {
-    let flags = db.entities[def_idx].flags;
-    match kind {
-        entity::EntityKind::Enemy => (flags & entity::DEF_FLAG_NO_ENEMY_COLLISION) != 0,
-        entity::EntityKind::Friend => (flags & entity::DEF_FLAG_NO_FRIEND_COLLISION) != 0,
-        entity::EntityKind::Misc => (flags & entity::DEF_FLAG_NO_MISC_COLLISION) != 0,
+fn interactor_in_range(player_pos: Vec2, area: Rect, range_world: f32) -> bool {
+    if range_world <= 0.0 {
+        return true;
     }
+    let nearest = vec2(
+        player_pos.x.clamp(area.x, area.x + area.w),
+        player_pos.y.clamp(area.y, area.y + area.h),
+    );
+    player_pos.distance(nearest) <= range_world
 }
 
 fn draw_player_health(
     hp: f32,
     max_hp: f32,
     view_height: f32,
-    heart_full: &Texture2D,
-    heart_empty: &Texture2D,
-) {
+    heart_full: &Sprite,
+    heart_empty: &Sprite,
+    top: f32,
+) -> Rect {
+    let padding = 8.0;
+    let empty = Rect::new(screen_width() - padding, top + padding, 0.0, 0.0);
     if max_hp <= 0.0 {
-        return;
+        return empty;
     }
     let hp_per_heart = 1.0;
-    let padding = 8.0;
     let base_fov = 300.0;
     let fov_scale = (base_fov / view_height.max(1.0)).clamp(0.7, 1.35);
     let scale = fov_scale;
 
-    let heart_w = heart_full.width() * scale;
-    let heart_h = heart_full.height() * scale;
+    let heart_w = heart_full.size().x * scale;
+    let heart_h = heart_full.size().y * scale;
     if heart_w <= 0.0 || heart_h <= 0.0 {
-        return;
+        return empty;
     }
     // Terraria-style overlap: sprite has padding, so compress spacing hard.
     let step_x = (heart_w * 0.4).max(1.0);
@@ -996,23 +2561,24 @@ fn draw_player_health(
     let hearts_per_row = 10;
     let rows = ((total_hearts + hearts_per_row - 1) / hearts_per_row) as i32;
 
+    let mut bounds = empty;
     for row in 0..rows {
         let row_start = row * hearts_per_row;
         let row_count = (total_hearts - row_start).min(hearts_per_row);
         let row_width = heart_w + (row_count as f32 - 1.0) * step_x;
         let start_x = screen_width() - padding - row_width;
-        let y = padding + row as f32 * step_y;
+        let y = top + padding + row as f32 * step_y;
+        bounds = bounds.combine_with(Rect::new(start_x, y, row_width, heart_h));
 
         for i in 0..row_count {
             let idx = row_start + i;
-            let tex = if idx < full_hearts {
+            let sprite = if idx < full_hearts {
                 heart_full
             } else {
                 heart_empty
             };
             let x = start_x + i as f32 * step_x;
-            draw_texture_ex(
-                tex,
+            sprite.draw(
                 x,
                 y,
                 WHITE,
@@ -1023,4 +2589,5 @@ fn draw_player_health(
             );
         }
     }
+    bounds
 }
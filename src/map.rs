@@ -1,11 +1,36 @@
 use macroquad::prelude::*;
-use macroquad::file::load_string;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::path::Path;
-use crate::helpers::{asset_path, data_path, load_wasm_manifest_files};
+use crate::helpers::{asset_path, data_path, load_file, load_string, load_wasm_manifest_files};
+use crate::paged::{PAGE_SIZE, PagedGrid};
+use crate::entity::EntityDrop;
+use crate::item::ItemStack;
+use crate::decal::{DecalDatabase, DecalLayer};
+use crate::trigger::{PlacedTrigger, TriggerDef};
+use crate::power::PowerDef;
+use crate::conveyor::ConveyorDef;
+use crate::automation::AutomationDef;
+use crate::tiled::{TiledTileset, is_tiled_tileset};
+use crate::texture::upload;
 
 const EMPTY_TILE: u8 = u8::MAX;
-const CHUNK_SIZE: usize = 32;
+const CHUNK_SIZE: usize = PAGE_SIZE;
+const NO_SORTED_OWNER: u32 = u32::MAX;
+const MAX_RESIDENT_CHUNKS: usize = 64;
+/// Spare render targets kept for reuse: enough for a full set of resident chunks.
+const MAX_POOLED_CHUNK_TARGETS: usize = MAX_RESIDENT_CHUNKS * 3;
+/// Tiles per chunk mesh, keeping each within one of macroquad's draw call buffers.
+const CHUNK_MESH_QUADS: usize = 800;
+const CHUNK_EVICT_MARGIN: i32 = 2;
+const OCCLUDED_ALPHA: f32 = 0.5;
+const OCCLUSION_FADE_SPEED: f32 = 10.0;
+/// Seed for picking tile variants, so they don't line up with other per-cell hashes.
+const VARIANT_SEED: u32 = 0x51F1_5EED;
+/// Seed for picking structure variants, mixed with the structure's id.
+const STRUCTURE_VARIANT_SEED: u32 = 0x7A12_C0DE;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct GridIndex {
@@ -32,6 +57,30 @@ struct TilesetFile {
     #[serde(default)]
     tile_count: Option<u16>,
     tiles: Vec<TileInfoFile>,
+    #[serde(default)]
+    animations: Vec<TileAnimationFile>,
+    #[serde(default)]
+    variants: Vec<TileVariantsFile>,
+}
+
+impl From<TiledTileset> for TilesetFile {
+    fn from(tiled: TiledTileset) -> Self {
+        Self {
+            image: tiled.image,
+            tile_width: tiled.tile_width,
+            tile_height: tiled.tile_height,
+            columns: tiled.columns,
+            rows: tiled.tile_count.div_ceil(tiled.columns.max(1)),
+            tile_count: Some(tiled.tile_count),
+            tiles: Vec::new(),
+            animations: tiled
+                .animations
+                .into_iter()
+                .map(|(tile, frames, fps)| TileAnimationFile { tile, frames, fps })
+                .collect(),
+            variants: Vec::new(),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -43,9 +92,64 @@ struct TileInfoFile {
     height: u16,
 }
 
+/// A tile that cycles through other tiles when drawn. Maps place `tile`; `frames` are the tile
+/// ids shown in turn, `tile` itself included if it should appear.
+#[derive(Deserialize)]
+struct TileAnimationFile {
+    tile: u8,
+    frames: Vec<u8>,
+    fps: f32,
+}
+
+struct TileAnimation {
+    frames: Vec<u8>,
+    fps: f32,
+}
+
+/// Alternatives drawn in place of `tile`, one picked per map cell so large fills don't repeat
+/// in a grid. `flip` and `rotate` additionally mirror and quarter-turn the pick per cell, for
+/// tiles that still look right that way.
+#[derive(Deserialize)]
+struct TileVariantsFile {
+    tile: u8,
+    tiles: Vec<u8>,
+    #[serde(default)]
+    flip: bool,
+    #[serde(default)]
+    rotate: bool,
+}
+
+/// How one map cell's tile is laid, on top of whatever its variant picks: mirrored left to
+/// right first, then turned a quarter clockwise `turns` times.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct TileOrientation {
+    pub flip_x: bool,
+    pub turns: u8,
+}
+
+impl TileOrientation {
+    /// This orientation applied after `first`.
+    fn after(self, first: TileOrientation) -> Self {
+        // A mirror reverses the direction of any turn made before it.
+        let first_turns = if self.flip_x { 4 - first.turns % 4 } else { first.turns };
+        Self {
+            flip_x: self.flip_x != first.flip_x,
+            turns: (self.turns + first_turns) % 4,
+        }
+    }
+}
+
 pub struct TileSet {
     texture: Texture2D,
     tiles: Vec<Option<Rect>>,
+    /// Each tile's average color, for drawing the map small. Indexed by tile id.
+    colors: Vec<Color>,
+    /// Indexed by tile id.
+    animations: Vec<Option<TileAnimation>>,
+    /// Indexed by tile id.
+    variants: Vec<Option<TileVariantsFile>>,
+    /// Seconds of animation played, advanced by `update`.
+    clock: f32,
 }
 
 impl TileSet {
@@ -53,7 +157,11 @@ impl TileSet {
         let json_path = asset_path(tileset_json);
         let texture_path = asset_path(texture_path);
         let json_content = load_string(&json_path).await?;
-        let parsed: TilesetFile = serde_json::from_str(&json_content)?;
+        let parsed: TilesetFile = if is_tiled_tileset(tileset_json) {
+            TiledTileset::parse(tileset_json, &json_content)?.into()
+        } else {
+            serde_json::from_str(&json_content)?
+        };
 
         let has_tiles = !parsed.tiles.is_empty();
         let tile_count = parsed
@@ -102,8 +210,9 @@ impl TileSet {
             tiles.truncate(EMPTY_TILE as usize);
         }
 
-        let texture = load_texture(&texture_path).await?;
-        texture.set_filter(FilterMode::Nearest);
+        let image = Image::from_file_with_format(&load_file(&texture_path).await?, None)?;
+        let texture = upload(image.width, image.height, &image.bytes, FilterMode::Nearest);
+        let colors = tiles.iter().map(|rect| rect.map_or(BLANK, |rect| average_color(&image, rect))).collect();
 
         if let Some(image) = parsed.image.as_ref() {
             if !image.is_empty() && image != Path::new(&texture_path).file_name().and_then(|name| name.to_str()).unwrap_or("") {
@@ -111,7 +220,63 @@ impl TileSet {
             }
         }
 
-        Ok(Self { texture, tiles })
+        let mut animations: Vec<Option<TileAnimation>> = Vec::new();
+        for animation in parsed.animations {
+            let idx = animation.tile as usize;
+            if idx >= tiles.len() || animation.frames.is_empty() || animation.fps <= 0.0 {
+                eprintln!("tileset animation for tile {} ignored", animation.tile);
+                continue;
+            }
+            if animations.len() <= idx {
+                animations.resize_with(idx + 1, || None);
+            }
+            animations[idx] = Some(TileAnimation {
+                frames: animation.frames,
+                fps: animation.fps,
+            });
+        }
+
+        let mut variants: Vec<Option<TileVariantsFile>> = Vec::new();
+        for group in parsed.variants {
+            let idx = group.tile as usize;
+            if idx >= tiles.len() || group.tiles.is_empty() {
+                eprintln!("tileset variants for tile {} ignored", group.tile);
+                continue;
+            }
+            if variants.len() <= idx {
+                variants.resize_with(idx + 1, || None);
+            }
+            variants[idx] = Some(group);
+        }
+
+        Ok(Self {
+            texture,
+            tiles,
+            colors,
+            animations,
+            variants,
+            clock: 0.0,
+        })
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.clock += dt;
+    }
+
+    /// Whether `id` animates, and so is drawn every frame rather than baked into chunks.
+    pub fn is_animated(&self, id: u8) -> bool {
+        self.animations.get(id as usize).is_some_and(|animation| animation.is_some())
+    }
+
+    /// The tile `id` shows right now at tile `cell`. Neighbouring cells start a frame apart so a
+    /// field of the same animated tile doesn't move in lockstep.
+    fn frame(&self, id: u8, cell: (i32, i32)) -> u8 {
+        let Some(Some(animation)) = self.animations.get(id as usize) else {
+            return id;
+        };
+        let phase = cell.0.wrapping_mul(3).wrapping_add(cell.1.wrapping_mul(5)) as i64;
+        let step = (self.clock * animation.fps) as i64 + phase;
+        animation.frames[step.rem_euclid(animation.frames.len() as i64) as usize]
     }
 
     fn get(&self, id: u8) -> Option<Rect> {
@@ -121,13 +286,135 @@ impl TileSet {
         self.tiles.get(id as usize).and_then(|rect| *rect)
     }
 
-    pub fn texture(&self) -> &Texture2D {
-        &self.texture
+    /// Draws a single tile at `pos` (top-left, world units), scaled to `size`, as it looks in
+    /// the map cell under `pos`.
+    pub fn draw_tile(&self, id: u8, pos: Vec2, size: f32, color: Color) {
+        self.draw_oriented_tile(id, pos, size, color, TileOrientation::default());
+    }
+
+    /// Like `draw_tile`, laid as `orientation` says.
+    pub fn draw_oriented_tile(&self, id: u8, pos: Vec2, size: f32, color: Color, orientation: TileOrientation) {
+        let cell = ((pos.x / size).floor() as i32, (pos.y / size).floor() as i32);
+        self.draw_tile_in_cell(id, cell, pos, size, color, orientation);
+    }
+
+    /// The atlas rect `id` shows at map cell `cell`, after its animation frame and variant,
+    /// and whether it's flipped and how many quarter turns it's rotated.
+    fn resolve(&self, id: u8, cell: (i32, i32), orientation: TileOrientation) -> Option<(Rect, bool, u32)> {
+        let id = self.frame(id, cell);
+        let hash = hash_u32(cell.0 as u32, cell.1 as u32, VARIANT_SEED);
+        let (id, picked) = match self.variants.get(id as usize) {
+            Some(Some(group)) => (
+                group.tiles[hash as usize % group.tiles.len()],
+                TileOrientation {
+                    flip_x: group.flip && (hash >> 8) & 1 == 1,
+                    turns: if group.rotate { ((hash >> 9) & 3) as u8 } else { 0 },
+                },
+            ),
+            _ => (id, TileOrientation::default()),
+        };
+        let laid = orientation.after(picked);
+        Some((self.get(id)?, laid.flip_x, laid.turns as u32))
+    }
+
+    /// Draws `id` at `pos` with the animation frame and variant of map cell `cell`, for
+    /// callers drawing somewhere other than the cell's world position, like a chunk's target.
+    pub fn draw_tile_in_cell(
+        &self,
+        id: u8,
+        cell: (i32, i32),
+        pos: Vec2,
+        size: f32,
+        color: Color,
+        orientation: TileOrientation,
+    ) {
+        let Some((source, flip_x, turns)) = self.resolve(id, cell, orientation) else {
+            return;
+        };
+        draw_texture_ex(
+            &self.texture,
+            pos.x,
+            pos.y,
+            color,
+            DrawTextureParams {
+                source: Some(source),
+                dest_size: Some(vec2(size, size)),
+                flip_x,
+                rotation: turns as f32 * std::f32::consts::FRAC_PI_2,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Adds `id` to `mesh` as a quad filling `dest`, looking as `draw_tile_in_cell` draws it.
+    pub fn push_tile_quad(
+        &self,
+        mesh: &mut Mesh,
+        id: u8,
+        cell: (i32, i32),
+        dest: Rect,
+        color: Color,
+        orientation: TileOrientation,
+    ) {
+        let Some((source, flip_x, turns)) = self.resolve(id, cell, orientation) else {
+            return;
+        };
+        let atlas = self.texture.size();
+        let (mut left, mut right) = (source.x / atlas.x, source.right() / atlas.x);
+        let (top, bottom) = (source.y / atlas.y, source.bottom() / atlas.y);
+        if flip_x {
+            std::mem::swap(&mut left, &mut right);
+        }
+        // Corners clockwise from the top left; turning the tile shifts which uv each corner gets.
+        let uvs = [vec2(left, top), vec2(right, top), vec2(right, bottom), vec2(left, bottom)];
+        let corners = [
+            dest.point(),
+            vec2(dest.right(), dest.y),
+            vec2(dest.right(), dest.bottom()),
+            vec2(dest.x, dest.bottom()),
+        ];
+        let first = mesh.vertices.len() as u16;
+        for (i, corner) in corners.into_iter().enumerate() {
+            let uv = uvs[(i + 4 - turns as usize) % 4];
+            mesh.vertices.push(Vertex::new(corner.x, corner.y, 0.0, uv.x, uv.y, color));
+        }
+        mesh.indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
     }
 
     pub fn count(&self) -> usize {
         self.tiles.len()
     }
+
+    /// Grass, which fresh ground is filled with, or the first tile of a set too short to have it.
+    pub fn ground_tile(&self) -> u8 {
+        if self.count() > 24 { 24 } else { 0 }
+    }
+
+    /// The average color of tile `id`, ignoring transparent pixels; None for empty tiles.
+    pub fn color(&self, id: u8) -> Option<Color> {
+        self.colors.get(id as usize).copied().filter(|color| color.a > 0.0)
+    }
+}
+
+fn average_color(image: &Image, rect: Rect) -> Color {
+    let (mut sum, mut count) = (Vec4::ZERO, 0.0);
+    for y in rect.y as u32..(rect.y + rect.h) as u32 {
+        for x in rect.x as u32..(rect.x + rect.w) as u32 {
+            if x >= image.width() as u32 || y >= image.height() as u32 {
+                continue;
+            }
+            let pixel = image.get_pixel(x, y);
+            if pixel.a > 0.5 {
+                sum += Vec4::new(pixel.r, pixel.g, pixel.b, 1.0);
+                count += 1.0;
+            }
+        }
+    }
+    if count == 0.0 {
+        return BLANK;
+    }
+    let average = sum / count;
+    Color::new(average.x, average.y, average.z, 1.0)
 }
 
 #[derive(Clone)]
@@ -145,6 +432,7 @@ pub struct Structure {
     occupied_offsets: Vec<(usize, usize)>,
     collider_offsets: Vec<(usize, usize, u8)>,
     interactor_offsets: Vec<(usize, usize, u8)>,
+    sort_base: f32,
 }
 
 impl Structure {
@@ -215,6 +503,7 @@ impl Structure {
             occupied_offsets: Vec::new(),
             collider_offsets: Vec::new(),
             interactor_offsets: Vec::new(),
+            sort_base: height as f32,
         };
         structure.rebuild_cache();
         structure
@@ -270,6 +559,48 @@ impl Structure {
                 }
             }
         }
+
+        // Depth sorting keys off the lowest solid quarter (the trunk/feet of the
+        // structure) rather than the sprite bottom, so characters standing just
+        // below a collider draw in front of it.
+        self.sort_base = self
+            .collider_offsets
+            .iter()
+            .map(|&(_, y, mask)| y as f32 + if (mask & 0b1100) != 0 { 1.0 } else { 0.5 })
+            .fold(None, |acc: Option<f32>, v| Some(acc.map_or(v, |a| a.max(v))))
+            .unwrap_or(self.height as f32);
+    }
+
+    /// Draws the structure's tiles tinted at `origin` (top-left, world units), for
+    /// placement previews.
+    pub fn draw_ghost(&self, tileset: &TileSet, origin: Vec2, tile_size: f32, tint: Color) {
+        for updates in [&self.background_updates, &self.foreground_updates, &self.overlay_updates] {
+            for &(x, y, tile) in updates {
+                tileset.draw_tile(tile, origin + vec2(x as f32, y as f32) * tile_size, tile_size, tint);
+            }
+        }
+        draw_rectangle(
+            origin.x,
+            origin.y,
+            self.width as f32 * tile_size,
+            self.height as f32 * tile_size,
+            Color::new(tint.r, tint.g, tint.b, 0.2),
+        );
+    }
+
+    /// A copy with every tile listed as `from` in `swaps` turned into its `to`.
+    fn with_swaps(&self, swaps: &[(u8, u8)]) -> Self {
+        let swap = |tile: &mut u8| {
+            if let Some(&(_, to)) = swaps.iter().find(|&&(from, _)| from == *tile) {
+                *tile = to;
+            }
+        };
+        let mut structure = self.clone();
+        structure.background.iter_mut().for_each(swap);
+        structure.foreground.iter_mut().for_each(swap);
+        structure.overlay.iter_mut().for_each(swap);
+        structure.rebuild_cache();
+        structure
     }
 
     fn is_empty(&self) -> bool {
@@ -285,11 +616,93 @@ impl Structure {
 pub struct StructureDef {
     pub id: String,
     pub structure: Structure,
+    pub y_sort: bool,
     pub on_interact: Vec<String>,
+    pub scene_transition: Option<String>,
+    pub interior: Option<String>,
+    /// Hit points; structures with none can't be broken.
+    pub hp: f32,
+    /// Only tool hits damage it; dashes pass by.
+    pub hit_only: bool,
+    /// Tiles swapped in once hp drops to half.
+    pub damaged: Option<DamagedTiles>,
+    pub drops: Vec<EntityDrop>,
+    pub break_particles: Option<String>,
+    /// Items consumed to place it in build mode; structures without one can't be built.
+    /// Built structures only take damage from enemies, so tools can't wreck them.
+    pub build_cost: Option<Vec<ItemStack>>,
+    /// Items a hammer uses to bring it back to full hp; empty means it can't be repaired.
+    pub repair_cost: Vec<ItemStack>,
+    /// Inventory slots of a storage container; 0 for anything that isn't one.
+    pub storage_slots: usize,
+    /// Interacting sleeps through to the next morning.
+    pub bed: bool,
+    /// Trigger regions placed along with the structure.
+    pub triggers: Vec<TriggerDef>,
+    /// Entity that stands on the structure while it's built, like a turret's gun.
+    pub entity: Option<String>,
+    pub power: Option<PowerDef>,
+    pub conveyor: Option<ConveyorDef>,
+    pub automation: Option<AutomationDef>,
+    /// Days an expedition resource stays gone once broken.
+    pub respawn_days: u32,
+    /// Shows on the world map once discovered, and interacting opens it to fast travel.
+    pub waypoint: bool,
     pub interact_range: f32,
     pub frequency: f32,
     pub max_per_map: usize,
     pub min_distance: f32,
+    /// Alternative tiles, picked per placement by weight against the base tiles' `weight`.
+    pub variants: Vec<StructureVariant>,
+    pub weight: f32,
+    /// Tile swaps by palette name, applied on maps placing structures with that palette.
+    pub palettes: HashMap<String, Vec<(u8, u8)>>,
+}
+
+impl StructureDef {
+    /// The tiles placed at (`x`, `y`): a variant picked from a hash of the spot, so the same
+    /// spot always gets the same one, with `palette`'s swaps applied.
+    pub fn structure_at(&self, x: usize, y: usize, palette: Option<&str>) -> Cow<'_, Structure> {
+        let structure = self.variant_at(x, y);
+        match palette.and_then(|name| self.palettes.get(name)) {
+            Some(swaps) if !swaps.is_empty() => Cow::Owned(structure.with_swaps(swaps)),
+            _ => Cow::Borrowed(structure),
+        }
+    }
+
+    fn variant_at(&self, x: usize, y: usize) -> &Structure {
+        let total = self.weight + self.variants.iter().map(|variant| variant.weight).sum::<f32>();
+        if self.variants.is_empty() || total <= 0.0 {
+            return &self.structure;
+        }
+        let seed = self.id.bytes().fold(STRUCTURE_VARIANT_SEED, |seed, byte| hash_u32(byte as u32, seed, 0));
+        let mut roll = (hash_u32(x as u32, y as u32, seed) % 10_000) as f32 / 10_000.0 * total - self.weight;
+        for variant in &self.variants {
+            if roll < 0.0 {
+                break;
+            }
+            roll -= variant.weight;
+            if roll < 0.0 {
+                return &variant.structure;
+            }
+        }
+        &self.structure
+    }
+
+    /// `tile` after `palette`'s swaps.
+    fn palette_tile(&self, palette: Option<&str>, tile: u8) -> u8 {
+        palette
+            .and_then(|name| self.palettes.get(name))
+            .and_then(|swaps| swaps.iter().find(|&&(from, _)| from == tile))
+            .map_or(tile, |&(_, to)| to)
+    }
+}
+
+/// Another look for a structure, the same size and with the same colliders and interactors.
+#[derive(Clone)]
+pub struct StructureVariant {
+    pub weight: f32,
+    pub structure: Structure,
 }
 
 #[derive(Clone)]
@@ -298,9 +711,94 @@ pub struct StructureInteractor {
     pub rect: Rect,
     pub group_rect: Rect,
     pub on_interact: Vec<String>,
+    pub scene_transition: Option<String>,
+    pub interior: Option<String>,
     pub interact_range_world: f32,
 }
 
+/// A placed structure whose foreground/overlay tiles are drawn in the depth-sorted
+/// pass with entities instead of being baked into chunk layers.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SortedStructure {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub base_y: f32,
+}
+
+#[derive(Clone)]
+pub struct DamagedTiles {
+    pub foreground: Option<Vec<u8>>,
+    pub overlay: Option<Vec<u8>>,
+}
+
+/// A placed structure with hit points that tools (and, unless `hit_only`, dashes) can break.
+/// Enemies can break it either way.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BreakableStructure {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub hp: f32,
+    #[serde(default)]
+    pub max_hp: f32,
+    #[serde(default)]
+    pub structure_id: String,
+    #[serde(default)]
+    pub hit_only: bool,
+    /// Only enemies damage it; tool hits pass by. Not saved: the farm marks its built
+    /// structures again on load.
+    #[serde(skip)]
+    pub enemy_only: bool,
+}
+
+impl BreakableStructure {
+    fn takes(&self, hit: StructureHit) -> bool {
+        match hit {
+            StructureHit::Dash => !self.hit_only && !self.enemy_only,
+            StructureHit::Tool => !self.enemy_only,
+            StructureHit::Enemy => true,
+        }
+    }
+
+    pub fn is_damaged(&self) -> bool {
+        self.hp < self.max_hp
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StructureHit {
+    Dash,
+    Tool,
+    /// An enemy chewing on what it touches, or attacking a spot.
+    Enemy,
+}
+
+pub struct BrokenStructure {
+    pub structure_id: String,
+    pub rect: Rect,
+}
+
+/// A structure standing on the map, as the map's structure index knows it. Structures restored
+/// from a snapshot are only known if they were depth-sorted or breakable, and the sorted ones
+/// without an id.
+#[derive(Clone)]
+pub struct PlacedStructure {
+    pub structure_id: String,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl PlacedStructure {
+    fn overlaps(&self, x: usize, y: usize, width: usize, height: usize) -> bool {
+        self.x < x + width && x < self.x + self.width && self.y < y + height && y < self.y + self.height
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TileMapSnapshot {
     pub width: usize,
@@ -310,25 +808,136 @@ pub struct TileMapSnapshot {
     pub foreground: Vec<u8>,
     pub overlay: Vec<u8>,
     pub collision_mask: Vec<u8>,
+    #[serde(default)]
+    pub sorted_structures: Vec<SortedStructure>,
+    #[serde(default, alias = "fragile_structures")]
+    pub breakable_structures: Vec<BreakableStructure>,
+}
+
+impl TileMapSnapshot {
+    /// The same map on a `width` by `height` one, anchored at the top left. The new cells are
+    /// bare `ground_tile` with nothing on them.
+    pub fn grown(&self, width: usize, height: usize, ground_tile: u8) -> Self {
+        let grow = |cells: &[u8], fill: u8| {
+            let mut out = vec![fill; width * height];
+            for y in 0..self.height.min(height) {
+                let copied = self.width.min(width);
+                out[y * width..y * width + copied].copy_from_slice(&cells[y * self.width..y * self.width + copied]);
+            }
+            out
+        };
+        Self {
+            width,
+            height,
+            tile_size: self.tile_size,
+            background: grow(&self.background, ground_tile),
+            foreground: grow(&self.foreground, EMPTY_TILE),
+            overlay: grow(&self.overlay, EMPTY_TILE),
+            collision_mask: grow(&self.collision_mask, 0),
+            sorted_structures: self.sorted_structures.clone(),
+            breakable_structures: self.breakable_structures.clone(),
+        }
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LayerKind {
     Background,
     Foreground,
     Overlay,
 }
 
+thread_local! {
+    /// Render targets of chunks that were evicted or whose map went away, with their size in
+    /// pixels. New chunks take from here before allocating, so swapping scenes doesn't free
+    /// and allocate thousands of targets on the GPU.
+    static CHUNK_TARGET_POOL: RefCell<Vec<(u32, RenderTarget)>> = const { RefCell::new(Vec::new()) };
+    /// Whether maps built from now on bake foreground tiles into the background's target.
+    static COMPOSITE_CHUNK_LAYERS: Cell<bool> = const { Cell::new(false) };
+    /// Whether maps built from now on mesh their chunks; see `TileMap::set_mesh_chunks`.
+    static MESH_CHUNKS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Bakes each chunk's foreground into its background target instead of a target of its own,
+/// for maps built from now on. Saves a third of the chunk memory, but decals and crops then
+/// draw over foreground tiles instead of under them.
+pub fn set_composite_chunk_layers(on: bool) {
+    COMPOSITE_CHUNK_LAYERS.with(|cell| cell.set(on));
+}
+
+/// A `size` pixels square chunk render target, reused from the pool if one fits.
+fn chunk_target(size: u32) -> RenderTarget {
+    let pooled = CHUNK_TARGET_POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        let index = pool.iter().rposition(|(pooled, _)| *pooled == size)?;
+        Some(pool.swap_remove(index).1)
+    });
+    pooled.unwrap_or_else(|| {
+        let target = render_target(size, size);
+        target.texture.set_filter(FilterMode::Nearest);
+        target
+    })
+}
+
+/// Hands a chunk render target back to the pool, unless it's full.
+fn release_chunk_target(target: &RenderTarget) {
+    let size = target.texture.width() as u32;
+    // The pool may already be gone while the game shuts down.
+    let _ = CHUNK_TARGET_POOL.try_with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if pool.len() < MAX_POOLED_CHUNK_TARGETS {
+            pool.push((size, target.clone()));
+        }
+    });
+}
+
+/// How many spare chunk render targets are waiting to be reused.
+pub fn pooled_chunk_targets() -> usize {
+    CHUNK_TARGET_POOL.with(|pool| pool.borrow().len())
+}
+
+/// Render targets held by a map's resident chunks, by layer, and the meshes standing in for
+/// them when chunks are meshed.
+#[derive(Clone, Copy, Default)]
+pub struct ChunkTargetStats {
+    pub background: usize,
+    pub foreground: usize,
+    pub overlay: usize,
+    pub meshes: usize,
+}
+
+/// Tiles left out of a chunk's baked layer to be drawn live, as `(x, y)` tile coordinates.
+type AnimatedTiles = Vec<(usize, usize)>;
+
 struct Chunk {
-    background: RenderTarget,
-    foreground: RenderTarget,
-    overlay: RenderTarget,
+    /// None when the chunk is meshed.
+    background: Option<RenderTarget>,
+    /// None when composited into `background`, or meshed.
+    foreground: Option<RenderTarget>,
+    /// Only there while the chunk has overlay tiles to bake.
+    overlay: Option<RenderTarget>,
     dirty_background: bool,
     dirty_foreground: bool,
     dirty_overlay: bool,
     ready_background: bool,
     ready_foreground: bool,
     ready_overlay: bool,
+    /// Animated tiles left out of each layer's render target, as `(x, y)` tile coordinates,
+    /// drawn live over the chunk every frame.
+    animated_background: Vec<(usize, usize)>,
+    animated_foreground: Vec<(usize, usize)>,
+    animated_overlay: Vec<(usize, usize)>,
+    /// Each layer's static tiles as quads, background to overlay, when the chunk is meshed.
+    meshes: [Vec<Mesh>; 3],
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        let targets = [self.background.as_ref(), self.foreground.as_ref(), self.overlay.as_ref()];
+        for target in targets.into_iter().flatten() {
+            release_chunk_target(target);
+        }
+    }
 }
 
 struct StructureApplyState {
@@ -465,6 +1074,8 @@ impl StructureApplyState {
 
             map.place_structure_unchecked(&def.structure, x, y);
             map.register_structure_interactors(def, x, y);
+            map.register_sorted_structure(def, x, y);
+            map.register_breakable_structure(def, x, y);
             for &(sx, sy) in def.structure.occupied_offsets.iter() {
                 let idx = map.idx(x + sx, y + sy);
                 self.occupied[idx] = true;
@@ -528,11 +1139,10 @@ pub struct TileMap {
     width: usize,
     height: usize,
     tile_size: f32,
-    background: Vec<u8>,
-    foreground: Vec<u8>,
-    overlay: Vec<u8>,
-    solid: Vec<bool>,
-    collision_mask: Vec<u8>,
+    background: PagedGrid<u8>,
+    foreground: PagedGrid<u8>,
+    overlay: PagedGrid<u8>,
+    collision_mask: PagedGrid<u8>,
     collision_blocks: Vec<Rect>,
     collision_dirty: bool,
     chunk_cols: usize,
@@ -547,11 +1157,41 @@ pub struct TileMap {
     chunk_rebuild_budget_per_frame: usize,
     chunk_allocs_this_frame: usize,
     chunk_rebuilds_this_frame: usize,
+    chunk_rebuild_queue: Vec<usize>,
+    chunk_rebuilt_frame: Vec<u32>,
+    chunk_last_visible: Vec<u32>,
+    resident_chunks: Vec<usize>,
+    chunk_frame: u32,
     structure_apply: Option<StructureApplyState>,
     structure_interactors: Vec<StructureInteractor>,
+    structure_triggers: Vec<PlacedTrigger>,
+    sorted_structures: Vec<SortedStructure>,
+    sorted_owner: PagedGrid<u32>,
+    sorted_by_chunk: Vec<Vec<usize>>,
+    sorted_alpha: Vec<f32>,
+    sorted_fading: Vec<usize>,
+    sorted_scratch: Vec<usize>,
+    breakable_structures: Vec<BreakableStructure>,
+    /// Every structure placed, for telling whether a spot is free.
+    placed_structures: Vec<PlacedStructure>,
+    /// Indices into `placed_structures` by the chunks their footprints touch.
+    placed_by_chunk: Vec<Vec<usize>>,
     grid_size: Vec2,
     border_thickness: f32,
     custom_border_hitbox: Option<Rect>,
+    decals: DecalLayer,
+    tint: Color,
+    /// Foreground tiles are baked into the background's chunk targets.
+    composite_layers: bool,
+    /// Chunks are baked into meshes instead of render targets.
+    mesh_chunks: bool,
+    /// Which of each structure's palettes placements use, if any.
+    structure_palette: Option<String>,
+    /// Tiles changed through `set_tile` since they were last taken, while recording.
+    tile_log: Option<Vec<(LayerKind, usize, usize, u8)>>,
+    /// Cells laid other than upright, with the tile they were laid for. Only counts while
+    /// that tile is still there, so nothing needs clearing when a cell changes.
+    orientations: HashMap<(LayerKind, usize, usize), (u8, TileOrientation)>,
 }
 
 impl TileMap {
@@ -566,29 +1206,29 @@ impl TileMap {
     }
 
     pub fn new(width: usize, height: usize, tile_size: f32, grid_size: Vec2, border_thickness: f32) -> Self {
-        let len = width * height;
         let chunk_cols = (width + CHUNK_SIZE - 1) / CHUNK_SIZE;
         let chunk_rows = (height + CHUNK_SIZE - 1) / CHUNK_SIZE;
         let chunk_pixel_size = tile_size * CHUNK_SIZE as f32;
         let chunk_size_u32 = chunk_pixel_size.round().max(1.0) as u32;
         let mut chunks = Vec::with_capacity(chunk_cols * chunk_rows);
         for _ in 0..chunk_cols * chunk_rows {
-            let background = render_target(chunk_size_u32, chunk_size_u32);
-            let foreground = render_target(chunk_size_u32, chunk_size_u32);
-            let overlay = render_target(chunk_size_u32, chunk_size_u32);
-            background.texture.set_filter(FilterMode::Nearest);
-            foreground.texture.set_filter(FilterMode::Nearest);
-            overlay.texture.set_filter(FilterMode::Nearest);
+            let meshed = MESH_CHUNKS.with(Cell::get);
+            let background = (!meshed).then(|| chunk_target(chunk_size_u32));
+            let foreground = (!meshed && !COMPOSITE_CHUNK_LAYERS.with(Cell::get)).then(|| chunk_target(chunk_size_u32));
             chunks.push(Some(Chunk {
                 background,
                 foreground,
-                overlay,
+                overlay: None,
                 dirty_background: true,
                 dirty_foreground: true,
                 dirty_overlay: true,
                 ready_background: false,
                 ready_foreground: false,
                 ready_overlay: false,
+                animated_background: Vec::new(),
+                animated_foreground: Vec::new(),
+                animated_overlay: Vec::new(),
+                meshes: Default::default(),
             }));
         }
 
@@ -598,11 +1238,10 @@ impl TileMap {
             width,
             height,
             tile_size,
-            background: vec![EMPTY_TILE; len],
-            foreground: vec![EMPTY_TILE; len],
-            overlay: vec![EMPTY_TILE; len],
-            solid: vec![false; len],
-            collision_mask: vec![0; len],
+            background: PagedGrid::new(width, height, EMPTY_TILE),
+            foreground: PagedGrid::new(width, height, EMPTY_TILE),
+            overlay: PagedGrid::new(width, height, EMPTY_TILE),
+            collision_mask: PagedGrid::new(width, height, 0),
             collision_blocks: Vec::new(),
             collision_dirty: true,
             chunk_cols,
@@ -617,16 +1256,37 @@ impl TileMap {
             chunk_rebuild_budget_per_frame: usize::MAX,
             chunk_allocs_this_frame: 0,
             chunk_rebuilds_this_frame: 0,
+            chunk_rebuild_queue: Vec::new(),
+            chunk_rebuilt_frame: vec![0; chunk_count],
+            chunk_last_visible: vec![0; chunk_count],
+            resident_chunks: (0..chunk_count).collect(),
+            chunk_frame: 1,
             structure_apply: None,
             structure_interactors: Vec::new(),
+            structure_triggers: Vec::new(),
+            sorted_structures: Vec::new(),
+            sorted_owner: PagedGrid::new(width, height, NO_SORTED_OWNER),
+            sorted_by_chunk: vec![Vec::new(); chunk_count],
+            sorted_alpha: Vec::new(),
+            sorted_fading: Vec::new(),
+            sorted_scratch: Vec::new(),
+            breakable_structures: Vec::new(),
+            placed_structures: Vec::new(),
+            placed_by_chunk: vec![Vec::new(); chunk_count],
             grid_size,
             border_thickness,
             custom_border_hitbox: None,
+            decals: DecalLayer::new(chunk_pixel_size),
+            tint: WHITE,
+            composite_layers: COMPOSITE_CHUNK_LAYERS.with(Cell::get),
+            mesh_chunks: MESH_CHUNKS.with(Cell::get),
+            structure_palette: None,
+            tile_log: None,
+            orientations: HashMap::new(),
         }
     }
 
     pub fn new_deferred(width: usize, height: usize, tile_size: f32, grid_size: Vec2, border_thickness: f32) -> Self {
-        let len = width * height;
         let chunk_cols = (width + CHUNK_SIZE - 1) / CHUNK_SIZE;
         let chunk_rows = (height + CHUNK_SIZE - 1) / CHUNK_SIZE;
         let chunk_pixel_size = tile_size * CHUNK_SIZE as f32;
@@ -641,11 +1301,10 @@ impl TileMap {
             width,
             height,
             tile_size,
-            background: vec![EMPTY_TILE; len],
-            foreground: vec![EMPTY_TILE; len],
-            overlay: vec![EMPTY_TILE; len],
-            solid: vec![false; len],
-            collision_mask: vec![0; len],
+            background: PagedGrid::new(width, height, EMPTY_TILE),
+            foreground: PagedGrid::new(width, height, EMPTY_TILE),
+            overlay: PagedGrid::new(width, height, EMPTY_TILE),
+            collision_mask: PagedGrid::new(width, height, 0),
             collision_blocks: Vec::new(),
             collision_dirty: true,
             chunk_cols,
@@ -660,11 +1319,33 @@ impl TileMap {
             chunk_rebuild_budget_per_frame: usize::MAX,
             chunk_allocs_this_frame: 0,
             chunk_rebuilds_this_frame: 0,
+            chunk_rebuild_queue: Vec::new(),
+            chunk_rebuilt_frame: vec![0; total_chunks],
+            chunk_last_visible: vec![0; total_chunks],
+            resident_chunks: Vec::new(),
+            chunk_frame: 1,
             structure_apply: None,
             structure_interactors: Vec::new(),
+            structure_triggers: Vec::new(),
+            sorted_structures: Vec::new(),
+            sorted_owner: PagedGrid::new(width, height, NO_SORTED_OWNER),
+            sorted_by_chunk: vec![Vec::new(); total_chunks],
+            sorted_alpha: Vec::new(),
+            sorted_fading: Vec::new(),
+            sorted_scratch: Vec::new(),
+            breakable_structures: Vec::new(),
+            placed_structures: Vec::new(),
+            placed_by_chunk: vec![Vec::new(); total_chunks],
             grid_size,
             border_thickness,
             custom_border_hitbox: None,
+            decals: DecalLayer::new(chunk_pixel_size),
+            tint: WHITE,
+            composite_layers: COMPOSITE_CHUNK_LAYERS.with(Cell::get),
+            mesh_chunks: MESH_CHUNKS.with(Cell::get),
+            structure_palette: None,
+            tile_log: None,
+            orientations: HashMap::new(),
         }
     }
 
@@ -745,22 +1426,8 @@ impl TileMap {
                     break 'chunk_loop;
                 }
 
-                let is_dirty = match layer {
-                    LayerKind::Background => self.chunks[chunk_index]
-                        .as_ref()
-                        .map(|c| c.dirty_background)
-                        .unwrap_or(false),
-                    LayerKind::Foreground => self.chunks[chunk_index]
-                        .as_ref()
-                        .map(|c| c.dirty_foreground)
-                        .unwrap_or(false),
-                    LayerKind::Overlay => self.chunks[chunk_index]
-                        .as_ref()
-                        .map(|c| c.dirty_overlay)
-                        .unwrap_or(false),
-                };
-                if is_dirty {
-                    self.rebuild_chunk_layer_if_dirty(chunk_index, layer, tileset);
+                if self.chunk_layer_dirty(chunk_index, layer) {
+                    self.render_dirty_chunk_layer(chunk_index, layer, tileset);
                 }
             }
         }
@@ -785,22 +1452,148 @@ impl TileMap {
     pub fn begin_frame_chunk_work(&mut self) {
         self.chunk_allocs_this_frame = 0;
         self.chunk_rebuilds_this_frame = 0;
+        self.chunk_frame = self.chunk_frame.wrapping_add(1).max(1);
     }
 
-    pub fn prewarm_visible_chunks(&mut self, camera_target: Vec2, camera_zoom: Vec2) {
+    /// Rebuilds dirty visible chunks nearest the camera first, spending one rebuild
+    /// budget unit per chunk no matter how many of its layers are dirty.
+    pub fn process_chunk_rebuild_queue(
+        &mut self,
+        tileset: &TileSet,
+        camera_target: Vec2,
+        camera_zoom: Vec2,
+    ) {
         let (min_cx, max_cx, min_cy, max_cy) = self.visible_chunk_range(camera_target, camera_zoom);
+        let mut queue = std::mem::take(&mut self.chunk_rebuild_queue);
+        queue.clear();
         for cy in min_cy..=max_cy {
             for cx in min_cx..=max_cx {
                 let chunk_index = self.chunk_index(cx as usize, cy as usize);
+                if self.chunks[chunk_index].is_some() && self.chunk_has_dirty_layer(chunk_index) {
+                    queue.push(chunk_index);
+                }
+            }
+        }
+
+        if queue.len() > 1 {
+            let cols = self.chunk_cols;
+            let size = self.chunk_pixel_size;
+            let distance_sq = |chunk_index: usize| {
+                let center = vec2(
+                    ((chunk_index % cols) as f32 + 0.5) * size,
+                    ((chunk_index / cols) as f32 + 0.5) * size,
+                );
+                center.distance_squared(camera_target)
+            };
+            queue.sort_unstable_by(|&a, &b| distance_sq(a).total_cmp(&distance_sq(b)));
+        }
+
+        for &chunk_index in &queue {
+            if self.chunk_rebuilds_this_frame >= self.chunk_rebuild_budget_per_frame {
+                break;
+            }
+            if self.chunk_rebuilt_frame[chunk_index] == self.chunk_frame {
+                continue;
+            }
+            for layer in [LayerKind::Background, LayerKind::Foreground, LayerKind::Overlay] {
+                if self.chunk_layer_dirty(chunk_index, layer) {
+                    self.render_dirty_chunk_layer(chunk_index, layer, tileset);
+                }
+            }
+            self.chunk_rebuilds_this_frame += 1;
+            self.chunk_rebuilt_frame[chunk_index] = self.chunk_frame;
+        }
+
+        self.chunk_rebuild_queue = queue;
+    }
+
+    /// Number of chunk layers (allocated or pending) still waiting for a rebuild.
+    pub fn chunk_rebuild_queue_depth(&self) -> usize {
+        let mut depth = 0usize;
+        for (chunk_index, chunk) in self.chunks.iter().enumerate() {
+            match chunk {
+                Some(chunk) => {
+                    depth += chunk.dirty_background as usize
+                        + chunk.dirty_foreground as usize
+                        + chunk.dirty_overlay as usize;
+                }
+                None => {
+                    depth += self.pending_dirty_background[chunk_index] as usize
+                        + self.pending_dirty_foreground[chunk_index] as usize
+                        + self.pending_dirty_overlay[chunk_index] as usize;
+                }
+            }
+        }
+        depth
+    }
+
+    /// Allocates render targets for chunks around the camera and evicts the least
+    /// recently seen ones once more than `MAX_RESIDENT_CHUNKS` are resident.
+    pub fn prewarm_visible_chunks(&mut self, camera_target: Vec2, camera_zoom: Vec2) {
+        let (min_cx, max_cx, min_cy, max_cy) = self.visible_chunk_range(camera_target, camera_zoom);
+        'alloc: for cy in min_cy..=max_cy {
+            for cx in min_cx..=max_cx {
+                let chunk_index = self.chunk_index(cx as usize, cy as usize);
+                self.chunk_last_visible[chunk_index] = self.chunk_frame;
                 if !self.ensure_chunk_allocated(chunk_index) {
-                    return;
+                    break 'alloc;
                 }
             }
         }
+        self.evict_distant_chunks(
+            min_cx - CHUNK_EVICT_MARGIN,
+            max_cx + CHUNK_EVICT_MARGIN,
+            min_cy - CHUNK_EVICT_MARGIN,
+            max_cy + CHUNK_EVICT_MARGIN,
+        );
+    }
+
+    pub fn resident_chunk_count(&self) -> usize {
+        self.resident_chunks.len()
+    }
+
+    pub fn chunk_target_stats(&self) -> ChunkTargetStats {
+        let mut stats = ChunkTargetStats::default();
+        for chunk in self.chunks.iter().flatten() {
+            stats.background += usize::from(chunk.background.is_some());
+            stats.meshes += chunk.meshes.iter().map(Vec::len).sum::<usize>();
+            stats.foreground += usize::from(chunk.foreground.is_some());
+            stats.overlay += usize::from(chunk.overlay.is_some());
+        }
+        stats
+    }
+
+    fn evict_distant_chunks(&mut self, min_cx: i32, max_cx: i32, min_cy: i32, max_cy: i32) {
+        if self.resident_chunks.len() <= MAX_RESIDENT_CHUNKS {
+            return;
+        }
+        let cols = self.chunk_cols;
+        let mut candidates: Vec<usize> = self
+            .resident_chunks
+            .iter()
+            .copied()
+            .filter(|&chunk_index| {
+                let cx = (chunk_index % cols) as i32;
+                let cy = (chunk_index / cols) as i32;
+                cx < min_cx || cx > max_cx || cy < min_cy || cy > max_cy
+            })
+            .collect();
+        candidates.sort_unstable_by_key(|&chunk_index| self.chunk_last_visible[chunk_index]);
+        let excess = self.resident_chunks.len() - MAX_RESIDENT_CHUNKS;
+        for &chunk_index in candidates.iter().take(excess) {
+            self.chunks[chunk_index] = None;
+            self.pending_dirty_background[chunk_index] = true;
+            self.pending_dirty_foreground[chunk_index] = true;
+            self.pending_dirty_overlay[chunk_index] = true;
+        }
+        let chunks = &self.chunks;
+        self.resident_chunks
+            .retain(|&chunk_index| chunks[chunk_index].is_some());
     }
 
     pub fn start_structure_apply(&mut self, defs: Vec<StructureDef>, seed: u32) {
         self.structure_interactors.clear();
+        self.structure_triggers.clear();
         self.structure_apply = Some(StructureApplyState::new(self, defs, seed));
     }
 
@@ -826,6 +1619,24 @@ impl TileMap {
         &self.structure_interactors
     }
 
+    /// Adds a trigger that isn't part of any structure, like one laid out in Tiled.
+    pub fn add_trigger(&mut self, trigger: PlacedTrigger) {
+        self.structure_triggers.push(trigger);
+    }
+
+    pub fn structure_triggers(&self) -> &[PlacedTrigger] {
+        &self.structure_triggers
+    }
+
+    pub fn world_bounds(&self) -> Rect {
+        Rect::new(
+            0.0,
+            0.0,
+            self.width as f32 * self.tile_size,
+            self.height as f32 * self.tile_size,
+        )
+    }
+
     pub fn get_border_hitbox(&self) -> Rect {
         if let Some(rect) = self.custom_border_hitbox {
             return rect;
@@ -844,6 +1655,39 @@ impl TileMap {
         self.custom_border_hitbox = rect;
     }
 
+    /// Palette structures placed from now on swap their tiles by, e.g. the season's.
+    pub fn set_structure_palette(&mut self, palette: Option<&str>) {
+        self.structure_palette = palette.map(str::to_string);
+    }
+
+    /// Color multiplied into every drawn tile, e.g. for seasonal lighting.
+    pub fn set_tint(&mut self, tint: Color) {
+        if self.mesh_chunks && self.tint != tint {
+            // Meshes carry the tint in their vertices, so they're rebuilt to change it.
+            let (width, height) = (self.width, self.height);
+            self.mark_chunks_dirty_rect(0, 0, width, height, true, true, true);
+        }
+        self.tint = tint;
+    }
+
+    /// Switches between baking chunks into meshes and into render targets, for this map and
+    /// the ones built after it. Switching throws the chunks away to be rebuilt the new way as
+    /// they come into view.
+    pub fn set_mesh_chunks(&mut self, on: bool) {
+        MESH_CHUNKS.with(|cell| cell.set(on));
+        if self.mesh_chunks == on {
+            return;
+        }
+        self.mesh_chunks = on;
+        for chunk in &mut self.chunks {
+            *chunk = None;
+        }
+        self.resident_chunks.clear();
+        self.pending_dirty_background.fill(true);
+        self.pending_dirty_foreground.fill(true);
+        self.pending_dirty_overlay.fill(true);
+    }
+
     pub fn draw_background(
         &mut self,
         tileset: &TileSet,
@@ -898,20 +1742,90 @@ impl TileMap {
         );
     }
 
-    pub fn place_structure(&mut self, structure: &Structure, x: usize, y: usize) {
-        if x >= self.width || y >= self.height || structure.is_empty() {
-            return;
-        }
+    /// Stamps decal `id` onto the ground at world `pos`; see `DecalLayer::stamp`.
+    pub fn stamp_decal(&mut self, decals: &DecalDatabase, id: &str, pos: Vec2, rotation: Option<f32>) {
+        self.decals.stamp(decals, id, pos, rotation);
+    }
 
-        if x + structure.width <= self.width && y + structure.height <= self.height {
-            self.place_structure_unchecked(structure, x, y);
-            return;
-        }
+    pub fn update_decals(&mut self, dt: f32) {
+        self.decals.update(dt);
+    }
 
-        let max_x = (x + structure.width).min(self.width);
-        let max_y = (y + structure.height).min(self.height);
-        let mut collision_changed = false;
-        let mut bg_changed = false;
+    /// Draws the decals in `view`, with the world camera active, between the background and
+    /// foreground passes.
+    pub fn draw_decals(&mut self, view: Rect) {
+        self.decals.draw(view, self.tint);
+    }
+
+    /// Map debug view, drawn with the world camera active: solid tiles in `view` tinted red,
+    /// chunk borders across the visible chunk range, and chunks colored by rebuild state. Gray
+    /// chunks have no render targets yet, orange ones wait for a rebuild and green ones were
+    /// rebuilt this frame.
+    pub fn draw_debug(&self, camera_target: Vec2, camera_zoom: Vec2, view: Rect) {
+        let ts = self.tile_size;
+        let min_x = (view.x / ts).floor().max(0.0) as usize;
+        let min_y = (view.y / ts).floor().max(0.0) as usize;
+        let max_x = (((view.x + view.w) / ts).ceil().max(0.0) as usize).min(self.width);
+        let max_y = (((view.y + view.h) / ts).ceil().max(0.0) as usize).min(self.height);
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                if self.is_solid(x, y) {
+                    draw_rectangle(x as f32 * ts, y as f32 * ts, ts, ts, Color::new(1.0, 0.15, 0.15, 0.35));
+                }
+            }
+        }
+        for placed in self.placed_structures_in(view) {
+            let (x, y) = (placed.x as f32 * ts, placed.y as f32 * ts);
+            let (w, h) = (placed.width as f32 * ts, placed.height as f32 * ts);
+            draw_rectangle_lines(x, y, w, h, 1.0, Color::new(0.4, 0.8, 1.0, 0.6));
+        }
+
+        let (min_cx, max_cx, min_cy, max_cy) = self.visible_chunk_range(camera_target, camera_zoom);
+        let size = self.chunk_pixel_size;
+        for cy in min_cy..=max_cy {
+            for cx in min_cx..=max_cx {
+                let chunk_index = self.chunk_index(cx as usize, cy as usize);
+                let (x, y) = (cx as f32 * size, cy as f32 * size);
+                let fill = if self.chunks[chunk_index].is_none() {
+                    Color::new(0.5, 0.5, 0.5, 0.2)
+                } else if self.chunk_rebuilt_frame[chunk_index] == self.chunk_frame {
+                    Color::new(0.3, 1.0, 0.3, 0.25)
+                } else if self.chunk_has_dirty_layer(chunk_index) {
+                    Color::new(1.0, 0.6, 0.1, 0.25)
+                } else {
+                    BLANK
+                };
+                draw_rectangle(x, y, size, size, fill);
+                draw_rectangle_lines(x, y, size, size, 1.0, Color::new(1.0, 1.0, 1.0, 0.4));
+                crate::font::draw_text(&format!("{cx},{cy}"), x + 2.0, y + 9.0, 8.0, WHITE);
+            }
+        }
+        let range_x = min_cx as f32 * size;
+        let range_y = min_cy as f32 * size;
+        draw_rectangle_lines(
+            range_x,
+            range_y,
+            (max_cx - min_cx + 1) as f32 * size,
+            (max_cy - min_cy + 1) as f32 * size,
+            2.0,
+            YELLOW,
+        );
+    }
+
+    pub fn place_structure(&mut self, structure: &Structure, x: usize, y: usize) {
+        if x >= self.width || y >= self.height || structure.is_empty() {
+            return;
+        }
+
+        if x + structure.width <= self.width && y + structure.height <= self.height {
+            self.place_structure_unchecked(structure, x, y);
+            return;
+        }
+
+        let max_x = (x + structure.width).min(self.width);
+        let max_y = (y + structure.height).min(self.height);
+        let mut collision_changed = false;
+        let mut bg_changed = false;
         let mut fg_changed = false;
         let mut ov_changed = false;
 
@@ -921,9 +1835,7 @@ impl TileMap {
             if tx >= max_x || ty >= max_y {
                 continue;
             }
-            let idx = self.idx(tx, ty);
-            if self.background[idx] != tile {
-                self.background[idx] = tile;
+            if self.background.set(tx, ty, tile) {
                 bg_changed = true;
             }
         }
@@ -933,9 +1845,7 @@ impl TileMap {
             if tx >= max_x || ty >= max_y {
                 continue;
             }
-            let idx = self.idx(tx, ty);
-            if self.foreground[idx] != tile {
-                self.foreground[idx] = tile;
+            if self.foreground.set(tx, ty, tile) {
                 fg_changed = true;
             }
         }
@@ -945,9 +1855,7 @@ impl TileMap {
             if tx >= max_x || ty >= max_y {
                 continue;
             }
-            let idx = self.idx(tx, ty);
-            if self.overlay[idx] != tile {
-                self.overlay[idx] = tile;
+            if self.overlay.set(tx, ty, tile) {
                 ov_changed = true;
             }
         }
@@ -957,11 +1865,7 @@ impl TileMap {
             if tx >= max_x || ty >= max_y {
                 continue;
             }
-            let idx = self.idx(tx, ty);
-            let next_mask = mask & 0x0F;
-            if self.collision_mask[idx] != next_mask {
-                self.collision_mask[idx] = next_mask;
-                self.solid[idx] = next_mask != 0;
+            if self.collision_mask.set(tx, ty, mask & 0x0F) {
                 collision_changed = true;
             }
         }
@@ -984,8 +1888,157 @@ impl TileMap {
     }
 
     pub fn place_structure_def(&mut self, def: &StructureDef, x: usize, y: usize) {
-        self.place_structure(&def.structure, x, y);
+        let structure = def.structure_at(x, y, self.structure_palette.as_deref());
+        self.place_structure(&structure, x, y);
         self.register_structure_interactors(def, x, y);
+        self.register_sorted_structure(def, x, y);
+        self.register_breakable_structure(def, x, y);
+        self.index_structure(def, x, y);
+    }
+
+    /// Adds `def` at (`x`, `y`) to the structure index without touching tiles, for structures
+    /// restored from a snapshot. Replaces whatever was indexed with the same footprint.
+    pub fn index_structure(&mut self, def: &StructureDef, x: usize, y: usize) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        self.index_placed(PlacedStructure {
+            structure_id: def.id.clone(),
+            x,
+            y,
+            width: def.structure.width.min(self.width - x),
+            height: def.structure.height.min(self.height - y),
+        });
+    }
+
+    fn index_placed(&mut self, placed: PlacedStructure) {
+        if placed.width == 0
+            || placed.height == 0
+            || placed.x + placed.width > self.width
+            || placed.y + placed.height > self.height
+        {
+            return;
+        }
+        let same = |other: &PlacedStructure| {
+            (other.x, other.y, other.width, other.height) == (placed.x, placed.y, placed.width, placed.height)
+        };
+        if let Some(other) = self.placed_structures.iter_mut().find(|other| same(other)) {
+            *other = placed;
+            return;
+        }
+        let index = self.placed_structures.len();
+        for chunk_index in self.chunks_touching(placed.x, placed.y, placed.width, placed.height) {
+            self.placed_by_chunk[chunk_index].push(index);
+        }
+        self.placed_structures.push(placed);
+    }
+
+    /// Drops indexed structures whose footprints lie inside the given tile rect.
+    fn unindex_structures_in(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        let before = self.placed_structures.len();
+        self.placed_structures.retain(|placed| {
+            placed.x < x
+                || placed.y < y
+                || placed.x + placed.width > x + width
+                || placed.y + placed.height > y + height
+        });
+        if self.placed_structures.len() != before {
+            self.rebuild_placed_index();
+        }
+    }
+
+    fn rebuild_placed_index(&mut self) {
+        for bucket in &mut self.placed_by_chunk {
+            bucket.clear();
+        }
+        for index in 0..self.placed_structures.len() {
+            let placed = &self.placed_structures[index];
+            for chunk_index in self.chunks_touching(placed.x, placed.y, placed.width, placed.height) {
+                self.placed_by_chunk[chunk_index].push(index);
+            }
+        }
+    }
+
+    /// Indices of the chunks a tile rect touches, clamped to the map.
+    fn chunks_touching(&self, x: usize, y: usize, width: usize, height: usize) -> Vec<usize> {
+        if self.width == 0 || self.height == 0 || width == 0 || height == 0 {
+            return Vec::new();
+        }
+        let start_cx = (x / CHUNK_SIZE).min(self.chunk_cols - 1);
+        let start_cy = (y / CHUNK_SIZE).min(self.chunk_rows - 1);
+        let end_cx = ((x + width - 1) / CHUNK_SIZE).min(self.chunk_cols - 1);
+        let end_cy = ((y + height - 1) / CHUNK_SIZE).min(self.chunk_rows - 1);
+        (start_cy..=end_cy)
+            .flat_map(|cy| (start_cx..=end_cx).map(move |cx| (cx, cy)))
+            .map(|(cx, cy)| self.chunk_index(cx, cy))
+            .collect()
+    }
+
+    /// Indexed structures whose footprints overlap the tile rect, each once.
+    fn placed_overlapping(&self, x: usize, y: usize, width: usize, height: usize) -> Vec<&PlacedStructure> {
+        let mut indices: Vec<usize> = self
+            .chunks_touching(x, y, width, height)
+            .into_iter()
+            .flat_map(|chunk_index| self.placed_by_chunk[chunk_index].iter().copied())
+            .filter(|&index| self.placed_structures[index].overlaps(x, y, width, height))
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices.into_iter().map(|index| &self.placed_structures[index]).collect()
+    }
+
+    /// Structures placed with their footprints overlapping `area`, in world space.
+    pub fn placed_structures_in(&self, area: Rect) -> Vec<&PlacedStructure> {
+        let ts = self.tile_size;
+        let (x, y) = ((area.x / ts).floor().max(0.0) as usize, (area.y / ts).floor().max(0.0) as usize);
+        let right = ((area.right() / ts).ceil().max(0.0) as usize).min(self.width);
+        let bottom = ((area.bottom() / ts).ceil().max(0.0) as usize).min(self.height);
+        self.placed_overlapping(x, y, right.saturating_sub(x), bottom.saturating_sub(y))
+    }
+
+    /// The structure standing on tile (`x`, `y`), if one was placed there.
+    pub fn placed_structure_at(&self, x: usize, y: usize) -> Option<&PlacedStructure> {
+        self.placed_overlapping(x, y, 1, 1).into_iter().next()
+    }
+
+    /// Whether `def` can go with its top-left tile at (`x`, `y`): inside the map, with nothing
+    /// solid or standing under its footprint and no placed structure overlapping it. Others
+    /// of its own kind also have to keep `min_distance` away, padded on both sides.
+    pub fn structure_site_clear(&self, def: &StructureDef, x: usize, y: usize) -> bool {
+        let (width, height) = (def.structure.width, def.structure.height);
+        if width == 0 || height == 0 || x + width > self.width || y + height > self.height {
+            return false;
+        }
+        let blocked = (y..y + height).any(|ty| {
+            (x..x + width).any(|tx| {
+                self.is_solid(tx, ty)
+                    || self.foreground.get(tx, ty) != EMPTY_TILE
+                    || self.overlay.get(tx, ty) != EMPTY_TILE
+            })
+        });
+        if blocked || !self.placed_overlapping(x, y, width, height).is_empty() {
+            return false;
+        }
+        let spacing = def.min_distance.max(0.0);
+        if spacing <= 0.0 {
+            return true;
+        }
+        let ts = self.tile_size;
+        let pad = |x: usize, y: usize, width: usize, height: usize| {
+            Rect::new(
+                x as f32 * ts - spacing,
+                y as f32 * ts - spacing,
+                width as f32 * ts + spacing * 2.0,
+                height as f32 * ts + spacing * 2.0,
+            )
+        };
+        let padded = pad(x, y, width, height);
+        let reach = (spacing * 2.0 / ts).ceil() as usize;
+        let (near_x, near_y) = (x.saturating_sub(reach), y.saturating_sub(reach));
+        let near = self.placed_overlapping(near_x, near_y, x + width + reach - near_x, y + height + reach - near_y);
+        !near.into_iter().any(|other| {
+            other.structure_id == def.id && pad(other.x, other.y, other.width, other.height).overlaps(&padded)
+        })
     }
 
     fn place_structure_unchecked(&mut self, structure: &Structure, x: usize, y: usize) {
@@ -995,32 +2048,22 @@ impl TileMap {
         let mut ov_changed = false;
 
         for &(sx, sy, tile) in structure.background_updates.iter() {
-            let idx = self.idx(x + sx, y + sy);
-            if self.background[idx] != tile {
-                self.background[idx] = tile;
+            if self.background.set(x + sx, y + sy, tile) {
                 bg_changed = true;
             }
         }
         for &(sx, sy, tile) in structure.foreground_updates.iter() {
-            let idx = self.idx(x + sx, y + sy);
-            if self.foreground[idx] != tile {
-                self.foreground[idx] = tile;
+            if self.foreground.set(x + sx, y + sy, tile) {
                 fg_changed = true;
             }
         }
         for &(sx, sy, tile) in structure.overlay_updates.iter() {
-            let idx = self.idx(x + sx, y + sy);
-            if self.overlay[idx] != tile {
-                self.overlay[idx] = tile;
+            if self.overlay.set(x + sx, y + sy, tile) {
                 ov_changed = true;
             }
         }
         for &(sx, sy, mask) in structure.collider_offsets.iter() {
-            let idx = self.idx(x + sx, y + sy);
-            let next_mask = mask & 0x0F;
-            if self.collision_mask[idx] != next_mask {
-                self.collision_mask[idx] = next_mask;
-                self.solid[idx] = next_mask != 0;
+            if self.collision_mask.set(x + sx, y + sy, mask & 0x0F) {
                 collision_changed = true;
             }
         }
@@ -1042,6 +2085,7 @@ impl TileMap {
 
     pub fn apply_structures(&mut self, defs: &[StructureDef], seed: u32) {
         self.structure_interactors.clear();
+        self.structure_triggers.clear();
         let mut occupied = vec![false; self.width * self.height];
         let mut placed_rects: Vec<Rect> = Vec::new();
 
@@ -1123,6 +2167,8 @@ impl TileMap {
 
                 self.place_structure_unchecked(&def.structure, x, y);
                 self.register_structure_interactors(def, x, y);
+                self.register_sorted_structure(def, x, y);
+                self.register_breakable_structure(def, x, y);
                 for &(sx, sy) in def.structure.occupied_offsets.iter() {
                     let idx = self.idx(x + sx, y + sy);
                     occupied[idx] = true;
@@ -1136,8 +2182,14 @@ impl TileMap {
         }
     }
 
-    fn register_structure_interactors(&mut self, def: &StructureDef, x: usize, y: usize) {
-        if def.structure.interactor_offsets.is_empty() || def.on_interact.is_empty() {
+    /// Registers `def`'s interactors at (`x`, `y`) without touching tiles, for structures
+    /// restored from a snapshot, which only records tiles.
+    pub fn register_structure_interactors(&mut self, def: &StructureDef, x: usize, y: usize) {
+        for (index, trigger) in def.triggers.iter().enumerate() {
+            let placed = PlacedTrigger::new(&def.id, index, x, y, trigger, self.tile_size);
+            self.structure_triggers.push(placed);
+        }
+        if def.structure.interactor_offsets.is_empty() {
             return;
         }
         let tile_size = self.tile_size;
@@ -1178,18 +2230,434 @@ impl TileMap {
                 rect,
                 group_rect: group,
                 on_interact: def.on_interact.clone(),
+                scene_transition: def.scene_transition.clone(),
+                interior: def.interior.clone(),
                 interact_range_world,
             });
         }
     }
 
+    fn register_sorted_structure(&mut self, def: &StructureDef, x: usize, y: usize) {
+        if !def.y_sort || x >= self.width || y >= self.height {
+            return;
+        }
+        let sorted = SortedStructure {
+            x,
+            y,
+            width: def.structure.width.min(self.width - x),
+            height: def.structure.height.min(self.height - y),
+            base_y: (y as f32 + def.structure.sort_base) * self.tile_size,
+        };
+        self.insert_sorted_structure(sorted);
+    }
+
+    fn insert_sorted_structure(&mut self, sorted: SortedStructure) {
+        if sorted.width == 0
+            || sorted.height == 0
+            || sorted.x + sorted.width > self.width
+            || sorted.y + sorted.height > self.height
+        {
+            return;
+        }
+        // Re-placing the same structure (e.g. the farm border over a restored save)
+        // reuses the existing instance.
+        let index = match self.sorted_structures.iter().position(|other| {
+            other.x == sorted.x
+                && other.y == sorted.y
+                && other.width == sorted.width
+                && other.height == sorted.height
+        }) {
+            Some(index) => {
+                self.sorted_structures[index] = sorted;
+                index
+            }
+            None => {
+                self.sorted_structures.push(sorted);
+                self.sorted_alpha.push(1.0);
+                let index = self.sorted_structures.len() - 1;
+                let start_cx = sorted.x / CHUNK_SIZE;
+                let start_cy = sorted.y / CHUNK_SIZE;
+                let end_cx = (sorted.x + sorted.width - 1) / CHUNK_SIZE;
+                let end_cy = (sorted.y + sorted.height - 1) / CHUNK_SIZE;
+                for cy in start_cy..=end_cy {
+                    for cx in start_cx..=end_cx {
+                        let chunk_index = self.chunk_index(cx, cy);
+                        self.sorted_by_chunk[chunk_index].push(index);
+                    }
+                }
+                index
+            }
+        };
+
+        for ty in sorted.y..sorted.y + sorted.height {
+            for tx in sorted.x..sorted.x + sorted.width {
+                if self.foreground.get(tx, ty) != EMPTY_TILE || self.overlay.get(tx, ty) != EMPTY_TILE {
+                    self.sorted_owner.set(tx, ty, index as u32);
+                }
+            }
+        }
+        self.mark_chunks_dirty_rect(
+            sorted.x,
+            sorted.y,
+            sorted.width,
+            sorted.height,
+            false,
+            true,
+            true,
+        );
+    }
+
+    fn clear_sorted_structures(&mut self) {
+        self.sorted_structures.clear();
+        self.sorted_alpha.clear();
+        self.sorted_fading.clear();
+        self.sorted_owner.fill(NO_SORTED_OWNER);
+        for bucket in self.sorted_by_chunk.iter_mut() {
+            bucket.clear();
+        }
+    }
+
+    fn register_breakable_structure(&mut self, def: &StructureDef, x: usize, y: usize) {
+        if def.hp <= 0.0 || x >= self.width || y >= self.height {
+            return;
+        }
+        let breakable = BreakableStructure {
+            x,
+            y,
+            width: def.structure.width.min(self.width - x),
+            height: def.structure.height.min(self.height - y),
+            hp: def.hp,
+            max_hp: def.hp,
+            structure_id: def.id.clone(),
+            hit_only: def.hit_only,
+            enemy_only: def.build_cost.is_some(),
+        };
+        match self
+            .breakable_structures
+            .iter_mut()
+            .find(|other| other.x == x && other.y == y)
+        {
+            Some(other) => *other = breakable,
+            None => self.breakable_structures.push(breakable),
+        }
+    }
+
+    fn breakable_structure_rect(&self, breakable: &BreakableStructure) -> Rect {
+        Rect::new(
+            breakable.x as f32 * self.tile_size,
+            breakable.y as f32 * self.tile_size,
+            breakable.width as f32 * self.tile_size,
+            breakable.height as f32 * self.tile_size,
+        )
+    }
+
+    /// Footprints of every breakable structure still standing that tools can break.
+    pub fn breakable_structure_rects(&self) -> impl Iterator<Item = Rect> + '_ {
+        self.breakable_structures
+            .iter()
+            .filter(|breakable| breakable.takes(StructureHit::Tool))
+            .map(|breakable| self.breakable_structure_rect(breakable))
+    }
+
+    /// Whether a hit of kind `hit` over `area` would land on any breakable structure.
+    pub fn overlaps_breakable_structure(&self, area: Rect, hit: StructureHit) -> bool {
+        self.breakable_structures.iter().any(|breakable| {
+            breakable.takes(hit) && self.breakable_structure_rect(breakable).overlaps(&area)
+        })
+    }
+
+    /// Damages every breakable structure overlapping `area`. Crossing half hp swaps in the
+    /// def's damaged tiles; reaching zero clears foreground, overlay and colliders while
+    /// keeping the ground beneath. Returns the structures that broke.
+    pub fn damage_structures(
+        &mut self,
+        area: Rect,
+        amount: f32,
+        hit: StructureHit,
+        structures: &[StructureDef],
+    ) -> Vec<BrokenStructure> {
+        let mut broken = Vec::new();
+        if amount <= 0.0 {
+            return broken;
+        }
+        let mut breakables = std::mem::take(&mut self.breakable_structures);
+        breakables.retain_mut(|breakable| {
+            let rect = self.breakable_structure_rect(breakable);
+            if !breakable.takes(hit) || !rect.overlaps(&area) {
+                return true;
+            }
+            let half = breakable.max_hp * 0.5;
+            let was_intact = breakable.hp > half;
+            breakable.hp -= amount;
+            if breakable.hp > 0.0 {
+                if was_intact
+                    && breakable.hp <= half
+                    && let Some(def) = structures.iter().find(|def| def.id == breakable.structure_id)
+                {
+                    self.swap_damaged_tiles(breakable, def);
+                }
+                return true;
+            }
+            self.clear_structure_tiles(breakable.x, breakable.y, breakable.width, breakable.height);
+            broken.push(BrokenStructure {
+                structure_id: std::mem::take(&mut breakable.structure_id),
+                rect,
+            });
+            false
+        });
+        self.breakable_structures = breakables;
+        if !broken.is_empty() {
+            self.structure_interactors.retain(|interactor| {
+                !broken
+                    .iter()
+                    .any(|broken| broken.rect.overlaps(&interactor.group_rect))
+            });
+            self.structure_triggers
+                .retain(|trigger| !broken.iter().any(|broken| broken.rect.contains(trigger.rect.center())));
+        }
+        broken
+    }
+
+    /// Makes the breakable structure placed at (`x`, `y`) take damage only from enemies.
+    pub fn mark_enemy_only(&mut self, x: usize, y: usize) {
+        if let Some(breakable) = self.breakable_structures.iter_mut().find(|other| other.x == x && other.y == y) {
+            breakable.enemy_only = true;
+        }
+    }
+
+    /// The damaged breakable structure overlapping `area` that has a repair cost, if any.
+    pub fn repairable_structure<'a>(
+        &self,
+        area: Rect,
+        structures: &'a [StructureDef],
+    ) -> Option<(&BreakableStructure, &'a StructureDef)> {
+        self.breakable_structures.iter().find_map(|breakable| {
+            if !breakable.is_damaged() || !self.breakable_structure_rect(breakable).overlaps(&area) {
+                return None;
+            }
+            let def = structures.iter().find(|def| def.id == breakable.structure_id)?;
+            (!def.repair_cost.is_empty()).then_some((breakable, def))
+        })
+    }
+
+    /// Brings the structure `repairable_structure` finds over `area` back to full hp and its
+    /// intact tiles. Returns whether there was one.
+    pub fn repair_structure(&mut self, area: Rect, structures: &[StructureDef]) -> bool {
+        let Some((breakable, def)) = self.repairable_structure(area, structures) else {
+            return false;
+        };
+        let (x, y) = (breakable.x, breakable.y);
+        let structure = def.structure_at(x, y, self.structure_palette.as_deref());
+        self.place_structure(&structure, x, y);
+        if let Some(breakable) = self.breakable_structures.iter_mut().find(|other| other.x == x && other.y == y) {
+            breakable.hp = breakable.max_hp;
+        }
+        true
+    }
+
+    /// Breakable structures below full hp, with their footprints.
+    pub fn damaged_structures(&self) -> impl Iterator<Item = (&BreakableStructure, Rect)> + '_ {
+        self.breakable_structures
+            .iter()
+            .filter(|breakable| breakable.is_damaged())
+            .map(|breakable| (breakable, self.breakable_structure_rect(breakable)))
+    }
+
+    /// Draws a health bar over each damaged structure in `view`, in world space.
+    pub fn draw_structure_health(&self, view: Rect) {
+        for (breakable, rect) in self.damaged_structures().filter(|(_, rect)| rect.overlaps(&view)) {
+            let filled = (breakable.hp / breakable.max_hp.max(f32::EPSILON)).clamp(0.0, 1.0);
+            let bar = Rect::new(rect.x + 2.0, rect.y - 4.0, rect.w - 4.0, 2.0);
+            draw_rectangle(bar.x - 1.0, bar.y - 1.0, bar.w + 2.0, bar.h + 2.0, Color::new(0.0, 0.0, 0.0, 0.6));
+            draw_rectangle(bar.x, bar.y, bar.w * filled, bar.h, Color::new(0.9, 0.55, 0.2, 1.0));
+        }
+    }
+
+    /// Clears breakable structures centered inside any of `areas` without dropping anything,
+    /// for resources already harvested from an earlier copy of a regenerated map.
+    pub fn remove_breakable_structures(&mut self, areas: &[Rect]) {
+        if areas.is_empty() {
+            return;
+        }
+        let mut breakables = std::mem::take(&mut self.breakable_structures);
+        let mut removed = Vec::new();
+        breakables.retain(|breakable| {
+            let rect = self.breakable_structure_rect(breakable);
+            if !areas.iter().any(|area| area.contains(rect.center())) {
+                return true;
+            }
+            self.clear_structure_tiles(breakable.x, breakable.y, breakable.width, breakable.height);
+            removed.push(rect);
+            false
+        });
+        self.breakable_structures = breakables;
+        self.structure_interactors
+            .retain(|interactor| !removed.iter().any(|rect| rect.overlaps(&interactor.group_rect)));
+        self.structure_triggers
+            .retain(|trigger| !removed.iter().any(|rect| rect.contains(trigger.rect.center())));
+    }
+
+    fn swap_damaged_tiles(&mut self, breakable: &BreakableStructure, def: &StructureDef) {
+        let Some(damaged) = def.damaged.as_ref() else {
+            return;
+        };
+        let stride = def.structure.width;
+        for ty in 0..breakable.height {
+            for tx in 0..breakable.width {
+                let i = ty * stride + tx;
+                let (x, y) = (breakable.x + tx, breakable.y + ty);
+                let palette = self.structure_palette.as_deref();
+                let tile_at = |tiles: &Option<Vec<u8>>| {
+                    let tile = *tiles.as_ref()?.get(i)?;
+                    Some(if tile == 0 { EMPTY_TILE } else { def.palette_tile(palette, tile) })
+                };
+                if let Some(tile) = tile_at(&damaged.foreground) {
+                    self.foreground.set(x, y, tile);
+                }
+                if let Some(tile) = tile_at(&damaged.overlay) {
+                    self.overlay.set(x, y, tile);
+                }
+            }
+        }
+        self.mark_chunks_dirty_rect(breakable.x, breakable.y, breakable.width, breakable.height, false, true, true);
+    }
+
+    fn clear_structure_tiles(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        self.unindex_structures_in(x, y, width, height);
+        for ty in y..y + height {
+            for tx in x..x + width {
+                self.foreground.set(tx, ty, EMPTY_TILE);
+                self.overlay.set(tx, ty, EMPTY_TILE);
+                self.sorted_owner.set(tx, ty, NO_SORTED_OWNER);
+                if self.collision_mask.set(tx, ty, 0) {
+                    self.collision_dirty = true;
+                }
+            }
+        }
+        self.mark_chunks_dirty_rect(x, y, width, height, false, true, true);
+    }
+
+    pub fn sorted_structure(&self, index: usize) -> Option<&SortedStructure> {
+        self.sorted_structures.get(index)
+    }
+
+    /// Collects depth-sorted structures whose footprint overlaps `view`.
+    pub fn visible_sorted_structures(&self, view: Rect, out: &mut Vec<usize>) {
+        out.clear();
+        if self.sorted_structures.is_empty() {
+            return;
+        }
+        let chunk_px = self.chunk_pixel_size.max(1.0);
+        let max_cx = self.chunk_cols as i32 - 1;
+        let max_cy = self.chunk_rows as i32 - 1;
+        let min_cx = ((view.x / chunk_px).floor() as i32).clamp(0, max_cx);
+        let min_cy = ((view.y / chunk_px).floor() as i32).clamp(0, max_cy);
+        let end_cx = (((view.x + view.w) / chunk_px).floor() as i32).clamp(0, max_cx);
+        let end_cy = (((view.y + view.h) / chunk_px).floor() as i32).clamp(0, max_cy);
+        for cy in min_cy..=end_cy {
+            for cx in min_cx..=end_cx {
+                let chunk_index = self.chunk_index(cx as usize, cy as usize);
+                for &index in &self.sorted_by_chunk[chunk_index] {
+                    if self.sorted_structure_rect(index).overlaps(&view) {
+                        out.push(index);
+                    }
+                }
+            }
+        }
+        out.sort_unstable();
+        out.dedup();
+    }
+
+    pub fn sorted_structure_alpha(&self, index: usize) -> f32 {
+        self.sorted_alpha.get(index).copied().unwrap_or(1.0)
+    }
+
+    /// Fades depth-sorted structures that draw over `subject` (a sprite whose feet are
+    /// at `feet_y`) towards half alpha, and restores the rest.
+    pub fn update_sorted_occlusion(&mut self, subject: Rect, feet_y: f32, dt: f32) {
+        let mut near = std::mem::take(&mut self.sorted_scratch);
+        self.visible_sorted_structures(subject, &mut near);
+        near.retain(|&index| self.sorted_structure_occludes(index, subject, feet_y));
+        for &index in &near {
+            if !self.sorted_fading.contains(&index) {
+                self.sorted_fading.push(index);
+            }
+        }
+
+        let t = 1.0 - (-OCCLUSION_FADE_SPEED * dt).exp();
+        let alphas = &mut self.sorted_alpha;
+        self.sorted_fading.retain(|&index| {
+            let occluding = near.contains(&index);
+            let target = if occluding { OCCLUDED_ALPHA } else { 1.0 };
+            let alpha = &mut alphas[index];
+            *alpha += (target - *alpha) * t;
+            if !occluding && *alpha > 0.99 {
+                *alpha = 1.0;
+                return false;
+            }
+            true
+        });
+        self.sorted_scratch = near;
+    }
+
+    fn sorted_structure_occludes(&self, index: usize, subject: Rect, feet_y: f32) -> bool {
+        let Some(sorted) = self.sorted_structures.get(index) else {
+            return false;
+        };
+        if sorted.base_y <= feet_y {
+            return false;
+        }
+        for ty in sorted.y..sorted.y + sorted.height {
+            for tx in sorted.x..sorted.x + sorted.width {
+                if self.sorted_owner.get(tx, ty) == index as u32
+                    && self.tile_bounds(tx, ty).overlaps(&subject)
+                {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    pub fn sorted_structure_rect(&self, index: usize) -> Rect {
+        let Some(sorted) = self.sorted_structures.get(index) else {
+            return Rect::new(0.0, 0.0, 0.0, 0.0);
+        };
+        Rect::new(
+            sorted.x as f32 * self.tile_size,
+            sorted.y as f32 * self.tile_size,
+            sorted.width as f32 * self.tile_size,
+            sorted.height as f32 * self.tile_size,
+        )
+    }
+
+    pub fn draw_sorted_structure(&self, index: usize, tileset: &TileSet, alpha: f32) {
+        let Some(sorted) = self.sorted_structures.get(index) else {
+            return;
+        };
+        let color = Color::new(self.tint.r, self.tint.g, self.tint.b, alpha.clamp(0.0, 1.0));
+        for layer in [LayerKind::Foreground, LayerKind::Overlay] {
+            for ty in sorted.y..sorted.y + sorted.height {
+                for tx in sorted.x..sorted.x + sorted.width {
+                    if self.sorted_owner.get(tx, ty) != index as u32 {
+                        continue;
+                    }
+                    let pos = vec2(tx as f32, ty as f32) * self.tile_size;
+                    let tile = self.get_tile(layer, tx, ty);
+                    let orientation = self.tile_orientation(layer, tx, ty, tile);
+                    tileset.draw_oriented_tile(tile, pos, self.tile_size, color, orientation);
+                }
+            }
+        }
+    }
+
     pub fn fill_layer(&mut self, layer: LayerKind, id: u8) {
         let tiles = match layer {
             LayerKind::Background => &mut self.background,
             LayerKind::Foreground => &mut self.foreground,
             LayerKind::Overlay => &mut self.overlay,
         };
-        if tiles.iter().all(|&tile| tile == id) {
+        if tiles.all(id) {
             return;
         }
         tiles.fill(id);
@@ -1215,38 +2683,63 @@ impl TileMap {
     }
 
     pub fn set_tile(&mut self, layer: LayerKind, x: usize, y: usize, id: u8) {
-        let i = self.idx(x, y);
-        let old = match layer {
-            LayerKind::Background => self.background[i],
-            LayerKind::Foreground => self.foreground[i],
-            LayerKind::Overlay => self.overlay[i],
+        let changed = match layer {
+            LayerKind::Background => self.background.set(x, y, id),
+            LayerKind::Foreground => self.foreground.set(x, y, id),
+            LayerKind::Overlay => self.overlay.set(x, y, id),
         };
-        if old == id {
+        if changed {
+            self.mark_chunk_dirty(x, y, layer);
+            if let Some(log) = self.tile_log.as_mut() {
+                log.push((layer, x, y, id));
+            }
+        }
+    }
+
+    /// Lays the tile at (`x`, `y`) on `layer` as `orientation` says, for as long as it's there.
+    pub fn set_tile_orientation(&mut self, layer: LayerKind, x: usize, y: usize, orientation: TileOrientation) {
+        if x >= self.width || y >= self.height {
             return;
         }
-        match layer {
-            LayerKind::Background => self.background[i] = id,
-            LayerKind::Foreground => self.foreground[i] = id,
-            LayerKind::Overlay => self.overlay[i] = id,
+        let key = (layer, x, y);
+        let tile = self.get_tile(layer, x, y);
+        let changed = if orientation == TileOrientation::default() {
+            self.orientations.remove(&key).is_some()
+        } else {
+            self.orientations.insert(key, (tile, orientation)) != Some((tile, orientation))
+        };
+        if changed {
+            self.mark_chunk_dirty(x, y, layer);
+        }
+    }
+
+    fn tile_orientation(&self, layer: LayerKind, x: usize, y: usize, tile: u8) -> TileOrientation {
+        match self.orientations.get(&(layer, x, y)) {
+            Some(&(laid, orientation)) if laid == tile => orientation,
+            _ => TileOrientation::default(),
         }
-        self.mark_chunk_dirty(x, y, layer);
+    }
+
+    /// Starts keeping the tiles `set_tile` changes, for `take_tile_changes`.
+    pub fn record_tile_changes(&mut self) {
+        self.tile_log.get_or_insert_with(Vec::new);
+    }
+
+    /// The tiles changed since last asked, oldest first.
+    pub fn take_tile_changes(&mut self) -> Vec<(LayerKind, usize, usize, u8)> {
+        self.tile_log.as_mut().map(std::mem::take).unwrap_or_default()
     }
 
     pub fn set_collision(&mut self, x: usize, y: usize, solid: bool) {
         if x >= self.width || y >= self.height {
             return;
         }
-        let i = self.idx(x, y);
-        let next_mask = if solid { 0x0F } else { 0 };
-        if self.solid[i] != solid || self.collision_mask[i] != next_mask {
-            self.solid[i] = solid;
-            self.collision_mask[i] = next_mask;
+        if self.collision_mask.set(x, y, if solid { 0x0F } else { 0 }) {
             self.collision_dirty = true;
         }
     }
 
     pub fn fill_collision(&mut self, solid: bool) {
-        self.solid.fill(solid);
         self.collision_mask.fill(if solid { 0x0F } else { 0 });
         self.collision_dirty = true;
     }
@@ -1255,7 +2748,7 @@ impl TileMap {
         if x >= self.width || y >= self.height {
             return false;
         }
-        self.solid[self.idx(x, y)]
+        self.collision_mask.get(x, y) & 0x0F != 0
     }
 
     pub fn set_collision_from_layer(&mut self, layer: LayerKind, solid_ids: &[u8]) {
@@ -1278,9 +2771,7 @@ impl TileMap {
                 let solid = tile != EMPTY_TILE
                     && (tile as usize) < lookup.len()
                     && lookup[tile as usize];
-                let idx = self.idx(x, y);
-                self.solid[idx] = solid;
-                self.collision_mask[idx] = if solid { 0x0F } else { 0 };
+                self.collision_mask.set(x, y, if solid { 0x0F } else { 0 });
             }
         }
 
@@ -1346,6 +2837,7 @@ impl TileMap {
                 }
                 self.rebuild_chunk_layer_if_dirty(chunk_index, layer, tileset);
                 self.draw_chunk_layer(chunk_index, layer, cx as usize, cy as usize);
+                self.draw_animated_tiles(chunk_index, layer, tileset);
             }
         }
     }
@@ -1381,34 +2873,52 @@ impl TileMap {
         layer: LayerKind,
         tileset: &TileSet,
     ) {
-        if self.chunks.get(chunk_index).and_then(|c| c.as_ref()).is_none() {
+        if !self.chunk_layer_dirty(chunk_index, layer) {
             return;
         }
-        let is_dirty = match layer {
-            LayerKind::Background => self.chunks[chunk_index].as_ref().map(|c| c.dirty_background).unwrap_or(false),
-            LayerKind::Foreground => self.chunks[chunk_index].as_ref().map(|c| c.dirty_foreground).unwrap_or(false),
-            LayerKind::Overlay => self.chunks[chunk_index].as_ref().map(|c| c.dirty_overlay).unwrap_or(false),
-        };
-
-        if !is_dirty {
+        // Coalesce: a chunk already rebuilt this frame keeps its current contents
+        // until next frame instead of paying for a second render target pass.
+        if self.chunk_rebuilt_frame[chunk_index] == self.chunk_frame
+            && self.chunk_layer_ready(chunk_index, layer)
+        {
             return;
         }
         if self.chunk_rebuilds_this_frame >= self.chunk_rebuild_budget_per_frame {
             return;
         }
 
-        let target = match layer {
-            LayerKind::Background => self.chunks[chunk_index].as_ref().map(|c| c.background.clone()),
-            LayerKind::Foreground => self.chunks[chunk_index].as_ref().map(|c| c.foreground.clone()),
-            LayerKind::Overlay => self.chunks[chunk_index].as_ref().map(|c| c.overlay.clone()),
-        };
-        let Some(target) = target else {
+        self.render_dirty_chunk_layer(chunk_index, layer, tileset);
+        self.chunk_rebuilds_this_frame += 1;
+        self.chunk_rebuilt_frame[chunk_index] = self.chunk_frame;
+    }
+
+    fn render_dirty_chunk_layer(&mut self, chunk_index: usize, layer: LayerKind, tileset: &TileSet) {
+        if self.chunks[chunk_index].is_none() {
             return;
+        }
+        if matches!(layer, LayerKind::Overlay) && !self.mesh_chunks {
+            self.fit_overlay_target(chunk_index);
+        }
+        let over = (self.composite_layers && matches!(layer, LayerKind::Background)).then_some(LayerKind::Foreground);
+        let (animated, animated_over) = if self.mesh_chunks {
+            let mut meshes = Vec::new();
+            let animated = self.mesh_chunk_layer(&mut meshes, chunk_index, layer, over, tileset);
+            if let Some(chunk) = self.chunks[chunk_index].as_mut() {
+                chunk.meshes[layer as usize] = meshes;
+            }
+            animated
+        } else {
+            let target = match layer {
+                LayerKind::Background => self.chunks[chunk_index].as_ref().and_then(|c| c.background.clone()),
+                LayerKind::Foreground => self.chunks[chunk_index].as_ref().and_then(|c| c.foreground.clone()),
+                LayerKind::Overlay => self.chunks[chunk_index].as_ref().and_then(|c| c.overlay.clone()),
+            };
+            match target {
+                Some(target) => self.render_chunk_layer(target, chunk_index, layer, over, tileset),
+                None => (Vec::new(), Vec::new()),
+            }
         };
 
-        self.render_chunk_layer(target, chunk_index, layer, tileset);
-        self.chunk_rebuilds_this_frame += 1;
-
         let Some(chunk) = self.chunks[chunk_index].as_mut() else {
             return;
         };
@@ -1416,33 +2926,98 @@ impl TileMap {
             LayerKind::Background => {
                 chunk.dirty_background = false;
                 chunk.ready_background = true;
+                chunk.animated_background = animated;
+                if over.is_some() {
+                    chunk.dirty_foreground = false;
+                    chunk.ready_foreground = true;
+                    chunk.animated_foreground = animated_over;
+                }
             }
             LayerKind::Foreground => {
                 chunk.dirty_foreground = false;
                 chunk.ready_foreground = true;
+                chunk.animated_foreground = animated;
             }
             LayerKind::Overlay => {
                 chunk.dirty_overlay = false;
                 chunk.ready_overlay = true;
+                chunk.animated_overlay = animated;
             }
         }
     }
 
+    /// Gives the chunk an overlay target if it has overlay tiles to bake, and hands it back
+    /// to the pool if it no longer does.
+    fn fit_overlay_target(&mut self, chunk_index: usize) {
+        let needed = self.chunk_has_tiles(chunk_index, LayerKind::Overlay);
+        let size = self.chunk_pixel_size.round().max(1.0) as u32;
+        let Some(chunk) = self.chunks[chunk_index].as_mut() else {
+            return;
+        };
+        if needed && chunk.overlay.is_none() {
+            chunk.overlay = Some(chunk_target(size));
+        } else if !needed && let Some(target) = chunk.overlay.take() {
+            release_chunk_target(&target);
+        }
+    }
+
+    fn chunk_has_tiles(&self, chunk_index: usize, layer: LayerKind) -> bool {
+        let (origin_x, origin_y) = self.chunk_origin(chunk_index);
+        let (max_x, max_y) = ((origin_x + CHUNK_SIZE).min(self.width), (origin_y + CHUNK_SIZE).min(self.height));
+        (origin_y..max_y).any(|ty| (origin_x..max_x).any(|tx| self.bakeable_tile(layer, tx, ty).is_some()))
+    }
+
+    /// The tile a chunk bakes at (`x`, `y`) on `layer`: none where it's empty, or where a
+    /// sorted structure draws it instead.
+    fn bakeable_tile(&self, layer: LayerKind, x: usize, y: usize) -> Option<u8> {
+        if !matches!(layer, LayerKind::Background) && self.sorted_owner.get(x, y) != NO_SORTED_OWNER {
+            return None;
+        }
+        let tile = self.get_tile(layer, x, y);
+        (tile != EMPTY_TILE).then_some(tile)
+    }
+
+    fn chunk_layer_dirty(&self, chunk_index: usize, layer: LayerKind) -> bool {
+        let Some(chunk) = self.chunks.get(chunk_index).and_then(|c| c.as_ref()) else {
+            return false;
+        };
+        match layer {
+            LayerKind::Background if self.composite_layers => chunk.dirty_background || chunk.dirty_foreground,
+            LayerKind::Foreground if self.composite_layers => false,
+            LayerKind::Background => chunk.dirty_background,
+            LayerKind::Foreground => chunk.dirty_foreground,
+            LayerKind::Overlay => chunk.dirty_overlay,
+        }
+    }
+
+    fn chunk_layer_ready(&self, chunk_index: usize, layer: LayerKind) -> bool {
+        let Some(chunk) = self.chunks.get(chunk_index).and_then(|c| c.as_ref()) else {
+            return false;
+        };
+        match layer {
+            LayerKind::Background => chunk.ready_background,
+            LayerKind::Foreground => chunk.ready_foreground,
+            LayerKind::Overlay => chunk.ready_overlay,
+        }
+    }
+
+    fn chunk_has_dirty_layer(&self, chunk_index: usize) -> bool {
+        self.chunk_layer_dirty(chunk_index, LayerKind::Background)
+            || self.chunk_layer_dirty(chunk_index, LayerKind::Foreground)
+            || self.chunk_layer_dirty(chunk_index, LayerKind::Overlay)
+    }
+
+    /// Bakes the chunk's static tiles into `target` and returns where its animated ones are.
+    /// With `over`, that layer is baked on top in the same pass; its tiles over animated ones
+    /// are left out too and returned second, to be drawn live along with them.
     fn render_chunk_layer(
         &self,
         target: RenderTarget,
         chunk_index: usize,
         layer: LayerKind,
+        over: Option<LayerKind>,
         tileset: &TileSet,
-    ) {
-        let chunk_x = chunk_index % self.chunk_cols;
-        let chunk_y = chunk_index / self.chunk_cols;
-
-        let origin_x = chunk_x * CHUNK_SIZE;
-        let origin_y = chunk_y * CHUNK_SIZE;
-        let max_x = (origin_x + CHUNK_SIZE).min(self.width);
-        let max_y = (origin_y + CHUNK_SIZE).min(self.height);
-
+    ) -> (AnimatedTiles, AnimatedTiles) {
         let mut cam = Camera2D::from_display_rect(Rect::new(
             0.0,
             0.0,
@@ -1455,31 +3030,88 @@ impl TileMap {
         set_camera(&cam);
         clear_background(Color::new(0.0, 0.0, 0.0, 0.0));
 
-        let dest = Some(vec2(self.tile_size, self.tile_size));
+        let origin = self.chunk_origin(chunk_index);
+        let animated = self.bake_chunk_layer(chunk_index, layer, over, tileset, |tile, (tx, ty), orientation| {
+            let local = vec2((tx - origin.0) as f32, (ty - origin.1) as f32) * self.tile_size;
+            tileset.draw_tile_in_cell(tile, (tx as i32, ty as i32), local, self.tile_size, WHITE, orientation);
+        });
+
+        pop_camera_state();
+        animated
+    }
+
+    /// Like `render_chunk_layer`, but builds the static tiles into `meshes` in world space,
+    /// tinted, instead of drawing them.
+    fn mesh_chunk_layer(
+        &self,
+        meshes: &mut Vec<Mesh>,
+        chunk_index: usize,
+        layer: LayerKind,
+        over: Option<LayerKind>,
+        tileset: &TileSet,
+    ) -> (AnimatedTiles, AnimatedTiles) {
+        let animated = self.bake_chunk_layer(chunk_index, layer, over, tileset, |tile, (tx, ty), orientation| {
+            if meshes.last().is_none_or(|mesh| mesh.vertices.len() >= CHUNK_MESH_QUADS * 4) {
+                meshes.push(Mesh {
+                    vertices: Vec::new(),
+                    indices: Vec::new(),
+                    texture: Some(tileset.texture.clone()),
+                });
+            }
+            let Some(mesh) = meshes.last_mut() else {
+                return;
+            };
+            let size = self.tile_size;
+            let dest = Rect::new(tx as f32 * size, ty as f32 * size, size, size);
+            tileset.push_tile_quad(mesh, tile, (tx as i32, ty as i32), dest, self.tint, orientation);
+        });
+        meshes.retain(|mesh| !mesh.vertices.is_empty());
+        animated
+    }
+
+    /// Hands each static tile of the chunk's `layer`, then `over`'s on top, to `bake` with its
+    /// cell, and returns the animated ones it held back, as `render_chunk_layer` describes.
+    fn bake_chunk_layer(
+        &self,
+        chunk_index: usize,
+        layer: LayerKind,
+        over: Option<LayerKind>,
+        tileset: &TileSet,
+        mut bake: impl FnMut(u8, (usize, usize), TileOrientation),
+    ) -> (AnimatedTiles, AnimatedTiles) {
+        let (origin_x, origin_y) = self.chunk_origin(chunk_index);
+        let max_x = (origin_x + CHUNK_SIZE).min(self.width);
+        let max_y = (origin_y + CHUNK_SIZE).min(self.height);
+
+        let mut animated = Vec::new();
+        let mut animated_over = Vec::new();
         for ty in origin_y..max_y {
             for tx in origin_x..max_x {
-                let tile = self.get_tile(layer, tx, ty);
-                let Some(source) = tileset.get(tile) else {
+                let mut live = false;
+                if let Some(tile) = self.bakeable_tile(layer, tx, ty) {
+                    live = tileset.is_animated(tile);
+                    if live {
+                        animated.push((tx, ty));
+                    } else {
+                        bake(tile, (tx, ty), self.tile_orientation(layer, tx, ty, tile));
+                    }
+                }
+                let Some((over, tile)) = over.and_then(|over| Some((over, self.bakeable_tile(over, tx, ty)?))) else {
                     continue;
                 };
-
-                let local_x = (tx - origin_x) as f32 * self.tile_size;
-                let local_y = (ty - origin_y) as f32 * self.tile_size;
-                draw_texture_ex(
-                    tileset.texture(),
-                    local_x,
-                    local_y,
-                    WHITE,
-                    DrawTextureParams {
-                        source: Some(source),
-                        dest_size: dest,
-                        ..Default::default()
-                    },
-                );
+                if live || tileset.is_animated(tile) {
+                    animated_over.push((tx, ty));
+                } else {
+                    bake(tile, (tx, ty), self.tile_orientation(over, tx, ty, tile));
+                }
             }
         }
+        (animated, animated_over)
+    }
 
-        pop_camera_state();
+    /// The top-left tile of chunk `chunk_index`.
+    fn chunk_origin(&self, chunk_index: usize) -> (usize, usize) {
+        ((chunk_index % self.chunk_cols) * CHUNK_SIZE, (chunk_index / self.chunk_cols) * CHUNK_SIZE)
     }
 
     fn draw_chunk_layer(&self, chunk_index: usize, layer: LayerKind, cx: usize, cy: usize) {
@@ -1495,10 +3127,19 @@ impl TileMap {
         if !ready {
             return;
         }
-        let texture = match layer {
-            LayerKind::Background => &chunk.background.texture,
-            LayerKind::Foreground => &chunk.foreground.texture,
-            LayerKind::Overlay => &chunk.overlay.texture,
+        if self.mesh_chunks {
+            for mesh in &chunk.meshes[layer as usize] {
+                draw_mesh(mesh);
+            }
+            return;
+        }
+        let target = match layer {
+            LayerKind::Background => chunk.background.as_ref(),
+            LayerKind::Foreground => chunk.foreground.as_ref(),
+            LayerKind::Overlay => chunk.overlay.as_ref(),
+        };
+        let Some(texture) = target.map(|target| &target.texture) else {
+            return;
         };
 
         let world_x = cx as f32 * self.chunk_pixel_size;
@@ -1509,7 +3150,7 @@ impl TileMap {
             texture,
             world_x,
             world_y,
-            WHITE,
+            self.tint,
             DrawTextureParams {
                 dest_size: dest,
                 flip_y: true,
@@ -1518,30 +3159,51 @@ impl TileMap {
         );
     }
 
+    /// Draws the tiles `render_chunk_layer` left out, over the chunk's baked layer. Animating
+    /// them here instead of re-rendering their chunks keeps rebuilds for actual tile changes.
+    fn draw_animated_tiles(&self, chunk_index: usize, layer: LayerKind, tileset: &TileSet) {
+        let Some(chunk) = self.chunks.get(chunk_index).and_then(|c| c.as_ref()) else {
+            return;
+        };
+        let (ready, animated) = match layer {
+            LayerKind::Background => (chunk.ready_background, &chunk.animated_background),
+            LayerKind::Foreground => (chunk.ready_foreground, &chunk.animated_foreground),
+            LayerKind::Overlay => (chunk.ready_overlay, &chunk.animated_overlay),
+        };
+        if !ready {
+            return;
+        }
+        for &(tx, ty) in animated {
+            let pos = vec2(tx as f32, ty as f32) * self.tile_size;
+            let tile = self.get_tile(layer, tx, ty);
+            let orientation = self.tile_orientation(layer, tx, ty, tile);
+            tileset.draw_oriented_tile(tile, pos, self.tile_size, self.tint, orientation);
+        }
+    }
+
     fn get_tile(&self, layer: LayerKind, x: usize, y: usize) -> u8 {
-        let i = self.idx(x, y);
         match layer {
-            LayerKind::Background => self.background[i],
-            LayerKind::Foreground => self.foreground[i],
-            LayerKind::Overlay => self.overlay[i],
+            LayerKind::Background => self.background.get(x, y),
+            LayerKind::Foreground => self.foreground.get(x, y),
+            LayerKind::Overlay => self.overlay.get(x, y),
         }
     }
 
     fn rebuild_collision_blocks(&mut self) {
         self.collision_blocks.clear();
-        let mut visited = vec![false; self.solid.len()];
+        let mut visited = vec![false; self.width * self.height];
 
         for y in 0..self.height {
             for x in 0..self.width {
                 let i = self.idx(x, y);
-                if visited[i] || !self.solid[i] {
+                if visited[i] || !self.is_solid(x, y) {
                     continue;
                 }
 
                 let mut max_w = 0;
                 while x + max_w < self.width {
                     let idx = self.idx(x + max_w, y);
-                    if self.solid[idx] && !visited[idx] {
+                    if self.is_solid(x + max_w, y) && !visited[idx] {
                         max_w += 1;
                     } else {
                         break;
@@ -1555,7 +3217,7 @@ impl TileMap {
                     }
                     for tx in 0..max_w {
                         let idx = self.idx(x + tx, y + max_h);
-                        if !self.solid[idx] || visited[idx] {
+                        if !self.is_solid(x + tx, y + max_h) || visited[idx] {
                             break 'height;
                         }
                     }
@@ -1602,7 +3264,7 @@ impl TileMap {
                 if ux >= self.width || uy >= self.height {
                     continue;
                 }
-                let mask = self.collision_mask[self.idx(ux, uy)] & 0x0F;
+                let mask = self.collision_mask.get(ux, uy) & 0x0F;
                 if mask == 0 {
                     continue;
                 }
@@ -1645,12 +3307,18 @@ impl TileMap {
         self.background.fill(EMPTY_TILE);
         self.foreground.fill(EMPTY_TILE);
         self.overlay.fill(EMPTY_TILE);
-        self.solid.fill(false);
+        self.orientations.clear();
         self.collision_mask.fill(0);
         self.collision_dirty = true;
         self.structure_apply = None;
         self.structure_interactors.clear();
+        self.structure_triggers.clear();
+        self.clear_sorted_structures();
+        self.breakable_structures.clear();
+        self.placed_structures.clear();
+        self.rebuild_placed_index();
         self.custom_border_hitbox = None;
+        self.decals.clear();
         self.mark_all_chunks_dirty_all_layers();
     }
 
@@ -1659,10 +3327,12 @@ impl TileMap {
             width: self.width,
             height: self.height,
             tile_size: self.tile_size,
-            background: self.background.clone(),
-            foreground: self.foreground.clone(),
-            overlay: self.overlay.clone(),
-            collision_mask: self.collision_mask.clone(),
+            background: self.background.to_row_major(),
+            foreground: self.foreground.to_row_major(),
+            overlay: self.overlay.to_row_major(),
+            collision_mask: self.collision_mask.to_row_major(),
+            sorted_structures: self.sorted_structures.clone(),
+            breakable_structures: self.breakable_structures.clone(),
         }
     }
 
@@ -1682,16 +3352,50 @@ impl TileMap {
             return Err("snapshot layer lengths do not match map dimensions".to_string());
         }
 
-        self.background.clone_from(&snapshot.background);
-        self.foreground.clone_from(&snapshot.foreground);
-        self.overlay.clone_from(&snapshot.overlay);
-        self.collision_mask.clone_from(&snapshot.collision_mask);
-        for (i, mask) in self.collision_mask.iter().enumerate() {
-            self.solid[i] = (*mask & 0x0F) != 0;
-        }
+        let (w, h) = (self.width, self.height);
+        self.background = PagedGrid::from_row_major(w, h, &snapshot.background, EMPTY_TILE);
+        self.foreground = PagedGrid::from_row_major(w, h, &snapshot.foreground, EMPTY_TILE);
+        self.overlay = PagedGrid::from_row_major(w, h, &snapshot.overlay, EMPTY_TILE);
+        self.orientations.clear();
+        let masks: Vec<u8> = snapshot.collision_mask.iter().map(|mask| mask & 0x0F).collect();
+        self.collision_mask = PagedGrid::from_row_major(w, h, &masks, 0);
         self.collision_dirty = true;
         self.structure_apply = None;
         self.structure_interactors.clear();
+        self.structure_triggers.clear();
+        self.clear_sorted_structures();
+        for sorted in snapshot.sorted_structures.iter() {
+            self.insert_sorted_structure(*sorted);
+        }
+        self.breakable_structures = snapshot
+            .breakable_structures
+            .iter()
+            .filter(|breakable| {
+                breakable.x + breakable.width <= self.width && breakable.y + breakable.height <= self.height
+            })
+            .cloned()
+            .collect();
+        self.placed_structures.clear();
+        self.rebuild_placed_index();
+        for sorted in &snapshot.sorted_structures {
+            self.index_placed(PlacedStructure {
+                structure_id: String::new(),
+                x: sorted.x,
+                y: sorted.y,
+                width: sorted.width,
+                height: sorted.height,
+            });
+        }
+        for index in 0..self.breakable_structures.len() {
+            let breakable = &self.breakable_structures[index];
+            self.index_placed(PlacedStructure {
+                structure_id: breakable.structure_id.clone(),
+                x: breakable.x,
+                y: breakable.y,
+                width: breakable.width,
+                height: breakable.height,
+            });
+        }
         self.custom_border_hitbox = None;
         self.mark_all_chunks_dirty_all_layers();
         Ok(())
@@ -1794,26 +3498,29 @@ impl TileMap {
 
     fn create_chunk(&mut self, chunk_index: usize) {
         let chunk_size_u32 = self.chunk_pixel_size.round().max(1.0) as u32;
-        let background = render_target(chunk_size_u32, chunk_size_u32);
-        let foreground = render_target(chunk_size_u32, chunk_size_u32);
-        let overlay = render_target(chunk_size_u32, chunk_size_u32);
-        background.texture.set_filter(FilterMode::Nearest);
-        foreground.texture.set_filter(FilterMode::Nearest);
-        overlay.texture.set_filter(FilterMode::Nearest);
+        let background = (!self.mesh_chunks).then(|| chunk_target(chunk_size_u32));
+        let foreground = (!self.composite_layers && !self.mesh_chunks).then(|| chunk_target(chunk_size_u32));
         let dirty_background = self.pending_dirty_background.get(chunk_index).copied().unwrap_or(true);
         let dirty_foreground = self.pending_dirty_foreground.get(chunk_index).copied().unwrap_or(true);
         let dirty_overlay = self.pending_dirty_overlay.get(chunk_index).copied().unwrap_or(true);
         if let Some(slot) = self.chunks.get_mut(chunk_index) {
+            if slot.is_none() {
+                self.resident_chunks.push(chunk_index);
+            }
             *slot = Some(Chunk {
                 background,
                 foreground,
-                overlay,
+                overlay: None,
                 dirty_background,
                 dirty_foreground,
                 dirty_overlay,
                 ready_background: false,
                 ready_foreground: false,
                 ready_overlay: false,
+                animated_background: Vec::new(),
+                animated_foreground: Vec::new(),
+                animated_overlay: Vec::new(),
+                meshes: Default::default(),
             });
         }
         if let Some(flag) = self.pending_dirty_background.get_mut(chunk_index) {
@@ -1929,7 +3636,36 @@ pub async fn load_structures_from_dir(dir: impl AsRef<Path>) -> Result<Vec<Struc
 
     if cfg!(target_arch = "wasm32") {
         let dir = data_path(&dir.as_ref().to_string_lossy());
-        let files = load_wasm_manifest_files(&dir, &["tree_plains.json", "bush_plains.json"]).await;
+        let files = load_wasm_manifest_files(&dir, &[
+            "tree_plains.json",
+            "ore_rock_plains.json",
+            "scrap_pile_plains.json",
+            "bush_plains.json",
+            "grass_tuft_plains.json",
+            "expedition_gate.json",
+            "farm_gate.json",
+            "extraction_beacon.json",
+            "waypoint.json",
+            "farm_house_door.json",
+            "land_office.json",
+            "interior_exit.json",
+            "wood_pile.json",
+            "workbench.json",
+            "chest.json",
+            "bed.json",
+            "breeding_pod.json",
+            "turret.json",
+            "generator.json",
+            "conduit.json",
+            "sprinkler.json",
+            "harvester.json",
+            "charging_pad.json",
+            "laser_turret.json",
+            "conveyor_up.json",
+            "conveyor_down.json",
+            "conveyor_left.json",
+            "conveyor_right.json",
+        ]).await;
         for file in files {
             let path = format!("{}/{}", dir, file);
             let raw_str = load_string(&path)
@@ -1937,28 +3673,7 @@ pub async fn load_structures_from_dir(dir: impl AsRef<Path>) -> Result<Vec<Struc
                 .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
             let raw: StructureFile = serde_json::from_str(&raw_str)
                 .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-            let tile_len = raw.width * raw.height;
-            let colliders = normalized_collider_pins(raw.colliders, tile_len);
-            let interactors = normalized_collider_pins(raw.interactors, tile_len);
-            let structure = Structure::new(
-                raw.width,
-                raw.height,
-                raw.background,
-                raw.foreground,
-                raw.overlay,
-                colliders,
-                interactors,
-            );
-
-            defs.push(StructureDef {
-                id: raw.id,
-                structure,
-                on_interact: raw.on_interact.unwrap_or_default(),
-                interact_range: raw.interact_range.unwrap_or(0.0).max(0.0),
-                frequency: raw.frequency.unwrap_or(0.05),
-                max_per_map: raw.max_per_map.unwrap_or(10),
-                min_distance: raw.min_distance.unwrap_or(64.0),
-            });
+            defs.push(structure_def_from_file(raw));
         }
         return Ok(defs);
     }
@@ -1979,33 +3694,86 @@ pub async fn load_structures_from_dir(dir: impl AsRef<Path>) -> Result<Vec<Struc
         }
         let raw: StructureFile = serde_json::from_str(&std::fs::read_to_string(&path)?)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        let tile_len = raw.width * raw.height;
-        let colliders = normalized_collider_pins(raw.colliders, tile_len);
-        let interactors = normalized_collider_pins(raw.interactors, tile_len);
-        let structure = Structure::new(
-            raw.width,
-            raw.height,
-            raw.background,
-            raw.foreground,
-            raw.overlay,
-            colliders,
-            interactors,
-        );
-
-        defs.push(StructureDef {
-            id: raw.id,
-            structure,
-            on_interact: raw.on_interact.unwrap_or_default(),
-            interact_range: raw.interact_range.unwrap_or(0.0).max(0.0),
-            frequency: raw.frequency.unwrap_or(0.05),
-            max_per_map: raw.max_per_map.unwrap_or(10),
-            min_distance: raw.min_distance.unwrap_or(64.0),
-        });
+        defs.push(structure_def_from_file(raw));
     }
 
     Ok(defs)
 }
 
+fn structure_def_from_file(raw: StructureFile) -> StructureDef {
+    let tile_len = raw.width * raw.height;
+    let colliders = normalized_collider_pins(raw.colliders, tile_len);
+    let interactors = normalized_collider_pins(raw.interactors, tile_len);
+    let variants = raw
+        .variants
+        .unwrap_or_default()
+        .into_iter()
+        .map(|variant| {
+            let layer = |tiles: Option<Vec<u8>>, base: &Vec<u8>| {
+                tiles.filter(|tiles| tiles.len() == tile_len).unwrap_or_else(|| base.clone())
+            };
+            StructureVariant {
+                weight: variant.weight.unwrap_or(1.0).max(0.0),
+                structure: Structure::new(
+                    raw.width,
+                    raw.height,
+                    layer(variant.background, &raw.background),
+                    layer(variant.foreground, &raw.foreground),
+                    layer(variant.overlay, &raw.overlay),
+                    colliders.clone(),
+                    interactors.clone(),
+                ),
+            }
+        })
+        .collect();
+    let structure = Structure::new(
+        raw.width,
+        raw.height,
+        raw.background,
+        raw.foreground,
+        raw.overlay,
+        colliders,
+        interactors,
+    );
+    let damaged = (raw.damaged_foreground.is_some() || raw.damaged_overlay.is_some()).then_some(DamagedTiles {
+        foreground: raw.damaged_foreground,
+        overlay: raw.damaged_overlay,
+    });
+
+    StructureDef {
+        id: raw.id,
+        structure,
+        y_sort: raw.y_sort.unwrap_or(false),
+        on_interact: raw.on_interact.unwrap_or_default(),
+        scene_transition: raw.scene_transition,
+        interior: raw.interior,
+        // `fragile_hp` predates tool hits: it marks structures dashes can knock down.
+        hp: raw.hp.or(raw.fragile_hp).unwrap_or(0.0).max(0.0),
+        hit_only: raw.fragile_hp.is_none(),
+        damaged,
+        drops: raw.drops.unwrap_or_default(),
+        break_particles: raw.break_particles,
+        build_cost: raw.build_cost,
+        repair_cost: raw.repair_cost.unwrap_or_default(),
+        storage_slots: raw.storage_slots.unwrap_or(0),
+        bed: raw.bed.unwrap_or(false),
+        triggers: raw.triggers.unwrap_or_default(),
+        entity: raw.entity,
+        power: raw.power,
+        conveyor: raw.conveyor,
+        automation: raw.automation,
+        respawn_days: raw.respawn_days.unwrap_or(1).max(1),
+        waypoint: raw.waypoint.unwrap_or(false),
+        interact_range: raw.interact_range.unwrap_or(0.0).max(0.0),
+        frequency: raw.frequency.unwrap_or(0.05),
+        max_per_map: raw.max_per_map.unwrap_or(10),
+        min_distance: raw.min_distance.unwrap_or(64.0),
+        variants,
+        weight: raw.weight.unwrap_or(1.0).max(0.0),
+        palettes: raw.palettes.unwrap_or_default(),
+    }
+}
+
 #[derive(Deserialize)]
 struct StructureFile {
     id: String,
@@ -2030,6 +3798,66 @@ struct StructureFile {
     max_per_map: Option<usize>,
     #[serde(default)]
     min_distance: Option<f32>,
+    #[serde(default)]
+    y_sort: Option<bool>,
+    #[serde(default)]
+    scene_transition: Option<String>,
+    #[serde(default)]
+    interior: Option<String>,
+    #[serde(default)]
+    fragile_hp: Option<f32>,
+    #[serde(default)]
+    hp: Option<f32>,
+    #[serde(default)]
+    damaged_foreground: Option<Vec<u8>>,
+    #[serde(default)]
+    damaged_overlay: Option<Vec<u8>>,
+    #[serde(default)]
+    drops: Option<Vec<EntityDrop>>,
+    #[serde(default)]
+    break_particles: Option<String>,
+    #[serde(default)]
+    build_cost: Option<Vec<ItemStack>>,
+    #[serde(default)]
+    repair_cost: Option<Vec<ItemStack>>,
+    #[serde(default)]
+    storage_slots: Option<usize>,
+    #[serde(default)]
+    bed: Option<bool>,
+    #[serde(default)]
+    triggers: Option<Vec<TriggerDef>>,
+    #[serde(default)]
+    entity: Option<String>,
+    #[serde(default)]
+    power: Option<PowerDef>,
+    #[serde(default)]
+    conveyor: Option<ConveyorDef>,
+    #[serde(default)]
+    automation: Option<AutomationDef>,
+    #[serde(default)]
+    respawn_days: Option<u32>,
+    #[serde(default)]
+    waypoint: Option<bool>,
+    #[serde(default)]
+    variants: Option<Vec<StructureVariantFile>>,
+    #[serde(default)]
+    weight: Option<f32>,
+    /// Palette name to `[from, to]` tile pairs.
+    #[serde(default)]
+    palettes: Option<HashMap<String, Vec<(u8, u8)>>>,
+}
+
+/// Layers left out, or of the wrong length, keep the base structure's tiles.
+#[derive(Deserialize)]
+struct StructureVariantFile {
+    #[serde(default)]
+    weight: Option<f32>,
+    #[serde(default)]
+    background: Option<Vec<u8>>,
+    #[serde(default)]
+    foreground: Option<Vec<u8>>,
+    #[serde(default)]
+    overlay: Option<Vec<u8>>,
 }
 
 #[derive(Deserialize)]
@@ -2054,3 +3882,21 @@ fn normalized_collider_pins(raw: Option<ColliderPinsFile>, tile_len: usize) -> V
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn laid(flip_x: bool, turns: u8) -> TileOrientation {
+        TileOrientation { flip_x, turns }
+    }
+
+    #[test]
+    fn orientations_compose() {
+        assert_eq!(laid(false, 1).after(laid(false, 3)), laid(false, 0));
+        assert_eq!(laid(true, 0).after(laid(true, 0)), laid(false, 0));
+        // A mirror after a turn is the same mirror before the opposite turn.
+        assert_eq!(laid(true, 0).after(laid(false, 1)), laid(true, 3));
+        assert_eq!(laid(false, 2).after(laid(true, 1)), laid(true, 3));
+    }
+}
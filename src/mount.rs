@@ -0,0 +1,78 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use crate::entity::{DEFAULT_IFRAMES, Entity, EntityDatabase, EntityKind, EntityTarget};
+use crate::player::Player;
+
+/// Lets the player ride a friendly entity.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct MountDef {
+    /// Multiplies the player's acceleration and top speed while riding.
+    pub speed: f32,
+    /// World units the rider sits above the ground.
+    pub height: f32,
+}
+
+impl Default for MountDef {
+    fn default() -> Self {
+        Self {
+            speed: 1.5,
+            height: 4.0,
+        }
+    }
+}
+
+pub fn rideable(ent: &Entity, db: &EntityDatabase) -> bool {
+    let def = &db.entities[ent.instance.def];
+    def.kind == EntityKind::Friend && def.mount.is_some() && !ent.instance.is_dying()
+}
+
+/// Puts the player on the rideable friend `uid`. Returns whether they got on.
+pub fn ride(player: &mut Player, entities: &mut [Entity], db: &EntityDatabase, uid: u64) -> bool {
+    let Some(ent) = entities.iter_mut().find(|ent| ent.instance.uid == uid && rideable(ent, db)) else {
+        return false;
+    };
+    ent.instance.ridden = true;
+    ent.instance.vel = Vec2::ZERO;
+    ent.instance.waypoints.clear();
+    player.set_riding(db.entities[ent.instance.def].mount);
+    true
+}
+
+pub fn dismount(player: &mut Player, entities: &mut [Entity]) {
+    for ent in entities.iter_mut() {
+        ent.instance.ridden = false;
+    }
+    player.set_riding(None);
+}
+
+/// Carries the ridden entity along under the player, and gets the player off one that died
+/// or is gone.
+pub fn sync(player: &mut Player, entities: &mut [Entity], db: &EntityDatabase) {
+    if player.riding().is_none() {
+        return;
+    }
+    let seat = player.world_hitbox().center();
+    match entities.iter_mut().find(|ent| ent.instance.ridden) {
+        Some(ent) if !ent.instance.is_dying() => {
+            let offset = ent.hitbox(db).center() - ent.instance.pos;
+            ent.instance.pos = seat - offset;
+        }
+        _ => dismount(player, entities),
+    }
+}
+
+/// The entity being ridden, standing in for the player: while they ride, enemies go for it
+/// and hits meant for the player land on it instead.
+pub fn ridden_target(entities: &[Entity], db: &EntityDatabase) -> Option<EntityTarget> {
+    let ent = entities.iter().find(|ent| ent.instance.ridden)?;
+    Some(EntityTarget {
+        id: ent.instance.uid,
+        def: ent.instance.def,
+        kind: db.entities[ent.instance.def].kind,
+        pos: ent.instance.pos,
+        hitbox: ent.hitbox(db),
+        alive: ent.instance.hp > 0.0,
+        iframes: ent.instance.stats.get("iframes", DEFAULT_IFRAMES),
+    })
+}
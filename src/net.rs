@@ -0,0 +1,823 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{SocketAddr, UdpSocket};
+use crate::entity::{Entity, EntityDatabase, MovementRegistry, PlayerTarget};
+use crate::interp::Interpolator;
+use crate::map::{LayerKind, TileMap};
+use crate::player::{Player, PlayerInput};
+
+pub const DEFAULT_PORT: u16 = 7777;
+const PROTOCOL_VERSION: u16 = 3;
+/// Players in a session, the host's included unless it's a dedicated server.
+const MAX_PLAYERS: usize = 4;
+/// Sessions step remote players and trade packets at a fixed 20 per second, whatever the frame
+/// rate.
+const NET_TICK: f32 = 1.0 / 20.0;
+/// Ticks run at most per frame, so a long hitch doesn't turn into a burst of packets.
+const MAX_TICKS_PER_FRAME: u32 = 4;
+/// Seconds without a packet before a peer or the host counts as gone.
+const TIMEOUT: f32 = 5.0;
+/// Ticks between hellos while waiting to be let in.
+const HELLO_INTERVAL: u32 = 10;
+/// Tile runs per snapshot; the rest wait for later ones.
+const MAX_TILES_PER_SNAPSHOT: usize = 48;
+/// Entities per snapshot, the nearest to its player first.
+const MAX_ENTITIES_PER_SNAPSHOT: usize = 20;
+/// World units from a player past which entities aren't sent to them.
+const ENTITY_SEND_RADIUS: f32 = 400.0;
+/// What a full snapshot stays under, so it fits one packet of the usual internet MTU and isn't
+/// split into fragments that all have to arrive.
+const SNAPSHOT_BYTES: usize = 1200;
+/// How far the host may put the local player from where they were predicted before they're
+/// moved there.
+const RECONCILE_DISTANCE: f32 = 24.0;
+/// Inputs remembered for matching against the host's answers.
+const SENT_HISTORY: usize = 64;
+const MAX_PACKET: usize = 65_507;
+/// Seconds behind the newest snapshot that clients show the host's entities and players, two
+/// ticks' worth so one lost snapshot still leaves something to blend towards.
+const INTERP_DELAY: f64 = 0.1;
+/// Seconds a client keeps moving things along on their own once snapshots stop coming.
+const MAX_EXTRAPOLATION: f64 = 0.25;
+/// Remote players are targeted as this plus their peer id, after the local ones.
+const TARGET_INDEX_BASE: u8 = 2;
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(std::io::Error),
+    Encode(postcard::Error),
+}
+
+impl std::fmt::Display for NetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Encode(err) => write!(f, "encode error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl From<std::io::Error> for NetError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<postcard::Error> for NetError {
+    fn from(err: postcard::Error) -> Self {
+        Self::Encode(err)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    Hello { version: u16 },
+    /// The farm's size in tiles travels along so a mismatched one is caught up front.
+    Welcome { peer: u8, width: u32, height: u32 },
+    Refused(String),
+    Input { seq: u32, ack: u32, movement: [f32; 2], dash: bool },
+    Snapshot(Snapshot),
+    /// Sent instead of snapshots while the host is off the farm, so clients don't give up on it.
+    Away,
+    Bye,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    tick: u32,
+    players: Vec<PlayerState>,
+    entities: Vec<EntityState>,
+    /// Tile changes the client hasn't acknowledged yet, oldest first.
+    tiles: Vec<TileDelta>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct PlayerState {
+    peer: u8,
+    x: f32,
+    y: f32,
+    hp: f32,
+    /// The last of this player's inputs the host has applied.
+    input_seq: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct EntityState {
+    uid: u64,
+    def: u16,
+    x: f32,
+    y: f32,
+    hp: f32,
+}
+
+/// `len` cells of one row set to `id`, from (`x`, `y`) rightwards.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct TileDelta {
+    layer: u8,
+    x: u16,
+    y: u16,
+    id: u8,
+    len: u16,
+}
+
+impl TileDelta {
+    fn new(layer: LayerKind, x: usize, y: usize, id: u8) -> Self {
+        let layer = match layer {
+            LayerKind::Background => 0,
+            LayerKind::Foreground => 1,
+            LayerKind::Overlay => 2,
+        };
+        Self {
+            layer,
+            x: x as u16,
+            y: y as u16,
+            id,
+            len: 1,
+        }
+    }
+
+    fn layer(self) -> LayerKind {
+        match self.layer {
+            0 => LayerKind::Background,
+            1 => LayerKind::Foreground,
+            _ => LayerKind::Overlay,
+        }
+    }
+}
+
+/// What a session reads and, on a client, overwrites with what the host sends.
+pub struct NetWorld<'a> {
+    pub map: &'a mut TileMap,
+    pub entities: &'a mut Vec<Entity>,
+    pub db: &'a EntityDatabase,
+    pub registry: &'a MovementRegistry,
+    /// The local player. A dedicated server has none to show, so it passes one standing at the
+    /// spawn point that's never sent, for others to join beside.
+    pub player: &'a mut Player,
+    /// What the local player was driven by this frame.
+    pub input: PlayerInput,
+    /// Whether the farm is the scene being played; nothing is shared anywhere else.
+    pub on_farm: bool,
+}
+
+struct Peer {
+    addr: SocketAddr,
+    id: u8,
+    input: PlayerInput,
+    input_seq: u32,
+    /// The newest snapshot the peer has had.
+    ack: u32,
+    /// Tile changes it still needs, with the tick each was first sent in once it has been.
+    tiles: VecDeque<(Option<u32>, TileDelta)>,
+    silence: f32,
+}
+
+enum Role {
+    Host {
+        peers: Vec<Peer>,
+        /// No one plays on the host itself, so every seat is a peer's.
+        dedicated: bool,
+    },
+    Client {
+        host: SocketAddr,
+        peer: Option<u8>,
+        seq: u32,
+        /// The newest snapshot applied; older ones arriving late are dropped.
+        last_snapshot: u32,
+        /// Set when the dash key went down since the last input was sent.
+        dash: bool,
+        /// Where the local player was as each input went out.
+        sent: VecDeque<(u32, Vec2)>,
+        silence: f32,
+        /// Where the host has had each entity, by uid, and each other player, by peer id.
+        entity_motion: Interpolator<u64, Vec2>,
+        player_motion: Interpolator<u8, Vec2>,
+    },
+}
+
+/// Co-op over UDP, with the host's game the one that counts: clients send the keys they hold
+/// and get back where every player and entity on the farm is and the tiles that changed, which
+/// they draw instead of simulating their own. Clients move their own player straight away and
+/// are only pulled back when the host disagrees by more than a little. Only movement and health
+/// are shared for now; tools, crops and structures stay local to each game.
+pub struct NetSession {
+    socket: UdpSocket,
+    role: Role,
+    accumulator: f32,
+    tick: u32,
+    /// The other players, keyed by peer id; the host's is 0 unless it's dedicated.
+    remotes: Vec<(u8, Player)>,
+    closed: bool,
+    /// Every packet is read into this, so receiving doesn't allocate.
+    recv_buf: Box<[u8]>,
+}
+
+impl NetSession {
+    /// Opens the farm to up to three others on `port`.
+    pub fn host(port: u16) -> Result<Self, NetError> {
+        Self::open(&format!("0.0.0.0:{port}"), Role::Host { peers: Vec::new(), dedicated: false })
+    }
+
+    /// Opens the farm to up to four others on `port`, with no one playing on this end.
+    pub fn dedicated(port: u16) -> Result<Self, NetError> {
+        Self::open(&format!("0.0.0.0:{port}"), Role::Host { peers: Vec::new(), dedicated: true })
+    }
+
+    /// Asks the host at `addr` to be let in; `host:port`, or just a host for the default port.
+    pub fn join(addr: &str) -> Result<Self, NetError> {
+        let addr = if addr.contains(':') { addr.to_string() } else { format!("{addr}:{DEFAULT_PORT}") };
+        let host = std::net::ToSocketAddrs::to_socket_addrs(&addr)?
+            .next()
+            .ok_or_else(|| std::io::Error::other(format!("no address for '{addr}'")))?;
+        let role = Role::Client {
+            host,
+            peer: None,
+            seq: 0,
+            last_snapshot: 0,
+            dash: false,
+            sent: VecDeque::new(),
+            silence: 0.0,
+            entity_motion: Interpolator::new(INTERP_DELAY, MAX_EXTRAPOLATION),
+            player_motion: Interpolator::new(INTERP_DELAY, MAX_EXTRAPOLATION),
+        };
+        let session = Self::open("0.0.0.0:0", role)?;
+        session.send(host, &Packet::Hello { version: PROTOCOL_VERSION })?;
+        Ok(session)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open(bind: &str, role: Role) -> Result<Self, NetError> {
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            role,
+            accumulator: 0.0,
+            tick: 0,
+            remotes: Vec::new(),
+            closed: false,
+            recv_buf: vec![0; MAX_PACKET].into_boxed_slice(),
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn open(_bind: &str, _role: Role) -> Result<Self, NetError> {
+        let err = std::io::Error::new(std::io::ErrorKind::Unsupported, "co-op isn't available in the browser");
+        Err(err.into())
+    }
+
+    pub fn is_client(&self) -> bool {
+        matches!(self.role, Role::Client { .. })
+    }
+
+    /// Whether the session has ended, by the host leaving, refusing or going quiet.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn remote_players(&self) -> impl Iterator<Item = &Player> {
+        self.remotes.iter().map(|(_, player)| player)
+    }
+
+    /// The remote players for entities to go after, on the host; a client's are only drawn.
+    pub fn player_targets(&self, iframes: f32) -> Vec<PlayerTarget> {
+        if self.is_client() {
+            return Vec::new();
+        }
+        self.remotes
+            .iter()
+            .map(|(id, player)| PlayerTarget {
+                index: TARGET_INDEX_BASE + id,
+                pos: player.position(),
+                hitbox: player.world_hitbox(),
+                iframes,
+            })
+            .collect()
+    }
+
+    /// Hurts the remote player targeted as `index`, if it's one of them. One whose health runs
+    /// out is healed and sent back to `respawn`. Returns a line for the console when that happens.
+    pub fn damage_player(&mut self, index: u8, amount: f32, respawn: Vec2) -> Option<String> {
+        let id = index.checked_sub(TARGET_INDEX_BASE)?;
+        let (_, player) = self.remotes.iter_mut().find(|(remote, _)| *remote == id)?;
+        player.apply_damage(amount);
+        if player.hp() > 0.0 {
+            return None;
+        }
+        player.heal(player.max_hp());
+        player.set_position(respawn);
+        Some(format!("player {} was knocked out", id + 1))
+    }
+
+    /// Tells everyone else the session is over.
+    pub fn leave(self) {
+        match &self.role {
+            Role::Host { peers, .. } => {
+                for peer in peers {
+                    let _ = self.send(peer.addr, &Packet::Bye);
+                }
+            }
+            Role::Client { host, .. } => {
+                let _ = self.send(*host, &Packet::Bye);
+            }
+        }
+    }
+
+    /// Reads what arrived, runs the fixed ticks `dt` adds up to and sends what they produced.
+    /// Returns lines for the console.
+    pub fn update(&mut self, dt: f32, mut world: NetWorld) -> Vec<String> {
+        let mut messages = Vec::new();
+        if world.on_farm && !self.is_client() {
+            world.map.record_tile_changes();
+        }
+        if world.input.dash
+            && let Role::Client { dash, .. } = &mut self.role
+        {
+            *dash = true;
+        }
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut self.recv_buf) {
+                Ok(received) => received,
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break,
+                // Windows reports a peer's closed port on the next read; there may be more behind it.
+                Err(err) if err.kind() == std::io::ErrorKind::ConnectionReset => continue,
+                Err(err) => {
+                    messages.push(format!("network error: {err}"));
+                    break;
+                }
+            };
+            let Ok(packet) = postcard::from_bytes::<Packet>(&self.recv_buf[..len]) else {
+                continue;
+            };
+            if self.is_client() {
+                self.client_receive(from, packet, &mut world, &mut messages);
+            } else {
+                self.host_receive(from, packet, &world, &mut messages);
+            }
+        }
+        match &mut self.role {
+            Role::Host { peers, .. } => {
+                let remotes = &mut self.remotes;
+                peers.retain(|peer| {
+                    let alive = peer.silence < TIMEOUT;
+                    if !alive {
+                        messages.push(format!("player {} timed out", peer.id + 1));
+                        remotes.retain(|(id, _)| *id != peer.id);
+                    }
+                    alive
+                });
+                for peer in peers.iter_mut() {
+                    peer.silence += dt;
+                }
+            }
+            Role::Client { silence, entity_motion, player_motion, .. } => {
+                *silence += dt;
+                if *silence >= TIMEOUT {
+                    messages.push("lost the host".to_string());
+                    self.closed = true;
+                }
+                if world.on_farm {
+                    entity_motion.advance(dt);
+                    player_motion.advance(dt);
+                    for ent in world.entities.iter_mut() {
+                        if let Some(pos) = entity_motion.sample(&ent.instance.uid) {
+                            // Kept so walk animations play.
+                            if dt > 0.0 {
+                                ent.instance.vel = (pos - ent.instance.pos) / dt;
+                            }
+                            ent.instance.pos = pos;
+                        }
+                    }
+                    for (id, player) in &mut self.remotes {
+                        if let Some(pos) = player_motion.sample(id) {
+                            player.follow(pos, dt);
+                        }
+                    }
+                }
+            }
+        }
+        self.accumulator = (self.accumulator + dt).min(NET_TICK * MAX_TICKS_PER_FRAME as f32);
+        let mut ticked = false;
+        while self.accumulator >= NET_TICK {
+            self.accumulator -= NET_TICK;
+            self.tick += 1;
+            ticked = true;
+            if let Role::Host { peers, .. } = &mut self.role
+                && world.on_farm
+            {
+                for peer in peers.iter_mut() {
+                    if let Some((_, player)) = self.remotes.iter_mut().find(|(id, _)| *id == peer.id) {
+                        player.step(peer.input, world.map, NET_TICK);
+                    }
+                    peer.input.dash = false;
+                }
+            }
+        }
+        if ticked {
+            if self.is_client() {
+                if let Err(err) = self.client_send(&world) {
+                    messages.push(format!("network error: {err}"));
+                }
+            } else {
+                self.host_send(&mut world, &mut messages);
+            }
+        }
+        messages
+    }
+
+    fn host_receive(&mut self, from: SocketAddr, packet: Packet, world: &NetWorld, messages: &mut Vec<String>) {
+        let Role::Host { peers, dedicated } = &mut self.role else {
+            return;
+        };
+        let first_id = if *dedicated { 0 } else { 1 };
+        let known = peers.iter().position(|peer| peer.addr == from);
+        let reply = match (packet, known) {
+            (Packet::Hello { version }, _) if version != PROTOCOL_VERSION => {
+                Some(Packet::Refused(format!("the host runs protocol {PROTOCOL_VERSION}, not {version}")))
+            }
+            (Packet::Hello { .. }, Some(index)) => Some(welcome(peers[index].id, world.map)),
+            (Packet::Hello { .. }, None) => match (first_id..MAX_PLAYERS as u8)
+                .find(|id| peers.iter().all(|p| p.id != *id))
+            {
+                None => Some(Packet::Refused("the farm is full".to_string())),
+                Some(id) => {
+                    peers.push(Peer {
+                        addr: from,
+                        id,
+                        input: PlayerInput::default(),
+                        input_seq: 0,
+                        ack: 0,
+                        tiles: full_layers(world.map).map(|delta| (None, delta)).collect(),
+                        silence: 0.0,
+                    });
+                    self.remotes.push((id, world.player.remote(world.player.position())));
+                    messages.push(format!("player {} joined from {from}", id + 1));
+                    Some(welcome(id, world.map))
+                }
+            },
+            (Packet::Input { seq, ack, movement, dash }, Some(index)) => {
+                let peer = &mut peers[index];
+                peer.silence = 0.0;
+                peer.ack = peer.ack.max(ack);
+                if seq > peer.input_seq {
+                    peer.input_seq = seq;
+                    peer.input.movement = Vec2::from(movement).clamp_length_max(1.0);
+                    // Held until a tick uses it, so a dash isn't lost between two of them.
+                    peer.input.dash |= dash;
+                }
+                None
+            }
+            (Packet::Bye, Some(index)) => {
+                let id = peers.remove(index).id;
+                self.remotes.retain(|(remote, _)| *remote != id);
+                messages.push(format!("player {} left", id + 1));
+                None
+            }
+            _ => None,
+        };
+        if let Some(reply) = reply
+            && let Err(err) = self.send(from, &reply)
+        {
+            messages.push(format!("network error: {err}"));
+        }
+    }
+
+    /// Sends every peer its snapshot. A peer that can't be reached doesn't hold up the rest.
+    fn host_send(&mut self, world: &mut NetWorld, messages: &mut Vec<String>) {
+        let Role::Host { peers, dedicated } = &mut self.role else {
+            return;
+        };
+        let mut outgoing = Vec::with_capacity(peers.len());
+        if !world.on_farm {
+            outgoing.extend(peers.iter().map(|peer| (peer.id, peer.addr, Packet::Away)));
+        } else {
+            let changes: Vec<TileDelta> = world
+                .map
+                .take_tile_changes()
+                .into_iter()
+                .map(|(layer, x, y, id)| TileDelta::new(layer, x, y, id))
+                .collect();
+            let mut players = Vec::with_capacity(self.remotes.len() + 1);
+            if !*dedicated {
+                players.push(PlayerState {
+                    peer: 0,
+                    x: world.player.position().x,
+                    y: world.player.position().y,
+                    hp: world.player.hp(),
+                    input_seq: 0,
+                });
+            }
+            for (id, player) in &self.remotes {
+                let input_seq = peers.iter().find(|peer| peer.id == *id).map_or(0, |peer| peer.input_seq);
+                players.push(PlayerState {
+                    peer: *id,
+                    x: player.position().x,
+                    y: player.position().y,
+                    hp: player.hp(),
+                    input_seq,
+                });
+            }
+            for peer in peers.iter_mut() {
+                peer.tiles.extend(changes.iter().map(|&delta| (None, delta)));
+                // Every snapshot from a change's first on carries it until it's acknowledged,
+                // so any of those having arrived means the change did.
+                while peer.tiles.front().is_some_and(|(sent, _)| sent.is_some_and(|sent| sent <= peer.ack)) {
+                    peer.tiles.pop_front();
+                }
+                let mut tiles = Vec::with_capacity(peer.tiles.len().min(MAX_TILES_PER_SNAPSHOT));
+                for (sent, delta) in peer.tiles.iter_mut().take(MAX_TILES_PER_SNAPSHOT) {
+                    sent.get_or_insert(self.tick);
+                    tiles.push(*delta);
+                }
+                let around = self
+                    .remotes
+                    .iter()
+                    .find(|(id, _)| *id == peer.id)
+                    .map_or(world.player.position(), |(_, player)| player.position());
+                let mut snapshot = Snapshot {
+                    tick: self.tick,
+                    players: players.clone(),
+                    entities: nearby_entities(world.entities, around),
+                    tiles,
+                };
+                // The caps keep snapshots in budget with room to spare; big uids and tile
+                // coordinates could still tip one over, and the farthest entities go first.
+                while !snapshot.entities.is_empty()
+                    && postcard::to_allocvec(&snapshot).is_ok_and(|bytes| bytes.len() > SNAPSHOT_BYTES)
+                {
+                    snapshot.entities.pop();
+                }
+                outgoing.push((peer.id, peer.addr, Packet::Snapshot(snapshot)));
+            }
+        }
+        for (id, addr, packet) in outgoing {
+            if let Err(err) = self.send(addr, &packet) {
+                messages.push(format!("network error sending to player {}: {err}", id + 1));
+            }
+        }
+    }
+
+    fn client_receive(&mut self, from: SocketAddr, packet: Packet, world: &mut NetWorld, messages: &mut Vec<String>) {
+        let Role::Client { host, peer, last_snapshot, sent, silence, entity_motion, player_motion, .. } = &mut self.role
+        else {
+            return;
+        };
+        if from != *host {
+            return;
+        }
+        *silence = 0.0;
+        let snapshot = match packet {
+            Packet::Welcome { peer: id, width, height } => {
+                if peer.is_none() {
+                    messages.push(format!("joined as player {}", id + 1));
+                    if (width as usize, height as usize) != (world.map.width(), world.map.height()) {
+                        messages.push("the host's farm is a different size, so its tiles won't line up".to_string());
+                    }
+                }
+                *peer = Some(id);
+                return;
+            }
+            Packet::Refused(reason) => {
+                messages.push(format!("the host refused: {reason}"));
+                self.closed = true;
+                return;
+            }
+            Packet::Bye => {
+                messages.push("the host left".to_string());
+                self.closed = true;
+                return;
+            }
+            // Held back until both are on the farm, so the tiles in it aren't acknowledged
+            // without having been applied.
+            Packet::Snapshot(snapshot) if snapshot.tick > *last_snapshot && world.on_farm => snapshot,
+            _ => return,
+        };
+        *last_snapshot = snapshot.tick;
+        for delta in &snapshot.tiles {
+            let (x, y) = (delta.x as usize, delta.y as usize);
+            if y >= world.map.height() {
+                continue;
+            }
+            for x in x..(x + delta.len as usize).min(world.map.width()) {
+                world.map.set_tile(delta.layer(), x, y, delta.id);
+            }
+        }
+        let time = snapshot.tick as f64 * NET_TICK as f64;
+        mirror_entities(&snapshot.entities, world);
+        for state in &snapshot.entities {
+            entity_motion.push(state.uid, time, vec2(state.x, state.y));
+        }
+        let sent_uids: HashSet<u64> = snapshot.entities.iter().map(|state| state.uid).collect();
+        entity_motion.retain(|uid| sent_uids.contains(uid));
+        let own = *peer;
+        for state in snapshot.players.iter().filter(|state| Some(state.peer) == own) {
+            // The host's position answers an input once it's been applied, which is where the
+            // following input went out from here.
+            if let Some(&(_, predicted)) = sent.iter().find(|(seq, _)| *seq == state.input_seq + 1) {
+                let error = vec2(state.x, state.y) - predicted;
+                if error.length() > RECONCILE_DISTANCE {
+                    world.player.nudge(error);
+                }
+            }
+            while sent.front().is_some_and(|(seq, _)| *seq <= state.input_seq) {
+                sent.pop_front();
+            }
+            // Only the host's entities can hurt anyone, so its word on health is final.
+            let lost = world.player.hp() - state.hp;
+            if lost > 0.0 {
+                world.player.apply_damage(lost);
+            } else if lost < 0.0 {
+                world.player.heal(-lost);
+            }
+        }
+        let others: Vec<&PlayerState> = snapshot.players.iter().filter(|state| Some(state.peer) != own).collect();
+        self.remotes.retain(|(id, _)| others.iter().any(|state| state.peer == *id));
+        player_motion.retain(|id| others.iter().any(|state| state.peer == *id));
+        for state in others {
+            let pos = vec2(state.x, state.y);
+            player_motion.push(state.peer, time, pos);
+            if self.remotes.iter().all(|(id, _)| *id != state.peer) {
+                self.remotes.push((state.peer, world.player.remote(pos)));
+            }
+        }
+    }
+
+    fn client_send(&mut self, world: &NetWorld) -> Result<(), NetError> {
+        let Role::Client { host, peer, seq, last_snapshot, dash, sent, .. } = &mut self.role else {
+            return Ok(());
+        };
+        let host = *host;
+        if peer.is_none() {
+            if self.tick.is_multiple_of(HELLO_INTERVAL) {
+                return self.send(host, &Packet::Hello { version: PROTOCOL_VERSION });
+            }
+            return Ok(());
+        }
+        *seq += 1;
+        sent.push_back((*seq, world.player.position()));
+        while sent.len() > SENT_HISTORY {
+            sent.pop_front();
+        }
+        let movement = if world.on_farm { world.input.movement } else { Vec2::ZERO };
+        let packet = Packet::Input {
+            seq: *seq,
+            ack: *last_snapshot,
+            movement: movement.to_array(),
+            dash: std::mem::take(dash),
+        };
+        self.send(host, &packet)
+    }
+
+    fn send(&self, to: SocketAddr, packet: &Packet) -> Result<(), NetError> {
+        let bytes = postcard::to_allocvec(packet)?;
+        match self.socket.send_to(&bytes, to) {
+            Ok(_) => Ok(()),
+            // A full send buffer drops the packet like the network would.
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+fn welcome(peer: u8, map: &TileMap) -> Packet {
+    Packet::Welcome {
+        peer,
+        width: map.width() as u32,
+        height: map.height() as u32,
+    }
+}
+
+/// Every tile of every layer, as runs along each row, for a player who just joined.
+fn full_layers(map: &TileMap) -> impl Iterator<Item = TileDelta> + '_ {
+    [LayerKind::Background, LayerKind::Foreground, LayerKind::Overlay]
+        .into_iter()
+        .flat_map(move |layer| (0..map.height()).map(move |y| (layer, y)))
+        .flat_map(move |(layer, y)| {
+            let mut runs: Vec<TileDelta> = Vec::new();
+            for x in 0..map.width() {
+                let id = map.tile_at(layer, x, y);
+                match runs.last_mut() {
+                    Some(run) if run.id == id && run.len < u16::MAX => run.len += 1,
+                    _ => runs.push(TileDelta::new(layer, x, y, id)),
+                }
+            }
+            runs
+        })
+}
+
+/// The entities within reach of `around`, nearest first, as many as a snapshot has room for.
+fn nearby_entities(entities: &[Entity], around: Vec2) -> Vec<EntityState> {
+    let mut near: Vec<(f32, &Entity)> = entities
+        .iter()
+        .map(|ent| (ent.instance.pos.distance_squared(around), ent))
+        .filter(|(distance, _)| *distance <= ENTITY_SEND_RADIUS * ENTITY_SEND_RADIUS)
+        .collect();
+    near.sort_by(|a, b| a.0.total_cmp(&b.0));
+    near.into_iter()
+        .take(MAX_ENTITIES_PER_SNAPSHOT)
+        .map(|(_, ent)| EntityState {
+            uid: ent.instance.uid,
+            def: ent.instance.def as u16,
+            x: ent.instance.pos.x,
+            y: ent.instance.pos.y,
+            hp: ent.instance.hp,
+        })
+        .collect()
+}
+
+/// Makes `world.entities` the host's: spawned where it says if new and dropped if gone. Those
+/// already here are left to the interpolator to move.
+fn mirror_entities(states: &[EntityState], world: &mut NetWorld) {
+    let by_uid: HashMap<u64, &EntityState> = states.iter().map(|state| (state.uid, state)).collect();
+    world
+        .entities
+        .retain(|ent| by_uid.get(&ent.instance.uid).is_some_and(|state| state.def as usize == ent.instance.def));
+    let present: HashMap<u64, usize> =
+        world.entities.iter().enumerate().map(|(index, ent)| (ent.instance.uid, index)).collect();
+    for state in states {
+        let pos = vec2(state.x, state.y);
+        if let Some(&index) = present.get(&state.uid) {
+            world.entities[index].instance.hp = state.hp;
+            continue;
+        }
+        let Some(def) = world.db.entities.get(state.def as usize) else {
+            continue;
+        };
+        if let Some(mut ent) = Entity::spawn(world.db, &def.id, pos, world.registry) {
+            ent.instance.uid = state.uid;
+            ent.instance.hp = state.hp;
+            world.entities.push(ent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_full_snapshot_fits_one_packet() {
+        let players = (0..MAX_PLAYERS as u8)
+            .map(|peer| PlayerState {
+                peer,
+                x: f32::MAX,
+                y: f32::MAX,
+                hp: f32::MAX,
+                input_seq: u32::MAX,
+            })
+            .collect();
+        let entities = (0..MAX_ENTITIES_PER_SNAPSHOT)
+            .map(|_| EntityState {
+                uid: u64::MAX,
+                def: u16::MAX,
+                x: f32::MAX,
+                y: f32::MAX,
+                hp: f32::MAX,
+            })
+            .collect();
+        let tiles = (0..MAX_TILES_PER_SNAPSHOT)
+            .map(|_| TileDelta {
+                layer: 2,
+                x: u16::MAX,
+                y: u16::MAX,
+                id: u8::MAX,
+                len: u16::MAX,
+            })
+            .collect();
+        let snapshot = Snapshot {
+            tick: u32::MAX,
+            players,
+            entities,
+            tiles,
+        };
+        let bytes = postcard::to_allocvec(&Packet::Snapshot(snapshot)).unwrap();
+        assert!(bytes.len() <= SNAPSHOT_BYTES, "{} bytes", bytes.len());
+    }
+
+    #[test]
+    fn join_state_covers_every_layer_in_runs() {
+        let mut map = TileMap::new_deferred(40, 2, 16.0, vec2(16.0, 16.0), 0.0);
+        map.fill_layer(LayerKind::Background, 24);
+        map.set_tile(LayerKind::Background, 10, 0, 7);
+        map.set_tile(LayerKind::Overlay, 39, 1, 3);
+        let runs: Vec<TileDelta> = full_layers(&map).collect();
+        // Three runs around the odd background tile, two up to the overlay's and one a row
+        // for the rest.
+        assert_eq!(runs.len(), 3 + 2 + 4);
+
+        let mut copy = TileMap::new_deferred(40, 2, 16.0, vec2(16.0, 16.0), 0.0);
+        for run in &runs {
+            for x in run.x..run.x + run.len {
+                copy.set_tile(run.layer(), x as usize, run.y as usize, run.id);
+            }
+        }
+        for layer in [LayerKind::Background, LayerKind::Foreground, LayerKind::Overlay] {
+            for (x, y) in (0..2).flat_map(|y| (0..40).map(move |x| (x, y))) {
+                assert_eq!(copy.tile_at(layer, x, y), map.tile_at(layer, x, y));
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+/// Side length (in cells) of one page. Matches the map's render chunk size so a page
+/// never straddles two chunks.
+pub const PAGE_SIZE: usize = 32;
+
+#[derive(Clone)]
+enum Page<T> {
+    Uniform(T),
+    Dense(Box<[T]>),
+}
+
+/// A 2D grid stored as square pages. Pages holding a single value stay collapsed, so
+/// large, mostly-uniform layers cost one value per page until something is written.
+#[derive(Clone)]
+pub struct PagedGrid<T> {
+    width: usize,
+    height: usize,
+    page_cols: usize,
+    pages: Vec<Page<T>>,
+}
+
+impl<T: Copy + PartialEq> PagedGrid<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Self {
+        let page_cols = width.div_ceil(PAGE_SIZE);
+        let page_rows = height.div_ceil(PAGE_SIZE);
+        Self {
+            width,
+            height,
+            page_cols,
+            pages: vec![Page::Uniform(value); page_cols * page_rows],
+        }
+    }
+
+    /// Builds a grid from row-major `data`, collapsing pages that hold one value.
+    pub fn from_row_major(width: usize, height: usize, data: &[T], fallback: T) -> Self {
+        let mut grid = Self::new(width, height, fallback);
+        for y in 0..height {
+            for x in 0..width {
+                if let Some(&value) = data.get(y * width + x) {
+                    grid.set(x, y, value);
+                }
+            }
+        }
+        grid.compact();
+        grid
+    }
+
+    /// A copy `width` by `height` cells big, with the cells past the old edges set to `value`.
+    pub fn grown(&self, width: usize, height: usize, value: T) -> Self {
+        let mut grid = Self::new(width, height, value);
+        for y in 0..self.height.min(height) {
+            for x in 0..self.width.min(width) {
+                grid.set(x, y, self.get(x, y));
+            }
+        }
+        grid.compact();
+        grid
+    }
+
+    pub fn to_row_major(&self) -> Vec<T> {
+        let mut out = Vec::with_capacity(self.width * self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                out.push(self.get(x, y));
+            }
+        }
+        out
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> T {
+        let (page, offset) = self.locate(x, y);
+        match &self.pages[page] {
+            Page::Uniform(value) => *value,
+            Page::Dense(cells) => cells[offset],
+        }
+    }
+
+    /// Writes one cell. Returns whether the stored value changed.
+    pub fn set(&mut self, x: usize, y: usize, value: T) -> bool {
+        let (page, offset) = self.locate(x, y);
+        match &mut self.pages[page] {
+            Page::Uniform(current) => {
+                if *current == value {
+                    return false;
+                }
+                let mut cells = vec![*current; PAGE_SIZE * PAGE_SIZE].into_boxed_slice();
+                cells[offset] = value;
+                self.pages[page] = Page::Dense(cells);
+                true
+            }
+            Page::Dense(cells) => {
+                if cells[offset] == value {
+                    return false;
+                }
+                cells[offset] = value;
+                true
+            }
+        }
+    }
+
+    pub fn fill(&mut self, value: T) {
+        self.pages.fill(Page::Uniform(value));
+    }
+
+    pub fn all(&self, value: T) -> bool {
+        self.pages.iter().all(|page| match page {
+            Page::Uniform(current) => *current == value,
+            Page::Dense(cells) => cells.iter().all(|cell| *cell == value),
+        })
+    }
+
+    /// Collapses dense pages whose cells all hold the same value.
+    pub fn compact(&mut self) {
+        for page in self.pages.iter_mut() {
+            if let Page::Dense(cells) = page
+                && let Some(&first) = cells.first()
+                && cells.iter().all(|cell| *cell == first)
+            {
+                *page = Page::Uniform(first);
+            }
+        }
+    }
+
+    fn locate(&self, x: usize, y: usize) -> (usize, usize) {
+        let page = (y / PAGE_SIZE) * self.page_cols + x / PAGE_SIZE;
+        let offset = (y % PAGE_SIZE) * PAGE_SIZE + x % PAGE_SIZE;
+        (page, offset)
+    }
+}
@@ -3,13 +3,14 @@ use macroquad::file::load_string;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::path::Path;
-use crate::helpers::{asset_path, data_path, load_wasm_manifest_files};
+use crate::helpers::{asset_path, data_path, load_wasm_manifest_files, random_f32};
+use crate::atlas::Sprite;
+use crate::texture::load_texture_or_placeholder;
 
 #[derive(Debug)]
 pub enum ParticleLoadError {
     Io(std::io::Error),
     Yaml(serde_yaml::Error),
-    Texture(String),
 }
 
 impl std::fmt::Display for ParticleLoadError {
@@ -17,7 +18,6 @@ impl std::fmt::Display for ParticleLoadError {
         match self {
             Self::Io(err) => write!(f, "io error: {err}"),
             Self::Yaml(err) => write!(f, "yaml error: {err}"),
-            Self::Texture(err) => write!(f, "texture error: {err}"),
         }
     }
 }
@@ -92,6 +92,8 @@ struct Particle {
     rotation_speed: f32,
     template: usize,
     texture: Option<Texture2D>,
+    /// Part of `texture` to draw, when it's an atlas page.
+    source: Option<Rect>,
     dest_size: Option<Vec2>,
 }
 
@@ -110,6 +112,7 @@ impl Default for Particle {
             rotation_speed: 0.0,
             template: 0,
             texture: None,
+            source: None,
             dest_size: None,
         }
     }
@@ -206,6 +209,7 @@ impl ParticlePool {
                     if let Some(tex) = tex {
                         let base_dest = particle
                             .dest_size
+                            .or(particle.source.map(|source| source.size()))
                             .unwrap_or_else(|| vec2(tex.width(), tex.height()));
                         let dest = base_dest * size;
                         draw_texture_ex(
@@ -215,6 +219,7 @@ impl ParticlePool {
                             color,
                             DrawTextureParams {
                                 dest_size: Some(dest),
+                                source: particle.source,
                                 rotation: particle.rotation,
                                 ..Default::default()
                             },
@@ -239,7 +244,7 @@ impl ParticlePool {
                 ParticleShape::Quad => size * 0.5,
                 ParticleShape::Texture => {
                     let tex = particle.texture.as_ref().or(template.texture.as_ref());
-                    let base = particle.dest_size.unwrap_or_else(|| {
+                    let base = particle.dest_size.or(particle.source.map(|source| source.size())).unwrap_or_else(|| {
                         tex.map(|t| vec2(t.width(), t.height()))
                             .unwrap_or(vec2(size, size))
                     });
@@ -279,6 +284,7 @@ impl ParticlePool {
                     if let Some(tex) = tex {
                         let base_dest = particle
                             .dest_size
+                            .or(particle.source.map(|source| source.size()))
                             .unwrap_or_else(|| vec2(tex.width(), tex.height()));
                         let dest = base_dest * size;
                         draw_texture_ex(
@@ -288,6 +294,7 @@ impl ParticlePool {
                             color,
                             DrawTextureParams {
                                 dest_size: Some(dest),
+                                source: particle.source,
                                 rotation: particle.rotation,
                                 ..Default::default()
                             },
@@ -348,7 +355,7 @@ impl ParticleSystem {
 
         if cfg!(target_arch = "wasm32") {
             let dir = data_path(&dir.to_string_lossy());
-            let files = load_wasm_manifest_files(&dir, &["trail.yaml", "dash.yaml"]).await;
+            let files = load_wasm_manifest_files(&dir, &["trail.yaml", "dash.yaml", "bench.yaml", "debris.yaml"]).await;
             for file in files {
                 let path = format!("{}/{}", dir, file);
                 let raw_str = load_string(&path)
@@ -359,10 +366,7 @@ impl ParticleSystem {
                 total_capacity = total_capacity.saturating_add(config.max_particles);
 
                 let texture = if let Some(path) = texture_path {
-                    let tex = load_texture(&asset_path(&path))
-                        .await
-                        .map_err(|err| ParticleLoadError::Texture(err.to_string()))?;
-                    tex.set_filter(FilterMode::Nearest);
+                    let tex = load_texture_or_placeholder(&asset_path(&path)).await;
                     Some(tex)
                 } else {
                     None
@@ -383,10 +387,7 @@ impl ParticleSystem {
                 total_capacity = total_capacity.saturating_add(config.max_particles);
 
                 let texture = if let Some(path) = texture_path {
-                    let tex = load_texture(&asset_path(&path))
-                        .await
-                        .map_err(|err| ParticleLoadError::Texture(err.to_string()))?;
-                    tex.set_filter(FilterMode::Nearest);
+                    let tex = load_texture_or_placeholder(&asset_path(&path)).await;
                     Some(tex)
                 } else {
                     None
@@ -416,6 +417,13 @@ impl ParticleSystem {
         Some(ParticleEmitter::new(idx, pos))
     }
 
+    /// Fires a template's one-shot burst at `pos`.
+    pub fn burst(&mut self, id: &str, pos: Vec2) {
+        if let Some(mut emitter) = self.emitter(id, pos) {
+            self.update_emitter(&mut emitter, pos, 0.0);
+        }
+    }
+
     pub fn update_emitter(&mut self, emitter: &mut ParticleEmitter, pos: Vec2, dt: f32) {
         self.update_emitter_with_texture(emitter, pos, dt, None, None);
     }
@@ -425,7 +433,7 @@ impl ParticleSystem {
         emitter: &mut ParticleEmitter,
         pos: Vec2,
         dt: f32,
-        texture: Option<&Texture2D>,
+        sprite: Option<&Sprite>,
         dest_size: Option<Vec2>,
     ) {
         let cfg = self.templates[emitter.template].config.clone();
@@ -437,7 +445,7 @@ impl ParticleSystem {
 
         if !emitter.burst_done && cfg.burst > 0 {
             for _ in 0..cfg.burst {
-                self.spawn_particle(emitter.template, pos, Vec2::ZERO, texture, dest_size);
+                self.spawn_particle(emitter.template, pos, Vec2::ZERO, sprite, dest_size);
             }
             emitter.burst_done = true;
         }
@@ -452,7 +460,7 @@ impl ParticleSystem {
                     emitter.template,
                     pos,
                     (pos - emitter.last_pos) / dt.max(0.0001),
-                    texture,
+                    sprite,
                     dest_size,
                 );
             }
@@ -473,7 +481,7 @@ impl ParticleSystem {
                         emitter.template,
                         spawn_pos,
                         dir / dt.max(0.0001),
-                        texture,
+                        sprite,
                         dest_size,
                     );
                 }
@@ -503,6 +511,29 @@ impl ParticleSystem {
         self.pool.draw_in_rect(&self.templates, rect);
     }
 
+    /// Per-template particle cap, before the budget scale is applied.
+    pub fn capacity(&self, id: &str) -> Option<usize> {
+        let idx = self.lookup.get(id).copied()?;
+        Some(self.templates[idx].config.max_particles)
+    }
+
+    /// Spawns particles of `id` at random points in `area` until `target` are alive
+    /// (or the template cap is hit). Returns the live count.
+    pub fn top_up_in_rect(&mut self, id: &str, area: Rect, target: usize) -> usize {
+        let Some(idx) = self.lookup.get(id).copied() else {
+            return 0;
+        };
+        let missing = target.saturating_sub(self.template_counts[idx]);
+        for _ in 0..missing {
+            let pos = vec2(
+                area.x + random_f32() * area.w.max(0.0),
+                area.y + random_f32() * area.h.max(0.0),
+            );
+            self.spawn_particle(idx, pos, Vec2::ZERO, None, None);
+        }
+        self.template_counts[idx]
+    }
+
     pub fn set_budget_scale(&mut self, scale: f32) {
         self.budget_scale = scale.clamp(0.1, 1.0);
     }
@@ -512,7 +543,7 @@ impl ParticleSystem {
         template: usize,
         pos: Vec2,
         emitter_vel: Vec2,
-        override_texture: Option<&Texture2D>,
+        override_sprite: Option<&Sprite>,
         override_dest_size: Option<Vec2>,
     ) {
         let cfg = &self.templates[template].config;
@@ -534,10 +565,9 @@ impl ParticleSystem {
 
         let rotation = cfg.rotation + rand_range(cfg.rotation_variance);
         let rotation_speed = cfg.rotation_speed + rand_range(cfg.rotation_speed_variance);
-        let texture = if cfg.dynamic_sprite {
-            override_texture.map(|tex| tex.weak_clone())
-        } else {
-            None
+        let (texture, source) = match override_sprite {
+            Some(sprite) if cfg.dynamic_sprite => (Some(sprite.texture.weak_clone()), Some(sprite.source)),
+            _ => (None, None),
         };
         let dest_size = if cfg.dynamic_sprite {
             override_dest_size
@@ -558,6 +588,7 @@ impl ParticleSystem {
             rotation_speed,
             template,
             texture,
+            source,
             dest_size,
         });
         if spawned {
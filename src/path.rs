@@ -0,0 +1,137 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use crate::map::TileMap;
+
+/// Tiles expanded before a search gives up, so an unreachable goal can't stall a frame.
+const MAX_EXPANDED: usize = 4096;
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+pub type Tile = (usize, usize);
+
+#[derive(PartialEq, Eq)]
+struct Open {
+    estimate: u32,
+    cost: u32,
+    tile: Tile,
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the heap pops the cheapest estimate first.
+        other
+            .estimate
+            .cmp(&self.estimate)
+            .then_with(|| self.cost.cmp(&other.cost))
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A* over non-solid tiles from `start` to `goal`, both included in the returned path.
+pub fn find_path(map: &TileMap, start: Tile, goal: Tile) -> Option<Vec<Tile>> {
+    if map.is_solid(goal.0, goal.1) {
+        return None;
+    }
+    search(map, start, goal, |tile| tile == goal)
+}
+
+/// Like [`find_path`], but stops on any open tile touching `goal`, for targets that are
+/// themselves solid (a bush to harvest) or that only need to be reached, not stood on.
+pub fn find_path_near(map: &TileMap, start: Tile, goal: Tile) -> Option<Vec<Tile>> {
+    search(map, start, goal, |tile| {
+        tile.0.abs_diff(goal.0) <= 1 && tile.1.abs_diff(goal.1) <= 1
+    })
+}
+
+fn search(map: &TileMap, start: Tile, goal: Tile, arrived: impl Fn(Tile) -> bool) -> Option<Vec<Tile>> {
+    if start.0 >= map.width() || start.1 >= map.height() {
+        return None;
+    }
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Tile, Tile> = HashMap::new();
+    let mut costs: HashMap<Tile, u32> = HashMap::new();
+    costs.insert(start, 0);
+    open.push(Open {
+        estimate: heuristic(start, goal),
+        cost: 0,
+        tile: start,
+    });
+
+    let mut expanded = 0;
+    while let Some(Open { cost, tile, .. }) = open.pop() {
+        if arrived(tile) {
+            return Some(rebuild(&came_from, tile));
+        }
+        if costs.get(&tile).is_some_and(|&best| cost > best) {
+            continue;
+        }
+        expanded += 1;
+        if expanded > MAX_EXPANDED {
+            return None;
+        }
+        for (next, step) in neighbors(map, tile) {
+            let next_cost = cost + step;
+            if costs.get(&next).is_some_and(|&best| best <= next_cost) {
+                continue;
+            }
+            costs.insert(next, next_cost);
+            came_from.insert(next, tile);
+            open.push(Open {
+                estimate: next_cost + heuristic(next, goal),
+                cost: next_cost,
+                tile: next,
+            });
+        }
+    }
+    None
+}
+
+/// Open neighbors of `tile`. Diagonal steps need both adjacent straight tiles open so
+/// paths never clip a wall corner.
+fn neighbors(map: &TileMap, tile: Tile) -> impl Iterator<Item = (Tile, u32)> + '_ {
+    let open = move |x: isize, y: isize| {
+        x >= 0
+            && y >= 0
+            && (x as usize) < map.width()
+            && (y as usize) < map.height()
+            && !map.is_solid(x as usize, y as usize)
+    };
+    let (x, y) = (tile.0 as isize, tile.1 as isize);
+    [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)]
+        .into_iter()
+        .filter_map(move |(dx, dy)| {
+            let (nx, ny) = (x + dx, y + dy);
+            if !open(nx, ny) {
+                return None;
+            }
+            if dx != 0 && dy != 0 {
+                if !open(x + dx, y) || !open(x, y + dy) {
+                    return None;
+                }
+                return Some(((nx as usize, ny as usize), DIAGONAL_COST));
+            }
+            Some(((nx as usize, ny as usize), STRAIGHT_COST))
+        })
+}
+
+/// Octile distance, exact on an open 8-connected grid.
+fn heuristic(from: Tile, to: Tile) -> u32 {
+    let dx = from.0.abs_diff(to.0) as u32;
+    let dy = from.1.abs_diff(to.1) as u32;
+    STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+}
+
+fn rebuild(came_from: &HashMap<Tile, Tile>, mut tile: Tile) -> Vec<Tile> {
+    let mut path = vec![tile];
+    while let Some(&previous) = came_from.get(&tile) {
+        tile = previous;
+        path.push(tile);
+    }
+    path.reverse();
+    path
+}
@@ -0,0 +1,196 @@
+use macroquad::prelude::*;
+use crate::camera::GameCamera;
+use crate::font::{TextStyle, draw_text_styled};
+
+/// View heights per second the free camera flies, so it covers the same share of the screen
+/// at any zoom.
+const FLY_SPEED: f32 = 0.8;
+const FAST_MULTIPLIER: f32 = 3.0;
+/// Seconds a saved or failed screenshot message stays up.
+const MESSAGE_TIME: f32 = 3.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum PhotoFilter {
+    #[default]
+    None,
+    Mono,
+    Sepia,
+    Vignette,
+}
+
+impl PhotoFilter {
+    const ALL: [PhotoFilter; 4] = [Self::None, Self::Mono, Self::Sepia, Self::Vignette];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Mono => "black and white",
+            Self::Sepia => "sepia",
+            Self::Vignette => "vignette",
+        }
+    }
+
+    /// Mode uniform of the photo filter shader.
+    pub fn shader_mode(self) -> f32 {
+        Self::ALL.iter().position(|&filter| filter == self).unwrap_or(0) as f32
+    }
+
+    fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&filter| filter == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// Toggled with F9: pauses the game, hides the HUD and frees the camera. WASD flies, shift
+/// flies faster, the wheel zooms, tab cycles filters and space saves a screenshot.
+#[derive(Default)]
+pub struct PhotoMode {
+    active: bool,
+    position: Vec2,
+    filter: PhotoFilter,
+    /// Set for the frame a screenshot should be taken on.
+    shutter: bool,
+    message: Option<(String, f32)>,
+}
+
+impl PhotoMode {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Enters photo mode with the camera where it is, or leaves it.
+    pub fn toggle(&mut self, camera: &GameCamera) {
+        self.active = !self.active;
+        self.position = camera.target();
+        self.shutter = false;
+    }
+
+    pub fn close(&mut self) {
+        self.active = false;
+        self.shutter = false;
+    }
+
+    pub fn filter(&self) -> PhotoFilter {
+        self.filter
+    }
+
+    /// Flies the camera and handles the filter and shutter keys.
+    pub fn update(&mut self, camera: &mut GameCamera, render_target: Option<RenderTarget>) {
+        let dt = get_frame_time();
+        if let Some((_, remaining)) = self.message.as_mut() {
+            *remaining -= dt;
+        }
+        if self.message.as_ref().is_some_and(|(_, remaining)| *remaining <= 0.0) {
+            self.message = None;
+        }
+        if !self.active {
+            return;
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            self.close();
+            return;
+        }
+        if is_key_pressed(KeyCode::Tab) {
+            self.filter = self.filter.next();
+        }
+        if is_key_pressed(KeyCode::Space) {
+            self.shutter = true;
+        }
+        let mut direction = Vec2::ZERO;
+        if is_key_down(KeyCode::A) || is_key_down(KeyCode::Left) {
+            direction.x -= 1.0;
+        }
+        if is_key_down(KeyCode::D) || is_key_down(KeyCode::Right) {
+            direction.x += 1.0;
+        }
+        if is_key_down(KeyCode::W) || is_key_down(KeyCode::Up) {
+            direction.y -= 1.0;
+        }
+        if is_key_down(KeyCode::S) || is_key_down(KeyCode::Down) {
+            direction.y += 1.0;
+        }
+        let fast = if is_key_down(KeyCode::LeftShift) { FAST_MULTIPLIER } else { 1.0 };
+        self.position += direction.normalize_or_zero() * camera.view_height() * FLY_SPEED * fast * dt;
+        camera.update_free(self.position, dt, render_target);
+        // Stay with the camera where the map bounds stopped it.
+        self.position = camera.target();
+    }
+
+    /// Whether this frame's scene should be saved, clearing the request.
+    pub fn take_shutter(&mut self) -> bool {
+        std::mem::take(&mut self.shutter)
+    }
+
+    /// Writes `screen` as a PNG under `~/.cropbots/screenshots/`.
+    pub fn save_screenshot(&mut self, screen: Image) {
+        let message = match write_screenshot(screen) {
+            Ok(path) => format!("saved {path}"),
+            Err(err) => format!("screenshot failed: {err}"),
+        };
+        self.message = Some((message, MESSAGE_TIME));
+    }
+
+    /// Key hints while active, and the last screenshot's outcome.
+    pub fn draw(&self) {
+        let bottom = screen_height() - 16.0;
+        if self.active {
+            draw_text_styled(
+                &format!(
+                    "PHOTO  WASD fly, shift fast, wheel zoom, tab filter: ^y{}^0, space capture, F9 exit",
+                    self.filter.label()
+                ),
+                16.0,
+                bottom,
+                16.0,
+                WHITE,
+                TextStyle::SHADOWED,
+            );
+        }
+        if let Some((message, _)) = &self.message {
+            draw_text_styled(message, 16.0, bottom - 22.0, 16.0, LIGHTGRAY, TextStyle::SHADOWED);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_screenshot(screen: Image) -> Result<String, String> {
+    let dir = crate::helpers::cropbots_dir()
+        .ok_or("no home directory")?
+        .join("screenshots");
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    let path = dir.join(format!("cropbots-{}.png", timestamp(miniquad::date::now())));
+    let image = image::RgbaImage::from_raw(screen.width as u32, screen.height as u32, screen.bytes)
+        .ok_or("screen capture had the wrong size")?;
+    // Screen data is read bottom row first.
+    image::imageops::flip_vertical(&image)
+        .save(&path)
+        .map_err(|err| err.to_string())?;
+    Ok(path.display().to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_screenshot(_screen: Image) -> Result<String, String> {
+    Err("not available in the browser".to_string())
+}
+
+/// `unix_seconds` as a UTC `YYYY-MM-DD_HH-MM-SS` string that sorts by time.
+pub fn timestamp(unix_seconds: f64) -> String {
+    let seconds = unix_seconds.max(0.0) as i64;
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    // Civil date from days since 1970-01-01, counting in 400-year eras that start in March.
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}",
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
@@ -1,7 +1,134 @@
 use macroquad::prelude::*;
+use serde::Deserialize;
 
 use crate::helpers::{clamp_hitbox_to_rect, resolve_collisions_axis, Axis};
 use crate::map::TileMap;
+use crate::equipment::EquipOverlay;
+use crate::item::Inventory;
+use crate::level::GrowthDef;
+use crate::mount::MountDef;
+use crate::status::StatusEffects;
+
+const STARTING_COINS: u32 = 20;
+/// Around the player's feet, which `Player::position` is.
+pub const PLAYER_HITBOX: Rect = Rect::new(-6.5 / 2.0, -8.0, 6.5, 8.0);
+
+/// The player's tunable numbers, as `config.yaml` gives them and upgrades raise them.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct PlayerStats {
+    pub max_hp: f32,
+    /// Multiplies acceleration and top speed.
+    pub move_speed: f32,
+    /// Seconds before the player can dash again.
+    pub dash_cooldown: f32,
+    /// Multiplies the damage the player's tools deal.
+    pub damage: f32,
+}
+
+impl Default for PlayerStats {
+    fn default() -> Self {
+        Self {
+            max_hp: 50.0,
+            move_speed: 1.0,
+            dash_cooldown: 0.5,
+            damage: 1.0,
+        }
+    }
+}
+
+impl PlayerStats {
+    /// These stats with `mods` added on.
+    pub fn with(mut self, mods: &StatModifiers) -> Self {
+        self.max_hp += mods.max_hp;
+        self.move_speed += mods.move_speed;
+        self.dash_cooldown = (self.dash_cooldown + mods.dash_cooldown).max(0.1);
+        self.damage += mods.damage;
+        self
+    }
+}
+
+/// Amounts added to `PlayerStats`, e.g. by worn equipment.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct StatModifiers {
+    pub max_hp: f32,
+    pub move_speed: f32,
+    pub dash_cooldown: f32,
+    pub damage: f32,
+}
+
+impl StatModifiers {
+    /// The non-zero modifiers, e.g. `+10 hp, -5% speed`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.max_hp != 0.0 {
+            parts.push(format!("{:+} hp", self.max_hp));
+        }
+        if self.move_speed != 0.0 {
+            parts.push(format!("{:+}% speed", (self.move_speed * 100.0).round()));
+        }
+        if self.dash_cooldown != 0.0 {
+            parts.push(format!("{:+}s dash", self.dash_cooldown));
+        }
+        if self.damage != 0.0 {
+            parts.push(format!("{:+}% damage", (self.damage * 100.0).round()));
+        }
+        parts.join(", ")
+    }
+}
+
+/// What the player is asked to do for a step: the keys held here, or another player's sent over
+/// the network.
+#[derive(Clone, Copy, Default)]
+pub struct PlayerInput {
+    pub movement: Vec2,
+    /// Set on the step the dash key goes down.
+    pub dash: bool,
+}
+
+impl PlayerInput {
+    pub fn from_keys() -> Self {
+        let mut movement = Vec2::ZERO;
+        if is_key_down(KeyCode::D) {
+            movement.x += 1.0;
+        }
+        if is_key_down(KeyCode::A) {
+            movement.x -= 1.0;
+        }
+        if is_key_down(KeyCode::W) {
+            movement.y -= 1.0;
+        }
+        if is_key_down(KeyCode::S) {
+            movement.y += 1.0;
+        }
+        Self {
+            movement,
+            dash: is_key_pressed(KeyCode::Space),
+        }
+    }
+
+    /// The second local player's keys: the arrows, and right shift to dash.
+    pub fn from_arrow_keys() -> Self {
+        let mut movement = Vec2::ZERO;
+        if is_key_down(KeyCode::Right) {
+            movement.x += 1.0;
+        }
+        if is_key_down(KeyCode::Left) {
+            movement.x -= 1.0;
+        }
+        if is_key_down(KeyCode::Up) {
+            movement.y -= 1.0;
+        }
+        if is_key_down(KeyCode::Down) {
+            movement.y += 1.0;
+        }
+        Self {
+            movement,
+            dash: is_key_pressed(KeyCode::RightShift),
+        }
+    }
+}
 
 pub struct Player {
     pos: Vec2,
@@ -16,11 +143,20 @@ pub struct Player {
     collision_scratch: Vec<Rect>,
     hp: f32,
     max_hp: f32,
+    status_effects: StatusEffects,
+    inventory: Inventory,
+    coins: u32,
+    /// The mount being ridden, if any.
+    riding: Option<MountDef>,
+    xp: f32,
+    stats: PlayerStats,
+    /// Sprites of worn equipment, drawn over the player in order.
+    overlays: Vec<EquipOverlay>,
 }
 
 impl Player {
-    pub fn new(pos: Vec2, texture: Texture2D, hitbox: Rect) -> Self {
-        let max_hp = 50.0;
+    pub fn new(pos: Vec2, texture: Texture2D, hitbox: Rect, stats: PlayerStats) -> Self {
+        let max_hp = stats.max_hp.max(1.0);
         Self {
             pos,
             vel: Vec2::ZERO,
@@ -34,37 +170,31 @@ impl Player {
             collision_scratch: Vec::with_capacity(25),
             hp: max_hp,
             max_hp,
+            status_effects: StatusEffects::default(),
+            inventory: Inventory::default(),
+            coins: STARTING_COINS,
+            riding: None,
+            xp: 0.0,
+            stats,
+            overlays: Vec::new(),
         }
     }
 
-    pub fn update(&mut self, map: &TileMap) {
-        let dt = get_frame_time();
-
-        let mut input = vec2(0.0, 0.0);
-        if is_key_down(KeyCode::D) {
-            input.x += 1.0;
-        }
-        if is_key_down(KeyCode::A) {
-            input.x -= 1.0;
-        }
-        if is_key_down(KeyCode::W) {
-            input.y -= 1.0;
-        }
-        if is_key_down(KeyCode::S) {
-            input.y += 1.0;
-        }
-
+    /// Moves the player `dt` seconds on `input`, whoever is holding the keys.
+    pub fn step(&mut self, input: PlayerInput, map: &TileMap, dt: f32) {
+        let PlayerInput { movement: mut input, dash } = input;
         if input.length_squared() > 0.0 {
             input = input.normalize();
             self.last_move_dir = input;
         }
 
-        let accel = 1800.0;
-        let max_speed = 640.0;
+        let speed_scale = self.riding.map_or(1.0, |mount| mount.speed) * self.stats.move_speed.max(0.1);
+        let accel = 1800.0 * speed_scale;
+        let max_speed = 640.0 * speed_scale;
         let damping = 8.0;
         let dash_speed = 1100.0;
         let dash_duration = 0.07;
-        let dash_cooldown = 0.5;
+        let dash_cooldown = self.stats.dash_cooldown.max(0.0);
 
         if self.dash_cooldown > 0.0 {
             self.dash_cooldown = (self.dash_cooldown - dt).max(0.0);
@@ -76,7 +206,8 @@ impl Player {
 
         if self.dash_timer <= 0.0
             && self.dash_cooldown <= 0.0
-            && is_key_pressed(KeyCode::Space)
+            && self.riding.is_none()
+            && dash
         {
             let dir = if input.length_squared() > 0.0 {
                 input
@@ -153,10 +284,12 @@ impl Player {
         let scale = 0.5;
         let center_x = self.texture.width() as f32 * scale / 2.0;
         let center_y = self.texture.height() as f32 * scale / 2.0;
+        let seat = self.riding.map_or(0.0, |mount| mount.height);
+        let origin = vec2(self.pos.x - center_x / 2.0, self.pos.y - center_y - seat);
         draw_texture_ex(
             &self.texture,
-            self.pos.x - center_x / 2.0,
-            self.pos.y - center_y,
+            origin.x,
+            origin.y,
             WHITE,
             DrawTextureParams {
                 dest_size: Some(Vec2::new(self.texture.width() / 2 as f32 * scale, self.texture.height() / 2 as f32 * scale)),
@@ -164,12 +297,70 @@ impl Player {
                 ..Default::default()
             },
         );
+        for overlay in &self.overlays {
+            let pos = origin + overlay.offset;
+            overlay.sprite.draw(
+                pos.x,
+                pos.y,
+                overlay.color,
+                DrawTextureParams {
+                    dest_size: Some(overlay.size),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    pub fn set_overlays(&mut self, overlays: Vec<EquipOverlay>) {
+        self.overlays = overlays;
+    }
+
+    pub fn draw_shadow(&self) {
+        let hitbox = self.world_hitbox();
+        crate::entity::draw_blob_shadow(vec2(hitbox.center().x, hitbox.bottom()), hitbox.w * 1.4, 0.3);
     }
 
     pub fn position(&self) -> Vec2 {
         self.pos
     }
 
+    /// The way the player last moved; down until they first do.
+    pub fn facing(&self) -> Vec2 {
+        if self.last_move_dir == Vec2::ZERO { Vec2::Y } else { self.last_move_dir }
+    }
+
+    pub fn riding(&self) -> Option<MountDef> {
+        self.riding
+    }
+
+    /// Puts the player on a mount with `mount`'s stats, or back on foot with None.
+    pub fn set_riding(&mut self, mount: Option<MountDef>) {
+        self.riding = mount;
+        self.dash_timer = 0.0;
+    }
+
+    /// Someone else's player, drawn like this one, standing at `pos`.
+    pub fn remote(&self, pos: Vec2) -> Self {
+        Self::new(pos, self.texture.clone(), self.hitbox, self.stats)
+    }
+
+    /// Shifts the player without touching their momentum, for small corrections from elsewhere.
+    pub fn nudge(&mut self, offset: Vec2) {
+        self.pos += offset;
+    }
+
+    /// Moves a player steered from elsewhere to `pos`, with the velocity and facing of having
+    /// walked there over `dt` so they animate as if they had.
+    pub fn follow(&mut self, pos: Vec2, dt: f32) {
+        if dt > 0.0 {
+            self.vel = (pos - self.pos) / dt;
+            if let Some(dir) = self.vel.try_normalize() {
+                self.last_move_dir = dir;
+            }
+        }
+        self.pos = pos;
+    }
+
     pub fn set_position(&mut self, pos: Vec2) {
         self.pos = pos;
         self.vel = Vec2::ZERO;
@@ -178,6 +369,12 @@ impl Player {
         self.dash_dir = Vec2::ZERO;
     }
 
+    pub fn sprite_rect(&self) -> Rect {
+        let w = self.texture.width() / 4.0;
+        let h = self.texture.height() / 4.0;
+        Rect::new(self.pos.x - self.texture.width() / 8.0, self.pos.y - h, w, h)
+    }
+
     pub fn world_hitbox(&self) -> Rect {
         Rect::new(
             self.pos.x + self.hitbox.x,
@@ -217,6 +414,35 @@ impl Player {
         self.max_hp = new_max;
     }
 
+    pub fn stats(&self) -> PlayerStats {
+        self.stats
+    }
+
+    /// Swaps in new stats, e.g. after an upgrade. Max hp moves by the difference, so what
+    /// levels added stays.
+    pub fn set_stats(&mut self, stats: PlayerStats) {
+        let hp_gain = stats.max_hp - self.stats.max_hp;
+        self.stats = stats;
+        self.set_max_hp(self.max_hp + hp_gain);
+        self.heal(hp_gain);
+    }
+
+    pub fn xp(&self) -> f32 {
+        self.xp
+    }
+
+    /// Adds `amount` XP, raising max hp by `growth` for each level reached. Returns the levels
+    /// gained.
+    pub fn gain_xp(&mut self, growth: &GrowthDef, amount: f32) -> u32 {
+        let before = growth.level_for(self.xp);
+        self.xp += amount.max(0.0);
+        let gained = growth.level_for(self.xp).saturating_sub(before);
+        let hp_gain = growth.stat("hp") * gained as f32;
+        self.add_max_hp(hp_gain);
+        self.heal(hp_gain);
+        gained
+    }
+
     pub fn hp(&self) -> f32 {
         self.hp
     }
@@ -225,6 +451,39 @@ impl Player {
         self.max_hp
     }
 
+    pub fn status_effects(&self) -> &StatusEffects {
+        &self.status_effects
+    }
+
+    pub fn status_effects_mut(&mut self) -> &mut StatusEffects {
+        &mut self.status_effects
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    pub fn inventory_mut(&mut self) -> &mut Inventory {
+        &mut self.inventory
+    }
+
+    pub fn coins(&self) -> u32 {
+        self.coins
+    }
+
+    pub fn add_coins(&mut self, amount: u32) {
+        self.coins = self.coins.saturating_add(amount);
+    }
+
+    /// Pays `amount` if the player can afford it.
+    pub fn spend_coins(&mut self, amount: u32) -> bool {
+        if self.coins < amount {
+            return false;
+        }
+        self.coins -= amount;
+        true
+    }
+
     pub fn velocity(&self) -> Vec2 {
         self.vel
     }
@@ -0,0 +1,150 @@
+use macroquad::prelude::*;
+use std::fmt::Write;
+use crate::font::{TextStyle, draw_text_styled, measure_text};
+
+/// Seconds a popup stays on screen.
+const LIFETIME: f32 = 0.9;
+/// World units per second a popup drifts upward.
+const RISE_SPEED: f32 = 18.0;
+/// One font pixel per world pixel, matching the sprites.
+const FONT_SIZE: f32 = 8.0;
+/// Crits draw at twice the size under a small label.
+const CRIT_FONT_SIZE: f32 = 16.0;
+/// Popups kept at once; past this the oldest is reused.
+const POOL_SIZE: usize = 48;
+
+const DAMAGE_COLOR: Color = WHITE;
+const PLAYER_DAMAGE_COLOR: Color = Color::new(1.0, 0.35, 0.3, 1.0);
+const HEAL_COLOR: Color = Color::new(0.45, 0.9, 0.4, 1.0);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PopupKind {
+    Damage,
+    /// Damage taken by the player, tinted so it stands apart from hits the player lands.
+    PlayerDamage,
+    Crit,
+    Heal,
+}
+
+struct Popup {
+    pos: Vec2,
+    kind: PopupKind,
+    /// Reused between spawns so a busy fight doesn't allocate.
+    text: String,
+    age: f32,
+}
+
+/// Floating numbers shown over whatever just took damage or healed.
+pub struct DamageNumbers {
+    popups: Vec<Popup>,
+    enabled: bool,
+}
+
+impl Default for DamageNumbers {
+    fn default() -> Self {
+        Self {
+            popups: Vec::with_capacity(POOL_SIZE),
+            enabled: true,
+        }
+    }
+}
+
+impl DamageNumbers {
+    /// Turning popups off also drops the ones on screen.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    /// Shows `amount` rising from `pos`, in world space.
+    pub fn spawn(&mut self, pos: Vec2, amount: f32, kind: PopupKind) {
+        if !self.enabled || amount <= 0.0 {
+            return;
+        }
+        let popup = self.free_popup();
+        popup.pos = pos;
+        popup.kind = kind;
+        popup.age = 0.0;
+        popup.text.clear();
+        if kind == PopupKind::Heal {
+            popup.text.push('+');
+        }
+        let _ = if amount < 1.0 {
+            write!(popup.text, "{amount:.1}")
+        } else {
+            write!(popup.text, "{}", amount.round() as i64)
+        };
+    }
+
+    /// A popup that has finished, a new one while the pool has room, or else the oldest.
+    fn free_popup(&mut self) -> &mut Popup {
+        let index = match self.popups.iter().position(|popup| popup.age >= LIFETIME) {
+            Some(index) => index,
+            None if self.popups.len() < POOL_SIZE => {
+                self.popups.push(Popup {
+                    pos: Vec2::ZERO,
+                    kind: PopupKind::Damage,
+                    text: String::new(),
+                    age: LIFETIME,
+                });
+                self.popups.len() - 1
+            }
+            None => self
+                .popups
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.age.total_cmp(&b.1.age))
+                .map_or(0, |(index, _)| index),
+        };
+        &mut self.popups[index]
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for popup in self.popups.iter_mut().filter(|popup| popup.age < LIFETIME) {
+            popup.age += dt;
+            popup.pos.y -= RISE_SPEED * dt;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for popup in &mut self.popups {
+            popup.age = LIFETIME;
+        }
+    }
+
+    /// Draws with the world camera active.
+    pub fn draw(&self) {
+        for popup in self.popups.iter().filter(|popup| popup.age < LIFETIME) {
+            let fade = (1.0 - popup.age / LIFETIME).clamp(0.0, 1.0).sqrt();
+            let (color, size) = match popup.kind {
+                PopupKind::Damage => (DAMAGE_COLOR, FONT_SIZE),
+                PopupKind::PlayerDamage => (PLAYER_DAMAGE_COLOR, FONT_SIZE),
+                PopupKind::Crit => (GOLD, CRIT_FONT_SIZE),
+                PopupKind::Heal => (HEAL_COLOR, FONT_SIZE),
+            };
+            let color = Color { a: color.a * fade, ..color };
+            let width = measure_text(&popup.text, size).width;
+            draw_text_styled(
+                &popup.text,
+                (popup.pos.x - width * 0.5).round(),
+                popup.pos.y.round(),
+                size,
+                color,
+                TextStyle::OUTLINED,
+            );
+            if popup.kind == PopupKind::Crit {
+                let label = measure_text("CRIT", FONT_SIZE);
+                draw_text_styled(
+                    "CRIT",
+                    (popup.pos.x - label.width * 0.5).round(),
+                    (popup.pos.y - CRIT_FONT_SIZE).round(),
+                    FONT_SIZE,
+                    color,
+                    TextStyle::OUTLINED,
+                );
+            }
+        }
+    }
+}
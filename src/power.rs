@@ -0,0 +1,214 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use crate::entity::{Entity, EntityDatabase, EntityKind};
+use crate::farm::FarmState;
+use crate::font::{TextStyle, draw_text_styled, measure_text};
+use crate::map::StructureDef;
+
+/// Seconds between network solves.
+const TICK_SECONDS: f32 = 0.5;
+/// Tiles from a charging pad's center a bot has to stand within to charge.
+const CHARGE_REACH: f32 = 1.0;
+const LINK_COLOR: Color = Color::new(1.0, 0.85, 0.3, 0.6);
+const UNPOWERED_COLOR: Color = Color::new(0.9, 0.25, 0.2, 0.9);
+const POWERED_COLOR: Color = Color::new(0.4, 0.9, 0.4, 0.9);
+
+/// A structure's part in the farm's power network, from `power` in its json.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct PowerDef {
+    /// Energy a second it feeds its network.
+    pub produces: f32,
+    /// Energy a second it needs to run.
+    pub consumes: f32,
+    /// Tiles it reaches to link up with other powered structures.
+    pub range: f32,
+    /// Hp a second given back to bots standing on it while it runs.
+    pub charges: f32,
+}
+
+struct Node {
+    x: usize,
+    y: usize,
+    center: Vec2,
+    def: PowerDef,
+    /// What stands on the structure, held still while it's unpowered.
+    entity: Option<String>,
+    network: usize,
+    powered: bool,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Network {
+    supply: f32,
+    demand: f32,
+}
+
+/// Generators, conduits and consumers built on the farm, grouped into networks of structures in
+/// reach of each other. Each network's supply runs its consumers in the order they were built
+/// until it runs out. P toggles the overlay; build mode always shows it.
+#[derive(Default)]
+pub struct PowerGrid {
+    nodes: Vec<Node>,
+    links: Vec<(usize, usize)>,
+    networks: Vec<Network>,
+    timer: f32,
+    overlay: bool,
+}
+
+impl PowerGrid {
+    pub fn update_input(&mut self) {
+        if is_key_pressed(KeyCode::P) {
+            self.overlay = !self.overlay;
+        }
+    }
+
+    /// Solves the networks every tick and runs what they power: charging pads heal bots, and
+    /// mounted entities like turrets hold fire without power.
+    pub fn update(
+        &mut self,
+        dt: f32,
+        farm: &FarmState,
+        structures: &[StructureDef],
+        entities: &mut [Entity],
+        db: &EntityDatabase,
+        tile_size: f32,
+    ) {
+        self.timer -= dt;
+        if self.timer <= 0.0 {
+            self.timer = TICK_SECONDS;
+            self.solve(farm, structures, tile_size);
+            self.run(entities, db, tile_size);
+        }
+        for node in self.nodes.iter().filter(|node| node.powered && node.def.charges > 0.0) {
+            for ent in entities.iter_mut() {
+                if db.entities[ent.instance.def].kind == EntityKind::Friend
+                    && !ent.instance.is_dying()
+                    && ent.hitbox(db).center().distance(node.center) <= CHARGE_REACH * tile_size
+                {
+                    ent.instance.hp = (ent.instance.hp + node.def.charges * dt).min(ent.instance.max_hp);
+                }
+            }
+        }
+    }
+
+    /// Whether the built structure at (`x`, `y`) draws power and has it. Anything off the
+    /// network counts as unpowered.
+    pub fn is_powered(&self, x: usize, y: usize) -> bool {
+        self.nodes.iter().any(|node| node.x == x && node.y == y && node.powered)
+    }
+
+    fn solve(&mut self, farm: &FarmState, structures: &[StructureDef], tile_size: f32) {
+        self.nodes = farm
+            .built
+            .iter()
+            .filter_map(|built| {
+                let def = structures.iter().find(|def| def.id == built.id)?;
+                let size = vec2(def.structure.width() as f32, def.structure.height() as f32);
+                Some(Node {
+                    x: built.x,
+                    y: built.y,
+                    center: (vec2(built.x as f32, built.y as f32) + size * 0.5) * tile_size,
+                    def: def.power?,
+                    entity: def.entity.clone(),
+                    network: usize::MAX,
+                    powered: false,
+                })
+            })
+            .collect();
+        self.links.clear();
+        for a in 0..self.nodes.len() {
+            for b in a + 1..self.nodes.len() {
+                let reach = self.nodes[a].def.range.max(self.nodes[b].def.range) * tile_size;
+                if self.nodes[a].center.distance(self.nodes[b].center) <= reach {
+                    self.links.push((a, b));
+                }
+            }
+        }
+
+        self.networks.clear();
+        for start in 0..self.nodes.len() {
+            if self.nodes[start].network != usize::MAX {
+                continue;
+            }
+            let network = self.networks.len();
+            self.networks.push(Network::default());
+            let mut stack = vec![start];
+            self.nodes[start].network = network;
+            while let Some(index) = stack.pop() {
+                for &(a, b) in &self.links {
+                    let next = if a == index { b } else if b == index { a } else { continue };
+                    if self.nodes[next].network == usize::MAX {
+                        self.nodes[next].network = network;
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+
+        for node in &self.nodes {
+            let network = &mut self.networks[node.network];
+            network.supply += node.def.produces.max(0.0);
+            network.demand += node.def.consumes.max(0.0);
+        }
+        let mut left: Vec<f32> = self.networks.iter().map(|network| network.supply).collect();
+        for node in &mut self.nodes {
+            let need = node.def.consumes.max(0.0);
+            node.powered = need <= left[node.network];
+            if node.powered {
+                left[node.network] -= need;
+            }
+        }
+    }
+
+    fn run(&self, entities: &mut [Entity], db: &EntityDatabase, tile_size: f32) {
+        for node in self.nodes.iter().filter(|node| node.def.consumes > 0.0) {
+            let Some(mounted) = node.entity.as_deref() else {
+                continue;
+            };
+            for ent in entities.iter_mut() {
+                if db.entities[ent.instance.def].id == mounted
+                    && ent.hitbox(db).center().distance(node.center) < tile_size * 0.5
+                {
+                    ent.instance.unpowered = !node.powered;
+                }
+            }
+        }
+    }
+
+    /// Draws links, node states and each network's supply over demand, in world space.
+    pub fn draw_overlay(&self, view: Rect, building: bool, tile_size: f32) {
+        if !self.overlay && !building {
+            return;
+        }
+        for &(a, b) in &self.links {
+            let (a, b) = (self.nodes[a].center, self.nodes[b].center);
+            draw_line(a.x, a.y, b.x, b.y, 1.0, LINK_COLOR);
+        }
+        for node in &self.nodes {
+            if !view.contains(node.center) {
+                continue;
+            }
+            if node.def.range > 0.0 {
+                let range = node.def.range * tile_size;
+                draw_circle_lines(node.center.x, node.center.y, range, 0.5, Color::new(1.0, 0.85, 0.3, 0.25));
+            }
+            let color = if node.def.consumes <= 0.0 {
+                LINK_COLOR
+            } else if node.powered {
+                POWERED_COLOR
+            } else {
+                UNPOWERED_COLOR
+            };
+            draw_circle(node.center.x, node.center.y, 2.0, color);
+            if node.def.produces > 0.0 {
+                let network = self.networks[node.network];
+                let text = format!("{:.0}/{:.0}", network.demand, network.supply);
+                let width = measure_text(&text, 8.0).width;
+                let pos = vec2(node.center.x - width * 0.5, node.center.y - tile_size * 0.5 - 2.0).round();
+                let color = if network.demand > network.supply { UNPOWERED_COLOR } else { WHITE };
+                draw_text_styled(&text, pos.x, pos.y, 8.0, color, TextStyle::OUTLINED);
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use macroquad::file::load_string;
+use macroquad::miniquad;
+use serde::Deserialize;
+use std::future::{Future, poll_fn};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::Poll;
+use crate::helpers::data_path;
+
+pub const PRELOAD_PATH: &str = "src/preload.yaml";
+/// Seconds of synchronous loading, as every native load is, to do before letting a frame draw.
+const FRAME_BUDGET: f64 = 1.0 / 60.0;
+
+#[derive(Debug)]
+pub enum PreloadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for PreloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for PreloadError {}
+
+impl From<std::io::Error> for PreloadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for PreloadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+/// Where everything loaded behind the startup spinner comes from. Missing keys keep the paths
+/// the game ships with.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct PreloadManifest {
+    pub tileset_json: String,
+    pub tileset_png: String,
+    pub structures: String,
+    pub interiors: String,
+    pub player_texture: String,
+    pub heart_full: String,
+    pub heart_empty: String,
+    pub hotbar_slot: String,
+    pub entities: String,
+    pub particles: String,
+    pub sounds: String,
+    pub footsteps: String,
+    pub audio_events: String,
+    pub ambient: String,
+    pub triggers: String,
+    pub cutscenes: String,
+    pub raids: String,
+    pub status_effects: String,
+    pub items: String,
+    pub shops: String,
+    pub recipes: String,
+    pub upgrades: String,
+    pub crops: String,
+    pub backdrops: String,
+    pub decals: String,
+    pub locales: String,
+    pub content: String,
+}
+
+impl Default for PreloadManifest {
+    fn default() -> Self {
+        Self {
+            tileset_json: "src/assets/tileset.json".to_string(),
+            tileset_png: "src/assets/tileset.png".to_string(),
+            structures: "src/structure".to_string(),
+            interiors: "src/interior".to_string(),
+            player_texture: "src/assets/objects/player08.png".to_string(),
+            heart_full: "src/assets/ui/heart.png".to_string(),
+            heart_empty: "src/assets/ui/heart-empty.png".to_string(),
+            hotbar_slot: "src/assets/ui/hotbar-slot.png".to_string(),
+            entities: "src/entity".to_string(),
+            particles: "src/particle".to_string(),
+            sounds: "src/sound".to_string(),
+            footsteps: "src/sound/terrain".to_string(),
+            audio_events: "src/sound/events".to_string(),
+            ambient: "src/ambient".to_string(),
+            triggers: "src/trigger".to_string(),
+            cutscenes: "src/cutscene".to_string(),
+            raids: "src/raid".to_string(),
+            status_effects: "src/status".to_string(),
+            items: "src/item".to_string(),
+            shops: "src/shop".to_string(),
+            recipes: "src/recipe".to_string(),
+            upgrades: "src/upgrade".to_string(),
+            crops: "src/crop".to_string(),
+            backdrops: "src/backdrop".to_string(),
+            decals: "src/decal".to_string(),
+            locales: "src/locale".to_string(),
+            content: "src/content.yaml".to_string(),
+        }
+    }
+}
+
+impl PreloadManifest {
+    pub async fn load_from(path: impl AsRef<Path>) -> Result<Self, PreloadError> {
+        let path = path.as_ref();
+        let raw_str = if cfg!(target_arch = "wasm32") {
+            load_string(&data_path(&path.to_string_lossy()))
+                .await
+                .map_err(|err| PreloadError::Io(std::io::Error::other(err.to_string())))?
+        } else {
+            std::fs::read_to_string(path)?
+        };
+        Ok(serde_yaml::from_str(&raw_str)?)
+    }
+}
+
+/// Loads started together and polled side by side, each writing its result into a slot the
+/// caller reads back once `is_done`. On the web every fetch is in flight at once; native loads
+/// finish as they're polled, so `step` hands back between them to keep the spinner turning.
+#[derive(Default)]
+pub struct Preload<'a> {
+    tasks: Vec<Pin<Box<dyn Future<Output = ()> + 'a>>>,
+    total: usize,
+}
+
+impl<'a> Preload<'a> {
+    pub fn add<T: 'a>(&mut self, slot: &'a mut Option<T>, future: impl Future<Output = T> + 'a) {
+        self.tasks.push(Box::pin(async move {
+            *slot = Some(future.await);
+        }));
+        self.total += 1;
+    }
+
+    /// Polls the unfinished loads in turn until each is waiting, or a frame's worth of time has
+    /// gone into ones that finished on the spot.
+    pub async fn step(&mut self) {
+        let started = miniquad::date::now();
+        poll_fn(|cx| {
+            let mut index = 0;
+            while index < self.tasks.len() {
+                if self.tasks[index].as_mut().poll(cx).is_pending() {
+                    index += 1;
+                    continue;
+                }
+                drop(self.tasks.remove(index));
+                if miniquad::date::now() - started > FRAME_BUDGET {
+                    break;
+                }
+            }
+            Poll::Ready(())
+        })
+        .await;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Loads finished over loads added.
+    pub fn progress(&self) -> f32 {
+        (self.total - self.tasks.len()) as f32 / self.total.max(1) as f32
+    }
+}
+
+/// The result a finished `Preload` left in `slot`.
+pub fn loaded<T>(slot: Option<T>) -> T {
+    slot.expect("preload finished without filling a slot")
+}
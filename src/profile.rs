@@ -0,0 +1,191 @@
+use macroquad::prelude::*;
+use std::collections::VecDeque;
+use crate::font::draw_text;
+
+/// Frames kept for the graph and the rolling averages, about two seconds at 60 fps.
+const HISTORY: usize = 120;
+const GRAPH_WIDTH: f32 = 240.0;
+const GRAPH_HEIGHT: f32 = 80.0;
+/// Milliseconds at the top of the graph: two 60 fps frames, so spikes show without rescaling.
+const GRAPH_MS: f32 = 33.3;
+/// A 60 fps frame, drawn as a line across the graph.
+const BUDGET_MS: f32 = 16.7;
+const LINE_HEIGHT: f32 = 16.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    EntityUpdate,
+    ParticleUpdate,
+    ChunkRebuild,
+    WorldDraw,
+    Hud,
+}
+
+impl Section {
+    const ALL: [Section; 5] = [
+        Self::EntityUpdate,
+        Self::ParticleUpdate,
+        Self::ChunkRebuild,
+        Self::WorldDraw,
+        Self::Hud,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::EntityUpdate => "entities",
+            Self::ParticleUpdate => "particles",
+            Self::ChunkRebuild => "chunks",
+            Self::WorldDraw => "world draw",
+            Self::Hud => "hud",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Self::EntityUpdate => Color::new(0.95, 0.4, 0.35, 1.0),
+            Self::ParticleUpdate => Color::new(0.95, 0.75, 0.3, 1.0),
+            Self::ChunkRebuild => Color::new(0.45, 0.8, 0.4, 1.0),
+            Self::WorldDraw => Color::new(0.35, 0.6, 0.95, 1.0),
+            Self::Hud => Color::new(0.75, 0.5, 0.9, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct FrameTimings {
+    sections: [f32; Section::ALL.len()],
+    frame: f32,
+    /// Chunks still waiting to be rebuilt once the frame's chunk work was done.
+    chunk_queue: usize,
+}
+
+/// Per-system CPU time for recent frames, toggled with F4. Draw passes only count the time
+/// spent batching; the GPU work lands in whichever section flushes it, usually the next frame's
+/// `next_frame`, which shows up as "other".
+pub struct Profiler {
+    visible: bool,
+    current: FrameTimings,
+    history: VecDeque<FrameTimings>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            current: FrameTimings::default(),
+            history: VecDeque::with_capacity(HISTORY),
+        }
+    }
+}
+
+impl Profiler {
+    pub fn update(&mut self) {
+        if is_key_pressed(KeyCode::F4) {
+            self.visible = !self.visible;
+        }
+    }
+
+    /// A timestamp to pass to `record` once the section's work is done.
+    pub fn start(&self) -> f64 {
+        miniquad::date::now()
+    }
+
+    /// Adds the time since `started` to `section`; a section timed twice in a frame sums.
+    pub fn record(&mut self, section: Section, started: f64) {
+        let index = Section::ALL.iter().position(|&s| s == section).unwrap_or(0);
+        self.current.sections[index] += ((miniquad::date::now() - started) * 1000.0) as f32;
+    }
+
+    /// Notes how many chunks are left queued for rebuilding this frame.
+    pub fn set_chunk_queue(&mut self, depth: usize) {
+        self.current.chunk_queue = depth;
+    }
+
+    /// Closes the frame that took `frame_time` seconds end to end.
+    pub fn end_frame(&mut self, frame_time: f32) {
+        self.current.frame = frame_time * 1000.0;
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(std::mem::take(&mut self.current));
+    }
+
+    fn averages(&self) -> FrameTimings {
+        let mut sum = FrameTimings::default();
+        for frame in &self.history {
+            for (total, ms) in sum.sections.iter_mut().zip(frame.sections) {
+                *total += ms;
+            }
+            sum.frame += frame.frame;
+        }
+        let count = self.history.len().max(1) as f32;
+        for total in &mut sum.sections {
+            *total /= count;
+        }
+        sum.frame /= count;
+        sum
+    }
+
+    /// Stacked bars per frame, oldest on the left, with rolling averages underneath.
+    pub fn draw(&self) {
+        if !self.visible {
+            return;
+        }
+        let x = screen_width() - GRAPH_WIDTH - 16.0;
+        let y = 160.0;
+        let panel_height = GRAPH_HEIGHT + LINE_HEIGHT * (Section::ALL.len() as f32 + 3.5);
+        draw_rectangle(x - 8.0, y - 8.0, GRAPH_WIDTH + 16.0, panel_height, Color::new(0.0, 0.0, 0.0, 0.7));
+        draw_line(x, y, x + GRAPH_WIDTH, y, 1.0, Color::new(1.0, 1.0, 1.0, 0.2));
+        let budget_y = y + GRAPH_HEIGHT * (1.0 - BUDGET_MS / GRAPH_MS);
+        draw_line(x, budget_y, x + GRAPH_WIDTH, budget_y, 1.0, Color::new(1.0, 1.0, 1.0, 0.35));
+
+        let bar_width = GRAPH_WIDTH / HISTORY as f32;
+        let px_per_ms = GRAPH_HEIGHT / GRAPH_MS;
+        let start = HISTORY - self.history.len();
+        for (index, frame) in self.history.iter().enumerate() {
+            let bar_x = x + (start + index) as f32 * bar_width;
+            let mut bottom = y + GRAPH_HEIGHT;
+            let frame_top = (bottom - frame.frame * px_per_ms).max(y);
+            draw_rectangle(bar_x, frame_top, bar_width, bottom - frame_top, Color::new(1.0, 1.0, 1.0, 0.15));
+            for (section, ms) in Section::ALL.iter().zip(frame.sections) {
+                let height = (ms * px_per_ms).min(bottom - y);
+                bottom -= height;
+                draw_rectangle(bar_x, bottom, bar_width, height, section.color());
+            }
+        }
+
+        let averages = self.averages();
+        let mut line_y = y + GRAPH_HEIGHT + LINE_HEIGHT;
+        let measured: f32 = averages.sections.iter().sum();
+        for (section, ms) in Section::ALL.iter().zip(averages.sections) {
+            draw_rectangle(x, line_y - 8.0, 8.0, 8.0, section.color());
+            draw_text(&format!("{:<11}{ms:6.2} ms", section.label()), x + 14.0, line_y, 16.0, WHITE);
+            line_y += LINE_HEIGHT;
+        }
+        draw_text(
+            &format!("{:<11}{:6.2} ms", "other", (averages.frame - measured).max(0.0)),
+            x + 14.0,
+            line_y,
+            16.0,
+            LIGHTGRAY,
+        );
+        line_y += LINE_HEIGHT;
+        draw_text(
+            &format!("{:<11}{:6.2} ms", "frame", averages.frame),
+            x + 14.0,
+            line_y,
+            16.0,
+            WHITE,
+        );
+        line_y += LINE_HEIGHT;
+        let queued = self.history.back().map_or(0, |frame| frame.chunk_queue);
+        let peak = self.history.iter().map(|frame| frame.chunk_queue).max().unwrap_or(0);
+        draw_text(
+            &format!("{:<11}{queued:6} (peak {peak})", "chunk queue"),
+            x + 14.0,
+            line_y,
+            16.0,
+            Section::ChunkRebuild.color(),
+        );
+    }
+}
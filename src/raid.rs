@@ -0,0 +1,395 @@
+use macroquad::prelude::*;
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::path::Path;
+use crate::crop::CropField;
+use crate::dialogue::DialogueBox;
+use crate::entity::{DamageEvent, Entity, EntityDatabase, MovementRegistry, Target};
+use crate::farm::DayClock;
+use crate::font::{TextStyle, draw_text_styled, measure_text};
+use crate::helpers::{data_path, load_wasm_manifest_files, random_u32};
+use crate::item::{GroundItems, ItemDatabase, ItemStack};
+use crate::level::GrowthDef;
+use crate::map::TileMap;
+use crate::path;
+use crate::player::Player;
+use crate::scene;
+
+const RAID_COLOR: Color = Color::new(1.0, 0.45, 0.35, 1.0);
+
+#[derive(Debug)]
+pub enum RaidLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for RaidLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RaidLoadError {}
+
+impl From<std::io::Error> for RaidLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for RaidLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+/// Enemies that come for the farm at night, in waves.
+#[derive(Clone, Deserialize)]
+pub struct RaidDef {
+    pub id: String,
+    pub name: String,
+    /// First day the raid can come; later raids that are due win over earlier ones.
+    #[serde(default)]
+    pub first_day: u32,
+    /// Days between raids.
+    #[serde(default = "default_every")]
+    pub every: u32,
+    /// How far through the day it starts, from 0 to 1.
+    #[serde(default = "default_start")]
+    pub start: f32,
+    pub waves: Vec<RaidWave>,
+    #[serde(default)]
+    pub reward: RaidReward,
+}
+
+fn default_every() -> u32 {
+    1
+}
+
+fn default_start() -> f32 {
+    0.8
+}
+
+impl RaidDef {
+    fn due(&self, day: u32) -> bool {
+        day >= self.first_day && (day - self.first_day).is_multiple_of(self.every.max(1))
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct RaidWave {
+    /// Seconds after the raid starts.
+    #[serde(default)]
+    pub delay: f32,
+    pub spawns: Vec<RaidSpawn>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct RaidSpawn {
+    pub entity: String,
+    #[serde(default = "default_count")]
+    pub count: u32,
+    /// Damage per second to breakable structures the raider touches.
+    #[serde(default)]
+    pub chop_damage: f32,
+    /// Damage per second to the crop the raider stands on.
+    #[serde(default = "default_crop_damage")]
+    pub crop_damage: f32,
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+fn default_crop_damage() -> f32 {
+    1.0
+}
+
+/// Paid out for lasting until the raid is beaten or the night ends.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RaidReward {
+    pub coins: u32,
+    pub xp: f32,
+    pub items: Vec<ItemStack>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    /// Every raider was beaten.
+    Repelled,
+    /// The night ended with raiders still about; they flee.
+    Dawn,
+    PlayerDied,
+    /// The player left the farm.
+    Abandoned,
+}
+
+struct ActiveRaid {
+    def: usize,
+    day: u32,
+    elapsed: f32,
+    next_wave: usize,
+    raiders: Vec<u64>,
+    spawned: u32,
+    /// Tiles raiders have bitten into, to tell crops they destroyed.
+    chewed: Vec<(usize, usize)>,
+    crops_lost: u32,
+}
+
+/// What a raid reaches into while it runs.
+pub struct RaidContext<'a> {
+    pub clock: DayClock,
+    /// False off the farm or while the player is in an interior, which calls the raid off.
+    pub on_farm: bool,
+    pub player_dead: bool,
+    pub crops: &'a CropField,
+    pub map: &'a TileMap,
+    pub entities: &'a mut Vec<Entity>,
+    pub db: &'a EntityDatabase,
+    pub registry: &'a MovementRegistry,
+    pub player: &'a mut Player,
+    pub growth: &'a GrowthDef,
+    pub item_db: &'a ItemDatabase,
+    pub ground_items: &'a mut GroundItems,
+    pub dialogue: &'a mut DialogueBox,
+    /// Raiders' bites at crops, dealt along with the frame's other damage.
+    pub damage: &'a mut Vec<DamageEvent>,
+}
+
+/// Starts the raid due each night on the farm and runs it until it's beaten, the night ends,
+/// or the player dies or leaves.
+pub struct Raids {
+    defs: Vec<RaidDef>,
+    active: Option<ActiveRaid>,
+    /// Day of the last raid, so one night doesn't get two.
+    last_day: Option<u32>,
+}
+
+impl Raids {
+    pub fn empty() -> Self {
+        Self {
+            defs: Vec::new(),
+            active: None,
+            last_day: None,
+        }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, RaidLoadError> {
+        let dir = dir.as_ref();
+        let mut defs = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let names = load_wasm_manifest_files(&dir, &["virat_raid.yaml"]).await;
+            for name in names {
+                let path = format!("{}/{}", dir, name);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| RaidLoadError::Io(std::io::Error::other(err.to_string())))?;
+                defs.push(serde_yaml::from_str::<RaidDef>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                defs.push(serde_yaml::from_str::<RaidDef>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+        defs.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(Self {
+            defs,
+            ..Self::empty()
+        })
+    }
+
+    pub fn update(&mut self, dt: f32, ctx: &mut RaidContext) {
+        let day = ctx.clock.day;
+        if self.active.is_none() {
+            let due = self
+                .defs
+                .iter()
+                .enumerate()
+                .filter(|(_, def)| def.due(day) && ctx.clock.day_fraction() >= def.start)
+                .max_by_key(|(_, def)| def.first_day);
+            if let Some((index, def)) = due
+                && ctx.on_farm
+                && !ctx.player_dead
+                && self.last_day != Some(day)
+            {
+                self.last_day = Some(day);
+                self.active = Some(ActiveRaid {
+                    def: index,
+                    day,
+                    elapsed: 0.0,
+                    next_wave: 0,
+                    raiders: Vec::new(),
+                    spawned: 0,
+                    chewed: Vec::new(),
+                    crops_lost: 0,
+                });
+                ctx.dialogue.start(Some(&def.name), &["Raiders are coming for the crops!".to_string()]);
+            }
+            return;
+        }
+        let Some(raid) = self.active.as_mut() else {
+            return;
+        };
+        let def = &self.defs[raid.def];
+
+        raid.elapsed += dt;
+        while let Some(wave) = def.waves.get(raid.next_wave)
+            && raid.elapsed >= wave.delay
+        {
+            for spawn in &wave.spawns {
+                for _ in 0..spawn.count {
+                    if let Some(uid) = spawn_raider(spawn, ctx) {
+                        raid.raiders.push(uid);
+                        raid.spawned += 1;
+                    }
+                }
+            }
+            raid.next_wave += 1;
+        }
+
+        raid.raiders.retain(|&uid| {
+            ctx.entities.iter().any(|ent| ent.instance.uid == uid && !ent.instance.is_dying())
+        });
+        let crops = ctx.crops;
+        let lost = raid.chewed.iter().filter(|&&(x, y)| !crops.is_planted(x, y)).count();
+        raid.crops_lost += lost as u32;
+        raid.chewed.retain(|&(x, y)| crops.is_planted(x, y));
+        let tile_size = ctx.map.tile_size();
+        for ent in ctx.entities.iter().filter(|ent| raid.raiders.contains(&ent.instance.uid)) {
+            let center = ent.hitbox(ctx.db).center();
+            let tile = ((center.x / tile_size) as usize, (center.y / tile_size) as usize);
+            let bite = ent.instance.stats.get("crop_damage", 0.0) * dt;
+            if bite <= 0.0 || !crops.is_planted(tile.0, tile.1) {
+                continue;
+            }
+            ctx.damage.push(DamageEvent {
+                amount: bite,
+                target: Target::Position(center),
+                crit: false,
+                source: Some(ent.instance.uid),
+            });
+            if !raid.chewed.contains(&tile) {
+                raid.chewed.push(tile);
+            }
+        }
+
+        let outcome = if !ctx.on_farm {
+            Some(Outcome::Abandoned)
+        } else if ctx.player_dead {
+            Some(Outcome::PlayerDied)
+        } else if day != raid.day {
+            Some(Outcome::Dawn)
+        } else if raid.next_wave >= def.waves.len() && raid.raiders.is_empty() {
+            Some(Outcome::Repelled)
+        } else {
+            None
+        };
+        if let Some(outcome) = outcome {
+            self.finish(outcome, ctx);
+        }
+    }
+
+    fn finish(&mut self, outcome: Outcome, ctx: &mut RaidContext) {
+        let Some(raid) = self.active.take() else {
+            return;
+        };
+        let def = &self.defs[raid.def];
+        if outcome == Outcome::Dawn {
+            ctx.entities.retain(|ent| !raid.raiders.contains(&ent.instance.uid));
+        }
+        let beaten = raid.spawned - raid.raiders.len() as u32;
+        let mut lines = vec![match outcome {
+            Outcome::Repelled => "The raid was repelled!".to_string(),
+            Outcome::Dawn => "The raiders fled at dawn.".to_string(),
+            Outcome::PlayerDied => "The raiders overran the farm.".to_string(),
+            Outcome::Abandoned => "The farm was left to the raiders.".to_string(),
+        }];
+        lines.push(format!("Raiders beaten: {beaten}. Crops lost: {}.", raid.crops_lost));
+        if matches!(outcome, Outcome::Repelled | Outcome::Dawn) {
+            let reward = &def.reward;
+            ctx.player.add_coins(reward.coins);
+            ctx.player.gain_xp(ctx.growth, reward.xp);
+            let feet = ctx.player.world_hitbox().center();
+            for stack in &reward.items {
+                let left = ctx.player.inventory_mut().add(ctx.item_db, &stack.item, stack.count);
+                if left > 0 {
+                    ctx.ground_items.spawn(ctx.item_db, &stack.item, left, feet);
+                }
+            }
+            lines.push(format!("Reward: {} coins, {} XP.", reward.coins, reward.xp));
+        }
+        ctx.dialogue.start(Some(&def.name), &lines);
+    }
+
+    /// Names the raid at the top of the screen, with its wave and how many raiders are left.
+    pub fn draw(&self) {
+        let Some(raid) = self.active.as_ref() else {
+            return;
+        };
+        let def = &self.defs[raid.def];
+        let text = format!(
+            "{}  -  wave {}/{}  -  {} raiders left",
+            def.name,
+            raid.next_wave.max(1),
+            def.waves.len(),
+            raid.raiders.len()
+        );
+        let width = measure_text(&text, 20.0).width;
+        draw_text_styled(&text, (screen_width() - width) * 0.5, 80.0, 20.0, RAID_COLOR, TextStyle::SHADOWED);
+    }
+}
+
+/// Spawns one raider at the farm's edge, headed for a crop or, with none planted, the middle
+/// of the farm. Returns its uid.
+fn spawn_raider(spawn: &RaidSpawn, ctx: &mut RaidContext) -> Option<u64> {
+    let start = scene::farm_edge_point(ctx.map);
+    let Some(mut ent) = Entity::spawn(ctx.db, &spawn.entity, start, ctx.registry) else {
+        eprintln!("raid spawns unknown entity '{}'", spawn.entity);
+        return None;
+    };
+    if spawn.chop_damage > 0.0 {
+        ent.instance.stats.add("chop_damage", spawn.chop_damage);
+    }
+    ent.instance.stats.add("crop_damage", spawn.crop_damage);
+    let tile_size = ctx.map.tile_size();
+    let crops = ctx.crops.crops();
+    let goal = if crops.is_empty() {
+        scene::farm_spawn_point(ctx.map)
+    } else {
+        let crop = &crops[random_u32() as usize % crops.len()];
+        vec2(crop.x as f32 + 0.5, crop.y as f32 + 0.5) * tile_size
+    };
+    let offset = ent.hitbox(ctx.db).center() - ent.instance.pos;
+    let tile_of = |point: Vec2| ((point.x / tile_size) as usize, (point.y / tile_size) as usize);
+    let route = path::find_path_near(ctx.map, tile_of(start + offset), tile_of(goal))
+        .map(|tiles| {
+            tiles
+                .into_iter()
+                .map(|(x, y)| vec2(x as f32 + 0.5, y as f32 + 0.5) * tile_size)
+                .chain(std::iter::once(goal))
+                .collect()
+        })
+        .unwrap_or_else(|| vec![goal]);
+    ent.instance.waypoints = route.into_iter().map(|point: Vec2| point - offset).collect();
+    let uid = ent.instance.uid;
+    ctx.entities.push(ent);
+    Some(uid)
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
@@ -0,0 +1,175 @@
+use macroquad::prelude::*;
+use std::collections::VecDeque;
+use crate::entity::Entity;
+use crate::interp::Interpolator;
+use crate::player::Player;
+
+/// Seconds of play kept for playback.
+const KEPT_SECONDS: f64 = 10.0;
+/// Seconds between samples. Playback blends between them, so they can be far sparser than frames.
+const SAMPLE_INTERVAL: f64 = 0.1;
+/// How far playback runs behind the newest sample fed to it, so there's one either side to blend.
+const PLAYBACK_DELAY: f64 = SAMPLE_INTERVAL * 2.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Subject {
+    Player,
+    Entity(u64),
+}
+
+struct Sample {
+    time: f64,
+    positions: Vec<(Subject, Vec2)>,
+}
+
+struct Playback {
+    motion: Interpolator<Subject, Vec2>,
+    /// Seconds played, counted from the first kept sample.
+    elapsed: f64,
+    /// Samples fed to `motion` so far.
+    fed: usize,
+    /// Where the player was, and each entity and how it was moving, to put back afterwards.
+    player: Vec2,
+    entities: Vec<(u64, Vec2, Vec2)>,
+}
+
+/// Where the player and entities were over the last few seconds, played back on request through
+/// the same interpolation co-op clients use, so the sparse samples still move smoothly.
+#[derive(Default)]
+pub struct Replay {
+    samples: VecDeque<Sample>,
+    clock: f64,
+    next_sample: f64,
+    playback: Option<Playback>,
+}
+
+impl Replay {
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Forgets what's been kept, for when the scene changes under it.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Starts playing back what's been kept. False when already playing or there's too little.
+    pub fn play(&mut self, entities: &[Entity], player: &Player) -> bool {
+        if self.playback.is_some() || self.samples.len() < 2 {
+            return false;
+        }
+        self.playback = Some(Playback {
+            motion: Interpolator::new(PLAYBACK_DELAY, 0.0),
+            elapsed: 0.0,
+            fed: 0,
+            player: player.position(),
+            entities: entities
+                .iter()
+                .map(|ent| (ent.instance.uid, ent.instance.pos, ent.instance.vel))
+                .collect(),
+        });
+        true
+    }
+
+    /// Keeps a sample whenever one's due, or while playing moves everything `dt` seconds on and
+    /// puts it all back at the end.
+    pub fn update(&mut self, dt: f32, entities: &mut [Entity], player: &mut Player) {
+        let Some(playback) = self.playback.as_mut() else {
+            self.record(dt, entities, player);
+            return;
+        };
+        let (Some(first), Some(last)) = (self.samples.front(), self.samples.back()) else {
+            self.playback = None;
+            return;
+        };
+        let start = first.time;
+        if playback.elapsed >= last.time - start + PLAYBACK_DELAY {
+            for ent in entities.iter_mut() {
+                if let Some(&(_, pos, vel)) = playback.entities.iter().find(|(uid, ..)| *uid == ent.instance.uid) {
+                    ent.instance.pos = pos;
+                    ent.instance.vel = vel;
+                }
+            }
+            player.set_position(playback.player);
+            self.playback = None;
+            return;
+        }
+        playback.elapsed += dt as f64;
+        // Fed as they'd have come in live, so the interpolator plays them the same way.
+        while let Some(sample) = self.samples.get(playback.fed)
+            && sample.time - start <= playback.elapsed
+        {
+            for &(subject, pos) in &sample.positions {
+                playback.motion.push(subject, sample.time - start, pos);
+            }
+            playback.fed += 1;
+        }
+        playback.motion.advance(dt);
+        for ent in entities.iter_mut() {
+            if let Some(pos) = playback.motion.sample(&Subject::Entity(ent.instance.uid)) {
+                // Kept so walk animations play.
+                if dt > 0.0 {
+                    ent.instance.vel = (pos - ent.instance.pos) / dt;
+                }
+                ent.instance.pos = pos;
+            }
+        }
+        if let Some(pos) = playback.motion.sample(&Subject::Player) {
+            player.follow(pos, dt);
+        }
+    }
+
+    fn record(&mut self, dt: f32, entities: &[Entity], player: &Player) {
+        self.clock += dt as f64;
+        if self.clock < self.next_sample {
+            return;
+        }
+        self.next_sample = self.clock + SAMPLE_INTERVAL;
+        let mut positions = Vec::with_capacity(entities.len() + 1);
+        positions.push((Subject::Player, player.position()));
+        positions.extend(entities.iter().map(|ent| (Subject::Entity(ent.instance.uid), ent.instance.pos)));
+        self.samples.push_back(Sample {
+            time: self.clock,
+            positions,
+        });
+        while self.samples.front().is_some_and(|sample| sample.time < self.clock - KEPT_SECONDS) {
+            self.samples.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::{PLAYER_HITBOX, PlayerStats};
+    use crate::texture::blank_texture;
+
+    #[test]
+    fn plays_back_then_puts_the_player_back() {
+        let mut player = Player::new(Vec2::ZERO, blank_texture(), PLAYER_HITBOX, PlayerStats::default());
+        let mut replay = Replay::default();
+        assert!(!replay.play(&[], &player));
+        // Walk right at 100 a second for a second, a frame at a time.
+        for frame in 1..=60 {
+            player.set_position(vec2(frame as f32 * 100.0 / 60.0, 0.0));
+            replay.update(1.0 / 60.0, &mut [], &mut player);
+        }
+        let now = player.position();
+        assert!(replay.play(&[], &player));
+
+        replay.update(0.0, &mut [], &mut player);
+        let start = player.position();
+        for _ in 0..30 {
+            replay.update(1.0 / 60.0, &mut [], &mut player);
+        }
+        // Half a second in, less the playback delay, and moving between samples.
+        let halfway = player.position();
+        assert!(halfway.x > start.x + 20.0 && halfway.x < now.x, "{start} {halfway}");
+        assert!(player.velocity().x > 0.0);
+
+        while replay.is_playing() {
+            replay.update(1.0 / 60.0, &mut [], &mut player);
+        }
+        assert_eq!(player.position(), now);
+    }
+}
@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use crate::map::{BreakableStructure, SortedStructure, TileMapSnapshot};
+
+/// Current save layout. Version 1 was the raw-array JSON written before layers were
+/// run-length encoded and has no `version` field; version 3 records content pack versions,
+/// version 4 gives breakable structures their def id and max hp, version 5 adds soil
+/// states and the calendar, version 6 adds built structures and chest contents, version 7
+/// adds planted crops, version 8 adds entities and worker job regions, version 9 adds bred
+/// entities' stats, version 10 adds entity XP, and version 11 adds explored expedition tiles.
+pub const SAVE_VERSION: u16 = 11;
+const BINARY_MAGIC: &[u8; 4] = b"CBSV";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SaveFormat {
+    Json,
+    Binary,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Json(serde_json::Error),
+    Binary(postcard::Error),
+    Version(u16),
+    Corrupt(&'static str),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Json(err) => write!(f, "json error: {err}"),
+            Self::Binary(err) => write!(f, "binary error: {err}"),
+            Self::Version(version) => write!(f, "unsupported save version {version}"),
+            Self::Corrupt(reason) => write!(f, "corrupt save: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl From<postcard::Error> for SaveError {
+    fn from(err: postcard::Error) -> Self {
+        Self::Binary(err)
+    }
+}
+
+/// A tile layer stored as a palette of distinct values plus `(palette index, length)` runs.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RleLayer {
+    pub palette: Vec<u8>,
+    pub runs: Vec<(u8, u32)>,
+}
+
+impl RleLayer {
+    pub fn encode(cells: &[u8]) -> Self {
+        let mut palette: Vec<u8> = Vec::new();
+        let mut runs: Vec<(u8, u32)> = Vec::new();
+        for &cell in cells {
+            let index = match palette.iter().position(|value| *value == cell) {
+                Some(index) => index,
+                None => {
+                    palette.push(cell);
+                    palette.len() - 1
+                }
+            } as u8;
+            match runs.last_mut() {
+                Some((last, len)) if *last == index && *len < u32::MAX => *len += 1,
+                _ => runs.push((index, 1)),
+            }
+        }
+        Self { palette, runs }
+    }
+
+    pub fn decode(&self, len: usize) -> Result<Vec<u8>, SaveError> {
+        let mut cells = Vec::with_capacity(len);
+        for &(index, run) in &self.runs {
+            let value = *self
+                .palette
+                .get(index as usize)
+                .ok_or(SaveError::Corrupt("run references a missing palette entry"))?;
+            if cells.len() + run as usize > len {
+                return Err(SaveError::Corrupt("layer runs overflow the map"));
+            }
+            cells.resize(cells.len() + run as usize, value);
+        }
+        if cells.len() != len {
+            return Err(SaveError::Corrupt("layer runs do not cover the map"));
+        }
+        Ok(cells)
+    }
+}
+
+/// `TileMapSnapshot` with every layer run-length encoded.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EncodedSnapshot {
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: f32,
+    pub background: RleLayer,
+    pub foreground: RleLayer,
+    pub overlay: RleLayer,
+    pub collision_mask: RleLayer,
+    pub sorted_structures: Vec<SortedStructure>,
+    pub breakable_structures: Vec<BreakableStructure>,
+}
+
+impl EncodedSnapshot {
+    pub fn encode(snapshot: &TileMapSnapshot) -> Self {
+        Self {
+            width: snapshot.width,
+            height: snapshot.height,
+            tile_size: snapshot.tile_size,
+            background: RleLayer::encode(&snapshot.background),
+            foreground: RleLayer::encode(&snapshot.foreground),
+            overlay: RleLayer::encode(&snapshot.overlay),
+            collision_mask: RleLayer::encode(&snapshot.collision_mask),
+            sorted_structures: snapshot.sorted_structures.clone(),
+            breakable_structures: snapshot.breakable_structures.clone(),
+        }
+    }
+
+    pub fn decode(&self) -> Result<TileMapSnapshot, SaveError> {
+        let len = self.width * self.height;
+        Ok(TileMapSnapshot {
+            width: self.width,
+            height: self.height,
+            tile_size: self.tile_size,
+            background: self.background.decode(len)?,
+            foreground: self.foreground.decode(len)?,
+            overlay: self.overlay.decode(len)?,
+            collision_mask: self.collision_mask.decode(len)?,
+            sorted_structures: self.sorted_structures.clone(),
+            breakable_structures: self.breakable_structures.clone(),
+        })
+    }
+}
+
+/// Breakable structure as stored by save versions 2 and 3, when only dashes could break them.
+#[derive(Deserialize)]
+pub struct LegacyFragileStructure {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub hp: f32,
+}
+
+/// `EncodedSnapshot` layout used by save versions 2 and 3.
+#[derive(Deserialize)]
+pub struct LegacyEncodedSnapshot {
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: f32,
+    pub background: RleLayer,
+    pub foreground: RleLayer,
+    pub overlay: RleLayer,
+    pub collision_mask: RleLayer,
+    pub sorted_structures: Vec<SortedStructure>,
+    pub fragile_structures: Vec<LegacyFragileStructure>,
+}
+
+impl LegacyEncodedSnapshot {
+    pub fn decode(self) -> Result<TileMapSnapshot, SaveError> {
+        let current = EncodedSnapshot {
+            width: self.width,
+            height: self.height,
+            tile_size: self.tile_size,
+            background: self.background,
+            foreground: self.foreground,
+            overlay: self.overlay,
+            collision_mask: self.collision_mask,
+            sorted_structures: self.sorted_structures,
+            breakable_structures: self
+                .fragile_structures
+                .into_iter()
+                .map(|fragile| BreakableStructure {
+                    x: fragile.x,
+                    y: fragile.y,
+                    width: fragile.width,
+                    height: fragile.height,
+                    hp: fragile.hp,
+                    max_hp: fragile.hp,
+                    structure_id: String::new(),
+                    hit_only: false,
+                    enemy_only: false,
+                })
+                .collect(),
+        };
+        current.decode()
+    }
+}
+
+/// Serializes `value` as JSON or as magic + version + postcard payload.
+pub fn encode<T: Serialize>(value: &T, format: SaveFormat) -> Result<Vec<u8>, SaveError> {
+    match format {
+        SaveFormat::Json => Ok(serde_json::to_vec(value)?),
+        SaveFormat::Binary => {
+            let mut bytes = Vec::with_capacity(BINARY_MAGIC.len() + 2);
+            bytes.extend_from_slice(BINARY_MAGIC);
+            bytes.extend_from_slice(&SAVE_VERSION.to_le_bytes());
+            Ok(postcard::to_extend(value, bytes)?)
+        }
+    }
+}
+
+/// Splits a binary save written by `encode` into its version and payload. JSON saves
+/// carry their own version field.
+pub fn split_binary(bytes: &[u8]) -> Result<(u16, &[u8]), SaveError> {
+    let header = BINARY_MAGIC.len() + 2;
+    if bytes.len() < header || !is_binary(bytes) {
+        return Err(SaveError::Corrupt("missing binary save header"));
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    Ok((version, &bytes[header..]))
+}
+
+pub fn decode_payload<T: DeserializeOwned>(payload: &[u8]) -> Result<T, SaveError> {
+    Ok(postcard::from_bytes(payload)?)
+}
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(BINARY_MAGIC)
+}
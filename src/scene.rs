@@ -1,27 +1,56 @@
 use macroquad::prelude::*;
 
-use crate::entity::{Entity, EntityDatabase, MovementRegistry};
-use crate::helpers::random_range;
-use crate::map::{LayerKind, StructureDef, TileMap, TileMapSnapshot};
+use crate::build::BuiltStructure;
+use crate::content::{ContentManifest, ContentVersions, SavedContent};
+use crate::crop::{Crop, CropField};
+use crate::entity::{Entity, EntityDatabase, MovementRegistry, SavedEntity, StatBlock};
+use crate::helpers::{random_range, random_u32};
+use crate::item::{GroundItem, GroundItems, Inventory, ItemDatabase};
+use crate::map::{BrokenStructure, LayerKind, StructureDef, TileMap, TileMapSnapshot};
+use crate::farm::{DayClock, FarmState, Season, SoilLayer};
+use crate::save::{self, EncodedSnapshot, LegacyEncodedSnapshot, RleLayer, SAVE_VERSION, SaveError};
+use crate::slot::{FARM_SAVE_FORMAT, SaveManager};
+use crate::storage::{Chest, ChestStorage};
+use crate::worker::{JobRegion, WorkerBoard};
+use crate::expedition::EXTRACTION_BEACON;
+use serde::{Deserialize, Serialize};
 
 pub const EXPEDITION_WIDTH: usize = 1024;
 pub const EXPEDITION_HEIGHT: usize = 1024;
 pub const FARM_WIDTH: usize = 100;
 pub const FARM_HEIGHT: usize = 50;
 
+/// Sizes the fenced farm grows through as land is bought, starting at `FARM_WIDTH` by
+/// `FARM_HEIGHT`. It grows right and down so everything already on it keeps its tile.
+pub const FARM_SIZES: [(usize, usize); 4] = [(FARM_WIDTH, FARM_HEIGHT), (124, 62), (148, 74), (172, 86)];
+pub const LAND_OFFICE_ID: &str = "land_office";
+
 const FARM_OUTER_MARGIN: usize = 128;
-const FARM_MAP_WIDTH: usize = FARM_WIDTH + FARM_OUTER_MARGIN * 2;
-const FARM_MAP_HEIGHT: usize = FARM_HEIGHT + FARM_OUTER_MARGIN * 2;
 
 const EXPEDITION_DECOR_SEED: u32 = 0x6D2B_79F5;
 const FARM_DECOR_SEED: u32 = 0xA531_2D91;
 const EXPEDITION_EDGE_BAND: usize = 96;
 const DECOR_STRUCTURE_IDS: [&str; 2] = ["tree_plains", "bush_plains"];
+/// Expedition edges also get ground cover, which would sit in the way of tilling on the farm.
+const EXPEDITION_DECOR_STRUCTURE_IDS: [&str; 3] = ["tree_plains", "bush_plains", "grass_tuft_plains"];
 const SCENE_DECOR_DENSITY_SCALE: f32 = 0.75;
 const SCENE_DECOR_MAX_PER_DEF: usize = 1200;
-
-#[cfg(target_arch = "wasm32")]
-const FARM_STORAGE_KEY: &str = "cropbots:farm.json";
+/// Resource nodes scattered across the whole expedition, away from where the player arrives.
+const EXPEDITION_RESOURCE_IDS: [&str; 2] = ["ore_rock_plains", "scrap_pile_plains"];
+/// Tiles around the arrival point kept clear of resource nodes.
+const EXPEDITION_ARRIVAL_CLEARING: usize = 12;
+const EXPEDITION_RESOURCE_SEED: u32 = 0x2C1B_3C6D;
+const EXPEDITION_GATE_ID: &str = "expedition_gate";
+const FARM_GATE_ID: &str = "farm_gate";
+pub const WAYPOINT_ID: &str = "waypoint";
+/// The expedition is split into this many sectors a side, each but the arrival's getting a
+/// waypoint.
+const WAYPOINT_SECTORS: usize = 3;
+const WAYPOINT_SEED: u32 = 0x5A3D_91C7;
+/// Entities scattered over a fresh expedition and how many of each, before seasonal scaling.
+const EXPEDITION_SPAWNS: [(&str, usize); 4] =
+    [("virabird", 200), ("virat", 200), ("chopbot", 200), ("virat_nest", 12)];
+const FARM_HOUSE_DOOR_ID: &str = "farm_house_door";
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum SceneKind {
@@ -29,6 +58,482 @@ pub enum SceneKind {
     Farm,
 }
 
+impl SceneKind {
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "expedition" => Some(Self::Expedition),
+            "farm" => Some(Self::Farm),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum SceneRequest {
+    Switch(SceneKind),
+    EnterInterior(String),
+    ExitInterior,
+}
+
+impl SceneRequest {
+    /// Maps a structure's `scene_transition` id to a request; `exit` leaves the current interior.
+    pub fn from_id(id: &str) -> Option<Self> {
+        if id == "exit" {
+            return Some(Self::ExitInterior);
+        }
+        SceneKind::from_id(id).map(Self::Switch)
+    }
+}
+
+/// Everything needed to resume a map the player stepped out of into an interior.
+pub struct SceneFrame {
+    pub map: TileMap,
+    pub entities: Vec<Entity>,
+    pub ground_items: Vec<GroundItem>,
+    pub player_pos: Vec2,
+    pub camera_target: Vec2,
+}
+
+#[derive(Default)]
+pub struct SceneStack {
+    frames: Vec<SceneFrame>,
+}
+
+impl SceneStack {
+    pub fn push(&mut self, frame: SceneFrame) {
+        self.frames.push(frame);
+    }
+
+    pub fn pop(&mut self) -> Option<SceneFrame> {
+        self.frames.pop()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Interiors the player is nested in.
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The outdoor map the player first stepped out of.
+    pub fn root(&self) -> Option<&SceneFrame> {
+        self.frames.first()
+    }
+
+    /// Drops every nested interior and returns the outermost map, if any.
+    pub fn unwind(&mut self) -> Option<SceneFrame> {
+        self.frames.truncate(1);
+        self.frames.pop()
+    }
+}
+
+/// On-disk farm save with run-length encoded layers.
+#[derive(Serialize, Deserialize)]
+struct FarmSave {
+    version: u16,
+    content: ContentVersions,
+    clock: DayClock,
+    map: EncodedSnapshot,
+    soil: RleLayer,
+    ground_items: Vec<GroundItem>,
+    built: Vec<BuiltStructure>,
+    chests: Vec<Chest>,
+    crops: Vec<Crop>,
+    entities: Vec<SavedEntity>,
+    regions: Vec<JobRegion>,
+    explored: SavedExploration,
+}
+
+/// Expedition tiles the player has explored, as `Exploration` bytes.
+#[derive(Serialize, Deserialize)]
+struct SavedExploration {
+    width: usize,
+    height: usize,
+    bits: RleLayer,
+}
+
+/// Version 10 farm save, written before exploration was kept.
+#[derive(Deserialize)]
+struct FarmSaveV10 {
+    #[serde(rename = "version")]
+    _version: u16,
+    content: ContentVersions,
+    clock: DayClock,
+    map: EncodedSnapshot,
+    soil: RleLayer,
+    ground_items: Vec<GroundItem>,
+    built: Vec<BuiltStructure>,
+    chests: Vec<Chest>,
+    crops: Vec<Crop>,
+    entities: Vec<SavedEntity>,
+    regions: Vec<JobRegion>,
+}
+
+/// Version 9 farm save, written before entities kept their XP.
+#[derive(Deserialize)]
+struct FarmSaveV9 {
+    #[serde(rename = "version")]
+    _version: u16,
+    content: ContentVersions,
+    clock: DayClock,
+    map: EncodedSnapshot,
+    soil: RleLayer,
+    ground_items: Vec<GroundItem>,
+    built: Vec<BuiltStructure>,
+    chests: Vec<Chest>,
+    crops: Vec<Crop>,
+    entities: Vec<SavedEntityV9>,
+    regions: Vec<JobRegion>,
+}
+
+/// Entity as written to a version 9 farm save.
+#[derive(Deserialize)]
+struct SavedEntityV9 {
+    def: String,
+    x: f32,
+    y: f32,
+    hp: f32,
+    job: Option<usize>,
+    inventory: Inventory,
+    stats: Option<StatBlock>,
+    generation: u32,
+}
+
+impl From<SavedEntityV9> for SavedEntity {
+    fn from(saved: SavedEntityV9) -> Self {
+        Self {
+            def: saved.def,
+            x: saved.x,
+            y: saved.y,
+            hp: saved.hp,
+            job: saved.job,
+            inventory: saved.inventory,
+            stats: saved.stats,
+            generation: saved.generation,
+            xp: 0.0,
+        }
+    }
+}
+
+/// Version 8 farm save, written before bred entities kept their stats.
+#[derive(Deserialize)]
+struct FarmSaveV8 {
+    #[serde(rename = "version")]
+    _version: u16,
+    content: ContentVersions,
+    clock: DayClock,
+    map: EncodedSnapshot,
+    soil: RleLayer,
+    ground_items: Vec<GroundItem>,
+    built: Vec<BuiltStructure>,
+    chests: Vec<Chest>,
+    crops: Vec<Crop>,
+    entities: Vec<SavedEntityV8>,
+    regions: Vec<JobRegion>,
+}
+
+/// Entity as written to a version 8 farm save.
+#[derive(Deserialize)]
+struct SavedEntityV8 {
+    def: String,
+    x: f32,
+    y: f32,
+    hp: f32,
+    job: Option<usize>,
+    inventory: Inventory,
+}
+
+impl From<SavedEntityV8> for SavedEntity {
+    fn from(saved: SavedEntityV8) -> Self {
+        Self {
+            def: saved.def,
+            x: saved.x,
+            y: saved.y,
+            hp: saved.hp,
+            job: saved.job,
+            inventory: saved.inventory,
+            stats: None,
+            generation: 0,
+            xp: 0.0,
+        }
+    }
+}
+
+/// Version 7 farm save, written before entities and job regions were saved.
+#[derive(Deserialize)]
+struct FarmSaveV7 {
+    #[serde(rename = "version")]
+    _version: u16,
+    content: ContentVersions,
+    clock: DayClock,
+    map: EncodedSnapshot,
+    soil: RleLayer,
+    ground_items: Vec<GroundItem>,
+    built: Vec<BuiltStructure>,
+    chests: Vec<Chest>,
+    crops: Vec<Crop>,
+}
+
+/// Version 6 farm save, written before crops were saved.
+#[derive(Deserialize)]
+struct FarmSaveV6 {
+    #[serde(rename = "version")]
+    _version: u16,
+    content: ContentVersions,
+    clock: DayClock,
+    map: EncodedSnapshot,
+    soil: RleLayer,
+    ground_items: Vec<GroundItem>,
+    built: Vec<BuiltStructure>,
+    chests: Vec<Chest>,
+}
+
+/// Version 5 farm save, written before built structures and chests were saved.
+#[derive(Deserialize)]
+struct FarmSaveV5 {
+    #[serde(rename = "version")]
+    _version: u16,
+    content: ContentVersions,
+    clock: DayClock,
+    map: EncodedSnapshot,
+    soil: RleLayer,
+    ground_items: Vec<GroundItem>,
+}
+
+/// Version 4 farm save, written before soil states and the calendar were saved.
+#[derive(Deserialize)]
+struct FarmSaveV4 {
+    #[serde(rename = "version")]
+    _version: u16,
+    content: ContentVersions,
+    map: EncodedSnapshot,
+    ground_items: Vec<GroundItem>,
+}
+
+/// Version 3 farm save, written before structures could be chopped.
+#[derive(Deserialize)]
+struct FarmSaveV3 {
+    #[serde(rename = "version")]
+    _version: u16,
+    content: ContentVersions,
+    map: LegacyEncodedSnapshot,
+    ground_items: Vec<GroundItem>,
+}
+
+/// Version 2 farm save, written before content pack versions were recorded.
+#[derive(Deserialize)]
+struct FarmSaveV2 {
+    #[serde(rename = "version")]
+    _version: u16,
+    map: LegacyEncodedSnapshot,
+    ground_items: Vec<GroundItem>,
+}
+
+/// Unversioned JSON farm save with raw layer arrays. The map snapshot is flattened so
+/// saves written before ground items were persisted still load.
+#[derive(Deserialize)]
+struct LegacyFarmSave {
+    #[serde(flatten)]
+    map: TileMapSnapshot,
+    #[serde(default)]
+    ground_items: Vec<GroundItem>,
+}
+
+struct LoadedFarm {
+    snapshot: TileMapSnapshot,
+    content: ContentVersions,
+    ground_items: Vec<GroundItem>,
+    soil: Option<Vec<u8>>,
+    clock: Option<DayClock>,
+    built: Vec<BuiltStructure>,
+    chests: Vec<Chest>,
+    crops: Vec<Crop>,
+    entities: Vec<SavedEntity>,
+    regions: Vec<JobRegion>,
+    /// Explored expedition tiles as `Exploration` bytes, with the map size they cover.
+    explored: Option<(usize, usize, Vec<u8>)>,
+}
+
+impl LoadedFarm {
+    fn from_save(save: FarmSave) -> Result<Self, SaveError> {
+        let snapshot = save.map.decode()?;
+        let soil = save.soil.decode(snapshot.width * snapshot.height)?;
+        let (width, height) = (save.explored.width, save.explored.height);
+        let explored = save.explored.bits.decode((width * height).div_ceil(8))?;
+        Ok(Self {
+            snapshot,
+            content: save.content,
+            ground_items: save.ground_items,
+            soil: Some(soil),
+            clock: Some(save.clock),
+            built: save.built,
+            chests: save.chests,
+            crops: save.crops,
+            entities: save.entities,
+            regions: save.regions,
+            explored: Some((width, height, explored)),
+        })
+    }
+
+    fn from_v10(save: FarmSaveV10) -> Result<Self, SaveError> {
+        let snapshot = save.map.decode()?;
+        let soil = save.soil.decode(snapshot.width * snapshot.height)?;
+        Ok(Self {
+            snapshot,
+            content: save.content,
+            ground_items: save.ground_items,
+            soil: Some(soil),
+            clock: Some(save.clock),
+            built: save.built,
+            chests: save.chests,
+            crops: save.crops,
+            entities: save.entities,
+            regions: save.regions,
+            explored: None,
+        })
+    }
+
+    fn from_v9(save: FarmSaveV9) -> Result<Self, SaveError> {
+        let snapshot = save.map.decode()?;
+        let soil = save.soil.decode(snapshot.width * snapshot.height)?;
+        Ok(Self {
+            snapshot,
+            content: save.content,
+            ground_items: save.ground_items,
+            soil: Some(soil),
+            clock: Some(save.clock),
+            built: save.built,
+            chests: save.chests,
+            crops: save.crops,
+            entities: save.entities.into_iter().map(SavedEntity::from).collect(),
+            regions: save.regions,
+            explored: None,
+        })
+    }
+
+    fn from_v8(save: FarmSaveV8) -> Result<Self, SaveError> {
+        let snapshot = save.map.decode()?;
+        let soil = save.soil.decode(snapshot.width * snapshot.height)?;
+        Ok(Self {
+            snapshot,
+            content: save.content,
+            ground_items: save.ground_items,
+            soil: Some(soil),
+            clock: Some(save.clock),
+            built: save.built,
+            chests: save.chests,
+            crops: save.crops,
+            entities: save.entities.into_iter().map(SavedEntity::from).collect(),
+            regions: save.regions,
+            explored: None,
+        })
+    }
+
+    fn from_v7(save: FarmSaveV7) -> Result<Self, SaveError> {
+        let snapshot = save.map.decode()?;
+        let soil = save.soil.decode(snapshot.width * snapshot.height)?;
+        Ok(Self {
+            snapshot,
+            content: save.content,
+            ground_items: save.ground_items,
+            soil: Some(soil),
+            clock: Some(save.clock),
+            built: save.built,
+            chests: save.chests,
+            crops: save.crops,
+            entities: Vec::new(),
+            regions: Vec::new(),
+            explored: None,
+        })
+    }
+
+    fn from_v6(save: FarmSaveV6) -> Result<Self, SaveError> {
+        let snapshot = save.map.decode()?;
+        let soil = save.soil.decode(snapshot.width * snapshot.height)?;
+        Ok(Self {
+            snapshot,
+            content: save.content,
+            ground_items: save.ground_items,
+            soil: Some(soil),
+            clock: Some(save.clock),
+            built: save.built,
+            chests: save.chests,
+            crops: Vec::new(),
+            entities: Vec::new(),
+            regions: Vec::new(),
+            explored: None,
+        })
+    }
+
+    fn from_v5(save: FarmSaveV5) -> Result<Self, SaveError> {
+        let snapshot = save.map.decode()?;
+        let soil = save.soil.decode(snapshot.width * snapshot.height)?;
+        Ok(Self {
+            snapshot,
+            content: save.content,
+            ground_items: save.ground_items,
+            soil: Some(soil),
+            clock: Some(save.clock),
+            built: Vec::new(),
+            chests: Vec::new(),
+            crops: Vec::new(),
+            entities: Vec::new(),
+            regions: Vec::new(),
+            explored: None,
+        })
+    }
+
+    fn from_v4(save: FarmSaveV4) -> Result<Self, SaveError> {
+        Ok(Self {
+            snapshot: save.map.decode()?,
+            content: save.content,
+            ground_items: save.ground_items,
+            soil: None,
+            clock: None,
+            built: Vec::new(),
+            chests: Vec::new(),
+            crops: Vec::new(),
+            entities: Vec::new(),
+            regions: Vec::new(),
+            explored: None,
+        })
+    }
+
+    fn from_v3(save: FarmSaveV3) -> Result<Self, SaveError> {
+        Ok(Self {
+            snapshot: save.map.decode()?,
+            content: save.content,
+            ground_items: save.ground_items,
+            soil: None,
+            clock: None,
+            built: Vec::new(),
+            chests: Vec::new(),
+            crops: Vec::new(),
+            entities: Vec::new(),
+            regions: Vec::new(),
+            explored: None,
+        })
+    }
+
+    fn from_v2(save: FarmSaveV2) -> Result<Self, SaveError> {
+        Ok(Self {
+            snapshot: save.map.decode()?,
+            content: ContentVersions::new(),
+            ground_items: save.ground_items,
+            soil: None,
+            clock: None,
+            built: Vec::new(),
+            chests: Vec::new(),
+            crops: Vec::new(),
+            entities: Vec::new(),
+            regions: Vec::new(),
+            explored: None,
+        })
+    }
+}
+
 #[derive(Clone, Copy)]
 struct TileRect {
     x: usize,
@@ -57,7 +562,7 @@ pub fn expedition_spawn_point() -> Vec2 {
 }
 
 pub fn farm_spawn_point(map: &TileMap) -> Vec2 {
-    let area = inset_tile_rect(farm_core_rect(), 1);
+    let area = inset_tile_rect(farm_home_rect(), 1);
     let ts = map.tile_size();
     vec2(
         (area.x as f32 + area.w as f32 * 0.5) * ts,
@@ -65,6 +570,38 @@ pub fn farm_spawn_point(map: &TileMap) -> Vec2 {
     )
 }
 
+/// Where the `index`th shopkeeper stands: a row left of the expedition gate.
+pub fn farm_shop_point(map: &TileMap, index: usize) -> Vec2 {
+    let area = inset_tile_rect(farm_home_rect(), 1);
+    let ts = map.tile_size();
+    vec2(
+        (area.x as f32 + area.w as f32 * 0.5 - 4.0 - index as f32 * 2.0) * ts,
+        (area.y as f32 + 3.0) * ts,
+    )
+}
+
+/// A random point just outside the fenced farm, on any of its four sides.
+pub fn farm_edge_point(map: &TileMap) -> Vec2 {
+    let area = farm_core_rect(map);
+    let ts = map.tile_size();
+    let (x0, y0) = (area.x as f32 - 2.0, area.y as f32 - 2.0);
+    let (x1, y1) = ((area.x + area.w) as f32 + 2.0, (area.y + area.h) as f32 + 2.0);
+    let tile = match random_u32() % 4 {
+        0 => vec2(random_range(x0, x1), y0),
+        1 => vec2(random_range(x0, x1), y1),
+        2 => vec2(x0, random_range(y0, y1)),
+        _ => vec2(x1, random_range(y0, y1)),
+    };
+    tile * ts
+}
+
+/// Where the `index`th cropbot starts: a row just below the player's spawn.
+pub fn farm_worker_point(map: &TileMap, index: usize) -> Vec2 {
+    let spawn = farm_spawn_point(map);
+    let ts = map.tile_size();
+    spawn + vec2((index as f32 * 2.0 - 1.0) * ts, 2.0 * ts)
+}
+
 pub fn place_structure_from_defs(
     map: &mut TileMap,
     structures: &[StructureDef],
@@ -79,105 +616,385 @@ pub fn place_structure_from_defs(
     true
 }
 
+/// The loaded data and map settings scenes are built from.
+#[derive(Clone, Copy)]
+pub struct SceneAssets<'a> {
+    pub db: &'a EntityDatabase,
+    pub registry: &'a MovementRegistry,
+    pub structures: &'a [StructureDef],
+    pub content: &'a ContentManifest,
+    pub item_db: &'a ItemDatabase,
+    /// Tile the ground is filled with.
+    pub ground_tile: u8,
+    pub tile_size: f32,
+    pub chunk_alloc_per_frame: usize,
+    pub chunk_rebuild_per_frame: usize,
+}
+
 pub fn scene_expedition(
     map: &mut TileMap,
     entities: &mut Vec<Entity>,
-    db: &EntityDatabase,
-    registry: &MovementRegistry,
-    structures: &[StructureDef],
-    ground_tile: u8,
-    tile_size: f32,
-    chunk_alloc_per_frame: usize,
-    chunk_rebuild_per_frame: usize,
-) {
-    clear_scenes(map, entities);
+    assets: SceneAssets,
+    season: Season,
+    harvested: &[Rect],
+    extraction_distance: f32,
+) -> Vec2 {
+    let mut build = ExpeditionBuild::new(assets, season, harvested, extraction_distance);
+    while !build.step(assets, f32::INFINITY) {}
+    build.finish(map, entities)
+}
 
-    let mut next = TileMap::new_deferred(
-        EXPEDITION_WIDTH,
-        EXPEDITION_HEIGHT,
-        tile_size,
-        Vec2::new(tile_size, tile_size),
-        0.0,
-    );
-    next.set_chunk_work_budget(chunk_alloc_per_frame, chunk_rebuild_per_frame);
-    next.fill_layer(LayerKind::Background, ground_tile);
-    next.set_custom_border_hitbox(None);
-    spawn_expedition_edge_decorations(&mut next, structures);
-    *map = next;
+/// One piece of building the expedition, small enough to fit between loading screen frames.
+#[derive(Clone, Copy)]
+enum ExpeditionStep {
+    EdgeDecor(usize),
+    Landmarks,
+    Resources(usize),
+    Harvested,
+    Spawns(usize),
+}
 
-    entities.clear();
-    for _ in 0..200 {
-        let pos = vec2(random_range(0.0, 500.0), random_range(0.0, 500.0));
-        if let Some(virabird) = Entity::spawn(db, "virabird", pos, registry) {
-            entities.push(virabird);
+const EXPEDITION_STEPS: usize =
+    EXPEDITION_DECOR_STRUCTURE_IDS.len() + 1 + EXPEDITION_RESOURCE_IDS.len() + 1 + EXPEDITION_SPAWNS.len();
+
+impl ExpeditionStep {
+    fn nth(index: usize) -> Option<Self> {
+        let decor = EXPEDITION_DECOR_STRUCTURE_IDS.len();
+        let resources = decor + 1 + EXPEDITION_RESOURCE_IDS.len();
+        match index {
+            i if i < decor => Some(Self::EdgeDecor(i)),
+            i if i == decor => Some(Self::Landmarks),
+            i if i < resources => Some(Self::Resources(i - decor - 1)),
+            i if i == resources => Some(Self::Harvested),
+            i if i < EXPEDITION_STEPS => Some(Self::Spawns(i - resources - 1)),
+            _ => None,
+        }
+    }
+}
+
+/// The expedition built a step at a time, so the loading screen keeps drawing and shows how
+/// far along it is. Nothing touches the current scene until `finish` swaps it in.
+pub struct ExpeditionBuild {
+    next: TileMap,
+    entities: Vec<Entity>,
+    season: Season,
+    harvested: Vec<Rect>,
+    extraction_distance: f32,
+    beacon: Vec2,
+    step: usize,
+}
+
+impl ExpeditionBuild {
+    pub fn new(assets: SceneAssets, season: Season, harvested: &[Rect], extraction_distance: f32) -> Self {
+        let tile_size = assets.tile_size;
+        let mut next = TileMap::new_deferred(
+            EXPEDITION_WIDTH,
+            EXPEDITION_HEIGHT,
+            tile_size,
+            Vec2::new(tile_size, tile_size),
+            0.0,
+        );
+        next.set_chunk_work_budget(assets.chunk_alloc_per_frame, assets.chunk_rebuild_per_frame);
+        next.fill_layer(LayerKind::Background, assets.ground_tile);
+        next.set_custom_border_hitbox(None);
+        next.set_structure_palette(Some(season.key()));
+        Self {
+            next,
+            entities: Vec::new(),
+            season,
+            harvested: harvested.to_vec(),
+            extraction_distance,
+            beacon: expedition_spawn_point(),
+            step: 0,
         }
     }
-    for _ in 0..200 {
-        let pos = vec2(random_range(0.0, 500.0), random_range(0.0, 500.0));
-        if let Some(virat) = Entity::spawn(db, "virat", pos, registry) {
-            entities.push(virat);
+
+    /// Runs steps until `time_budget_s` is spent, always at least one. Returns whether it's
+    /// all built.
+    pub fn step(&mut self, assets: SceneAssets, time_budget_s: f32) -> bool {
+        // miniquad's clock, unlike macroquad's, runs without a window.
+        let start = miniquad::date::now();
+        while let Some(step) = ExpeditionStep::nth(self.step) {
+            self.step += 1;
+            self.run(step, assets);
+            if miniquad::date::now() - start >= time_budget_s as f64 {
+                break;
+            }
         }
+        self.step >= EXPEDITION_STEPS
     }
-    for _ in 0..200 {
-        let pos = vec2(random_range(0.0, 500.0), random_range(0.0, 500.0));
-        if let Some(chopbot) = Entity::spawn(db, "chopbot", pos, registry) {
-            entities.push(chopbot);
+
+    fn run(&mut self, step: ExpeditionStep, assets: SceneAssets) {
+        let SceneAssets { structures, db, registry, .. } = assets;
+        let spawn = expedition_spawn_point();
+        match step {
+            ExpeditionStep::EdgeDecor(i) => spawn_expedition_edge_decorations(&mut self.next, structures, i),
+            ExpeditionStep::Landmarks => {
+                let tile_size = self.next.tile_size();
+                let (x, y) = ((spawn.x / tile_size) as usize, (spawn.y / tile_size) as usize - 3);
+                place_structure_from_defs(&mut self.next, structures, FARM_GATE_ID, x, y);
+                self.beacon = place_extraction_beacon(&mut self.next, structures, spawn, self.extraction_distance);
+                place_waypoints(&mut self.next, structures, spawn);
+            }
+            ExpeditionStep::Resources(i) => spawn_expedition_resources(&mut self.next, structures, spawn, i),
+            ExpeditionStep::Harvested => self.next.remove_breakable_structures(&self.harvested),
+            ExpeditionStep::Spawns(i) => {
+                let (id, base) = EXPEDITION_SPAWNS[i];
+                let count = db.difficulty().scale_count(base as f32 * seasonal_spawn_scale(self.season, id));
+                for _ in 0..count {
+                    let pos = vec2(random_range(0.0, 500.0), random_range(0.0, 500.0));
+                    if let Some(ent) = Entity::spawn(db, id, pos, registry) {
+                        self.entities.push(ent);
+                    }
+                }
+            }
         }
     }
+
+    pub fn progress(&self) -> f32 {
+        self.step as f32 / EXPEDITION_STEPS as f32
+    }
+
+    /// Replaces the current scene with the built expedition. Returns where the extraction
+    /// beacon stands.
+    pub fn finish(self, map: &mut TileMap, entities: &mut Vec<Entity>) -> Vec2 {
+        clear_scenes(map, entities);
+        *map = self.next;
+        *entities = self.entities;
+        self.beacon
+    }
+}
+
+/// How much more or less common an expedition entity is in `season`: birds migrate away for
+/// winter, rats swarm in fall.
+fn seasonal_spawn_scale(season: Season, id: &str) -> f32 {
+    match (season, id) {
+        (Season::Spring, "virabird") => 1.25,
+        (Season::Winter, "virabird") => 0.25,
+        (Season::Fall, "virat") => 1.5,
+        (Season::Winter, "virat") => 0.5,
+        _ => 1.0,
+    }
 }
 
 pub fn scene_farm(
     map: &mut TileMap,
     entities: &mut Vec<Entity>,
-    structures: &[StructureDef],
-    ground_tile: u8,
-    tile_size: f32,
-    chunk_alloc_per_frame: usize,
-    chunk_rebuild_per_frame: usize,
+    assets: SceneAssets,
+    farm: &mut FarmState,
+    workers: &mut WorkerBoard,
+    ground_items: &mut GroundItems,
+    saves: &SaveManager,
 ) {
+    let SceneAssets {
+        db,
+        registry,
+        structures,
+        content,
+        item_db,
+        ground_tile,
+        tile_size,
+        ..
+    } = assets;
     clear_scenes(map, entities);
 
-    let mut next = TileMap::new_deferred(
-        FARM_MAP_WIDTH,
-        FARM_MAP_HEIGHT,
-        tile_size,
-        Vec2::new(tile_size, tile_size),
-        0.0,
-    );
-    next.set_chunk_work_budget(chunk_alloc_per_frame, chunk_rebuild_per_frame);
+    // The save's map says how much land has been bought.
+    let save = load_farm_snapshot(saves);
+    let (width, height) = save
+        .as_ref()
+        .map(|save| (save.snapshot.width, save.snapshot.height))
+        .filter(|&size| FARM_SIZES.iter().any(|&farm| farm_map_size(farm) == size))
+        .unwrap_or(farm_map_size(FARM_SIZES[0]));
+    let mut next = TileMap::new_deferred(width, height, tile_size, Vec2::new(tile_size, tile_size), 0.0);
+    next.set_chunk_work_budget(assets.chunk_alloc_per_frame, assets.chunk_rebuild_per_frame);
     next.fill_layer(LayerKind::Background, ground_tile);
 
-    let farm_area = farm_core_rect();
+    let farm_area = farm_core_rect(&next);
     let farm_inner_area = inset_tile_rect(farm_area, 1);
-    let loaded = load_farm_snapshot()
-        .map(|snapshot| next.apply_snapshot(&snapshot).is_ok())
-        .unwrap_or(false);
+    let mut loaded = false;
+    let mut saved_entities = Vec::new();
+    ground_items.take();
+    if let Some(save) = save
+        && next.apply_snapshot(&save.snapshot).is_ok()
+    {
+        loaded = true;
+        let mut saved = SavedContent {
+            ground_items: save.ground_items,
+            chests: save.chests,
+            entities: save.entities,
+        };
+        let warnings = content.migrate(&save.content, &mut saved);
+        for warning in warnings.into_iter().chain(saved.drop_missing(item_db)) {
+            eprintln!("farm save: {warning}");
+        }
+        ground_items.replace(saved.ground_items);
+        saved_entities = saved.entities;
+        // Only the first load of a session restores farm state; afterwards the in-memory
+        // state is newer than the save.
+        if farm.soil.is_empty() {
+            farm.built = save.built;
+            farm.chests = ChestStorage::from_chests(saved.chests);
+            farm.crops = CropField::from_crops(save.crops);
+            workers.set_regions(save.regions);
+            let (width, height) = (next.width(), next.height());
+            farm.soil = match save.soil {
+                Some(bytes) if bytes.len() == width * height => SoilLayer::from_bytes(width, height, &bytes),
+                _ => SoilLayer::new(width, height),
+            };
+            if let Some(clock) = save.clock {
+                farm.clock = clock;
+            }
+            // Anything explored before this first load, on the opening expedition, is kept too.
+            if let Some((width, height, bytes)) = save.explored.filter(|(width, height, _)| width * height > 0) {
+                farm.explored.fit(width, height);
+                farm.explored.merge(&bytes);
+            }
+        }
+    }
 
     if !loaded {
+        farm.soil = SoilLayer::new(next.width(), next.height());
+        farm.built.clear();
+        farm.chests = ChestStorage::default();
+        farm.crops = CropField::default();
         spawn_farm_outer_decorations(&mut next, structures, farm_area);
         spawn_farm_inner_decorations(&mut next, structures, farm_inner_area);
     }
 
-    place_farm_bush_border(&mut next, structures, farm_area);
-    next.set_custom_border_hitbox(Some(tile_rect_to_world_rect(farm_inner_area, tile_size)));
+    finish_farm_map(&mut next, structures, farm);
 
     *map = next;
     entities.clear();
+    for saved in &saved_entities {
+        let Some(ent) = Entity::from_saved(db, saved, registry) else {
+            eprintln!("farm save: dropped unknown entity '{}'", saved.def);
+            continue;
+        };
+        if db.entities[ent.instance.def].worker {
+            workers.restore_worker(ent.instance.uid, saved);
+        }
+        entities.push(ent);
+    }
+}
+
+/// Fences the farm and puts back what every farm map has: the gate, the house door, the land
+/// office and the built structures' interactors and footprints.
+fn finish_farm_map(map: &mut TileMap, structures: &[StructureDef], farm: &FarmState) {
+    let farm_area = farm_core_rect(map);
+    let home = inset_tile_rect(farm_home_rect(), 1);
+    place_farm_bush_border(map, structures, farm_area);
+    place_structure_from_defs(map, structures, EXPEDITION_GATE_ID, home.x + home.w / 2, home.y + 1);
+    place_structure_from_defs(map, structures, FARM_HOUSE_DOOR_ID, home.x + home.w / 2 + 4, home.y + 1);
+    place_structure_from_defs(map, structures, LAND_OFFICE_ID, home.x + home.w / 2 + 8, home.y + 1);
+    for built in &farm.built {
+        if let Some(def) = find_structure(structures, &built.id) {
+            map.register_structure_interactors(def, built.x, built.y);
+            map.index_structure(def, built.x, built.y);
+            map.mark_enemy_only(built.x, built.y);
+        }
+    }
+    let tile_size = map.tile_size();
+    map.set_custom_border_hitbox(Some(tile_rect_to_world_rect(inset_tile_rect(farm_area, 1), tile_size)));
 }
 
-pub fn save_farm_scene(map: &TileMap) -> bool {
-    let snapshot = map.snapshot();
-    let json = match serde_json::to_string(&snapshot) {
-        Ok(json) => json,
-        Err(err) => {
-            eprintln!("failed to serialize farm scene: {err}");
-            return false;
+/// Grows the farm to the next of `FARM_SIZES`. The map is rebuilt bigger around the old one,
+/// keeping every tile where it was, and the fence moves out to the new edges. Returns the new
+/// size, or None if the farm is already as big as it gets.
+pub fn expand_farm(
+    map: &mut TileMap,
+    structures: &[StructureDef],
+    farm: &mut FarmState,
+    ground_tile: u8,
+    chunk_alloc_per_frame: usize,
+    chunk_rebuild_per_frame: usize,
+) -> Option<(usize, usize)> {
+    let size = *FARM_SIZES.get(farm_expansion(map)? + 1)?;
+    let (width, height) = farm_map_size(size);
+    let tile_size = map.tile_size();
+    let old = farm_core_rect(map);
+    let mut next = TileMap::new_deferred(width, height, tile_size, Vec2::new(tile_size, tile_size), 0.0);
+    next.set_chunk_work_budget(chunk_alloc_per_frame, chunk_rebuild_per_frame);
+    next.apply_snapshot(&map.snapshot().grown(width, height, ground_tile)).ok()?;
+
+    // The old fence's right and bottom sides come down, and anything standing where the new
+    // ones go makes way.
+    let fences = [right_and_bottom_edges(old), right_and_bottom_edges(farm_core_rect(&next))].concat();
+    let areas: Vec<Rect> = fences.iter().map(|&fence| tile_rect_to_world_rect(fence, tile_size)).collect();
+    next.remove_breakable_structures(&areas);
+    for fence in fences {
+        for y in fence.y..fence.max_y() {
+            for x in fence.x..fence.max_x() {
+                next.set_collision(x, y, false);
+            }
         }
+    }
+
+    farm.soil = farm.soil.grown(width, height);
+    finish_farm_map(&mut next, structures, farm);
+    *map = next;
+    Some(size)
+}
+
+fn right_and_bottom_edges(rect: TileRect) -> [TileRect; 2] {
+    [
+        TileRect {
+            x: rect.max_x() - 1,
+            y: rect.y,
+            w: 1,
+            h: rect.h,
+        },
+        TileRect {
+            x: rect.x,
+            y: rect.max_y() - 1,
+            w: rect.w,
+            h: 1,
+        },
+    ]
+}
+
+/// Encodes the farm for [`SaveManager::write_farm`].
+pub fn save_farm_scene(
+    map: &TileMap,
+    ground_items: &[GroundItem],
+    entities: &[Entity],
+    db: &EntityDatabase,
+    workers: &WorkerBoard,
+    farm: &FarmState,
+    content: &ContentManifest,
+) -> Option<Vec<u8>> {
+    let save = FarmSave {
+        version: SAVE_VERSION,
+        content: content.versions().clone(),
+        clock: farm.clock,
+        map: EncodedSnapshot::encode(&map.snapshot()),
+        soil: RleLayer::encode(&farm.soil.to_bytes()),
+        ground_items: ground_items.to_vec(),
+        built: farm.built.clone(),
+        chests: farm.chests.chests().to_vec(),
+        crops: farm.crops.crops().to_vec(),
+        entities: entities
+            .iter()
+            .filter(|ent| !ent.instance.is_dying())
+            .map(|ent| {
+                let mut saved = ent.to_saved(db);
+                workers.save_worker(ent.instance.uid, &mut saved);
+                saved
+            })
+            .collect(),
+        regions: workers.regions().to_vec(),
+        explored: SavedExploration {
+            width: farm.explored.size().0,
+            height: farm.explored.size().1,
+            bits: RleLayer::encode(farm.explored.to_bytes()),
+        },
     };
-    save_farm_snapshot_json(&json)
+    save::encode(&save, FARM_SAVE_FORMAT)
+        .map_err(|err| eprintln!("failed to serialize farm scene: {err}"))
+        .ok()
 }
 
-fn spawn_expedition_edge_decorations(map: &mut TileMap, structures: &[StructureDef]) {
+/// Scatters the `i`th of the expedition's edge decorations around the band along its border.
+fn spawn_expedition_edge_decorations(map: &mut TileMap, structures: &[StructureDef], i: usize) {
     let band = EXPEDITION_EDGE_BAND
         .min(map.width() / 2)
         .min(map.height() / 2);
@@ -192,14 +1009,121 @@ fn spawn_expedition_edge_decorations(map: &mut TileMap, structures: &[StructureD
         .saturating_mul(map.height())
         .saturating_sub(inner.w.saturating_mul(inner.h));
 
-    for (i, id) in DECOR_STRUCTURE_IDS.iter().enumerate() {
-        let Some(def) = find_structure(structures, id) else {
-            continue;
+    let Some(def) = find_structure(structures, EXPEDITION_DECOR_STRUCTURE_IDS[i]) else {
+        return;
+    };
+    let seed = EXPEDITION_DECOR_SEED ^ ((i as u32 + 1).wrapping_mul(0x9E37_79B9));
+    scatter_structure_where(map, def, seed, edge_area_tiles, |candidate| {
+        inner.w == 0 || inner.h == 0 || !tile_rect_intersects(candidate, inner)
+    });
+}
+
+/// Places the extraction beacon `distance` tiles from `arrival` in a random direction, on the
+/// first clear spot found. Returns its world center.
+fn place_extraction_beacon(map: &mut TileMap, structures: &[StructureDef], arrival: Vec2, distance: f32) -> Vec2 {
+    let tile_size = map.tile_size();
+    let Some(def) = find_structure(structures, EXTRACTION_BEACON) else {
+        return arrival;
+    };
+    let (w, h) = (def.structure.width(), def.structure.height());
+    let max = vec2((map.width() - w) as f32, (map.height() - h) as f32);
+    let mut site = None;
+    for _ in 0..32 {
+        let angle = random_range(0.0, std::f32::consts::TAU);
+        let tile = (arrival / tile_size + Vec2::from_angle(angle) * distance).clamp(Vec2::ZERO, max);
+        let rect = TileRect {
+            x: tile.x as usize,
+            y: tile.y as usize,
+            w,
+            h,
         };
-        let seed = EXPEDITION_DECOR_SEED ^ ((i as u32 + 1).wrapping_mul(0x9E37_79B9));
-        scatter_structure_where(map, def, seed, edge_area_tiles, |candidate| {
-            inner.w == 0 || inner.h == 0 || !tile_rect_intersects(candidate, inner)
-        });
+        site = Some(rect);
+        if map.structure_site_clear(def, rect.x, rect.y) {
+            break;
+        }
+    }
+    let Some(rect) = site else {
+        return arrival;
+    };
+    map.place_structure_def(def, rect.x, rect.y);
+    tile_rect_to_world_rect(rect, tile_size).center()
+}
+
+/// Puts a waypoint somewhere clear in every sector of the map except the one the player
+/// arrives in, which already has the gate. They stand in the same spots every trip, so the
+/// ones already explored stay found.
+fn place_waypoints(map: &mut TileMap, structures: &[StructureDef], arrival: Vec2) {
+    let tile_size = map.tile_size();
+    let Some(def) = find_structure(structures, WAYPOINT_ID) else {
+        return;
+    };
+    let (w, h) = (def.structure.width(), def.structure.height());
+    let sector = (map.width() / WAYPOINT_SECTORS, map.height() / WAYPOINT_SECTORS);
+    let home = ((arrival.x / tile_size) as usize / sector.0, (arrival.y / tile_size) as usize / sector.1);
+    for sy in 0..WAYPOINT_SECTORS {
+        for sx in 0..WAYPOINT_SECTORS {
+            if (sx, sy) == home {
+                continue;
+            }
+            let index = (sy * WAYPOINT_SECTORS + sx) as u32;
+            for attempt in 0..32 {
+                let (hx, hy) = (hash_u32(index, WAYPOINT_SEED, attempt), hash_u32(index, WAYPOINT_SEED, attempt + 32));
+                let rect = TileRect {
+                    x: sx * sector.0 + hx as usize % sector.0.saturating_sub(w).max(1),
+                    y: sy * sector.1 + hy as usize % sector.1.saturating_sub(h).max(1),
+                    w,
+                    h,
+                };
+                if map.structure_site_clear(def, rect.x, rect.y) {
+                    map.place_structure_def(def, rect.x, rect.y);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Scatters the `i`th of the expedition's resource nodes, keeping clear of the arrival.
+fn spawn_expedition_resources(map: &mut TileMap, structures: &[StructureDef], arrival: Vec2, i: usize) {
+    let tile_size = map.tile_size();
+    let (ax, ay) = ((arrival.x / tile_size) as usize, (arrival.y / tile_size) as usize);
+    let clearing = TileRect {
+        x: ax.saturating_sub(EXPEDITION_ARRIVAL_CLEARING),
+        y: ay.saturating_sub(EXPEDITION_ARRIVAL_CLEARING),
+        w: EXPEDITION_ARRIVAL_CLEARING * 2,
+        h: EXPEDITION_ARRIVAL_CLEARING * 2,
+    };
+    let area_tiles = map.width().saturating_mul(map.height());
+    let Some(def) = find_structure(structures, EXPEDITION_RESOURCE_IDS[i]) else {
+        return;
+    };
+    let seed = EXPEDITION_RESOURCE_SEED ^ ((i as u32 + 1).wrapping_mul(0x85EB_CA6B));
+    scatter_structure_where(map, def, seed, area_tiles, |candidate| !tile_rect_intersects(candidate, clearing));
+}
+
+/// Expedition resources broken so far, kept gone each time the expedition is rebuilt until
+/// their `respawn_days` have passed.
+#[derive(Default)]
+pub struct HarvestedNodes {
+    /// Each node's area and the day it grows back.
+    nodes: Vec<(Rect, u32)>,
+}
+
+impl HarvestedNodes {
+    pub fn record(&mut self, broken: &[BrokenStructure], structures: &[StructureDef], today: u32) {
+        for broken in broken {
+            let days = find_structure(structures, &broken.structure_id).map_or(1, |def| def.respawn_days);
+            self.nodes.push((broken.rect, today + days));
+        }
+    }
+
+    /// Lets the nodes due back by `today` grow back the next time the expedition is built.
+    pub fn regrow(&mut self, today: u32) {
+        self.nodes.retain(|&(_, back)| back > today);
+    }
+
+    pub fn areas(&self) -> Vec<Rect> {
+        self.nodes.iter().map(|&(rect, _)| rect).collect()
     }
 }
 
@@ -311,10 +1235,7 @@ where
     let max_x = map.width() - sw;
     let max_y = map.height() - sh;
     let attempts = (target * 18).max(64);
-    let tile_size = map.tile_size();
-    let min_distance = def.min_distance.max(0.0);
     let mut placed = 0usize;
-    let mut placed_rects: Vec<Rect> = Vec::with_capacity(target.min(512));
 
     for i in 0..attempts {
         if placed >= target {
@@ -323,52 +1244,42 @@ where
 
         let x = (hash_u32(i as u32, seed, 11) as usize) % (max_x + 1);
         let y = (hash_u32(i as u32, seed, 37) as usize) % (max_y + 1);
-        let rect = TileRect { x, y, w: sw, h: sh };
-        if !allow(rect) {
-            continue;
-        }
-        if structure_footprint_blocked(map, rect) {
-            continue;
-        }
-
-        let world = tile_rect_to_world_rect(rect, tile_size);
-        let padded = if min_distance > 0.0 {
-            Rect::new(
-                world.x - min_distance,
-                world.y - min_distance,
-                world.w + min_distance * 2.0,
-                world.h + min_distance * 2.0,
-            )
-        } else {
-            world
-        };
-        if placed_rects.iter().any(|other| other.overlaps(&padded)) {
+        if !allow(TileRect { x, y, w: sw, h: sh }) || !map.structure_site_clear(def, x, y) {
             continue;
         }
 
         map.place_structure_def(def, x, y);
-        placed_rects.push(padded);
         placed += 1;
     }
 
     placed
 }
 
-fn structure_footprint_blocked(map: &TileMap, rect: TileRect) -> bool {
-    for y in rect.y..rect.max_y() {
-        for x in rect.x..rect.max_x() {
-            if map.is_solid(x, y)
-                || map.tile_at(LayerKind::Foreground, x, y) != u8::MAX
-                || map.tile_at(LayerKind::Overlay, x, y) != u8::MAX
-            {
-                return true;
-            }
-        }
+/// Whether `def` fits with its top-left tile at (`x`, `y`) inside the walkable farm area
+/// without overlapping anything already placed.
+pub fn farm_build_site_clear(map: &TileMap, def: &StructureDef, x: usize, y: usize) -> bool {
+    let rect = TileRect {
+        x,
+        y,
+        w: def.structure.width(),
+        h: def.structure.height(),
+    };
+    tile_rect_contains(inset_tile_rect(farm_core_rect(map), 1), rect) && map.structure_site_clear(def, x, y)
+}
+
+/// The fenced farm on `map`, border included: whatever's left once the margin is taken off.
+fn farm_core_rect(map: &TileMap) -> TileRect {
+    TileRect {
+        x: FARM_OUTER_MARGIN,
+        y: FARM_OUTER_MARGIN,
+        w: map.width().saturating_sub(FARM_OUTER_MARGIN * 2),
+        h: map.height().saturating_sub(FARM_OUTER_MARGIN * 2),
     }
-    false
 }
 
-fn farm_core_rect() -> TileRect {
+/// The farm as it starts out, where the gate, house and shops stand however much land is
+/// bought.
+fn farm_home_rect() -> TileRect {
     TileRect {
         x: FARM_OUTER_MARGIN,
         y: FARM_OUTER_MARGIN,
@@ -377,6 +1288,15 @@ fn farm_core_rect() -> TileRect {
     }
 }
 
+fn farm_map_size((width, height): (usize, usize)) -> (usize, usize) {
+    (width + FARM_OUTER_MARGIN * 2, height + FARM_OUTER_MARGIN * 2)
+}
+
+/// Which of `FARM_SIZES` the farm on `map` is, or None if it isn't one.
+pub fn farm_expansion(map: &TileMap) -> Option<usize> {
+    FARM_SIZES.iter().position(|&size| farm_map_size(size) == (map.width(), map.height()))
+}
+
 fn inset_tile_rect(rect: TileRect, inset: usize) -> TileRect {
     let doubled = inset.saturating_mul(2);
     let w = rect.w.saturating_sub(doubled);
@@ -427,104 +1347,69 @@ fn hash_u32(x: u32, seed: u32, salt: u32) -> u32 {
     v
 }
 
-fn load_farm_snapshot() -> Option<TileMapSnapshot> {
-    let json = load_farm_snapshot_json()?;
-    serde_json::from_str(&json).ok()
-}
-
-#[cfg(not(target_arch = "wasm32"))]
-fn farm_save_path() -> Option<std::path::PathBuf> {
-    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
-    Some(std::path::PathBuf::from(home).join(".cropbots").join("farm.json"))
-}
-
-#[cfg(not(target_arch = "wasm32"))]
-fn save_farm_snapshot_json(json: &str) -> bool {
-    let Some(path) = farm_save_path() else {
-        return false;
-    };
-    let Some(parent) = path.parent() else {
-        return false;
-    };
-    if std::fs::create_dir_all(parent).is_err() {
-        return false;
-    }
-    std::fs::write(path, json.as_bytes()).is_ok()
-}
-
-#[cfg(not(target_arch = "wasm32"))]
-fn load_farm_snapshot_json() -> Option<String> {
-    let path = farm_save_path()?;
-    std::fs::read_to_string(path).ok()
-}
-
-#[cfg(target_arch = "wasm32")]
-fn save_farm_snapshot_json(json: &str) -> bool {
-    wasm_storage_set_item(FARM_STORAGE_KEY, json)
-}
-
-#[cfg(target_arch = "wasm32")]
-fn load_farm_snapshot_json() -> Option<String> {
-    wasm_storage_get_item(FARM_STORAGE_KEY)
+/// Loads the newest of the slot's farm saves that decodes, falling back to older rotations
+/// if a save is damaged.
+fn load_farm_snapshot(saves: &SaveManager) -> Option<LoadedFarm> {
+    saves.read_farm().into_iter().find_map(|bytes| {
+        let loaded = if save::is_binary(&bytes) {
+            decode_binary_farm_save(&bytes)
+        } else {
+            decode_json_farm_save(&bytes)
+        };
+        loaded
+            .map_err(|err| eprintln!("failed to load farm save: {err}"))
+            .ok()
+    })
 }
 
-#[cfg(target_arch = "wasm32")]
-fn wasm_storage_set_item(key: &str, value: &str) -> bool {
-    let key_bytes = key.as_bytes();
-    let value_bytes = value.as_bytes();
-    unsafe {
-        mq_storage_set_item(
-            key_bytes.as_ptr(),
-            key_bytes.len(),
-            value_bytes.as_ptr(),
-            value_bytes.len(),
-        ) != 0
+fn decode_binary_farm_save(bytes: &[u8]) -> Result<LoadedFarm, SaveError> {
+    match save::split_binary(bytes)? {
+        (SAVE_VERSION, payload) => LoadedFarm::from_save(save::decode_payload(payload)?),
+        (10, payload) => LoadedFarm::from_v10(save::decode_payload(payload)?),
+        (9, payload) => LoadedFarm::from_v9(save::decode_payload(payload)?),
+        (8, payload) => LoadedFarm::from_v8(save::decode_payload(payload)?),
+        (7, payload) => LoadedFarm::from_v7(save::decode_payload(payload)?),
+        (6, payload) => LoadedFarm::from_v6(save::decode_payload(payload)?),
+        (5, payload) => LoadedFarm::from_v5(save::decode_payload(payload)?),
+        (4, payload) => LoadedFarm::from_v4(save::decode_payload(payload)?),
+        (3, payload) => LoadedFarm::from_v3(save::decode_payload(payload)?),
+        (2, payload) => LoadedFarm::from_v2(save::decode_payload(payload)?),
+        (version, _) => Err(SaveError::Version(version)),
     }
 }
 
-#[cfg(target_arch = "wasm32")]
-fn wasm_storage_get_item(key: &str) -> Option<String> {
-    let key_bytes = key.as_bytes();
-    let len = unsafe { mq_storage_get_item_len(key_bytes.as_ptr(), key_bytes.len()) };
-    if len < 0 {
-        return None;
+/// Reads either a versioned save or a legacy raw-array save from JSON.
+fn decode_json_farm_save(bytes: &[u8]) -> Result<LoadedFarm, SaveError> {
+    let value: serde_json::Value = serde_json::from_slice(bytes)?;
+    match value.get("version").and_then(|version| version.as_u64()) {
+        None => {
+            let legacy: LegacyFarmSave = serde_json::from_value(value)?;
+            Ok(LoadedFarm {
+                snapshot: legacy.map,
+                content: ContentVersions::new(),
+                ground_items: legacy.ground_items,
+                soil: None,
+                clock: None,
+                built: Vec::new(),
+                chests: Vec::new(),
+                crops: Vec::new(),
+                entities: Vec::new(),
+                regions: Vec::new(),
+                explored: None,
+            })
+        }
+        Some(version) if version == SAVE_VERSION as u64 => {
+            LoadedFarm::from_save(serde_json::from_value(value)?)
+        }
+        Some(10) => LoadedFarm::from_v10(serde_json::from_value(value)?),
+        Some(9) => LoadedFarm::from_v9(serde_json::from_value(value)?),
+        Some(8) => LoadedFarm::from_v8(serde_json::from_value(value)?),
+        Some(7) => LoadedFarm::from_v7(serde_json::from_value(value)?),
+        Some(6) => LoadedFarm::from_v6(serde_json::from_value(value)?),
+        Some(5) => LoadedFarm::from_v5(serde_json::from_value(value)?),
+        Some(4) => LoadedFarm::from_v4(serde_json::from_value(value)?),
+        Some(3) => LoadedFarm::from_v3(serde_json::from_value(value)?),
+        Some(2) => LoadedFarm::from_v2(serde_json::from_value(value)?),
+        Some(version) => Err(SaveError::Version(version.min(u16::MAX as u64) as u16)),
     }
-
-    let mut buf = vec![0u8; len as usize];
-    let written = unsafe {
-        mq_storage_get_item(
-            key_bytes.as_ptr(),
-            key_bytes.len(),
-            buf.as_mut_ptr(),
-            buf.len(),
-        )
-    };
-    if written < 0 {
-        return None;
-    }
-    let written = written as usize;
-    if written > buf.len() {
-        return None;
-    }
-    buf.truncate(written);
-    String::from_utf8(buf).ok()
-}
-
-#[cfg(target_arch = "wasm32")]
-unsafe extern "C" {
-    fn mq_storage_set_item(
-        key_ptr: *const u8,
-        key_len: usize,
-        value_ptr: *const u8,
-        value_len: usize,
-    ) -> i32;
-
-    fn mq_storage_get_item_len(key_ptr: *const u8, key_len: usize) -> i32;
-
-    fn mq_storage_get_item(
-        key_ptr: *const u8,
-        key_len: usize,
-        out_ptr: *mut u8,
-        out_len: usize,
-    ) -> i32;
 }
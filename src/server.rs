@@ -0,0 +1,298 @@
+use macroquad::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use crate::dialogue::DialogueBox;
+use crate::difficulty::Difficulty;
+use crate::entity::{Entity, Target};
+use crate::farm::FarmState;
+use crate::headless::WorldData;
+use crate::item::GroundItems;
+use crate::map::{StructureHit, TileMap};
+use crate::net::{DEFAULT_PORT, NetSession, NetWorld};
+use crate::player::PlayerInput;
+use crate::power::PowerGrid;
+use crate::automation::Automation;
+use crate::raid::RaidContext;
+use crate::scene;
+use crate::sim::{self, EntitySim, FarmWorld, SimWorld};
+use crate::slot::{SaveManager, SlotProgress};
+use crate::worker::WorkerBoard;
+
+/// The farm steps at a fixed 60 per second, like the headless runner.
+const TICK_DT: f32 = 1.0 / 60.0;
+/// Ticks run at most per pass, so a stall doesn't turn into a burst of catching up.
+const MAX_TICKS_PER_PASS: u32 = 8;
+/// Seconds between saves of the farm.
+const SAVE_INTERVAL: f32 = 300.0;
+
+/// Set by Ctrl-C, SIGTERM or `quit` on stdin; the server saves and stops once it sees it.
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// `--server port=P slot=N`: host save slot `slot` (0 is the latest played) for co-op clients
+/// without anyone playing on this end.
+pub struct ServerOptions {
+    pub port: u16,
+    pub slot: usize,
+}
+
+impl ServerOptions {
+    /// None unless `--server` is among `args`.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let args: Vec<String> = args.into_iter().collect();
+        if !args.iter().any(|arg| arg == "--server") {
+            return Ok(None);
+        }
+        let mut options = Self {
+            port: DEFAULT_PORT,
+            slot: 0,
+        };
+        for arg in args.iter().filter(|arg| *arg != "--server") {
+            let Some((key, value)) = arg.split_once('=') else {
+                return Err(format!("unexpected argument: {arg}"));
+            };
+            match key {
+                "port" => options.port = value.parse().map_err(|_| format!("bad port: {value}"))?,
+                "slot" => options.slot = value.parse().map_err(|_| format!("bad slot: {value}"))?,
+                _ => return Err(format!("unknown option: {key}")),
+            }
+        }
+        Ok(Some(options))
+    }
+}
+
+/// Loads the farm from the save slot and keeps it running for whoever joins, with entities
+/// going after the remote players, until stopped. Runs without a window, stepping the farm with
+/// the same code as the game. The farm is saved every few minutes and on stopping.
+pub async fn run(options: ServerOptions) {
+    let mut data = WorldData::load().await;
+
+    let mut saves = SaveManager::load();
+    if !saves.select(options.slot) {
+        if !saves.slots().is_empty() {
+            eprintln!("there's no save slot {}, only {}", options.slot, saves.slots().len());
+            return;
+        }
+        if !saves.create("Server", Difficulty::default()) {
+            eprintln!("failed to create a save slot");
+            return;
+        }
+    }
+    let Some(meta) = saves.active().cloned() else {
+        return;
+    };
+    data.db.set_difficulty(meta.difficulty);
+
+    let mut maps = TileMap::new_deferred(1, 1, crate::TILE_SIZE, Vec2::new(crate::TILE_SIZE, crate::TILE_SIZE), 0.0);
+    let mut entities = Vec::<Entity>::new();
+    let mut worker_board = WorkerBoard::default();
+    let mut ground_items = GroundItems::default();
+    let mut farm = FarmState::default();
+    let assets = data.scene_assets();
+    scene::scene_farm(&mut maps, &mut entities, assets, &mut farm, &mut worker_board, &mut ground_items, &saves);
+    // Never sent or hurt; joining players start beside it, and it takes raid rewards.
+    let mut spawn = data.player(scene::farm_spawn_point(&maps));
+    let WorldData {
+        config,
+        grass,
+        structures,
+        db,
+        registry,
+        items,
+        content,
+        crops,
+        mut raids,
+    } = data;
+    let mut session = match NetSession::dedicated(options.port) {
+        Ok(session) => session,
+        Err(err) => {
+            eprintln!("failed to open port {}: {err}", options.port);
+            return;
+        }
+    };
+    catch_stop_signals();
+    println!("serving '{}' on port {}; Ctrl-C or `quit` saves and stops", meta.name, options.port);
+
+    let mut entity_sim = EntitySim::default();
+    let mut power = PowerGrid::default();
+    let mut automation = Automation::default();
+    // Raids announce themselves here; nobody reads it on this end.
+    let mut dialogue = DialogueBox::default();
+    let mut accumulator = 0.0f32;
+    let mut playtime = meta.playtime;
+    let mut save_timer = 0.0f32;
+    let mut last_pass = Instant::now();
+    loop {
+        let now = Instant::now();
+        let dt = now.duration_since(last_pass).as_secs_f32();
+        last_pass = now;
+        accumulator = (accumulator + dt).min(TICK_DT * MAX_TICKS_PER_PASS as f32);
+        while accumulator >= TICK_DT {
+            accumulator -= TICK_DT;
+            farm.update(TICK_DT, &crops);
+            let world = FarmWorld {
+                farm: &mut farm,
+                map: &mut maps,
+                entities: &mut entities,
+                ground_items: &mut ground_items,
+                workers: &mut worker_board,
+                power: &mut power,
+                automation: &mut automation,
+                db: &db,
+                registry: &registry,
+                structures: &structures,
+                items: &items,
+                crops: &crops,
+                ground_tile: grass,
+                host: true,
+            };
+            let broken = sim::step_farm(TICK_DT, world);
+            let tile_size = maps.tile_size();
+            sim::break_structures(broken, &structures, Some(&mut farm), tile_size, &mut ground_items, &items, None);
+
+            let targets = session.player_targets(config.player_iframes);
+            let world = SimWorld {
+                db: &db,
+                registry: &registry,
+                map: &maps,
+                view_height: config.camera_fov,
+            };
+            let mut events = entity_sim.step(&mut entities, world, &targets, TICK_DT);
+            for hit in events.tiles {
+                let broken = maps.damage_structures(hit.area, hit.amount, hit.hit, &structures);
+                sim::break_structures(broken, &structures, Some(&mut farm), tile_size, &mut ground_items, &items, None);
+            }
+            raids.update(
+                TICK_DT,
+                &mut RaidContext {
+                    clock: farm.clock,
+                    // An empty farm has nobody to raid.
+                    on_farm: session.remote_players().next().is_some(),
+                    player_dead: false,
+                    crops: &farm.crops,
+                    map: &maps,
+                    entities: &mut entities,
+                    db: &db,
+                    registry: &registry,
+                    player: &mut spawn,
+                    growth: &config.player_growth,
+                    item_db: &items,
+                    ground_items: &mut ground_items,
+                    dialogue: &mut dialogue,
+                    damage: &mut events.damage,
+                },
+            );
+            for event in &events.damage {
+                match event.target {
+                    Target::Player(hit) => {
+                        let respawn = scene::farm_spawn_point(&maps);
+                        if let Some(message) = session.damage_player(hit.index, event.amount, respawn) {
+                            println!("{message}");
+                        }
+                    }
+                    Target::Entity(target) => {
+                        if let Some(ent) = entities
+                            .iter_mut()
+                            .find(|ent| ent.instance.uid == target.id && !ent.instance.is_dying())
+                        {
+                            ent.instance.apply_damage(event.amount);
+                        }
+                    }
+                    // The crop planted there, or else the structure standing on it.
+                    Target::Position(pos) => {
+                        let (x, y) = ((pos.x / tile_size) as usize, (pos.y / tile_size) as usize);
+                        if farm.crops.is_planted(x, y) {
+                            farm.crops.damage(x, y, event.amount, &crops);
+                            continue;
+                        }
+                        let area = Rect::new(pos.x - 0.5, pos.y - 0.5, 1.0, 1.0);
+                        let broken = maps.damage_structures(area, event.amount, StructureHit::Enemy, &structures);
+                        let farm = Some(&mut farm);
+                        sim::break_structures(broken, &structures, farm, tile_size, &mut ground_items, &items, None);
+                    }
+                }
+            }
+            sim::tick_deaths(&mut entities, &db, TICK_DT, |ent| {
+                let def = &db.entities[ent.instance.def];
+                sim::spawn_drops(&def.drops, ent.hitbox(&db).center(), &mut ground_items, &items);
+            });
+            ground_items.update(TICK_DT);
+        }
+
+        let world = NetWorld {
+            map: &mut maps,
+            entities: &mut entities,
+            db: &db,
+            registry: &registry,
+            player: &mut spawn,
+            input: PlayerInput::default(),
+            on_farm: true,
+        };
+        for message in session.update(dt, world) {
+            println!("{message}");
+        }
+
+        playtime += dt;
+        save_timer += dt;
+        let stopping = STOP.load(Ordering::Relaxed);
+        if save_timer >= SAVE_INTERVAL || stopping {
+            save_timer = 0.0;
+            if let Some(bytes) = scene::save_farm_scene(
+                &maps,
+                ground_items.items(),
+                &entities,
+                &db,
+                &worker_board,
+                &farm,
+                &content,
+            ) {
+                let progress = SlotProgress {
+                    playtime,
+                    day: farm.clock.day,
+                    player_hp: meta.player_hp,
+                    player_xp: meta.player_xp,
+                    upgrades: meta.upgrades.clone(),
+                    equipment: meta.equipment.clone(),
+                };
+                match saves.write_farm(&bytes, progress, None) {
+                    Ok(_) if stopping => println!("saved '{}'", meta.name),
+                    Ok(_) => {}
+                    Err(err) => eprintln!("failed to save the farm: {err}"),
+                }
+            }
+        }
+        if stopping {
+            session.leave();
+            break;
+        }
+        std::thread::sleep(Duration::from_secs_f32((TICK_DT - accumulator).max(0.0)));
+    }
+}
+
+/// Routes Ctrl-C, SIGTERM and a `quit` line on stdin to `STOP`. A stdin that closes, as it does
+/// under most service managers, is left alone.
+fn catch_stop_signals() {
+    #[cfg(unix)]
+    {
+        extern "C" fn on_signal(_: libc::c_int) {
+            // Storing to an atomic is all a signal handler can safely do.
+            STOP.store(true, Ordering::Relaxed);
+        }
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // SAFETY: the handler only touches an atomic.
+        unsafe {
+            libc::signal(libc::SIGINT, handler);
+            libc::signal(libc::SIGTERM, handler);
+        }
+    }
+    std::thread::spawn(|| {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            if matches!(line.trim(), "quit" | "stop") {
+                STOP.store(true, Ordering::Relaxed);
+                return;
+            }
+        }
+    });
+}
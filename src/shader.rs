@@ -0,0 +1,165 @@
+use macroquad::prelude::*;
+use miniquad::{BlendFactor, BlendState, BlendValue, Equation};
+use std::cell::RefCell;
+
+const VERTEX: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1.0);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+"#;
+
+/// `flash` blends the sprite toward white. `dissolve` discards texels whose noise falls below
+/// it, with a glowing rim just above the cut. `uv_rect` is the sprite's place on its texture, so
+/// the noise grid covers the sprite rather than the whole atlas page.
+const SPRITE_FRAGMENT: &str = r#"#version 100
+precision mediump float;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform sampler2D Texture;
+uniform float flash;
+uniform float dissolve;
+uniform vec4 uv_rect;
+
+float noise(vec2 cell) {
+    return fract(sin(dot(cell, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+    vec4 texel = texture2D(Texture, uv) * color;
+    float n = noise(floor((uv - uv_rect.xy) / uv_rect.zw * 16.0));
+    if (dissolve > 0.0 && n < dissolve) {
+        discard;
+    }
+    float rim = dissolve > 0.0 ? step(n, dissolve + 0.12) : 0.0;
+    vec3 rgb = mix(texel.rgb, vec3(1.0), flash);
+    rgb = mix(rgb, vec3(1.0, 0.62, 0.25), rim);
+    gl_FragColor = vec4(rgb, texel.a);
+}
+"#;
+
+/// Photo mode filters over the whole scene. `mode` 1 is black and white, 2 sepia, 3 a warm
+/// vignette; anything else passes the scene through.
+const PHOTO_FRAGMENT: &str = r#"#version 100
+precision mediump float;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform sampler2D Texture;
+uniform float mode;
+
+void main() {
+    vec4 texel = texture2D(Texture, uv) * color;
+    float luma = dot(texel.rgb, vec3(0.299, 0.587, 0.114));
+    vec3 rgb = texel.rgb;
+    if (mode > 0.5 && mode < 1.5) {
+        rgb = vec3(luma);
+    } else if (mode > 1.5 && mode < 2.5) {
+        rgb = vec3(luma * 1.07, luma * 0.87, luma * 0.66);
+    } else if (mode > 2.5) {
+        float edge = smoothstep(0.35, 0.85, distance(uv, vec2(0.5)));
+        rgb = mix(rgb * vec3(1.05, 1.0, 0.9), vec3(0.0), edge * 0.7);
+    }
+    gl_FragColor = vec4(rgb, texel.a);
+}
+"#;
+
+#[derive(Default)]
+struct Shaders {
+    sprite_effect: Option<Material>,
+    photo_filter: Option<Material>,
+}
+
+thread_local! {
+    static SHADERS: RefCell<Shaders> = RefCell::new(Shaders::default());
+}
+
+/// Compiles the game's materials. Without them sprites still fade out but don't flash, and
+/// photos are taken unfiltered.
+pub fn load() {
+    let sprite_effect = compile(
+        "sprite effect",
+        SPRITE_FRAGMENT,
+        vec![
+            UniformDesc::new("flash", UniformType::Float1),
+            UniformDesc::new("dissolve", UniformType::Float1),
+            UniformDesc::new("uv_rect", UniformType::Float4),
+        ],
+    );
+    let photo_filter = compile("photo filter", PHOTO_FRAGMENT, vec![UniformDesc::new("mode", UniformType::Float1)]);
+    SHADERS.with(|cell| {
+        *cell.borrow_mut() = Shaders {
+            sprite_effect,
+            photo_filter,
+        }
+    });
+}
+
+fn compile(name: &str, fragment: &str, uniforms: Vec<UniformDesc>) -> Option<Material> {
+    let material = load_material(
+        ShaderSource::Glsl { vertex: VERTEX, fragment },
+        MaterialParams {
+            uniforms,
+            pipeline_params: PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    );
+    material
+        .map_err(|err| eprintln!("failed to compile {name} shader: {err}"))
+        .ok()
+}
+
+/// Runs `draw` with the sprite effect applied to a sprite covering `uv_rect` of its texture.
+/// Returns false, drawing nothing, if the shader isn't available.
+pub fn with_sprite_effect(flash: f32, dissolve: f32, uv_rect: Rect, draw: impl FnOnce()) -> bool {
+    SHADERS.with(|cell| {
+        let shaders = cell.borrow();
+        let Some(material) = shaders.sprite_effect.as_ref() else {
+            return false;
+        };
+        material.set_uniform("flash", flash.clamp(0.0, 1.0));
+        material.set_uniform("dissolve", dissolve.clamp(0.0, 1.0));
+        material.set_uniform("uv_rect", vec4(uv_rect.x, uv_rect.y, uv_rect.w.max(f32::EPSILON), uv_rect.h.max(f32::EPSILON)));
+        gl_use_material(material);
+        draw();
+        gl_use_default_material();
+        true
+    })
+}
+
+/// Runs `draw` through photo filter `mode`. Returns false, drawing nothing, if the shader isn't
+/// available.
+pub fn with_photo_filter(mode: f32, draw: impl FnOnce()) -> bool {
+    SHADERS.with(|cell| {
+        let shaders = cell.borrow();
+        let Some(material) = shaders.photo_filter.as_ref() else {
+            return false;
+        };
+        material.set_uniform("mode", mode);
+        gl_use_material(material);
+        draw();
+        gl_use_default_material();
+        true
+    })
+}
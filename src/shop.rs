@@ -0,0 +1,293 @@
+use macroquad::prelude::*;
+use crate::font::{draw_text, measure_text};
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::path::Path;
+use crate::helpers::{data_path, load_wasm_manifest_files};
+use crate::item::{ItemDatabase, ItemStack};
+use crate::locale::Locale;
+use crate::player::Player;
+
+const PANEL_WIDTH: f32 = 520.0;
+const ROW_HEIGHT: f32 = 28.0;
+const ICON_SIZE: f32 = 20.0;
+
+#[derive(Debug)]
+pub enum ShopLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for ShopLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ShopLoadError {}
+
+impl From<std::io::Error> for ShopLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for ShopLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ShopPrice {
+    pub item: String,
+    pub price: u32,
+}
+
+/// A shop run by an entity: what it sells and what it pays for items.
+#[derive(Clone, Deserialize)]
+pub struct ShopDef {
+    pub id: String,
+    pub name: String,
+    /// Entity def id of the shopkeeper.
+    pub entity: String,
+    #[serde(default)]
+    pub stock: Vec<ShopPrice>,
+    #[serde(default)]
+    pub buys: Vec<ShopPrice>,
+}
+
+pub struct ShopDatabase {
+    shops: Vec<ShopDef>,
+}
+
+impl ShopDatabase {
+    pub fn empty() -> Self {
+        Self { shops: Vec::new() }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, ShopLoadError> {
+        let dir = dir.as_ref();
+        let mut shops = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let files = load_wasm_manifest_files(&dir, &["general_store.yaml"]).await;
+            for file in files {
+                let path = format!("{}/{}", dir, file);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| ShopLoadError::Io(std::io::Error::other(err.to_string())))?;
+                shops.push(serde_yaml::from_str::<ShopDef>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                shops.push(serde_yaml::from_str::<ShopDef>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+        for (i, shop) in shops.iter().enumerate() {
+            if shops[..i].iter().any(|other| other.id == shop.id) {
+                eprintln!("duplicate shop id '{}'", shop.id);
+            }
+        }
+        Ok(Self { shops })
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ShopDef> {
+        self.shops.get(index)
+    }
+
+    /// Entity def ids of every shopkeeper.
+    pub fn keepers(&self) -> impl Iterator<Item = &str> {
+        self.shops.iter().map(|shop| shop.entity.as_str())
+    }
+
+    /// The shop run by entities of def `entity_id`, if any.
+    pub fn find_by_entity(&self, entity_id: &str) -> Option<usize> {
+        self.shops.iter().position(|shop| shop.entity == entity_id)
+    }
+}
+
+/// Buy/sell window. Clicking a stock row buys one; clicking a sell row sells one.
+#[derive(Default)]
+pub struct ShopUi {
+    open: Option<usize>,
+}
+
+impl ShopUi {
+    pub fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+
+    pub fn open(&mut self, shop: usize) {
+        self.open = Some(shop);
+    }
+
+    pub fn close(&mut self) {
+        self.open = None;
+    }
+
+    /// Handles closing and clicks. Returns a message when a trade is refused.
+    pub fn update(
+        &mut self,
+        shops: &ShopDatabase,
+        items: &ItemDatabase,
+        player: &mut Player,
+    ) -> Option<String> {
+        let shop = self.open.and_then(|index| shops.get(index))?;
+        if is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::E) {
+            self.close();
+            return None;
+        }
+        if !is_mouse_button_pressed(MouseButton::Left) {
+            return None;
+        }
+        let mouse = vec2(mouse_position().0, mouse_position().1);
+        let panel = panel_rect(shop);
+        if !panel.contains(mouse) {
+            self.close();
+            return None;
+        }
+
+        if let Some(offer) = hovered_row(&shop.stock, buy_column(panel), mouse) {
+            if player.coins() < offer.price {
+                return Some(format!("not enough coins for {}", offer.item));
+            }
+            if player.inventory_mut().add(items, &offer.item, 1) > 0 {
+                return Some("inventory full".to_string());
+            }
+            player.spend_coins(offer.price);
+            return None;
+        }
+
+        let sellable = sellable(shop, player);
+        if let Some(offer) = hovered_row(&sellable, sell_column(panel), mouse) {
+            let one = ItemStack {
+                item: offer.item.clone(),
+                count: 1,
+            };
+            if player.inventory_mut().remove_all(std::slice::from_ref(&one)) {
+                player.add_coins(offer.price);
+            }
+        }
+        None
+    }
+
+    pub fn draw(&self, shops: &ShopDatabase, items: &ItemDatabase, player: &Player, locale: &Locale) {
+        let Some(shop) = self.open.and_then(|index| shops.get(index)) else {
+            return;
+        };
+        let panel = panel_rect(shop);
+        let mouse = vec2(mouse_position().0, mouse_position().1);
+        draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.05, 0.05, 0.08, 0.9));
+        draw_rectangle_lines(panel.x, panel.y, panel.w, panel.h, 2.0, GRAY);
+        draw_text(&shop.name, panel.x + 12.0, panel.y + 26.0, 24.0, WHITE);
+        let coins = locale.format_currency(player.coins() as i64);
+        let size = measure_text(&coins, 20.0);
+        draw_text(&coins, panel.right() - size.width - 12.0, panel.y + 26.0, 20.0, GOLD);
+
+        let buy = buy_column(panel);
+        let sell = sell_column(panel);
+        draw_text("Buy", buy.x, buy.y - 8.0, 20.0, LIGHTGRAY);
+        draw_text("Sell", sell.x, sell.y - 8.0, 20.0, LIGHTGRAY);
+        for (i, offer) in shop.stock.iter().enumerate() {
+            let affordable = player.coins() >= offer.price;
+            draw_row(items, locale, offer, None, row_rect(buy, i), mouse, affordable);
+        }
+        for (i, offer) in sellable(shop, player).iter().enumerate() {
+            let owned = player.inventory().count(&offer.item);
+            draw_row(items, locale, offer, Some(owned), row_rect(sell, i), mouse, true);
+        }
+        draw_text("click to trade, E to close", panel.x + 12.0, panel.bottom() - 12.0, 16.0, GRAY);
+    }
+}
+
+/// Items the shop buys that the player currently has.
+fn sellable(shop: &ShopDef, player: &Player) -> Vec<ShopPrice> {
+    shop.buys
+        .iter()
+        .filter(|offer| player.inventory().count(&offer.item) > 0)
+        .cloned()
+        .collect()
+}
+
+fn draw_row(
+    items: &ItemDatabase,
+    locale: &Locale,
+    offer: &ShopPrice,
+    owned: Option<u32>,
+    rect: Rect,
+    mouse: Vec2,
+    enabled: bool,
+) {
+    if rect.contains(mouse) {
+        draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(1.0, 1.0, 1.0, 0.1));
+    }
+    if let Some(texture) = items.get(&offer.item).and_then(|def| def.texture.as_ref()) {
+        texture.draw(
+            rect.x + 4.0,
+            rect.y + (rect.h - ICON_SIZE) * 0.5,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(ICON_SIZE, ICON_SIZE)),
+                ..Default::default()
+            },
+        );
+    }
+    let label = match owned {
+        Some(count) => format!("{} x{}", offer.item, locale.format_int(count as i64)),
+        None => offer.item.clone(),
+    };
+    let color = if enabled { WHITE } else { GRAY };
+    draw_text(&label, rect.x + ICON_SIZE + 10.0, rect.y + rect.h * 0.65, 18.0, color);
+    let price = locale.format_currency(offer.price as i64);
+    let size = measure_text(&price, 18.0);
+    let price_color = if enabled { GOLD } else { GRAY };
+    draw_text(&price, rect.right() - size.width - 6.0, rect.y + rect.h * 0.65, 18.0, price_color);
+}
+
+fn hovered_row(offers: &[ShopPrice], column: Rect, mouse: Vec2) -> Option<&ShopPrice> {
+    offers
+        .iter()
+        .enumerate()
+        .find(|(i, _)| row_rect(column, *i).contains(mouse))
+        .map(|(_, offer)| offer)
+}
+
+/// Tall enough for the longer of the shop's two lists, so every row can be clicked.
+fn panel_rect(shop: &ShopDef) -> Rect {
+    let rows = shop.stock.len().max(shop.buys.len()).max(1);
+    let height = 96.0 + rows as f32 * ROW_HEIGHT;
+    Rect::new(
+        (screen_width() - PANEL_WIDTH) * 0.5,
+        (screen_height() - height) * 0.5,
+        PANEL_WIDTH,
+        height,
+    )
+}
+
+fn buy_column(panel: Rect) -> Rect {
+    Rect::new(panel.x + 12.0, panel.y + 64.0, panel.w * 0.5 - 18.0, panel.h - 96.0)
+}
+
+fn sell_column(panel: Rect) -> Rect {
+    Rect::new(panel.x + panel.w * 0.5 + 6.0, panel.y + 64.0, panel.w * 0.5 - 18.0, panel.h - 96.0)
+}
+
+fn row_rect(column: Rect, index: usize) -> Rect {
+    Rect::new(column.x, column.y + index as f32 * ROW_HEIGHT, column.w, ROW_HEIGHT - 2.0)
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
@@ -0,0 +1,653 @@
+use macroquad::prelude::*;
+use std::collections::HashMap;
+use crate::entity::{
+    BerserkDef, DEFAULT_IFRAMES, DamageCooldowns, DamageEvent, Entity, EntityCommand, EntityCommands, EntityContext,
+    EntityDatabase, EntityDrop, EntityKind, EntityTarget, HitTarget, MinionFate, MovementRegistry, PlayerTarget, Target,
+    TileDamageEvent,
+};
+use crate::map::{BrokenStructure, StructureDef, TileMap};
+use crate::attack::{HitFilter, Projectile};
+use crate::automation::Automation;
+use crate::crop::CropDatabase;
+use crate::farm::FarmState;
+use crate::item::{GroundItems, ItemDatabase};
+use crate::particle::ParticleSystem;
+use crate::power::PowerGrid;
+use crate::worker::{WorkSite, WorkerBoard};
+use crate::{build, conveyor, helpers, turret};
+
+/// What the entity step reads but never changes.
+#[derive(Clone, Copy)]
+pub struct SimWorld<'a> {
+    pub db: &'a EntityDatabase,
+    pub registry: &'a MovementRegistry,
+    pub map: &'a TileMap,
+    pub view_height: f32,
+}
+
+/// Hits dealt during one step, left for the caller to apply.
+#[derive(Default)]
+pub struct StepEvents {
+    pub damage: Vec<DamageEvent>,
+    pub tiles: Vec<TileDamageEvent>,
+    /// Where entities that started a dash this step were.
+    pub dashes: Vec<Vec2>,
+    /// Particle effect ids to burst, and where.
+    pub particles: Vec<(String, Vec2)>,
+    /// Sound ids to play, and where.
+    pub sounds: Vec<(String, Vec2)>,
+}
+
+/// Seconds the player's last attacker is remembered for companions to go after.
+const ATTACKER_MEMORY: f32 = 5.0;
+/// Fewest living entities worth spreading an update across threads for.
+const PARALLEL_MIN_ENTITIES: usize = 64;
+
+/// Entity simulation state that outlives a frame but belongs to no single entity. Nothing here
+/// draws or plays sound, so the headless runner steps the world with the same code as the game.
+pub struct EntitySim {
+    target_cache: HashMap<(u64, u8), Option<EntityTarget>>,
+    damage_cooldowns: DamageCooldowns,
+    crowd: CrowdSolver,
+    /// One per entity, reused from step to step.
+    commands: Vec<EntityCommands>,
+    projectiles: Vec<Projectile>,
+    /// Uid of the entity that last hurt the player, and seconds until it's forgotten.
+    player_attacker: Option<(u64, f32)>,
+    parallel: bool,
+}
+
+impl Default for EntitySim {
+    fn default() -> Self {
+        Self {
+            target_cache: HashMap::new(),
+            damage_cooldowns: DamageCooldowns::default(),
+            crowd: CrowdSolver::default(),
+            commands: Vec::new(),
+            projectiles: Vec::new(),
+            player_attacker: None,
+            parallel: true,
+        }
+    }
+}
+
+impl EntitySim {
+    /// Whether big crowds update on several threads, on native builds. Entities draw random
+    /// numbers as they update, so a seeded run only plays out the same way with this off.
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
+
+    /// Forgets targets, cooldowns and shots in flight, for when the entities they refer to are
+    /// replaced.
+    pub fn clear(&mut self) {
+        self.target_cache.clear();
+        self.damage_cooldowns.clear();
+        self.projectiles.clear();
+        self.player_attacker = None;
+    }
+
+    pub fn projectiles(&self) -> &[Projectile] {
+        &self.projectiles
+    }
+
+    /// Moves every living entity by `dt`, carries out the spawns and despawns they asked for and
+    /// pushes apart the ones that overlap. `players` are the players that can be targeted.
+    pub fn step(
+        &mut self,
+        entities: &mut Vec<Entity>,
+        world: SimWorld<'_>,
+        players: &[PlayerTarget],
+        dt: f32,
+    ) -> StepEvents {
+        let SimWorld {
+            db,
+            registry,
+            map,
+            view_height,
+        } = world;
+        let targets: Vec<EntityTarget> = entities
+            .iter()
+            .map(|ent| EntityTarget {
+                id: ent.instance.uid,
+                def: ent.instance.def,
+                kind: db.entities[ent.instance.def].kind,
+                pos: ent.instance.pos,
+                hitbox: ent.hitbox(db),
+                alive: ent.instance.hp > 0.0,
+                iframes: ent.instance.stats.get("iframes", DEFAULT_IFRAMES),
+            })
+            .collect();
+
+        self.damage_cooldowns.tick(dt);
+        if let Some((_, left)) = self.player_attacker.as_mut() {
+            *left -= dt;
+        }
+        self.player_attacker = self.player_attacker.filter(|&(_, left)| left > 0.0);
+        let ctx = EntityContext {
+            players: players.to_vec(),
+            target: None,
+            grid: TargetGrid::build(&targets),
+            entities: targets,
+            target_cache: std::mem::take(&mut self.target_cache),
+            damage_cooldowns: std::mem::take(&mut self.damage_cooldowns),
+            view_height,
+            player_attacker: self.player_attacker.map(|(uid, _)| uid),
+        };
+        self.commands.resize_with(entities.len(), EntityCommands::default);
+        let update = |ent: &mut Entity, out: &mut EntityCommands| {
+            out.clear();
+            if ent.instance.is_dying() || ent.instance.ridden {
+                return;
+            }
+            let was_dashing = ent.instance.is_dashing();
+            ent.update(dt, db, &ctx, out, map, registry);
+            ent.clamp_to_map(map, db);
+            if !was_dashing && ent.instance.is_dashing() {
+                out.dash_started = Some(ent.instance.pos);
+            }
+        };
+        let count = entities.len();
+        let parallel = self.parallel && count >= PARALLEL_MIN_ENTITIES;
+        for_each_entity(parallel, entities, &mut self.commands[..count], update);
+
+        // Applied in entity order, so the outcome doesn't depend on which thread finished first.
+        let EntityContext {
+            target_cache,
+            damage_cooldowns,
+            entities: targets,
+            ..
+        } = ctx;
+        self.target_cache = target_cache;
+        self.damage_cooldowns = damage_cooldowns;
+        let mut events = StepEvents::default();
+        let mut spawned = Vec::new();
+        let mut despawned = Vec::new();
+        for (ent, out) in entities.iter_mut().zip(&mut self.commands) {
+            if let Some((key, target)) = out.cached_target.take() {
+                self.target_cache.insert(key, target);
+            }
+            for hit in out.hits.drain(..) {
+                let source = ent.instance.uid;
+                if !self.damage_cooldowns.can_hit(source, hit.target) {
+                    continue;
+                }
+                self.damage_cooldowns.record_hit(source, hit.target, hit.cooldown, hit.iframes);
+                if matches!(hit.target, HitTarget::Player(_)) {
+                    self.player_attacker = Some((source, ATTACKER_MEMORY));
+                }
+                ent.instance.confirm_hit();
+                events.damage.push(hit.event);
+            }
+            events.tiles.append(&mut out.tile_damage);
+            events.dashes.extend(out.dash_started.take());
+            for command in out.queued.drain(..) {
+                match command {
+                    EntityCommand::Spawn { def, pos } => match Entity::spawn(db, &def, pos, registry) {
+                        Some(child) => {
+                            ent.instance.spawned.push(child.instance.uid);
+                            spawned.push(child);
+                        }
+                        None => eprintln!("can't spawn unknown entity '{def}'"),
+                    },
+                    EntityCommand::Despawn { uid } => despawned.push(uid),
+                    EntityCommand::EmitParticles { id, pos } => events.particles.push((id, pos)),
+                    EntityCommand::PlaySound { id, pos } => events.sounds.push((id, pos)),
+                    EntityCommand::FireProjectile(projectile) => self.projectiles.push(projectile),
+                }
+            }
+        }
+        if !despawned.is_empty() {
+            entities.retain(|ent| !despawned.contains(&ent.instance.uid));
+        }
+        entities.extend(spawned);
+        self.step_projectiles(&targets, players, map, dt, &mut events.damage);
+        self.crowd.relax(entities, db, map);
+        events
+    }
+
+    /// Moves shots in flight, dropping them when they expire or meet a wall and landing them on
+    /// the first thing they touch that they're after. `targets` are the entities as the step
+    /// started.
+    fn step_projectiles(
+        &mut self,
+        targets: &[EntityTarget],
+        players: &[PlayerTarget],
+        map: &TileMap,
+        dt: f32,
+        damage: &mut Vec<DamageEvent>,
+    ) {
+        let cooldowns = &mut self.damage_cooldowns;
+        let attacker = &mut self.player_attacker;
+        self.projectiles.retain_mut(|shot| {
+            shot.pos += shot.vel * dt;
+            shot.age += dt;
+            let bounds = Rect::new(shot.pos.x - shot.radius, shot.pos.y - shot.radius, shot.radius * 2.0, shot.radius * 2.0);
+            if shot.age >= shot.lifetime || hits_solid(map, bounds) {
+                return false;
+            }
+            let victim = match shot.hits {
+                HitFilter::Player => players
+                    .iter()
+                    .find(|player| shot.hits_rect(player.hitbox))
+                    .map(|&player| (Target::Player(player), HitTarget::Player(player.index), player.iframes)),
+                HitFilter::Kind(kind) => targets
+                    .iter()
+                    .find(|target| {
+                        target.id != shot.owner && target.alive && target.kind == kind && shot.hits_rect(target.hitbox)
+                    })
+                    .map(|target| (Target::Entity(*target), HitTarget::Entity(target.id), target.iframes)),
+            };
+            let Some((target, hit, iframes)) = victim else {
+                return true;
+            };
+            // A shot spends itself on whatever it meets, hurting it only outside its iframes.
+            if !cooldowns.is_invulnerable(hit) {
+                cooldowns.record_hit(shot.owner, hit, 0.0, iframes);
+                if matches!(hit, HitTarget::Player(_)) {
+                    *attacker = Some((shot.owner, ATTACKER_MEMORY));
+                }
+                damage.push(DamageEvent {
+                    amount: shot.damage,
+                    target,
+                    crit: false,
+                    source: Some(shot.owner),
+                });
+            }
+            false
+        });
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn for_each_entity(
+    parallel: bool,
+    entities: &mut [Entity],
+    commands: &mut [EntityCommands],
+    update: impl Fn(&mut Entity, &mut EntityCommands) + Sync,
+) {
+    if parallel {
+        use rayon::prelude::*;
+        entities
+            .par_iter_mut()
+            .zip(commands.par_iter_mut())
+            .for_each(|(ent, out)| update(ent, out));
+    } else {
+        for (ent, out) in entities.iter_mut().zip(commands) {
+            update(ent, out);
+        }
+    }
+}
+
+/// The web build has no threads to spread across.
+#[cfg(target_arch = "wasm32")]
+fn for_each_entity(
+    _parallel: bool,
+    entities: &mut [Entity],
+    commands: &mut [EntityCommands],
+    update: impl Fn(&mut Entity, &mut EntityCommands),
+) {
+    for (ent, out) in entities.iter_mut().zip(commands) {
+        update(ent, out);
+    }
+}
+
+/// Advances hit, death and hover effects and animations, calls `on_death` once for each entity
+/// whose hp just ran out, and drops the ones whose death effect has finished.
+pub fn tick_deaths(entities: &mut Vec<Entity>, db: &EntityDatabase, dt: f32, mut on_death: impl FnMut(&Entity)) {
+    // Spawns of spawners that just died, with what's to become of them.
+    let mut orphans: Vec<(u64, MinionFate, BerserkDef)> = Vec::new();
+    for ent in entities.iter_mut() {
+        ent.instance.tick_feedback(dt);
+        ent.instance.tick_hover(db, dt);
+        ent.instance.tick_animation(db, dt);
+        if ent.instance.hp <= 0.0 && !ent.instance.is_dying() {
+            on_death(ent);
+            ent.instance.start_dying();
+            if let Some(spawner) = &db.entities[ent.instance.def].spawner
+                && spawner.on_death != MinionFate::Keep
+            {
+                orphans.extend(ent.instance.spawned.drain(..).map(|uid| (uid, spawner.on_death, spawner.berserk)));
+            }
+        }
+    }
+    if !orphans.is_empty() {
+        entities.retain_mut(|ent| {
+            let Some(&(_, fate, berserk)) = orphans.iter().find(|(uid, ..)| *uid == ent.instance.uid) else {
+                return true;
+            };
+            if ent.instance.is_dying() {
+                return true;
+            }
+            match fate {
+                MinionFate::Despawn => return false,
+                MinionFate::Berserk => ent.instance.go_berserk(berserk),
+                MinionFate::Keep => {}
+            }
+            true
+        });
+    }
+    entities.retain(|ent| !ent.instance.death_finished(db));
+}
+
+/// The farm around its entities, for `step_farm`.
+pub struct FarmWorld<'a> {
+    pub farm: &'a mut FarmState,
+    pub map: &'a mut TileMap,
+    pub entities: &'a mut Vec<Entity>,
+    pub ground_items: &'a mut GroundItems,
+    pub workers: &'a mut WorkerBoard,
+    pub power: &'a mut PowerGrid,
+    pub automation: &'a mut Automation,
+    pub db: &'a EntityDatabase,
+    pub registry: &'a MovementRegistry,
+    pub structures: &'a [StructureDef],
+    pub items: &'a ItemDatabase,
+    pub crops: &'a CropDatabase,
+    pub ground_tile: u8,
+    /// False on a co-op client, whose turrets and workers are the host's.
+    pub host: bool,
+}
+
+/// Runs the farm's turrets, power, conveyors, machines and workers for `dt`, dropping what the
+/// machines pick; the game and the server both run the farm through here. Returns the
+/// structures the workers broke, for `break_structures`.
+pub fn step_farm(dt: f32, world: FarmWorld) -> Vec<BrokenStructure> {
+    let tile_size = world.map.tile_size();
+    if world.host {
+        turret::sync(world.entities, world.db, world.registry, world.farm, world.structures, tile_size);
+    }
+    world.power.update(dt, world.farm, world.structures, world.entities, world.db, tile_size);
+    conveyor::update(dt, world.farm, world.structures, world.ground_items, world.items, tile_size);
+    let picked = world.automation.update(dt, world.farm, world.structures, world.power, world.crops, tile_size);
+    for (produce, pos) in picked {
+        world.ground_items.spawn(world.items, &produce.item, produce.count, pos);
+    }
+    if !world.host {
+        return Vec::new();
+    }
+    let mut site = WorkSite {
+        map: world.map,
+        farm: world.farm,
+        ground_items: world.ground_items,
+        structures: world.structures,
+        items: world.items,
+        crops: world.crops,
+        ground_tile: world.ground_tile,
+    };
+    world.workers.update(dt, world.entities, world.db, &mut site)
+}
+
+/// Rolls each of `drops` and leaves what comes up on the ground at `pos`.
+pub fn spawn_drops(drops: &[EntityDrop], pos: Vec2, ground_items: &mut GroundItems, item_db: &ItemDatabase) {
+    for drop in drops {
+        if helpers::random_f32() > drop.chance {
+            continue;
+        }
+        let count = drop.min + helpers::random_u32() % (drop.max.saturating_sub(drop.min) + 1);
+        ground_items.spawn(item_db, &drop.item, count, pos);
+    }
+}
+
+/// Scatters loot, and debris given `particles`, for structures that just broke. `farm`, given on
+/// the farm, forgets the ones the player built and spills their chests.
+pub fn break_structures(
+    broken: Vec<BrokenStructure>,
+    structures: &[StructureDef],
+    mut farm: Option<&mut FarmState>,
+    tile_size: f32,
+    ground_items: &mut GroundItems,
+    item_db: &ItemDatabase,
+    mut particles: Option<&mut ParticleSystem>,
+) {
+    for broken in broken {
+        let center = broken.rect.center();
+        if let Some(farm) = farm.as_deref_mut() {
+            for stack in build::demolish(farm, broken.rect, tile_size) {
+                ground_items.spawn(item_db, &stack.item, stack.count, center);
+            }
+        }
+        let Some(def) = structures.iter().find(|def| def.id == broken.structure_id) else {
+            continue;
+        };
+        spawn_drops(&def.drops, center, ground_items, item_db);
+        if let Some(particle) = def.break_particles.as_deref()
+            && let Some(particles) = particles.as_deref_mut()
+        {
+            particles.burst(particle, center);
+        }
+    }
+}
+
+/// Entities bucketed by the cell their hitbox center falls in, so a search around a point only
+/// looks at the few nearby. Built once per step from the step's targets.
+#[derive(Default)]
+pub struct TargetGrid {
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl TargetGrid {
+    const CELL_SIZE: f32 = 64.0;
+
+    pub fn build(targets: &[EntityTarget]) -> Self {
+        let mut cells: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (index, target) in targets.iter().enumerate() {
+            cells.entry(Self::cell(target.hitbox.center())).or_default().push(index);
+        }
+        Self { cells }
+    }
+
+    fn cell(pos: Vec2) -> (i32, i32) {
+        ((pos.x / Self::CELL_SIZE).floor() as i32, (pos.y / Self::CELL_SIZE).floor() as i32)
+    }
+
+    /// Indices into the step's targets of every entity whose hitbox center may be within
+    /// `radius` of `pos`; the caller checks the actual distance.
+    pub fn near(&self, pos: Vec2, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let (min, max) = (Self::cell(pos - Vec2::splat(radius)), Self::cell(pos + Vec2::splat(radius)));
+        (min.1..=max.1)
+            .flat_map(move |cy| (min.0..=max.0).map(move |cx| (cx, cy)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+/// Pushes overlapping entities apart after they've moved. Neighbour pairs are found once per
+/// step through a spatial hash, then relaxed a few times over, each pass resolving part of every
+/// overlap, so a packed crowd settles into place over a few frames instead of shoving back and
+/// forth. Scratch buffers are kept between steps.
+#[derive(Default)]
+pub struct CrowdSolver {
+    hitboxes: Vec<Rect>,
+    cells: Vec<((i32, i32), usize)>,
+    pairs: Vec<(usize, usize)>,
+    start: Vec<Vec2>,
+    collide_cache: HashMap<(usize, usize), bool>,
+}
+
+impl CrowdSolver {
+    const ITERATIONS: usize = 4;
+    /// Share of each overlap resolved per pass.
+    const RELAXATION: f32 = 0.6;
+    /// Pairs this close without touching are still relaxed, in case an earlier push closes
+    /// the gap.
+    const PAIR_SLACK: f32 = 2.0;
+    /// Most one pair push can move an entity, as a share of the smaller hitbox side.
+    const MAX_PAIR_PUSH: f32 = 0.35;
+    const EPSILON: f32 = 0.0005;
+
+    pub fn relax(&mut self, entities: &mut [Entity], db: &EntityDatabase, map: &TileMap) {
+        if entities.len() < 2 {
+            return;
+        }
+        self.collect_pairs(entities, db);
+        if self.pairs.is_empty() {
+            return;
+        }
+
+        self.start.clear();
+        self.start.extend(entities.iter().map(|ent| ent.instance.pos));
+        for _ in 0..Self::ITERATIONS {
+            let mut any = false;
+            for &(i, j) in &self.pairs {
+                let a_hb = entities[i].hitbox(db);
+                let b_hb = entities[j].hitbox(db);
+                let overlap_x = a_hb.right().min(b_hb.right()) - a_hb.x.max(b_hb.x);
+                let overlap_y = a_hb.bottom().min(b_hb.bottom()) - a_hb.y.max(b_hb.y);
+                if overlap_x <= 0.0 || overlap_y <= 0.0 {
+                    continue;
+                }
+                any = true;
+
+                let delta = b_hb.center() - a_hb.center();
+                let choose_x = if (overlap_x - overlap_y).abs() <= 0.0001 {
+                    if delta.x.abs() != delta.y.abs() {
+                        delta.x.abs() > delta.y.abs()
+                    } else {
+                        pair_sign(i, j, 0xA53C_7E19) > 0.0
+                    }
+                } else {
+                    overlap_x < overlap_y
+                };
+                let max_push = a_hb.w.min(a_hb.h).min(b_hb.w.min(b_hb.h)).max(1.0) * Self::MAX_PAIR_PUSH;
+                let push = if choose_x {
+                    let dir = if delta.x.abs() > 0.0001 { delta.x.signum() } else { pair_sign(i, j, 0x5F4D_CC3B) };
+                    let amount = ((overlap_x + Self::EPSILON) * 0.5 * Self::RELAXATION).min(max_push);
+                    vec2(dir * amount, 0.0)
+                } else {
+                    let dir = if delta.y.abs() > 0.0001 { delta.y.signum() } else { pair_sign(i, j, 0x73D2_A11F) };
+                    let amount = ((overlap_y + Self::EPSILON) * 0.5 * Self::RELAXATION).min(max_push);
+                    vec2(0.0, dir * amount)
+                };
+                entities[i].instance.pos -= push;
+                entities[j].instance.pos += push;
+            }
+            if !any {
+                break;
+            }
+        }
+
+        for (ent, &start) in entities.iter_mut().zip(&self.start) {
+            if ent.instance.pos == start {
+                continue;
+            }
+            ent.clamp_to_map(map, db);
+            // A push never carries an entity into a wall it couldn't walk into.
+            let def = &db.entities[ent.instance.def];
+            if def.collides && hits_solid(map, def.world_hitbox(ent.instance.pos)) {
+                let moved = ent.instance.pos;
+                ent.instance.pos = if !hits_solid(map, def.world_hitbox(vec2(moved.x, start.y))) {
+                    vec2(moved.x, start.y)
+                } else if !hits_solid(map, def.world_hitbox(vec2(start.x, moved.y))) {
+                    vec2(start.x, moved.y)
+                } else {
+                    start
+                };
+            }
+        }
+    }
+
+    /// Fills `pairs` with every living pair whose hitboxes come within `PAIR_SLACK` of each other
+    /// and whose flags let them collide. Each entity goes in the cell of its center; cells are at
+    /// least as big as any hitbox, so only neighbouring cells need checking.
+    fn collect_pairs(&mut self, entities: &[Entity], db: &EntityDatabase) {
+        self.pairs.clear();
+        self.hitboxes.clear();
+        self.cells.clear();
+        self.collide_cache.clear();
+
+        let mut cell_size: f32 = 1.0;
+        for ent in entities {
+            let hb = ent.hitbox(db);
+            cell_size = cell_size.max(hb.w + Self::PAIR_SLACK).max(hb.h + Self::PAIR_SLACK);
+            self.hitboxes.push(Rect::new(
+                hb.x - Self::PAIR_SLACK * 0.5,
+                hb.y - Self::PAIR_SLACK * 0.5,
+                hb.w + Self::PAIR_SLACK,
+                hb.h + Self::PAIR_SLACK,
+            ));
+        }
+        let cell_of = |rect: &Rect| {
+            let center = rect.center();
+            ((center.x / cell_size).floor() as i32, (center.y / cell_size).floor() as i32)
+        };
+        for (idx, ent) in entities.iter().enumerate() {
+            if !ent.instance.is_dying() {
+                self.cells.push((cell_of(&self.hitboxes[idx]), idx));
+            }
+        }
+        self.cells.sort_unstable();
+
+        for &(cell, i) in &self.cells {
+            let a_def = entities[i].instance.def;
+            for cy in cell.1 - 1..=cell.1 + 1 {
+                for cx in cell.0 - 1..=cell.0 + 1 {
+                    let first = self.cells.partition_point(|&(other, _)| other < (cx, cy));
+                    for &(other, j) in &self.cells[first..] {
+                        if other != (cx, cy) {
+                            break;
+                        }
+                        if j <= i || !self.hitboxes[i].overlaps(&self.hitboxes[j]) {
+                            continue;
+                        }
+                        let b_def = entities[j].instance.def;
+                        let key = (a_def.min(b_def), a_def.max(b_def));
+                        let can_collide = *self
+                            .collide_cache
+                            .entry(key)
+                            .or_insert_with(|| entities_should_collide(db, a_def, b_def));
+                        if can_collide {
+                            self.pairs.push((i, j));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+fn pair_sign(i: usize, j: usize, salt: u64) -> f32 {
+    let mut h = (i as u64).wrapping_mul(0x9E37_79B1_85EB_CA87);
+    h ^= (j as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    h ^= salt;
+    if (h & 1) == 0 {
+        -1.0
+    } else {
+        1.0
+    }
+}
+
+fn hits_solid(map: &TileMap, rect: Rect) -> bool {
+    let ts = map.tile_size().max(1.0);
+    if rect.right() <= 0.0 || rect.bottom() <= 0.0 {
+        return false;
+    }
+    let (min_x, min_y) = ((rect.x / ts).floor().max(0.0) as usize, (rect.y / ts).floor().max(0.0) as usize);
+    let (max_x, max_y) = (((rect.right() - 0.001) / ts).floor() as usize, ((rect.bottom() - 0.001) / ts).floor() as usize);
+    (min_y..=max_y).any(|y| (min_x..=max_x).any(|x| map.is_solid(x, y)))
+}
+
+fn entities_should_collide(db: &EntityDatabase, a_def_idx: usize, b_def_idx: usize) -> bool {
+    let a_flags = db.entities[a_def_idx].flags;
+    let b_flags = db.entities[b_def_idx].flags;
+    if (a_flags & crate::entity::DEF_FLAG_NO_ENTITY_COLLISION) != 0
+        || (b_flags & crate::entity::DEF_FLAG_NO_ENTITY_COLLISION) != 0
+    {
+        return false;
+    }
+
+    let a_kind = db.entities[a_def_idx].kind;
+    let b_kind = db.entities[b_def_idx].kind;
+    !blocks_kind(db, a_def_idx, b_kind) && !blocks_kind(db, b_def_idx, a_kind)
+}
+
+fn blocks_kind(db: &EntityDatabase, def_idx: usize, kind: EntityKind) -> bool {
+    let flags = db.entities[def_idx].flags;
+    match kind {
+        EntityKind::Enemy => (flags & crate::entity::DEF_FLAG_NO_ENEMY_COLLISION) != 0,
+        EntityKind::Friend => (flags & crate::entity::DEF_FLAG_NO_FRIEND_COLLISION) != 0,
+        EntityKind::Misc => (flags & crate::entity::DEF_FLAG_NO_MISC_COLLISION) != 0,
+    }
+}
@@ -0,0 +1,66 @@
+use macroquad::prelude::*;
+
+/// Seconds to fade to black, and again to fade back in.
+const FADE_TIME: f32 = 0.8;
+/// Seconds the screen stays black between the two fades.
+const HOLD_TIME: f32 = 0.4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    Out,
+    Hold,
+    In,
+}
+
+/// Screen fade played while the player sleeps. The night passes while the screen is black.
+#[derive(Default)]
+pub struct SleepFade {
+    phase: Option<(Phase, f32)>,
+}
+
+impl SleepFade {
+    pub fn is_active(&self) -> bool {
+        self.phase.is_some()
+    }
+
+    pub fn start(&mut self) {
+        if self.phase.is_none() {
+            self.phase = Some((Phase::Out, 0.0));
+        }
+    }
+
+    /// Advances the fade. Returns true on the frame the screen turns fully black.
+    pub fn update(&mut self, dt: f32) -> bool {
+        let Some((phase, elapsed)) = self.phase.as_mut() else {
+            return false;
+        };
+        *elapsed += dt;
+        match *phase {
+            Phase::Out if *elapsed >= FADE_TIME => {
+                self.phase = Some((Phase::Hold, 0.0));
+                true
+            }
+            Phase::Hold if *elapsed >= HOLD_TIME => {
+                self.phase = Some((Phase::In, 0.0));
+                false
+            }
+            Phase::In if *elapsed >= FADE_TIME => {
+                self.phase = None;
+                false
+            }
+            _ => false,
+        }
+    }
+
+    pub fn draw(&self) {
+        let Some((phase, elapsed)) = self.phase else {
+            return;
+        };
+        let alpha = match phase {
+            Phase::Out => elapsed / FADE_TIME,
+            Phase::Hold => 1.0,
+            Phase::In => 1.0 - elapsed / FADE_TIME,
+        };
+        draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, alpha.clamp(0.0, 1.0)));
+    }
+}
@@ -0,0 +1,616 @@
+use macroquad::prelude::*;
+use image::RgbaImage;
+use image::imageops::{self, FilterType};
+use serde::{Deserialize, Serialize};
+use crate::archive::{ARCHIVE_EXTENSION, ArchiveError, ArchiveFile, SaveArchive};
+use crate::difficulty::Difficulty;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::helpers::cropbots_dir;
+#[cfg(target_arch = "wasm32")]
+use crate::helpers::{
+    base64_decode, base64_encode, wasm_download, wasm_storage_get_item, wasm_storage_get_large, wasm_storage_remove_item,
+    wasm_storage_remove_large, wasm_storage_set_item, wasm_storage_set_large,
+};
+use crate::save::SaveFormat;
+use crate::equipment::Equipment;
+use crate::upgrade::Upgrades;
+
+/// Older copies kept beside each native farm save, from `farm.bin.1` (newest) to `farm.bin.3`.
+pub const SAVE_ROTATIONS: usize = 3;
+pub const THUMBNAIL_WIDTH: u32 = 160;
+pub const THUMBNAIL_HEIGHT: u32 = 90;
+
+// Browser storage only holds strings, so wasm keeps the JSON form.
+pub const FARM_SAVE_FORMAT: SaveFormat = if cfg!(target_arch = "wasm32") {
+    SaveFormat::Json
+} else {
+    SaveFormat::Binary
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+const META_FILE: &str = "meta.json";
+const THUMBNAIL_FILE: &str = "thumbnail.png";
+/// Name the game settings are stored under inside an exported archive.
+#[cfg(not(target_arch = "wasm32"))]
+const SETTINGS_FILE: &str = "config.yaml";
+#[cfg(target_arch = "wasm32")]
+const SLOT_INDEX_KEY: &str = "cropbots:saves";
+/// Where the single farm save lived before slots; migrated into the first slot.
+#[cfg(target_arch = "wasm32")]
+const LEGACY_STORAGE_KEY: &str = "cropbots:farm.json";
+
+/// Summary shown on the title screen, written next to the slot's farm save.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SlotMeta {
+    pub id: String,
+    pub name: String,
+    /// Seconds played on this slot.
+    #[serde(default)]
+    pub playtime: f32,
+    #[serde(default)]
+    pub day: u32,
+    #[serde(default)]
+    pub player_hp: f32,
+    #[serde(default)]
+    pub player_xp: f32,
+    #[serde(default)]
+    pub upgrades: Upgrades,
+    #[serde(default)]
+    pub equipment: Equipment,
+    /// Unix time of the last save, in seconds.
+    #[serde(default)]
+    pub saved_at: f64,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+}
+
+pub struct SaveSlot {
+    pub meta: SlotMeta,
+    pub thumbnail: Option<Texture2D>,
+}
+
+/// Named save slots, one directory each under `~/.cropbots/saves/`, or a set of localStorage
+/// keys on wasm. Browser storage only holds strings, so wasm thumbnails are kept as base64, and
+/// its chunked writes already keep the old save until the new one is whole, so there are no
+/// rotations either.
+pub struct SaveManager {
+    slots: Vec<SaveSlot>,
+    active: Option<usize>,
+}
+
+impl SaveManager {
+    pub fn load() -> Self {
+        let mut slots: Vec<SaveSlot> = read_slot_metas()
+            .into_iter()
+            .map(|meta| SaveSlot {
+                thumbnail: read_thumbnail(&meta.id),
+                meta,
+            })
+            .collect();
+        if slots.is_empty()
+            && let Some(meta) = migrate_legacy_save()
+        {
+            slots.push(SaveSlot { meta, thumbnail: None });
+        }
+        slots.sort_by(|a, b| b.meta.saved_at.total_cmp(&a.meta.saved_at));
+        Self { slots, active: None }
+    }
+
+    pub fn slots(&self) -> &[SaveSlot] {
+        &self.slots
+    }
+
+    pub fn active(&self) -> Option<&SlotMeta> {
+        self.active.and_then(|index| self.slots.get(index)).map(|slot| &slot.meta)
+    }
+
+    pub fn select(&mut self, index: usize) -> bool {
+        if index >= self.slots.len() {
+            return false;
+        }
+        self.active = Some(index);
+        true
+    }
+
+    /// Adds an empty slot called `name` and makes it active.
+    pub fn create(&mut self, name: &str, difficulty: Difficulty) -> bool {
+        let name = name.trim();
+        let name = if name.is_empty() { "Farm" } else { name };
+        let meta = SlotMeta {
+            id: self.unique_id(name),
+            name: name.to_string(),
+            playtime: 0.0,
+            day: 0,
+            player_hp: 0.0,
+            player_xp: 0.0,
+            upgrades: Upgrades::default(),
+            equipment: Equipment::default(),
+            saved_at: miniquad::date::now(),
+            difficulty,
+        };
+        if !write_slot_meta(&meta, &self.slots) {
+            return false;
+        }
+        self.slots.insert(0, SaveSlot { meta, thumbnail: None });
+        self.active = Some(0);
+        true
+    }
+
+    /// Changes the active slot's difficulty and writes its metadata.
+    pub fn set_difficulty(&mut self, difficulty: Difficulty) -> bool {
+        let Some(index) = self.active else {
+            return false;
+        };
+        self.slots[index].meta.difficulty = difficulty;
+        write_slot_meta(&self.slots[index].meta, &self.slots)
+    }
+
+    pub fn delete(&mut self, index: usize) -> bool {
+        if index >= self.slots.len() {
+            return false;
+        }
+        let slot = self.slots.remove(index);
+        self.active = match self.active {
+            Some(active) if active == index => None,
+            Some(active) if active > index => Some(active - 1),
+            active => active,
+        };
+        remove_slot(&slot.meta.id, &self.slots)
+    }
+
+    /// The active slot's farm save followed by its older rotations, newest first. Empty if
+    /// the slot hasn't been saved yet.
+    pub fn read_farm(&self) -> Vec<Vec<u8>> {
+        self.active().map(|meta| read_farm_saves(&meta.id)).unwrap_or_default()
+    }
+
+    /// Writes the farm save to the active slot and refreshes its metadata and thumbnail. On the
+    /// web build a full browser quota fails with `StorageFull`.
+    pub fn write_farm(
+        &mut self,
+        bytes: &[u8],
+        progress: SlotProgress,
+        thumbnail: Option<&RgbaImage>,
+    ) -> std::io::Result<()> {
+        let Some(index) = self.active else {
+            return Err(std::io::Error::other("no save slot is active"));
+        };
+        let slot = &mut self.slots[index];
+        slot.meta.playtime = progress.playtime;
+        slot.meta.day = progress.day;
+        slot.meta.player_hp = progress.player_hp;
+        slot.meta.player_xp = progress.player_xp;
+        slot.meta.upgrades = progress.upgrades;
+        slot.meta.equipment = progress.equipment;
+        slot.meta.saved_at = miniquad::date::now();
+        write_farm_bytes(&slot.meta.id, bytes)?;
+        if let Some(image) = thumbnail {
+            write_thumbnail(&slot.meta.id, image);
+            slot.thumbnail = Some(thumbnail_texture(image));
+        }
+        if write_slot_meta(&self.slots[index].meta, &self.slots) {
+            Ok(())
+        } else {
+            Err(std::io::Error::other("failed to write slot metadata"))
+        }
+    }
+
+    /// Where the title screen exports slot `index`: `~/.cropbots/exports/<slot>.cbsave`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn export_path(&self, index: usize) -> Option<std::path::PathBuf> {
+        let id = &self.slots.get(index)?.meta.id;
+        Some(cropbots_dir()?.join("exports").join(format!("{id}.{ARCHIVE_EXTENSION}")))
+    }
+
+    /// Packs slot `index` into one archive at `path`, for moving progress between machines or
+    /// attaching a world to a bug report.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn export_save(&self, index: usize, path: &std::path::Path) -> Result<(), ArchiveError> {
+        let slot = self.slots.get(index).ok_or(ArchiveError::Corrupt("no such save slot"))?;
+        let dir = slot_dir(&slot.meta.id).ok_or(ArchiveError::Corrupt("no home directory"))?;
+        let mut files = Vec::new();
+        for name in [farm_file(SaveFormat::Binary), farm_file(SaveFormat::Json), THUMBNAIL_FILE] {
+            if let Ok(bytes) = std::fs::read(dir.join(name)) {
+                files.push(ArchiveFile {
+                    name: name.to_string(),
+                    bytes,
+                });
+            }
+        }
+        if files.iter().all(|file| file.name == THUMBNAIL_FILE) {
+            return Err(ArchiveError::Corrupt("the slot hasn't been saved yet"));
+        }
+        // Bundled so a bug report shows the tuning the world was played with.
+        if let Ok(bytes) = std::fs::read(crate::config::CONFIG_PATH) {
+            files.push(ArchiveFile {
+                name: SETTINGS_FILE.to_string(),
+                bytes,
+            });
+        }
+        let archive = SaveArchive {
+            meta: slot.meta.clone(),
+            files,
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(write_atomic(path, &archive.encode()?, 0)?)
+    }
+
+    /// Unpacks an exported archive into a new slot, which becomes the first one listed. The
+    /// bundled settings are left out; the local `config.yaml` stays in charge.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn import_save(&mut self, path: &std::path::Path) -> Result<(), ArchiveError> {
+        let archive = SaveArchive::decode(&std::fs::read(path)?)?;
+        let saves: Vec<SaveFormat> = [SaveFormat::Binary, SaveFormat::Json]
+            .into_iter()
+            .filter(|&format| archive.file(farm_file(format)).is_some())
+            .collect();
+        if saves.is_empty() {
+            return Err(ArchiveError::Corrupt("archive holds no farm save"));
+        }
+        let mut meta = archive.meta.clone();
+        meta.id = self.unique_id(&meta.name);
+        let dir = slot_dir(&meta.id).ok_or(ArchiveError::Corrupt("no home directory"))?;
+        std::fs::create_dir_all(&dir)?;
+        for format in saves {
+            if let Some(bytes) = archive.file(farm_file(format)) {
+                write_atomic(&dir.join(farm_file(format)), bytes, 0)?;
+            }
+        }
+        let thumbnail = archive
+            .file(THUMBNAIL_FILE)
+            .and_then(|bytes| image::load_from_memory(bytes).ok())
+            .map(|image| image.to_rgba8());
+        if let Some(image) = thumbnail.as_ref() {
+            write_thumbnail(&meta.id, image);
+        }
+        if !write_slot_meta(&meta, &self.slots) {
+            return Err(ArchiveError::Corrupt("failed to write slot metadata"));
+        }
+        self.slots.insert(
+            0,
+            SaveSlot {
+                meta,
+                thumbnail: thumbnail.as_ref().map(thumbnail_texture),
+            },
+        );
+        self.active = self.active.map(|active| active + 1);
+        Ok(())
+    }
+
+    /// Browsers have no file paths, so this is only the name the download is offered under.
+    #[cfg(target_arch = "wasm32")]
+    pub fn export_path(&self, index: usize) -> Option<std::path::PathBuf> {
+        let id = &self.slots.get(index)?.meta.id;
+        Some(format!("{id}.{ARCHIVE_EXTENSION}").into())
+    }
+
+    /// Packs slot `index` into an archive and has the browser download it as `path`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn export_save(&self, index: usize, path: &std::path::Path) -> Result<(), ArchiveError> {
+        let slot = self.slots.get(index).ok_or(ArchiveError::Corrupt("no such save slot"))?;
+        let farm = wasm_storage_get_large(&farm_key(&slot.meta.id))
+            .ok_or(ArchiveError::Corrupt("the slot hasn't been saved yet"))?;
+        let mut files = vec![ArchiveFile {
+            name: "farm.json".to_string(),
+            bytes: farm.into_bytes(),
+        }];
+        if let Some(bytes) = wasm_storage_get_large(&thumbnail_key(&slot.meta.id)).and_then(|png| base64_decode(&png)) {
+            files.push(ArchiveFile {
+                name: THUMBNAIL_FILE.to_string(),
+                bytes,
+            });
+        }
+        let archive = SaveArchive {
+            meta: slot.meta.clone(),
+            files,
+        };
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("save.cbsave");
+        Ok(wasm_download(name, &archive.encode()?)?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn import_save(&mut self, _path: &std::path::Path) -> Result<(), ArchiveError> {
+        Err(ArchiveError::Io(std::io::ErrorKind::Unsupported.into()))
+    }
+
+    fn unique_id(&self, name: &str) -> String {
+        let mut base: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+            .collect();
+        base = base.trim_matches('-').to_string();
+        if base.is_empty() {
+            base = "farm".to_string();
+        }
+        let taken = |id: &str| self.slots.iter().any(|slot| slot.meta.id == id);
+        if !taken(&base) {
+            return base;
+        }
+        (2..)
+            .map(|n| format!("{base}-{n}"))
+            .find(|id| !taken(id))
+            .unwrap_or(base)
+    }
+}
+
+/// Progress recorded in a slot's metadata whenever the farm is saved.
+#[derive(Clone)]
+pub struct SlotProgress {
+    pub playtime: f32,
+    pub day: u32,
+    pub player_hp: f32,
+    pub player_xp: f32,
+    pub upgrades: Upgrades,
+    pub equipment: Equipment,
+}
+
+/// Grabs the current scene from `target`, or the screen when the scene isn't drawn to a render
+/// target, scaled down to thumbnail size. Call before the HUD is drawn.
+pub fn capture_thumbnail(target: Option<&RenderTarget>) -> RgbaImage {
+    let screen = match target {
+        Some(target) => target.texture.get_texture_data(),
+        None => get_screen_data(),
+    };
+    let image = RgbaImage::from_raw(screen.width as u32, screen.height as u32, screen.bytes)
+        .unwrap_or_else(|| RgbaImage::new(1, 1));
+    // Both are read bottom row first.
+    let image = imageops::flip_vertical(&image);
+    imageops::resize(&image, THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT, FilterType::Triangle)
+}
+
+fn thumbnail_texture(image: &RgbaImage) -> Texture2D {
+    crate::texture::upload(image.width() as u16, image.height() as u16, image.as_raw(), FilterMode::Linear)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn slot_dir(id: &str) -> Option<std::path::PathBuf> {
+    Some(cropbots_dir()?.join("saves").join(id))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn farm_file(format: SaveFormat) -> &'static str {
+    match format {
+        SaveFormat::Json => "farm.json",
+        SaveFormat::Binary => "farm.bin",
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_slot_metas() -> Vec<SlotMeta> {
+    let Some(dir) = cropbots_dir().map(|dir| dir.join("saves")) else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let raw = std::fs::read_to_string(entry.path().join(META_FILE)).ok()?;
+            serde_json::from_str::<SlotMeta>(&raw)
+                .map_err(|err| eprintln!("bad save slot metadata in {}: {err}", entry.path().display()))
+                .ok()
+        })
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_slot_meta(meta: &SlotMeta, _slots: &[SaveSlot]) -> bool {
+    let Some(dir) = slot_dir(&meta.id) else {
+        return false;
+    };
+    let Ok(json) = serde_json::to_string_pretty(meta) else {
+        return false;
+    };
+    std::fs::create_dir_all(&dir).is_ok() && write_atomic(&dir.join(META_FILE), json.as_bytes(), 0).is_ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn remove_slot(id: &str, _slots: &[SaveSlot]) -> bool {
+    slot_dir(id).is_some_and(|dir| std::fs::remove_dir_all(dir).is_ok())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_thumbnail(id: &str) -> Option<Texture2D> {
+    let image = image::open(slot_dir(id)?.join(THUMBNAIL_FILE)).ok()?;
+    Some(thumbnail_texture(&image.to_rgba8()))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_thumbnail(id: &str, image: &RgbaImage) {
+    let Some(dir) = slot_dir(id) else {
+        return;
+    };
+    let mut png = std::io::Cursor::new(Vec::new());
+    let written = image
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|err| err.to_string())
+        .and_then(|()| write_atomic(&dir.join(THUMBNAIL_FILE), png.get_ref(), 0).map_err(|err| err.to_string()));
+    if let Err(err) = written {
+        eprintln!("failed to save thumbnail: {err}");
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_farm_bytes(id: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let dir = slot_dir(id).ok_or_else(|| std::io::Error::other("no home directory"))?;
+    std::fs::create_dir_all(&dir)?;
+    write_atomic(&dir.join(farm_file(FARM_SAVE_FORMAT)), bytes, SAVE_ROTATIONS)
+}
+
+/// Prefers the configured format and falls back to the other, so existing
+/// `farm.json` saves still load after switching to binary.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_farm_saves(id: &str) -> Vec<Vec<u8>> {
+    let Some(dir) = slot_dir(id) else {
+        return Vec::new();
+    };
+    let fallback = match FARM_SAVE_FORMAT {
+        SaveFormat::Json => SaveFormat::Binary,
+        SaveFormat::Binary => SaveFormat::Json,
+    };
+    [FARM_SAVE_FORMAT, fallback]
+        .into_iter()
+        .flat_map(|format| {
+            let path = dir.join(farm_file(format));
+            std::iter::once(path.clone()).chain((1..=SAVE_ROTATIONS).map(move |n| rotation_path(&path, n)))
+        })
+        .filter_map(|path| std::fs::read(path).ok())
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn rotation_path(path: &std::path::Path, n: usize) -> std::path::PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    name.into()
+}
+
+/// Writes `bytes` to a temp file beside `path` and renames it over `path`, so a crash
+/// mid-write leaves the previous file whole. The file being replaced is first copied into
+/// the newest of `rotations` numbered backups, shifting older ones down.
+#[cfg(not(target_arch = "wasm32"))]
+fn write_atomic(path: &std::path::Path, bytes: &[u8], rotations: usize) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = std::path::PathBuf::from(tmp);
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+
+    if rotations > 0 && path.exists() {
+        for n in (1..rotations).rev() {
+            let from = rotation_path(path, n);
+            if from.exists() {
+                std::fs::rename(&from, rotation_path(path, n + 1))?;
+            }
+        }
+        std::fs::copy(path, rotation_path(path, 1))?;
+    }
+    std::fs::rename(&tmp, path)
+}
+
+/// Moves a farm save from before slots, `~/.cropbots/farm.{bin,json}`, into a slot of its own.
+#[cfg(not(target_arch = "wasm32"))]
+fn migrate_legacy_save() -> Option<SlotMeta> {
+    let root = cropbots_dir()?;
+    let (format, path) = [SaveFormat::Binary, SaveFormat::Json]
+        .into_iter()
+        .map(|format| (format, root.join(farm_file(format))))
+        .find(|(_, path)| path.exists())?;
+    let meta = SlotMeta {
+        id: "farm".to_string(),
+        name: "Farm".to_string(),
+        playtime: 0.0,
+        day: 0,
+        player_hp: 0.0,
+        player_xp: 0.0,
+        upgrades: Upgrades::default(),
+        equipment: Equipment::default(),
+        saved_at: miniquad::date::now(),
+        difficulty: Difficulty::default(),
+    };
+    let dir = slot_dir(&meta.id)?;
+    std::fs::create_dir_all(&dir).ok()?;
+    std::fs::rename(&path, dir.join(farm_file(format)))
+        .map_err(|err| eprintln!("failed to migrate {}: {err}", path.display()))
+        .ok()?;
+    write_slot_meta(&meta, &[]).then_some(meta)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn farm_key(id: &str) -> String {
+    format!("cropbots:saves:{id}:farm.json")
+}
+
+#[cfg(target_arch = "wasm32")]
+fn thumbnail_key(id: &str) -> String {
+    format!("cropbots:saves:{id}:{THUMBNAIL_FILE}")
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_slot_metas() -> Vec<SlotMeta> {
+    wasm_storage_get_item(SLOT_INDEX_KEY)
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Wasm keeps every slot's metadata in one index entry, so this rewrites all of it.
+#[cfg(target_arch = "wasm32")]
+fn write_slot_meta(meta: &SlotMeta, slots: &[SaveSlot]) -> bool {
+    let mut metas: Vec<&SlotMeta> = slots.iter().map(|slot| &slot.meta).filter(|other| other.id != meta.id).collect();
+    metas.push(meta);
+    serde_json::to_string(&metas).is_ok_and(|json| wasm_storage_set_item(SLOT_INDEX_KEY, &json).is_ok())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn remove_slot(id: &str, slots: &[SaveSlot]) -> bool {
+    let metas: Vec<&SlotMeta> = slots.iter().map(|slot| &slot.meta).collect();
+    wasm_storage_remove_large(&farm_key(id));
+    wasm_storage_remove_large(&thumbnail_key(id));
+    serde_json::to_string(&metas).is_ok_and(|json| wasm_storage_set_item(SLOT_INDEX_KEY, &json).is_ok())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_thumbnail(id: &str) -> Option<Texture2D> {
+    let png = base64_decode(&wasm_storage_get_large(&thumbnail_key(id))?)?;
+    let image = image::load_from_memory(&png).ok()?;
+    Some(thumbnail_texture(&image.to_rgba8()))
+}
+
+/// Thumbnails are a nicety; one that doesn't fit in the quota is just left out.
+#[cfg(target_arch = "wasm32")]
+fn write_thumbnail(id: &str, image: &RgbaImage) {
+    let mut png = std::io::Cursor::new(Vec::new());
+    if image.write_to(&mut png, image::ImageFormat::Png).is_err() {
+        return;
+    }
+    if wasm_storage_set_large(&thumbnail_key(id), &base64_encode(png.get_ref())).is_err() {
+        wasm_storage_remove_large(&thumbnail_key(id));
+    }
+}
+
+/// When the quota is full, the slot's thumbnail is given up to make room before failing.
+#[cfg(target_arch = "wasm32")]
+fn write_farm_bytes(id: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let json = std::str::from_utf8(bytes).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    match wasm_storage_set_large(&farm_key(id), json) {
+        Err(err) if err.kind() == std::io::ErrorKind::StorageFull => {
+            wasm_storage_remove_large(&thumbnail_key(id));
+            wasm_storage_set_large(&farm_key(id), json)
+        }
+        result => result,
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_farm_saves(id: &str) -> Vec<Vec<u8>> {
+    wasm_storage_get_large(&farm_key(id)).map(String::into_bytes).into_iter().collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn migrate_legacy_save() -> Option<SlotMeta> {
+    let json = wasm_storage_get_item(LEGACY_STORAGE_KEY)?;
+    let meta = SlotMeta {
+        id: "farm".to_string(),
+        name: "Farm".to_string(),
+        playtime: 0.0,
+        day: 0,
+        player_hp: 0.0,
+        player_xp: 0.0,
+        upgrades: Upgrades::default(),
+        equipment: Equipment::default(),
+        saved_at: miniquad::date::now(),
+        difficulty: Difficulty::default(),
+    };
+    if wasm_storage_set_large(&farm_key(&meta.id), &json).is_err() || !write_slot_meta(&meta, &[]) {
+        return None;
+    }
+    wasm_storage_remove_item(LEGACY_STORAGE_KEY);
+    Some(meta)
+}
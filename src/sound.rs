@@ -1,9 +1,11 @@
-use macroquad::audio::{load_sound, play_sound, stop_sound, PlaySoundParams, Sound};
+use macroquad::audio::{load_sound_from_bytes, play_sound, set_sound_volume, stop_sound, PlaySoundParams, Sound};
+use macroquad::file::{load_file, load_string};
 use macroquad::prelude::Vec2;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::path::Path;
-use crate::helpers::asset_path;
+use crate::helpers::{asset_path, data_path, load_wasm_manifest_files};
+use crate::map::{LayerKind, TileMap};
 
 #[derive(Debug)]
 pub enum SoundLoadError {
@@ -62,8 +64,35 @@ pub struct SoundEntry {
 struct LoadedSound {
     entry: SoundEntry,
     sound: Sound,
+    /// Copies resampled across the sound's pitch range and, for spatial sounds, panned from hard
+    /// left to hard right, since the mixer has neither control. Stored pitch-major, `pans` per
+    /// pitch. Empty when the clip plays as-is or couldn't be decoded.
+    variants: Vec<Sound>,
+    pans: usize,
 }
 
+impl LoadedSound {
+    /// A copy panned to `pan`, -1 left to 1 right, at a random pitch within the variance.
+    fn clip(&self, pan: f32) -> &Sound {
+        if self.variants.is_empty() {
+            return &self.sound;
+        }
+        let pitches = self.variants.len() / self.pans;
+        let pitch = macroquad::rand::gen_range(0, pitches);
+        let pan = ((pan + 1.0) * 0.5 * (self.pans - 1) as f32).round() as usize;
+        &self.variants[pitch * self.pans + pan.min(self.pans - 1)]
+    }
+
+    fn clips(&self) -> impl Iterator<Item = &Sound> {
+        std::iter::once(&self.sound).chain(&self.variants)
+    }
+}
+
+/// Pan positions baked for each spatial sound; odd so one sits dead center.
+const PAN_STEPS: usize = 5;
+/// Pitches baked across `pitch ± variance` for sounds with variance.
+const PITCH_STEPS: usize = 4;
+
 #[derive(Clone, Copy)]
 struct BuiltinSoundDef {
     id: &'static str,
@@ -85,11 +114,11 @@ const WASM_BUILTIN_SOUNDS: &[BuiltinSoundDef] = &[
         channel: SoundChannel::Sfx,
         volume: 0.5,
         looped: false,
-        spatial: false,
+        spatial: true,
         pitch: 1.0,
-        max_distance: 600.0,
-        min_distance: 60.0,
-        variance: 0.0,
+        max_distance: 200.0,
+        min_distance: 24.0,
+        variance: 0.08,
     },
     BuiltinSoundDef {
         id: "hurt",
@@ -97,11 +126,11 @@ const WASM_BUILTIN_SOUNDS: &[BuiltinSoundDef] = &[
         channel: SoundChannel::Sfx,
         volume: 0.6,
         looped: false,
-        spatial: false,
+        spatial: true,
         pitch: 1.0,
-        max_distance: 600.0,
-        min_distance: 60.0,
-        variance: 0.0,
+        max_distance: 420.0,
+        min_distance: 48.0,
+        variance: 0.1,
     },
     BuiltinSoundDef {
         id: "hurt2",
@@ -109,6 +138,30 @@ const WASM_BUILTIN_SOUNDS: &[BuiltinSoundDef] = &[
         channel: SoundChannel::Sfx,
         volume: 0.6,
         looped: false,
+        spatial: true,
+        pitch: 1.0,
+        max_distance: 420.0,
+        min_distance: 48.0,
+        variance: 0.1,
+    },
+    BuiltinSoundDef {
+        id: "forest",
+        path: "src/assets/sounds/forest.wav",
+        channel: SoundChannel::Ambient,
+        volume: 0.6,
+        looped: true,
+        spatial: false,
+        pitch: 1.0,
+        max_distance: 600.0,
+        min_distance: 60.0,
+        variance: 0.0,
+    },
+    BuiltinSoundDef {
+        id: "meadow",
+        path: "src/assets/sounds/meadow.wav",
+        channel: SoundChannel::Ambient,
+        volume: 0.6,
+        looped: true,
         spatial: false,
         pitch: 1.0,
         max_distance: 600.0,
@@ -117,10 +170,19 @@ const WASM_BUILTIN_SOUNDS: &[BuiltinSoundDef] = &[
     },
 ];
 
+/// Where spatial sounds are heard from: the camera target, with sounds half a view width to
+/// either side panned fully.
+#[derive(Clone, Copy)]
+struct Listener {
+    pos: Vec2,
+    half_width: f32,
+}
+
 pub struct SoundSystem {
     sounds: Vec<LoadedSound>,
     lookup: HashMap<String, usize>,
     channel_volume: HashMap<SoundChannel, f32>,
+    listener: Listener,
 }
 
 impl SoundSystem {
@@ -134,6 +196,10 @@ impl SoundSystem {
             sounds: Vec::new(),
             lookup: HashMap::new(),
             channel_volume,
+            listener: Listener {
+                pos: Vec2::ZERO,
+                half_width: 1.0,
+            },
         }
     }
 
@@ -143,25 +209,47 @@ impl SoundSystem {
         let mut lookup = HashMap::new();
 
         if cfg!(target_arch = "wasm32") {
-            for def in WASM_BUILTIN_SOUNDS {
-                let sound = load_sound(&asset_path(def.path))
-                    .await
-                    .map_err(|err| SoundLoadError::Sound(err.to_string()))?;
-
-                let entry = SoundEntry {
-                    id: def.id.to_string(),
-                    channel: def.channel,
-                    volume: def.volume,
-                    looped: def.looped,
-                    pitch: def.pitch,
-                    spatial: def.spatial,
-                    max_distance: def.max_distance,
-                    min_distance: def.min_distance,
-                    variance: def.variance,
-                };
-
-                lookup.insert(def.id.to_string(), sounds.len());
-                sounds.push(LoadedSound { entry, sound });
+            let dir = data_path(&dir.to_string_lossy());
+            // The prebuilt manifest inlines every entry; without it, fetch each yaml the index lists.
+            let raw_files = match load_string(&format!("{}/sounds.json", dir)).await {
+                Ok(raw) => serde_json::from_str::<SoundManifest>(&raw)
+                    .map_err(|err| SoundLoadError::Sound(format!("bad sound manifest: {err}")))?
+                    .sounds,
+                Err(_) => {
+                    let mut raw_files = Vec::new();
+                    for file in load_wasm_manifest_files(&dir, &[]).await {
+                        let path = format!("{}/{}", dir, file);
+                        let raw_str = load_string(&path)
+                            .await
+                            .map_err(|err| SoundLoadError::Sound(err.to_string()))?;
+                        raw_files.push(serde_yaml::from_str(&raw_str)?);
+                    }
+                    raw_files
+                }
+            };
+            for raw in raw_files {
+                let loaded = load_sound_file(raw).await?;
+                lookup.insert(loaded.entry.id.clone(), sounds.len());
+                sounds.push(loaded);
+            }
+            // Builds deployed without a sound index still get the basics.
+            if sounds.is_empty() {
+                for def in WASM_BUILTIN_SOUNDS {
+                    let entry = SoundEntry {
+                        id: def.id.to_string(),
+                        channel: def.channel,
+                        volume: def.volume,
+                        looped: def.looped,
+                        pitch: def.pitch,
+                        spatial: def.spatial,
+                        max_distance: def.max_distance,
+                        min_distance: def.min_distance,
+                        variance: def.variance,
+                    };
+
+                    lookup.insert(def.id.to_string(), sounds.len());
+                    sounds.push(load_entry(entry, def.path).await?);
+                }
             }
         } else if dir.exists() {
             for entry in std::fs::read_dir(dir)? {
@@ -170,25 +258,9 @@ impl SoundSystem {
                 if !is_yaml(&path) {
                     continue;
                 }
-                let raw: SoundFile = serde_yaml::from_str(&std::fs::read_to_string(&path)?)?;
-                let sound = load_sound(&asset_path(&raw.path))
-                    .await
-                    .map_err(|err| SoundLoadError::Sound(err.to_string()))?;
-
-                let entry = SoundEntry {
-                    id: raw.id.clone(),
-                    channel: raw.channel.unwrap_or(SoundChannel::Sfx),
-                    volume: raw.volume.unwrap_or(1.0),
-                    looped: raw.looped.unwrap_or(false),
-                    pitch: raw.pitch.unwrap_or(1.0),
-                    spatial: raw.spatial.unwrap_or(false),
-                    max_distance: raw.max_distance.unwrap_or(600.0),
-                    min_distance: raw.min_distance.unwrap_or(60.0),
-                    variance: raw.variance.unwrap_or(0.0),
-                };
-
-                lookup.insert(raw.id, sounds.len());
-                sounds.push(LoadedSound { entry, sound });
+                let loaded = load_sound_file(serde_yaml::from_str(&std::fs::read_to_string(&path)?)?).await?;
+                lookup.insert(loaded.entry.id.clone(), sounds.len());
+                sounds.push(loaded);
             }
         }
 
@@ -202,6 +274,10 @@ impl SoundSystem {
             sounds,
             lookup,
             channel_volume,
+            listener: Listener {
+                pos: Vec2::ZERO,
+                half_width: 1.0,
+            },
         })
     }
 
@@ -212,16 +288,28 @@ impl SoundSystem {
     pub fn play(&self, id: &str) {
         if let Some(sound) = self.get(id) {
             // Interrupt any currently playing instance of the same sound.
-            stop_sound(&sound.sound);
+            self.stop(id);
             let params = PlaySoundParams {
                 looped: sound.entry.looped,
                 volume: sound.entry.volume * self.channel_volume.get(&sound.entry.channel).copied().unwrap_or(1.0),
             };
-            play_sound(&sound.sound, params);
+            play_sound(sound.clip(0.0), params);
         }
     }
 
-    pub fn play_at(&self, id: &str, source: Vec2, listener: Vec2) {
+    /// Moves the listener spatial sounds are heard from, normally to the camera target.
+    /// `half_width` is how far to the side a sound is panned fully, half the view's width.
+    pub fn set_listener(&mut self, pos: Vec2, half_width: f32) {
+        self.listener = Listener {
+            pos,
+            half_width: half_width.max(1.0),
+        };
+    }
+
+    /// Plays `id` as if emitted at `source`: quieter with distance from the listener, silent
+    /// past the sound's `max_distance`, and panned toward the side it came from. Sounds that
+    /// aren't spatial play as with `play`.
+    pub fn play_at(&self, id: &str, source: Vec2) {
         let Some(sound) = self.get(id) else {
             return;
         };
@@ -230,7 +318,7 @@ impl SoundSystem {
             return;
         }
 
-        let dist = source.distance(listener);
+        let dist = source.distance(self.listener.pos);
         if dist > sound.entry.max_distance {
             return;
         }
@@ -240,37 +328,51 @@ impl SoundSystem {
             let t = ((dist - sound.entry.min_distance)
                 / (sound.entry.max_distance - sound.entry.min_distance))
                 .clamp(0.0, 1.0);
-            1.0 - t
-        };
-
-        let pitch = if sound.entry.variance > 0.0 {
-            let rand = macroquad::rand::gen_range(-sound.entry.variance, sound.entry.variance);
-            (sound.entry.pitch + rand).max(0.05)
-        } else {
-            sound.entry.pitch
+            // Falls off faster close in, like a real source, but still reaches zero at the edge.
+            (1.0 - t) * (1.0 - t)
         };
 
+        let pan = ((source.x - self.listener.pos.x) / self.listener.half_width).clamp(-1.0, 1.0);
         // Interrupt any currently playing instance of the same sound.
-        stop_sound(&sound.sound);
+        self.stop(id);
         play_sound(
-            &sound.sound,
+            sound.clip(pan),
             PlaySoundParams {
                 looped: sound.entry.looped,
-                volume: volume
-                    * sound.entry.volume
-                    * self.channel_volume.get(&sound.entry.channel).copied().unwrap_or(1.0),
+                volume: self.mixed_volume(sound, volume),
             },
         );
+    }
 
-        if pitch != 1.0 {
-            // Macroquad doesn't expose pitch in PlaySoundParams; kept for future extension.
-            let _ = pitch;
+    /// Starts `id` at `volume`, a multiplier on its own and its channel's volume.
+    pub fn play_with_volume(&self, id: &str, volume: f32) {
+        if let Some(sound) = self.get(id) {
+            self.stop(id);
+            let params = PlaySoundParams {
+                looped: sound.entry.looped,
+                volume: self.mixed_volume(sound, volume),
+            };
+            play_sound(sound.clip(0.0), params);
+        }
+    }
+
+    /// Changes the volume of `id` while it plays, for fading loops in and out.
+    pub fn set_volume(&self, id: &str, volume: f32) {
+        if let Some(sound) = self.get(id) {
+            let volume = self.mixed_volume(sound, volume);
+            for clip in sound.clips() {
+                set_sound_volume(clip, volume);
+            }
         }
     }
 
+    fn mixed_volume(&self, sound: &LoadedSound, volume: f32) -> f32 {
+        volume * sound.entry.volume * self.channel_volume.get(&sound.entry.channel).copied().unwrap_or(1.0)
+    }
+
     pub fn stop(&self, id: &str) {
         if let Some(sound) = self.get(id) {
-            stop_sound(&sound.sound);
+            sound.clips().for_each(stop_sound);
         }
     }
 
@@ -280,6 +382,253 @@ impl SoundSystem {
     }
 }
 
+async fn load_sound_file(raw: SoundFile) -> Result<LoadedSound, SoundLoadError> {
+    let entry = SoundEntry {
+        id: raw.id,
+        channel: raw.channel.unwrap_or(SoundChannel::Sfx),
+        volume: raw.volume.unwrap_or(1.0),
+        looped: raw.looped.unwrap_or(false),
+        pitch: raw.pitch.unwrap_or(1.0),
+        spatial: raw.spatial.unwrap_or(false),
+        max_distance: raw.max_distance.unwrap_or(600.0),
+        min_distance: raw.min_distance.unwrap_or(60.0),
+        variance: raw.variance.unwrap_or(0.0),
+    };
+    load_entry(entry, &raw.path).await
+}
+
+async fn load_entry(entry: SoundEntry, path: &str) -> Result<LoadedSound, SoundLoadError> {
+    let bytes = load_file(&asset_path(path))
+        .await
+        .map_err(|err| SoundLoadError::Sound(err.to_string()))?;
+    let sound = load_sound_from_bytes(&bytes)
+        .await
+        .map_err(|err| SoundLoadError::Sound(err.to_string()))?;
+    let pans = if entry.spatial { PAN_STEPS } else { 1 };
+    let pitches = if entry.variance > 0.0 { PITCH_STEPS } else { 1 };
+    let mut variants = Vec::new();
+    if entry.spatial || pitches > 1 || entry.pitch != 1.0 {
+        match decode_wav_mono(&bytes) {
+            Some((rate, samples)) => {
+                for pitch_step in 0..pitches {
+                    let spread = if pitches > 1 {
+                        pitch_step as f32 / (pitches - 1) as f32 * 2.0 - 1.0
+                    } else {
+                        0.0
+                    };
+                    let resampled = resample(&samples, (entry.pitch + spread * entry.variance).max(0.05));
+                    for pan_step in 0..pans {
+                        let pan = if pans > 1 {
+                            pan_step as f32 / (pans - 1) as f32 * 2.0 - 1.0
+                        } else {
+                            0.0
+                        };
+                        let wav = encode_wav_stereo(rate, &resampled, pan);
+                        let clip = load_sound_from_bytes(&wav)
+                            .await
+                            .map_err(|err| SoundLoadError::Sound(err.to_string()))?;
+                        variants.push(clip);
+                    }
+                }
+            }
+            None => eprintln!("sound '{}' isn't 16-bit PCM wav, so it won't pan or pitch", entry.id),
+        }
+    }
+    Ok(LoadedSound {
+        entry,
+        sound,
+        variants,
+        pans,
+    })
+}
+
+/// `samples` sped up by `pitch` with linear interpolation, which shifts the pitch and shortens
+/// the clip together like a tape played faster.
+fn resample(samples: &[i16], pitch: f32) -> Vec<i16> {
+    if pitch == 1.0 {
+        return samples.to_vec();
+    }
+    let len = (samples.len() as f32 / pitch) as usize;
+    (0..len)
+        .map(|i| {
+            let t = i as f32 * pitch;
+            let index = t as usize;
+            let a = samples.get(index).copied().unwrap_or(0) as f32;
+            let b = samples.get(index + 1).copied().unwrap_or(0) as f32;
+            (a + (b - a) * t.fract()) as i16
+        })
+        .collect()
+}
+
+/// Sample rate and samples of a 16-bit PCM wav, with stereo mixed down to mono.
+fn decode_wav_mono(bytes: &[u8]) -> Option<(u32, Vec<i16>)> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return None;
+    }
+    let mut format = None;
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let body = bytes.get(pos + 8..pos + 8 + len)?;
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let tag = u16::from_le_bytes([body[0], body[1]]);
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                if tag != 1 || bits != 16 || !(1..=2).contains(&channels) {
+                    return None;
+                }
+                format = Some((rate, channels as usize));
+            }
+            b"data" => {
+                let (rate, channels) = format?;
+                let samples = body
+                    .chunks_exact(2 * channels)
+                    .map(|frame| {
+                        let sum: i32 = frame
+                            .chunks_exact(2)
+                            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]) as i32)
+                            .sum();
+                        (sum / channels as i32) as i16
+                    })
+                    .collect();
+                return Some((rate, samples));
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even length.
+        pos += 8 + len + (len & 1);
+    }
+    None
+}
+
+/// A 16-bit stereo wav of `samples` panned by `pan`, -1 hard left to 1 hard right. The near
+/// channel stays at full level so a centered copy sounds like the mono original.
+fn encode_wav_stereo(rate: u32, samples: &[i16], pan: f32) -> Vec<u8> {
+    let left = (1.0 - pan).min(1.0);
+    let right = (1.0 + pan).min(1.0);
+    let data_len = (samples.len() * 4) as u32;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&rate.to_le_bytes());
+    wav.extend_from_slice(&(rate * 4).to_le_bytes());
+    wav.extend_from_slice(&4u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for &sample in samples {
+        wav.extend_from_slice(&((sample as f32 * left) as i16).to_le_bytes());
+        wav.extend_from_slice(&((sample as f32 * right) as i16).to_le_bytes());
+    }
+    wav
+}
+
+/// Footstep clips for one kind of ground, chosen by the background tile underfoot.
+#[derive(Clone, Deserialize)]
+pub struct FootstepSetDef {
+    pub id: String,
+    /// Sound ids played in turn so consecutive steps differ.
+    pub sounds: Vec<String>,
+    /// Background tile ids that count as this ground.
+    #[serde(default)]
+    pub tiles: Vec<u8>,
+    /// Used for any tile no set claims.
+    #[serde(default)]
+    pub fallback: bool,
+}
+
+pub struct FootstepSets {
+    sets: Vec<FootstepSetDef>,
+    by_tile: HashMap<u8, usize>,
+    fallback: Option<usize>,
+    /// Round-robin position in each set.
+    next: Vec<usize>,
+}
+
+impl FootstepSets {
+    pub fn empty() -> Self {
+        Self {
+            sets: Vec::new(),
+            by_tile: HashMap::new(),
+            fallback: None,
+            next: Vec::new(),
+        }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, SoundLoadError> {
+        let dir = dir.as_ref();
+        let mut sets = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let files = load_wasm_manifest_files(&dir, &["dirt.yaml", "grass.yaml", "water.yaml", "wood.yaml"]).await;
+            for file in files {
+                let path = format!("{}/{}", dir, file);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| SoundLoadError::Io(std::io::Error::other(err.to_string())))?;
+                sets.push(serde_yaml::from_str::<FootstepSetDef>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                sets.push(serde_yaml::from_str::<FootstepSetDef>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+        // Directory order varies by platform; sort so overlapping tile claims resolve the same.
+        sets.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut by_tile = HashMap::new();
+        for (index, set) in sets.iter().enumerate() {
+            for &tile in &set.tiles {
+                if by_tile.insert(tile, index).is_some() {
+                    eprintln!("tile {tile} is in more than one footstep set");
+                }
+            }
+        }
+        let fallback = sets.iter().position(|set| set.fallback);
+        Ok(Self {
+            next: vec![0; sets.len()],
+            sets,
+            by_tile,
+            fallback,
+        })
+    }
+
+    /// The set for the ground at `pos`. `tilled` soil counts as dirt whatever tile is under it.
+    pub fn set_at(&self, map: &TileMap, pos: Vec2, tilled: bool) -> Option<usize> {
+        if tilled && let Some(dirt) = self.sets.iter().position(|set| set.id == "dirt") {
+            return Some(dirt);
+        }
+        let tile = map
+            .grid_index(pos)
+            .map(|grid| map.tile_at(LayerKind::Background, grid.x as usize, grid.y as usize));
+        tile.and_then(|tile| self.by_tile.get(&tile).copied())
+            .or(self.fallback)
+    }
+
+    /// The next clip of `set`, cycling through its sounds.
+    pub fn next_sound(&mut self, set: usize) -> Option<&str> {
+        let sounds = &self.sets.get(set)?.sounds;
+        if sounds.is_empty() {
+            return None;
+        }
+        let index = self.next[set] % sounds.len();
+        self.next[set] = index + 1;
+        Some(&sounds[index])
+    }
+}
+
 fn is_yaml(path: &Path) -> bool {
     path.extension()
         .and_then(|ext| ext.to_str())
@@ -287,6 +636,12 @@ fn is_yaml(path: &Path) -> bool {
         .unwrap_or(false)
 }
 
+/// `sounds.json`, generated next to the sound yamls for the web build.
+#[derive(Deserialize)]
+struct SoundManifest {
+    sounds: Vec<SoundFile>,
+}
+
 #[derive(Deserialize)]
 struct SoundFile {
     id: String,
@@ -0,0 +1,290 @@
+use macroquad::prelude::*;
+use crate::font::draw_text;
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::helpers::{asset_path, data_path, load_wasm_manifest_files};
+use crate::atlas::{AtlasBuilder, Sprite};
+
+#[derive(Debug)]
+pub enum StatusLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for StatusLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for StatusLoadError {}
+
+impl From<std::io::Error> for StatusLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for StatusLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusKind {
+    Buff,
+    Debuff,
+}
+
+/// How re-applying an effect that is already active behaves.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusStacking {
+    Refresh,
+    Stack,
+    Extend,
+    Ignore,
+}
+
+#[derive(Clone)]
+pub struct StatusEffectDef {
+    pub kind: StatusKind,
+    pub duration: f32,
+    pub stacking: StatusStacking,
+    pub max_stacks: u32,
+    pub icon: Option<Sprite>,
+}
+
+#[derive(Clone, Copy)]
+pub struct ActiveStatus {
+    pub def: usize,
+    pub remaining: f32,
+    pub duration: f32,
+    pub stacks: u32,
+}
+
+#[derive(Default)]
+pub struct StatusEffects {
+    active: Vec<ActiveStatus>,
+}
+
+impl StatusEffects {
+    pub fn update(&mut self, dt: f32) {
+        for status in self.active.iter_mut() {
+            status.remaining -= dt;
+        }
+        self.active.retain(|status| status.remaining > 0.0);
+    }
+
+    pub fn active(&self) -> &[ActiveStatus] {
+        &self.active
+    }
+}
+
+pub struct StatusEffectDatabase {
+    defs: Vec<StatusEffectDef>,
+    lookup: HashMap<String, usize>,
+}
+
+impl StatusEffectDatabase {
+    pub fn empty() -> Self {
+        Self {
+            defs: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, StatusLoadError> {
+        let dir = dir.as_ref();
+        let mut raws = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let files = load_wasm_manifest_files(&dir, &["hurt.yaml", "winded.yaml"]).await;
+            for file in files {
+                let path = format!("{}/{}", dir, file);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| StatusLoadError::Io(std::io::Error::other(err.to_string())))?;
+                raws.push(serde_yaml::from_str::<StatusEffectFile>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                raws.push(serde_yaml::from_str::<StatusEffectFile>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+
+        let mut atlas = AtlasBuilder::default();
+        let mut icons = Vec::with_capacity(raws.len());
+        for raw in &raws {
+            icons.push(match raw.icon.as_deref() {
+                Some(path) => Some(atlas.add(&asset_path(path)).await),
+                None => None,
+            });
+        }
+        let atlas = atlas.build();
+
+        let mut db = Self::empty();
+        for (raw, icon) in raws.into_iter().zip(icons) {
+            let icon = icon.map(|handle| atlas.sprite(handle));
+            db.lookup.insert(raw.id, db.defs.len());
+            db.defs.push(StatusEffectDef {
+                kind: raw.kind.unwrap_or(StatusKind::Buff),
+                duration: raw.duration.unwrap_or(1.0).max(0.0),
+                stacking: raw.stacking.unwrap_or(StatusStacking::Refresh),
+                max_stacks: raw.max_stacks.unwrap_or(1).max(1),
+                icon,
+            });
+        }
+        Ok(db)
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&StatusEffectDef> {
+        self.defs.get(idx)
+    }
+
+    /// Applies `id` to `effects` following the def's stacking rule. Unknown ids are ignored.
+    pub fn apply(&self, effects: &mut StatusEffects, id: &str) {
+        let Some(&idx) = self.lookup.get(id) else {
+            return;
+        };
+        let def = &self.defs[idx];
+        if def.duration <= 0.0 {
+            return;
+        }
+        let Some(status) = effects.active.iter_mut().find(|status| status.def == idx) else {
+            effects.active.push(ActiveStatus {
+                def: idx,
+                remaining: def.duration,
+                duration: def.duration,
+                stacks: 1,
+            });
+            return;
+        };
+        match def.stacking {
+            StatusStacking::Refresh => {
+                status.remaining = def.duration;
+                status.duration = def.duration;
+            }
+            StatusStacking::Stack => {
+                status.stacks = (status.stacks + 1).min(def.max_stacks);
+                status.remaining = def.duration;
+                status.duration = def.duration;
+            }
+            StatusStacking::Extend => {
+                let cap = def.duration * def.max_stacks as f32;
+                status.remaining = (status.remaining + def.duration).min(cap);
+                status.duration = status.duration.max(status.remaining);
+            }
+            StatusStacking::Ignore => {}
+        }
+    }
+}
+
+/// Draws active effects as a right-aligned row of icons ending at `right_x`,
+/// with a radial wipe over the elapsed part of each effect's duration.
+pub fn draw_status_icons(
+    db: &StatusEffectDatabase,
+    effects: &StatusEffects,
+    right_x: f32,
+    y: f32,
+    size: f32,
+) {
+    let gap = 4.0;
+    let mut x = right_x - size;
+    for status in effects.active().iter().rev() {
+        let Some(def) = db.get(status.def) else {
+            continue;
+        };
+        let border = match def.kind {
+            StatusKind::Buff => Color::new(0.35, 0.85, 0.4, 0.9),
+            StatusKind::Debuff => Color::new(0.9, 0.3, 0.3, 0.9),
+        };
+        draw_rectangle(x, y, size, size, Color::new(0.0, 0.0, 0.0, 0.45));
+        if let Some(icon) = def.icon.as_ref() {
+            icon.draw(
+                x,
+                y,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(size, size)),
+                    ..Default::default()
+                },
+            );
+        }
+
+        let elapsed = 1.0 - (status.remaining / status.duration.max(0.0001)).clamp(0.0, 1.0);
+        draw_radial_wipe(vec2(x + size * 0.5, y + size * 0.5), size * 0.5, elapsed);
+        draw_rectangle_lines(x, y, size, size, 1.0, border);
+
+        if status.stacks > 1 {
+            draw_text(
+                &status.stacks.to_string(),
+                x + size - 8.0,
+                y + size - 2.0,
+                16.0,
+                WHITE,
+            );
+        }
+        x -= size + gap;
+    }
+}
+
+fn draw_radial_wipe(center: Vec2, radius: f32, fraction: f32) {
+    if fraction <= 0.0 {
+        return;
+    }
+    let segments = 24;
+    let sweep = fraction.min(1.0) * std::f32::consts::TAU;
+    let start = -std::f32::consts::FRAC_PI_2;
+    let color = Color::new(0.0, 0.0, 0.0, 0.55);
+    // Square icons: push the fan out to the corners so the wipe covers the whole cell.
+    let reach = radius * std::f32::consts::SQRT_2;
+    for i in 0..segments {
+        let a0 = start + sweep * i as f32 / segments as f32;
+        let a1 = start + sweep * (i + 1) as f32 / segments as f32;
+        let p0 = center + vec2(a0.cos(), a0.sin()) * reach;
+        let p1 = center + vec2(a1.cos(), a1.sin()) * reach;
+        draw_triangle(center, clamp_to_square(p0, center, radius), clamp_to_square(p1, center, radius), color);
+    }
+}
+
+fn clamp_to_square(point: Vec2, center: Vec2, radius: f32) -> Vec2 {
+    vec2(
+        point.x.clamp(center.x - radius, center.x + radius),
+        point.y.clamp(center.y - radius, center.y + radius),
+    )
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
+
+#[derive(Deserialize)]
+struct StatusEffectFile {
+    id: String,
+    #[serde(default)]
+    kind: Option<StatusKind>,
+    #[serde(default)]
+    icon: Option<String>,
+    #[serde(default)]
+    duration: Option<f32>,
+    #[serde(default)]
+    stacking: Option<StatusStacking>,
+    #[serde(default)]
+    max_stacks: Option<u32>,
+}
@@ -0,0 +1,196 @@
+use macroquad::prelude::*;
+use crate::font::draw_text;
+use serde::{Deserialize, Serialize};
+use crate::item::{Inventory, ItemDatabase, ItemStack};
+use crate::locale::Locale;
+
+const PANEL_WIDTH: f32 = 520.0;
+const ROW_HEIGHT: f32 = 24.0;
+const ICON_SIZE: f32 = 18.0;
+
+/// A placed storage container and its contents, keyed by its top-left tile.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Chest {
+    pub x: usize,
+    pub y: usize,
+    pub inventory: Inventory,
+}
+
+#[derive(Default)]
+pub struct ChestStorage {
+    chests: Vec<Chest>,
+}
+
+impl ChestStorage {
+    pub fn from_chests(chests: Vec<Chest>) -> Self {
+        Self { chests }
+    }
+
+    pub fn chests(&self) -> &[Chest] {
+        &self.chests
+    }
+
+    /// Registers an empty chest at (`x`, `y`) unless one is already there.
+    pub fn insert(&mut self, x: usize, y: usize, slots: usize) {
+        if self.get(x, y).is_none() {
+            self.chests.push(Chest {
+                x,
+                y,
+                inventory: Inventory::with_slots(slots),
+            });
+        }
+    }
+
+    pub fn remove(&mut self, x: usize, y: usize) -> Option<Chest> {
+        let index = self.chests.iter().position(|chest| chest.x == x && chest.y == y)?;
+        Some(self.chests.swap_remove(index))
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&Chest> {
+        self.chests.iter().find(|chest| chest.x == x && chest.y == y)
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Chest> {
+        self.chests.iter_mut().find(|chest| chest.x == x && chest.y == y)
+    }
+
+    /// Deposits `items` into the chest at (`x`, `y`). Returns whatever didn't fit.
+    pub fn deposit(&mut self, x: usize, y: usize, items: &[ItemStack], db: &ItemDatabase) -> Vec<ItemStack> {
+        let Some(chest) = self.get_mut(x, y) else {
+            return items.to_vec();
+        };
+        items
+            .iter()
+            .filter_map(|stack| {
+                let left = chest.inventory.add(db, &stack.item, stack.count);
+                (left > 0).then(|| ItemStack {
+                    item: stack.item.clone(),
+                    count: left,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Two-column transfer window between the player's inventory and an open chest. Clicking a
+/// stack moves as much of it as fits to the other side.
+#[derive(Default)]
+pub struct ChestUi {
+    open: Option<(usize, usize)>,
+}
+
+impl ChestUi {
+    pub fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+
+    pub fn open(&mut self, x: usize, y: usize) {
+        self.open = Some((x, y));
+    }
+
+    pub fn close(&mut self) {
+        self.open = None;
+    }
+
+    pub fn update(&mut self, storage: &mut ChestStorage, items: &ItemDatabase, inventory: &mut Inventory) {
+        let Some(chest) = self.open.and_then(|(x, y)| storage.get_mut(x, y)) else {
+            self.open = None;
+            return;
+        };
+        if is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::E) {
+            self.close();
+            return;
+        }
+        if !is_mouse_button_pressed(MouseButton::Left) {
+            return;
+        }
+        let mouse = vec2(mouse_position().0, mouse_position().1);
+        let panel = panel_rect(chest.inventory.slots());
+        if !panel.contains(mouse) {
+            self.close();
+            return;
+        }
+        let (left, right) = columns(panel);
+        if let Some(slot) = hovered_slot(inventory, left, mouse) {
+            inventory.transfer(slot, &mut chest.inventory, items);
+        } else if let Some(slot) = hovered_slot(&chest.inventory, right, mouse) {
+            chest.inventory.transfer(slot, inventory, items);
+        }
+    }
+
+    pub fn draw(&self, storage: &ChestStorage, items: &ItemDatabase, inventory: &Inventory, locale: &Locale) {
+        let Some(chest) = self.open.and_then(|(x, y)| storage.get(x, y)) else {
+            return;
+        };
+        let panel = panel_rect(chest.inventory.slots());
+        let mouse = vec2(mouse_position().0, mouse_position().1);
+        draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.05, 0.05, 0.08, 0.9));
+        draw_rectangle_lines(panel.x, panel.y, panel.w, panel.h, 2.0, GRAY);
+        let (left, right) = columns(panel);
+        draw_text("Inventory", left.x, left.y - 8.0, 20.0, LIGHTGRAY);
+        draw_text(
+            &format!(
+                "Chest  {}/{}",
+                locale.format_int(chest.inventory.stacks().len() as i64),
+                locale.format_int(chest.inventory.slots() as i64)
+            ),
+            right.x,
+            right.y - 8.0,
+            20.0,
+            LIGHTGRAY,
+        );
+        draw_stacks(items, locale, inventory, left, mouse);
+        draw_stacks(items, locale, &chest.inventory, right, mouse);
+        draw_text("click to move, E to close", panel.x + 12.0, panel.bottom() - 12.0, 16.0, GRAY);
+    }
+}
+
+fn draw_stacks(items: &ItemDatabase, locale: &Locale, inventory: &Inventory, column: Rect, mouse: Vec2) {
+    for (i, stack) in inventory.stacks().iter().enumerate() {
+        let rect = row_rect(column, i);
+        if rect.contains(mouse) {
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(1.0, 1.0, 1.0, 0.1));
+        }
+        if let Some(texture) = items.get(&stack.item).and_then(|def| def.texture.as_ref()) {
+            texture.draw(
+                rect.x + 4.0,
+                rect.y + (rect.h - ICON_SIZE) * 0.5,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(ICON_SIZE, ICON_SIZE)),
+                    ..Default::default()
+                },
+            );
+        }
+        let label = format!("{} x{}", stack.item, locale.format_int(stack.count as i64));
+        draw_text(&label, rect.x + ICON_SIZE + 10.0, rect.y + rect.h * 0.7, 18.0, WHITE);
+    }
+}
+
+fn hovered_slot(inventory: &Inventory, column: Rect, mouse: Vec2) -> Option<usize> {
+    (0..inventory.stacks().len()).find(|&i| row_rect(column, i).contains(mouse))
+}
+
+fn panel_rect(chest_slots: usize) -> Rect {
+    let rows = chest_slots.max(crate::item::INVENTORY_SLOTS);
+    let height = 84.0 + rows as f32 * ROW_HEIGHT;
+    Rect::new(
+        (screen_width() - PANEL_WIDTH) * 0.5,
+        (screen_height() - height) * 0.5,
+        PANEL_WIDTH,
+        height,
+    )
+}
+
+fn columns(panel: Rect) -> (Rect, Rect) {
+    let width = panel.w * 0.5 - 18.0;
+    let height = panel.h - 84.0;
+    (
+        Rect::new(panel.x + 12.0, panel.y + 44.0, width, height),
+        Rect::new(panel.x + panel.w * 0.5 + 6.0, panel.y + 44.0, width, height),
+    )
+}
+
+fn row_rect(column: Rect, index: usize) -> Rect {
+    Rect::new(column.x, column.y + index as f32 * ROW_HEIGHT, column.w, ROW_HEIGHT - 2.0)
+}
@@ -0,0 +1,96 @@
+use macroquad::prelude::*;
+use crate::font::{draw_text, measure_text};
+use crate::helpers::load_file;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+const PLACEHOLDER_SIZE: u16 = 16;
+const PLACEHOLDER_CHECKER: u16 = 4;
+
+static MISSING_TEXTURES: AtomicUsize = AtomicUsize::new(0);
+static WINDOWLESS: AtomicBool = AtomicBool::new(false);
+
+/// For runs without a window, which have no GL context to upload to: from here on `upload`
+/// hands back `blank_texture()`.
+pub fn go_windowless() {
+    WINDOWLESS.store(true, Ordering::Relaxed);
+}
+
+/// `width` by `height` RGBA pixels as a texture sampled with `filter`.
+pub fn upload(width: u16, height: u16, bytes: &[u8], filter: FilterMode) -> Texture2D {
+    if WINDOWLESS.load(Ordering::Relaxed) {
+        return blank_texture();
+    }
+    let texture = Texture2D::from_rgba8(width, height, bytes);
+    texture.set_filter(filter);
+    texture
+}
+
+/// Loads a texture with nearest filtering. A failed load is logged, counted, and
+/// replaced by the placeholder checker so missing art shows up on screen.
+pub async fn load_texture_or_placeholder(path: &str) -> Texture2D {
+    let texture = match load_texture(path).await {
+        Ok(texture) => texture,
+        Err(err) => {
+            eprintln!("missing texture '{path}': {err}");
+            MISSING_TEXTURES.fetch_add(1, Ordering::Relaxed);
+            placeholder_texture()
+        }
+    };
+    texture.set_filter(FilterMode::Nearest);
+    texture
+}
+
+/// Loads an image for packing into an atlas, with the same fallback as
+/// `load_texture_or_placeholder`.
+pub async fn load_image_or_placeholder(path: &str) -> Image {
+    let image = load_file(path)
+        .await
+        .and_then(|bytes| Image::from_file_with_format(&bytes, None));
+    match image {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("missing texture '{path}': {err}");
+            MISSING_TEXTURES.fetch_add(1, Ordering::Relaxed);
+            placeholder_image()
+        }
+    }
+}
+
+/// A handle to no texture at all, for what's filled in later or never drawn. Unlike
+/// `Texture2D::empty()` it needs no window.
+pub fn blank_texture() -> Texture2D {
+    Texture2D::from_miniquad_texture(miniquad::TextureId::from_raw_id(miniquad::RawId::OpenGl(0)))
+}
+
+/// A magenta/black checkerboard.
+pub fn placeholder_texture() -> Texture2D {
+    let image = placeholder_image();
+    upload(image.width, image.height, &image.bytes, FilterMode::Nearest)
+}
+
+fn placeholder_image() -> Image {
+    let mut image = Image::gen_image_color(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE, MAGENTA);
+    for y in 0..PLACEHOLDER_SIZE {
+        for x in 0..PLACEHOLDER_SIZE {
+            if (x / PLACEHOLDER_CHECKER + y / PLACEHOLDER_CHECKER) % 2 == 1 {
+                image.set_pixel(x as u32, y as u32, BLACK);
+            }
+        }
+    }
+    image
+}
+
+pub fn missing_texture_count() -> usize {
+    MISSING_TEXTURES.load(Ordering::Relaxed)
+}
+
+/// Draws a warning in the top-right corner while any texture failed to load.
+pub fn draw_missing_texture_warning() {
+    let missing = missing_texture_count();
+    if missing == 0 {
+        return;
+    }
+    let label = format!("missing textures: {missing}");
+    let size = measure_text(&label, 20.0);
+    draw_text(&label, screen_width() - size.width - 20.0, 40.0, 20.0, MAGENTA);
+}
@@ -0,0 +1,637 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::entity::{Entity, EntityDatabase, MovementRegistry};
+use crate::map::{LayerKind, StructureDef, TileMap, TileOrientation};
+use crate::scene::WAYPOINT_ID;
+use crate::trigger::{PlacedTrigger, SpawnWave, TriggerAction, TriggerDef, TriggerWhen};
+
+/// Gid bits Tiled uses for flipped and rotated tiles.
+const GID_FLAGS: u32 = 0xF000_0000;
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+
+#[derive(Debug)]
+pub enum TiledError {
+    Json(serde_json::Error),
+    Xml(roxmltree::Error),
+    /// Valid Tiled data this importer doesn't handle, like compressed layers.
+    Unsupported(String),
+}
+
+impl std::fmt::Display for TiledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Json(err) => write!(f, "json error: {err}"),
+            Self::Xml(err) => write!(f, "xml error: {err}"),
+            Self::Unsupported(what) => write!(f, "unsupported: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for TiledError {}
+
+impl From<serde_json::Error> for TiledError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl From<roxmltree::Error> for TiledError {
+    fn from(err: roxmltree::Error) -> Self {
+        Self::Xml(err)
+    }
+}
+
+/// Whether `path` names a Tiled map, `.tmj` or `.tmx`.
+pub fn is_tiled_map(path: &str) -> bool {
+    path.ends_with(".tmj") || path.ends_with(".tmx")
+}
+
+/// A map authored in Tiled. Tile layers named `background`, `foreground` and `overlay` fill
+/// those layers, with the one tileset's local tile ids read as the game's own and flipped or
+/// rotated tiles laid that way; any tile on a `collision` layer is solid. Objects are picked out by class:
+/// - `spawn`: where the player arrives;
+/// - `entity`: spawns the entity named by its `entity` property (or its name), `count` times;
+/// - `structure`: places the structure named by its `structure` property (or its name);
+/// - `waypoint`: places a waypoint;
+/// - `trigger`: a trigger region, with `on`, `once`, `every`, `say`, `speaker`, `sound`,
+///   `cutscene`, `boss`, `interact`, `spawn`, `count` and `radius` properties as in trigger files.
+#[derive(Clone)]
+pub struct TiledMap {
+    /// Tells this map's triggers apart from other maps'.
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    layers: Vec<TiledLayer>,
+    /// First gid of each tileset, ascending.
+    first_gids: Vec<u32>,
+    objects: Vec<TiledObject>,
+}
+
+#[derive(Clone)]
+struct TiledLayer {
+    name: String,
+    gids: Vec<u32>,
+}
+
+#[derive(Clone)]
+struct TiledObject {
+    name: String,
+    class: String,
+    /// In tiles.
+    rect: Rect,
+    properties: HashMap<String, String>,
+}
+
+impl TiledObject {
+    fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(String::as_str).filter(|value| !value.is_empty())
+    }
+
+    /// The named property, falling back to the object's name.
+    fn id(&self, key: &str) -> Option<&str> {
+        self.property(key).or(Some(self.name.as_str()).filter(|name| !name.is_empty()))
+    }
+
+    fn tile(&self) -> (usize, usize) {
+        (self.rect.x.max(0.0) as usize, self.rect.y.max(0.0) as usize)
+    }
+}
+
+impl TiledMap {
+    /// An empty map, for other importers to fill in.
+    pub fn new(name: &str, width: usize, height: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            width,
+            height,
+            layers: Vec::new(),
+            first_gids: Vec::new(),
+            objects: Vec::new(),
+        }
+    }
+
+    /// Adds a tile layer of `gids` numbered as Tiled does: 0 for none, else the tile id plus one.
+    pub fn push_layer(&mut self, name: &str, gids: Vec<u32>) {
+        self.layers.push(TiledLayer {
+            name: name.to_string(),
+            gids,
+        });
+    }
+
+    /// Adds an object of `class`, with `rect` in tiles.
+    pub fn push_object(&mut self, name: &str, class: &str, rect: Rect, properties: HashMap<String, String>) {
+        self.objects.push(TiledObject {
+            name: name.to_string(),
+            class: class.to_string(),
+            rect,
+            properties,
+        });
+    }
+
+    /// Parses a map saved as `.tmj` or, for any other `path`, `.tmx`.
+    pub fn parse(name: &str, path: &str, text: &str) -> Result<Self, TiledError> {
+        let mut map = if path.ends_with(".tmj") { parse_json_map(text)? } else { parse_xml_map(text)? };
+        map.name = name.to_string();
+        map.first_gids.sort_unstable();
+        // Every tileset's ids would land on the game's one tileset.
+        if map.first_gids.len() > 1 {
+            return Err(TiledError::Unsupported(format!("{} tilesets, the game draws from one", map.first_gids.len())));
+        }
+        for layer in &map.layers {
+            if layer.gids.len() != map.width * map.height {
+                return Err(TiledError::Unsupported(format!("layer '{}' isn't the map's size", layer.name)));
+            }
+        }
+        Ok(map)
+    }
+
+    /// Builds the map's tiles, collision, structures and triggers.
+    pub fn build(
+        &self,
+        structures: &[StructureDef],
+        tile_size: f32,
+        chunk_alloc_per_frame: usize,
+        chunk_rebuild_per_frame: usize,
+    ) -> TileMap {
+        let mut map = TileMap::new_deferred(self.width, self.height, tile_size, Vec2::new(tile_size, tile_size), 0.0);
+        map.set_chunk_work_budget(chunk_alloc_per_frame, chunk_rebuild_per_frame);
+        for layer in &self.layers {
+            let kind = match layer.name.to_lowercase().as_str() {
+                "background" => Some(LayerKind::Background),
+                "foreground" => Some(LayerKind::Foreground),
+                "overlay" => Some(LayerKind::Overlay),
+                "collision" => None,
+                _ => {
+                    eprintln!("tiled map '{}': layer '{}' ignored", self.name, layer.name);
+                    continue;
+                }
+            };
+            let mut out_of_range = 0usize;
+            for (i, &gid) in layer.gids.iter().enumerate() {
+                let (x, y) = (i % self.width, i / self.width);
+                match kind {
+                    Some(kind) => match self.tile(gid) {
+                        Ok(Some(tile)) => {
+                            map.set_tile(kind, x, y, tile);
+                            map.set_tile_orientation(kind, x, y, orientation(gid));
+                        }
+                        Ok(None) => {}
+                        Err(_) => out_of_range += 1,
+                    },
+                    None if gid != 0 => map.set_collision(x, y, true),
+                    None => {}
+                }
+            }
+            if out_of_range > 0 {
+                eprintln!(
+                    "tiled map '{}': {out_of_range} tiles on layer '{}' are past the game's last tile id, {}",
+                    self.name,
+                    layer.name,
+                    u8::MAX - 1
+                );
+            }
+        }
+
+        let find = |id: &str| structures.iter().find(|def| def.id == id);
+        for (index, object) in self.objects.iter().enumerate() {
+            let (x, y) = object.tile();
+            match object.class.as_str() {
+                "structure" | "waypoint" => {
+                    let id = if object.class == "waypoint" { Some(WAYPOINT_ID) } else { object.id("structure") };
+                    match id.and_then(find) {
+                        Some(def) => map.place_structure_def(def, x, y),
+                        None => eprintln!("tiled map '{}': unknown structure '{}'", self.name, id.unwrap_or("")),
+                    }
+                }
+                "trigger" => {
+                    map.add_trigger(PlacedTrigger::new(&self.name, index, 0, 0, &trigger_def(object), tile_size));
+                }
+                _ => {}
+            }
+        }
+        map
+    }
+
+    /// Where the player arrives: the `spawn` object, if there is one.
+    pub fn spawn_point(&self, tile_size: f32) -> Option<Vec2> {
+        let spawn = self.objects.iter().find(|object| object.class == "spawn")?;
+        Some(spawn.rect.center() * tile_size)
+    }
+
+    /// The entities the map's `entity` objects spawn.
+    pub fn spawn_entities(&self, db: &EntityDatabase, registry: &MovementRegistry, tile_size: f32) -> Vec<Entity> {
+        let mut entities = Vec::new();
+        for object in self.objects.iter().filter(|object| object.class == "entity") {
+            let Some(id) = object.id("entity") else {
+                continue;
+            };
+            let count = object.property("count").and_then(|count| count.parse().ok()).unwrap_or(1);
+            for _ in 0..count {
+                let pos = object.rect.center() * tile_size;
+                match Entity::spawn(db, id, pos, registry) {
+                    Some(entity) => entities.push(entity),
+                    None => eprintln!("tiled map '{}': unknown entity '{id}'", self.name),
+                }
+            }
+        }
+        entities
+    }
+
+    /// The game tile for `gid`: its id within the tileset, or None for an empty cell. Errs
+    /// with the id when the game's tile ids, a byte with the top value meaning empty, can't hold it.
+    fn tile(&self, gid: u32) -> Result<Option<u8>, u32> {
+        let gid = gid & !GID_FLAGS;
+        if gid == 0 {
+            return Ok(None);
+        }
+        let first = self.first_gids.iter().rev().find(|&&first| first <= gid).copied().unwrap_or(1);
+        let id = gid - first;
+        match u8::try_from(id) {
+            Ok(tile) if tile != u8::MAX => Ok(Some(tile)),
+            _ => Err(id),
+        }
+    }
+}
+
+/// How Tiled's flip bits on `gid` lay its tile. Tiled mirrors along the diagonal first, then
+/// horizontally, then vertically; every mix of those is a mirror and some quarter turns.
+fn orientation(gid: u32) -> TileOrientation {
+    let flags = (
+        gid & FLIPPED_HORIZONTALLY != 0,
+        gid & FLIPPED_VERTICALLY != 0,
+        gid & FLIPPED_DIAGONALLY != 0,
+    );
+    let (flip_x, turns) = match flags {
+        (false, false, false) => (false, 0),
+        (true, false, false) => (true, 0),
+        (false, true, false) => (true, 2),
+        (true, true, false) => (false, 2),
+        (false, false, true) => (true, 3),
+        (true, false, true) => (false, 1),
+        (false, true, true) => (false, 3),
+        (true, true, true) => (true, 1),
+    };
+    TileOrientation { flip_x, turns }
+}
+
+fn trigger_def(object: &TiledObject) -> TriggerDef {
+    let flag = |key: &str| object.property(key).is_some_and(|value| value == "true");
+    let number = |key: &str| object.property(key).and_then(|value| value.parse::<f32>().ok());
+    let on = match object.property("on") {
+        Some("exit") => TriggerWhen::Exit,
+        Some("stay") => TriggerWhen::Stay,
+        _ => TriggerWhen::Enter,
+    };
+    let action = TriggerAction {
+        say: object.property("say").map(|say| say.lines().map(str::to_string).collect()).unwrap_or_default(),
+        speaker: object.property("speaker").map(str::to_string),
+        sound: object.property("sound").map(str::to_string),
+        spawn: object.property("spawn").map(|entity| SpawnWave {
+            entity: entity.to_string(),
+            count: number("count").map_or(1, |count| count.max(1.0) as u32),
+            radius: number("radius").unwrap_or(0.0),
+        }),
+        boss: object.property("boss").map(str::to_string),
+        interact: object
+            .property("interact")
+            .map(|interact| interact.split(',').map(|name| name.trim().to_string()).collect())
+            .unwrap_or_default(),
+        cutscene: object.property("cutscene").map(str::to_string),
+    };
+    TriggerDef {
+        rect: [object.rect.x, object.rect.y, object.rect.w, object.rect.h],
+        on,
+        once: flag("once"),
+        every: number("every").unwrap_or(1.0),
+        actions: vec![action],
+    }
+}
+
+/// A tileset saved as `.tsj` or `.tsx`, with what the game's tileset format needs.
+pub struct TiledTileset {
+    pub image: Option<String>,
+    pub tile_width: u16,
+    pub tile_height: u16,
+    pub columns: u16,
+    pub tile_count: u16,
+    /// Animated tiles with their frames and frames per second. Tiled gives every frame its
+    /// own duration; the game plays them evenly at the average.
+    pub animations: Vec<(u8, Vec<u8>, f32)>,
+}
+
+/// Whether `path` names a Tiled tileset, `.tsj` or `.tsx`.
+pub fn is_tiled_tileset(path: &str) -> bool {
+    path.ends_with(".tsj") || path.ends_with(".tsx")
+}
+
+impl TiledTileset {
+    pub fn parse(path: &str, text: &str) -> Result<Self, TiledError> {
+        if path.ends_with(".tsj") {
+            let raw: TilesetJson = serde_json::from_str(text)?;
+            let animations = raw
+                .tiles
+                .into_iter()
+                .filter_map(|tile| Some((tile.id, tile.animation?)))
+                .map(|(id, frames)| (id, frames.into_iter().map(|frame| (frame.tileid, frame.duration)).collect()))
+                .filter_map(|(id, frames)| animation(id, frames))
+                .collect();
+            return Ok(Self {
+                image: raw.image,
+                tile_width: raw.tilewidth,
+                tile_height: raw.tileheight,
+                columns: raw.columns,
+                tile_count: raw.tilecount,
+                animations,
+            });
+        }
+        let doc = roxmltree::Document::parse(text)?;
+        let root = doc.root_element();
+        let number = |key: &str| root.attribute(key).and_then(|value| value.parse::<u16>().ok()).unwrap_or(0);
+        let mut animations = Vec::new();
+        for tile in root.children().filter(|node| node.has_tag_name("tile")) {
+            let id = tile.attribute("id").and_then(|id| id.parse().ok()).unwrap_or(0);
+            let frames = tile
+                .children()
+                .filter(|node| node.has_tag_name("animation"))
+                .flat_map(|node| node.children().filter(|frame| frame.has_tag_name("frame")))
+                .map(|frame| {
+                    let attribute = |key: &str| frame.attribute(key).and_then(|value| value.parse().ok()).unwrap_or(0);
+                    (attribute("tileid"), attribute("duration"))
+                })
+                .collect();
+            animations.extend(animation(id, frames));
+        }
+        let image = root.children().find(|node| node.has_tag_name("image"));
+        Ok(Self {
+            image: image.and_then(|image| image.attribute("source")).map(str::to_string),
+            tile_width: number("tilewidth"),
+            tile_height: number("tileheight"),
+            columns: number("columns"),
+            tile_count: number("tilecount"),
+            animations,
+        })
+    }
+}
+
+/// An animation from Tiled's (tile, milliseconds) frames, if every tile fits the game's ids.
+fn animation(id: u32, frames: Vec<(u32, u32)>) -> Option<(u8, Vec<u8>, f32)> {
+    let total: u32 = frames.iter().map(|&(_, duration)| duration).sum();
+    if frames.is_empty() || total == 0 {
+        return None;
+    }
+    let fps = frames.len() as f32 * 1000.0 / total as f32;
+    let frames = frames.into_iter().map(|(tile, _)| u8::try_from(tile).ok()).collect::<Option<Vec<u8>>>()?;
+    Some((u8::try_from(id).ok()?, frames, fps))
+}
+
+#[derive(Deserialize)]
+struct TilesetJson {
+    #[serde(default)]
+    image: Option<String>,
+    tilewidth: u16,
+    tileheight: u16,
+    columns: u16,
+    tilecount: u16,
+    #[serde(default)]
+    tiles: Vec<TilesetTileJson>,
+}
+
+#[derive(Deserialize)]
+struct TilesetTileJson {
+    id: u32,
+    #[serde(default)]
+    animation: Option<Vec<FrameJson>>,
+}
+
+#[derive(Deserialize)]
+struct FrameJson {
+    tileid: u32,
+    duration: u32,
+}
+
+#[derive(Deserialize)]
+struct MapJson {
+    width: usize,
+    height: usize,
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    infinite: bool,
+    layers: Vec<LayerJson>,
+    #[serde(default)]
+    tilesets: Vec<TilesetRefJson>,
+}
+
+#[derive(Deserialize)]
+struct TilesetRefJson {
+    firstgid: u32,
+}
+
+#[derive(Deserialize)]
+struct LayerJson {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    data: Option<LayerDataJson>,
+    #[serde(default)]
+    objects: Vec<ObjectJson>,
+    /// A group's own layers.
+    #[serde(default)]
+    layers: Vec<LayerJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LayerDataJson {
+    Gids(Vec<u32>),
+    Encoded(serde::de::IgnoredAny),
+}
+
+#[derive(Deserialize)]
+struct ObjectJson {
+    #[serde(default)]
+    name: String,
+    #[serde(default, alias = "class")]
+    r#type: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    properties: Vec<PropertyJson>,
+}
+
+#[derive(Deserialize)]
+struct PropertyJson {
+    name: String,
+    value: serde_json::Value,
+}
+
+fn parse_json_map(text: &str) -> Result<TiledMap, TiledError> {
+    let raw: MapJson = serde_json::from_str(text)?;
+    if raw.infinite {
+        return Err(TiledError::Unsupported("infinite maps".to_string()));
+    }
+    let tile = vec2(raw.tilewidth.max(1.0), raw.tileheight.max(1.0));
+    let mut map = TiledMap::new("", raw.width, raw.height);
+    map.first_gids = raw.tilesets.iter().map(|tileset| tileset.firstgid).collect();
+    let mut pending: Vec<LayerJson> = raw.layers;
+    pending.reverse();
+    while let Some(layer) = pending.pop() {
+        match layer.kind.as_str() {
+            "tilelayer" => match layer.data {
+                Some(LayerDataJson::Gids(gids)) => map.layers.push(TiledLayer { name: layer.name, gids }),
+                Some(LayerDataJson::Encoded(_)) => {
+                    return Err(TiledError::Unsupported(format!("encoded layer '{}', save as CSV", layer.name)));
+                }
+                None => {}
+            },
+            "objectgroup" => map.objects.extend(layer.objects.into_iter().map(|object| TiledObject {
+                name: object.name,
+                class: object.r#type,
+                rect: Rect::new(object.x / tile.x, object.y / tile.y, object.width / tile.x, object.height / tile.y),
+                properties: object
+                    .properties
+                    .into_iter()
+                    .map(|property| {
+                        let value = match property.value {
+                            serde_json::Value::String(value) => value,
+                            value => value.to_string(),
+                        };
+                        (property.name, value)
+                    })
+                    .collect(),
+            })),
+            "group" => pending.extend(layer.layers.into_iter().rev()),
+            _ => {}
+        }
+    }
+    Ok(map)
+}
+
+fn parse_xml_map(text: &str) -> Result<TiledMap, TiledError> {
+    let doc = roxmltree::Document::parse(text)?;
+    let root = doc.root_element();
+    if root.attribute("infinite") == Some("1") {
+        return Err(TiledError::Unsupported("infinite maps".to_string()));
+    }
+    let number = |key: &str| root.attribute(key).and_then(|value| value.parse::<f32>().ok()).unwrap_or(0.0);
+    let tile = vec2(number("tilewidth").max(1.0), number("tileheight").max(1.0));
+    let first_gids = root
+        .children()
+        .filter(|node| node.has_tag_name("tileset"))
+        .filter_map(|node| node.attribute("firstgid")?.parse().ok())
+        .collect();
+    let mut map = TiledMap::new("", number("width") as usize, number("height") as usize);
+    map.first_gids = first_gids;
+    let mut pending: Vec<roxmltree::Node> = root.children().filter(|node| node.is_element()).collect();
+    pending.reverse();
+    while let Some(node) = pending.pop() {
+        let name = node.attribute("name").unwrap_or_default().to_string();
+        match node.tag_name().name() {
+            "layer" => {
+                let Some(data) = node.children().find(|child| child.has_tag_name("data")) else {
+                    continue;
+                };
+                let gids = match data.attribute("encoding") {
+                    Some("csv") => data
+                        .text()
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(|gid| gid.trim().parse().unwrap_or(0))
+                        .collect(),
+                    Some(_) => return Err(TiledError::Unsupported(format!("encoded layer '{name}', save as CSV"))),
+                    None => data
+                        .children()
+                        .filter(|child| child.has_tag_name("tile"))
+                        .map(|tile| tile.attribute("gid").and_then(|gid| gid.parse().ok()).unwrap_or(0))
+                        .collect(),
+                };
+                map.layers.push(TiledLayer { name, gids });
+            }
+            "objectgroup" => {
+                for object in node.children().filter(|child| child.has_tag_name("object")) {
+                    let attribute = |key: &str| object.attribute(key).and_then(|value| value.parse::<f32>().ok());
+                    let properties = object
+                        .children()
+                        .filter(|child| child.has_tag_name("properties"))
+                        .flat_map(|properties| properties.children().filter(|child| child.has_tag_name("property")))
+                        .map(|property| {
+                            let value = property.attribute("value").or(property.text()).unwrap_or_default();
+                            (property.attribute("name").unwrap_or_default().to_string(), value.to_string())
+                        })
+                        .collect();
+                    let class = object.attribute("type").or(object.attribute("class")).unwrap_or_default();
+                    map.objects.push(TiledObject {
+                        name: object.attribute("name").unwrap_or_default().to_string(),
+                        class: class.to_string(),
+                        rect: Rect::new(
+                            attribute("x").unwrap_or(0.0) / tile.x,
+                            attribute("y").unwrap_or(0.0) / tile.y,
+                            attribute("width").unwrap_or(0.0) / tile.x,
+                            attribute("height").unwrap_or(0.0) / tile.y,
+                        ),
+                        properties,
+                    });
+                }
+            }
+            "group" => pending.extend(node.children().filter(|child| child.is_element()).rev()),
+            _ => {}
+        }
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json_map(tilesets: &str, gids: &[u32]) -> String {
+        format!(
+            r#"{{"width": {}, "height": 1, "tilewidth": 16, "tileheight": 16, "tilesets": [{tilesets}],
+                "layers": [{{"name": "background", "type": "tilelayer", "data": {gids:?}}}]}}"#,
+            gids.len()
+        )
+    }
+
+    #[test]
+    fn gids_map_to_the_tileset_ids() {
+        let map = TiledMap::parse("test", "test.tmj", &json_map(r#"{"firstgid": 1}"#, &[0, 1, 25])).unwrap();
+        assert_eq!(map.tile(0), Ok(None));
+        assert_eq!(map.tile(1), Ok(Some(0)));
+        assert_eq!(map.tile(25), Ok(Some(24)));
+        assert_eq!(map.tile(25 | FLIPPED_HORIZONTALLY | FLIPPED_DIAGONALLY), Ok(Some(24)));
+        // 255 means an empty cell to the game.
+        assert_eq!(map.tile(255), Ok(Some(254)));
+        assert_eq!(map.tile(256), Err(255));
+        assert_eq!(map.tile(301), Err(300));
+    }
+
+    #[test]
+    fn several_tilesets_are_refused() {
+        let text = json_map(r#"{"firstgid": 1}, {"firstgid": 65}"#, &[1, 65]);
+        assert!(matches!(TiledMap::parse("test", "test.tmj", &text), Err(TiledError::Unsupported(_))));
+    }
+
+    #[test]
+    fn flip_bits_become_orientations() {
+        let laid = |gid: u32| {
+            let orientation = orientation(gid);
+            (orientation.flip_x, orientation.turns)
+        };
+        assert_eq!(laid(1), (false, 0));
+        assert_eq!(laid(1 | FLIPPED_HORIZONTALLY), (true, 0));
+        assert_eq!(laid(1 | FLIPPED_VERTICALLY), (true, 2));
+        assert_eq!(laid(1 | FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY), (false, 2));
+        // Tiled's quarter turns clockwise and anticlockwise.
+        assert_eq!(laid(1 | FLIPPED_HORIZONTALLY | FLIPPED_DIAGONALLY), (false, 1));
+        assert_eq!(laid(1 | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY), (false, 3));
+    }
+}
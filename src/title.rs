@@ -0,0 +1,309 @@
+use macroquad::prelude::*;
+use crate::difficulty::{Difficulty, DifficultyPreset};
+use crate::farm::DayClock;
+use crate::font::{draw_text, measure_text};
+use crate::locale::Locale;
+use crate::slot::{SaveManager, SaveSlot, THUMBNAIL_HEIGHT, THUMBNAIL_WIDTH};
+
+const PANEL_WIDTH: f32 = 560.0;
+const CARD_HEIGHT: f32 = THUMBNAIL_HEIGHT as f32 + 16.0;
+const BUTTON_HEIGHT: f32 = 36.0;
+const MAX_NAME_LEN: usize = 24;
+const MAX_PATH_LEN: usize = 260;
+/// Browsers have no file paths to type in, so importing archives is native only; exports
+/// there become downloads.
+const IMPORTS: bool = !cfg!(target_arch = "wasm32");
+
+#[derive(Clone, Copy, PartialEq)]
+enum Prompt {
+    Name,
+    ImportPath,
+}
+
+#[derive(Default)]
+struct TitleScreen {
+    scroll: usize,
+    /// Text typed so far into the open prompt.
+    prompt: Option<(Prompt, String)>,
+    /// Slot whose delete button was clicked once and awaits a second click.
+    confirm_delete: Option<usize>,
+    /// Outcome of the last export or import.
+    message: Option<String>,
+    /// Difficulty the next new farm starts on, cycled with tab in the name prompt.
+    difficulty: DifficultyPreset,
+}
+
+/// Shows the save slots until one is loaded or a new one created, which becomes the active
+/// slot. Returns false if the window was closed instead.
+pub async fn run(saves: &mut SaveManager, locale: &Locale) -> bool {
+    let mut title = TitleScreen::default();
+    loop {
+        if is_quit_requested() {
+            return false;
+        }
+        if title.update(saves) {
+            return true;
+        }
+        title.draw(saves, locale);
+        next_frame().await;
+    }
+}
+
+impl TitleScreen {
+    /// Returns true once a slot is active.
+    fn update(&mut self, saves: &mut SaveManager) -> bool {
+        if let Some((prompt, text)) = self.prompt.as_mut() {
+            let max_len = match prompt {
+                Prompt::Name => MAX_NAME_LEN,
+                Prompt::ImportPath => MAX_PATH_LEN,
+            };
+            while let Some(c) = get_char_pressed() {
+                if !c.is_control() && text.chars().count() < max_len {
+                    text.push(c);
+                }
+            }
+            if is_key_pressed(KeyCode::Backspace) {
+                text.pop();
+            }
+            if *prompt == Prompt::Name && is_key_pressed(KeyCode::Tab) {
+                let choices = DifficultyPreset::CHOICES;
+                let current = choices.iter().position(|&preset| preset == self.difficulty).unwrap_or(0);
+                self.difficulty = choices[(current + 1) % choices.len()];
+            }
+            if is_key_pressed(KeyCode::Escape) {
+                self.prompt = None;
+            } else if is_key_pressed(KeyCode::Enter) {
+                let (prompt, text) = self.prompt.take().unwrap_or((Prompt::Name, String::new()));
+                match prompt {
+                    Prompt::Name if saves.create(&text, Difficulty::preset(self.difficulty)) => return true,
+                    Prompt::Name => self.message = Some(format!("failed to create save slot '{text}'")),
+                    Prompt::ImportPath => {
+                        let path = std::path::Path::new(text.trim());
+                        self.message = Some(match saves.import_save(path) {
+                            Ok(()) => format!("imported {}", path.display()),
+                            Err(err) => format!("import failed: {err}"),
+                        });
+                        self.scroll = 0;
+                    }
+                }
+            }
+            return false;
+        }
+
+        let visible = visible_cards();
+        let (_, wheel) = mouse_wheel();
+        if wheel < 0.0 {
+            self.scroll = (self.scroll + 1).min(saves.slots().len().saturating_sub(visible));
+        } else if wheel > 0.0 {
+            self.scroll = self.scroll.saturating_sub(1);
+        }
+        if !is_mouse_button_pressed(MouseButton::Left) {
+            return false;
+        }
+        let mouse = vec2(mouse_position().0, mouse_position().1);
+        let (new_button, import_button) = bottom_buttons();
+        let prompt = if new_button.contains(mouse) {
+            Some(Prompt::Name)
+        } else if IMPORTS && import_button.contains(mouse) {
+            Some(Prompt::ImportPath)
+        } else {
+            None
+        };
+        if let Some(prompt) = prompt {
+            // Drop keys typed before the prompt opened.
+            while get_char_pressed().is_some() {}
+            self.prompt = Some((prompt, String::new()));
+            self.confirm_delete = None;
+            return false;
+        }
+        let hit = (self.scroll..saves.slots().len().min(self.scroll + visible))
+            .find(|&index| card_rect(index - self.scroll).contains(mouse));
+        let Some(index) = hit else {
+            self.confirm_delete = None;
+            return false;
+        };
+        let card = card_rect(index - self.scroll);
+        if export_button_rect(card).contains(mouse) {
+            self.confirm_delete = None;
+            self.message = Some(match saves.export_path(index) {
+                Some(path) => match saves.export_save(index, &path) {
+                    Ok(()) => format!("exported to {}", path.display()),
+                    Err(err) => format!("export failed: {err}"),
+                },
+                None => "export failed: no home directory".to_string(),
+            });
+            return false;
+        }
+        if delete_button_rect(card).contains(mouse) {
+            if self.confirm_delete == Some(index) {
+                if !saves.delete(index) {
+                    eprintln!("failed to delete save slot");
+                }
+                self.confirm_delete = None;
+                self.scroll = self.scroll.min(saves.slots().len().saturating_sub(visible));
+            } else {
+                self.confirm_delete = Some(index);
+            }
+            return false;
+        }
+        saves.select(index)
+    }
+
+    fn draw(&self, saves: &SaveManager, locale: &Locale) {
+        clear_background(Color::new(0.06, 0.08, 0.07, 1.0));
+        let panel_x = (screen_width() - PANEL_WIDTH) * 0.5;
+        draw_text("cropbots", panel_x, 72.0, 56.0, WHITE);
+        let mouse = vec2(mouse_position().0, mouse_position().1);
+
+        if saves.slots().is_empty() {
+            draw_text("No saves yet", panel_x, card_rect(0).y + 28.0, 22.0, GRAY);
+        }
+        let visible = visible_cards();
+        for (row, index) in (self.scroll..saves.slots().len().min(self.scroll + visible)).enumerate() {
+            let rect = card_rect(row);
+            draw_card(&saves.slots()[index], rect, mouse, self.confirm_delete == Some(index), locale);
+        }
+
+        let (new_button, import_button) = bottom_buttons();
+        match &self.prompt {
+            Some((prompt, text)) => {
+                let field = Rect::new(new_button.x, new_button.y, PANEL_WIDTH, new_button.h);
+                let (label, hint) = match prompt {
+                    Prompt::Name => ("Name", "tab to change difficulty, enter to start, escape to cancel"),
+                    Prompt::ImportPath => ("Archive path", "enter to import, escape to cancel"),
+                };
+                draw_rectangle(field.x, field.y, field.w, field.h, Color::new(0.15, 0.18, 0.16, 1.0));
+                draw_rectangle_lines(field.x, field.y, field.w, field.h, 2.0, WHITE);
+                draw_text(&format!("{label}: {text}_"), field.x + 12.0, field.y + 24.0, 22.0, WHITE);
+                if *prompt == Prompt::Name {
+                    let difficulty = self.difficulty.label();
+                    let width = measure_text(difficulty, 20.0).width;
+                    draw_text(difficulty, field.right() - width - 12.0, field.y + 24.0, 20.0, GOLD);
+                }
+                draw_text(hint, field.x, field.bottom() + 20.0, 16.0, GRAY);
+            }
+            None => {
+                draw_button(new_button, "New farm", mouse);
+                if IMPORTS {
+                    draw_button(import_button, "Import", mouse);
+                }
+                if let Some(message) = &self.message {
+                    draw_text(message, new_button.x, new_button.bottom() + 20.0, 16.0, LIGHTGRAY);
+                }
+            }
+        }
+    }
+}
+
+fn draw_card(slot: &SaveSlot, rect: Rect, mouse: Vec2, confirm_delete: bool, locale: &Locale) {
+    let fill = if rect.contains(mouse) {
+        Color::new(0.18, 0.22, 0.2, 1.0)
+    } else {
+        Color::new(0.11, 0.14, 0.12, 1.0)
+    };
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, fill);
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, GRAY);
+
+    let thumb = Rect::new(rect.x + 8.0, rect.y + 8.0, THUMBNAIL_WIDTH as f32, THUMBNAIL_HEIGHT as f32);
+    match &slot.thumbnail {
+        Some(texture) => draw_texture_ex(
+            texture,
+            thumb.x,
+            thumb.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(thumb.size()),
+                ..Default::default()
+            },
+        ),
+        None => draw_rectangle(thumb.x, thumb.y, thumb.w, thumb.h, Color::new(0.0, 0.0, 0.0, 0.4)),
+    }
+
+    let meta = &slot.meta;
+    let text_x = thumb.right() + 14.0;
+    let clock = DayClock { day: meta.day, time: 0.0 };
+    let minutes = (meta.playtime / 60.0) as i64;
+    draw_text(&meta.name, text_x, rect.y + 28.0, 24.0, WHITE);
+    draw_text(
+        &format!("{}  {}", locale.format_date(clock), meta.difficulty.preset.label()),
+        text_x,
+        rect.y + 52.0,
+        18.0,
+        LIGHTGRAY,
+    );
+    draw_text(
+        &format!("played {}h {:02}m", locale.format_int(minutes / 60), minutes % 60),
+        text_x,
+        rect.y + 72.0,
+        18.0,
+        LIGHTGRAY,
+    );
+    if meta.player_hp > 0.0 {
+        draw_text(
+            &format!("hp {}", locale.format_decimal(meta.player_hp as f64, 1)),
+            text_x,
+            rect.y + 92.0,
+            18.0,
+            LIGHTGRAY,
+        );
+    }
+    draw_button(export_button_rect(rect), "Export", mouse);
+    let delete = delete_button_rect(rect);
+    draw_button(delete, if confirm_delete { "Sure?" } else { "Delete" }, mouse);
+}
+
+fn draw_button(rect: Rect, label: &str, mouse: Vec2) {
+    let fill = if rect.contains(mouse) {
+        Color::new(0.3, 0.36, 0.32, 1.0)
+    } else {
+        Color::new(0.2, 0.24, 0.21, 1.0)
+    };
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, fill);
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, LIGHTGRAY);
+    let size = measure_text(label, 20.0);
+    draw_text(
+        label,
+        rect.x + (rect.w - size.width) * 0.5,
+        rect.y + (rect.h + size.offset_y) * 0.5,
+        20.0,
+        WHITE,
+    );
+}
+
+fn card_rect(row: usize) -> Rect {
+    Rect::new(
+        (screen_width() - PANEL_WIDTH) * 0.5,
+        110.0 + row as f32 * (CARD_HEIGHT + 8.0),
+        PANEL_WIDTH,
+        CARD_HEIGHT,
+    )
+}
+
+fn delete_button_rect(card: Rect) -> Rect {
+    Rect::new(card.right() - 88.0, card.y + (card.h - 28.0) * 0.5, 76.0, 28.0)
+}
+
+fn export_button_rect(card: Rect) -> Rect {
+    let delete = delete_button_rect(card);
+    Rect::new(delete.x - delete.w - 8.0, delete.y, delete.w, delete.h)
+}
+
+/// The new farm button and, beside it where imports are supported, the import button.
+fn bottom_buttons() -> (Rect, Rect) {
+    let x = (screen_width() - PANEL_WIDTH) * 0.5;
+    let y = screen_height() - BUTTON_HEIGHT - 40.0;
+    if !IMPORTS {
+        return (Rect::new(x, y, PANEL_WIDTH, BUTTON_HEIGHT), Rect::default());
+    }
+    let width = (PANEL_WIDTH - 8.0) * 0.5;
+    (
+        Rect::new(x, y, width, BUTTON_HEIGHT),
+        Rect::new(x + width + 8.0, y, width, BUTTON_HEIGHT),
+    )
+}
+
+/// How many slot cards fit between the heading and the bottom buttons.
+fn visible_cards() -> usize {
+    let space = bottom_buttons().0.y - 16.0 - card_rect(0).y;
+    ((space + 8.0) / (CARD_HEIGHT + 8.0)).floor().max(1.0) as usize
+}
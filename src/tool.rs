@@ -0,0 +1,151 @@
+use macroquad::prelude::*;
+use crate::crop::CropDatabase;
+use crate::farm::FarmState;
+use crate::item::ItemDef;
+use crate::map::{StructureHit, TileMap};
+
+const TARGET_COLOR: Color = Color::new(1.0, 1.0, 1.0, 0.8);
+const BLOCKED_COLOR: Color = Color::new(1.0, 0.35, 0.3, 0.5);
+
+/// What a tool item does, from its `tool` key.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ToolAction {
+    Till,
+    Water,
+    Fertilize,
+    Plant,
+    Chop,
+    /// Mends damaged structures, paid for from the inventory by the caller.
+    Repair,
+    /// Used on entities, not tiles.
+    Capture,
+}
+
+/// What using a tool on a tile came to.
+pub enum ToolUse {
+    Nothing,
+    Used,
+    /// Breakable structures in this area are for the caller to chop at.
+    Chop(Rect),
+    /// The damaged structure in this area is for the caller to repair.
+    Repair(Rect),
+}
+
+impl ToolAction {
+    pub fn of(def: &ItemDef) -> Option<Self> {
+        Some(match def.tool.as_deref()? {
+            "till" => Self::Till,
+            "water" => Self::Water,
+            "fertilize" => Self::Fertilize,
+            "plant" => Self::Plant,
+            "chop" => Self::Chop,
+            "repair" => Self::Repair,
+            "capture" => Self::Capture,
+            _ => return None,
+        })
+    }
+
+    /// Whether using the tool on `tile` would do anything. `farm` is only given on the farm,
+    /// the one place soil can be worked.
+    pub fn can_use(
+        self,
+        def: &ItemDef,
+        farm: Option<&FarmState>,
+        map: &TileMap,
+        crop_db: &CropDatabase,
+        ground_tile: u8,
+        (x, y): (usize, usize),
+    ) -> bool {
+        if self == Self::Chop {
+            return map.overlaps_breakable_structure(chop_area(map, x, y), StructureHit::Tool);
+        }
+        if self == Self::Repair {
+            let area = chop_area(map, x, y);
+            return map.damaged_structures().any(|(_, rect)| rect.overlaps(&area));
+        }
+        let Some(farm) = farm else {
+            return false;
+        };
+        match self {
+            Self::Till => farm.soil.can_till(map, x, y, ground_tile),
+            Self::Water => farm.soil.can_water(x, y),
+            Self::Fertilize => farm.soil.can_fertilize(x, y),
+            Self::Plant => crop_db
+                .by_seed(&def.id)
+                .is_some_and(|crop| farm.crops.can_plant(&farm.soil, x, y, crop, farm.clock.season())),
+            Self::Chop | Self::Repair | Self::Capture => false,
+        }
+    }
+
+    /// Uses the tool on `tile`, leaving chopping and repairs to the caller.
+    pub fn apply(
+        self,
+        def: &ItemDef,
+        farm: Option<&mut FarmState>,
+        map: &mut TileMap,
+        crop_db: &CropDatabase,
+        ground_tile: u8,
+        (x, y): (usize, usize),
+    ) -> ToolUse {
+        if !self.can_use(def, farm.as_deref(), map, crop_db, ground_tile, (x, y)) {
+            return ToolUse::Nothing;
+        }
+        if self == Self::Chop {
+            return ToolUse::Chop(chop_area(map, x, y));
+        }
+        if self == Self::Repair {
+            return ToolUse::Repair(chop_area(map, x, y));
+        }
+        let Some(farm) = farm else {
+            return ToolUse::Nothing;
+        };
+        let used = match self {
+            Self::Till => farm.soil.till(map, x, y, ground_tile),
+            Self::Water => farm.soil.water(x, y),
+            Self::Fertilize => farm.soil.fertilize(x, y),
+            Self::Plant => crop_db
+                .by_seed(&def.id)
+                .is_some_and(|crop| farm.crops.plant(&farm.soil, x, y, crop, farm.clock.season())),
+            Self::Chop | Self::Repair | Self::Capture => false,
+        };
+        if used { ToolUse::Used } else { ToolUse::Nothing }
+    }
+}
+
+pub fn tile_at(map: &TileMap, world: Vec2) -> Option<(usize, usize)> {
+    if world.x < 0.0 || world.y < 0.0 {
+        return None;
+    }
+    let (x, y) = ((world.x / map.tile_size()) as usize, (world.y / map.tile_size()) as usize);
+    (x < map.width() && y < map.height()).then_some((x, y))
+}
+
+/// The tile next to the one at `from`, in whichever of the four directions `dir` leans most.
+pub fn facing_tile(map: &TileMap, from: Vec2, dir: Vec2) -> Option<(usize, usize)> {
+    let step = if dir.x.abs() > dir.y.abs() {
+        vec2(dir.x.signum(), 0.0)
+    } else {
+        vec2(0.0, dir.y.signum())
+    };
+    let (x, y) = tile_at(map, from)?;
+    let center = vec2(x as f32 + 0.5, y as f32 + 0.5) * map.tile_size();
+    tile_at(map, center + step * map.tile_size())
+}
+
+pub fn tile_rect(map: &TileMap, x: usize, y: usize) -> Rect {
+    let size = map.tile_size();
+    Rect::new(x as f32 * size, y as f32 * size, size, size)
+}
+
+/// The tile, inset so a structure on a neighbouring tile isn't caught by its edge.
+fn chop_area(map: &TileMap, x: usize, y: usize) -> Rect {
+    let tile = tile_rect(map, x, y);
+    Rect::new(tile.x + 1.0, tile.y + 1.0, tile.w - 2.0, tile.h - 2.0)
+}
+
+/// Outlines the targeted tile, dimmed red when the tool would do nothing there.
+pub fn draw_target(map: &TileMap, (x, y): (usize, usize), usable: bool) {
+    let rect = tile_rect(map, x, y);
+    let color = if usable { TARGET_COLOR } else { BLOCKED_COLOR };
+    draw_rectangle_lines(rect.x + 0.5, rect.y + 0.5, rect.w - 1.0, rect.h - 1.0, 1.0, color);
+}
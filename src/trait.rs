@@ -350,7 +350,7 @@ pub fn movement_watch_player(
     params: &MovementParams,
     ctx: &EntityContext,
 ) {
-    if let Some(player) = ctx.player {
+    if let Some(player) = ctx.nearest_player(entity.pos) {
         watch_target(entity, behavior, dt, params, Target::Player(player));
     } else {
         entity.current_target = None;
@@ -420,7 +420,7 @@ pub fn movement_seek_player(
     params: &MovementParams,
     ctx: &EntityContext,
 ) {
-    if let Some(player) = ctx.player {
+    if let Some(player) = ctx.nearest_player(entity.pos) {
         seek_towards_target(
             entity,
             behavior,
@@ -497,7 +497,7 @@ pub fn movement_flee_player(
     params: &MovementParams,
     ctx: &EntityContext,
 ) {
-    if let Some(player) = ctx.player {
+    if let Some(player) = ctx.nearest_player(entity.pos) {
         flee_from_target(
             entity,
             behavior,
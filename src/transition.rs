@@ -0,0 +1,117 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+/// Seconds to cover the screen, and again to uncover it.
+const WIPE_TIME: f32 = 0.45;
+/// Triangles making up the iris's ring.
+const IRIS_SEGMENTS: usize = 48;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionStyle {
+    #[default]
+    Fade,
+    /// A circle closing in on the middle of the screen.
+    Iris,
+    /// Bars sliding in from the top and bottom.
+    Letterbox,
+}
+
+/// Wipes over scene switches instead of cutting: the last frame is held while the wipe closes
+/// over it, the next scene builds underneath behind the loading screen, and the wipe opens
+/// again on the new scene.
+#[derive(Default)]
+pub struct SceneTransition {
+    style: TransitionStyle,
+    held: Option<Texture2D>,
+    /// Seconds into uncovering the new scene, while that's happening.
+    reveal: Option<f32>,
+}
+
+impl SceneTransition {
+    /// Keeps what's on screen to wipe over. Call once the frame is fully drawn.
+    pub fn hold_frame(&mut self) {
+        let texture = Texture2D::from_image(&get_screen_data());
+        texture.set_filter(FilterMode::Nearest);
+        self.held = Some(texture);
+    }
+
+    /// Plays the wipe closing over the held frame, drawing its own frames, until the screen is
+    /// covered.
+    pub async fn cover(&mut self, style: TransitionStyle) {
+        self.style = style;
+        self.reveal = None;
+        let held = self.held.take();
+        let mut elapsed = 0.0;
+        while elapsed < WIPE_TIME {
+            elapsed += get_frame_time().min(0.05);
+            set_default_camera();
+            clear_background(BLACK);
+            if let Some(held) = held.as_ref() {
+                draw_texture_ex(
+                    held,
+                    0.0,
+                    0.0,
+                    WHITE,
+                    DrawTextureParams {
+                        dest_size: Some(vec2(screen_width(), screen_height())),
+                        // Screen data is read bottom row first.
+                        flip_y: true,
+                        ..Default::default()
+                    },
+                );
+            }
+            draw_wipe(style, elapsed / WIPE_TIME);
+            next_frame().await;
+        }
+    }
+
+    /// Starts opening the wipe on the scene now underneath.
+    pub fn reveal(&mut self) {
+        self.reveal = Some(0.0);
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if let Some(elapsed) = self.reveal.as_mut() {
+            *elapsed += dt;
+            if *elapsed >= WIPE_TIME {
+                self.reveal = None;
+            }
+        }
+    }
+
+    /// Draws the opening wipe over everything, in screen space.
+    pub fn draw(&self) {
+        if let Some(elapsed) = self.reveal {
+            draw_wipe(self.style, 1.0 - elapsed / WIPE_TIME);
+        }
+    }
+}
+
+/// Covers `amount` of the screen, 0 being none of it and 1 all of it.
+fn draw_wipe(style: TransitionStyle, amount: f32) {
+    let amount = amount.clamp(0.0, 1.0);
+    let amount = amount * amount * (3.0 - 2.0 * amount);
+    let (w, h) = (screen_width(), screen_height());
+    match style {
+        TransitionStyle::Fade => draw_rectangle(0.0, 0.0, w, h, Color::new(0.0, 0.0, 0.0, amount)),
+        TransitionStyle::Iris => {
+            let center = vec2(w, h) * 0.5;
+            let outer = center.length() + 2.0;
+            let inner = outer * (1.0 - amount);
+            for i in 0..IRIS_SEGMENTS {
+                let a = Vec2::from_angle(i as f32 / IRIS_SEGMENTS as f32 * std::f32::consts::TAU);
+                let b = Vec2::from_angle((i + 1) as f32 / IRIS_SEGMENTS as f32 * std::f32::consts::TAU);
+                let (a_in, b_in) = (center + a * inner, center + b * inner);
+                let (a_out, b_out) = (center + a * outer * 1.1, center + b * outer * 1.1);
+                draw_triangle(a_in, a_out, b_out, BLACK);
+                draw_triangle(a_in, b_out, b_in, BLACK);
+            }
+        }
+        TransitionStyle::Letterbox => {
+            let bar = h * 0.5 * amount;
+            draw_rectangle(0.0, 0.0, w, bar, BLACK);
+            draw_rectangle(0.0, h - bar, w, bar, BLACK);
+        }
+    }
+}
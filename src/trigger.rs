@@ -0,0 +1,345 @@
+use macroquad::prelude::*;
+use macroquad::file::load_string;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use crate::cutscene::Cutscenes;
+use crate::dialogue::DialogueBox;
+use crate::entity::{Entity, EntityDatabase, MovementRegistry};
+use crate::font::{TextStyle, draw_text_styled, measure_text};
+use crate::helpers::{data_path, load_wasm_manifest_files};
+use crate::interact::{InteractContext, InteractRegistry};
+use crate::map::TileMap;
+use crate::player::Player;
+use crate::scene::SceneKind;
+use crate::sound::SoundSystem;
+
+#[derive(Debug)]
+pub enum TriggerLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for TriggerLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for TriggerLoadError {}
+
+impl From<std::io::Error> for TriggerLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for TriggerLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerWhen {
+    #[default]
+    Enter,
+    Exit,
+    /// Every `every` seconds while the player is inside.
+    Stay,
+}
+
+/// A region that runs its actions when the player walks in, out, or stays.
+#[derive(Clone, Deserialize)]
+pub struct TriggerDef {
+    /// `[x, y, w, h]` in tiles; from the structure's top left when on a structure.
+    pub rect: [f32; 4],
+    #[serde(default)]
+    pub on: TriggerWhen,
+    /// Fires only the first time this session.
+    #[serde(default)]
+    pub once: bool,
+    #[serde(default = "default_every")]
+    pub every: f32,
+    pub actions: Vec<TriggerAction>,
+}
+
+fn default_every() -> f32 {
+    1.0
+}
+
+/// One step of a trigger's script. Every field given runs, in the order listed here.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TriggerAction {
+    /// Lines of dialogue shown one after another.
+    pub say: Vec<String>,
+    pub speaker: Option<String>,
+    pub sound: Option<String>,
+    pub spawn: Option<SpawnWave>,
+    /// Entity spawned at the trigger's center with a health bar across the screen.
+    pub boss: Option<String>,
+    /// Interact functions, as on structures.
+    pub interact: Vec<String>,
+    /// Cutscene id to play.
+    pub cutscene: Option<String>,
+}
+
+/// A group of entities scattered around the trigger's center.
+#[derive(Clone, Deserialize)]
+pub struct SpawnWave {
+    pub entity: String,
+    #[serde(default = "default_count")]
+    pub count: u32,
+    /// Tiles from the center they can land.
+    #[serde(default)]
+    pub radius: f32,
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+struct TriggerFile {
+    /// Scene id, `expedition` or `farm`.
+    scene: String,
+    triggers: Vec<TriggerDef>,
+}
+
+/// A structure's trigger, placed in the world.
+#[derive(Clone)]
+pub struct PlacedTrigger {
+    /// Tells apart copies of one structure's trigger for enter and exit tracking.
+    pub key: String,
+    pub rect: Rect,
+    pub def: TriggerDef,
+}
+
+impl PlacedTrigger {
+    pub fn new(structure_id: &str, index: usize, x: usize, y: usize, def: &TriggerDef, tile_size: f32) -> Self {
+        let [rx, ry, w, h] = def.rect.map(|v| v * tile_size);
+        let origin = vec2(x as f32, y as f32) * tile_size;
+        Self {
+            key: format!("{structure_id}@{x},{y}#{index}"),
+            rect: Rect::new(origin.x + rx, origin.y + ry, w, h),
+            def: def.clone(),
+        }
+    }
+}
+
+/// What the actions of fired triggers need to reach.
+pub struct TriggerContext<'a> {
+    pub player: &'a mut Player,
+    pub map: &'a mut TileMap,
+    pub entities: &'a mut Vec<Entity>,
+    pub db: &'a EntityDatabase,
+    pub registry: &'a MovementRegistry,
+    pub sounds: &'a SoundSystem,
+    pub interact: &'a InteractRegistry,
+    pub dialogue: &'a mut DialogueBox,
+    pub cutscenes: &'a mut Cutscenes,
+}
+
+struct Fired {
+    key: String,
+    center: Vec2,
+    area: Rect,
+    actions: Vec<TriggerAction>,
+}
+
+/// Scene triggers, plus whatever structures place, checked against the player each frame.
+pub struct TriggerSystem {
+    scene_triggers: Vec<(SceneKind, TriggerDef)>,
+    inside: HashSet<String>,
+    spent: HashSet<String>,
+    stay_timers: HashMap<String, f32>,
+    boss: Option<u64>,
+}
+
+impl TriggerSystem {
+    pub fn empty() -> Self {
+        Self {
+            scene_triggers: Vec::new(),
+            inside: HashSet::new(),
+            spent: HashSet::new(),
+            stay_timers: HashMap::new(),
+            boss: None,
+        }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, TriggerLoadError> {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let names = load_wasm_manifest_files(&dir, &["expedition.yaml", "farm.yaml"]).await;
+            for name in names {
+                let path = format!("{}/{}", dir, name);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| TriggerLoadError::Io(std::io::Error::other(err.to_string())))?;
+                files.push(serde_yaml::from_str::<TriggerFile>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                files.push(serde_yaml::from_str::<TriggerFile>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+
+        let mut system = Self::empty();
+        for file in files {
+            let Some(scene) = SceneKind::from_id(&file.scene) else {
+                eprintln!("triggers for unknown scene '{}'", file.scene);
+                continue;
+            };
+            system.scene_triggers.extend(file.triggers.into_iter().map(|trigger| (scene, trigger)));
+        }
+        Ok(system)
+    }
+
+    /// Checks `feet` against `scene`'s triggers (None inside an interior) and the structure
+    /// triggers on `ctx.map`, running the actions of any that fire.
+    pub fn update(&mut self, scene: Option<SceneKind>, feet: Vec2, dt: f32, ctx: &mut TriggerContext) {
+        let tile_size = ctx.map.tile_size();
+        let scene_volumes = self
+            .scene_triggers
+            .iter()
+            .enumerate()
+            .filter(|(_, (trigger_scene, _))| Some(*trigger_scene) == scene)
+            .map(|(index, (_, def))| {
+                let [x, y, w, h] = def.rect.map(|v| v * tile_size);
+                (format!("scene#{index}"), Rect::new(x, y, w, h), def)
+            });
+        let structure_volumes = ctx
+            .map
+            .structure_triggers()
+            .iter()
+            .map(|placed| (placed.key.clone(), placed.rect, &placed.def));
+
+        let mut inside = HashSet::new();
+        let mut fired = Vec::new();
+        for (key, rect, def) in scene_volumes.chain(structure_volumes) {
+            let now = rect.contains(feet);
+            let was = self.inside.contains(&key);
+            let fires = match def.on {
+                TriggerWhen::Enter => now && !was,
+                TriggerWhen::Exit => was && !now,
+                TriggerWhen::Stay if now => {
+                    let timer = self.stay_timers.entry(key.clone()).or_insert(0.0);
+                    *timer -= dt;
+                    let due = *timer <= 0.0;
+                    if due {
+                        *timer = def.every.max(0.0);
+                    }
+                    due
+                }
+                TriggerWhen::Stay => {
+                    self.stay_timers.remove(&key);
+                    false
+                }
+            };
+            if fires && !(def.once && self.spent.contains(&key)) {
+                if def.once {
+                    self.spent.insert(key.clone());
+                }
+                fired.push(Fired {
+                    key: key.clone(),
+                    center: rect.center(),
+                    area: rect,
+                    actions: def.actions.clone(),
+                });
+            }
+            if now {
+                inside.insert(key);
+            }
+        }
+        self.inside = inside;
+
+        for fire in fired {
+            for action in &fire.actions {
+                self.run(action, &fire, ctx);
+            }
+        }
+        self.boss = self.boss.filter(|&uid| {
+            ctx.entities.iter().any(|ent| ent.instance.uid == uid && !ent.instance.is_dying())
+        });
+    }
+
+    fn run(&mut self, action: &TriggerAction, fire: &Fired, ctx: &mut TriggerContext) {
+        let tile_size = ctx.map.tile_size();
+        if !action.say.is_empty() {
+            ctx.dialogue.start(action.speaker.as_deref(), &action.say);
+        }
+        if let Some(sound) = action.sound.as_deref() {
+            ctx.sounds.play(sound);
+        }
+        if let Some(wave) = action.spawn.as_ref() {
+            for _ in 0..wave.count {
+                let angle = rand::gen_range(0.0, std::f32::consts::TAU);
+                let distance = rand::gen_range(0.0, wave.radius.max(0.0)) * tile_size;
+                let pos = fire.center + Vec2::from_angle(angle) * distance;
+                match Entity::spawn(ctx.db, &wave.entity, pos, ctx.registry) {
+                    Some(ent) => ctx.entities.push(ent),
+                    None => {
+                        eprintln!("trigger '{}' spawns unknown entity '{}'", fire.key, wave.entity);
+                        break;
+                    }
+                }
+            }
+        }
+        if let Some(boss) = action.boss.as_deref() {
+            match Entity::spawn(ctx.db, boss, fire.center, ctx.registry) {
+                Some(ent) => {
+                    self.boss = Some(ent.instance.uid);
+                    ctx.entities.push(ent);
+                }
+                None => eprintln!("trigger '{}' spawns unknown boss '{boss}'", fire.key),
+            }
+        }
+        if !action.interact.is_empty() {
+            let mut interact = InteractContext {
+                structure_id: &fire.key,
+                area: fire.area,
+                player: ctx.player,
+                map: ctx.map,
+            };
+            ctx.interact.execute(&action.interact, &mut interact);
+        }
+        if let Some(cutscene) = action.cutscene.as_deref()
+            && !ctx.cutscenes.play(cutscene)
+        {
+            eprintln!("trigger '{}' plays unknown cutscene '{cutscene}'", fire.key);
+        }
+    }
+
+    /// Draws the boss's health bar on the HUD.
+    pub fn draw(&self, entities: &[Entity], db: &EntityDatabase) {
+        let Some(boss) = self.boss.and_then(|uid| entities.iter().find(|ent| ent.instance.uid == uid)) else {
+            return;
+        };
+        let name = &db.entities[boss.instance.def].name;
+        let bar = Rect::new(screen_width() * 0.5 - 200.0, 40.0, 400.0, 10.0);
+        let filled = (boss.instance.hp / boss.instance.max_hp.max(1.0)).clamp(0.0, 1.0);
+        let width = measure_text(name, 20.0).width;
+        draw_text_styled(name, bar.center().x - width * 0.5, bar.y - 6.0, 20.0, WHITE, TextStyle::SHADOWED);
+        draw_rectangle(bar.x - 2.0, bar.y - 2.0, bar.w + 4.0, bar.h + 4.0, Color::new(0.0, 0.0, 0.0, 0.6));
+        draw_rectangle(bar.x, bar.y, bar.w * filled, bar.h, Color::new(0.85, 0.2, 0.2, 1.0));
+    }
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
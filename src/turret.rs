@@ -0,0 +1,133 @@
+use macroquad::prelude::*;
+use serde::Deserialize;
+use crate::entity::{
+    Entity, EntityContext, EntityDatabase, EntityDef, EntityInstance, EntityKind, MovementRegistry, Target,
+};
+use crate::farm::FarmState;
+use crate::item::{Inventory, ItemStack};
+use crate::map::StructureDef;
+
+/// Makes an entity a stationary gun: it shoots the nearest enemy in range instead of picking
+/// targets like other entities, and each shot takes a round of `ammo`.
+#[derive(Clone, Deserialize)]
+pub struct TurretDef {
+    /// World units it spots enemies within.
+    pub range: f32,
+    /// Item loaded as rounds; turrets with none run on the power network and never run dry.
+    #[serde(default)]
+    pub ammo: Option<String>,
+    #[serde(default = "default_capacity")]
+    pub capacity: u32,
+}
+
+fn default_capacity() -> u32 {
+    30
+}
+
+/// The nearest live enemy within range, found through the step's grid.
+pub fn acquire(entity: &EntityInstance, def: &EntityDef, turret: &TurretDef, ctx: &EntityContext) -> Option<Target> {
+    let center = def.world_hitbox(entity.pos).center();
+    ctx.grid
+        .near(center, turret.range)
+        .map(|index| &ctx.entities[index])
+        .filter(|target| target.alive && target.kind == EntityKind::Enemy)
+        .map(|target| (target.hitbox.center().distance(center), target))
+        .filter(|&(distance, _)| distance <= turret.range)
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, target)| Target::Entity(*target))
+}
+
+/// Whether the entity has power and a round to fire; always true for anything that isn't a
+/// turret.
+pub fn loaded(entity: &EntityInstance, def: &EntityDef) -> bool {
+    def.turret.as_ref().is_none_or(|turret| !entity.unpowered && (turret.ammo.is_none() || entity.ammo > 0))
+}
+
+pub fn spend_round(entity: &mut EntityInstance, def: &EntityDef) {
+    if def.turret.as_ref().is_some_and(|turret| turret.ammo.is_some()) {
+        entity.ammo = entity.ammo.saturating_sub(1);
+    }
+}
+
+/// Rounds from `inventory` that `ent` still has room for.
+pub fn loadable(ent: &Entity, db: &EntityDatabase, inventory: &Inventory) -> u32 {
+    let Some(turret) = db.entities[ent.instance.def].turret.as_ref() else {
+        return 0;
+    };
+    let Some(ammo) = turret.ammo.as_deref() else {
+        return 0;
+    };
+    inventory.count(ammo).min(turret.capacity.saturating_sub(ent.instance.ammo))
+}
+
+/// Moves as many rounds as fit from `inventory` into turret `uid`. Returns how many moved.
+pub fn load(entities: &mut [Entity], db: &EntityDatabase, uid: u64, inventory: &mut Inventory) -> u32 {
+    let Some(ent) = entities.iter_mut().find(|ent| ent.instance.uid == uid) else {
+        return 0;
+    };
+    let rounds = loadable(ent, db, inventory);
+    let Some(ammo) = db.entities[ent.instance.def].turret.as_ref().and_then(|turret| turret.ammo.clone()) else {
+        return 0;
+    };
+    if rounds == 0 || !inventory.remove_all(&[ItemStack { item: ammo, count: rounds }]) {
+        return 0;
+    }
+    ent.instance.ammo += rounds;
+    rounds
+}
+
+/// Keeps one entity standing on each built structure that mounts one, spawning missing ones
+/// and removing those whose structure was broken.
+pub fn sync(
+    entities: &mut Vec<Entity>,
+    db: &EntityDatabase,
+    registry: &MovementRegistry,
+    farm: &FarmState,
+    structures: &[StructureDef],
+    tile_size: f32,
+) {
+    let mounts: Vec<(Vec2, &str)> = farm
+        .built
+        .iter()
+        .filter_map(|built| {
+            let def = structures.iter().find(|def| def.id == built.id)?;
+            let size = vec2(def.structure.width() as f32, def.structure.height() as f32);
+            Some(((vec2(built.x as f32, built.y as f32) + size * 0.5) * tile_size, def.entity.as_deref()?))
+        })
+        .collect();
+    let mounted_at = |ent: &Entity, (center, id): (Vec2, &str)| {
+        db.entities[ent.instance.def].id == id && ent.hitbox(db).center().distance(center) < tile_size * 0.5
+    };
+    entities.retain(|ent| {
+        db.entities[ent.instance.def].turret.is_none() || mounts.iter().any(|&mount| mounted_at(ent, mount))
+    });
+    for &(center, id) in &mounts {
+        if entities.iter().any(|ent| mounted_at(ent, (center, id))) {
+            continue;
+        }
+        let Some(mut ent) = Entity::spawn(db, id, center, registry) else {
+            eprintln!("structure mounts unknown entity '{id}'");
+            continue;
+        };
+        let offset = ent.hitbox(db).center() - ent.instance.pos;
+        ent.instance.pos = center - offset;
+        entities.push(ent);
+    }
+}
+
+/// Draws a bar under each turret showing how full it is, in world space.
+pub fn draw_ammo(entities: &[Entity], db: &EntityDatabase, view: Rect) {
+    for ent in entities {
+        let Some(turret) = db.entities[ent.instance.def].turret.as_ref().filter(|turret| turret.ammo.is_some()) else {
+            continue;
+        };
+        let hitbox = ent.hitbox(db);
+        if !view.overlaps(&hitbox) {
+            continue;
+        }
+        let filled = ent.instance.ammo as f32 / turret.capacity.max(1) as f32;
+        let bar = Rect::new(hitbox.x, hitbox.bottom() + 2.0, hitbox.w, 2.0);
+        draw_rectangle(bar.x, bar.y, bar.w, bar.h, Color::new(0.0, 0.0, 0.0, 0.6));
+        draw_rectangle(bar.x, bar.y, bar.w * filled.min(1.0), bar.h, Color::new(0.95, 0.75, 0.2, 1.0));
+    }
+}
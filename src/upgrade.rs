@@ -0,0 +1,471 @@
+use macroquad::prelude::*;
+use crate::font::draw_text;
+use macroquad::file::load_string;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use crate::equipment::{EquipSlot, Equipment};
+use crate::helpers::{data_path, load_wasm_manifest_files};
+use crate::item::ItemDatabase;
+use crate::level::GrowthDef;
+use crate::locale::Locale;
+use crate::player::{Player, PlayerStats};
+
+const PANEL_WIDTH: f32 = 520.0;
+const ROW_HEIGHT: f32 = 30.0;
+/// Screen pixels each tier of the tree is indented by.
+const INDENT: f32 = 18.0;
+
+#[derive(Debug)]
+pub enum UpgradeLoadError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for UpgradeLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Yaml(err) => write!(f, "yaml error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for UpgradeLoadError {}
+
+impl From<std::io::Error> for UpgradeLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for UpgradeLoadError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::Yaml(err)
+    }
+}
+
+/// Which of the player's stats an upgrade raises.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpgradeEffect {
+    MaxHp,
+    MoveSpeed,
+    DashCooldown,
+    Damage,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct UpgradeDef {
+    pub id: String,
+    pub name: String,
+    pub effect: UpgradeEffect,
+    /// Added to the stat per rank; negative for dash cooldown.
+    pub amount: f32,
+    #[serde(default = "default_max_rank")]
+    pub max_rank: u32,
+    /// XP each rank costs, times the rank being bought.
+    #[serde(default)]
+    pub xp: f32,
+    /// Coins each rank costs, times the rank being bought.
+    #[serde(default)]
+    pub coins: u32,
+    /// Upgrade that needs a rank before this one can be bought.
+    #[serde(default)]
+    pub requires: Option<String>,
+}
+
+fn default_max_rank() -> u32 {
+    1
+}
+
+impl UpgradeDef {
+    fn cost(&self, rank: u32) -> (f32, u32) {
+        (self.xp * rank as f32, self.coins * rank)
+    }
+}
+
+pub struct UpgradeDatabase {
+    /// In tree order: each upgrade comes after the one it requires.
+    upgrades: Vec<(UpgradeDef, usize)>,
+}
+
+impl UpgradeDatabase {
+    pub fn empty() -> Self {
+        Self { upgrades: Vec::new() }
+    }
+
+    pub async fn load_from(dir: impl AsRef<Path>) -> Result<Self, UpgradeLoadError> {
+        let dir = dir.as_ref();
+        let mut defs = Vec::new();
+
+        if cfg!(target_arch = "wasm32") {
+            let dir = data_path(&dir.to_string_lossy());
+            let files = load_wasm_manifest_files(&dir, &[
+                "vitality.yaml",
+                "fleet_foot.yaml",
+                "quick_dash.yaml",
+                "sharp_tools.yaml",
+            ])
+            .await;
+            for file in files {
+                let path = format!("{}/{}", dir, file);
+                let raw_str = load_string(&path)
+                    .await
+                    .map_err(|err| UpgradeLoadError::Io(std::io::Error::other(err.to_string())))?;
+                defs.push(serde_yaml::from_str::<UpgradeDef>(&raw_str)?);
+            }
+        } else if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_yaml(&path) {
+                    continue;
+                }
+                defs.push(serde_yaml::from_str::<UpgradeDef>(&std::fs::read_to_string(&path)?)?);
+            }
+        }
+        defs.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut db = Self::empty();
+        db.add_branch(&mut defs, None, 0);
+        // Whatever's left requires an upgrade that doesn't exist; it's listed at the root.
+        for def in defs {
+            eprintln!("upgrade '{}' requires unknown upgrade", def.id);
+            db.upgrades.push((def, 0));
+        }
+        Ok(db)
+    }
+
+    fn add_branch(&mut self, defs: &mut Vec<UpgradeDef>, parent: Option<&str>, depth: usize) {
+        while let Some(index) = defs.iter().position(|def| def.requires.as_deref() == parent) {
+            let def = defs.remove(index);
+            let id = def.id.clone();
+            self.upgrades.push((def, depth));
+            self.add_branch(defs, Some(&id), depth + 1);
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&UpgradeDef> {
+        self.upgrades.iter().map(|(def, _)| def).find(|def| def.id == id)
+    }
+}
+
+/// Upgrades a save has bought.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Upgrades {
+    ranks: HashMap<String, u32>,
+    /// XP spent on upgrades. Levels still count all XP earned.
+    xp_spent: f32,
+}
+
+impl Upgrades {
+    pub fn rank(&self, id: &str) -> u32 {
+        self.ranks.get(id).copied().unwrap_or(0)
+    }
+
+    /// XP earned and not yet spent.
+    pub fn xp_available(&self, player: &Player) -> f32 {
+        (player.xp() - self.xp_spent).max(0.0)
+    }
+
+    /// `base` with every bought rank added on.
+    pub fn apply(&self, db: &UpgradeDatabase, base: PlayerStats) -> PlayerStats {
+        let mut stats = base;
+        for (id, &rank) in &self.ranks {
+            let Some(def) = db.get(id) else {
+                continue;
+            };
+            let amount = def.amount * rank.min(def.max_rank) as f32;
+            match def.effect {
+                UpgradeEffect::MaxHp => stats.max_hp += amount,
+                UpgradeEffect::MoveSpeed => stats.move_speed += amount,
+                UpgradeEffect::DashCooldown => stats.dash_cooldown = (stats.dash_cooldown + amount).max(0.1),
+                UpgradeEffect::Damage => stats.damage += amount,
+            }
+        }
+        stats
+    }
+
+    fn unlocked(&self, def: &UpgradeDef) -> bool {
+        def.requires.as_deref().is_none_or(|required| self.rank(required) > 0)
+    }
+
+    /// Buys the next rank of `def`, paying from the player's XP and coins.
+    fn buy(&mut self, def: &UpgradeDef, player: &mut Player) -> Result<u32, String> {
+        let rank = self.rank(&def.id) + 1;
+        if rank > def.max_rank {
+            return Err(format!("{} is maxed out", def.name));
+        }
+        if !self.unlocked(def) {
+            return Err(format!("{} needs {} first", def.name, def.requires.as_deref().unwrap_or_default()));
+        }
+        let (xp, coins) = self.cost_of(def);
+        if self.xp_available(player) < xp || player.coins() < coins {
+            return Err(format!("can't afford {}", def.name));
+        }
+        player.spend_coins(coins);
+        self.xp_spent += xp;
+        self.ranks.insert(def.id.clone(), rank);
+        Ok(rank)
+    }
+
+    fn cost_of(&self, def: &UpgradeDef) -> (f32, u32) {
+        def.cost(self.rank(&def.id) + 1)
+    }
+}
+
+/// What a save has bought and is wearing, on top of the player's base stats.
+#[derive(Clone, Default)]
+pub struct Loadout {
+    pub upgrades: Upgrades,
+    pub equipment: Equipment,
+}
+
+impl Loadout {
+    /// `base` with worn equipment and bought upgrades added on.
+    pub fn stats(&self, base: PlayerStats, db: &UpgradeDatabase, items: &ItemDatabase) -> PlayerStats {
+        self.upgrades.apply(db, self.equipment.apply(items, base))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum Tab {
+    #[default]
+    Upgrades,
+    Equipment,
+}
+
+impl Tab {
+    const ALL: [Self; 2] = [Self::Upgrades, Self::Equipment];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Upgrades => "Upgrades",
+            Self::Equipment => "Equipment",
+        }
+    }
+}
+
+/// Upgrade tree and equipment, in tabs of one panel toggled with U; Tab or clicking a tab's
+/// name switches between them. Clicking an upgrade buys its next rank, clicking a worn item
+/// takes it off and clicking a wearable one in the inventory puts it on.
+#[derive(Default)]
+pub struct UpgradeUi {
+    open: bool,
+    tab: Tab,
+}
+
+impl UpgradeUi {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Handles toggling and clicks, updating the player's stats on top of `base` when anything
+    /// changes. Returns a message when something's bought or can't be.
+    pub fn update(
+        &mut self,
+        db: &UpgradeDatabase,
+        items: &ItemDatabase,
+        loadout: &mut Loadout,
+        player: &mut Player,
+        base: PlayerStats,
+    ) -> Option<String> {
+        if is_key_pressed(KeyCode::U) {
+            self.open = !self.open;
+            return None;
+        }
+        if !self.open {
+            return None;
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            self.close();
+            return None;
+        }
+        if is_key_pressed(KeyCode::Tab) {
+            self.tab = if self.tab == Tab::Upgrades { Tab::Equipment } else { Tab::Upgrades };
+        }
+        if !is_mouse_button_pressed(MouseButton::Left) {
+            return None;
+        }
+        let mouse = vec2(mouse_position().0, mouse_position().1);
+        let panel = panel_rect(self.rows(db, items, player));
+        if !panel.contains(mouse) {
+            self.close();
+            return None;
+        }
+        if let Some(tab) = Tab::ALL.into_iter().find(|&tab| tab_rect(panel, tab).contains(mouse)) {
+            self.tab = tab;
+            return None;
+        }
+        let row = (0..self.rows(db, items, player)).find(|&i| row_rect(panel, i).contains(mouse))?;
+        let result = match self.tab {
+            Tab::Upgrades => {
+                let (def, _) = &db.upgrades[row];
+                loadout.upgrades.buy(def, player).map(|rank| format!("{} rank {rank}", def.name))
+            }
+            Tab::Equipment => match EquipSlot::ALL.get(row) {
+                Some(&slot) => loadout.equipment.unequip(items, player.inventory_mut(), slot).map(|_| String::new()),
+                None => {
+                    let index = wearable(items, player).nth(row - EquipSlot::ALL.len())?;
+                    loadout.equipment.equip(items, player.inventory_mut(), index).map(|_| String::new())
+                }
+            },
+        };
+        match result {
+            Ok(message) => {
+                player.set_stats(loadout.stats(base, db, items));
+                player.set_overlays(loadout.equipment.overlays(items));
+                (!message.is_empty()).then_some(message)
+            }
+            Err(message) => Some(message),
+        }
+    }
+
+    fn rows(&self, db: &UpgradeDatabase, items: &ItemDatabase, player: &Player) -> usize {
+        match self.tab {
+            Tab::Upgrades => db.upgrades.len(),
+            Tab::Equipment => EquipSlot::ALL.len() + wearable(items, player).count(),
+        }
+    }
+
+    pub fn draw(
+        &self,
+        db: &UpgradeDatabase,
+        items: &ItemDatabase,
+        loadout: &Loadout,
+        player: &Player,
+        growth: &GrowthDef,
+        locale: &Locale,
+    ) {
+        if !self.open {
+            return;
+        }
+        let panel = panel_rect(self.rows(db, items, player));
+        let mouse = vec2(mouse_position().0, mouse_position().1);
+        draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.05, 0.05, 0.08, 0.9));
+        draw_rectangle_lines(panel.x, panel.y, panel.w, panel.h, 2.0, GRAY);
+        for tab in Tab::ALL {
+            let rect = tab_rect(panel, tab);
+            let color = if tab == self.tab { WHITE } else { GRAY };
+            draw_text(tab.label(), rect.x, rect.bottom() - 6.0, 24.0, color);
+        }
+        if self.tab == Tab::Equipment {
+            draw_equipment(panel, items, &loadout.equipment, player, mouse);
+            draw_text("click to wear or take off, Tab to switch", panel.x + 12.0, panel.bottom() - 12.0, 16.0, GRAY);
+            return;
+        }
+        let upgrades = &loadout.upgrades;
+        let available = upgrades.xp_available(player);
+        let summary = format!(
+            "Lv {}  {} XP to spend",
+            growth.level_for(player.xp()),
+            locale.format_int(available as i64)
+        );
+        draw_text(&summary, panel.right() - 220.0, panel.y + 26.0, 18.0, LIGHTGRAY);
+
+        for (i, (def, depth)) in db.upgrades.iter().enumerate() {
+            let rect = row_rect(panel, i);
+            if rect.contains(mouse) {
+                draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(1.0, 1.0, 1.0, 0.1));
+            }
+            let rank = upgrades.rank(&def.id);
+            let mut label = format!("{}  {}/{}", def.name, rank, def.max_rank);
+            let color = if rank >= def.max_rank {
+                GOLD
+            } else if !upgrades.unlocked(def) {
+                DARKGRAY
+            } else {
+                let (xp, coins) = upgrades.cost_of(def);
+                let mut costs = Vec::new();
+                if xp > 0.0 {
+                    costs.push(format!("{} XP", locale.format_int(xp as i64)));
+                }
+                if coins > 0 {
+                    costs.push(locale.format_currency(coins as i64));
+                }
+                label.push_str(&format!("  [{}]", costs.join(", ")));
+                if available >= xp && player.coins() >= coins { WHITE } else { GRAY }
+            };
+            let x = rect.x + 8.0 + *depth as f32 * INDENT;
+            let marker = if *depth > 0 { "+ " } else { "" };
+            draw_text(&format!("{marker}{label}"), x, rect.y + rect.h * 0.65, 18.0, color);
+        }
+        draw_text("click to buy, Tab to switch, U to close", panel.x + 12.0, panel.bottom() - 12.0, 16.0, GRAY);
+    }
+}
+
+/// Inventory slots holding something that can be worn.
+fn wearable<'a>(items: &'a ItemDatabase, player: &'a Player) -> impl Iterator<Item = usize> + 'a {
+    player
+        .inventory()
+        .stacks()
+        .iter()
+        .enumerate()
+        .filter(|(_, stack)| items.get(&stack.item).is_some_and(|def| def.equip.is_some()))
+        .map(|(index, _)| index)
+}
+
+fn draw_equipment(panel: Rect, items: &ItemDatabase, equipment: &Equipment, player: &Player, mouse: Vec2) {
+    let describe = |item: &str| {
+        let stats = items.get(item).and_then(|def| def.equip.as_ref()).map(|equip| equip.stats.describe());
+        format!("{item}  {}", stats.unwrap_or_default())
+    };
+    for (i, slot) in EquipSlot::ALL.into_iter().enumerate() {
+        let rect = row_rect(panel, i);
+        if rect.contains(mouse) {
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(1.0, 1.0, 1.0, 0.1));
+        }
+        let (label, color) = match equipment.get(slot) {
+            Some(item) => (format!("{}: {}", slot.label(), describe(item)), WHITE),
+            None => (format!("{}: -", slot.label()), GRAY),
+        };
+        draw_text(&label, rect.x + 8.0, rect.y + rect.h * 0.65, 18.0, color);
+    }
+    for (i, index) in wearable(items, player).enumerate() {
+        let rect = row_rect(panel, EquipSlot::ALL.len() + i);
+        if rect.contains(mouse) {
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(1.0, 1.0, 1.0, 0.1));
+        }
+        let Some(stack) = player.inventory().get(index) else {
+            continue;
+        };
+        let slot = items.get(&stack.item).and_then(|def| def.equip.as_ref()).map(|equip| equip.slot.label());
+        let label = format!("+ {}  [{}]", describe(&stack.item), slot.unwrap_or_default());
+        draw_text(&label, rect.x + 8.0 + INDENT, rect.y + rect.h * 0.65, 18.0, LIGHTGRAY);
+    }
+}
+
+fn tab_rect(panel: Rect, tab: Tab) -> Rect {
+    let index = Tab::ALL.iter().position(|&other| other == tab).unwrap_or(0);
+    Rect::new(panel.x + 12.0 + index as f32 * 130.0, panel.y + 6.0, 120.0, 26.0)
+}
+
+fn panel_rect(rows: usize) -> Rect {
+    let height = 80.0 + rows.max(1) as f32 * ROW_HEIGHT;
+    Rect::new(
+        (screen_width() - PANEL_WIDTH) * 0.5,
+        (screen_height() - height) * 0.5,
+        PANEL_WIDTH,
+        height,
+    )
+}
+
+fn row_rect(panel: Rect, index: usize) -> Rect {
+    Rect::new(
+        panel.x + 12.0,
+        panel.y + 40.0 + index as f32 * ROW_HEIGHT,
+        panel.w - 24.0,
+        ROW_HEIGHT - 2.0,
+    )
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+        .unwrap_or(false)
+}
@@ -0,0 +1,615 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::crop::CropDatabase;
+use crate::entity::{Entity, EntityDatabase, SavedEntity};
+use crate::farm::FarmState;
+use crate::font::draw_text;
+use crate::item::{GroundItems, Inventory, ItemDatabase};
+use crate::map::{BrokenStructure, StructureDef, StructureHit, TileMap};
+use crate::path::{self, Tile};
+
+/// Entity def id of the worker robots the farm starts with. Any def marked `worker` takes jobs.
+pub const WORKER_ENTITY: &str = "cropbot";
+/// Seconds a worker spends on a tile once it arrives.
+const WORK_TIME: f32 = 0.75;
+/// Seconds an idle worker waits before looking for work again.
+const IDLE_RETRY: f32 = 1.0;
+const PICKUP_RADIUS: f32 = 10.0;
+const MENU_LINE_HEIGHT: f32 = 20.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Job {
+    Till,
+    Water,
+    /// Breaks bushes, trees and other breakable structures for their drops.
+    Harvest,
+    /// Carries loose ground items to a chest in the region, or onto its floor if it has none.
+    Haul,
+}
+
+impl Job {
+    pub const ALL: [Job; 4] = [Job::Till, Job::Water, Job::Harvest, Job::Haul];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Job::Till => "till",
+            Job::Water => "water",
+            Job::Harvest => "harvest",
+            Job::Haul => "haul",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Job::Till => Color::new(0.75, 0.5, 0.25, 1.0),
+            Job::Water => Color::new(0.3, 0.55, 1.0, 1.0),
+            Job::Harvest => Color::new(0.35, 0.9, 0.35, 1.0),
+            Job::Haul => Color::new(0.95, 0.85, 0.3, 1.0),
+        }
+    }
+}
+
+/// A rectangle of farm tiles designated for one job.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct JobRegion {
+    pub job: Job,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl JobRegion {
+    /// Region spanning two corner tiles, in either order.
+    pub fn from_corners(job: Job, a: Tile, b: Tile) -> Self {
+        Self {
+            job,
+            x: a.0.min(b.0),
+            y: a.1.min(b.1),
+            width: a.0.abs_diff(b.0) + 1,
+            height: a.1.abs_diff(b.1) + 1,
+        }
+    }
+
+    pub fn contains(&self, tile: Tile) -> bool {
+        (self.x..self.x + self.width).contains(&tile.0) && (self.y..self.y + self.height).contains(&tile.1)
+    }
+
+    pub fn world_rect(&self, tile_size: f32) -> Rect {
+        Rect::new(
+            self.x as f32 * tile_size,
+            self.y as f32 * tile_size,
+            self.width as f32 * tile_size,
+            self.height as f32 * tile_size,
+        )
+    }
+
+    fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        (self.y..self.y + self.height).flat_map(move |y| (self.x..self.x + self.width).map(move |x| (x, y)))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Task {
+    Till(Tile),
+    Water(Tile),
+    /// Picking the ripe crop on this tile.
+    Reap(Tile),
+    /// Chopping the breakable structure with this footprint.
+    Harvest(Rect),
+    /// Collecting ground items lying here.
+    Pickup(Vec2),
+    /// Dropping carried items on this stockpile tile.
+    Deliver(Tile),
+    /// Putting carried items into the chest on this tile.
+    Store(Tile),
+}
+
+struct Worker {
+    uid: u64,
+    region: Option<usize>,
+    task: Option<Task>,
+    work_timer: f32,
+    idle_timer: f32,
+    carrying: Inventory,
+    /// Targets a path couldn't reach; skipped until the worker runs out of other work.
+    unreachable: Vec<Tile>,
+}
+
+impl Worker {
+    fn new(uid: u64) -> Self {
+        Self {
+            uid,
+            region: None,
+            task: None,
+            work_timer: 0.0,
+            idle_timer: 0.0,
+            carrying: Inventory::default(),
+            unreachable: Vec::new(),
+        }
+    }
+
+    fn reset(&mut self, ent: &mut Entity) {
+        self.task = None;
+        self.work_timer = 0.0;
+        self.unreachable.clear();
+        ent.instance.waypoints.clear();
+    }
+}
+
+/// Farm job regions and the workers staffing them. Unassigned workers join whichever
+/// region has the fewest workers.
+#[derive(Default)]
+pub struct WorkerBoard {
+    regions: Vec<JobRegion>,
+    workers: Vec<Worker>,
+}
+
+/// Everything on the farm a worker can act on this frame.
+pub struct WorkSite<'a> {
+    pub map: &'a mut TileMap,
+    pub farm: &'a mut FarmState,
+    pub ground_items: &'a mut GroundItems,
+    pub structures: &'a [StructureDef],
+    pub items: &'a ItemDatabase,
+    pub crops: &'a CropDatabase,
+    pub ground_tile: u8,
+}
+
+impl WorkerBoard {
+    pub fn regions(&self) -> &[JobRegion] {
+        &self.regions
+    }
+
+    /// Replaces every region with saved ones, dropping all current assignments.
+    pub fn set_regions(&mut self, regions: Vec<JobRegion>) {
+        self.regions = regions;
+        self.workers.clear();
+    }
+
+    /// Records a worker's assignment and load on its saved entity.
+    pub fn save_worker(&self, uid: u64, saved: &mut SavedEntity) {
+        if let Some(worker) = self.workers.iter().find(|worker| worker.uid == uid) {
+            saved.job = worker.region;
+            saved.inventory = worker.carrying.clone();
+        }
+    }
+
+    /// Resumes a respawned worker with its saved assignment and load.
+    pub fn restore_worker(&mut self, uid: u64, saved: &SavedEntity) {
+        let mut worker = Worker::new(uid);
+        worker.region = saved.job.filter(|&region| region < self.regions.len());
+        worker.carrying = saved.inventory.clone();
+        self.workers.retain(|other| other.uid != uid);
+        self.workers.push(worker);
+    }
+
+    pub fn add_region(&mut self, region: JobRegion) {
+        self.regions.push(region);
+    }
+
+    /// Removes the most recently added region under `tile`, unassigning its workers.
+    pub fn remove_region_at(&mut self, tile: Tile) -> bool {
+        let Some(index) = self.regions.iter().rposition(|region| region.contains(tile)) else {
+            return false;
+        };
+        self.regions.remove(index);
+        for worker in self.workers.iter_mut() {
+            worker.region = match worker.region {
+                Some(region) if region == index => {
+                    worker.task = None;
+                    None
+                }
+                Some(region) if region > index => Some(region - 1),
+                other => other,
+            };
+        }
+        true
+    }
+
+    /// Moves a worker to the next region, wrapping around. Returns its new job.
+    pub fn cycle_assignment(&mut self, uid: u64) -> Option<Job> {
+        let count = self.regions.len();
+        let worker = self.workers.iter_mut().find(|worker| worker.uid == uid)?;
+        if count == 0 {
+            return None;
+        }
+        let next = worker.region.map_or(0, |region| (region + 1) % count);
+        worker.region = Some(next);
+        worker.task = None;
+        worker.unreachable.clear();
+        Some(self.regions[next].job)
+    }
+
+    fn staff(&self, region: usize) -> usize {
+        self.workers.iter().filter(|worker| worker.region == Some(region)).count()
+    }
+
+    /// Runs every worker's job. Returns structures harvested to pieces so the caller can
+    /// spawn their drops.
+    pub fn update(
+        &mut self,
+        dt: f32,
+        entities: &mut [Entity],
+        db: &EntityDatabase,
+        site: &mut WorkSite,
+    ) -> Vec<BrokenStructure> {
+        self.workers.retain(|worker| {
+            entities
+                .iter()
+                .any(|ent| ent.instance.uid == worker.uid)
+        });
+        for ent in entities.iter() {
+            if db.entities[ent.instance.def].worker
+                && !self.workers.iter().any(|worker| worker.uid == ent.instance.uid)
+            {
+                self.workers.push(Worker::new(ent.instance.uid));
+            }
+        }
+        for i in 0..self.workers.len() {
+            if self.workers[i].region.is_none()
+                && let Some(region) = (0..self.regions.len()).min_by_key(|&region| self.staff(region))
+            {
+                self.workers[i].region = Some(region);
+            }
+        }
+
+        let mut broken = Vec::new();
+        let mut workers = std::mem::take(&mut self.workers);
+        for i in 0..workers.len() {
+            let Some(ent) = entities.iter_mut().find(|ent| ent.instance.uid == workers[i].uid) else {
+                continue;
+            };
+            let Some(region) = workers[i].region.map(|region| self.regions[region]) else {
+                workers[i].reset(ent);
+                continue;
+            };
+            let claimed: Vec<Task> = workers
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .filter_map(|(_, other)| other.task)
+                .collect();
+            let worker = &mut workers[i];
+            match worker.task {
+                None => {
+                    worker.idle_timer -= dt;
+                    if worker.idle_timer <= 0.0 {
+                        worker.idle_timer = IDLE_RETRY;
+                        self.plan(worker, ent, db, region, &claimed, site);
+                    }
+                }
+                Some(_) if !ent.instance.waypoints.is_empty() => {}
+                Some(task) => {
+                    if work(worker, ent, task, dt, site, &mut broken) {
+                        worker.task = None;
+                        worker.work_timer = 0.0;
+                        worker.idle_timer = 0.0;
+                    }
+                }
+            }
+        }
+        self.workers = workers;
+        broken
+    }
+
+    /// Picks the nearest unclaimed target for the worker's job and paths to it.
+    fn plan(
+        &self,
+        worker: &mut Worker,
+        ent: &mut Entity,
+        db: &EntityDatabase,
+        region: JobRegion,
+        claimed: &[Task],
+        site: &WorkSite,
+    ) {
+        let tile_size = site.map.tile_size();
+        let center_offset = ent.hitbox(db).center() - ent.instance.pos;
+        let here = world_tile(ent.hitbox(db).center(), tile_size);
+        let soil = &site.farm.soil;
+
+        let found = match region.job {
+            Job::Till => nearest(
+                region
+                    .tiles()
+                    .filter(|&(x, y)| soil.can_till(site.map, x, y, site.ground_tile))
+                    .map(|tile| (tile, Task::Till(tile))),
+                here,
+                &worker.unreachable,
+                claimed,
+            ),
+            Job::Water => nearest(
+                region
+                    .tiles()
+                    .filter(|&(x, y)| soil.get(x, y).tilled() && !soil.get(x, y).watered())
+                    .map(|tile| (tile, Task::Water(tile))),
+                here,
+                &worker.unreachable,
+                claimed,
+            ),
+            Job::Harvest => {
+                let area = region.world_rect(tile_size);
+                let crops = site
+                    .farm
+                    .crops
+                    .ripe(site.crops)
+                    .filter(|&tile| region.contains(tile))
+                    .map(|tile| (tile, Task::Reap(tile)));
+                nearest(crops, here, &worker.unreachable, claimed).or_else(|| {
+                    nearest(
+                        site.map
+                            .breakable_structure_rects()
+                            .filter(|rect| rect.overlaps(&area))
+                            .map(|rect| (world_tile(rect.center(), tile_size), Task::Harvest(rect))),
+                        here,
+                        &worker.unreachable,
+                        claimed,
+                    )
+                })
+            }
+            Job::Haul if !worker.carrying.stacks().is_empty() => {
+                let chests = site
+                    .farm
+                    .chests
+                    .chests()
+                    .iter()
+                    .filter(|chest| {
+                        region.contains((chest.x, chest.y))
+                            && chest.inventory.stacks().len() < chest.inventory.slots()
+                    })
+                    .map(|chest| ((chest.x, chest.y), Task::Store((chest.x, chest.y))));
+                nearest(chests, here, &worker.unreachable, &[]).or_else(|| {
+                    nearest(
+                        region
+                            .tiles()
+                            .filter(|&(x, y)| !site.map.is_solid(x, y))
+                            .map(|tile| (tile, Task::Deliver(tile))),
+                        here,
+                        &[],
+                        &[],
+                    )
+                })
+            }
+            Job::Haul => nearest(
+                site.ground_items
+                    .items()
+                    .iter()
+                    .map(|item| vec2(item.x, item.y))
+                    .map(|pos| (world_tile(pos, tile_size), Task::Pickup(pos)))
+                    .filter(|&(tile, _)| {
+                        !self
+                            .regions
+                            .iter()
+                            .any(|region| region.job == Job::Haul && region.contains(tile))
+                    }),
+                here,
+                &worker.unreachable,
+                claimed,
+            ),
+        };
+        let Some((target, task)) = found else {
+            worker.unreachable.clear();
+            return;
+        };
+
+        let route = match task {
+            Task::Harvest(_) | Task::Store(_) => path::find_path_near(site.map, here, target),
+            _ => path::find_path(site.map, here, target),
+        };
+        let Some(route) = route else {
+            worker.unreachable.push(target);
+            return;
+        };
+        ent.instance.waypoints = route
+            .into_iter()
+            .skip(1)
+            .map(|(x, y)| vec2((x as f32 + 0.5) * tile_size, (y as f32 + 0.5) * tile_size) - center_offset)
+            .collect();
+        worker.task = Some(task);
+        worker.work_timer = 0.0;
+    }
+
+    /// Outlines every region, labelled with its job and staff count.
+    pub fn draw_regions(&self, tile_size: f32) {
+        for (i, region) in self.regions.iter().enumerate() {
+            let rect = region.world_rect(tile_size);
+            let color = region.job.color();
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(color.r, color.g, color.b, 0.15));
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, color);
+            draw_text(
+                &format!("{} ({})", region.job.label(), self.staff(i)),
+                rect.x + 2.0,
+                rect.y + 8.0,
+                10.0,
+                color,
+            );
+        }
+    }
+}
+
+/// Carries out an arrived-at task. Returns true once it's finished or no longer possible.
+fn work(
+    worker: &mut Worker,
+    ent: &mut Entity,
+    task: Task,
+    dt: f32,
+    site: &mut WorkSite,
+    broken: &mut Vec<BrokenStructure>,
+) -> bool {
+    match task {
+        Task::Till((x, y)) | Task::Water((x, y)) => {
+            worker.work_timer += dt;
+            if worker.work_timer < WORK_TIME {
+                return false;
+            }
+            if matches!(task, Task::Till(_)) {
+                site.farm.soil.till(site.map, x, y, site.ground_tile);
+            } else {
+                site.farm.soil.water(x, y);
+            }
+            true
+        }
+        Task::Reap((x, y)) => {
+            worker.work_timer += dt;
+            if worker.work_timer < WORK_TIME {
+                return false;
+            }
+            // Produce is left on the ground for haulers.
+            if let Some(produce) = site.farm.crops.harvest(x, y, site.crops) {
+                let tile_size = site.map.tile_size();
+                let pos = vec2((x as f32 + 0.5) * tile_size, (y as f32 + 0.5) * tile_size);
+                site.ground_items.spawn(site.items, &produce.item, produce.count, pos);
+            }
+            true
+        }
+        Task::Harvest(rect) => {
+            if !site.map.overlaps_breakable_structure(rect, StructureHit::Tool) {
+                return true;
+            }
+            let damage = ent.instance.stats.get("harvest_damage", 1.0) * dt;
+            broken.extend(site.map.damage_structures(rect, damage, StructureHit::Tool, site.structures));
+            false
+        }
+        Task::Pickup(pos) => {
+            site.ground_items
+                .collect_near(site.items, &mut worker.carrying, pos, PICKUP_RADIUS);
+            true
+        }
+        Task::Deliver((x, y)) => {
+            let tile_size = site.map.tile_size();
+            let pos = vec2((x as f32 + 0.5) * tile_size, (y as f32 + 0.5) * tile_size);
+            for stack in std::mem::take(&mut worker.carrying).stacks() {
+                site.ground_items.spawn(site.items, &stack.item, stack.count, pos);
+            }
+            true
+        }
+        Task::Store((x, y)) => {
+            // Whatever doesn't fit is dropped where the worker stands.
+            let carried = std::mem::take(&mut worker.carrying);
+            for stack in site.farm.chests.deposit(x, y, carried.stacks(), site.items) {
+                site.ground_items.spawn(site.items, &stack.item, stack.count, ent.instance.pos);
+            }
+            true
+        }
+    }
+}
+
+/// The candidate closest to `here` that isn't unreachable or claimed by another worker.
+fn nearest(
+    candidates: impl Iterator<Item = (Tile, Task)>,
+    here: Tile,
+    unreachable: &[Tile],
+    claimed: &[Task],
+) -> Option<(Tile, Task)> {
+    candidates
+        .filter(|(tile, task)| !unreachable.contains(tile) && !claimed.contains(task))
+        .min_by_key(|(tile, _)| tile.0.abs_diff(here.0).pow(2) + tile.1.abs_diff(here.1).pow(2))
+}
+
+fn world_tile(pos: Vec2, tile_size: f32) -> Tile {
+    (
+        (pos.x.max(0.0) / tile_size) as usize,
+        (pos.y.max(0.0) / tile_size) as usize,
+    )
+}
+
+/// Job designation mode. J toggles it, Tab cycles the job, dragging marks a region,
+/// right click removes one, and clicking a cropbot moves it to the next region.
+#[derive(Default)]
+pub struct JobMode {
+    active: bool,
+    selected: usize,
+    drag_start: Option<Tile>,
+}
+
+impl JobMode {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn close(&mut self) {
+        self.active = false;
+        self.drag_start = None;
+    }
+
+    /// Handles input while the mode is open. Returns a message describing a reassignment.
+    pub fn update(
+        &mut self,
+        board: &mut WorkerBoard,
+        entities: &[Entity],
+        db: &EntityDatabase,
+        map: &TileMap,
+        mouse_world: Vec2,
+    ) -> Option<String> {
+        if is_key_pressed(KeyCode::J) {
+            self.active = !self.active;
+            self.drag_start = None;
+        }
+        if !self.active {
+            return None;
+        }
+        if is_key_pressed(KeyCode::Tab) {
+            self.selected = (self.selected + 1) % Job::ALL.len();
+        }
+        let tile = map
+            .world_bounds()
+            .contains(mouse_world)
+            .then(|| world_tile(mouse_world, map.tile_size()));
+        if is_mouse_button_pressed(MouseButton::Right)
+            && let Some(tile) = tile
+        {
+            board.remove_region_at(tile);
+        }
+        if is_mouse_button_pressed(MouseButton::Left) {
+            let clicked_bot = entities.iter().find(|ent| {
+                db.entities[ent.instance.def].worker && ent.hitbox(db).contains(mouse_world)
+            });
+            if let Some(ent) = clicked_bot {
+                let job = board.cycle_assignment(ent.instance.uid)?;
+                let name = &db.entities[ent.instance.def].name;
+                return Some(format!("{name} {} -> {}", ent.instance.uid, job.label()));
+            }
+            self.drag_start = tile;
+        }
+        if is_mouse_button_released(MouseButton::Left)
+            && let Some(start) = self.drag_start.take()
+            && let Some(end) = tile
+        {
+            board.add_region(JobRegion::from_corners(Job::ALL[self.selected], start, end));
+        }
+        None
+    }
+
+    /// Draws the region being dragged out.
+    pub fn draw_drag(&self, map: &TileMap, mouse_world: Vec2) {
+        let Some(start) = self.drag_start else {
+            return;
+        };
+        let end = world_tile(mouse_world, map.tile_size());
+        let rect = JobRegion::from_corners(Job::ALL[self.selected], start, end).world_rect(map.tile_size());
+        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.5, Job::ALL[self.selected].color());
+    }
+
+    pub fn draw_menu(&self, board: &WorkerBoard) {
+        if !self.active {
+            return;
+        }
+        let x = 20.0;
+        let y = screen_height() * 0.5 - Job::ALL.len() as f32 * MENU_LINE_HEIGHT * 0.5;
+        let height = (Job::ALL.len() as f32 + 1.5) * MENU_LINE_HEIGHT;
+        draw_rectangle(x - 8.0, y - MENU_LINE_HEIGHT, 300.0, height, Color::new(0.0, 0.0, 0.0, 0.7));
+        draw_text("jobs  (drag: mark, Tab: next, J: close)", x, y, 18.0, WHITE);
+        for (i, job) in Job::ALL.iter().enumerate() {
+            let regions = board.regions().iter().filter(|region| region.job == *job).count();
+            let marker = if i == self.selected { ">" } else { " " };
+            draw_text(
+                &format!("{marker} {}  [{regions} regions]", job.label()),
+                x,
+                y + (i as f32 + 1.0) * MENU_LINE_HEIGHT,
+                18.0,
+                job.color(),
+            );
+        }
+    }
+}
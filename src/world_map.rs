@@ -0,0 +1,213 @@
+use macroquad::prelude::*;
+use crate::explore::Exploration;
+use crate::font::{TextStyle, draw_text_styled, measure_text};
+use crate::map::{LayerKind, StructureDef, TileMap, TileSet};
+
+/// Map tiles a side that make up one pixel of the world map.
+const TILES_PER_PIXEL: usize = 4;
+const FOG_COLOR: Color = Color::new(0.06, 0.06, 0.09, 1.0);
+const SOLID_SHADE: f32 = 0.6;
+const MARKER_COLOR: Color = Color::new(0.95, 0.85, 0.5, 1.0);
+const WAYPOINT_COLOR: Color = Color::new(0.4, 0.85, 1.0, 1.0);
+const PLAYER_COLOR: Color = Color::new(1.0, 0.3, 0.3, 1.0);
+
+/// A structure worth marking on the map.
+struct Marker {
+    center: Vec2,
+    waypoint: bool,
+}
+
+/// The whole current map drawn small, with unexplored parts of the expedition left dark. M
+/// toggles it; opened from a waypoint, clicking another discovered one travels there.
+#[derive(Default)]
+pub struct WorldMap {
+    open: bool,
+    /// The waypoint it was opened from, while fast travel is on offer.
+    travel_from: Option<Vec2>,
+    /// Size in map pixels.
+    width: usize,
+    height: usize,
+    texture: Option<Texture2D>,
+    stale: bool,
+}
+
+impl WorldMap {
+    /// Starts over for a freshly loaded scene.
+    pub fn reset(&mut self, map: &TileMap) {
+        self.width = map.width().div_ceil(TILES_PER_PIXEL);
+        self.height = map.height().div_ceil(TILES_PER_PIXEL);
+        self.texture = None;
+        self.stale = true;
+        self.close();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.travel_from = None;
+    }
+
+    /// Opens the map at waypoint `from` with fast travel on offer.
+    pub fn open_travel(&mut self, from: Vec2) {
+        self.open = true;
+        self.travel_from = Some(from);
+    }
+
+    pub fn update_input(&mut self) {
+        if is_key_pressed(KeyCode::M) {
+            self.open = !self.open;
+            self.travel_from = None;
+        } else if self.open && (is_key_pressed(KeyCode::E) || is_key_pressed(KeyCode::Enter)) {
+            self.close();
+        }
+    }
+
+    /// Redraws the map picture the next time it's shown, once more has been explored.
+    pub fn refresh(&mut self) {
+        self.stale = true;
+    }
+
+    /// Redraws the map picture if it's out of date, and handles picking a waypoint to travel
+    /// to. Returns where to put the player if one was picked. Without `explored` the whole map
+    /// counts as seen.
+    pub fn update(
+        &mut self,
+        map: &TileMap,
+        tileset: &TileSet,
+        structures: &[StructureDef],
+        explored: Option<&Exploration>,
+    ) -> Option<Vec2> {
+        if !self.open {
+            return None;
+        }
+        if self.stale {
+            self.stale = false;
+            self.rebuild(map, tileset, explored);
+        }
+        let from = self.travel_from?;
+        if !is_mouse_button_pressed(MouseButton::Left) {
+            return None;
+        }
+        let tile_size = map.tile_size();
+        let area = self.area();
+        let mouse = Vec2::from(mouse_position());
+        let picked = self
+            .markers(map, structures, explored)
+            .into_iter()
+            .filter(|marker| marker.waypoint && marker.center.distance(from) > tile_size)
+            .find(|marker| self.to_screen(marker.center, tile_size, area).distance(mouse) <= 8.0)?;
+        self.close();
+        Some(picked.center + vec2(0.0, tile_size))
+    }
+
+    fn rebuild(&mut self, map: &TileMap, tileset: &TileSet, explored: Option<&Exploration>) {
+        let mut image = Image::gen_image_color(self.width as u16, self.height as u16, FOG_COLOR);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let tile = (x * TILES_PER_PIXEL + TILES_PER_PIXEL / 2, y * TILES_PER_PIXEL + TILES_PER_PIXEL / 2);
+                let (tx, ty) = (tile.0.min(map.width() - 1), tile.1.min(map.height() - 1));
+                if !seen(explored, tx, ty) {
+                    continue;
+                }
+                let color = [LayerKind::Overlay, LayerKind::Foreground, LayerKind::Background]
+                    .into_iter()
+                    .find_map(|layer| tileset.color(map.tile_at(layer, tx, ty)))
+                    .unwrap_or(FOG_COLOR);
+                let shade = if map.is_solid(tx, ty) { SOLID_SHADE } else { 1.0 };
+                let color = Color::new(color.r * shade, color.g * shade, color.b * shade, 1.0);
+                image.set_pixel(x as u32, y as u32, color);
+            }
+        }
+        match self.texture.as_ref() {
+            Some(texture) if texture.width() as usize == self.width && texture.height() as usize == self.height => {
+                texture.update(&image);
+            }
+            _ => {
+                let texture = Texture2D::from_image(&image);
+                texture.set_filter(FilterMode::Nearest);
+                self.texture = Some(texture);
+            }
+        }
+    }
+
+    /// Structures someone can use that the player has explored.
+    fn markers(&self, map: &TileMap, structures: &[StructureDef], explored: Option<&Exploration>) -> Vec<Marker> {
+        let tile_size = map.tile_size();
+        let mut markers: Vec<Marker> = Vec::new();
+        for interactor in map.structure_interactors() {
+            let center = interactor.group_rect.center();
+            let (tx, ty) = ((center.x / tile_size) as usize, (center.y / tile_size) as usize);
+            if !seen(explored, tx, ty) || markers.iter().any(|marker| marker.center == center) {
+                continue;
+            }
+            let waypoint = structures.iter().any(|def| def.id == interactor.structure_id && def.waypoint);
+            markers.push(Marker { center, waypoint });
+        }
+        markers
+    }
+
+    /// Where the map sits on screen, as large as fits.
+    fn area(&self) -> Rect {
+        let (w, h) = (self.width.max(1) as f32, self.height.max(1) as f32);
+        let scale = ((screen_width() - 80.0) / w).min((screen_height() - 120.0) / h).max(0.1);
+        let size = vec2(w, h) * scale;
+        Rect::new((screen_width() - size.x) * 0.5, (screen_height() - size.y) * 0.5, size.x, size.y)
+    }
+
+    fn to_screen(&self, world: Vec2, tile_size: f32, area: Rect) -> Vec2 {
+        let covered = vec2(self.width as f32, self.height as f32) * TILES_PER_PIXEL as f32 * tile_size;
+        area.point() + world / covered * area.size()
+    }
+
+    /// Draws the map over the screen, in screen space.
+    pub fn draw(&self, map: &TileMap, structures: &[StructureDef], explored: Option<&Exploration>, player: Vec2) {
+        if !self.open {
+            return;
+        }
+        let Some(texture) = self.texture.as_ref() else {
+            return;
+        };
+        let (area, tile_size) = (self.area(), map.tile_size());
+        draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.6));
+        draw_texture_ex(
+            texture,
+            area.x,
+            area.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(area.size()),
+                ..Default::default()
+            },
+        );
+        draw_rectangle_lines(area.x, area.y, area.w, area.h, 2.0, Color::new(1.0, 1.0, 1.0, 0.3));
+
+        let mouse = Vec2::from(mouse_position());
+        for marker in self.markers(map, structures, explored) {
+            let pos = self.to_screen(marker.center, tile_size, area);
+            if !marker.waypoint {
+                draw_rectangle(pos.x - 2.0, pos.y - 2.0, 4.0, 4.0, MARKER_COLOR);
+                continue;
+            }
+            let here = self.travel_from.is_some_and(|from| from.distance(marker.center) <= tile_size);
+            let hovered = self.travel_from.is_some() && !here && pos.distance(mouse) <= 8.0;
+            let size = if hovered { 7.0 } else { 5.0 };
+            draw_poly(pos.x, pos.y, 4, size, 0.0, if here { WHITE } else { WAYPOINT_COLOR });
+        }
+        let pos = self.to_screen(player, tile_size, area);
+        draw_circle(pos.x, pos.y, 3.0, PLAYER_COLOR);
+
+        let title = if self.travel_from.is_some() { "Pick a waypoint to travel to" } else { "Map" };
+        let width = measure_text(title, 22.0).width;
+        draw_text_styled(title, (screen_width() - width) * 0.5, area.y - 12.0, 22.0, WHITE, TextStyle::SHADOWED);
+        let hint = "M or Enter to close";
+        let width = measure_text(hint, 14.0).width;
+        draw_text_styled(hint, area.right() - width, area.bottom() + 18.0, 14.0, LIGHTGRAY, TextStyle::SHADOWED);
+    }
+}
+
+fn seen(explored: Option<&Exploration>, x: usize, y: usize) -> bool {
+    explored.is_none_or(|explored| explored.is_explored(x, y))
+}
@@ -1437,6 +1437,44 @@ var importObject = {
                 return 1;
             } catch (e) {
                 console.warn("mq_storage_set_item failed", e);
+                // Browsers disagree on how a full quota is reported.
+                if (e && (e.name == "QuotaExceededError" || e.name == "NS_ERROR_DOM_QUOTA_REACHED" || e.code == 22 || e.code == 1014)) {
+                    return -1;
+                }
+                return 0;
+            }
+        },
+        mq_download: function (name_ptr, name_len, data_ptr, data_len) {
+            try {
+                var name = UTF8ToString(name_ptr, name_len);
+                var bytes = new Uint8Array(wasm_memory.buffer, data_ptr, data_len).slice();
+                var url = URL.createObjectURL(new Blob([bytes], { type: "application/octet-stream" }));
+                var link = document.createElement("a");
+                link.href = url;
+                link.download = name;
+                document.body.appendChild(link);
+                link.click();
+                document.body.removeChild(link);
+                setTimeout(function () {
+                    URL.revokeObjectURL(url);
+                }, 0);
+                return 1;
+            } catch (e) {
+                console.warn("mq_download failed", e);
+                return 0;
+            }
+        },
+        mq_storage_remove_item: function (key_ptr, key_len) {
+            try {
+                if (window.localStorage == null) {
+                    return 0;
+                }
+
+                var key = UTF8ToString(key_ptr, key_len);
+                window.localStorage.removeItem(key);
+                return 1;
+            } catch (e) {
+                console.warn("mq_storage_remove_item failed", e);
                 return 0;
             }
         },